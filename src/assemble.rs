@@ -1,21 +1,72 @@
 use regex::Regex;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 use crate::search::*;
 use crate::types::*;
 use crate::util::*;
 
-pub(crate) fn search_relevant_sections(
-    query: &str,
-    index: &ForwardIndex,
-    max_sections: usize,
-) -> Vec<SectionMatch> {
-    let query_terms = parse_query_terms(query, true);
+/// Document contents read during one assemble run, so a doc reached by both
+/// primary selection and cross-reference expansion is read from disk once.
+#[derive(Default)]
+pub(crate) struct DocCache {
+    docs: RefCell<HashMap<String, Option<Rc<str>>>>,
+}
+
+impl DocCache {
+    /// Contents of an indexed doc, reading it on first use. Unreadable docs
+    /// are remembered as missing.
+    pub(crate) fn read(
+        &self,
+        index: &ForwardIndex,
+        doc_path: &str,
+        entry: &FileEntry,
+    ) -> Option<Rc<str>> {
+        if let Some(cached) = self.docs.borrow().get(doc_path) {
+            return cached.clone();
+        }
+        let content = read_indexed_doc(index, doc_path, entry).ok().map(Rc::from);
+        self.docs
+            .borrow_mut()
+            .insert(doc_path.to_string(), content.clone());
+        content
+    }
+}
+
+/// Index, document contents, and ranking settings for primary section
+/// selection.
+pub(crate) struct SectionSearch<'a> {
+    pub index: &'a ForwardIndex,
+    pub docs: &'a DocCache,
+    pub max_sections: usize,
+    /// Aliases scored as the query term they expand
+    pub synonyms: &'a Synonyms,
+    /// Documents kept in (or out of) the search
+    pub scope: &'a PathScope,
+    /// Canonicality share of the combined section ranking
+    pub canonicality_weight: f64,
+    /// Weight of heading term occurrences relative to body occurrences
+    pub heading_boost: f64,
+}
+
+pub(crate) fn search_relevant_sections(query: &str, search: &SectionSearch) -> Vec<SectionMatch> {
+    let &SectionSearch {
+        index,
+        docs,
+        max_sections,
+        synonyms,
+        scope,
+        canonicality_weight,
+        heading_boost,
+    } = search;
+    let query_terms = parse_query_terms(query, Some(&index.stop_words));
     if query_terms.is_empty() {
         return Vec::new();
     }
+    let term_groups = expand_query_terms(&query_terms, synonyms, index.stemmer);
 
     let mut all_sections: Vec<SectionMatch> = Vec::new();
 
@@ -23,14 +74,25 @@ pub(crate) fn search_relevant_sections(
     let mut doc_scores: Vec<(&String, &FileEntry, f64)> = index
         .files
         .iter()
+        .filter(|(path, _)| !scope.is_excluded(path))
         .map(|(path, entry)| {
-            let score = bm25_score(&query_terms, entry, index.avg_doc_length, &index.idf_map);
+            let score = bm25_score_groups(
+                &term_groups,
+                entry,
+                index.avg_doc_length,
+                &index.idf_map,
+                heading_boost,
+            );
             (path, entry, score)
         })
         .filter(|(_, _, score)| *score > 0.01)
         .collect();
 
-    doc_scores.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+    doc_scores.sort_by(|a, b| {
+        b.2.partial_cmp(&a.2)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(b.0))
+    });
 
     // Take top 20 documents
     for (doc_path, entry, doc_score) in doc_scores.iter().take(20) {
@@ -38,7 +100,7 @@ pub(crate) fn search_relevant_sections(
 
         // Split document into sections based on section_fingerprints
         if !entry.section_fingerprints.is_empty() {
-            if let Ok(content) = read_indexed_doc(index, doc_path, entry) {
+            if let Some(content) = docs.read(index, doc_path, entry) {
                 let lines: Vec<&str> = content.lines().collect();
 
                 // Use indexed sections
@@ -54,105 +116,328 @@ pub(crate) fn search_relevant_sections(
                             heading: section.heading.clone(),
                             line_start: section.line_start,
                             line_end: section.line_end,
-                            bm25_score: *doc_score, // Use doc-level score for now
+                            bm25_score: *doc_score, // Rescored per section below
                             content: section_content,
                             canonicality,
+                            via: None,
                         });
                     }
                 }
             }
         } else {
             // Fallback: treat whole doc as one section
-            if let Ok(content) = read_indexed_doc(index, doc_path, entry) {
+            if let Some(content) = docs.read(index, doc_path, entry) {
                 all_sections.push(SectionMatch {
                     doc_path: (*doc_path).to_string(),
                     heading: "Full Document".to_string(),
                     line_start: 1,
                     line_end: content.lines().count(),
                     bm25_score: *doc_score,
-                    content,
+                    content: content.to_string(),
                     canonicality,
+                    via: None,
                 });
             }
         }
     }
 
+    // The top documents only gate which sections are considered; each
+    // section is then ranked by its own text.
+    blend_section_bm25(&mut all_sections, &term_groups, index, heading_boost);
+
     // Sort by combined score with deterministic tie-breaks.
-    all_sections.sort_by(compare_sections_by_relevance);
+    all_sections.sort_by(|a, b| compare_sections_by_relevance(a, b, canonicality_weight));
 
     // Take top N sections
     all_sections.into_iter().take(max_sections).collect()
 }
 
-/// Score document canonicality based on path, recency, and patterns
-pub(crate) fn score_canonicality(doc_path: &str, _entry: &FileEntry) -> f64 {
-    let mut score: f64 = 0.5; // baseline
-
-    let path_lower = doc_path.to_lowercase();
-
-    // Path-based boosts
-    if path_lower.contains("docs/adr/") || path_lower.contains("docs/architecture/") {
-        score += 0.2;
-    }
-    if path_lower.contains("docs/index/") {
-        score += 0.15;
-    }
-    if path_lower.contains("scratch")
-        || path_lower.contains("archive")
-        || path_lower.contains("old")
-    {
-        score -= 0.3;
+/// Share of a section's score taken from its own text; the rest is the BM25
+/// score of the document it came from.
+const SECTION_BM25_WEIGHT: f64 = 0.7;
+
+/// Replace the document score each section inherited with a blend of the
+/// section's own BM25 and that document score. Section BM25 uses the corpus
+/// IDF and is length-normalized against the mean length of `sections`; the
+/// section's own heading is boosted like document headings.
+pub(crate) fn blend_section_bm25(
+    sections: &mut [SectionMatch],
+    term_groups: &[Vec<String>],
+    index: &ForwardIndex,
+    heading_boost: f64,
+) {
+    let counts: Vec<(HashMap<String, usize>, usize)> = sections
+        .iter()
+        .map(|section| text_term_frequencies(&section.content, index.stemmer, &index.stop_words))
+        .collect();
+    let total_length: usize = counts.iter().map(|(_, length)| length).sum();
+    if total_length == 0 {
+        return;
     }
-    if path_lower.contains("deprecated") || path_lower.contains("backup") {
-        score -= 0.25;
+    let avg_length = total_length as f64 / counts.len() as f64;
+
+    for (section, (term_frequencies, length)) in sections.iter_mut().zip(&counts) {
+        let (heading_frequencies, _) =
+            text_term_frequencies(&section.heading, index.stemmer, &index.stop_words);
+        let section_score = bm25_score_counts(
+            term_groups,
+            term_frequencies,
+            &heading_frequencies,
+            *length,
+            avg_length,
+            &index.idf_map,
+            heading_boost,
+        );
+        section.bm25_score =
+            SECTION_BM25_WEIGHT * section_score + (1.0 - SECTION_BM25_WEIGHT) * section.bm25_score;
     }
+}
 
-    // Filename patterns
+/// Score document canonicality from the built-in path and filename rules.
+/// Configured `canonicality --rules` files don't affect ranking.
+pub(crate) fn score_canonicality(doc_path: &str, _entry: &FileEntry) -> f64 {
+    let path_lower = doc_path.to_lowercase();
     let filename = Path::new(doc_path)
         .file_name()
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_lowercase();
 
-    if filename.contains("readme") || filename.contains("index") {
-        score += 0.1;
+    builtin_canonicality_rules()
+        .iter()
+        .filter(|rule| rule.matches(&path_lower, &filename))
+        .fold(0.5, |score, rule| score + rule.delta)
+        .clamp(0.0, 1.0)
+}
+
+/// A section chosen for a digest, with its content cut to fit if needed.
+pub(crate) struct DigestSection<'a> {
+    pub section: &'a SectionMatch,
+    pub content: String,
+    pub truncated: bool,
+}
+
+/// Format-agnostic result of fitting sections into a token budget.
+pub(crate) struct Digest<'a> {
+    pub query: String,
+    pub max_tokens: usize,
+    /// Every candidate section, including those that did not fit
+    pub candidates: &'a [SectionMatch],
+    pub sections: Vec<DigestSection<'a>>,
+    /// Candidate sections left out for lack of budget
+    pub dropped_sections: usize,
+    pub sources: Vec<AssembleSource>,
+    /// Estimated tokens of the rendered digest, excluding the footer
+    pub used_tokens: usize,
+    pub scope: DigestScope,
+    /// Canonicality share of the document ranking in the header
+    pub canonicality_weight: f64,
+    /// Tokenizer the budget was counted with
+    pub tokenizer: TokenizerKind,
+}
+
+/// Path scope a digest was retrieved under.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DigestScope {
+    /// `--include` / `--exclude` summary shown in the header
+    pub label: Option<String>,
+    /// Documents matching the query that `--exclude` filtered out, shown in
+    /// the footer
+    pub excluded_docs: usize,
+    /// Documents considered for the digest, shown in the header; when
+    /// unset, the distinct documents among the candidate sections
+    pub scanned_docs: Option<usize>,
+}
+
+/// Distill sections into a digest rendered in `format` within token budget
+pub(crate) fn distill(
+    sections: &[SectionMatch],
+    query: &str,
+    max_tokens: usize,
+    format: DigestFormat,
+    scope: &DigestScope,
+    canonicality_weight: f64,
+    tokenizer: TokenizerKind,
+) -> String {
+    let digest = plan_digest(
+        sections,
+        query,
+        max_tokens,
+        format,
+        scope,
+        canonicality_weight,
+        tokenizer,
+    );
+    match format {
+        DigestFormat::Markdown => render_digest_markdown(&digest),
+        DigestFormat::Text => render_digest_text(&digest),
+    }
+}
+
+/// Choose which sections fit in `max_tokens`, charging the framing that
+/// `format` adds around them.
+pub(crate) fn plan_digest<'a>(
+    sections: &'a [SectionMatch],
+    query: &str,
+    max_tokens: usize,
+    format: DigestFormat,
+    scope: &DigestScope,
+    canonicality_weight: f64,
+    tokenizer: TokenizerKind,
+) -> Digest<'a> {
+    let scope_label = scope.label.as_deref();
+    let mut used_tokens = match format {
+        DigestFormat::Markdown => {
+            markdown_preamble(
+                sections,
+                query,
+                max_tokens,
+                scope,
+                canonicality_weight,
+                tokenizer,
+            )
+            .1
+        }
+        DigestFormat::Text => count_tokens(&text_preamble(query, scope_label), tokenizer),
+    };
+    let section_header = |section: &SectionMatch| match format {
+        DigestFormat::Markdown => markdown_section_header(section),
+        DigestFormat::Text => text_section_header(section),
+    };
+    let sources_tokens = |included: &[&SectionMatch]| match format {
+        DigestFormat::Markdown => count_tokens(
+            &render_sources_markdown(&collect_sources(included.iter().copied())),
+            tokenizer,
+        ),
+        DigestFormat::Text => 0,
+    };
+
+    // Pack the highest-scoring sections first, whatever order they were
+    // collected in, reserving room for the sources list they would produce.
+    // A section that does not fit is skipped so smaller, lower-ranked ones
+    // can still use the remaining budget.
+    let mut ranked: Vec<usize> = (0..sections.len()).collect();
+    ranked.sort_by(|&a, &b| {
+        combined_section_score(&sections[b], canonicality_weight)
+            .total_cmp(&combined_section_score(&sections[a], canonicality_weight))
+            .then(a.cmp(&b))
+    });
+
+    let mut selected = vec![false; sections.len()];
+    let mut included: Vec<&SectionMatch> = Vec::new();
+    for &idx in &ranked {
+        let section = &sections[idx];
+        let section_tokens = count_tokens(&section_header(section), tokenizer)
+            + count_tokens(&section.content, tokenizer);
+        included.push(section);
+        if used_tokens + section_tokens + sources_tokens(&included) > max_tokens {
+            included.pop();
+            continue;
+        }
+        selected[idx] = true;
+        used_tokens += section_tokens;
     }
-    if filename.contains("guide") || filename.contains("runbook") || filename.contains("plan") {
-        score += 0.1;
+
+    // Spend what is left on a truncated copy of the best section that did
+    // not fit whole
+    let mut partial: Option<(usize, String)> = None;
+    if let Some(&idx) = ranked.iter().find(|&&idx| !selected[idx]) {
+        let section = &sections[idx];
+        included.push(section);
+        let header_tokens = count_tokens(&section_header(section), tokenizer);
+        let remaining_tokens =
+            max_tokens.saturating_sub(used_tokens + sources_tokens(&included) + header_tokens);
+        let chars_to_include = token_byte_budget(&section.content, remaining_tokens, tokenizer);
+
+        let truncated = truncate_markdown_to_bytes(&section.content, chars_to_include);
+        if truncated.len() > 200 {
+            used_tokens += header_tokens + count_tokens(&truncated, tokenizer);
+            partial = Some((idx, truncated));
+        } else {
+            included.pop();
+        }
     }
 
-    // Recency (approximate - we don't have mtime in index yet)
-    // For now, we'll just use this as a placeholder
-    // In future: add last_modified to FileEntry
+    // Render in collection order so related sections stay together
+    let mut chosen: Vec<DigestSection<'a>> = Vec::new();
+    for (idx, section) in sections.iter().enumerate() {
+        if selected[idx] {
+            chosen.push(DigestSection {
+                section,
+                content: section.content.clone(),
+                truncated: false,
+            });
+        } else if let Some((_, content)) = partial.take_if(|(partial_idx, _)| *partial_idx == idx) {
+            chosen.push(DigestSection {
+                section,
+                content,
+                truncated: true,
+            });
+        }
+    }
+    let dropped_sections = sections.len() - chosen.len();
+    let included: Vec<&SectionMatch> = chosen.iter().map(|c| c.section).collect();
 
-    // Clamp to [0.0, 1.0]
-    score.clamp(0.0, 1.0)
+    let sources = collect_sources(included.iter().copied());
+    if format == DigestFormat::Markdown {
+        used_tokens += count_tokens(&render_sources_markdown(&sources), tokenizer);
+    }
+
+    Digest {
+        query: query.to_string(),
+        max_tokens,
+        candidates: sections,
+        sections: chosen,
+        dropped_sections,
+        sources,
+        used_tokens,
+        scope: scope.clone(),
+        canonicality_weight,
+        tokenizer,
+    }
 }
 
-/// Distill sections into markdown digest within token budget
-pub(crate) fn distill_to_markdown(
+/// Markdown header and top-documents list, with its token charge.
+fn markdown_preamble(
     sections: &[SectionMatch],
     query: &str,
     max_tokens: usize,
-) -> String {
+    scope: &DigestScope,
+    canonicality_weight: f64,
+    tokenizer: TokenizerKind,
+) -> (String, usize) {
     let mut output = String::new();
     let mut used_tokens = 0;
 
     // Header
+    let scope_line = scope
+        .label
+        .as_deref()
+        .map(|label| format!("**Scope:** {label}\n"))
+        .unwrap_or_default();
+    let scanned_docs = scope.scanned_docs.unwrap_or_else(|| {
+        sections
+            .iter()
+            .map(|s| s.doc_path.as_str())
+            .collect::<HashSet<_>>()
+            .len()
+    });
     let header = format!(
         "# Context Digest for: \"{}\"\n\n\
          **Generated:** {}\n\
          **Token Budget:** {}\n\
-         **Documents Scanned:** N/A\n\
-         **Sections Selected:** {}\n\n\
+         **Documents Scanned:** {}\n\
+         {}**Sections Selected:** {}\n\n\
          ---\n\n",
         query,
-        chrono_now(),
+        rfc3339_now(),
         max_tokens,
+        scanned_docs,
+        scope_line,
         sections.len()
     );
     output.push_str(&header);
-    used_tokens += estimate_tokens(&header);
+    used_tokens += count_tokens(&header, tokenizer);
 
     // Group sections by document
     let mut doc_groups: HashMap<String, Vec<&SectionMatch>> = HashMap::new();
@@ -169,16 +454,17 @@ pub(crate) fn distill_to_markdown(
 
     let mut ranked_docs: Vec<_> = doc_groups.iter().collect();
     ranked_docs.sort_by(|a, b| {
-        let score_a = a.1[0].bm25_score * 0.7 + a.1[0].canonicality * 0.3;
-        let score_b = b.1[0].bm25_score * 0.7 + b.1[0].canonicality * 0.3;
+        let score_a = combined_section_score(a.1[0], canonicality_weight);
+        let score_b = combined_section_score(b.1[0], canonicality_weight);
         score_b
             .partial_cmp(&score_a)
             .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(b.0))
     });
 
     for (idx, (doc_path, doc_sections)) in ranked_docs.iter().enumerate().take(10) {
         let section = doc_sections[0];
-        let combined_score = section.bm25_score * 0.7 + section.canonicality * 0.3;
+        let combined_score = combined_section_score(section, canonicality_weight);
         let doc_line = format!(
             "{}. **{}** (score: {:.2}, canonical: {:.2})\n   - Sections included: {}\n\n",
             idx + 1,
@@ -188,53 +474,82 @@ pub(crate) fn distill_to_markdown(
             doc_sections.len()
         );
         output.push_str(&doc_line);
-        used_tokens += estimate_tokens(&doc_line);
+        used_tokens += count_tokens(&doc_line, tokenizer);
     }
 
     output.push_str("---\n\n## Distilled Content\n\n");
     used_tokens += 10;
 
-    // Add sections
-    for section in sections {
-        if used_tokens >= max_tokens {
-            output.push_str("\n\n*[Content truncated due to token budget]*\n");
-            break;
-        }
-
-        let section_header = format!(
-            "### {} (from {})\n\n**Source:** {}:{}-{} (canonical: {:.2})\n\n",
-            section.heading,
-            section.doc_path,
-            section.doc_path,
-            section.line_start,
-            section.line_end,
-            section.canonicality
-        );
+    (output, used_tokens)
+}
 
-        // Estimate how much space we need
-        let section_tokens = estimate_tokens(&section_header) + estimate_tokens(&section.content);
+fn markdown_section_header(section: &SectionMatch) -> String {
+    let via = section
+        .via
+        .as_deref()
+        .map(|origin| format!(", via {origin}"))
+        .unwrap_or_default();
+    format!(
+        "### {} (from {}{})\n\n**Source:** {}:{}-{} (canonical: {:.2})\n\n",
+        section.heading,
+        section.doc_path,
+        via,
+        section.doc_path,
+        section.line_start,
+        section.line_end,
+        section.canonicality
+    )
+}
 
-        if used_tokens + section_tokens > max_tokens {
-            // Try to fit a truncated version
-            let remaining_tokens = max_tokens - used_tokens;
-            let chars_to_include = remaining_tokens * 4; // rough approximation
+fn dropped_sections_note(dropped: usize) -> String {
+    if dropped == 1 {
+        "1 section dropped".to_string()
+    } else {
+        format!("{dropped} sections dropped")
+    }
+}
 
-            if chars_to_include > 200 {
-                output.push_str(&section_header);
-                output.push_str(&section.content[..chars_to_include.min(section.content.len())]);
-                output.push_str("\n\n*[Section truncated]*\n");
-            }
-            break;
-        }
+pub(crate) fn render_digest_markdown(digest: &Digest) -> String {
+    use std::fmt::Write;
 
-        output.push_str(&section_header);
-        output.push_str(&section.content);
-        output.push_str("\n\n---\n\n");
+    let (mut output, _) = markdown_preamble(
+        digest.candidates,
+        &digest.query,
+        digest.max_tokens,
+        &digest.scope,
+        digest.canonicality_weight,
+        digest.tokenizer,
+    );
 
-        used_tokens += section_tokens;
+    for chosen in &digest.sections {
+        output.push_str(&markdown_section_header(chosen.section));
+        output.push_str(&chosen.content);
+        if chosen.truncated {
+            output.push_str("\n\n*[Section truncated]*\n");
+        } else {
+            output.push_str("\n\n---\n\n");
+        }
+    }
+    if digest.dropped_sections > 0 {
+        let _ = write!(
+            output,
+            "\n\n*[Content truncated due to token budget: {}]*\n",
+            dropped_sections_note(digest.dropped_sections)
+        );
     }
 
+    // Sources manifest
+    output.push_str(&render_sources_markdown(&digest.sources));
+
     // Metadata footer
+    let excluded = if digest.scope.excluded_docs > 0 {
+        format!(
+            "**Documents Excluded:** {} (matched --exclude)\n\n",
+            digest.scope.excluded_docs
+        )
+    } else {
+        String::new()
+    };
     let footer = format!(
         "\n## Metadata\n\n\
          **Canonicality Scores:**\n\
@@ -242,12 +557,13 @@ pub(crate) fn distill_to_markdown(
          - 0.70-0.89: Reliable, current documentation\n\
          - 0.50-0.69: Secondary or supporting documentation\n\
          - <0.50: Potentially stale, use with caution\n\n\
-         **Actual Tokens Used:** ~{used_tokens}\n\n\
-         ---\n\n\
+         **Actual Tokens Used:** ~{}\n\n\
+         {}---\n\n\
          ## Usage with LLM\n\n\
          Paste this digest into your LLM conversation, then ask:\n\n\
-         > Using only the information in the context above, answer: \"{query}\"\n\
-         > Be explicit when something is not documented in the context.\n"
+         > Using only the information in the context above, answer: \"{}\"\n\
+         > Be explicit when something is not documented in the context.\n",
+        digest.used_tokens, excluded, digest.query
     );
 
     output.push_str(&footer);
@@ -255,11 +571,209 @@ pub(crate) fn distill_to_markdown(
     output
 }
 
-/// Estimate token count (rough approximation: 1 token ≈ 4 chars)
-pub(crate) fn estimate_tokens(text: &str) -> usize {
+fn text_preamble(query: &str, scope_label: Option<&str>) -> String {
+    match scope_label {
+        Some(label) => format!("Query: {query}\nScope: {label}\n\n"),
+        None => format!("Query: {query}\n\n"),
+    }
+}
+
+fn text_section_header(section: &SectionMatch) -> String {
+    let via = section
+        .via
+        .as_deref()
+        .map(|origin| format!(" (via {origin})"))
+        .unwrap_or_default();
+    format!(
+        "// {}:{}-{} {}{}\n",
+        section.doc_path, section.line_start, section.line_end, section.heading, via
+    )
+}
+
+/// Drop markdown heading markers and horizontal rules outside code fences;
+/// the section's own heading is dropped since the source comment names it.
+fn strip_markdown_structure(content: &str) -> String {
+    let (heading, body) = extract_heading(content);
+    let body = if heading.is_empty() { content } else { &body };
+
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if trimmed.starts_with('#') {
+                lines.push(trimmed.trim_start_matches('#').trim_start());
+                continue;
+            }
+            let rule = trimmed.trim_end();
+            if rule.len() >= 3
+                && (rule.chars().all(|c| c == '-')
+                    || rule.chars().all(|c| c == '*')
+                    || rule.chars().all(|c| c == '_'))
+            {
+                continue;
+            }
+            // Dropped rules would otherwise leave runs of blank lines
+            if trimmed.is_empty() && lines.last().is_some_and(|l: &&str| l.trim().is_empty()) {
+                continue;
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+pub(crate) fn render_digest_text(digest: &Digest) -> String {
+    use std::fmt::Write;
+
+    let mut output = text_preamble(&digest.query, digest.scope.label.as_deref());
+
+    for chosen in &digest.sections {
+        output.push_str(&text_section_header(chosen.section));
+        output.push_str(&strip_markdown_structure(&chosen.content));
+        if chosen.truncated {
+            output.push_str("\n[Section truncated]");
+        }
+        output.push_str("\n\n");
+    }
+    if digest.dropped_sections > 0 {
+        let _ = writeln!(
+            output,
+            "[Content truncated due to token budget: {}]",
+            dropped_sections_note(digest.dropped_sections)
+        );
+    }
+    if digest.scope.excluded_docs > 0 {
+        let _ = writeln!(
+            output,
+            "[{} document{} excluded by --exclude]",
+            digest.scope.excluded_docs,
+            if digest.scope.excluded_docs == 1 {
+                ""
+            } else {
+                "s"
+            }
+        );
+    }
+
+    output
+}
+
+/// Group sections by document, in order of first appearance, with each
+/// document's line ranges sorted and deduplicated.
+pub(crate) fn collect_sources<'a>(
+    sections: impl IntoIterator<Item = &'a SectionMatch>,
+) -> Vec<AssembleSource> {
+    let mut sources: Vec<AssembleSource> = Vec::new();
+    for section in sections {
+        let range = [section.line_start, section.line_end];
+        match sources.iter_mut().find(|s| s.doc_path == section.doc_path) {
+            Some(source) => {
+                if !source.line_ranges.contains(&range) {
+                    source.line_ranges.push(range);
+                    source.line_ranges.sort_unstable();
+                }
+            }
+            None => sources.push(AssembleSource {
+                doc_path: section.doc_path.clone(),
+                line_ranges: vec![range],
+                canonicality: section.canonicality,
+            }),
+        }
+    }
+    sources
+}
+
+/// Render the `## Sources` manifest appended to markdown digests.
+pub(crate) fn render_sources_markdown(sources: &[AssembleSource]) -> String {
+    use std::fmt::Write;
+
+    if sources.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("\n## Sources\n\n");
+    for source in sources {
+        let ranges: Vec<String> = source
+            .line_ranges
+            .iter()
+            .map(|[start, end]| format!("{start}-{end}"))
+            .collect();
+        let _ = writeln!(
+            out,
+            "- {}:{} (canonical: {:.2})",
+            source.doc_path,
+            ranges.join(", "),
+            source.canonicality
+        );
+    }
+    out
+}
+
+/// Reject `--tokenizer cl100k` when yore was built without tiktoken.
+pub(crate) fn check_tokenizer_available(
+    kind: TokenizerKind,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if kind == TokenizerKind::Cl100k && !cfg!(feature = "tiktoken") {
+        return Err("--tokenizer cl100k requires yore built with the `tiktoken` feature".into());
+    }
+    Ok(())
+}
+
+/// Token count of `text` under `kind`: exact cl100k counts, or roughly one
+/// token per 4 chars for the approximation.
+pub(crate) fn count_tokens(text: &str, kind: TokenizerKind) -> usize {
+    match kind {
+        TokenizerKind::Approx => text.len() / 4,
+        TokenizerKind::Cl100k => cl100k_token_count(text),
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+fn cl100k_token_count(text: &str) -> usize {
+    static BPE: std::sync::OnceLock<tiktoken_rs::CoreBPE> = std::sync::OnceLock::new();
+    BPE.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k_base ranks"))
+        .encode_with_special_tokens(text)
+        .len()
+}
+
+#[cfg(not(feature = "tiktoken"))]
+fn cl100k_token_count(text: &str) -> usize {
+    // Unreachable in practice: check_tokenizer_available rejects cl100k
+    // without the feature
     text.len() / 4
 }
 
+/// Byte budget that holds `max_tokens` worth of `text`.
+///
+/// With the approximate tokenizer this is simply four bytes per token. With a
+/// real tokenizer it is the longest char-boundary prefix whose token count
+/// fits, or the full length when the whole text fits.
+pub(crate) fn token_byte_budget(text: &str, max_tokens: usize, tokenizer: TokenizerKind) -> usize {
+    match tokenizer {
+        TokenizerKind::Approx => max_tokens.saturating_mul(4),
+        kind @ TokenizerKind::Cl100k => {
+            if count_tokens(text, kind) <= max_tokens {
+                return text.len();
+            }
+            let (mut lo, mut hi) = (0, text.len());
+            while lo < hi {
+                let mid = floor_char_boundary(text, (lo + hi).div_ceil(2));
+                if mid <= lo {
+                    break;
+                }
+                if count_tokens(&text[..mid], kind) <= max_tokens {
+                    lo = mid;
+                } else {
+                    hi = mid - 1;
+                }
+            }
+            floor_char_boundary(text, lo)
+        }
+    }
+}
+
 /// Build ADR index mapping ADR numbers to file paths
 /// Extract all deterministic relation edges from a forward index.
 /// Produces document-level links, section-level links, and ADR reference edges.
@@ -388,7 +902,7 @@ pub fn extract_relations(forward_index: &ForwardIndex) -> RelationIndex {
 
     RelationIndex {
         version: 1,
-        indexed_at: chrono_now(),
+        indexed_at: forward_index.indexed_at.clone(),
         total_edges: edges.len(),
         edges,
     }
@@ -479,17 +993,28 @@ pub fn find_containing_section(sections: &[SectionFingerprint], line: usize) ->
 
 /// Resolve an anchor fragment to a section in the target file entry
 pub fn resolve_anchor_to_section(entry: &FileEntry, anchor: &str) -> Option<SectionRef> {
-    let anchor_slug = anchor.to_lowercase().replace([' ', '_'], "-");
-    for section in &entry.section_fingerprints {
-        let heading_slug = section.heading.to_lowercase().replace(' ', "-");
-        if heading_slug == anchor_slug || heading_slug.contains(&anchor_slug) {
-            return Some(SectionRef {
-                heading: section.heading.clone(),
-                line_start: section.line_start,
-            });
+    let anchor_lower = anchor.to_lowercase();
+    let mut seen_slugs = HashMap::new();
+    let slugs: Vec<String> = entry
+        .section_fingerprints
+        .iter()
+        .map(|section| github_slug(&section.heading, &mut seen_slugs))
+        .collect();
+
+    // Prefer an exact GitHub slug match, then fall back to a loose substring match
+    let anchor_loose = anchor_lower.replace([' ', '_'], "-");
+    let position = slugs
+        .iter()
+        .position(|slug| *slug == anchor_lower)
+        .or_else(|| slugs.iter().position(|slug| slug.contains(&anchor_loose)));
+
+    position.map(|idx| {
+        let section = &entry.section_fingerprints[idx];
+        SectionRef {
+            heading: section.heading.clone(),
+            line_start: section.line_start,
         }
-    }
-    None
+    })
 }
 pub(crate) fn build_adr_index(index: &ForwardIndex) -> HashMap<String, String> {
     let mut adr_map = HashMap::new();
@@ -613,6 +1138,7 @@ pub(crate) fn classify_target_doc(path: &str) -> DocType {
 pub(crate) fn select_sections_for_adr(
     doc_path: &str,
     index: &ForwardIndex,
+    docs: &DocCache,
     entry: &FileEntry,
     max_sections: usize,
 ) -> Vec<SectionMatch> {
@@ -628,7 +1154,7 @@ pub(crate) fn select_sections_for_adr(
         "summary",
     ];
 
-    if let Ok(content) = read_indexed_doc(index, doc_path, entry) {
+    if let Some(content) = docs.read(index, doc_path, entry) {
         let lines: Vec<&str> = content.lines().collect();
 
         // Try to use section fingerprints
@@ -659,6 +1185,7 @@ pub(crate) fn select_sections_for_adr(
                         bm25_score: 0.0, // Cross-ref sections don't have BM25 scores
                         content: section_content,
                         canonicality: score_canonicality(doc_path, entry),
+                        via: None,
                     });
                 }
             }
@@ -674,6 +1201,7 @@ pub(crate) fn select_sections_for_adr(
                 bm25_score: 0.0,
                 content: lines[..lines.len().min(100)].join("\n"),
                 canonicality: score_canonicality(doc_path, entry),
+                via: None,
             });
         }
     }
@@ -685,13 +1213,14 @@ pub(crate) fn select_sections_for_adr(
 pub(crate) fn select_sections_for_design(
     doc_path: &str,
     index: &ForwardIndex,
+    docs: &DocCache,
     entry: &FileEntry,
     anchor: Option<&str>,
     max_sections: usize,
 ) -> Vec<SectionMatch> {
     let mut sections = Vec::new();
 
-    if let Ok(content) = read_indexed_doc(index, doc_path, entry) {
+    if let Some(content) = docs.read(index, doc_path, entry) {
         let lines: Vec<&str> = content.lines().collect();
 
         // If anchor is specified, try to find matching section
@@ -720,6 +1249,7 @@ pub(crate) fn select_sections_for_design(
                             bm25_score: 0.0,
                             content: section_content,
                             canonicality: score_canonicality(doc_path, entry),
+                            via: None,
                         });
                     }
 
@@ -745,6 +1275,7 @@ pub(crate) fn select_sections_for_design(
                         bm25_score: 0.0,
                         content: section_content,
                         canonicality: score_canonicality(doc_path, entry),
+                        via: None,
                     });
                 }
             }
@@ -760,6 +1291,7 @@ pub(crate) fn select_sections_for_design(
                 bm25_score: 0.0,
                 content: lines[..lines.len().min(50)].join("\n"),
                 canonicality: score_canonicality(doc_path, entry),
+                via: None,
             });
         }
     }
@@ -771,6 +1303,7 @@ pub(crate) fn select_sections_for_design(
 pub(crate) fn select_sections_for_ops(
     doc_path: &str,
     index: &ForwardIndex,
+    docs: &DocCache,
     entry: &FileEntry,
     max_sections: usize,
 ) -> Vec<SectionMatch> {
@@ -788,7 +1321,7 @@ pub(crate) fn select_sections_for_ops(
         "restore",
     ];
 
-    if let Ok(content) = read_indexed_doc(index, doc_path, entry) {
+    if let Some(content) = docs.read(index, doc_path, entry) {
         let lines: Vec<&str> = content.lines().collect();
 
         // Prioritize sections with ops keywords
@@ -815,6 +1348,7 @@ pub(crate) fn select_sections_for_ops(
                         bm25_score: 0.0,
                         content: section_content,
                         canonicality: score_canonicality(doc_path, entry),
+                        via: None,
                     });
                 }
             }
@@ -837,6 +1371,7 @@ pub(crate) fn select_sections_for_ops(
                     bm25_score: 0.0,
                     content: section_content,
                     canonicality: score_canonicality(doc_path, entry),
+                    via: None,
                 });
             }
         }
@@ -849,11 +1384,12 @@ pub(crate) fn select_sections_for_ops(
 pub(crate) fn select_sections_for_other(
     doc_path: &str,
     index: &ForwardIndex,
+    docs: &DocCache,
     entry: &FileEntry,
 ) -> Vec<SectionMatch> {
     let mut sections = Vec::new();
 
-    if let Ok(content) = read_indexed_doc(index, doc_path, entry) {
+    if let Some(content) = docs.read(index, doc_path, entry) {
         let lines: Vec<&str> = content.lines().collect();
 
         // Include only the first section (overview)
@@ -873,6 +1409,7 @@ pub(crate) fn select_sections_for_other(
                     bm25_score: 0.0,
                     content: section_content,
                     canonicality: score_canonicality(doc_path, entry),
+                    via: None,
                 });
             }
         }
@@ -881,27 +1418,83 @@ pub(crate) fn select_sections_for_other(
     sections
 }
 
-/// Resolve cross-references into additional sections to include
+/// Index, document contents, scope, and limits shared by cross-reference
+/// expansion.
+pub(crate) struct XrefExpansion<'a> {
+    pub index: &'a ForwardIndex,
+    pub docs: &'a DocCache,
+    /// Documents kept in (or out of) the expansion
+    pub scope: &'a PathScope,
+    pub config: &'a XrefConfig,
+    /// Counts section tokens against the expansion budgets
+    pub tokenizer: TokenizerKind,
+}
+
+/// Resolve cross-references into additional sections to include.
+///
+/// Expansion is iterative: cross-refs found in the sections added by one hop
+/// seed the next, up to `depth` hops. All hops share `xref_token_budget`, no
+/// doc is visited twice, and the per-doc token cap from the expansion's
+/// config halves with every hop.
 pub(crate) fn resolve_crossrefs(
     crossrefs: &[CrossRef],
     primary_docs: &HashSet<String>,
-    index: &ForwardIndex,
+    adr_index: &HashMap<String, String>,
     xref_token_budget: usize,
+    depth: usize,
+    expansion: &XrefExpansion,
 ) -> Vec<SectionMatch> {
-    const MAX_SECTIONS_PER_ADR: usize = 3;
-    const MAX_SECTIONS_PER_DESIGN: usize = 2;
-    const MAX_SECTIONS_PER_OPS: usize = 2;
-    const MAX_TOKENS_PER_XREF_DOC: usize = 600;
-
     let mut xref_sections = Vec::new();
     let mut remaining_budget = xref_token_budget;
     let mut visited_docs: HashSet<String> = primary_docs.clone();
+    let mut frontier = crossrefs.to_vec();
+
+    for hop in 0..depth {
+        if remaining_budget == 0 || frontier.is_empty() {
+            break;
+        }
+
+        let per_doc_cap = expansion.config.per_doc_tokens >> hop.min(16);
+        let hop_sections = resolve_crossref_hop(
+            &frontier,
+            &mut visited_docs,
+            &mut remaining_budget,
+            per_doc_cap,
+            expansion,
+        );
+        if hop_sections.is_empty() {
+            break;
+        }
+
+        frontier = collect_crossrefs(&hop_sections, adr_index);
+        xref_sections.extend(hop_sections);
+    }
+
+    xref_sections
+}
+
+/// Expand a single hop of cross-references, marking each included doc as visited
+fn resolve_crossref_hop(
+    crossrefs: &[CrossRef],
+    visited_docs: &mut HashSet<String>,
+    remaining_budget: &mut usize,
+    per_doc_cap: usize,
+    expansion: &XrefExpansion,
+) -> Vec<SectionMatch> {
+    let &XrefExpansion {
+        index,
+        docs,
+        scope,
+        config,
+        tokenizer,
+    } = expansion;
+    let mut xref_sections = Vec::new();
 
     // Group crossrefs by target doc
     let mut doc_refs: HashMap<String, Vec<&CrossRef>> = HashMap::new();
     for cr in crossrefs {
-        // Skip if already in primary docs or visited
-        if visited_docs.contains(&cr.target_doc_path) {
+        // Skip if already in primary docs or visited, or out of scope
+        if visited_docs.contains(&cr.target_doc_path) || scope.is_excluded(&cr.target_doc_path) {
             continue;
         }
 
@@ -929,7 +1522,7 @@ pub(crate) fn resolve_crossrefs(
 
     // Process each target doc in priority order
     for (target_path, refs) in target_docs {
-        if remaining_budget == 0 {
+        if *remaining_budget == 0 {
             break;
         }
 
@@ -939,27 +1532,18 @@ pub(crate) fn resolve_crossrefs(
         };
 
         let doc_type = classify_target_doc(&target_path);
+        let max_sections = config.max_sections(&doc_type);
 
         // Select sections based on doc type
         let mut doc_sections = match doc_type {
-            DocType::Adr => {
-                select_sections_for_adr(&target_path, index, entry, MAX_SECTIONS_PER_ADR)
-            }
+            DocType::Adr => select_sections_for_adr(&target_path, index, docs, entry, max_sections),
             DocType::Design => {
                 // Check if any ref has an anchor
                 let anchor = refs.iter().find_map(|r| r.target_anchor.as_deref());
-                select_sections_for_design(
-                    &target_path,
-                    index,
-                    entry,
-                    anchor,
-                    MAX_SECTIONS_PER_DESIGN,
-                )
-            }
-            DocType::Ops => {
-                select_sections_for_ops(&target_path, index, entry, MAX_SECTIONS_PER_OPS)
+                select_sections_for_design(&target_path, index, docs, entry, anchor, max_sections)
             }
-            DocType::Other => select_sections_for_other(&target_path, index, entry),
+            DocType::Ops => select_sections_for_ops(&target_path, index, docs, entry, max_sections),
+            DocType::Other => select_sections_for_other(&target_path, index, docs, entry),
         };
 
         // Apply per-doc token budget
@@ -967,23 +1551,27 @@ pub(crate) fn resolve_crossrefs(
         let mut filtered_sections = Vec::new();
 
         for section in doc_sections.drain(..) {
-            let section_tokens = estimate_tokens(&section.content);
+            let section_tokens = count_tokens(&section.content, tokenizer);
 
-            if doc_tokens + section_tokens > MAX_TOKENS_PER_XREF_DOC {
+            if doc_tokens + section_tokens > per_doc_cap {
                 break; // Exceeded per-doc limit
             }
 
-            if remaining_budget < section_tokens {
+            if *remaining_budget < section_tokens {
                 break; // Exceeded global budget
             }
 
             doc_tokens += section_tokens;
-            remaining_budget -= section_tokens;
+            *remaining_budget -= section_tokens;
             filtered_sections.push(section);
         }
 
         if !filtered_sections.is_empty() {
             visited_docs.insert(target_path.clone());
+            let origin = refs.iter().map(|r| r.origin_doc_path.as_str()).min();
+            for section in &mut filtered_sections {
+                section.via = origin.map(str::to_string);
+            }
             xref_sections.extend(filtered_sections);
         }
     }
@@ -997,15 +1585,23 @@ pub(crate) fn resolve_crossrefs(
 pub(crate) fn resolve_crossrefs_from_relations(
     relation_index: &RelationIndex,
     primary_docs: &HashSet<String>,
-    index: &ForwardIndex,
     xref_token_budget: usize,
+    expansion: &XrefExpansion,
 ) -> Vec<SectionMatch> {
-    const MAX_TOKENS_PER_XREF_DOC: usize = 600;
-
+    let &XrefExpansion {
+        index,
+        docs,
+        scope,
+        config,
+        tokenizer,
+    } = expansion;
     // Collect target docs reachable from primary docs, with edge info
     let mut target_edges: HashMap<String, Vec<&RelationEdge>> = HashMap::new();
     for edge in &relation_index.edges {
-        if primary_docs.contains(&edge.source) && !primary_docs.contains(&edge.target) {
+        if primary_docs.contains(&edge.source)
+            && !primary_docs.contains(&edge.target)
+            && !scope.is_excluded(&edge.target)
+        {
             target_edges
                 .entry(edge.target.clone())
                 .or_default()
@@ -1038,28 +1634,23 @@ pub(crate) fn resolve_crossrefs_from_relations(
 
         // Select sections: if anchor, try targeted; otherwise first few sections
         let doc_type = classify_target_doc(&target_path);
-        let max_sections = match doc_type {
-            DocType::Adr => 3,
-            DocType::Design => 2,
-            DocType::Ops => 2,
-            DocType::Other => 2,
-        };
+        let max_sections = config.max_sections(&doc_type);
 
         let mut doc_sections = match doc_type {
-            DocType::Adr => select_sections_for_adr(&target_path, index, entry, max_sections),
+            DocType::Adr => select_sections_for_adr(&target_path, index, docs, entry, max_sections),
             DocType::Design => {
-                select_sections_for_design(&target_path, index, entry, anchor, max_sections)
+                select_sections_for_design(&target_path, index, docs, entry, anchor, max_sections)
             }
-            DocType::Ops => select_sections_for_ops(&target_path, index, entry, max_sections),
-            DocType::Other => select_sections_for_other(&target_path, index, entry),
+            DocType::Ops => select_sections_for_ops(&target_path, index, docs, entry, max_sections),
+            DocType::Other => select_sections_for_other(&target_path, index, docs, entry),
         };
 
         // Apply token budget
         let mut doc_tokens = 0;
         let mut filtered = Vec::new();
         for section in doc_sections.drain(..) {
-            let section_tokens = estimate_tokens(&section.content);
-            if doc_tokens + section_tokens > MAX_TOKENS_PER_XREF_DOC {
+            let section_tokens = count_tokens(&section.content, tokenizer);
+            if doc_tokens + section_tokens > config.per_doc_tokens {
                 break;
             }
             if remaining_budget < section_tokens {
@@ -1069,6 +1660,10 @@ pub(crate) fn resolve_crossrefs_from_relations(
             remaining_budget -= section_tokens;
             filtered.push(section);
         }
+        let origin = edges.iter().map(|e| e.source.as_str()).min();
+        for section in &mut filtered {
+            section.via = origin.map(str::to_string);
+        }
 
         visited.insert(target_path);
         xref_sections.extend(filtered);
@@ -1246,6 +1841,7 @@ pub(crate) fn refine_section(
     section: &SectionMatch,
     query_terms: &[String],
     max_tokens: usize,
+    tokenizer: TokenizerKind,
 ) -> RefinedSection {
     let (heading, body) = extract_heading(&section.content);
 
@@ -1278,6 +1874,7 @@ pub(crate) fn refine_section(
             section: section.clone(),
             truncated: false,
             truncation_reasons: Vec::new(),
+            also_in: Vec::new(),
         };
     }
 
@@ -1336,7 +1933,7 @@ pub(crate) fn refine_section(
 
     let refined_text = refined_parts.join("\n\n");
     let (final_text, truncated, truncation_reasons) =
-        truncate_text_to_budget(&refined_text, max_tokens, 0);
+        truncate_text_to_budget(&refined_text, max_tokens, 0, tokenizer);
 
     RefinedSection {
         section: SectionMatch {
@@ -1347,9 +1944,11 @@ pub(crate) fn refine_section(
             bm25_score: section.bm25_score,
             content: final_text,
             canonicality: section.canonicality,
+            via: section.via.clone(),
         },
         truncated,
         truncation_reasons,
+        also_in: Vec::new(),
     }
 }
 
@@ -1357,16 +1956,113 @@ pub(crate) fn refine_section(
 pub(crate) fn apply_extractive_refiner(
     sections: Vec<SectionMatch>,
     query: &str,
+    stop_words: &StopWords,
     max_tokens_per_section: usize,
+    tokenizer: TokenizerKind,
 ) -> Vec<RefinedSection> {
-    let query_terms = parse_query_terms(query, true);
+    let query_terms = parse_query_terms(query, Some(stop_words));
 
     sections
         .into_iter()
-        .map(|section| refine_section(&section, &query_terms, max_tokens_per_section))
+        .map(|section| refine_section(&section, &query_terms, max_tokens_per_section, tokenizer))
         .collect()
 }
 
+/// Merge near-identical refined sections, such as a how-to block pasted
+/// into several docs.
+///
+/// Two sections are copies when their bodies (content below the leading
+/// heading) are equal after whitespace normalization or their SimHash
+/// similarity reaches `threshold`; bodies under three words have no
+/// meaningful SimHash and only match exactly. Each group keeps the copy from
+/// the most canonical doc, then the best BM25 score, at the position of the
+/// group's highest-ranked copy; the other docs are listed in `also_in`.
+pub(crate) fn merge_similar_sections(
+    sections: Vec<RefinedSection>,
+    threshold: f64,
+) -> Vec<RefinedSection> {
+    let fingerprints: Vec<(String, Option<u64>)> = sections
+        .iter()
+        .map(|refined| {
+            // Compare bodies only: the same block often sits under different headings.
+            let (_, body) = extract_heading(&refined.section.content);
+            let simhash = body
+                .split_whitespace()
+                .nth(2)
+                .is_some()
+                .then(|| compute_simhash(&body));
+            (normalize_content_for_dedupe(&body), simhash)
+        })
+        .collect();
+    let is_copy = |a: usize, b: usize| {
+        let (text_a, hash_a) = &fingerprints[a];
+        let (text_b, hash_b) = &fingerprints[b];
+        (!text_a.is_empty() && text_a == text_b)
+            || matches!((hash_a, hash_b), (Some(x), Some(y)) if simhash_similarity(*x, *y) >= threshold)
+    };
+
+    let mut assigned = vec![false; sections.len()];
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for i in 0..sections.len() {
+        if assigned[i] {
+            continue;
+        }
+        assigned[i] = true;
+        let mut group = vec![i];
+        for (j, taken) in assigned.iter_mut().enumerate().skip(i + 1) {
+            if !*taken && is_copy(i, j) {
+                *taken = true;
+                group.push(j);
+            }
+        }
+        groups.push(group);
+    }
+
+    let mut slots: Vec<Option<RefinedSection>> = sections.into_iter().map(Some).collect();
+    groups
+        .into_iter()
+        .map(|group| {
+            let best = *group
+                .iter()
+                .max_by(|&&a, &&b| {
+                    let (sa, sb) = (
+                        &slots[a].as_ref().unwrap().section,
+                        &slots[b].as_ref().unwrap().section,
+                    );
+                    sa.canonicality
+                        .partial_cmp(&sb.canonicality)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| {
+                            sa.bm25_score
+                                .partial_cmp(&sb.bm25_score)
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        })
+                        // Prefer the earlier section on ties.
+                        .then_with(|| b.cmp(&a))
+                })
+                .unwrap();
+            let mut kept = slots[best].take().unwrap();
+            for idx in group {
+                if let Some(copy) = slots[idx].take() {
+                    let doc = copy.section.doc_path;
+                    if doc != kept.section.doc_path && !kept.also_in.contains(&doc) {
+                        kept.also_in.push(doc);
+                    }
+                }
+            }
+            kept
+        })
+        .collect()
+}
+
+/// Order refined sections by combined BM25 and canonicality score, so the
+/// digest leads with sections that match the query rather than the
+/// zero-BM25 cross-references appended after them.
+pub(crate) fn rank_refined_sections(sections: &mut [RefinedSection], canonicality_weight: f64) {
+    sections
+        .sort_by(|a, b| compare_sections_by_relevance(&a.section, &b.section, canonicality_weight));
+}
+
 pub(crate) fn expand_from_files_args(
     args: &[String],
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
@@ -1457,17 +2153,28 @@ pub(crate) fn resolve_from_files(
     (resolved, missing)
 }
 
+/// Sections of explicitly selected files, ranked against `query` when it is
+/// not empty. The files are taken as given, so `search.scope` and
+/// `search.synonyms` do not apply.
 pub(crate) fn collect_sections_for_files(
     file_paths: &[String],
-    index: &ForwardIndex,
     query: &str,
-    max_sections: usize,
+    search: &SectionSearch,
 ) -> Vec<SectionMatch> {
+    let &SectionSearch {
+        index,
+        docs,
+        max_sections,
+        canonicality_weight,
+        heading_boost,
+        ..
+    } = search;
     let query_terms = if query.is_empty() {
         Vec::new()
     } else {
-        parse_query_terms(query, true)
+        parse_query_terms(query, Some(&index.stop_words))
     };
+    let term_groups = expand_query_terms(&query_terms, &Synonyms::default(), index.stemmer);
     let mut all_sections = Vec::new();
 
     for path in file_paths {
@@ -1477,12 +2184,18 @@ pub(crate) fn collect_sections_for_files(
         let doc_score = if query_terms.is_empty() {
             1.0
         } else {
-            bm25_score(&query_terms, entry, index.avg_doc_length, &index.idf_map)
+            bm25_score_groups(
+                &term_groups,
+                entry,
+                index.avg_doc_length,
+                &index.idf_map,
+                heading_boost,
+            )
         };
         let canonicality = score_canonicality(path, entry);
 
         if !entry.section_fingerprints.is_empty() {
-            if let Ok(content) = read_indexed_doc(index, path, entry) {
+            if let Some(content) = docs.read(index, path, entry) {
                 let lines: Vec<&str> = content.lines().collect();
                 for section in &entry.section_fingerprints {
                     let start = section.line_start.saturating_sub(1);
@@ -1497,24 +2210,26 @@ pub(crate) fn collect_sections_for_files(
                             bm25_score: doc_score,
                             content: section_content,
                             canonicality,
+                            via: None,
                         });
                     }
                 }
             }
-        } else if let Ok(content) = read_indexed_doc(index, path, entry) {
+        } else if let Some(content) = docs.read(index, path, entry) {
             all_sections.push(SectionMatch {
                 doc_path: path.to_string(),
                 heading: "Full Document".to_string(),
                 line_start: 1,
                 line_end: content.lines().count(),
                 bm25_score: doc_score,
-                content,
+                content: content.to_string(),
                 canonicality,
+                via: None,
             });
         }
     }
 
-    all_sections.sort_by(compare_sections_by_relevance);
+    all_sections.sort_by(|a, b| compare_sections_by_relevance(a, b, canonicality_weight));
 
     all_sections.into_iter().take(max_sections).collect()
 }
@@ -1522,9 +2237,15 @@ pub(crate) fn collect_sections_for_files(
 pub(crate) fn collect_context_selection(
     query: &str,
     from_files: &[String],
-    index: &ForwardIndex,
-    max_sections: usize,
+    search: &SectionSearch,
 ) -> Result<ContextSelection, ContextSelectionIssue> {
+    let &SectionSearch {
+        index,
+        scope,
+        synonyms,
+        heading_boost,
+        ..
+    } = search;
     let query_label = if query.trim().is_empty() {
         "selected files".to_string()
     } else {
@@ -1536,6 +2257,8 @@ pub(crate) fn collect_context_selection(
         query.to_string()
     };
 
+    let scanned_docs;
+    let excluded_docs;
     let sections = if !from_files.is_empty() {
         let expanded = expand_from_files_args(from_files)
             .map_err(|_| ContextSelectionIssue::NoIndexedFilesMatched)?;
@@ -1548,14 +2271,32 @@ pub(crate) fn collect_context_selection(
         if resolved.is_empty() {
             return Err(ContextSelectionIssue::NoIndexedFilesMatched);
         }
+        scanned_docs = resolved.len();
+        excluded_docs = 0;
 
-        collect_sections_for_files(&resolved, index, query, max_sections)
+        collect_sections_for_files(&resolved, query, search)
     } else {
-        let query_terms = parse_query_terms(query, true);
+        let query_terms = parse_query_terms(query, Some(&index.stop_words));
         if query_terms.is_empty() {
             return Err(ContextSelectionIssue::NoSearchableTerms);
         }
-        search_relevant_sections(query, index, max_sections)
+        scanned_docs = index
+            .files
+            .keys()
+            .filter(|path| !scope.is_excluded(path))
+            .count();
+        // Only excluded documents the query would have scored count as excluded
+        let term_groups = expand_query_terms(&query_terms, synonyms, index.stemmer);
+        excluded_docs = scope.count_excluded(index, |entry| {
+            bm25_score_groups(
+                &term_groups,
+                entry,
+                index.avg_doc_length,
+                &index.idf_map,
+                heading_boost,
+            ) > 0.01
+        });
+        search_relevant_sections(query, search)
     };
 
     if sections.is_empty() {
@@ -1566,5 +2307,7 @@ pub(crate) fn collect_context_selection(
         query_label,
         query_for_refiner,
         sections,
+        excluded_docs,
+        scanned_docs,
     })
 }
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 /// yore – Deterministic documentation indexer and context assembly engine.
@@ -68,7 +68,10 @@ OUTPUT FORMATS
 
     build, eval, query, similar, dupes, dupes-sections, check, check-links,
     fix-links, backlinks, orphans, canonicality, canonical-orphans, stale,
-    vocabulary, suggest-consolidation, policy, diff, stats, mv, fix-references
+    vocabulary, suggest-consolidation, policy, diff, stats, show, mv,
+    fix-references
+
+  `assemble` emits structured sections with --format json.
 
   Example: yore check-links --index .yore --json | jq '.broken[]'"#
 )]
@@ -87,6 +90,18 @@ pub struct Cli {
     /// Quiet mode - suppress non-essential output
     #[arg(short, long, global = true)]
     pub quiet: bool,
+
+    /// Token counter for context budgets: approx (4 bytes/token) or cl100k
+    #[arg(long, global = true, default_value = "approx")]
+    pub tokenizer: String,
+
+    /// Print per-phase timings to stderr (suppressed by --quiet)
+    #[arg(long, global = true)]
+    pub timing: bool,
+
+    /// Fail instead of warning when the index predates this yore's index format
+    #[arg(long, global = true)]
+    pub strict: bool,
 }
 
 #[derive(Subcommand)]
@@ -155,6 +170,15 @@ pub enum Commands {
         /// Staleness threshold in days (files older than this are candidates)
         #[arg(long, default_value = "30")]
         stale_days: u64,
+
+        /// Age stale candidates by last git commit instead of mtime
+        #[arg(long)]
+        use_git: bool,
+
+        /// Allowlist of known-bad links to suppress from link results
+        /// (`source_file:line` or `source_file:target`, one per line)
+        #[arg(long, value_name = "FILE")]
+        ignore_file: Option<PathBuf>,
     },
     /// Detect structural document-health issues from build-time metrics.
     ///
@@ -197,6 +221,31 @@ pub enum Commands {
         #[arg(long)]
         json: bool,
     },
+    /// Scaffold a `.yore.toml` config and a starter policy file.
+    ///
+    /// Writes a commented config (at the global `--config` path) with an
+    /// example `[index.docs]` profile, and a policy file with one example
+    /// rule. Both are checked to parse before anything is written.
+    ///
+    /// Limitations:
+    ///   - Refuses to overwrite existing files unless `--force` is given.
+    ///
+    /// Related:
+    ///   - `yore build`, `yore policy`
+    ///
+    /// Examples:
+    ///   yore init
+    ///   yore init --policy docs/policy.yaml --force
+    Init {
+        /// Policy file to write
+        #[arg(long, default_value = ".yore-policy.yaml")]
+        policy: PathBuf,
+
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool,
+    },
+
     /// Build forward and reverse indexes over documentation.
     ///
     /// Walks a directory tree, indexes Markdown/text files, and writes
@@ -210,6 +259,9 @@ pub enum Commands {
     ///   - Only indexes the extensions listed in `--types`.
     ///   - Ignores binary files and content outside the selected roots.
     ///   - `--track-renames` requires a git repo with history.
+    ///   - The `--stemmer` choice is stored in the index; rebuild to change it.
+    ///   - `--positions` grows the forward index by roughly one entry per token.
+    ///   - `--stop-words` lists are stored in the index; queries reuse them.
     ///
     /// Related:
     ///   - `yore stats`, `yore query`, `yore assemble`
@@ -217,6 +269,10 @@ pub enum Commands {
     /// Examples:
     ///   yore build docs --output .yore --types md,txt --json
     ///   yore build . --output .yore --exclude node_modules --exclude target
+    ///   yore build docs --jobs 2
+    ///   yore build docs --stemmer porter
+    ///   yore build docs --positions
+    ///   yore build docs --stop-words stopwords.txt --stop-words-mode replace
     Build {
         /// Path to index
         #[arg(default_value = ".")]
@@ -227,7 +283,7 @@ pub enum Commands {
         output: PathBuf,
 
         /// File extensions to index (comma-separated)
-        #[arg(short, long, default_value = "md,txt,rst")]
+        #[arg(short, long, default_value = "md,txt,rst,adoc")]
         types: String,
 
         /// Patterns to exclude (can be repeated)
@@ -241,6 +297,47 @@ pub enum Commands {
         /// Track file renames using git history
         #[arg(long)]
         track_renames: bool,
+
+        /// Number of indexing threads (default: number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Stemmer applied to keywords: porter, simple, or none
+        #[arg(long, default_value = "simple")]
+        stemmer: String,
+
+        /// Record term positions so quoted phrases match by exact adjacency
+        #[arg(long)]
+        positions: bool,
+
+        /// MinHash permutations per file; fewer build faster on small corpora
+        #[arg(long, default_value = "128")]
+        minhash_perms: usize,
+
+        /// Shingle weighting for file SimHash: idf (shared boilerplate counts
+        /// less) or uniform
+        #[arg(long, default_value = "idf", value_parser = ["idf", "uniform"])]
+        simhash_weighting: String,
+
+        /// File of whitespace-separated stop words to leave out of keywords and term statistics
+        #[arg(long, value_name = "FILE")]
+        stop_words: Option<PathBuf>,
+
+        /// How --stop-words combines with the built-in list: merge or replace
+        #[arg(long, default_value = "merge", requires = "stop_words")]
+        stop_words_mode: String,
+
+        /// Forward index encoding: json (forward_index.json) or bin (forward_index.bin, faster to load)
+        #[arg(long, default_value = "json")]
+        format: String,
+
+        /// After building, keep watching and re-index changed files until Ctrl-C
+        #[arg(long)]
+        watch: bool,
+
+        /// Milliseconds without changes before a watch rebuild starts
+        #[arg(long, default_value = "300", requires = "watch")]
+        debounce_ms: u64,
     },
 
     /// Search the index for relevant documents using BM25.
@@ -261,6 +358,9 @@ pub enum Commands {
     /// Examples:
     ///   yore query kubernetes deployment --index .yore --limit 5
     ///   yore query --query '"async migration"' --phrase --index .yore --files-only
+    ///   yore query rollout --tag runbook --index .yore
+    ///   yore query oncall escalation --group-by-dir --group-depth 2 --limit 50
+    ///   yore query rollback --min-score 2.5 --json --index .yore
     Query {
         /// Search terms
         terms: Vec<String>,
@@ -273,6 +373,14 @@ pub enum Commands {
         #[arg(short = 'n', long, default_value = "10")]
         limit: usize,
 
+        /// Skip the first N ranked results (JSON output gains offset, limit, total_matches)
+        #[arg(long)]
+        offset: Option<usize>,
+
+        /// Drop results scoring below this before paging (JSON output notes how many)
+        #[arg(long, value_name = "SCORE")]
+        min_score: Option<f64>,
+
         /// Show only file paths
         #[arg(short = 'l', long)]
         files_only: bool,
@@ -297,6 +405,43 @@ pub enum Commands {
         #[arg(long)]
         phrase: bool,
 
+        /// Only return documents whose frontmatter `tags` include this tag (can be repeated)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Show the best-matching lines of each result with query terms highlighted
+        #[arg(long)]
+        snippets: bool,
+
+        /// YAML file mapping canonical terms to aliases, expanded at query time
+        #[arg(long)]
+        synonyms: Option<PathBuf>,
+
+        /// Glob of indexed paths to leave out of ranking (can be repeated)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Only rank documents with these extensions (comma-separated, e.g. md,rst)
+        #[arg(long = "type", value_name = "EXT", value_delimiter = ',')]
+        types: Vec<String>,
+
+        /// Weight of a query term in a heading relative to the body in BM25
+        #[arg(long, default_value = "2.0")]
+        heading_boost: f64,
+
+        /// Multiply scores by 1 + F × (share of query terms named in the
+        /// document's inline code spans); 0 disables
+        #[arg(long, value_name = "F", default_value = "0.0")]
+        code_boost: f64,
+
+        /// Group the shown results by directory, with each group's summed score
+        #[arg(long)]
+        group_by_dir: bool,
+
+        /// Leading directory components that name a --group-by-dir group
+        #[arg(long, default_value = "1", requires = "group_by_dir")]
+        group_depth: usize,
+
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
@@ -343,6 +488,9 @@ pub enum Commands {
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
+
+        #[command(flatten)]
+        similarity: SimilarityWeightArgs,
     },
 
     /// Find duplicate or heavily overlapping documents.
@@ -376,9 +524,22 @@ pub enum Commands {
         #[arg(long)]
         json: bool,
 
+        /// Output as CSV (cannot be combined with --json)
+        #[arg(long, conflicts_with = "json")]
+        csv: bool,
+
+        /// LSH bands over the MinHash signature; more bands find more candidates
+        /// (must divide the index's permutation count; default: its largest
+        /// divisor up to 16)
+        #[arg(long)]
+        lsh_bands: Option<usize>,
+
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
+
+        #[command(flatten)]
+        similarity: SimilarityWeightArgs,
     },
 
     /// Find duplicate sections across documents.
@@ -400,6 +561,7 @@ pub enum Commands {
     /// Examples:
     ///   yore dupes-sections --index .yore --threshold 0.7 --min-files 2
     ///   yore dupes-sections --index .yore --threshold 0.85 --min-files 5 --json
+    ///   yore dupes-sections --index .yore --show-diff
     DupesSections {
         /// Similarity threshold (0.0 to 1.0)
         #[arg(short, long, default_value = "0.7")]
@@ -409,6 +571,46 @@ pub enum Commands {
         #[arg(short = 'n', long, default_value = "2")]
         min_files: usize,
 
+        /// Print a line diff of the two most dissimilar sections per cluster
+        #[arg(long)]
+        show_diff: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Index directory
+        #[arg(short, long, default_value = ".yore")]
+        index: PathBuf,
+    },
+
+    /// Find near-identical headings across documents.
+    ///
+    /// Clusters heading spellings that differ only slightly (for example
+    /// "Getting Started" and "Getting started guide") so terminology can be
+    /// standardized. Works from heading data in the index alone.
+    ///
+    /// Limitations:
+    ///   - Exact repeats of one spelling are not reported; only clusters
+    ///     with two or more distinct spellings are.
+    ///   - Similarity is lexical; synonyms ("Setup" vs "Installation") are
+    ///     not matched.
+    ///
+    /// Related:
+    ///   - `yore dupes-sections`, `yore vocabulary`
+    ///
+    /// Examples:
+    ///   yore dupes-headings --index .yore
+    ///   yore dupes-headings --index .yore --threshold 0.8 --json
+    DupesHeadings {
+        /// Similarity threshold (0.0 to 1.0)
+        #[arg(short, long, default_value = "0.7")]
+        threshold: f64,
+
+        /// Minimum number of files a cluster must span
+        #[arg(short = 'n', long, default_value = "2")]
+        min_files: usize,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
@@ -422,9 +624,11 @@ pub enum Commands {
     ///
     /// Compares two files using the index and reports what content they
     /// share, helping you understand drift or duplication between them.
+    /// Sections are aligned across the files by heading and SimHash, showing
+    /// near-identical pairs and sections unique to either file.
     ///
     /// Limitations:
-    ///   - Not a line-by-line diff; uses indexed keywords/headings.
+    ///   - Not a line-by-line diff; uses indexed keywords/headings/sections.
     ///   - Both files must be indexed.
     ///
     /// Related:
@@ -447,15 +651,26 @@ pub enum Commands {
         /// Output as JSON
         #[arg(long)]
         json: bool,
+
+        #[command(flatten)]
+        similarity: SimilarityWeightArgs,
     },
 
     /// Show high-level index statistics.
     ///
-    /// Prints counts of files, headings, links, and top keywords, which
-    /// is useful for sanity-checking an index and monitoring drift over time.
+    /// Prints counts of files, headings, links, and top keywords, plus the
+    /// distribution of document lengths and links, which is useful for
+    /// sanity-checking an index and monitoring drift over time.
+    ///
+    /// With `--check-drift`, compares the index with the files on disk
+    /// instead, listing files added, removed, or modified since the last
+    /// build, and exits nonzero if there are any.
     ///
     /// Limitations:
-    ///   - Reports only what is in the index, not the live filesystem.
+    ///   - Statistics cover only what is in the index, not the live filesystem.
+    ///   - Drift checks compare file sizes, so a same-size edit goes unnoticed.
+    ///   - Added files are only found for indexes that recorded their build
+    ///     scope; rebuild older indexes first.
     ///
     /// Related:
     ///   - `yore build`, `yore query`
@@ -463,11 +678,16 @@ pub enum Commands {
     /// Examples:
     ///   yore stats --index .yore --top-keywords 20 --json
     ///   yore stats --index docs/.index --top-keywords 50
+    ///   yore stats --index .yore --check-drift
     Stats {
         /// Show top N keywords
         #[arg(long, default_value = "20")]
         top_keywords: usize,
 
+        /// Compare the index with the files on disk; exit 1 on any drift
+        #[arg(long)]
+        check_drift: bool,
+
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
@@ -477,6 +697,42 @@ pub enum Commands {
         json: bool,
     },
 
+    /// Show what the index stores about a single file.
+    ///
+    /// Prints the file's line and word counts, headings with their levels
+    /// and lines, outbound links, how many indexed files link to it, its
+    /// canonicality score with the reasons behind it, its most frequent
+    /// body keywords, and its section fingerprints. A debugging companion
+    /// to `yore build`.
+    ///
+    /// Limitations:
+    ///   - Shows the file as of the last build, not the live filesystem.
+    ///   - Word counts are indexed terms, after stopword filtering.
+    ///
+    /// Related:
+    ///   - `yore stats`, `yore backlinks`, `yore canonicality`
+    ///
+    /// Examples:
+    ///   yore show docs/README.md --index .yore
+    ///   yore show docs/architecture/OVERVIEW.md --top-keywords 20
+    ///   yore show docs/README.md --index .yore --json
+    Show {
+        /// Indexed file to show
+        file: String,
+
+        /// Show top N body keywords
+        #[arg(long, default_value = "10")]
+        top_keywords: usize,
+
+        /// Index directory
+        #[arg(short, long, default_value = ".yore")]
+        index: PathBuf,
+
+        /// Dump the raw index entry as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Interactive query REPL over the index.
     ///
     /// Starts a simple read-eval-print loop where you can type queries
@@ -505,6 +761,9 @@ pub enum Commands {
     /// This is the primary entry point for agents and tools that want a
     /// deterministic, reproducible context to send to an LLM.
     ///
+    /// The query can also come from --query-file, or from stdin when no
+    /// query words are given, so long questions need no shell quoting.
+    ///
     /// Limitations:
     ///   - Uses indexed content only; run `yore build` first.
     ///   - Cross-reference expansion follows internal links only.
@@ -516,12 +775,20 @@ pub enum Commands {
     ///   yore assemble "How does authentication work?" \
     ///     --index .yore --max-tokens 8000 --depth 1 > context.md
     ///   yore assemble "async migration status" --index .yore --max-sections 10
+    ///   yore assemble --query-file question.txt --index .yore
+    ///   echo "How do we roll back a release?" | yore assemble --index .yore
     ///   yore assemble --from-files docs/adr/ADR-0010.md docs/adr/ADR-0011.md --index .yore
+    ///   yore assemble "deployment rollback" --format json --index .yore | jq '.sections[]'
+    ///   yore assemble "request routing" --include "docs/architecture/**" --index .yore
     Assemble {
-        /// Natural language query/question (required unless --from-files is used)
-        #[arg(required_unless_present = "from_files")]
+        /// Natural language query/question (read from --query-file or stdin
+        /// when omitted; optional with --from-files)
         query: Vec<String>,
 
+        /// Read the query from this file instead of the command line
+        #[arg(long, value_name = "PATH", conflicts_with = "query")]
+        query_file: Option<PathBuf>,
+
         /// Maximum tokens in output (approximate)
         #[arg(short = 't', long, default_value = "8000")]
         max_tokens: usize,
@@ -530,11 +797,12 @@ pub enum Commands {
         #[arg(short = 's', long, default_value = "20")]
         max_sections: usize,
 
-        /// Cross-reference expansion depth
+        /// Cross-reference expansion depth (0 disables; each extra hop follows
+        /// links in the previous hop's sections with a halved per-doc cap)
         #[arg(short = 'd', long, default_value = "1")]
         depth: usize,
 
-        /// Output format
+        /// Output format: markdown, text, or json
         #[arg(short = 'f', long, default_value = "markdown")]
         format: String,
 
@@ -550,6 +818,56 @@ pub enum Commands {
         #[arg(long)]
         use_relations: bool,
 
+        /// SimHash similarity (0.0 to 1.0) at which selected sections count as
+        /// copies; only the copy from the most canonical doc is kept
+        #[arg(long, default_value = "0.9")]
+        dedup_threshold: f64,
+
+        /// Keep near-duplicate sections (exact copies are still dropped)
+        #[arg(long)]
+        no_dedup: bool,
+
+        /// YAML file mapping canonical terms to aliases, expanded at query time
+        #[arg(long)]
+        synonyms: Option<PathBuf>,
+
+        /// Glob of indexed paths to restrict search and cross-reference
+        /// expansion to (can be repeated; applied before --exclude)
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Glob of indexed paths to leave out of search and cross-reference
+        /// expansion (can be repeated)
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Only search documents with these extensions (comma-separated,
+        /// e.g. md,rst); cross-reference expansion stays within them too
+        #[arg(long = "type", value_name = "EXT", value_delimiter = ',')]
+        types: Vec<String>,
+
+        /// Share of section and document ranking given to canonicality
+        /// (0.0 to 1.0); BM25 relevance gets the rest
+        #[arg(long, default_value = "0.3")]
+        canonicality_weight: f64,
+
+        /// Weight of a query term in a heading relative to the body in BM25
+        #[arg(long, default_value = "2.0")]
+        heading_boost: f64,
+
+        /// Share of --max-tokens cross-reference expansion may use (0.0 to 1.0)
+        #[arg(long, value_name = "F", default_value = "0.3")]
+        xref_fraction: f64,
+
+        /// Absolute ceiling on tokens added by cross-reference expansion
+        #[arg(long, value_name = "N", default_value = "2000")]
+        xref_max_tokens: usize,
+
+        /// Tokens one cross-referenced doc may add on the first hop (halved
+        /// on each further hop)
+        #[arg(long, value_name = "N", default_value = "600")]
+        xref_per_doc_tokens: usize,
+
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
@@ -607,6 +925,22 @@ pub enum Commands {
         /// Values of k for precision@k, recall@k, nDCG@k (comma-separated)
         #[arg(long, value_delimiter = ',', default_values_t = vec![5, 10])]
         k: Vec<usize>,
+
+        /// YAML file mapping canonical terms to aliases, expanded at query time
+        #[arg(long)]
+        synonyms: Option<PathBuf>,
+
+        /// Token budget of each assembled digest (as `assemble --max-tokens`)
+        #[arg(short = 't', long, default_value = "8000")]
+        max_tokens: usize,
+
+        /// Cross-reference expansion depth (as `assemble --depth`)
+        #[arg(short = 'd', long, default_value = "1")]
+        depth: usize,
+
+        /// Exit nonzero when the pass rate (0.0-1.0) falls below this value
+        #[arg(long, value_name = "RATE")]
+        fail_under: Option<f64>,
     },
 
     /// Derive a deterministic vocabulary list from a built index.
@@ -693,6 +1027,9 @@ pub enum Commands {
     ///
     ///   # Docs-only profile with summary for CI
     ///   yore --profile docs check-links --json --summary-only
+    ///
+    ///   # Ratchet down link debt: ignore known-bad links listed in a file
+    ///   yore check-links --ignore-file links-allowlist.txt
     CheckLinks {
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
@@ -713,18 +1050,51 @@ pub enum Commands {
         /// Only show the summary (suppress individual link entries)
         #[arg(long)]
         summary_only: bool,
+
+        /// Also check http(s) links over the network (results cached in the index dir)
+        #[arg(long)]
+        check_external: bool,
+
+        /// Per-request timeout in seconds for --check-external
+        #[arg(long, default_value = "10")]
+        timeout: u64,
+
+        /// Maximum concurrent requests for --check-external
+        #[arg(long, default_value = "8")]
+        concurrency: usize,
+
+        /// Allowlist of known-bad links to suppress from results and counts
+        /// (`source_file:line` or `source_file:target`, one per line)
+        #[arg(long, value_name = "FILE")]
+        ignore_file: Option<PathBuf>,
+
+        /// Lines of surrounding text to include before and after each
+        /// broken link's line in its context
+        #[arg(long, value_name = "N", default_value = "0")]
+        context_lines: usize,
+
+        /// Exit nonzero when the link health score (0-100) falls below this value
+        #[arg(long, value_name = "SCORE")]
+        fail_below: Option<f64>,
     },
 
     /// Find all files that link to a specific file.
     ///
     /// Traverses the link graph to list every document that links to the
-    /// given target file, including optional anchors.
+    /// given target file, including optional anchors. With --depth N, files
+    /// that reach it through other documents are listed too, grouped by
+    /// distance.
+    ///
+    /// A target containing glob characters (`*`, `?`, `[`, `{`) selects
+    /// every indexed file it matches; backlinks are then reported per
+    /// matching file, including files nothing links to.
     ///
     /// Useful for understanding impact of changes, cleaning up docs, and
     /// deciding whether a document is safe to delete.
     ///
     /// Limitations:
     ///   - Only considers indexed markdown links (not external URLs).
+    ///   - With a glob, links between matching files count as backlinks too.
     ///
     /// Related:
     ///   - `yore orphans`, `yore export-graph`
@@ -732,14 +1102,20 @@ pub enum Commands {
     /// Examples:
     ///   yore backlinks docs/architecture/DEPLOYMENT-GUIDE.md --index .yore
     ///   yore backlinks docs/README.md --index .yore --json
+    ///   yore backlinks docs/architecture/OVERVIEW.md --depth 3
+    ///   yore backlinks 'docs/architecture/**' --json
     Backlinks {
-        /// File to find backlinks for
+        /// File to find backlinks for, or a glob of indexed paths
         file: String,
 
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
 
+        /// Also report files linking to the linkers, up to this many hops
+        #[arg(long, default_value = "1")]
+        depth: usize,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
@@ -763,6 +1139,7 @@ pub enum Commands {
     /// Examples:
     ///   yore orphans --index .yore --exclude README
     ///   yore orphans --index .yore --exclude README --exclude INDEX --json
+    ///   yore orphans --index .yore --reachable-from docs/README.md
     Orphans {
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
@@ -772,9 +1149,24 @@ pub enum Commands {
         #[arg(long)]
         json: bool,
 
+        /// Output as CSV (cannot be combined with --json)
+        #[arg(long, conflicts_with = "json")]
+        csv: bool,
+
         /// Exclude files matching pattern (can be repeated)
         #[arg(short, long)]
         exclude: Vec<String>,
+
+        /// Report files unreachable by following links from this root
+        /// instead of files with no inbound links (can be repeated; with no
+        /// value, every README/index document is a root)
+        #[arg(
+            long,
+            value_name = "FILE",
+            num_args = 0..=1,
+            default_missing_value = ""
+        )]
+        reachable_from: Option<Vec<String>>,
     },
 
     /// Show canonicality scores for all documents.
@@ -792,6 +1184,9 @@ pub enum Commands {
     /// Examples:
     ///   yore canonicality --index .yore --threshold 0.7
     ///   yore canonicality --index .yore --json
+    ///   yore canonicality --index .yore --no-graph
+    ///   yore canonicality --sort asc --limit 20
+    ///   yore canonicality --rules canon.yaml
     Canonicality {
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
@@ -804,6 +1199,25 @@ pub enum Commands {
         /// Minimum score threshold (0.0 to 1.0)
         #[arg(short, long, default_value = "0.0")]
         threshold: f64,
+
+        /// Ignore link-graph authority and score on path/filename heuristics only
+        #[arg(long)]
+        no_graph: bool,
+
+        /// Files to print per score band (0 = all; default: 10 high, 5 medium, 5 low)
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Score order: desc (most canonical first) or asc (least canonical
+        /// first, low band printed first)
+        #[arg(long, default_value = "desc", value_parser = ["asc", "desc"])]
+        sort: String,
+
+        /// YAML file of path/filename rules adjusting scores, merged with
+        /// the built-in rules. Only this report uses them; query and
+        /// assemble rank with the built-in rules
+        #[arg(long, value_name = "FILE")]
+        rules: Option<PathBuf>,
     },
 
     /// Find canonical documents with no inbound links.
@@ -884,6 +1298,11 @@ pub enum Commands {
         /// Use git rename history to suggest fixes for moved files
         #[arg(long)]
         use_git_history: bool,
+
+        /// When several files share the link's filename, pick the most
+        /// canonical one (ties go to the shortest path) instead of skipping
+        #[arg(long)]
+        prefer_canonical: bool,
     },
 
     /// Rewrite references according to an explicit mapping file.
@@ -893,7 +1312,8 @@ pub enum Commands {
     ///
     /// Limitations:
     ///   - Does not move files; only rewrites references.
-    ///   - Requires a mapping file that lists exact from/to pairs.
+    ///   - Requires a mapping file of from/to pairs; each pair matches
+    ///     literally by default, or by `kind: prefix` / `kind: regex`.
     ///
     /// Related:
     ///   - `yore mv`, `yore fix-links`
@@ -965,12 +1385,15 @@ pub enum Commands {
 
     /// Report potentially stale documentation based on age and inbound links.
     ///
-    /// Uses file modification time and inbound link counts from the index
-    /// to highlight documents that may be unmaintained or dead.
+    /// Uses file modification time (or the last git commit with --use-git)
+    /// and inbound link counts from the index to highlight documents that
+    /// may be unmaintained or dead. --require combines the age, inlinks,
+    /// and orphaned conditions; each reported file lists the ones it met.
     ///
     /// Limitations:
     ///   - Staleness is heuristic; validate before deleting.
-    ///   - Depends on file mtime and inbound links only.
+    ///   - Depends on file age and inbound links only.
+    ///   - mtime resets on fresh clones; prefer --use-git in CI.
     ///
     /// Related:
     ///   - `yore orphans`, `yore canonicality`
@@ -978,6 +1401,9 @@ pub enum Commands {
     /// Examples:
     ///   yore stale --index .yore --days 90 --min-inlinks 0 --json
     ///   yore stale --index .yore --days 30 --min-inlinks 1
+    ///   yore stale --index .yore --days 180 --use-git
+    ///   yore stale --index .yore --require "age OR orphaned"
+    ///   yore stale --index .yore --max-inlinks 1 --json
     Stale {
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
@@ -987,22 +1413,43 @@ pub enum Commands {
         #[arg(long, default_value = "90")]
         days: u64,
 
-        /// Minimum inbound link count (files with >= this many links are included)
+        /// Lower bound of the `inlinks` condition: files need at least this
+        /// many inbound links to meet it
         #[arg(long, default_value = "0")]
         min_inlinks: usize,
 
+        /// Upper bound of the `inlinks` condition, to target low-connectivity docs
+        #[arg(long)]
+        max_inlinks: Option<usize>,
+
+        /// Conditions a file must meet, joined by AND or OR:
+        /// age (older than --days), inlinks (within --min/--max-inlinks),
+        /// orphaned (no inbound links)
+        #[arg(long, value_name = "EXPR", default_value = "age AND inlinks")]
+        require: String,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
+
+        /// Output as CSV (cannot be combined with --json)
+        #[arg(long, conflicts_with = "json")]
+        csv: bool,
+
+        /// Use each file's last git commit date instead of mtime
+        /// (untracked files fall back to mtime)
+        #[arg(long)]
+        use_git: bool,
     },
 
     /// Export the documentation link graph.
     ///
-    /// Emits either a JSON representation or a Graphviz DOT file
-    /// describing links between indexed documents.
+    /// Emits a JSON representation, a Graphviz DOT file, or a fenced
+    /// Mermaid diagram describing links between indexed documents.
     ///
     /// Limitations:
-    ///   - Graph only includes indexed documents and internal links.
+    ///   - Graph only includes indexed documents and internal links,
+    ///     unless `--include-external` adds one node per external host.
     ///
     /// Related:
     ///   - `yore backlinks`, `yore check-links`
@@ -1010,11 +1457,50 @@ pub enum Commands {
     /// Examples:
     ///   yore export-graph --format json --index .yore
     ///   yore export-graph --format dot --index .yore > graph.dot
+    ///   yore export-graph --format dot --include-external --index .yore
+    ///   yore export-graph --format mermaid --index .yore >> docs/MAP.md
     ExportGraph {
-        /// Output format: "json" or "dot"
+        /// Output format: "json", "dot", or "mermaid"
         #[arg(long, default_value = "json")]
         format: String,
 
+        /// Add a node per external (http/https) host and an edge for each
+        /// link out to it
+        #[arg(long)]
+        include_external: bool,
+
+        /// Index directory
+        #[arg(short, long, default_value = ".yore")]
+        index: PathBuf,
+    },
+
+    /// Show the shortest link chain between two documents.
+    ///
+    /// Runs a breadth-first search over the same link graph as
+    /// `export-graph` and prints the documents connecting FROM to TO.
+    ///
+    /// Related:
+    ///   - `yore backlinks`, `yore paths`, `yore export-graph`
+    ///
+    /// Examples:
+    ///   yore path docs/README.md docs/api-reference.md --index .yore
+    ///   yore path docs/adr/ADR-001.md docs/README.md --undirected --index .yore
+    ///   yore path docs/README.md docs/guides/deployment.md --json --index .yore
+    Path {
+        /// Document to start from
+        from: String,
+
+        /// Document to reach
+        to: String,
+
+        /// Follow links in both directions
+        #[arg(long)]
+        undirected: bool,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+
         /// Index directory
         #[arg(short, long, default_value = ".yore")]
         index: PathBuf,
@@ -1056,8 +1542,13 @@ pub enum Commands {
     /// Uses duplicate detection and canonicality scoring to propose a
     /// canonical document and a set of files that should be merged into it.
     ///
+    /// With `--plan`, each group also lists the sections of every merge
+    /// candidate that the canonical doc lacks (carry over) and those it
+    /// already covers, compared by section SimHash.
+    ///
     /// Limitations:
     ///   - Suggestions are heuristic; review before merging or deleting.
+    ///   - Heavily reworded sections may be listed as unique.
     ///
     /// Related:
     ///   - `yore dupes`, `yore canonicality`, `yore diff`
@@ -1065,11 +1556,17 @@ pub enum Commands {
     /// Examples:
     ///   yore suggest-consolidation --threshold 0.7 --json --index .yore
     ///   yore suggest-consolidation --threshold 0.6 --index .yore
+    ///   yore suggest-consolidation --plan --index .yore
     SuggestConsolidation {
         /// Minimum duplicate similarity threshold (0.0 to 1.0)
         #[arg(long, default_value = "0.7")]
         threshold: f64,
 
+        /// Compare sections against the canonical doc and list what each
+        /// merge would need to carry over
+        #[arg(long)]
+        plan: bool,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
@@ -1088,16 +1585,20 @@ pub enum Commands {
     /// Required links treat absolute paths as repo-root relative, and
     /// resolve relative paths against the source file.
     ///
+    /// Exits with status 1 when any violation has severity "error" (or
+    /// "warn" with --fail-on warn), so it can gate CI directly.
+    ///
     /// Limitations:
     ///   - Rules operate on indexed content; run `yore build` first.
-    ///   - Content checks are literal substring matches.
     ///
     /// Related:
     ///   - `yore check --taxonomy`, `yore check-links`
     ///
     /// Examples:
     ///   yore policy --config .yore-policy.yaml --index .yore --json
-    ///   yore policy --config .yore-policy.yaml --index .yore
+    ///   yore policy --config .yore-policy.yaml --index .yore --fail-on warn
+    ///   yore policy --config .yore-policy.yaml --index .yore --no-fail
+    ///   yore policy --config .yore-policy.yaml --index .yore --explain
     Policy {
         /// Path to policy configuration (YAML)
         #[arg(long, default_value = ".yore-policy.yaml")]
@@ -1110,6 +1611,19 @@ pub enum Commands {
         /// Output as JSON
         #[arg(long)]
         json: bool,
+
+        /// Lowest violation severity that fails the run: "error" or "warn"
+        #[arg(long, default_value = "error")]
+        fail_on: String,
+
+        /// Always exit 0, regardless of violations
+        #[arg(long)]
+        no_fail: bool,
+
+        /// List each rule's matched files and which checks each failed,
+        /// even when there are no violations
+        #[arg(long)]
+        explain: bool,
     },
 }
 
@@ -1175,3 +1689,20 @@ pub enum McpCommands {
         index: PathBuf,
     },
 }
+
+/// `--w-*` overrides of the similarity blend, shared by `similar`, `dupes`,
+/// and `diff`.
+#[derive(Args)]
+pub struct SimilarityWeightArgs {
+    /// Weight of keyword Jaccard similarity (weights are normalized to sum to 1)
+    #[arg(long)]
+    pub w_jaccard: Option<f64>,
+
+    /// Weight of SimHash similarity
+    #[arg(long)]
+    pub w_simhash: Option<f64>,
+
+    /// Weight of MinHash similarity
+    #[arg(long)]
+    pub w_minhash: Option<f64>,
+}
@@ -1,3 +1,4 @@
+use crate::commands_graph::*;
 use crate::commands_query::*;
 use colored::Colorize;
 use std::collections::{HashMap, HashSet};
@@ -7,76 +8,61 @@ use crate::assemble::*;
 use crate::types::*;
 use crate::util::*;
 
+/// List orphaned documents.
+///
+/// By default a file is orphaned when nothing links to it. With
+/// `reachable_from`, a file is orphaned when it cannot be reached by
+/// following links from any of the given roots; an empty list uses every
+/// README/index document as a root.
 pub(crate) fn cmd_orphans(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
+    csv: bool,
     exclude_patterns: &[String],
+    reachable_from: Option<&[String]>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Load the forward index
     let forward_index = load_forward_index(index_dir)?;
 
-    if !json {
-        println!("{}", "Finding orphaned files...".cyan().bold());
+    let roots = match reachable_from {
+        Some(requested) => Some(resolve_reachability_roots(&forward_index, requested)?),
+        None => None,
+    };
+
+    if !json && !csv {
+        match &roots {
+            Some(roots) => println!(
+                "{} {}",
+                "Finding files unreachable from".cyan().bold(),
+                roots.join(", ").white().bold()
+            ),
+            None => println!("{}", "Finding orphaned files...".cyan().bold()),
+        }
         println!();
     }
 
-    // Build a set of all files that are linked to
-    let mut linked_files: HashSet<String> = HashSet::new();
+    let reachable = roots
+        .as_ref()
+        .map(|roots| find_reachable_documents(&build_link_graph_edges(&forward_index), roots));
 
-    for (source_path, entry) in &forward_index.files {
-        for link in &entry.links {
-            let target = &link.target;
-
-            // Skip external links
-            if target.starts_with("http://")
-                || target.starts_with("https://")
-                || target.starts_with("mailto:")
-                || target.starts_with("ftp://")
-            {
-                continue;
-            }
-
-            // Parse link to separate file path and anchor
-            let (link_path, _) = if let Some(idx) = target.find('#') {
-                (
-                    target[..idx].to_string(),
-                    Some(target[idx + 1..].to_string()),
-                )
-            } else {
-                (target.clone(), None)
-            };
-
-            // Skip anchor-only links
-            if link_path.is_empty() {
-                continue;
-            }
-
-            // Resolve relative path from source file
-            let resolved_path = if let Some(stripped) = link_path.strip_prefix('/') {
-                // Absolute path - strip leading / and use as-is
-                stripped.to_string()
-            } else {
-                // Relative path
-                let source_file_path = Path::new(source_path);
-                if let Some(parent) = source_file_path.parent() {
-                    parent.join(&link_path).to_string_lossy().to_string()
-                } else {
-                    link_path.clone()
-                }
-            };
-
-            // Normalize the resolved path
-            let normalized_link = normalize_path(Path::new(&resolved_path));
-            linked_files.insert(normalized_link);
-        }
-    }
+    // Build a set of all files that are linked to from elsewhere
+    let linked_files: HashSet<String> = build_link_graph(&forward_index)
+        .edges
+        .into_iter()
+        .filter(|edge| !edge.is_self_link())
+        .map(|edge| edge.target)
+        .collect();
 
     // Find files that are NOT in the linked set
     let mut orphans = Vec::new();
 
     for (file_path, entry) in &forward_index.files {
-        // Check if this file has any inbound links
-        if !linked_files.contains(file_path) {
+        // Check if this file has any inbound links (or, with roots, is reachable)
+        let is_orphan = match &reachable {
+            Some(reachable) => !reachable.contains(file_path),
+            None => !linked_files.contains(file_path),
+        };
+        if is_orphan {
             // Check exclude patterns
             let mut excluded = false;
             for pattern in exclude_patterns {
@@ -104,10 +90,20 @@ pub(crate) fn cmd_orphans(
     let result = OrphansResult {
         total_orphans: orphans.len(),
         orphans: orphans.clone(),
+        reachable_from: roots,
     };
 
     if json {
         println!("{}", serde_json::to_string_pretty(&result)?);
+    } else if csv {
+        print_csv_row(&["file", "size_bytes", "line_count"]);
+        for orphan in &orphans {
+            print_csv_row(&[
+                orphan.file.clone(),
+                orphan.size_bytes.to_string(),
+                orphan.line_count.to_string(),
+            ]);
+        }
     } else {
         println!("{}", "Orphaned Files".cyan().bold());
         println!("{}", "=".repeat(60));
@@ -145,6 +141,39 @@ pub(crate) fn cmd_orphans(
     Ok(())
 }
 
+/// Resolve `--reachable-from` roots to index keys, defaulting to every
+/// README/index document when none are given.
+fn resolve_reachability_roots(
+    forward_index: &ForwardIndex,
+    requested: &[String],
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let requested: Vec<&String> = requested.iter().filter(|r| !r.is_empty()).collect();
+    if requested.is_empty() {
+        let roots = default_reachability_roots(forward_index);
+        if roots.is_empty() {
+            return Err(
+                "No README or index documents found to start from; pass --reachable-from <file>"
+                    .into(),
+            );
+        }
+        return Ok(roots);
+    }
+
+    let norm_to_key = normalized_file_keys(forward_index);
+    let mut roots = requested
+        .into_iter()
+        .map(|root| {
+            norm_to_key
+                .get(&normalize_path(Path::new(root)))
+                .cloned()
+                .ok_or_else(|| format!("'{root}' is not in the index").into())
+        })
+        .collect::<Result<Vec<String>, Box<dyn std::error::Error>>>()?;
+    roots.sort();
+    roots.dedup();
+    Ok(roots)
+}
+
 pub(crate) fn build_inbound_link_counts(forward_index: &ForwardIndex) -> HashMap<String, usize> {
     let mut inbound_counts: HashMap<String, usize> = HashMap::new();
 
@@ -193,7 +222,7 @@ pub(crate) fn build_inbound_link_counts(forward_index: &ForwardIndex) -> HashMap
 }
 
 pub(crate) fn cmd_canonical_orphans(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     threshold: f64,
     json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -263,51 +292,52 @@ pub(crate) fn cmd_canonical_orphans(
     Ok(())
 }
 
+/// Maximum canonicality boost from link authority, awarded to the document
+/// with the highest normalized PageRank.
+const LINK_AUTHORITY_WEIGHT: f64 = 0.2;
+
 /// Score canonicality with reasons
+///
+/// `authority` is the document's normalized PageRank from
+/// `compute_link_authority`; pass `None` for path/filename heuristics only.
+/// `rules` are the path and filename adjustments to apply, usually
+/// `builtin_canonicality_rules()`.
 pub(crate) fn score_canonicality_with_reasons(
     doc_path: &str,
     _entry: &FileEntry,
+    authority: Option<f64>,
+    rules: &[CanonicalityRule],
 ) -> (f64, Vec<String>) {
     let mut score: f64 = 0.5; // baseline
     let mut reasons = Vec::new();
 
     let path_lower = doc_path.to_lowercase();
-
-    // Path-based boosts
-    if path_lower.contains("docs/adr/") || path_lower.contains("docs/architecture/") {
-        score += 0.2;
-        reasons.push("Architecture/ADR document (+0.2)".to_string());
-    }
-    if path_lower.contains("docs/index/") {
-        score += 0.15;
-        reasons.push("Index document (+0.15)".to_string());
-    }
-    if path_lower.contains("scratch")
-        || path_lower.contains("archive")
-        || path_lower.contains("old")
-    {
-        score -= 0.3;
-        reasons.push("Scratch/archive/old location (-0.3)".to_string());
-    }
-    if path_lower.contains("deprecated") || path_lower.contains("backup") {
-        score -= 0.25;
-        reasons.push("Deprecated/backup location (-0.25)".to_string());
-    }
-
-    // Filename patterns
     let filename = Path::new(doc_path)
         .file_name()
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_lowercase();
 
-    if filename.contains("readme") || filename.contains("index") {
-        score += 0.1;
-        reasons.push("README/INDEX file (+0.1)".to_string());
+    // Path and filename rules
+    for rule in rules {
+        if rule.delta != 0.0 && rule.matches(&path_lower, &filename) {
+            score += rule.delta;
+            reasons.push(rule.reason());
+        }
     }
-    if filename.contains("guide") || filename.contains("runbook") || filename.contains("plan") {
-        score += 0.1;
-        reasons.push("Guide/runbook/plan document (+0.1)".to_string());
+
+    // Link-graph authority
+    if let Some(authority) = authority {
+        let boost = (authority * LINK_AUTHORITY_WEIGHT * 100.0).round() / 100.0;
+        if boost > 0.0 {
+            score += boost;
+            let label = if authority >= 0.5 {
+                "High inbound authority"
+            } else {
+                "Inbound authority"
+            };
+            reasons.push(format!("{label} (+{boost:.2})"));
+        }
     }
 
     // Clamp to [0.0, 1.0]
@@ -321,13 +351,30 @@ pub(crate) fn score_canonicality_with_reasons(
 }
 
 /// Show canonicality scores for all documents
+///
+/// `limit` caps the files printed per score band (`Some(0)` prints all,
+/// `None` keeps the 10/5/5 defaults); `ascending` lists the least canonical
+/// files first. JSON output always carries every file. `rules_path` is an
+/// optional `--rules` YAML file merged with the built-in path rules; it
+/// only changes this report, not the canonicality used for ranking.
 pub(crate) fn cmd_canonicality(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
     threshold: f64,
+    use_graph: bool,
+    limit: Option<usize>,
+    ascending: bool,
+    rules_path: Option<&Path>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let rules = load_canonicality_rules(rules_path)?;
+
     // Load the forward index
     let forward_index = load_forward_index(index_dir)?;
+    let authority = if use_graph {
+        compute_link_authority(&forward_index)
+    } else {
+        HashMap::new()
+    };
 
     if !json {
         println!("{}", "Computing canonicality scores...".cyan().bold());
@@ -337,7 +384,12 @@ pub(crate) fn cmd_canonicality(
     let mut scored_files = Vec::new();
 
     for (file_path, entry) in &forward_index.files {
-        let (score, reasons) = score_canonicality_with_reasons(file_path, entry);
+        let (score, reasons) = score_canonicality_with_reasons(
+            file_path,
+            entry,
+            authority.get(file_path).copied(),
+            &rules,
+        );
 
         if score >= threshold {
             scored_files.push(CanonicalityScore {
@@ -348,11 +400,18 @@ pub(crate) fn cmd_canonicality(
         }
     }
 
-    // Sort by score descending
+    // Sort by score (descending unless --sort asc), then path
     scored_files.sort_by(|a, b| {
-        b.score
+        let by_score = b
+            .score
             .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
+            .unwrap_or(std::cmp::Ordering::Equal);
+        let by_score = if ascending {
+            by_score.reverse()
+        } else {
+            by_score
+        };
+        by_score.then_with(|| a.file.cmp(&b.file))
     });
 
     let result = CanonicalityResult {
@@ -381,50 +440,76 @@ pub(crate) fn cmd_canonicality(
             .collect();
         let low_canon: Vec<_> = scored_files.iter().filter(|s| s.score < 0.5).collect();
 
-        println!(
-            "{} High canonicality (≥0.7): {} files",
-            "📚".green(),
-            high_canon.len()
-        );
-        for file in high_canon.iter().take(10) {
-            println!("  [{:.2}] {}", file.score, file.file.white().bold());
-            for reason in &file.reasons {
-                println!("         - {reason}");
-            }
-        }
-        if high_canon.len() > 10 {
-            println!("  ... and {} more", high_canon.len() - 10);
-        }
-        println!();
-
-        println!(
-            "{} Medium canonicality (0.5-0.7): {} files",
-            "📄".yellow(),
-            medium_canon.len()
-        );
-        for file in medium_canon.iter().take(5) {
-            println!("  [{:.2}] {}", file.score, file.file);
+        struct Band<'a> {
+            heading: String,
+            files: Vec<&'a CanonicalityScore>,
+            default_limit: usize,
+            bold: bool,
+            reasons: bool,
         }
-        if medium_canon.len() > 5 {
-            println!("  ... and {} more", medium_canon.len() - 5);
+        let mut bands = [
+            Band {
+                heading: format!(
+                    "{} High canonicality (≥0.7): {} files",
+                    "📚".green(),
+                    high_canon.len()
+                ),
+                files: high_canon,
+                default_limit: 10,
+                bold: true,
+                reasons: true,
+            },
+            Band {
+                heading: format!(
+                    "{} Medium canonicality (0.5-0.7): {} files",
+                    "📄".yellow(),
+                    medium_canon.len()
+                ),
+                files: medium_canon,
+                default_limit: 5,
+                bold: false,
+                reasons: false,
+            },
+            Band {
+                heading: format!(
+                    "{} Low canonicality (<0.5): {} files",
+                    "📋".red(),
+                    low_canon.len()
+                ),
+                files: low_canon,
+                default_limit: 5,
+                bold: false,
+                reasons: true,
+            },
+        ];
+        if ascending {
+            bands.reverse();
         }
-        println!();
 
-        println!(
-            "{} Low canonicality (<0.5): {} files",
-            "📋".red(),
-            low_canon.len()
-        );
-        for file in low_canon.iter().take(5) {
-            println!("  [{:.2}] {}", file.score, file.file);
-            for reason in &file.reasons {
-                println!("         - {reason}");
+        for band in &bands {
+            println!("{}", band.heading);
+            let shown = match limit {
+                Some(0) => band.files.len(),
+                Some(n) => n,
+                None => band.default_limit,
+            };
+            for file in band.files.iter().take(shown) {
+                if band.bold {
+                    println!("  [{:.2}] {}", file.score, file.file.white().bold());
+                } else {
+                    println!("  [{:.2}] {}", file.score, file.file);
+                }
+                if band.reasons {
+                    for reason in &file.reasons {
+                        println!("         - {reason}");
+                    }
+                }
             }
+            if band.files.len() > shown {
+                println!("  ... and {} more", band.files.len() - shown);
+            }
+            println!();
         }
-        if low_canon.len() > 5 {
-            println!("  ... and {} more", low_canon.len() - 5);
-        }
-        println!();
 
         println!("{}", "What does this mean?".yellow().bold());
         println!("  - High scores: Authoritative, well-placed documents");
@@ -441,13 +526,14 @@ pub(crate) fn cmd_canonicality(
 }
 
 pub(crate) fn cmd_suggest_consolidation(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     threshold: f64,
+    plan: bool,
     json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
 
-    let pairs = compute_duplicate_pairs(&forward_index, threshold);
+    let pairs = compute_duplicate_pairs(&forward_index, threshold, SimilarityWeights::DUPES);
     if pairs.is_empty() {
         println!(
             "{} No consolidation candidates found above threshold {}.",
@@ -457,7 +543,12 @@ pub(crate) fn cmd_suggest_consolidation(
         return Ok(());
     }
 
-    let result = build_consolidation_groups(&forward_index, &pairs);
+    let mut result = build_consolidation_groups(&forward_index, &pairs);
+    if plan {
+        for group in &mut result.groups {
+            build_merge_plan(&forward_index, group, MERGE_PLAN_SECTION_THRESHOLD);
+        }
+    }
 
     if json {
         println!("{}", serde_json::to_string_pretty(&result)?);
@@ -491,6 +582,33 @@ pub(crate) fn cmd_suggest_consolidation(
             println!("    - {m}");
         }
         println!("  Note: {}", group.note);
+        if let (Some(unique), Some(duplicated)) =
+            (&group.unique_sections, &group.duplicated_sections)
+        {
+            println!("  Carry over ({}):", unique.len());
+            for section in unique {
+                println!(
+                    "    [ ] {}:{}-{} {}",
+                    section.file, section.lines[0], section.lines[1], section.heading
+                );
+            }
+            println!("  Already in canonical ({}):", duplicated.len());
+            for section in duplicated {
+                println!(
+                    "    {}:{}-{} {} {}",
+                    section.file,
+                    section.lines[0],
+                    section.lines[1],
+                    section.heading,
+                    format!(
+                        "(matches \"{}\", {:.2})",
+                        section.closest_canonical.as_deref().unwrap_or(""),
+                        section.similarity
+                    )
+                    .dimmed()
+                );
+            }
+        }
         println!();
     }
 
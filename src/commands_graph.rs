@@ -1,18 +1,20 @@
+use crate::assemble::score_canonicality;
 use crate::commands_links::*;
 use crate::commands_query::*;
 use colored::Colorize;
 use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 
+use crate::index::extract_links;
 use crate::types::*;
 use crate::util::*;
 
 pub(crate) fn cmd_mv(
     from: &Path,
     to: &Path,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     update_refs: bool,
     dry_run: bool,
     json: bool,
@@ -21,6 +23,8 @@ pub(crate) fn cmd_mv(
     let to_str = to.to_string_lossy().to_string();
 
     let mut updated_files: Vec<String> = Vec::new();
+    let mut updated_links: Vec<LinkFix> = Vec::new();
+    let mut outbound_links: Vec<LinkFix> = Vec::new();
 
     if !dry_run {
         if let Some(parent) = to.parent() {
@@ -31,27 +35,46 @@ pub(crate) fn cmd_mv(
 
     if update_refs {
         let forward_index = load_forward_index(index_dir)?;
-
-        // Group by file for rewrites
-        let mut files_to_update: HashSet<String> = HashSet::new();
-        for (file_path, entry) in &forward_index.files {
-            for link in &entry.links {
-                if link.target == from_str {
-                    files_to_update.insert(file_path.clone());
-                }
-            }
-        }
-
-        for file in &files_to_update {
+        let mut rewrites = plan_inbound_link_rewrites(&forward_index, &from_str, &to_str);
+
+        // Self-links inside the moved file are handled with its outbound links
+        let from_norm = normalize_path(from);
+        let self_key = rewrites
+            .keys()
+            .find(|file| normalize_path(Path::new(file)) == from_norm)
+            .cloned();
+        let self_links = self_key
+            .as_ref()
+            .and_then(|key| rewrites.remove(key))
+            .unwrap_or_default();
+
+        for (file, file_rewrites) in &rewrites {
             let content = fs::read_to_string(file)?;
-            let new_content = apply_reference_mapping_to_content(&content, &from_str, &to_str);
+            let new_content = apply_link_rewrites_to_content(&content, file_rewrites);
             if content != new_content {
                 if !dry_run {
                     fs::write(file, &new_content)?;
                 }
                 updated_files.push(file.clone());
+                updated_links.extend(file_rewrites.iter().cloned());
             }
         }
+
+        // Non-text files have no links to rewrite
+        let moved_on_disk = if dry_run { from } else { to };
+        if let Ok(content) = fs::read_to_string(moved_on_disk) {
+            outbound_links = plan_outbound_link_rewrites(&content, &from_str, &to_str);
+            let moved_rewrites: Vec<LinkFix> =
+                self_links.iter().chain(&outbound_links).cloned().collect();
+            let new_content = apply_link_rewrites_to_content(&content, &moved_rewrites);
+            if content != new_content && !dry_run {
+                fs::write(moved_on_disk, &new_content)?;
+            }
+        }
+        if let Some(key) = self_key.filter(|_| !self_links.is_empty()) {
+            updated_files.push(key);
+            updated_links.extend(self_links);
+        }
     }
 
     updated_files.sort();
@@ -62,6 +85,8 @@ pub(crate) fn cmd_mv(
             to: to_str,
             moved: !dry_run,
             updated_files,
+            updated_links,
+            outbound_links,
         };
         println!("{}", serde_json::to_string_pretty(&result)?);
         return Ok(());
@@ -99,6 +124,25 @@ pub(crate) fn cmd_mv(
                 } else {
                     println!("  {file}");
                 }
+                for fix in updated_links.iter().filter(|f| f.file == file) {
+                    println!("    {} -> {}", fix.old_target.red(), fix.new_target.green());
+                }
+            }
+        }
+
+        if !outbound_links.is_empty() {
+            println!(
+                "{} {} outbound link(s) in {}",
+                if dry_run {
+                    "Would rewrite"
+                } else {
+                    "Rewriting"
+                },
+                outbound_links.len(),
+                to_str
+            );
+            for fix in &outbound_links {
+                println!("    {} -> {}", fix.old_target.red(), fix.new_target.green());
             }
         }
     }
@@ -106,47 +150,268 @@ pub(crate) fn cmd_mv(
     Ok(())
 }
 
-pub(crate) fn compute_inbound_link_counts(forward_index: &ForwardIndex) -> HashMap<String, usize> {
-    let mut counts: HashMap<String, usize> = HashMap::new();
+/// Plan rewrites for every indexed link that resolves to `from`, grouped by
+/// source file.
+///
+/// Links are matched on their normalized resolved path, so `../old/auth.md`,
+/// `auth.md`, and `./auth.md#setup` all count when they point at the moved
+/// file. Each rewrite targets the correct relative path to `to` from the
+/// linking file (or from `to` itself for self-links), keeping any anchor.
+pub(crate) fn plan_inbound_link_rewrites(
+    forward_index: &ForwardIndex,
+    from: &str,
+    to: &str,
+) -> BTreeMap<String, Vec<LinkFix>> {
+    let from_norm = normalize_path(Path::new(from));
+    let to_norm = normalize_path(Path::new(to));
+    let no_files = HashSet::new();
+    let mut rewrites: BTreeMap<String, Vec<LinkFix>> = BTreeMap::new();
+
+    for (file_path, entry) in &forward_index.files {
+        let source_after = if normalize_path(Path::new(file_path)) == from_norm {
+            to_norm.as_str()
+        } else {
+            file_path.as_str()
+        };
 
-    for (source_path, entry) in &forward_index.files {
-        let source_base = Path::new(source_path);
         for link in &entry.links {
-            let target = &link.target;
-            if target.starts_with("http://")
-                || target.starts_with("https://")
-                || target.starts_with("mailto:")
-                || target.starts_with("ftp://")
+            if resolve_internal_link_target(file_path, &link.target).as_deref()
+                != Some(from_norm.as_str())
             {
                 continue;
             }
 
-            let (link_path, _) = if let Some(idx) = target.find('#') {
-                (
-                    target[..idx].to_string(),
-                    Some(target[idx + 1..].to_string()),
-                )
-            } else {
-                (target.clone(), None)
-            };
-
-            if link_path.is_empty() {
+            let mut new_target = relative_link_target(source_after, &to_norm, &no_files);
+            if let Some(idx) = link.target.find('#') {
+                new_target.push_str(&link.target[idx..]);
+            }
+            if new_target == link.target {
                 continue;
             }
 
-            let resolved = if let Some(parent) = source_base.parent() {
-                parent.join(&link_path).to_string_lossy().to_string()
+            let file_rewrites = rewrites.entry(file_path.clone()).or_default();
+            if !file_rewrites.iter().any(|f| f.old_target == link.target) {
+                file_rewrites.push(LinkFix {
+                    file: file_path.clone(),
+                    old_target: link.target.clone(),
+                    new_target,
+                    resolution: None,
+                });
+            }
+        }
+    }
+
+    rewrites
+}
+
+/// Plan rewrites for the moved file's own relative links (images included)
+/// so they resolve to the same targets from its new directory.
+///
+/// External links, anchor-only links, root-absolute paths, wiki links, and
+/// links back to the moved file itself (rewritten as inbound self-links) are
+/// left alone.
+pub(crate) fn plan_outbound_link_rewrites(content: &str, from: &str, to: &str) -> Vec<LinkFix> {
+    let from_norm = normalize_path(Path::new(from));
+    let to_norm = normalize_path(Path::new(to));
+    let lines: Vec<&str> = content.lines().collect();
+    let no_files = HashSet::new();
+    let mut rewrites: Vec<LinkFix> = Vec::new();
+
+    for link in extract_links(&lines) {
+        let has_scheme = link
+            .target
+            .split(['/', '#'])
+            .next()
+            .is_some_and(|first| first.contains(':'));
+        if link.wiki.is_some() || link.target.starts_with('/') || has_scheme {
+            continue;
+        }
+        let Some(resolved) = resolve_internal_link_target(&from_norm, &link.target) else {
+            continue;
+        };
+        if resolved == from_norm {
+            continue;
+        }
+
+        let mut new_target = relative_link_target(&to_norm, &resolved, &no_files);
+        if let Some(idx) = link.target.find('#') {
+            new_target.push_str(&link.target[idx..]);
+        }
+        if new_target == link.target || rewrites.iter().any(|f| f.old_target == link.target) {
+            continue;
+        }
+        rewrites.push(LinkFix {
+            file: to_norm.clone(),
+            old_target: link.target,
+            new_target,
+            resolution: None,
+        });
+    }
+
+    rewrites
+}
+
+/// Resolve a markdown link target relative to its source document.
+///
+/// Returns `None` for external URLs and pure in-page anchors.
+pub(crate) fn resolve_internal_link_target(source_path: &str, target: &str) -> Option<String> {
+    if target.starts_with("http://")
+        || target.starts_with("https://")
+        || target.starts_with("mailto:")
+        || target.starts_with("ftp://")
+    {
+        return None;
+    }
+
+    let link_path = target.find('#').map_or(target, |idx| &target[..idx]);
+    if link_path.is_empty() {
+        return None;
+    }
+
+    let resolved = if let Some(parent) = Path::new(source_path).parent() {
+        parent.join(link_path).to_string_lossy().to_string()
+    } else {
+        link_path.to_string()
+    };
+    Some(normalize_path(Path::new(&resolved)))
+}
+
+/// Resolve every internal link in the index.
+///
+/// External URLs are dropped. Root-absolute targets (`/docs/a.md`) resolve
+/// from the source root, others from the linking document's directory, and
+/// anchor-only targets (`#setup`) resolve to the linking document itself.
+/// Commands that count or follow links share this graph so they agree on
+/// what a link points at.
+pub(crate) fn build_link_graph(forward_index: &ForwardIndex) -> LinkGraph {
+    let mut sources: Vec<&String> = forward_index.files.keys().collect();
+    sources.sort();
+
+    let mut edges = Vec::new();
+    for source_path in sources {
+        for link in &forward_index.files[source_path].links {
+            let (link_path, anchor) = match link.target.split_once('#') {
+                Some((path, anchor)) => (path, Some(anchor.to_string())),
+                None => (link.target.as_str(), None),
+            };
+            let target = if link_path.is_empty() {
+                normalize_path(Path::new(source_path))
             } else {
-                link_path.clone()
+                match resolve_internal_link_target(source_path, &link.target) {
+                    Some(target) => target,
+                    None => continue,
+                }
             };
-            let normalized = normalize_path(Path::new(&resolved));
-            *counts.entry(normalized).or_insert(0) += 1;
+            edges.push(LinkGraphEdge {
+                source: source_path.clone(),
+                target,
+                anchor,
+                text: link.text.clone(),
+                raw_target: link.target.clone(),
+            });
+        }
+    }
+
+    LinkGraph { edges }
+}
+
+/// Count links into each normalized path. Self-links don't count.
+pub(crate) fn compute_inbound_link_counts(forward_index: &ForwardIndex) -> HashMap<String, usize> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for edge in build_link_graph(forward_index).edges {
+        if !edge.is_self_link() {
+            *counts.entry(edge.target).or_insert(0) += 1;
         }
     }
 
     counts
 }
 
+/// Damping factor for link authority: the probability that a random reader
+/// follows a link rather than jumping to an arbitrary document. 0.85 is the
+/// value from the original PageRank paper.
+const PAGERANK_DAMPING: f64 = 0.85;
+
+/// Power iterations for link authority. Documentation graphs are small and
+/// shallow, so 20 rounds converge well below the two-decimal precision that
+/// canonicality reports.
+const PAGERANK_ITERATIONS: usize = 20;
+
+/// Compute PageRank over the indexed link graph, min-max normalized to
+/// `[0.0, 1.0]`.
+///
+/// Edges come from the shared link graph (`build_link_graph_edges`), so they
+/// resolve exactly as they do for backlinks and export-graph. Duplicate links
+/// and self-links are ignored. Documents without inbound links score 0.0,
+/// and every document scores 0.0 when the graph has no edges.
+pub(crate) fn compute_link_authority(forward_index: &ForwardIndex) -> HashMap<String, f64> {
+    let mut nodes: Vec<&String> = forward_index.files.keys().collect();
+    nodes.sort();
+    let n = nodes.len();
+    if n == 0 {
+        return HashMap::new();
+    }
+    let position: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (path.as_str(), i))
+        .collect();
+
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for edge in build_link_graph_edges(forward_index) {
+        let (Some(&i), Some(&j)) = (
+            position.get(edge.source.as_str()),
+            position.get(edge.target.as_str()),
+        ) else {
+            continue;
+        };
+        outgoing[i].push(j);
+    }
+    for targets in &mut outgoing {
+        targets.sort_unstable();
+        targets.dedup();
+    }
+
+    let n_f = n as f64;
+    let mut rank = vec![1.0 / n_f; n];
+    for _ in 0..PAGERANK_ITERATIONS {
+        // Dangling documents spread their rank evenly over the whole graph.
+        let dangling: f64 = (0..n)
+            .filter(|&i| outgoing[i].is_empty())
+            .map(|i| rank[i])
+            .sum();
+        let base = (1.0 - PAGERANK_DAMPING) / n_f + PAGERANK_DAMPING * dangling / n_f;
+        let mut next = vec![base; n];
+        for (i, targets) in outgoing.iter().enumerate() {
+            if targets.is_empty() {
+                continue;
+            }
+            let share = PAGERANK_DAMPING * rank[i] / targets.len() as f64;
+            for &j in targets {
+                next[j] += share;
+            }
+        }
+        rank = next;
+    }
+
+    let min = rank.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = rank.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let spread = max - min;
+    nodes
+        .into_iter()
+        .zip(rank)
+        .map(|(path, r)| {
+            let normalized = if spread > 1e-12 {
+                (r - min) / spread
+            } else {
+                0.0
+            };
+            (path.clone(), normalized)
+        })
+        .collect()
+}
+
 /// Show relation paths from a source document via the persisted relation graph.
 pub(crate) fn cmd_paths(
     source: &str,
@@ -300,13 +565,8 @@ pub(crate) fn cmd_paths(
     Ok(())
 }
 
-pub(crate) fn cmd_export_graph(
-    index_dir: &Path,
-    format: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let forward_index = load_forward_index(index_dir)?;
-
-    // Map normalized paths to canonical file keys
+/// Map normalized paths to their forward index keys.
+pub(crate) fn normalized_file_keys(forward_index: &ForwardIndex) -> HashMap<String, String> {
     let mut norm_to_key: HashMap<String, String> = HashMap::new();
     for path in forward_index.files.keys() {
         let normalized = normalize_path(Path::new(path));
@@ -314,66 +574,127 @@ pub(crate) fn cmd_export_graph(
             .entry(normalized)
             .or_insert_with(|| path.clone());
     }
+    norm_to_key
+}
+
+/// Build the directed link graph between indexed documents.
+///
+/// Self-links and links to unindexed files are dropped from
+/// `build_link_graph`; one edge is emitted per remaining link.
+pub(crate) fn build_link_graph_edges(forward_index: &ForwardIndex) -> Vec<GraphEdge> {
+    let norm_to_key = normalized_file_keys(forward_index);
+
+    build_link_graph(forward_index)
+        .edges
+        .into_iter()
+        .filter(|edge| !edge.is_self_link())
+        .filter_map(|edge| {
+            let target = norm_to_key.get(&edge.target)?;
+            Some(GraphEdge {
+                source: edge.source,
+                target: target.clone(),
+                anchor: edge.anchor,
+                kind: GraphKind::Internal,
+            })
+        })
+        .collect()
+}
+
+/// One node per indexed document, sorted by path, with the metadata
+/// downstream tools need to size and color nodes.
+pub(crate) fn build_graph_nodes(forward_index: &ForwardIndex) -> Vec<GraphNode> {
+    let inbound_counts = compute_inbound_link_counts(forward_index);
 
     let mut nodes: Vec<GraphNode> = forward_index
         .files
-        .keys()
-        .cloned()
-        .map(|id| GraphNode { id })
+        .iter()
+        .map(|(id, entry)| GraphNode {
+            id: id.clone(),
+            kind: GraphKind::Internal,
+            line_count: entry.line_count,
+            size_bytes: entry.size_bytes,
+            canonicality: score_canonicality(id, entry),
+            inbound_links: *inbound_counts.get(id).unwrap_or(&0),
+        })
         .collect();
     nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    nodes
+}
 
-    let mut edges: Vec<GraphEdge> = Vec::new();
+/// Host of an `http(s)` link, lowercased and without userinfo or port.
+/// Returns `None` for anything else.
+pub(crate) fn external_link_host(target: &str) -> Option<String> {
+    let rest = target
+        .strip_prefix("https://")
+        .or_else(|| target.strip_prefix("http://"))?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = authority.rsplit('@').next().unwrap_or(authority);
+    let host = host.split(':').next().unwrap_or(host).to_lowercase();
+    (!host.is_empty()).then_some(host)
+}
 
-    for (source_path, entry) in &forward_index.files {
-        let source_base = Path::new(source_path);
+/// One synthetic node per external host and one edge per external link,
+/// for `export-graph --include-external`. Node `inbound_links` counts the
+/// links pointing at the host.
+pub(crate) fn build_external_graph(
+    forward_index: &ForwardIndex,
+) -> (Vec<GraphNode>, Vec<GraphEdge>) {
+    let mut sources: Vec<&String> = forward_index.files.keys().collect();
+    sources.sort();
+
+    let mut hosts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut edges = Vec::new();
+    for source in sources {
+        for link in &forward_index.files[source].links {
+            let Some(host) = external_link_host(&link.target) else {
+                continue;
+            };
+            *hosts.entry(host.clone()).or_insert(0) += 1;
+            edges.push(GraphEdge {
+                source: source.clone(),
+                target: host,
+                anchor: None,
+                kind: GraphKind::External,
+            });
+        }
+    }
 
-        for link in &entry.links {
-            let target = &link.target;
+    let nodes = hosts
+        .into_iter()
+        .map(|(host, count)| GraphNode {
+            id: host,
+            kind: GraphKind::External,
+            line_count: 0,
+            size_bytes: 0,
+            canonicality: 0.0,
+            inbound_links: count,
+        })
+        .collect();
 
-            // Skip external links
-            if target.starts_with("http://")
-                || target.starts_with("https://")
-                || target.starts_with("mailto:")
-                || target.starts_with("ftp://")
-            {
-                continue;
-            }
+    (nodes, edges)
+}
 
-            // Split off anchor
-            let (link_path, anchor) = if let Some(idx) = target.find('#') {
-                (
-                    target[..idx].to_string(),
-                    Some(target[idx + 1..].to_string()),
-                )
-            } else {
-                (target.clone(), None)
-            };
+pub(crate) fn cmd_export_graph(
+    index_dir: &IndexDir,
+    format: &str,
+    include_external: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let forward_index = load_forward_index(index_dir)?;
 
-            if link_path.is_empty() {
-                continue;
-            }
+    let mut nodes = build_graph_nodes(&forward_index);
 
-            let resolved = if let Some(parent) = source_base.parent() {
-                parent.join(&link_path).to_string_lossy().to_string()
-            } else {
-                link_path.clone()
-            };
-            let normalized = normalize_path(Path::new(&resolved));
+    let mut edges = build_link_graph_edges(&forward_index);
 
-            if let Some(target_key) = norm_to_key.get(&normalized) {
-                edges.push(GraphEdge {
-                    source: source_path.clone(),
-                    target: target_key.clone(),
-                    anchor,
-                });
-            }
-        }
+    if include_external {
+        let (external_nodes, external_edges) = build_external_graph(&forward_index);
+        nodes.extend(external_nodes);
+        edges.extend(external_edges);
     }
 
     if edges.is_empty() {
+        let scope = if include_external { "" } else { "internal " };
         println!(
-            "{} No internal documentation links found to export.",
+            "{} No {scope}documentation links found to export.",
             "Info:".yellow()
         );
         return Ok(());
@@ -386,10 +707,26 @@ pub(crate) fn cmd_export_graph(
         }
         "dot" => {
             println!("digraph yore_docs {{");
+            for node in &nodes {
+                let id = node.id.replace('"', "\\\"");
+                if !node.kind.is_internal() {
+                    println!(
+                        "  \"{id}\" [shape=box, style=dashed, inbound_links={}];",
+                        node.inbound_links
+                    );
+                    continue;
+                }
+                println!(
+                    "  \"{id}\" [line_count={}, size_bytes={}, canonicality={:.2}, inbound_links={}];",
+                    node.line_count, node.size_bytes, node.canonicality, node.inbound_links
+                );
+            }
             for edge in &edges {
                 let src = edge.source.replace('"', "\\\"");
                 let dst = edge.target.replace('"', "\\\"");
-                if let Some(anchor) = &edge.anchor {
+                if !edge.kind.is_internal() {
+                    println!("  \"{src}\" -> \"{dst}\" [style=dashed];");
+                } else if let Some(anchor) = &edge.anchor {
                     let label = anchor.replace('"', "\\\"");
                     println!("  \"{src}\" -> \"{dst}\" [label=\"{label}\"];");
                 } else {
@@ -398,6 +735,7 @@ pub(crate) fn cmd_export_graph(
             }
             println!("}}");
         }
+        "mermaid" => print!("{}", render_mermaid_graph(&edges)),
         other => {
             return Err(format!("Unsupported format: {other}").into());
         }
@@ -406,15 +744,258 @@ pub(crate) fn cmd_export_graph(
     Ok(())
 }
 
+/// Find the shortest chain of documents linking `from` to `to`.
+///
+/// Breadth-first search over `edges`; with `undirected`, links are also
+/// followed from target back to source. Neighbours are visited in sorted
+/// order so ties resolve deterministically.
+pub(crate) fn find_shortest_link_path(
+    edges: &[GraphEdge],
+    from: &str,
+    to: &str,
+    undirected: bool,
+) -> Option<Vec<String>> {
+    if from == to {
+        return Some(vec![from.to_string()]);
+    }
+
+    let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in edges {
+        adjacency
+            .entry(edge.source.as_str())
+            .or_default()
+            .push(edge.target.as_str());
+        if undirected {
+            adjacency
+                .entry(edge.target.as_str())
+                .or_default()
+                .push(edge.source.as_str());
+        }
+    }
+    for neighbours in adjacency.values_mut() {
+        neighbours.sort_unstable();
+        neighbours.dedup();
+    }
+
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue: std::collections::VecDeque<&str> = std::collections::VecDeque::new();
+    queue.push_back(from);
+
+    while let Some(node) = queue.pop_front() {
+        for &next in adjacency.get(node).map_or(&[][..], Vec::as_slice) {
+            if next == from || previous.contains_key(next) {
+                continue;
+            }
+            previous.insert(next, node);
+            if next == to {
+                let mut path = vec![to.to_string()];
+                let mut current = to;
+                while let Some(&prev) = previous.get(current) {
+                    path.push(prev.to_string());
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back(next);
+        }
+    }
+
+    None
+}
+
+/// Collect every document reachable from `roots` by following links.
+///
+/// Roots are always included. Breadth-first over the directed `edges`.
+pub(crate) fn find_reachable_documents(edges: &[GraphEdge], roots: &[String]) -> HashSet<String> {
+    let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in edges {
+        adjacency
+            .entry(edge.source.as_str())
+            .or_default()
+            .push(edge.target.as_str());
+    }
+
+    let mut reachable: HashSet<String> = roots.iter().cloned().collect();
+    let mut queue: std::collections::VecDeque<&str> = roots.iter().map(String::as_str).collect();
+
+    while let Some(node) = queue.pop_front() {
+        for &next in adjacency.get(node).map_or(&[][..], Vec::as_slice) {
+            if reachable.insert(next.to_string()) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    reachable
+}
+
+/// Default roots for reachability: every indexed README or index document.
+pub(crate) fn default_reachability_roots(forward_index: &ForwardIndex) -> Vec<String> {
+    let mut roots: Vec<String> = forward_index
+        .files
+        .keys()
+        .filter(|path| {
+            Path::new(path)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|stem| {
+                    stem.eq_ignore_ascii_case("readme") || stem.eq_ignore_ascii_case("index")
+                })
+        })
+        .cloned()
+        .collect();
+    roots.sort();
+    roots
+}
+
+/// Show the shortest link chain between two indexed documents.
+pub(crate) fn cmd_path(
+    from: &str,
+    to: &str,
+    index_dir: &IndexDir,
+    json: bool,
+    undirected: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let forward_index = load_forward_index(index_dir)?;
+    let norm_to_key = normalized_file_keys(&forward_index);
+
+    let resolve = |doc: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let normalized = normalize_path(Path::new(doc));
+        norm_to_key
+            .get(&normalized)
+            .cloned()
+            .ok_or_else(|| format!("'{doc}' is not in the index").into())
+    };
+    let from_key = resolve(from)?;
+    let to_key = resolve(to)?;
+
+    let edges = build_link_graph_edges(&forward_index);
+    let path = find_shortest_link_path(&edges, &from_key, &to_key, undirected);
+
+    if json {
+        let result = LinkPathResult {
+            from: from_key,
+            to: to_key,
+            undirected,
+            hops: path.as_ref().map(|p| p.len() - 1),
+            path: path.unwrap_or_default(),
+        };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    let Some(path) = path else {
+        println!(
+            "{} No link path from {} to {}{}.",
+            "Info:".yellow(),
+            from_key.cyan(),
+            to_key.cyan(),
+            if undirected {
+                ""
+            } else {
+                " (try --undirected)"
+            }
+        );
+        return Ok(());
+    };
+
+    let forward: HashSet<(&str, &str)> = edges
+        .iter()
+        .map(|e| (e.source.as_str(), e.target.as_str()))
+        .collect();
+
+    println!(
+        "{} ({} hop{})",
+        "Shortest path".green().bold(),
+        path.len() - 1,
+        if path.len() == 2 { "" } else { "s" }
+    );
+    println!("  {}", path[0].white().bold());
+    for pair in path.windows(2) {
+        // Undirected paths may follow a link backwards; show which way it points.
+        let arrow = if forward.contains(&(pair[0].as_str(), pair[1].as_str())) {
+            "->"
+        } else {
+            "<-"
+        };
+        println!("  {arrow} {}", pair[1].white().bold());
+    }
+
+    Ok(())
+}
+
+/// Render link edges as a fenced Mermaid `graph LR` block.
+///
+/// Node IDs are derived from the file path with non-alphanumerics replaced
+/// by `_` (suffixed on collision); labels show the basename and a `%%`
+/// legend maps each ID back to its full path.
+pub(crate) fn render_mermaid_graph(edges: &[GraphEdge]) -> String {
+    use std::fmt::Write;
+
+    let mut paths: Vec<&str> = edges
+        .iter()
+        .flat_map(|e| [e.source.as_str(), e.target.as_str()])
+        .collect();
+    paths.sort_unstable();
+    paths.dedup();
+
+    let mut ids: HashMap<&str, String> = HashMap::new();
+    let mut used: HashSet<String> = HashSet::new();
+    for path in &paths {
+        let base: String = path
+            .trim_start_matches("./")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let base = format!("n_{base}");
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while !used.insert(id.clone()) {
+            suffix += 1;
+            id = format!("{base}_{suffix}");
+        }
+        ids.insert(path, id);
+    }
+
+    let escape = |text: &str| text.replace('"', "#quot;");
+    let mut out = String::from("```mermaid\ngraph LR\n");
+    for path in &paths {
+        let _ = writeln!(out, "  %% {} = {}", ids[path], path);
+    }
+    for path in &paths {
+        let label = Path::new(path)
+            .file_name()
+            .map_or_else(|| (*path).to_string(), |n| n.to_string_lossy().to_string());
+        let _ = writeln!(out, "  {}[\"{}\"]", ids[path], escape(&label));
+    }
+    for edge in edges {
+        let src = &ids[edge.source.as_str()];
+        let dst = &ids[edge.target.as_str()];
+        let _ = match &edge.anchor {
+            _ if !edge.kind.is_internal() => writeln!(out, "  {src} -.-> {dst}"),
+            Some(anchor) => writeln!(out, "  {src} -->|\"#{}\"| {dst}", escape(anchor)),
+            None => writeln!(out, "  {src} --> {dst}"),
+        };
+    }
+    out.push_str("```\n");
+    out
+}
+
+/// Find stale documents by age and inbound link count.
+///
+/// Age comes from file mtime, or with `use_git` from the last commit that
+/// touched the file (mtime is still used for untracked files).
 pub(crate) fn run_stale_check(
-    index_dir: &Path,
-    days: u64,
-    min_inlinks: usize,
+    index_dir: &IndexDir,
+    criteria: &StaleCriteria,
+    use_git: bool,
 ) -> Result<StaleResult, Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
     let inbound_counts = compute_inbound_link_counts(&forward_index);
 
     let now = std::time::SystemTime::now();
+    let mut git_times = use_git.then(GitCommitTimes::new);
     let mut files = Vec::new();
 
     for file_path in forward_index.files.keys() {
@@ -423,21 +1004,51 @@ pub(crate) fn run_stale_check(
             continue;
         }
         let meta = meta?;
-        let modified = meta.modified().unwrap_or(now);
+        let committed = git_times
+            .as_mut()
+            .and_then(|times| times.last_commit_secs(file_path))
+            .map(|secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        let modified = committed.unwrap_or_else(|| meta.modified().unwrap_or(now));
         let age = now.duration_since(modified).unwrap_or_default().as_secs() / 86_400;
 
         let inlinks = *inbound_counts.get(file_path).unwrap_or(&0);
 
-        if age >= days && inlinks >= min_inlinks {
+        let holds = |condition: StaleCondition| match condition {
+            StaleCondition::Age => age >= criteria.days,
+            StaleCondition::Inlinks => {
+                inlinks >= criteria.min_inlinks
+                    && criteria.max_inlinks.is_none_or(|max| inlinks <= max)
+            }
+            StaleCondition::Orphaned => inlinks == 0,
+        };
+        let reasons: Vec<String> = criteria
+            .require
+            .conditions
+            .iter()
+            .filter(|condition| holds(**condition))
+            .map(|condition| condition.name().to_string())
+            .collect();
+        let stale = if criteria.require.any {
+            !reasons.is_empty()
+        } else {
+            reasons.len() == criteria.require.conditions.len()
+        };
+
+        if stale {
             files.push(StaleFile {
                 file: file_path.clone(),
                 days_since_modified: age,
                 inbound_links: inlinks,
+                reasons,
             });
         }
     }
 
-    files.sort_by(|a, b| b.days_since_modified.cmp(&a.days_since_modified));
+    files.sort_by(|a, b| {
+        b.days_since_modified
+            .cmp(&a.days_since_modified)
+            .then_with(|| a.file.cmp(&b.file))
+    });
 
     Ok(StaleResult {
         total_stale: files.len(),
@@ -445,40 +1056,63 @@ pub(crate) fn run_stale_check(
     })
 }
 
+/// Inbound link range for display, e.g. `>= 1` or `0..=2`.
+fn describe_inlink_range(criteria: &StaleCriteria) -> String {
+    match criteria.max_inlinks {
+        Some(max) => format!("{}..={max}", criteria.min_inlinks),
+        None => format!(">= {}", criteria.min_inlinks),
+    }
+}
+
 pub(crate) fn cmd_stale(
-    index_dir: &Path,
-    days: u64,
-    min_inlinks: usize,
+    index_dir: &IndexDir,
+    criteria: &StaleCriteria,
     json: bool,
+    csv: bool,
+    use_git: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let result = run_stale_check(index_dir, days, min_inlinks)?;
+    let result = run_stale_check(index_dir, criteria, use_git)?;
 
     if json {
         println!("{}", serde_json::to_string_pretty(&result)?);
         return Ok(());
     }
+    if csv {
+        print_csv_row(&["file", "days_since_modified", "inbound_links", "reasons"]);
+        for f in &result.files {
+            print_csv_row(&[
+                f.file.clone(),
+                f.days_since_modified.to_string(),
+                f.inbound_links.to_string(),
+                f.reasons.join(";"),
+            ]);
+        }
+        return Ok(());
+    }
 
+    let criteria_text = format!(
+        "require: {}, age >= {} days, inbound_links {}",
+        criteria.require.describe(),
+        criteria.days,
+        describe_inlink_range(criteria)
+    );
     if result.files.is_empty() {
         println!(
-            "{} No stale files found (threshold: {} days, min_inlinks: {}).",
-            "✓".green().bold(),
-            days,
-            min_inlinks
+            "{} No stale files found ({criteria_text}).",
+            "✓".green().bold()
         );
         return Ok(());
     }
 
-    println!(
-        "{} Stale files (>= {} days old, inbound_links >= {}):",
-        "Stale".yellow().bold(),
-        days,
-        min_inlinks
-    );
+    println!("{} Stale files ({criteria_text}):", "Stale".yellow().bold());
     println!("{}", "=".repeat(60));
     for f in &result.files {
         println!(
-            "{} ({} days, {} inbound links)",
-            f.file, f.days_since_modified, f.inbound_links
+            "{} ({} days, {} inbound links; {})",
+            f.file,
+            f.days_since_modified,
+            f.inbound_links,
+            f.reasons.join(", ")
         );
     }
 
@@ -487,7 +1121,7 @@ pub(crate) fn cmd_stale(
 
 pub(crate) fn resolve_health_target_key(
     file: &Path,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     metrics_index: &DocumentMetricsIndex,
 ) -> Option<String> {
     let input = normalize_path(file);
@@ -599,7 +1233,7 @@ pub(crate) fn evaluate_document_health(
 pub(crate) fn cmd_health(
     file: Option<&Path>,
     all: bool,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     options: &HealthOptions,
     json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -731,6 +1365,9 @@ pub(crate) fn record_link_kind(
         LinkKind::ExternalReference => "external_reference",
         LinkKind::AnchorMissing => "anchor_missing",
         LinkKind::AnchorUnverified => "anchor_unverified",
+        LinkKind::AnchorDuplicate => "anchor_duplicate",
+        LinkKind::ExternalBroken => "external_broken",
+        LinkKind::WikiAmbiguous => "wiki_ambiguous",
     }
     .to_string();
 
@@ -743,18 +1380,85 @@ pub(crate) fn record_link_kind(
     entry.entry(kind_name).and_modify(|c| *c += 1).or_insert(1);
 }
 
+/// Links into `target` and, up to `depth` hops out, links into the files
+/// that link to it. Each file is reported at its shortest distance only;
+/// self-links are counted separately and never followed.
+pub(crate) fn collect_backlinks(
+    graph: &LinkGraph,
+    target: &str,
+    depth: usize,
+) -> (Vec<Backlink>, usize) {
+    let mut inbound: HashMap<&str, Vec<&LinkGraphEdge>> = HashMap::new();
+    let mut internal_anchors = 0;
+    for edge in &graph.edges {
+        if edge.is_self_link() {
+            // Anchor-only and other self-links don't make the file referenced
+            if edge.target == target {
+                internal_anchors += 1;
+            }
+            continue;
+        }
+        inbound.entry(edge.target.as_str()).or_default().push(edge);
+    }
+
+    let mut backlinks = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::from([target]);
+    let mut frontier: Vec<&str> = vec![target];
+    for distance in 1..=depth {
+        let mut next: BTreeSet<&str> = BTreeSet::new();
+        for file in &frontier {
+            for edge in inbound.get(file).into_iter().flatten() {
+                if seen.contains(edge.source.as_str()) {
+                    continue;
+                }
+                next.insert(edge.source.as_str());
+                backlinks.push(Backlink {
+                    source_file: edge.source.clone(),
+                    link_text: edge.text.clone(),
+                    link_target: edge.raw_target.clone(),
+                    anchor: edge.anchor.clone(),
+                    distance,
+                    via: (distance > 1).then(|| edge.target.clone()),
+                });
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        seen.extend(next.iter().copied());
+        frontier = next.into_iter().collect();
+    }
+
+    // Group by distance, then source file for consistent output
+    backlinks.sort_by(|a, b| {
+        a.distance
+            .cmp(&b.distance)
+            .then_with(|| a.source_file.cmp(&b.source_file))
+    });
+    (backlinks, internal_anchors)
+}
+
 /// Find all files that link to a specific file
 pub(crate) fn cmd_backlinks(
     target_file: &str,
-    index_dir: &Path,
+    index_dir: &IndexDir,
+    depth: usize,
     json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if depth == 0 {
+        return Err("--depth must be at least 1".into());
+    }
+
     // Load the forward index
     let forward_index = load_forward_index(index_dir)?;
 
     // Normalize the target file path for comparison
     let normalized_target = normalize_path(Path::new(target_file));
 
+    if is_glob_pattern(target_file) {
+        return backlinks_for_glob(&forward_index, &normalized_target, depth, json);
+    }
+
     if !json {
         println!(
             "{} {}",
@@ -764,71 +1468,16 @@ pub(crate) fn cmd_backlinks(
         println!();
     }
 
-    let mut backlinks = Vec::new();
-
-    // Iterate through all files and check if they link to the target
-    for (source_path, entry) in &forward_index.files {
-        for link in &entry.links {
-            let target = &link.target;
-
-            // Skip external links
-            if target.starts_with("http://")
-                || target.starts_with("https://")
-                || target.starts_with("mailto:")
-                || target.starts_with("ftp://")
-            {
-                continue;
-            }
-
-            // Parse link to separate file path and anchor
-            let (link_path, anchor) = if let Some(idx) = target.find('#') {
-                (
-                    target[..idx].to_string(),
-                    Some(target[idx + 1..].to_string()),
-                )
-            } else {
-                (target.clone(), None)
-            };
-
-            // Resolve relative path from source file
-            let resolved_path = if link_path.is_empty() {
-                // Just an anchor in the current file
-                source_path.clone()
-            } else if let Some(stripped) = link_path.strip_prefix('/') {
-                // Absolute path - strip leading / and use as-is
-                stripped.to_string()
-            } else {
-                // Relative path
-                let source_file_path = Path::new(source_path);
-                if let Some(parent) = source_file_path.parent() {
-                    parent.join(&link_path).to_string_lossy().to_string()
-                } else {
-                    link_path.clone()
-                }
-            };
-
-            // Normalize the resolved path
-            let normalized_link = normalize_path(Path::new(&resolved_path));
-
-            // Check if this link points to our target file
-            if normalized_link == normalized_target {
-                backlinks.push(Backlink {
-                    source_file: source_path.clone(),
-                    link_text: link.text.clone(),
-                    link_target: target.clone(),
-                    anchor,
-                });
-            }
-        }
-    }
-
-    // Sort backlinks by source file for consistent output
-    backlinks.sort_by(|a, b| a.source_file.cmp(&b.source_file));
+    let (backlinks, internal_anchors) =
+        collect_backlinks(&build_link_graph(&forward_index), &normalized_target, depth);
+    let direct = backlinks.iter().filter(|b| b.distance == 1).count();
 
     let result = BacklinksResult {
         target_file: normalized_target.clone(),
+        depth,
         total_backlinks: backlinks.len(),
         backlinks: backlinks.clone(),
+        internal_anchors,
     };
 
     if json {
@@ -838,6 +1487,12 @@ pub(crate) fn cmd_backlinks(
         println!("{}", "=".repeat(60));
         println!();
         println!("Total backlinks: {}", backlinks.len());
+        if depth > 1 {
+            println!("Direct backlinks: {direct}");
+        }
+        if internal_anchors > 0 {
+            println!("Internal anchors: {internal_anchors} (self-links, not counted)");
+        }
         println!();
 
         if backlinks.is_empty() {
@@ -850,8 +1505,25 @@ pub(crate) fn cmd_backlinks(
             println!("  - An orphaned document (consider reviewing for deletion)");
             println!("  - A new document that needs linking");
             println!("  - An entry point document (like README.md)");
+            if internal_anchors > 0 {
+                println!("  ({internal_anchors} link(s) within the file itself were ignored)");
+            }
         } else {
+            let mut current_distance = 0;
             for (idx, backlink) in backlinks.iter().enumerate() {
+                if depth > 1 && backlink.distance != current_distance {
+                    current_distance = backlink.distance;
+                    let label = if current_distance == 1 {
+                        "direct".to_string()
+                    } else {
+                        format!("{current_distance} hops")
+                    };
+                    println!(
+                        "{}",
+                        format!("Distance {current_distance} ({label})").cyan()
+                    );
+                    println!();
+                }
                 println!("[{}] {}", idx + 1, backlink.source_file.white().bold());
                 println!(
                     "    Link: [{}]({})",
@@ -860,18 +1532,135 @@ pub(crate) fn cmd_backlinks(
                 if let Some(anchor) = &backlink.anchor {
                     println!("    Anchor: #{anchor}");
                 }
+                if let Some(via) = &backlink.via {
+                    println!("    Via: {via}");
+                }
                 println!();
             }
 
             println!("{}", "Safe to delete?".yellow().bold());
             println!(
-                "  {} These {} file(s) link to this document.",
+                "  {} These {} link(s) from other files point to this document.",
                 "⚠".yellow(),
-                backlinks.len()
+                direct
             );
+            if backlinks.len() > direct {
+                println!(
+                    "  {} files reach it indirectly within {depth} hops.",
+                    backlinks.len() - direct
+                );
+            }
             println!("  Review and update references before deletion.");
         }
     }
 
     Ok(())
 }
+
+/// Whether a `backlinks` target is a glob rather than a single file
+fn is_glob_pattern(target: &str) -> bool {
+    target.contains(['*', '?', '[', '{'])
+}
+
+/// `backlinks` for each indexed file matching `pattern`, grouped by target
+fn backlinks_for_glob(
+    forward_index: &ForwardIndex,
+    pattern: &str,
+    depth: usize,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let matcher = globset::Glob::new(pattern)
+        .map_err(|err| format!("Invalid backlinks glob '{pattern}': {err}"))?
+        .compile_matcher();
+    let mut matched: Vec<&str> = forward_index
+        .files
+        .keys()
+        .map(String::as_str)
+        .filter(|path| matcher.is_match(path))
+        .collect();
+    if matched.is_empty() {
+        return Err(format!("No indexed files match '{pattern}'").into());
+    }
+    matched.sort_unstable();
+
+    let graph = build_link_graph(forward_index);
+    let mut targets = BTreeMap::new();
+    let mut internal_anchors = 0;
+    for target in &matched {
+        let (backlinks, anchors) = collect_backlinks(&graph, target, depth);
+        internal_anchors += anchors;
+        targets.insert(target.to_string(), backlinks);
+    }
+    let total_backlinks = targets.values().map(Vec::len).sum();
+
+    let result = BacklinksGlobResult {
+        pattern: pattern.to_string(),
+        depth,
+        matched_files: matched.len(),
+        total_backlinks,
+        targets,
+        internal_anchors,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} {} ({} files)",
+        "Finding backlinks for".cyan().bold(),
+        pattern.white().bold(),
+        result.matched_files
+    );
+    println!();
+    println!("{}", "Backlinks Found".cyan().bold());
+    println!("{}", "=".repeat(60));
+    println!();
+    let linked = result.targets.values().filter(|b| !b.is_empty()).count();
+    println!(
+        "Total backlinks: {} into {linked} of {} files",
+        result.total_backlinks, result.matched_files
+    );
+    if internal_anchors > 0 {
+        println!("Internal anchors: {internal_anchors} (self-links, not counted)");
+    }
+    println!();
+
+    for (target, backlinks) in result.targets.iter().filter(|(_, b)| !b.is_empty()) {
+        println!("{} ({})", target.white().bold(), backlinks.len());
+        for backlink in backlinks {
+            let distance = if backlink.distance > 1 {
+                format!(" [{} hops]", backlink.distance)
+            } else {
+                String::new()
+            };
+            println!(
+                "  {}{distance}  [{}]({})",
+                backlink.source_file, backlink.link_text, backlink.link_target
+            );
+            if let Some(via) = &backlink.via {
+                println!("    Via: {via}");
+            }
+        }
+        println!();
+    }
+
+    let unlinked: Vec<&String> = result
+        .targets
+        .iter()
+        .filter(|(_, b)| b.is_empty())
+        .map(|(target, _)| target)
+        .collect();
+    if !unlinked.is_empty() {
+        println!(
+            "{}",
+            format!("Not linked from any other file ({}):", unlinked.len()).yellow()
+        );
+        for target in unlinked {
+            println!("  {target}");
+        }
+    }
+
+    Ok(())
+}
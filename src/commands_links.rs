@@ -1,22 +1,40 @@
+use crate::commands_audit::score_canonicality_with_reasons;
 use crate::commands_graph::*;
 use colored::Colorize;
 use globset::Glob;
-use regex::Regex;
+#[cfg(feature = "external-links")]
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
 use serde::Serialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "external-links")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::index::parse_frontmatter;
+use crate::search::code_line_mask;
 use crate::types::*;
 use crate::util::*;
 
 pub(crate) fn run_link_check(
-    index_dir: &Path,
-    root: Option<&Path>,
-    include_summary: bool,
-    summary_only: bool,
-    external_paths: &[String],
+    index_dir: &IndexDir,
+    options: &LinkCheckOptions,
 ) -> Result<LinkCheckResult, Box<dyn std::error::Error>> {
+    let &LinkCheckOptions {
+        root,
+        include_summary,
+        summary_only,
+        external_paths,
+        external_check,
+        allowlist,
+        context_lines,
+    } = options;
+    if external_check.is_some() && !cfg!(feature = "external-links") {
+        return Err(
+            "--check-external requires yore built with the `external-links` feature".into(),
+        );
+    }
     // Load the forward index
     let forward_index = load_forward_index(index_dir)?;
 
@@ -51,21 +69,34 @@ pub(crate) fn run_link_check(
 
     // Build file set for fast lookup (keys of the HashMap)
     let file_set: HashSet<String> = forward_index.files.keys().cloned().collect();
+    let wiki_pages = build_wiki_page_map(forward_index.files.keys());
 
-    // Build heading index for anchor validation
+    // Build anchor index (heading slugs plus explicit ids) for validation
     let mut heading_index: HashMap<String, HashSet<String>> = HashMap::new();
     for (path, entry) in &forward_index.files {
-        let mut anchors = HashSet::new();
+        let mut anchors: HashSet<String> =
+            entry.anchors.iter().map(|id| id.to_lowercase()).collect();
+        let mut seen_slugs = HashMap::new();
+        let format = DocFormat::from_path(Path::new(path));
         for heading in &entry.headings {
-            // Convert heading text to anchor format (lowercase, replace spaces with hyphens)
-            let anchor = heading.text.to_lowercase().replace(' ', "-");
-            anchors.insert(anchor);
+            if let Some(anchor) = &heading.anchor {
+                anchors.insert(anchor.to_lowercase());
+            }
+            // HTML anchors are explicit ids only, never derived slugs
+            if format == DocFormat::Html {
+                continue;
+            }
+            anchors.insert(github_slug(&heading.text, &mut seen_slugs));
+            if format == DocFormat::AsciiDoc {
+                anchors.insert(asciidoc_section_id(&heading.text));
+            }
         }
         heading_index.insert(path.clone(), anchors);
     }
 
     let mut broken_links = Vec::new();
     let mut total_links = 0;
+    let mut suppressed_links = 0;
 
     // Cache file lines for context snippets
     let mut file_lines_cache: HashMap<String, Vec<String>> = HashMap::new();
@@ -74,22 +105,66 @@ pub(crate) fn run_link_check(
     let mut counts_by_file: HashMap<String, HashMap<String, usize>> = HashMap::new();
     let mut counts_by_kind: HashMap<String, usize> = HashMap::new();
 
+    // External URLs collected for the optional network pass
+    let mut external_links: Vec<(&String, &Link)> = Vec::new();
+
     // Iterate through all files and check their links
     for (file_path, entry) in &forward_index.files {
+        let normalized_source = normalize_path(Path::new(file_path));
         for link in &entry.links {
             total_links += 1;
 
             let target = &link.target;
+            // Allowlisted links that would be reported broken are left out of
+            // the broken list and the counts; valid ones count as usual
+            let allowlisted = allowlist
+                .is_some_and(|allowlist| allowlist.suppresses_link(&normalized_source, link));
 
             // Skip external links (http://, https://, mailto:, etc.)
-            if target.starts_with("http://")
-                || target.starts_with("https://")
-                || target.starts_with("mailto:")
-                || target.starts_with("ftp://")
-            {
+            if target.starts_with("http://") || target.starts_with("https://") {
+                if external_check.is_some() {
+                    external_links.push((file_path, link));
+                }
+                continue;
+            }
+            if target.starts_with("mailto:") || target.starts_with("ftp://") {
                 continue;
             }
 
+            // Wiki links matching several basenames cannot be resolved
+            if let Some(page) = link.wiki.as_deref() {
+                let candidates = wiki_pages
+                    .get(&wiki_page_key(page))
+                    .map_or(&[][..], Vec::as_slice);
+                if candidates.len() > 1 {
+                    if allowlisted {
+                        suppressed_links += 1;
+                        continue;
+                    }
+                    let context = get_link_context(
+                        &mut file_lines_cache,
+                        file_path,
+                        link.line,
+                        context_lines,
+                    )?;
+                    let kind = LinkKind::WikiAmbiguous;
+                    record_link_kind(&mut counts_by_file, &mut counts_by_kind, file_path, &kind);
+                    broken_links.push(BrokenLink {
+                        source_file: file_path.clone(),
+                        line_number: link.line,
+                        link_text: link.text.clone(),
+                        link_target: format!("[[{page}]]"),
+                        error: format!(
+                            "Ambiguous wiki link [[{page}]]: matches {}",
+                            candidates.join(", ")
+                        ),
+                        anchor: None,
+                        context,
+                    });
+                    continue;
+                }
+            }
+
             // Parse link to separate file path and anchor
             let (link_path, anchor) = if let Some(idx) = target.find('#') {
                 (
@@ -124,7 +199,12 @@ pub(crate) fn run_link_check(
 
             // Placeholder targets: treat as lower-severity broken links
             if !link_path.is_empty() && is_placeholder_target(&link_path) {
-                let context = get_link_context(&mut file_lines_cache, file_path, line_number)?;
+                if allowlisted {
+                    suppressed_links += 1;
+                    continue;
+                }
+                let context =
+                    get_link_context(&mut file_lines_cache, file_path, line_number, context_lines)?;
                 let kind = LinkKind::Placeholder;
                 record_link_kind(&mut counts_by_file, &mut counts_by_kind, file_path, &kind);
                 broken_links.push(BrokenLink {
@@ -206,6 +286,10 @@ pub(crate) fn run_link_check(
                     if found_in_external {
                         continue;
                     }
+                    if allowlisted {
+                        suppressed_links += 1;
+                        continue;
+                    }
 
                     // Missing target file: classify as doc_missing or code_missing
                     let ext = file_extension(&normalized_path);
@@ -214,7 +298,12 @@ pub(crate) fn run_link_check(
                     } else {
                         LinkKind::DocMissing
                     };
-                    let context = get_link_context(&mut file_lines_cache, file_path, line_number)?;
+                    let context = get_link_context(
+                        &mut file_lines_cache,
+                        file_path,
+                        line_number,
+                        context_lines,
+                    )?;
                     record_link_kind(&mut counts_by_file, &mut counts_by_kind, file_path, &kind);
                     broken_links.push(BrokenLink {
                         source_file: file_path.clone(),
@@ -237,10 +326,33 @@ pub(crate) fn run_link_check(
                     &normalized_path
                 };
 
-                if let Some(anchors) = heading_index.get(target_file) {
-                    if !anchors.contains(anchor_text as &str) {
-                        let context =
-                            get_link_context(&mut file_lines_cache, file_path, line_number)?;
+                // GitHub line anchors (`#L42`, `#L10-L20`) must fall within the file
+                let line_count = forward_index
+                    .files
+                    .get(target_file)
+                    .map_or(0, |entry| entry.line_count);
+                let line_range = parse_line_anchor(anchor_text);
+                if line_range.is_some_and(|(_, end)| end <= line_count) {
+                    continue;
+                }
+
+                // Files with no headings or explicit ids can't be checked
+                let anchors = heading_index
+                    .get(target_file)
+                    .filter(|anchors| !anchors.is_empty());
+                if anchors.is_some() || line_range.is_some() {
+                    if !anchors.is_some_and(|anchors| anchors.contains(&anchor_text.to_lowercase()))
+                    {
+                        if allowlisted {
+                            suppressed_links += 1;
+                            continue;
+                        }
+                        let context = get_link_context(
+                            &mut file_lines_cache,
+                            file_path,
+                            line_number,
+                            context_lines,
+                        )?;
                         let kind = LinkKind::AnchorMissing;
                         record_link_kind(
                             &mut counts_by_file,
@@ -253,13 +365,28 @@ pub(crate) fn run_link_check(
                             line_number,
                             link_text: link.text.clone(),
                             link_target: target.clone(),
-                            error: format!("Anchor not found: #{anchor_text}"),
+                            error: if line_range.is_some() {
+                                format!(
+                                    "Line anchor out of range: #{anchor_text} ({target_file} has {line_count} lines)"
+                                )
+                            } else {
+                                format!("Anchor not found: #{anchor_text}")
+                            },
                             anchor: Some(anchor_text.clone()),
                             context,
                         });
                     }
                 } else {
-                    let context = get_link_context(&mut file_lines_cache, file_path, line_number)?;
+                    if allowlisted {
+                        suppressed_links += 1;
+                        continue;
+                    }
+                    let context = get_link_context(
+                        &mut file_lines_cache,
+                        file_path,
+                        line_number,
+                        context_lines,
+                    )?;
                     let kind = LinkKind::AnchorUnverified;
                     record_link_kind(&mut counts_by_file, &mut counts_by_kind, file_path, &kind);
                     broken_links.push(BrokenLink {
@@ -268,7 +395,7 @@ pub(crate) fn run_link_check(
                         link_text: link.text.clone(),
                         link_target: target.clone(),
                         error: format!(
-                            "Could not verify anchor (file has no headings): #{anchor_text}"
+                            "Could not verify anchor (file has no anchors): #{anchor_text}"
                         ),
                         anchor: Some(anchor_text.clone()),
                         context,
@@ -278,13 +405,85 @@ pub(crate) fn run_link_check(
         }
     }
 
-    let valid_links = total_links - broken_links.len();
+    if let Some(options) = external_check {
+        let mut urls: Vec<String> = external_links
+            .iter()
+            .map(|(_, link)| link.target.clone())
+            .collect();
+        urls.sort();
+        urls.dedup();
+        let statuses = check_external_urls(index_dir, &urls, options)?;
+
+        for (file_path, link) in external_links {
+            let Some(status) = statuses.get(&link.target) else {
+                continue;
+            };
+            if !status.is_broken() {
+                continue;
+            }
+            if allowlist.is_some_and(|allowlist| {
+                allowlist.suppresses_link(&normalize_path(Path::new(file_path)), link)
+            }) {
+                suppressed_links += 1;
+                continue;
+            }
+            let context =
+                get_link_context(&mut file_lines_cache, file_path, link.line, context_lines)?;
+            let kind = LinkKind::ExternalBroken;
+            record_link_kind(&mut counts_by_file, &mut counts_by_kind, file_path, &kind);
+            let error = match (status.status, &status.error) {
+                (Some(code), _) => format!("External URL returned HTTP {code}"),
+                (None, Some(err)) => format!("External URL unreachable: {err}"),
+                (None, None) => "External URL unreachable".to_string(),
+            };
+            broken_links.push(BrokenLink {
+                source_file: file_path.clone(),
+                line_number: link.line,
+                link_text: link.text.clone(),
+                link_target: link.target.clone(),
+                error,
+                anchor: None,
+                context,
+            });
+        }
+    }
+
+    let valid_links = total_links - broken_links.len() - suppressed_links;
+
+    // Duplicate heading slugs are file-level issues, kept apart from links
+    let mut duplicate_anchors = find_duplicate_anchors(&forward_index);
+    duplicate_anchors.retain(|issue| {
+        let target = format!("#{}", issue.anchor);
+        !allowlist.is_some_and(|allowlist| {
+            allowlist.suppresses(
+                &normalize_path(Path::new(&issue.file)),
+                issue.lines[1],
+                &target,
+            )
+        })
+    });
+    for issue in &duplicate_anchors {
+        record_link_kind(
+            &mut counts_by_file,
+            &mut counts_by_kind,
+            &issue.file,
+            &LinkKind::AnchorDuplicate,
+        );
+    }
 
+    let health = link_health(
+        total_links - suppressed_links,
+        broken_links.len(),
+        &counts_by_kind,
+    );
     let mut result = LinkCheckResult {
         total_links,
         valid_links,
         broken_links: broken_links.len(),
+        suppressed_links,
+        health,
         broken: broken_links.clone(),
+        duplicate_anchors,
         summary: None,
     };
 
@@ -311,27 +510,255 @@ pub(crate) fn run_link_check(
     Ok(result)
 }
 
+/// Penalty per issue of each link kind in the link health score. Missing
+/// targets break navigation outright, so they cost a full link; an anchor
+/// that could not be verified is often fine and costs a tenth. Kinds not
+/// listed (`code_reference`, `directory_reference`, `external_reference`)
+/// are informational and cost nothing.
+pub(crate) const LINK_HEALTH_WEIGHTS: &[(&str, f64)] = &[
+    ("doc_missing", 1.0),
+    ("code_missing", 1.0),
+    ("external_broken", 0.75),
+    ("anchor_missing", 0.5),
+    ("wiki_ambiguous", 0.5),
+    ("placeholder", 0.25),
+    ("anchor_unverified", 0.1),
+];
+
+/// Issue kinds in the link summary that are not about a link (duplicate
+/// headings), left out of the health object.
+const NON_LINK_KINDS: &[&str] = &["anchor_duplicate"];
+
+/// Summarize link issues as a 0–100 score: 100 minus the weighted issue
+/// count per checked link (see `LINK_HEALTH_WEIGHTS`), rounded to one
+/// decimal. Each broken link is one issue, so the penalty never exceeds the
+/// link count. With no checked links the score is 100.
+pub(crate) fn link_health(
+    checked_links: usize,
+    broken_links: usize,
+    counts_by_kind: &HashMap<String, usize>,
+) -> LinkHealth {
+    let penalty: f64 = LINK_HEALTH_WEIGHTS
+        .iter()
+        .map(|(kind, weight)| weight * counts_by_kind.get(*kind).copied().unwrap_or(0) as f64)
+        .sum();
+    let (broken_ratio, score) = if checked_links == 0 {
+        (0.0, 100.0)
+    } else {
+        let checked = checked_links as f64;
+        (
+            broken_links as f64 / checked,
+            (100.0 * (1.0 - penalty / checked) * 10.0).round() / 10.0,
+        )
+    };
+    LinkHealth {
+        broken_ratio,
+        by_kind: counts_by_kind
+            .iter()
+            .filter(|(kind, _)| !NON_LINK_KINDS.contains(&kind.as_str()))
+            .map(|(kind, count)| (kind.clone(), *count))
+            .collect(),
+        score,
+    }
+}
+
+/// Find headings within a file whose GitHub slugs collide.
+///
+/// Reports one entry per colliding slug, listing the heading text and line
+/// of every collision.
+fn find_duplicate_anchors(forward_index: &ForwardIndex) -> Vec<DuplicateAnchor> {
+    let mut issues = Vec::new();
+
+    for (path, entry) in &forward_index.files {
+        let mut by_slug: HashMap<String, Vec<&Heading>> = HashMap::new();
+        for heading in &entry.headings {
+            let slug = github_slug(&heading.text, &mut HashMap::new());
+            by_slug.entry(slug).or_default().push(heading);
+        }
+
+        for (slug, headings) in by_slug {
+            if headings.len() < 2 {
+                continue;
+            }
+            let occurrences: Vec<String> = headings
+                .iter()
+                .map(|h| format!("{:?} (line {})", h.text, h.line))
+                .collect();
+            issues.push(DuplicateAnchor {
+                file: path.clone(),
+                error: format!("Duplicate anchor #{slug}: {}", occurrences.join(", ")),
+                anchor: slug,
+                lines: headings.iter().map(|h| h.line).collect(),
+            });
+        }
+    }
+
+    issues.sort_by(|a, b| a.file.cmp(&b.file).then(a.lines.cmp(&b.lines)));
+    issues
+}
+
+#[cfg(feature = "external-links")]
+const EXTERNAL_LINK_CACHE_FILE: &str = "external_links.json";
+#[cfg(feature = "external-links")]
+const EXTERNAL_LINK_CACHE_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Probe external URLs, reusing results cached in the index directory.
+///
+/// Cached entries younger than a day are trusted; the rest are re-checked
+/// with at most `options.concurrency` requests in flight.
+#[cfg(feature = "external-links")]
+fn check_external_urls(
+    index_dir: &Path,
+    urls: &[String],
+    options: &ExternalCheckOptions,
+) -> Result<HashMap<String, ExternalLinkStatus>, Box<dyn std::error::Error>> {
+    let cache_path = index_dir.join(EXTERNAL_LINK_CACHE_FILE);
+    let mut cache: HashMap<String, ExternalLinkStatus> = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let pending: Vec<&String> = urls
+        .iter()
+        .filter(|url| {
+            cache.get(*url).is_none_or(|status| {
+                now.saturating_sub(status.checked_at) > EXTERNAL_LINK_CACHE_TTL_SECS
+            })
+        })
+        .collect();
+
+    if !pending.is_empty() {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(options.timeout_secs))
+            .build();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.concurrency.max(1))
+            .build()?;
+        let fresh: Vec<(String, ExternalLinkStatus)> = pool.install(|| {
+            pending
+                .par_iter()
+                .map(|url| ((*url).clone(), probe_external_url(&agent, url, now)))
+                .collect()
+        });
+        cache.extend(fresh);
+        fs::write(&cache_path, serde_json::to_string_pretty(&cache)?)?;
+    }
+
+    Ok(cache)
+}
+
+/// Issue a HEAD request, falling back to GET when the server rejects HEAD
+#[cfg(feature = "external-links")]
+fn probe_external_url(agent: &ureq::Agent, url: &str, now: u64) -> ExternalLinkStatus {
+    let response = match agent.head(url).call() {
+        Err(ureq::Error::Status(..)) => agent.get(url).call(),
+        other => other,
+    };
+    let (status, error) = match response {
+        Ok(resp) => (Some(resp.status()), None),
+        Err(ureq::Error::Status(code, _)) => (Some(code), None),
+        Err(ureq::Error::Transport(err)) => (None, Some(err.to_string())),
+    };
+    ExternalLinkStatus {
+        status,
+        error,
+        checked_at: now,
+    }
+}
+
+#[cfg(not(feature = "external-links"))]
+fn check_external_urls(
+    _index_dir: &Path,
+    _urls: &[String],
+    _options: &ExternalCheckOptions,
+) -> Result<HashMap<String, ExternalLinkStatus>, Box<dyn std::error::Error>> {
+    // Unreachable in practice: run_link_check rejects --check-external
+    // without the feature
+    Err("--check-external requires yore built with the `external-links` feature".into())
+}
+
+/// Load a link allowlist for `--ignore-file`.
+///
+/// One entry per line, either `source_file:line` or `source_file:target`.
+/// Blank lines and lines starting with `#` are ignored.
+pub(crate) fn load_link_allowlist(
+    path: &Path,
+) -> Result<LinkAllowlist, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read ignore file {}: {e}", path.display()))?;
+    parse_link_allowlist(&content).map_err(|e| format!("{}: {e}", path.display()).into())
+}
+
+pub(crate) fn parse_link_allowlist(content: &str) -> Result<LinkAllowlist, String> {
+    let mut allowlist = LinkAllowlist::default();
+
+    for (idx, raw) in content.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Split on the first colon: targets (URLs) may contain more
+        let Some((source, rest)) = line
+            .split_once(':')
+            .filter(|(source, rest)| !source.trim().is_empty() && !rest.trim().is_empty())
+        else {
+            return Err(format!(
+                "line {}: expected `source_file:line` or `source_file:target`, got '{line}'",
+                idx + 1
+            ));
+        };
+        let source = normalize_path(Path::new(source.trim()));
+        let rest = rest.trim();
+        match rest.parse::<usize>() {
+            Ok(line_number) => allowlist.lines.insert((source, line_number)),
+            Err(_) => allowlist.targets.insert((source, rest.to_string())),
+        };
+    }
+
+    Ok(allowlist)
+}
+
 /// User-facing link check command that prints results.
+///
+/// Returns `true` when the health score is below `fail_below`. Text output
+/// always includes the summary.
 pub(crate) fn cmd_check_links(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
-    root: Option<&Path>,
-    summary_flag: bool,
-    summary_only: bool,
-    external_paths: &[String],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let include_summary = summary_flag || summary_only || !json;
-    let result = run_link_check(
-        index_dir,
+    fail_below: Option<f64>,
+    options: &LinkCheckOptions,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if let Some(score) = fail_below {
+        if !(0.0..=100.0).contains(&score) {
+            return Err(format!("--fail-below must be between 0 and 100, got {score}").into());
+        }
+    }
+    let options = LinkCheckOptions {
+        include_summary: options.include_summary || options.summary_only || !json,
+        ..*options
+    };
+    let LinkCheckOptions {
         root,
-        include_summary,
         summary_only,
-        external_paths,
-    )?;
+        allowlist,
+        ..
+    } = options;
+    let result = run_link_check(index_dir, &options)?;
+
+    let below_threshold = fail_below.is_some_and(|score| result.health.score < score);
+    if let Some(score) = fail_below.filter(|_| below_threshold) {
+        eprintln!(
+            "Link health score {:.1} is below --fail-below {score:.1}",
+            result.health.score
+        );
+    }
 
     if json {
         println!("{}", serde_json::to_string_pretty(&result)?);
-        return Ok(());
+        return Ok(below_threshold);
     }
 
     // Recompute root directory for display purposes only
@@ -370,6 +797,13 @@ pub(crate) fn cmd_check_links(
             "✗".red().bold().to_string()
         }
     );
+    if allowlist.is_some() {
+        println!(
+            "Suppressed:   {} (allowlisted, not counted)",
+            result.suppressed_links
+        );
+    }
+    println!("Health score: {:.1}/100", result.health.score);
     println!();
 
     if let Some(summary) = &result.summary {
@@ -391,21 +825,36 @@ pub(crate) fn cmd_check_links(
                 println!("    Line: {}", link.line_number);
             }
             if let Some(ref ctx) = link.context {
-                println!("    Context: {ctx}");
+                // Surrounding lines from --context-lines line up under the first
+                println!("    Context: {}", ctx.replace('\n', "\n             "));
             }
             println!("    Error: {}", link.error.red());
             println!();
         }
     }
 
-    Ok(())
+    if !summary_only && !result.duplicate_anchors.is_empty() {
+        println!("{}", "Duplicate Anchors:".yellow().bold());
+        println!();
+
+        for (idx, issue) in result.duplicate_anchors.iter().enumerate() {
+            println!("[{}] {}", idx + 1, issue.file.white().bold());
+            println!("    {}", issue.error.yellow());
+            println!();
+        }
+    }
+
+    Ok(below_threshold)
 }
 
-/// Load a single-line context snippet for a link location.
+/// Load a context snippet for a link location: the link's line plus up to
+/// `context_lines` lines before and after it, clamped to the file. Each line
+/// is truncated to 160 bytes and lines are joined with newlines.
 pub(crate) fn get_link_context(
     cache: &mut HashMap<String, Vec<String>>,
     file_path: &str,
     line_number: usize,
+    context_lines: usize,
 ) -> Result<Option<String>, Box<dyn std::error::Error>> {
     if line_number == 0 {
         return Ok(None);
@@ -426,13 +875,21 @@ pub(crate) fn get_link_context(
         return Ok(None);
     }
 
-    let mut line = lines[line_number - 1].clone();
-    if line.len() > 160 {
-        line.truncate(157);
-        line.push_str("...");
-    }
+    let first = line_number.saturating_sub(context_lines).max(1);
+    let last = (line_number + context_lines).min(lines.len());
+    let snippet: Vec<String> = lines[first - 1..last]
+        .iter()
+        .map(|line| {
+            let mut line = line.clone();
+            if line.len() > 160 {
+                line.truncate(157);
+                line.push_str("...");
+            }
+            line
+        })
+        .collect();
 
-    Ok(Some(line))
+    Ok(Some(snippet.join("\n")))
 }
 
 pub(crate) fn load_policy_config(path: &Path) -> Result<PolicyConfig, Box<dyn std::error::Error>> {
@@ -509,64 +966,72 @@ pub(crate) struct LinkTarget {
     anchor: Option<String>,
 }
 
-pub(crate) fn extract_markdown_link_targets(file_path: &str, content: &str) -> Vec<LinkTarget> {
-    let mut targets = Vec::new();
-    let link_regex = Regex::new(r"(!?)\[(?P<label>[^\]]+)\]\((?P<target>[^)]+)\)").unwrap();
-
-    let origin_dir = Path::new(file_path)
-        .parent()
-        .unwrap_or_else(|| Path::new("."));
-
-    for caps in link_regex.captures_iter(content) {
-        if caps.get(1).is_some_and(|m| m.as_str() == "!") {
-            continue;
-        }
+/// Resolve a raw link target from `file_path` into a normalized document
+/// path plus optional anchor. External URLs, pure anchors, and non-document
+/// targets yield `None`.
+fn resolve_policy_link_target(file_path: &str, target_str: &str) -> Option<LinkTarget> {
+    if target_str.starts_with("http://")
+        || target_str.starts_with("https://")
+        || target_str.starts_with("mailto:")
+        || target_str.starts_with("ftp://")
+    {
+        return None;
+    }
 
-        let target_str = match caps.name("target") {
-            Some(t) => t.as_str(),
-            None => continue,
-        };
+    let (path_part, anchor) = if let Some(hash_pos) = target_str.find('#') {
+        (
+            &target_str[..hash_pos],
+            Some(target_str[hash_pos + 1..].to_string()),
+        )
+    } else {
+        (target_str, None)
+    };
 
-        if target_str.starts_with("http://")
-            || target_str.starts_with("https://")
-            || target_str.starts_with("mailto:")
-            || target_str.starts_with("ftp://")
-        {
-            continue;
-        }
+    if path_part.is_empty() {
+        return None;
+    }
 
-        let (path_part, anchor) = if let Some(hash_pos) = target_str.find('#') {
-            (
-                &target_str[..hash_pos],
-                Some(target_str[hash_pos + 1..].to_string()),
-            )
-        } else {
-            (target_str, None)
-        };
+    let lc = path_part.to_ascii_lowercase();
+    if !lc.ends_with(".md") && !lc.ends_with(".txt") && !lc.ends_with(".rst") {
+        return None;
+    }
 
-        if path_part.is_empty() {
-            continue;
-        }
+    let origin_dir = Path::new(file_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+    let target_path = if let Some(stripped) = path_part.strip_prefix('/') {
+        PathBuf::from(stripped)
+    } else {
+        origin_dir.join(path_part)
+    };
 
-        let lc = path_part.to_ascii_lowercase();
-        if !lc.ends_with(".md") && !lc.ends_with(".txt") && !lc.ends_with(".rst") {
-            continue;
-        }
+    Some(LinkTarget {
+        path: normalize_path(&target_path),
+        anchor,
+    })
+}
 
-        let target_path = if let Some(stripped) = path_part.strip_prefix('/') {
-            PathBuf::from(stripped)
-        } else {
-            origin_dir.join(path_part)
-        };
+pub(crate) fn extract_markdown_link_targets(file_path: &str, content: &str) -> Vec<LinkTarget> {
+    let link_regex = Regex::new(r"(!?)\[(?P<label>[^\]]+)\]\((?P<target>[^)]+)\)").unwrap();
 
-        let normalized = normalize_path(&target_path);
-        targets.push(LinkTarget {
-            path: normalized,
-            anchor,
-        });
-    }
+    link_regex
+        .captures_iter(content)
+        .filter(|caps| caps.get(1).is_none_or(|m| m.as_str() != "!"))
+        .filter_map(|caps| {
+            let target_str = caps.name("target")?.as_str();
+            resolve_policy_link_target(file_path, target_str)
+        })
+        .collect()
+}
 
-    targets
+/// Link targets from an indexed entry's parsed links, which also cover
+/// reference-style and resolved wiki links.
+pub(crate) fn entry_link_targets(file_path: &str, entry: &FileEntry) -> Vec<LinkTarget> {
+    entry
+        .links
+        .iter()
+        .filter_map(|link| resolve_policy_link_target(file_path, &link.target))
+        .collect()
 }
 
 pub(crate) fn normalize_required_link(file_path: &str, required: &str) -> (String, Option<String>) {
@@ -607,10 +1072,50 @@ pub(crate) fn normalize_required_link(file_path: &str, required: &str) -> (Strin
     (normalize_path(&resolved), anchor)
 }
 
-pub(crate) fn collect_policy_violations_for_content(
+/// Compiled `must_match` / `must_not_match` regexes for one policy rule.
+#[derive(Debug, Default)]
+pub(crate) struct PolicyPatterns {
+    must_match: Vec<(String, Regex)>,
+    must_not_match: Vec<(String, Regex)>,
+}
+
+/// Compile a rule's content regexes once, in multi-line mode so `^` and `$`
+/// anchor to markdown lines.
+pub(crate) fn compile_policy_patterns(rule: &PolicyRule) -> Result<PolicyPatterns, String> {
+    let compile = |field: &str, patterns: &[String]| -> Result<Vec<(String, Regex)>, String> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .multi_line(true)
+                    .build()
+                    .map(|re| (pattern.clone(), re))
+                    .map_err(|e| {
+                        format!(
+                            "Invalid {field} regex in policy rule {:?}: {pattern}\n{e}",
+                            rule_name(rule)
+                        )
+                    })
+            })
+            .collect()
+    };
+
+    Ok(PolicyPatterns {
+        must_match: compile("must_match", &rule.must_match)?,
+        must_not_match: compile("must_not_match", &rule.must_not_match)?,
+    })
+}
+
+/// Check one file against a rule.
+///
+/// When the file's indexed `entry` is available, `must_link_to` uses its
+/// parsed links; otherwise inline links are extracted from `content`.
+pub(crate) fn collect_policy_violations_with_patterns(
     rule: &PolicyRule,
+    patterns: &PolicyPatterns,
     file_path: &str,
     content: &str,
+    entry: Option<&FileEntry>,
 ) -> Vec<PolicyViolation> {
     let mut violations = Vec::new();
 
@@ -640,6 +1145,53 @@ pub(crate) fn collect_policy_violations_for_content(
         }
     }
 
+    // Required patterns
+    for (pattern, re) in &patterns.must_match {
+        if !re.is_match(content) {
+            violations.push(PolicyViolation {
+                file: file_path.to_string(),
+                rule: rule_name(rule),
+                message: format!("No match for required pattern: {pattern}"),
+                severity: rule_severity(rule),
+                kind: "policy_violation".to_string(),
+            });
+        }
+    }
+
+    // Forbidden patterns
+    for (pattern, re) in &patterns.must_not_match {
+        if let Some(m) = re.find(content) {
+            let line = content[..m.start()].matches('\n').count() + 1;
+            violations.push(PolicyViolation {
+                file: file_path.to_string(),
+                rule: rule_name(rule),
+                message: format!("Forbidden pattern matched at line {line}: {pattern}"),
+                severity: rule_severity(rule),
+                kind: "policy_violation".to_string(),
+            });
+        }
+    }
+
+    // Required frontmatter fields
+    if !rule.require_frontmatter.is_empty() {
+        let lines: Vec<&str> = content.lines().collect();
+        let (frontmatter, _) = parse_frontmatter(&lines);
+        for key in &rule.require_frontmatter {
+            let present = frontmatter
+                .get(key)
+                .is_some_and(|value| !value.trim().is_empty());
+            if !present {
+                violations.push(PolicyViolation {
+                    file: file_path.to_string(),
+                    rule: rule_name(rule),
+                    message: format!("Missing required frontmatter field: {key:?}"),
+                    severity: rule_severity(rule),
+                    kind: "policy_violation".to_string(),
+                });
+            }
+        }
+    }
+
     // Length-based checks (line count)
     let line_count = content.lines().count();
     if let Some(min_len) = rule.min_length {
@@ -708,6 +1260,56 @@ pub(crate) fn collect_policy_violations_for_content(
         }
     }
 
+    // Heading structure checks (levels in document order, code and
+    // frontmatter excluded)
+    if rule.max_heading_level.is_some() || rule.no_skipped_levels {
+        let heading_re = Regex::new(r"^(#{1,6})\s+(.+)$").unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let is_code = code_line_mask(lines.iter().copied());
+        let (_, frontmatter_end) = parse_frontmatter(&lines);
+        let mut previous_level: Option<usize> = None;
+
+        for (idx, line) in lines.iter().enumerate().skip(frontmatter_end) {
+            if is_code[idx] {
+                continue;
+            }
+            let Some(caps) = heading_re.captures(line) else {
+                continue;
+            };
+            let level = caps[1].len();
+            let text = caps[2].trim();
+            let line_number = idx + 1;
+
+            if let Some(max_level) = rule.max_heading_level {
+                if level > max_level {
+                    violations.push(PolicyViolation {
+                        file: file_path.to_string(),
+                        rule: rule_name(rule),
+                        message: format!(
+                            "Heading {text:?} at line {line_number} is H{level} (max allowed: H{max_level})"
+                        ),
+                        severity: rule_severity(rule),
+                        kind: "policy_violation".to_string(),
+                    });
+                }
+            }
+            if rule.no_skipped_levels {
+                if let Some(previous) = previous_level.filter(|previous| level > previous + 1) {
+                    violations.push(PolicyViolation {
+                        file: file_path.to_string(),
+                        rule: rule_name(rule),
+                        message: format!(
+                            "Heading {text:?} at line {line_number} skips from H{previous} to H{level}"
+                        ),
+                        severity: rule_severity(rule),
+                        kind: "policy_violation".to_string(),
+                    });
+                }
+            }
+            previous_level = Some(level);
+        }
+    }
+
     // Section length checks (line count)
     if let Some(max_section_len) = rule.max_section_length {
         let heading_filter = match rule.section_heading_regex.as_deref() {
@@ -757,7 +1359,10 @@ pub(crate) fn collect_policy_violations_for_content(
 
     // Required link checks
     if !rule.must_link_to.is_empty() {
-        let targets = extract_markdown_link_targets(file_path, content);
+        let targets = match entry {
+            Some(entry) => entry_link_targets(file_path, entry),
+            None => extract_markdown_link_targets(file_path, content),
+        };
         let mut target_paths: HashSet<String> = HashSet::new();
         let mut target_keys: HashSet<String> = HashSet::new();
 
@@ -793,52 +1398,148 @@ pub(crate) fn collect_policy_violations_for_content(
     violations
 }
 
+/// Check every indexed file against the policy's rules. With `explain`,
+/// the result also lists each rule's matched files and how each fared.
 pub(crate) fn run_policy_check(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     policy_path: &Path,
+    explain: bool,
 ) -> Result<PolicyCheckResult, Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
     let policy = load_policy_config(policy_path)?;
 
     let mut violations = Vec::new();
+    let mut explanations = Vec::new();
 
     for rule in &policy.rules {
         let glob = Glob::new(&rule.pattern)?;
         let matcher = glob.compile_matcher();
+        let patterns = compile_policy_patterns(rule)?;
+        let mut matched_files = 0;
+        let mut outcomes = Vec::new();
 
-        for file_path in forward_index.files.keys() {
+        for (file_path, entry) in &forward_index.files {
             if !matcher.is_match(file_path.as_str()) {
                 continue;
             }
+            matched_files += 1;
 
             let content = fs::read_to_string(file_path.as_str())?;
-            let mut rule_violations =
-                collect_policy_violations_for_content(rule, file_path, &content);
+            let mut rule_violations = collect_policy_violations_with_patterns(
+                rule,
+                &patterns,
+                file_path,
+                &content,
+                Some(entry),
+            );
+            if explain {
+                outcomes.push(PolicyFileOutcome {
+                    file: file_path.clone(),
+                    passed: rule_violations.is_empty(),
+                    failed: rule_violations.iter().map(|v| v.message.clone()).collect(),
+                });
+            }
             violations.append(&mut rule_violations);
         }
+
+        let count_violation = file_count_violation(rule, matched_files);
+        if explain {
+            outcomes.sort_by(|a, b| a.file.cmp(&b.file));
+            explanations.push(PolicyRuleExplanation {
+                rule: rule_name(rule),
+                pattern: rule.pattern.clone(),
+                failed: count_violation.iter().map(|v| v.message.clone()).collect(),
+                files: outcomes,
+            });
+        }
+        violations.extend(count_violation);
     }
 
     Ok(PolicyCheckResult {
         policy_file: policy_path.to_string_lossy().to_string(),
         total_violations: violations.len(),
         violations,
+        rules: explain.then_some(explanations),
+    })
+}
+
+/// Check a rule's `min_files` / `max_files` bounds against the number of
+/// indexed files its pattern matched. The violation belongs to the rule as
+/// a whole, so its `file` is the pattern.
+pub(crate) fn file_count_violation(
+    rule: &PolicyRule,
+    matched_files: usize,
+) -> Option<PolicyViolation> {
+    let message = match (rule.min_files, rule.max_files) {
+        (Some(min), _) if matched_files < min => {
+            format!("Pattern matched {matched_files} files, expected at least {min}")
+        }
+        (_, Some(max)) if matched_files > max => {
+            format!("Pattern matched {matched_files} files, expected at most {max}")
+        }
+        _ => return None,
+    };
+    Some(PolicyViolation {
+        file: rule.pattern.clone(),
+        rule: rule_name(rule),
+        message,
+        severity: rule_severity(rule),
+        kind: "policy_violation".to_string(),
     })
 }
 
+/// Whether any violation is severe enough to fail a run.
+///
+/// `fail_on` is `"error"` (error severity only) or `"warn"` (warnings too).
+pub(crate) fn policy_should_fail(
+    result: &PolicyCheckResult,
+    fail_on: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let include_warnings = match fail_on {
+        "error" => false,
+        "warn" | "warning" => true,
+        other => {
+            return Err(
+                format!("Unsupported --fail-on value: {other} (expected error or warn)").into(),
+            )
+        }
+    };
+
+    Ok(result.violations.iter().any(|v| match v.severity.as_str() {
+        "error" => true,
+        "warn" | "warning" => include_warnings,
+        _ => false,
+    }))
+}
+
+/// Run policy checks and print the results.
+///
+/// Returns `true` when a violation at or above `fail_on` severity was
+/// found, so the caller can set the exit status; `None` never fails.
 pub(crate) fn cmd_policy(
     config_path: &Path,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
+    fail_on: Option<&str>,
+    explain: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
     if !config_path.exists() {
         return Err(format!("Policy file not found: {}", config_path.display()).into());
     }
 
-    let result = run_policy_check(index_dir, config_path)?;
+    let result = run_policy_check(index_dir, config_path, explain)?;
+    let should_fail = match fail_on {
+        Some(level) => policy_should_fail(&result, level)?,
+        None => false,
+    };
 
     if json {
         println!("{}", serde_json::to_string_pretty(&result)?);
-        return Ok(());
+        return Ok(should_fail);
+    }
+
+    if let Some(explanations) = &result.rules {
+        print_policy_explanations(explanations);
     }
 
     if result.violations.is_empty() {
@@ -847,7 +1548,7 @@ pub(crate) fn cmd_policy(
             "✓".green().bold(),
             result.policy_file
         );
-        return Ok(());
+        return Ok(should_fail);
     }
 
     println!(
@@ -869,16 +1570,42 @@ pub(crate) fn cmd_policy(
 
     println!("Total violations: {}", result.total_violations);
 
-    Ok(())
+    Ok(should_fail)
 }
 
-/// Suggest a new link target based on available files in the index.
-/// Very conservative: only rewrites when there is exactly one file with
-/// the same filename as the link target and that file lives under the
-/// same parent directory as the source file.
-/// Find all candidate files that match the broken link's filename
-pub(crate) fn find_link_candidates(
-    source_file: &str,
+/// Print each rule's matched files with a pass mark or the checks they failed.
+fn print_policy_explanations(explanations: &[PolicyRuleExplanation]) {
+    println!("{}", "Policy rule coverage".cyan().bold());
+    println!("{}", "=".repeat(60));
+    println!();
+
+    for explanation in explanations {
+        println!(
+            "{} ({}): {} matched",
+            explanation.rule.white().bold(),
+            explanation.pattern,
+            explanation.files.len()
+        );
+        for message in &explanation.failed {
+            println!("  {} {message}", "✗".red().bold());
+        }
+        for outcome in &explanation.files {
+            if outcome.passed {
+                println!("  {} {}", "✓".green().bold(), outcome.file);
+            } else {
+                println!("  {} {}", "✗".red().bold(), outcome.file);
+                for message in &outcome.failed {
+                    println!("      - {message}");
+                }
+            }
+        }
+        println!();
+    }
+}
+
+/// Find all indexed files whose filename matches the broken link's filename,
+/// returned as index paths in sorted order.
+pub(crate) fn find_link_candidate_files(
     link_path: &str,
     available_files: &HashSet<String>,
 ) -> Vec<String> {
@@ -890,11 +1617,7 @@ pub(crate) fn find_link_candidates(
         return vec![];
     };
 
-    let source_path = Path::new(source_file);
-    let source_parent = source_path.parent().unwrap_or(Path::new("."));
-
-    // Find all candidates whose filename matches
-    let mut candidates: Vec<String> = available_files
+    let mut files: Vec<String> = available_files
         .iter()
         .filter(|p| {
             Path::new(p)
@@ -902,24 +1625,86 @@ pub(crate) fn find_link_candidates(
                 .and_then(|s| s.to_str())
                 .is_some_and(|name| name == link_filename)
         })
-        .map(|candidate| {
-            // Try to create a relative path from source to candidate
-            let candidate_path = Path::new(candidate);
-            if let Ok(stripped) = candidate_path.strip_prefix(source_parent) {
-                let rel = stripped.to_string_lossy().to_string();
-                if !rel.is_empty() {
-                    return rel;
-                }
-            }
-            // Fall back to returning the full path
-            candidate.clone()
-        })
+        .cloned()
+        .collect();
+
+    files.sort();
+    files
+}
+
+/// Express an indexed file as a link path relative to the source file's
+/// directory, walking up with `../` when it lives outside that directory.
+pub(crate) fn relative_link_target(
+    source_file: &str,
+    candidate: &str,
+    available_files: &HashSet<String>,
+) -> String {
+    compute_relative_path(source_file, candidate, available_files)
+        .filter(|rel| !rel.is_empty())
+        .unwrap_or_else(|| candidate.to_string())
+}
+
+/// Find all candidate files that match the broken link's filename.
+/// Each candidate is returned as a link path relative to the source file's
+/// directory (e.g. `auth.md`, `guide/auth.md`, or `../other/auth.md`).
+pub(crate) fn find_link_candidates(
+    source_file: &str,
+    link_path: &str,
+    available_files: &HashSet<String>,
+) -> Vec<String> {
+    let mut candidates: Vec<String> = find_link_candidate_files(link_path, available_files)
+        .iter()
+        .map(|candidate| relative_link_target(source_file, candidate, available_files))
         .collect();
 
     candidates.sort();
     candidates
 }
 
+/// Pick the most canonical of several indexed candidates for a broken link.
+/// Candidates are ranked by `score_canonicality_with_reasons` (path and
+/// filename heuristics), with ties going to the shortest path and then to
+/// path order so the choice is stable across runs.
+pub(crate) fn resolve_canonical_candidate(
+    candidates: &[String],
+    forward_index: &ForwardIndex,
+) -> Option<CanonicalResolution> {
+    let mut scored: Vec<(&String, f64, Vec<String>)> = candidates
+        .iter()
+        .filter_map(|path| {
+            let entry = forward_index.files.get(path)?;
+            let (score, reasons) =
+                score_canonicality_with_reasons(path, entry, None, builtin_canonicality_rules());
+            Some((path, score, reasons))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.len().cmp(&b.0.len()))
+            .then_with(|| a.0.cmp(b.0))
+    });
+
+    let mut scored = scored.into_iter();
+    let (chosen, score, reasons) = scored.next()?;
+    Some(CanonicalResolution {
+        chosen: chosen.clone(),
+        score,
+        reasons,
+        rejected: scored
+            .map(|(path, score, _)| RejectedCandidate {
+                path: path.clone(),
+                score,
+            })
+            .collect(),
+    })
+}
+
+/// Suggest a new link target based on available files in the index.
+/// Conservative: only rewrites when exactly one indexed file shares the
+/// link target's filename, and returns the relative path to it from the
+/// source file's directory.
 #[allow(dead_code)] // Utility for future interactive fix mode
 pub(crate) fn suggest_new_link_target(
     source_file: &str,
@@ -935,14 +1720,18 @@ pub(crate) fn suggest_new_link_target(
 }
 
 pub(crate) fn cmd_fix_links(
-    index_dir: &Path,
-    dry_run: bool,
-    apply: bool,
-    propose: Option<PathBuf>,
-    apply_decisions: Option<PathBuf>,
-    json: bool,
-    use_git_history: bool,
+    index_dir: &IndexDir,
+    options: FixLinksOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let FixLinksOptions {
+        dry_run,
+        apply,
+        propose,
+        apply_decisions,
+        json,
+        use_git_history,
+        prefer_canonical,
+    } = options;
     // Handle apply-decisions mode: read and apply a proposal file
     if let Some(decisions_path) = apply_decisions {
         return apply_link_decisions(&decisions_path, dry_run, json);
@@ -953,6 +1742,8 @@ pub(crate) fn cmd_fix_links(
     if !propose_mode && !dry_run && !apply {
         return Err("Specify --dry-run, --apply, or --propose <file>".into());
     }
+    // --dry-run wins over --apply: never write when previewing
+    let apply = apply && !dry_run;
 
     let forward_index = load_forward_index(index_dir)?;
     let available_files: HashSet<String> = forward_index.files.keys().cloned().collect();
@@ -1016,7 +1807,12 @@ pub(crate) fn cmd_fix_links(
             }
 
             // Find candidates using index-based matching
-            let mut candidates = find_link_candidates(file_path, &link_path, &available_files);
+            let candidate_files = find_link_candidate_files(&link_path, &available_files);
+            let mut candidates: Vec<String> = candidate_files
+                .iter()
+                .map(|candidate| relative_link_target(file_path, candidate, &available_files))
+                .collect();
+            candidates.sort();
 
             // If no candidates found and git history is available, check for renames
             if candidates.is_empty() {
@@ -1054,8 +1850,28 @@ pub(crate) fn cmd_fix_links(
                         file: file_path.clone(),
                         old_target: target.clone(),
                         new_target,
+                        resolution: None,
                     });
                 }
+            } else if prefer_canonical {
+                // Multiple candidates - pick the most canonical one
+                let Some(resolution) =
+                    resolve_canonical_candidate(&candidate_files, &forward_index)
+                else {
+                    continue;
+                };
+                let mut new_target =
+                    relative_link_target(file_path, &resolution.chosen, &available_files);
+                if let Some(ref a) = anchor {
+                    new_target.push('#');
+                    new_target.push_str(a);
+                }
+                fixes.push(LinkFix {
+                    file: file_path.clone(),
+                    old_target: target.clone(),
+                    new_target,
+                    resolution: Some(resolution),
+                });
             } else if propose_mode {
                 // Multiple candidates - add to proposals
                 proposals.push(LinkFixProposal {
@@ -1110,33 +1926,31 @@ pub(crate) fn cmd_fix_links(
     // Regular fix mode (dry-run or apply)
     if fixes.is_empty() {
         if json {
-            println!(r#"{{"fixes": [], "applied": false}}"#);
+            let result = FixLinksResult {
+                fixes,
+                applied: false,
+                message: None,
+            };
+            println!("{}", serde_json::to_string_pretty(&result)?);
         } else {
             println!("{}", "No safe link fixes found.".green().bold());
         }
         return Ok(());
     }
 
-    // Group fixes by file
-    let mut fixes_by_file: HashMap<String, Vec<LinkFix>> = HashMap::new();
-    for fix in &fixes {
-        fixes_by_file
-            .entry(fix.file.clone())
-            .or_default()
-            .push(fix.clone());
-    }
+    fixes.sort_by(|a, b| {
+        a.file
+            .cmp(&b.file)
+            .then_with(|| a.old_target.cmp(&b.old_target))
+    });
+    let fixes_by_file = group_link_fixes_by_file(&fixes);
 
     if json {
-        let result = serde_json::json!({
-            "fixes": fixes.iter().map(|f| {
-                serde_json::json!({
-                    "file": f.file,
-                    "old_target": f.old_target,
-                    "new_target": f.new_target
-                })
-            }).collect::<Vec<_>>(),
-            "applied": apply
-        });
+        let result = FixLinksResult {
+            fixes: fixes.clone(),
+            applied: apply,
+            message: None,
+        };
         println!("{}", serde_json::to_string_pretty(&result)?);
     } else {
         println!(
@@ -1148,23 +1962,28 @@ pub(crate) fn cmd_fix_links(
             println!("{}", file.white().bold());
             for f in file_fixes {
                 println!("  {} -> {}", f.old_target.red(), f.new_target.green());
+                if let Some(ref resolution) = f.resolution {
+                    println!(
+                        "    {} {} (canonicality {:.2})",
+                        "chose".cyan(),
+                        resolution.chosen,
+                        resolution.score
+                    );
+                    for rejected in &resolution.rejected {
+                        println!(
+                            "    {} {} (canonicality {:.2})",
+                            "rejected".dimmed(),
+                            rejected.path,
+                            rejected.score
+                        );
+                    }
+                }
             }
         }
     }
 
     if apply {
-        for (file, file_fixes) in &fixes_by_file {
-            let content = fs::read_to_string(file)?;
-            let mut new_content = content.clone();
-            for f in file_fixes {
-                let old = format!("]({})", f.old_target);
-                let new = format!("]({})", f.new_target);
-                new_content = new_content.replace(&old, &new);
-            }
-            if new_content != content {
-                fs::write(file, new_content)?;
-            }
-        }
+        write_link_fixes(&fixes_by_file)?;
         if !json {
             println!("{}", "Link fixes applied.".green().bold());
         }
@@ -1196,6 +2015,7 @@ pub(crate) fn apply_link_decisions(
                     file: proposal.source.clone(),
                     old_target: proposal.broken_target.clone(),
                     new_target,
+                    resolution: None,
                 });
             }
         }
@@ -1203,9 +2023,12 @@ pub(crate) fn apply_link_decisions(
 
     if fixes.is_empty() {
         if json {
-            println!(
-                r#"{{"fixes": [], "applied": false, "message": "No decisions made in proposal file"}}"#
-            );
+            let result = FixLinksResult {
+                fixes,
+                applied: false,
+                message: Some("No decisions made in proposal file".to_string()),
+            };
+            println!("{}", serde_json::to_string_pretty(&result)?);
         } else {
             println!(
                 "{} No decisions found in {}. Set 'decision' field to candidate index.",
@@ -1217,25 +2040,14 @@ pub(crate) fn apply_link_decisions(
     }
 
     // Group and apply
-    let mut fixes_by_file: HashMap<String, Vec<LinkFix>> = HashMap::new();
-    for fix in &fixes {
-        fixes_by_file
-            .entry(fix.file.clone())
-            .or_default()
-            .push(fix.clone());
-    }
+    let fixes_by_file = group_link_fixes_by_file(&fixes);
 
     if json {
-        let result = serde_json::json!({
-            "fixes": fixes.iter().map(|f| {
-                serde_json::json!({
-                    "file": f.file,
-                    "old_target": f.old_target,
-                    "new_target": f.new_target
-                })
-            }).collect::<Vec<_>>(),
-            "applied": !dry_run
-        });
+        let result = FixLinksResult {
+            fixes: fixes.clone(),
+            applied: !dry_run,
+            message: None,
+        };
         println!("{}", serde_json::to_string_pretty(&result)?);
     } else {
         println!(
@@ -1252,18 +2064,7 @@ pub(crate) fn apply_link_decisions(
     }
 
     if !dry_run {
-        for (file, file_fixes) in &fixes_by_file {
-            let content = fs::read_to_string(file)?;
-            let mut new_content = content.clone();
-            for f in file_fixes {
-                let old = format!("]({})", f.old_target);
-                let new = format!("]({})", f.new_target);
-                new_content = new_content.replace(&old, &new);
-            }
-            if new_content != content {
-                fs::write(file, new_content)?;
-            }
-        }
+        write_link_fixes(&fixes_by_file)?;
         if !json {
             println!("{}", "Link fixes applied.".green().bold());
         }
@@ -1272,10 +2073,116 @@ pub(crate) fn apply_link_decisions(
     Ok(())
 }
 
-pub(crate) fn apply_reference_mapping_to_content(content: &str, from: &str, to: &str) -> String {
-    let old = format!("]({from})");
-    let new = format!("]({to})");
-    content.replace(&old, &new)
+/// Group link fixes by the file they edit, in path order.
+fn group_link_fixes_by_file(fixes: &[LinkFix]) -> BTreeMap<String, Vec<LinkFix>> {
+    let mut fixes_by_file: BTreeMap<String, Vec<LinkFix>> = BTreeMap::new();
+    for fix in fixes {
+        fixes_by_file
+            .entry(fix.file.clone())
+            .or_default()
+            .push(fix.clone());
+    }
+    fixes_by_file
+}
+
+/// Rewrite each file's link targets on disk in a single pass.
+fn write_link_fixes(
+    fixes_by_file: &BTreeMap<String, Vec<LinkFix>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (file, file_fixes) in fixes_by_file {
+        let content = fs::read_to_string(file)?;
+        let new_content = apply_link_rewrites_to_content(&content, file_fixes);
+        if new_content != content {
+            fs::write(file, new_content)?;
+        }
+    }
+    Ok(())
+}
+
+/// A `fix-references` mapping, with its regex compiled, ready to apply to
+/// link targets.
+pub(crate) struct CompiledReferenceMapping<'a> {
+    pub mapping: &'a ReferenceMapping,
+    regex: Option<Regex>,
+}
+
+impl<'a> CompiledReferenceMapping<'a> {
+    pub(crate) fn new(mapping: &'a ReferenceMapping) -> Result<Self, Box<dyn std::error::Error>> {
+        let regex = match mapping.kind {
+            ReferenceMappingKind::Regex => Some(
+                Regex::new(&mapping.from)
+                    .map_err(|e| format!("Invalid regex mapping '{}': {e}", mapping.from))?,
+            ),
+            ReferenceMappingKind::Literal | ReferenceMappingKind::Prefix => None,
+        };
+        Ok(Self { mapping, regex })
+    }
+
+    /// Rewrite one link target, or `None` when the mapping leaves it unchanged.
+    pub(crate) fn rewrite(&self, target: &str) -> Option<String> {
+        let ReferenceMapping { from, to, .. } = self.mapping;
+        let rewritten = match self.mapping.kind {
+            ReferenceMappingKind::Literal => (target == from).then(|| to.clone())?,
+            ReferenceMappingKind::Prefix => format!("{to}{}", target.strip_prefix(from.as_str())?),
+            ReferenceMappingKind::Regex => {
+                let regex = self.regex.as_ref()?;
+                if !regex.is_match(target) {
+                    return None;
+                }
+                regex.replace_all(target, to.as_str()).into_owned()
+            }
+        };
+        (rewritten != target).then_some(rewritten)
+    }
+}
+
+/// Apply one reference mapping to every link target in `content`, returning
+/// the updated content and how many links it changed.
+pub(crate) fn apply_reference_mapping_to_content(
+    content: &str,
+    mapping: &CompiledReferenceMapping,
+) -> (String, usize) {
+    let mut changed = 0;
+    let updated = rewrite_link_targets(content, |target| {
+        let rewritten = mapping.rewrite(target)?;
+        changed += 1;
+        Some(rewritten)
+    });
+    (updated, changed)
+}
+
+/// Rewrite link targets in inline `](target)` links and `[ref]: target`
+/// definitions, leaving those for which `rewrite` returns `None` unchanged.
+fn rewrite_link_targets(content: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let link_re = Regex::new(r"(?m)(\]\()([^)]+)(\))|^(\s{0,3}\[[^\]]+\]:\s*<?)([^\s>]+)").unwrap();
+
+    link_re
+        .replace_all(content, |caps: &regex::Captures| {
+            let (prefix, target, suffix) = match caps.get(2) {
+                Some(target) => (&caps[1], target.as_str(), &caps[3]),
+                None => (&caps[4], &caps[5], ""),
+            };
+            let target = rewrite(target).unwrap_or_else(|| target.to_string());
+            format!("{prefix}{target}{suffix}")
+        })
+        .into_owned()
+}
+
+/// Rewrite link targets in a single pass over inline `](target)` links and
+/// `[ref]: target` definitions. Applying every rewrite at once keeps a new
+/// target that equals another link's old target from being rewritten twice.
+pub(crate) fn apply_link_rewrites_to_content(content: &str, rewrites: &[LinkFix]) -> String {
+    if rewrites.is_empty() {
+        return content.to_string();
+    }
+    let targets: HashMap<&str, &str> = rewrites
+        .iter()
+        .map(|f| (f.old_target.as_str(), f.new_target.as_str()))
+        .collect();
+
+    rewrite_link_targets(content, |target| {
+        targets.get(target).map(|new| (*new).to_string())
+    })
 }
 
 pub(crate) fn load_reference_mappings(
@@ -1287,7 +2194,7 @@ pub(crate) fn load_reference_mappings(
 }
 
 pub(crate) fn cmd_fix_references(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     mapping_path: &Path,
     dry_run: bool,
     apply: bool,
@@ -1296,6 +2203,8 @@ pub(crate) fn cmd_fix_references(
     if !dry_run && !apply {
         return Err("Specify either --dry-run or --apply".into());
     }
+    // --dry-run wins over --apply: never write when previewing
+    let apply = apply && !dry_run;
     if !mapping_path.exists() {
         return Err(format!("Mapping file not found: {}", mapping_path.display()).into());
     }
@@ -1307,6 +2216,7 @@ pub(crate) fn cmd_fix_references(
                 mapping_file: mapping_path.to_string_lossy().to_string(),
                 mappings_count: 0,
                 updated_files: vec![],
+                file_changes: vec![],
                 applied: apply,
             };
             println!("{}", serde_json::to_string_pretty(&result)?);
@@ -1320,35 +2230,58 @@ pub(crate) fn cmd_fix_references(
         return Ok(());
     }
 
+    // Compile every mapping before touching any file
+    let mappings = mappings_cfg
+        .mappings
+        .iter()
+        .map(CompiledReferenceMapping::new)
+        .collect::<Result<Vec<_>, _>>()?;
+
     let forward_index = load_forward_index(index_dir)?;
 
     let mut changed_files: Vec<String> = Vec::new();
+    let mut file_changes: Vec<ReferenceFileChanges> = Vec::new();
 
     for file_path in forward_index.files.keys() {
         let content = fs::read_to_string(file_path)?;
         let mut new_content = content.clone();
-
-        for m in &mappings_cfg.mappings {
-            new_content = apply_reference_mapping_to_content(&new_content, &m.from, &m.to);
+        let mut counts = Vec::new();
+
+        // Mappings apply in order, each to the output of the previous one
+        for m in &mappings {
+            let (updated, links) = apply_reference_mapping_to_content(&new_content, m);
+            new_content = updated;
+            if links > 0 {
+                counts.push(ReferenceMappingCount {
+                    from: m.mapping.from.clone(),
+                    to: m.mapping.to.clone(),
+                    kind: m.mapping.kind,
+                    links,
+                });
+            }
         }
 
         if new_content != content {
-            if dry_run {
-                changed_files.push(file_path.clone());
-            } else if apply {
+            if apply {
                 fs::write(file_path, new_content)?;
-                changed_files.push(file_path.clone());
             }
+            changed_files.push(file_path.clone());
+            file_changes.push(ReferenceFileChanges {
+                file: file_path.clone(),
+                mappings: counts,
+            });
         }
     }
 
     changed_files.sort();
+    file_changes.sort_by(|a, b| a.file.cmp(&b.file));
 
     if json {
         let result = FixReferencesResult {
             mapping_file: mapping_path.to_string_lossy().to_string(),
             mappings_count: mappings_cfg.mappings.len(),
             updated_files: changed_files,
+            file_changes,
             applied: apply,
         };
         println!("{}", serde_json::to_string_pretty(&result)?);
@@ -1368,10 +2301,27 @@ pub(crate) fn cmd_fix_references(
             changed_files.len(),
             mapping_path.display()
         );
-        for f in changed_files {
-            println!("  {f}");
+        for change in &file_changes {
+            println!("  {}", change.file);
+            for count in &change.mappings {
+                println!(
+                    "    {} -> {} ({}): {} link(s)",
+                    count.from,
+                    count.to,
+                    reference_mapping_kind_label(count.kind),
+                    count.links
+                );
+            }
         }
     }
 
     Ok(())
 }
+
+fn reference_mapping_kind_label(kind: ReferenceMappingKind) -> &'static str {
+    match kind {
+        ReferenceMappingKind::Literal => "literal",
+        ReferenceMappingKind::Prefix => "prefix",
+        ReferenceMappingKind::Regex => "regex",
+    }
+}
@@ -1,6 +1,6 @@
 use crate::commands_audit::*;
 use colored::Colorize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::time::Instant;
 
@@ -25,14 +25,14 @@ pub(crate) fn build_query_diagnostics(
     let tokens = parsed.terms.clone();
     let stems: Vec<String> = tokens
         .iter()
-        .map(|t| stem_word(&t.to_lowercase()))
+        .map(|t| stem_word_with(t, forward_index.stemmer))
         .collect();
     let mut missing_set: HashSet<String> = HashSet::new();
     let mut missing_terms = Vec::new();
     let mut idf_values = Vec::new();
 
     for term in &tokens {
-        let stem = stem_word(&term.to_lowercase());
+        let stem = stem_word_with(term, forward_index.stemmer);
         let idf = *forward_index.idf_map.get(&stem).unwrap_or(&0.0);
         idf_values.push((term.clone(), stem.clone(), idf));
         if !forward_index.idf_map.contains_key(&stem) && missing_set.insert(term.clone()) {
@@ -123,8 +123,79 @@ pub(crate) struct QueryOptions {
     pub explain: bool,
     pub require_phrases: bool,
     pub filter_stopwords: bool,
+    pub tags: Vec<String>,
+    /// Show the best-matching lines of each result
+    pub snippets: bool,
+    /// Ranked results to skip before `limit`; when set, JSON output is
+    /// wrapped with paging fields
+    pub offset: Option<usize>,
+    /// Results scoring below this are dropped before paging; when set,
+    /// JSON output is wrapped and counts them
+    pub min_score: Option<f64>,
+    /// Aliases scored as the query term they expand
+    pub synonyms: Synonyms,
+    /// Documents never ranked
+    pub scope: PathScope,
+    /// Weight of heading term occurrences relative to body occurrences
+    pub heading_boost: f64,
+    /// Extra weight for documents whose inline code names a query term
+    pub code_boost: f64,
+    /// Group the shown results by this many leading directory components;
+    /// `None` lists them flat
+    pub group_depth: Option<usize>,
 }
 
+/// Ranked query results that share a directory prefix.
+#[derive(Debug, PartialEq)]
+pub(crate) struct QueryDirGroup {
+    pub dir: String,
+    /// Sum of the member scores
+    pub score: f64,
+    /// Indices into the ranked results, in rank order
+    pub members: Vec<usize>,
+}
+
+/// First `depth` directory components of an indexed path; files above that
+/// depth use their own directory, and top-level files use ".".
+pub(crate) fn dir_prefix(path: &str, depth: usize) -> String {
+    let path = path.trim_start_matches("./");
+    let dirs: Vec<&str> = path.split('/').collect();
+    let dirs = &dirs[..dirs.len() - 1];
+    if dirs.is_empty() {
+        return ".".to_string();
+    }
+    dirs[..depth.min(dirs.len())].join("/")
+}
+
+/// Group ranked results by `dir_prefix`, highest summed score first.
+pub(crate) fn group_results_by_dir(results: &[(String, f64)], depth: usize) -> Vec<QueryDirGroup> {
+    let mut groups: Vec<QueryDirGroup> = Vec::new();
+    for (i, (path, score)) in results.iter().enumerate() {
+        let dir = dir_prefix(path, depth);
+        match groups.iter_mut().find(|group| group.dir == dir) {
+            Some(group) => {
+                group.score += score;
+                group.members.push(i);
+            }
+            None => groups.push(QueryDirGroup {
+                dir,
+                score: *score,
+                members: vec![i],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.dir.cmp(&b.dir))
+    });
+    groups
+}
+
+/// Lines shown per result by `query --snippets`
+const QUERY_SNIPPET_LINES: usize = 2;
+
 pub(crate) struct AssembleOptions {
     pub max_tokens: usize,
     pub max_sections: usize,
@@ -132,6 +203,88 @@ pub(crate) struct AssembleOptions {
     pub format: String,
     pub doc_terms: usize,
     pub use_relations: bool,
+    /// SimHash similarity at which selected sections are merged; `None`
+    /// disables the pass
+    pub dedup_threshold: Option<f64>,
+    /// Aliases scored as the query term they expand
+    pub synonyms: Synonyms,
+    /// Documents kept in (or out of) both search and cross-reference
+    /// expansion
+    pub scope: PathScope,
+    /// Canonicality share of the combined section and document ranking
+    pub canonicality_weight: f64,
+    /// Weight of heading term occurrences relative to body occurrences
+    pub heading_boost: f64,
+    /// Token limits for cross-reference expansion
+    pub xref: XrefConfig,
+    /// Tokenizer used for every token budget (`--tokenizer`)
+    pub tokenizer: TokenizerKind,
+    /// Print per-phase timings to stderr (`--timing`)
+    pub timing: bool,
+}
+
+/// `assemble`'s flag defaults, which `eval` shares
+impl Default for AssembleOptions {
+    fn default() -> Self {
+        AssembleOptions {
+            max_tokens: 8000,
+            max_sections: 20,
+            depth: 1,
+            format: "markdown".to_string(),
+            doc_terms: 0,
+            use_relations: false,
+            dedup_threshold: Some(DEFAULT_DEDUP_THRESHOLD),
+            synonyms: Synonyms::default(),
+            scope: PathScope::default(),
+            canonicality_weight: DEFAULT_CANONICALITY_WEIGHT,
+            heading_boost: DEFAULT_HEADING_BOOST,
+            xref: XrefConfig::DEFAULT,
+            tokenizer: TokenizerKind::default(),
+            timing: false,
+        }
+    }
+}
+
+impl AssembleOptions {
+    /// Reject out-of-range weights, fractions, and thresholds.
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if !(0.0..=1.0).contains(&self.canonicality_weight) {
+            return Err(format!(
+                "--canonicality-weight must be between 0.0 and 1.0, got {}",
+                self.canonicality_weight
+            )
+            .into());
+        }
+        validate_boost("--heading-boost", self.heading_boost)?;
+        if !(0.0..=1.0).contains(&self.xref.fraction) {
+            return Err(format!(
+                "--xref-fraction must be between 0.0 and 1.0, got {}",
+                self.xref.fraction
+            )
+            .into());
+        }
+        if let Some(threshold) = self.dedup_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(format!(
+                    "--dedup-threshold must be between 0.0 and 1.0, got {threshold}"
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct DupesOptions {
+    pub threshold: f64,
+    pub group: bool,
+    pub json: bool,
+    pub csv: bool,
+    pub weights: SimilarityWeights,
+    /// LSH band count; `None` derives it from the index's MinHash length
+    pub lsh_bands: Option<usize>,
+    /// Print detection time to stderr (`--timing`)
+    pub timing: bool,
 }
 
 pub(crate) struct HealthOptions {
@@ -141,12 +294,79 @@ pub(crate) struct HealthOptions {
     pub max_changelog_entries: usize,
 }
 
+/// True when the entry's frontmatter `tags` field lists every requested tag.
+pub(crate) fn entry_has_tags(entry: &FileEntry, tags: &[String]) -> bool {
+    let doc_tags: HashSet<String> = entry
+        .frontmatter
+        .get("tags")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|tag| tag.trim().to_lowercase())
+                .filter(|tag| !tag.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    tags.iter()
+        .all(|tag| doc_tags.contains(&tag.trim().to_lowercase()))
+}
+
+/// Order `(path, score)` pairs by descending score, then ascending path.
+fn compare_ranked_paths(a: &(String, f64), b: &(String, f64)) -> std::cmp::Ordering {
+    b.1.partial_cmp(&a.1)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.0.cmp(&b.0))
+}
+
+/// Read an indexed document once per query; unreadable files yield "".
+fn cached_doc<'a>(
+    cache: &'a mut HashMap<String, String>,
+    index: &ForwardIndex,
+    path: &str,
+) -> &'a str {
+    cache.entry(path.to_string()).or_insert_with(|| {
+        index
+            .files
+            .get(path)
+            .and_then(|entry| read_indexed_doc(index, path, entry).ok())
+            .unwrap_or_default()
+    })
+}
+
+/// Highlight words in `text` whose stem matches a query term.
+fn highlight_query_terms(text: &str, terms: &[String], stemmer: StemmerKind) -> String {
+    let term_stems: HashSet<String> = terms.iter().map(|t| stem_word_with(t, stemmer)).collect();
+    let word_re = regex::Regex::new(r"[a-zA-Z][a-zA-Z0-9_-]*").unwrap();
+    word_re
+        .replace_all(text, |caps: &regex::Captures| {
+            let word = &caps[0];
+            if term_stems.contains(&stem_word_with(&word.to_lowercase(), stemmer)) {
+                word.yellow().bold().to_string()
+            } else {
+                word.to_string()
+            }
+        })
+        .into_owned()
+}
+
 pub(crate) fn cmd_query(
     query: &str,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     options: &QueryOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let parsed = parse_query(query, options.filter_stopwords);
+    validate_boost("--heading-boost", options.heading_boost)?;
+    validate_boost("--code-boost", options.code_boost)?;
+    if let Some(min_score) = options.min_score {
+        validate_boost("--min-score", min_score)?;
+    }
+    if options.group_depth == Some(0) {
+        return Err("--group-depth must be at least 1".into());
+    }
+    let reverse_index = load_reverse_index(index_dir)?;
+    let forward_index = load_forward_index(index_dir)?;
+    options.scope.check_types(&forward_index)?;
+    let stop_words = &forward_index.stop_words;
+    let parsed = parse_query(query, options.filter_stopwords.then_some(stop_words));
     if parsed.terms.is_empty() {
         if options.json {
             let obj = serde_json::json!({
@@ -163,41 +383,95 @@ pub(crate) fn cmd_query(
         }
         return Ok(());
     }
-    let _reverse_index = load_reverse_index(index_dir)?;
-    let forward_index = load_forward_index(index_dir)?;
+    warn_if_missing_bm25(&forward_index);
     let diagnostics = build_query_diagnostics(&parsed, &forward_index, index_dir);
 
     // Compute BM25 scores for all documents
     let mut file_scores: Vec<(String, f64)> = forward_index
         .files
         .iter()
+        .filter(|(path, _)| !options.scope.is_excluded(path))
         .map(|(path, entry)| {
-            let score = bm25_score(
-                &parsed.terms,
+            let score = bm25_score_fields(
+                &parsed,
                 entry,
                 forward_index.avg_doc_length,
                 &forward_index.idf_map,
-            );
+                forward_index.stemmer,
+                &options.synonyms,
+                options.heading_boost,
+            ) * code_term_boost(&parsed.terms, entry, options.code_boost);
             (path.clone(), score)
         })
         .filter(|(_, score)| *score > 0.0)
+        .filter(|(path, _)| {
+            options.tags.is_empty()
+                || forward_index
+                    .files
+                    .get(path)
+                    .is_some_and(|entry| entry_has_tags(entry, &options.tags))
+        })
         .collect();
 
-    // Sort by BM25 score (descending)
-    file_scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    let results = if parsed.phrases.is_empty() {
-        file_scores.truncate(options.limit);
+    // Boolean operators narrow the BM25 candidates; survivors keep their scores
+    if let Some(filter) = &parsed.filter {
+        file_scores.retain(|(path, _)| {
+            let Some(entry) = forward_index.files.get(path) else {
+                return false;
+            };
+            let mut cached_terms: Option<Vec<String>> = None;
+            let mut content_terms = || {
+                cached_terms
+                    .get_or_insert_with(|| {
+                        let content =
+                            read_indexed_doc(&forward_index, path, entry).unwrap_or_default();
+                        extract_keywords_with_options(&content, None)
+                    })
+                    .clone()
+            };
+            query_expr_matches(
+                filter,
+                entry,
+                forward_index.stemmer,
+                stop_words,
+                &mut content_terms,
+            )
+        });
+    }
+
+    // Documents read for phrase checks are reused for snippets
+    let mut doc_cache: HashMap<String, String> = HashMap::new();
+
+    // Sort by BM25 score (descending), ties by path so pages never overlap
+    file_scores.sort_by(compare_ranked_paths);
+    let mut ranked = if parsed.phrases.is_empty() {
+        file_scores
+    } else if forward_index.positions {
+        // Positional index: quoted phrases must match exactly
+        file_scores.retain(|(path, _)| {
+            forward_index.files.get(path).is_some_and(|entry| {
+                parsed.phrases.iter().all(|phrase| {
+                    phrase_matches_positions(
+                        entry,
+                        &phrase.terms,
+                        forward_index.stemmer,
+                        stop_words,
+                    )
+                })
+            })
+        });
         file_scores
     } else {
+        let wanted = options.offset.unwrap_or(0).saturating_add(options.limit);
         let candidate_cap = std::cmp::min(
             file_scores.len(),
-            std::cmp::max(options.limit.saturating_mul(10), 100),
+            std::cmp::max(wanted.saturating_mul(10), 100),
         );
         let mut candidates = file_scores[..candidate_cap].to_vec();
 
         for (path, score) in &mut candidates {
-            let content = std::fs::read_to_string(Path::new(path)).unwrap_or_default();
-            let content_terms = extract_keywords_with_options(&content, false);
+            let content = cached_doc(&mut doc_cache, &forward_index, path);
+            let content_terms = extract_keywords_with_options(content, None);
             let mut matched_phrases = 0usize;
 
             for phrase in &parsed.phrases {
@@ -215,13 +489,36 @@ pub(crate) fn cmd_query(
 
         if options.require_phrases {
             candidates.retain(|(_, score)| *score > 0.0);
+        } else {
+            // Unchecked documents still match; they rank below any boost
+            candidates.extend_from_slice(&file_scores[candidate_cap..]);
         }
 
-        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        candidates.truncate(options.limit);
+        candidates.sort_by(compare_ranked_paths);
         candidates
     };
 
+    // Drop weak matches before paging so pages hold only confident results
+    let below_min_score = match options.min_score {
+        Some(min_score) => {
+            let before = ranked.len();
+            ranked.retain(|(_, score)| *score >= min_score);
+            before - ranked.len()
+        }
+        None => 0,
+    };
+
+    // Page through the full ranking
+    let total_matches = ranked.len();
+    let offset = options.offset.unwrap_or(0);
+    ranked.drain(..offset.min(total_matches));
+    ranked.truncate(options.limit);
+    let results = ranked;
+    let heading_hits = find_heading_hits(&reverse_index, &parsed.terms, forward_index.stemmer);
+    let groups = options
+        .group_depth
+        .map(|depth| group_results_by_dir(&results, depth));
+
     if options.json {
         let output: Vec<_> = results
             .iter()
@@ -231,6 +528,9 @@ pub(crate) fn cmd_query(
                     "score": score,
                     "query": query
                 });
+                if let Some(hits) = heading_hits.get(path) {
+                    obj["heading_hits"] = serde_json::json!(hits);
+                }
                 if options.doc_terms > 0 {
                     if let Some(entry) = forward_index.files.get(path) {
                         let top_terms = get_top_doc_terms(
@@ -238,13 +538,39 @@ pub(crate) fn cmd_query(
                             &forward_index.idf_map,
                             &parsed.terms,
                             options.doc_terms,
+                            forward_index.stemmer,
                         );
                         obj["doc_terms"] = serde_json::json!(top_terms);
                     }
                 }
+                if options.snippets {
+                    let snippets = find_query_snippets(
+                        cached_doc(&mut doc_cache, &forward_index, path),
+                        &parsed.terms,
+                        forward_index.stemmer,
+                        QUERY_SNIPPET_LINES,
+                    );
+                    obj["snippets"] = serde_json::json!(snippets);
+                }
                 obj
             })
             .collect();
+        let (results_key, results_json) = match &groups {
+            Some(groups) => {
+                let groups: Vec<_> = groups
+                    .iter()
+                    .map(|group| {
+                        serde_json::json!({
+                            "dir": group.dir,
+                            "score": group.score,
+                            "results": group.members.iter().map(|&i| &output[i]).collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                ("groups", serde_json::json!(groups))
+            }
+            None => ("results", serde_json::json!(output)),
+        };
 
         if options.explain {
             let notice = if output.is_empty() {
@@ -277,11 +603,47 @@ pub(crate) fn cmd_query(
                     serde_json::Value::Null
                 }
             });
-            let wrapped = serde_json::json!({
+            let mut wrapped = serde_json::json!({
                 "query": query,
-                "results": output,
+                "offset": offset,
+                "limit": options.limit,
+                "total_matches": total_matches,
                 "diagnostics": diag_json
             });
+            wrapped[results_key] = results_json;
+            if let Some(depth) = options.group_depth {
+                wrapped["group_depth"] = serde_json::json!(depth);
+            }
+            if let Some(min_score) = options.min_score {
+                wrapped["min_score"] = serde_json::json!(min_score);
+                wrapped["below_min_score"] = serde_json::json!(below_min_score);
+            }
+            if !options.scope.types.is_empty() {
+                wrapped["types"] = serde_json::json!(options.scope.types);
+            }
+            println!("{}", serde_json::to_string_pretty(&wrapped)?);
+        } else if options.offset.is_some()
+            || options.group_depth.is_some()
+            || options.min_score.is_some()
+            || !options.scope.types.is_empty()
+        {
+            let mut wrapped = serde_json::json!({
+                "query": query,
+                "offset": offset,
+                "limit": options.limit,
+                "total_matches": total_matches
+            });
+            wrapped[results_key] = results_json;
+            if let Some(depth) = options.group_depth {
+                wrapped["group_depth"] = serde_json::json!(depth);
+            }
+            if let Some(min_score) = options.min_score {
+                wrapped["min_score"] = serde_json::json!(min_score);
+                wrapped["below_min_score"] = serde_json::json!(below_min_score);
+            }
+            if !options.scope.types.is_empty() {
+                wrapped["types"] = serde_json::json!(options.scope.types);
+            }
             println!("{}", serde_json::to_string_pretty(&wrapped)?);
         } else {
             println!("{}", serde_json::to_string_pretty(&output)?);
@@ -289,6 +651,13 @@ pub(crate) fn cmd_query(
         return Ok(());
     }
 
+    if below_min_score > 0 && !options.files_only {
+        println!(
+            "{}",
+            format!("{below_min_score} results below --min-score omitted").dimmed()
+        );
+    }
+
     if results.is_empty() {
         println!("{}", "No results found.".yellow());
         if options.explain {
@@ -298,11 +667,46 @@ pub(crate) fn cmd_query(
         return Ok(());
     }
 
-    println!(
-        "{} results for: {}\n",
-        results.len().to_string().green().bold(),
-        parsed.terms.join(" ").cyan()
-    );
+    if options.offset.is_some() {
+        println!(
+            "{} results for: {} ({}-{} of {})\n",
+            results.len().to_string().green().bold(),
+            parsed.terms.join(" ").cyan(),
+            offset + 1,
+            offset + results.len(),
+            total_matches
+        );
+    } else {
+        println!(
+            "{} results for: {}\n",
+            results.len().to_string().green().bold(),
+            parsed.terms.join(" ").cyan()
+        );
+    }
+
+    if let Some(groups) = groups {
+        for group in groups {
+            println!(
+                "{} (score: {:.2}, {} files)",
+                group.dir.cyan().bold(),
+                group.score,
+                group.members.len()
+            );
+            for i in group.members {
+                let (file, score) = &results[i];
+                if options.files_only {
+                    println!("  {file}");
+                } else {
+                    println!("  {} (score: {:.2})", file.cyan(), score);
+                }
+            }
+            println!();
+        }
+        if options.explain {
+            print_query_diagnostics(&diagnostics, true, false);
+        }
+        return Ok(());
+    }
 
     for (file, score) in results {
         if options.files_only {
@@ -318,6 +722,7 @@ pub(crate) fn cmd_query(
                         &forward_index.idf_map,
                         &parsed.terms,
                         options.doc_terms,
+                        forward_index.stemmer,
                     );
                     if !top_terms.is_empty() {
                         println!("  {} {}", "terms:".dimmed(), top_terms.join(", "));
@@ -325,28 +730,30 @@ pub(crate) fn cmd_query(
                 }
             }
 
-            // Show matching headings
-            if let Some(entry) = forward_index.files.get(&file) {
-                for heading in entry.headings.iter().take(3) {
-                    let heading_keywords: HashSet<String> = extract_keywords(&heading.text)
-                        .into_iter()
-                        .map(|k| stem_word(&k))
-                        .collect();
-
-                    let matches: Vec<_> = parsed
-                        .terms
-                        .iter()
-                        .filter(|t| heading_keywords.contains(&stem_word(&t.to_lowercase())))
-                        .collect();
-
-                    if !matches.is_empty() {
-                        println!(
-                            "  {} L{}: {}",
-                            ">".dimmed(),
-                            heading.line.to_string().dimmed(),
-                            heading.text
-                        );
-                    }
+            // Show the headings that contain a query term
+            for hit in heading_hits.get(&file).into_iter().flatten() {
+                println!(
+                    "  {} L{}: {}",
+                    ">".dimmed(),
+                    hit.line.to_string().dimmed(),
+                    hit.heading
+                );
+            }
+
+            if options.snippets {
+                let snippets = find_query_snippets(
+                    cached_doc(&mut doc_cache, &forward_index, &file),
+                    &parsed.terms,
+                    forward_index.stemmer,
+                    QUERY_SNIPPET_LINES,
+                );
+                for snippet in snippets {
+                    println!(
+                        "  {} L{}: {}",
+                        "|".dimmed(),
+                        snippet.line.to_string().dimmed(),
+                        highlight_query_terms(&snippet.text, &parsed.terms, forward_index.stemmer)
+                    );
                 }
             }
             println!();
@@ -366,7 +773,8 @@ pub(crate) fn cmd_similar(
     threshold: f64,
     json: bool,
     doc_terms: usize,
-    index_dir: &Path,
+    weights: SimilarityWeights,
+    index_dir: &IndexDir,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
 
@@ -393,14 +801,6 @@ pub(crate) fn cmd_similar(
         })
         .ok_or_else(|| format!("File not in index: {file_str}"))?;
 
-    // Combine heading and body keywords
-    let ref_keywords: HashSet<String> = ref_entry
-        .keywords
-        .iter()
-        .chain(ref_entry.body_keywords.iter())
-        .map(|k| k.to_lowercase())
-        .collect();
-
     // For doc_terms, exclude the reference file's terms
     let ref_terms_vec: Vec<String> = ref_entry
         .body_keywords
@@ -417,26 +817,23 @@ pub(crate) fn cmd_similar(
             continue;
         }
 
-        let other_keywords: HashSet<String> = entry
-            .keywords
-            .iter()
-            .chain(entry.body_keywords.iter())
-            .map(|k| k.to_lowercase())
-            .collect();
-
-        let jaccard = jaccard_similarity(&ref_keywords, &other_keywords);
-        let simhash_sim = simhash_similarity(ref_entry.simhash, entry.simhash);
-
-        // Combined score: weighted average
-        let combined = jaccard * 0.6 + simhash_sim * 0.4;
-
-        if combined >= threshold {
-            similarities.push((path.clone(), jaccard, simhash_sim, combined));
+        let scores = combined_similarity(ref_entry, entry, weights);
+        if scores.combined >= threshold {
+            similarities.push((
+                path.clone(),
+                scores.jaccard,
+                scores.simhash,
+                scores.combined,
+            ));
         }
     }
 
     // Sort by combined similarity
-    similarities.sort_by(|a, b| b.3.partial_cmp(&a.3).unwrap());
+    similarities.sort_by(|a, b| {
+        b.3.partial_cmp(&a.3)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(&b.0))
+    });
     similarities.truncate(limit);
 
     if json {
@@ -456,6 +853,7 @@ pub(crate) fn cmd_similar(
                             &forward_index.idf_map,
                             &ref_terms_vec,
                             doc_terms,
+                            forward_index.stemmer,
                         );
                         obj["doc_terms"] = serde_json::json!(top_terms);
                     }
@@ -491,8 +889,13 @@ pub(crate) fn cmd_similar(
         // Show doc terms if requested
         if doc_terms > 0 {
             if let Some(entry) = forward_index.files.get(&path) {
-                let top_terms =
-                    get_top_doc_terms(entry, &forward_index.idf_map, &ref_terms_vec, doc_terms);
+                let top_terms = get_top_doc_terms(
+                    entry,
+                    &forward_index.idf_map,
+                    &ref_terms_vec,
+                    doc_terms,
+                    forward_index.stemmer,
+                );
                 if !top_terms.is_empty() {
                     println!(
                         "                   {} {}",
@@ -508,16 +911,27 @@ pub(crate) fn cmd_similar(
 }
 
 pub(crate) fn cmd_dupes(
-    threshold: f64,
-    group: bool,
-    json: bool,
-    index_dir: &Path,
+    options: &DupesOptions,
+    index_dir: &IndexDir,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let &DupesOptions {
+        threshold,
+        group,
+        json,
+        csv,
+        weights,
+        lsh_bands,
+        timing,
+    } = options;
     let forward_index = load_forward_index(index_dir)?;
+    let num_hashes = forward_index.minhash_perms;
+    let bands = lsh_bands.unwrap_or_else(|| default_lsh_bands(num_hashes));
+    validate_lsh_bands(bands, num_hashes)?;
+    warn_if_missing_minhash(&forward_index);
     let start = Instant::now();
 
     // Build LSH buckets for fast duplicate detection
-    let buckets = lsh_buckets(&forward_index.files, 16); // 16 bands x 8 rows = 128 hashes
+    let buckets = lsh_buckets(&forward_index.files, num_hashes, bands);
     let mut candidates: HashSet<(String, String)> = HashSet::new();
 
     // Collect candidate pairs from buckets
@@ -544,32 +958,15 @@ pub(crate) fn cmd_dupes(
             forward_index.files.get(path1),
             forward_index.files.get(path2),
         ) {
-            let kw1: HashSet<String> = entry1
-                .keywords
-                .iter()
-                .chain(entry1.body_keywords.iter())
-                .map(|k| k.to_lowercase())
-                .collect();
-            let kw2: HashSet<String> = entry2
-                .keywords
-                .iter()
-                .chain(entry2.body_keywords.iter())
-                .map(|k| k.to_lowercase())
-                .collect();
-
-            let jaccard = jaccard_similarity(&kw1, &kw2);
-            let simhash_sim = simhash_similarity(entry1.simhash, entry2.simhash);
-            let minhash_sim = minhash_similarity(&entry1.minhash, &entry2.minhash);
-            let combined = jaccard * 0.4 + simhash_sim * 0.3 + minhash_sim * 0.3;
-
-            if combined >= threshold {
+            let scores = combined_similarity(entry1, entry2, weights);
+            if scores.combined >= threshold {
                 duplicates.push((
                     path1.clone(),
                     path2.clone(),
-                    jaccard,
-                    simhash_sim,
-                    minhash_sim,
-                    combined,
+                    scores.jaccard,
+                    scores.simhash,
+                    scores.minhash,
+                    scores.combined,
                 ));
             }
         }
@@ -578,7 +975,11 @@ pub(crate) fn cmd_dupes(
     let elapsed = start.elapsed();
 
     // Sort by combined similarity
-    duplicates.sort_by(|a, b| b.5.partial_cmp(&a.5).unwrap_or(std::cmp::Ordering::Equal));
+    duplicates.sort_by(|a, b| {
+        b.5.partial_cmp(&a.5)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1)))
+    });
 
     if json {
         let output: Vec<_> = duplicates
@@ -598,14 +999,34 @@ pub(crate) fn cmd_dupes(
         return Ok(());
     }
 
+    // One row per pair; --group does not apply
+    if csv {
+        print_csv_row(&[
+            "file1", "file2", "jaccard", "simhash", "minhash", "combined",
+        ]);
+        for (p1, p2, j, s, m, c) in &duplicates {
+            print_csv_row(&[
+                p1.clone(),
+                p2.clone(),
+                j.to_string(),
+                s.to_string(),
+                m.to_string(),
+                c.to_string(),
+            ]);
+        }
+        return Ok(());
+    }
+
     if duplicates.is_empty() {
         println!("{}", "No duplicates found above threshold.".green());
-        eprintln!(
-            "LSH duplicate detection: {:?} ({} candidate pairs from {} buckets)",
-            elapsed,
-            candidates.len(),
-            buckets.len()
-        );
+        if timing {
+            eprintln!(
+                "LSH duplicate detection: {:?} ({} candidate pairs from {} buckets)",
+                elapsed,
+                candidates.len(),
+                buckets.len()
+            );
+        }
         return Ok(());
     }
 
@@ -614,28 +1035,46 @@ pub(crate) fn cmd_dupes(
         duplicates.len().to_string().yellow().bold(),
         (threshold * 100.0) as u32
     );
-    eprintln!(
-        "LSH duplicate detection: {:?} ({} candidates from {} buckets)\n",
-        elapsed,
-        candidates.len(),
-        buckets.len()
-    );
+    if timing {
+        eprintln!(
+            "LSH duplicate detection: {:?} ({} candidates from {} buckets)\n",
+            elapsed,
+            candidates.len(),
+            buckets.len()
+        );
+    }
 
     if group {
-        // Group duplicates
-        let mut groups: HashMap<String, Vec<(String, f64)>> = HashMap::new();
+        // Transitive clusters: A~B and B~C land in one group
+        let pairs: Vec<(String, String, f64)> = duplicates
+            .iter()
+            .map(|(p1, p2, _, _, _, combined)| (p1.clone(), p2.clone(), *combined))
+            .collect();
+        let clusters = duplicate_clusters(&pairs);
 
-        for (path1, path2, _, _, _, combined) in &duplicates {
-            let group = groups.entry(path1.clone()).or_default();
-            if !group.iter().any(|(p, _)| p == path2) {
-                group.push((path2.clone(), *combined));
+        for (idx, members) in clusters.iter().enumerate() {
+            println!(
+                "{} ({} files)",
+                format!("Cluster {}", idx + 1).cyan().bold(),
+                members.len()
+            );
+            for member in members {
+                println!("  {member}");
             }
-        }
 
-        for (file, related) in groups {
-            println!("{}", file.cyan());
-            for (r, sim) in related {
-                println!("  {} {}% {}", "~".dimmed(), (sim * 100.0) as u32, r);
+            let mut cluster_pairs: Vec<&(String, String, f64)> = pairs
+                .iter()
+                .filter(|(p1, _, _)| members.binary_search(p1).is_ok())
+                .collect();
+            cluster_pairs.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+            for (p1, p2, sim) in cluster_pairs {
+                println!(
+                    "    {} {}% {} <-> {}",
+                    "~".dimmed(),
+                    (sim * 100.0) as u32,
+                    p1,
+                    p2
+                );
             }
             println!();
         }
@@ -669,9 +1108,15 @@ pub(crate) fn cmd_dupes(
 pub(crate) fn compute_duplicate_pairs(
     forward_index: &ForwardIndex,
     threshold: f64,
+    weights: SimilarityWeights,
 ) -> Vec<(String, String, f64)> {
     // Build LSH buckets for duplicate detection
-    let buckets = lsh_buckets(&forward_index.files, 16); // 16 bands x 8 rows = 128 hashes
+    let num_hashes = forward_index.minhash_perms;
+    let buckets = lsh_buckets(
+        &forward_index.files,
+        num_hashes,
+        default_lsh_bands(num_hashes),
+    );
     let mut candidates: HashSet<(String, String)> = HashSet::new();
 
     // Collect candidate pairs from buckets
@@ -697,24 +1142,7 @@ pub(crate) fn compute_duplicate_pairs(
             forward_index.files.get(path1),
             forward_index.files.get(path2),
         ) {
-            let kw1: HashSet<String> = entry1
-                .keywords
-                .iter()
-                .chain(entry1.body_keywords.iter())
-                .map(|k| k.to_lowercase())
-                .collect();
-            let kw2: HashSet<String> = entry2
-                .keywords
-                .iter()
-                .chain(entry2.body_keywords.iter())
-                .map(|k| k.to_lowercase())
-                .collect();
-
-            let jaccard = jaccard_similarity(&kw1, &kw2);
-            let simhash_sim = simhash_similarity(entry1.simhash, entry2.simhash);
-            let minhash_sim = minhash_similarity(&entry1.minhash, &entry2.minhash);
-            let combined = jaccard * 0.4 + simhash_sim * 0.3 + minhash_sim * 0.3;
-
+            let combined = combined_similarity(entry1, entry2, weights).combined;
             if combined >= threshold {
                 pairs.push((path1.clone(), path2.clone(), combined));
             }
@@ -722,68 +1150,138 @@ pub(crate) fn compute_duplicate_pairs(
     }
 
     // Sort descending by similarity for stable output
-    pairs.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+    pairs.sort_by(|a, b| {
+        b.2.partial_cmp(&a.2)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1)))
+    });
     pairs
 }
 
-pub(crate) fn build_consolidation_groups(
-    forward_index: &ForwardIndex,
-    pairs: &[(String, String, f64)],
-) -> ConsolidationResult {
-    use std::cmp::Ordering;
-
-    // Build adjacency graph
-    let mut adj: HashMap<String, HashSet<String>> = HashMap::new();
-    let mut pair_sims: HashMap<(String, String), f64> = HashMap::new();
-
-    for (a, b, sim) in pairs {
-        adj.entry(a.clone()).or_default().insert(b.clone());
-        adj.entry(b.clone()).or_default().insert(a.clone());
-
-        let key = if a <= b {
-            (a.clone(), b.clone())
-        } else {
-            (b.clone(), a.clone())
-        };
-        pair_sims.insert(key, *sim);
+/// Group duplicate pairs into connected components (transitive clusters).
+///
+/// Members of each cluster are sorted; clusters are ordered by their first
+/// member so output is stable.
+pub(crate) fn duplicate_clusters(pairs: &[(String, String, f64)]) -> Vec<Vec<String>> {
+    let mut adj: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (a, b, _) in pairs {
+        adj.entry(a.as_str()).or_default().push(b.as_str());
+        adj.entry(b.as_str()).or_default().push(a.as_str());
     }
 
-    let mut visited: HashSet<String> = HashSet::new();
-    let mut groups: Vec<ConsolidationGroup> = Vec::new();
+    let mut nodes: Vec<&str> = adj.keys().copied().collect();
+    nodes.sort_unstable();
+
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut clusters = Vec::new();
 
-    for start in adj.keys() {
+    for start in nodes {
         if visited.contains(start) {
             continue;
         }
 
-        // BFS/DFS to collect connected component
-        let mut stack = vec![start.clone()];
+        // DFS to collect connected component
+        let mut stack = vec![start];
         let mut component: Vec<String> = Vec::new();
 
         while let Some(node) = stack.pop() {
-            if !visited.insert(node.clone()) {
+            if !visited.insert(node) {
                 continue;
             }
-            component.push(node.clone());
-            if let Some(neighbors) = adj.get(&node) {
-                for n in neighbors {
-                    if !visited.contains(n) {
-                        stack.push(n.clone());
-                    }
-                }
+            component.push(node.to_string());
+            if let Some(neighbors) = adj.get(node) {
+                stack.extend(neighbors.iter().filter(|n| !visited.contains(*n)));
             }
         }
 
-        if component.len() < 2 {
+        if component.len() >= 2 {
+            component.sort();
+            clusters.push(component);
+        }
+    }
+
+    clusters
+}
+
+/// SimHash similarity at which a section counts as already present in the
+/// canonical doc for `suggest-consolidation --plan`.
+pub(crate) const MERGE_PLAN_SECTION_THRESHOLD: f64 = 0.8;
+
+/// Split each merge candidate's sections into those the canonical doc
+/// already covers and those that would need to be carried over.
+///
+/// Every section is compared against every canonical section; the closest
+/// one decides, so reordered sections still match.
+pub(crate) fn build_merge_plan(
+    forward_index: &ForwardIndex,
+    group: &mut ConsolidationGroup,
+    threshold: f64,
+) {
+    let canonical_sections = forward_index
+        .files
+        .get(&group.canonical)
+        .map(|entry| entry.section_fingerprints.as_slice())
+        .unwrap_or_default();
+
+    let mut unique = Vec::new();
+    let mut duplicated = Vec::new();
+    for file in &group.merge_into {
+        let Some(entry) = forward_index.files.get(file) else {
             continue;
+        };
+        for section in &entry.section_fingerprints {
+            let closest = canonical_sections
+                .iter()
+                .map(|c| (c, simhash_similarity(section.simhash, c.simhash)))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            let plan_section = MergePlanSection {
+                file: file.clone(),
+                heading: section.heading.clone(),
+                lines: [section.line_start, section.line_end],
+                closest_canonical: closest.map(|(c, _)| c.heading.clone()),
+                similarity: closest.map_or(0.0, |(_, sim)| sim),
+            };
+            if plan_section.similarity >= threshold {
+                duplicated.push(plan_section);
+            } else {
+                unique.push(plan_section);
+            }
         }
+    }
+
+    group.unique_sections = Some(unique);
+    group.duplicated_sections = Some(duplicated);
+}
 
+pub(crate) fn build_consolidation_groups(
+    forward_index: &ForwardIndex,
+    pairs: &[(String, String, f64)],
+) -> ConsolidationResult {
+    use std::cmp::Ordering;
+
+    let mut pair_sims: HashMap<(String, String), f64> = HashMap::new();
+    for (a, b, sim) in pairs {
+        let key = if a <= b {
+            (a.clone(), b.clone())
+        } else {
+            (b.clone(), a.clone())
+        };
+        pair_sims.insert(key, *sim);
+    }
+
+    let mut groups: Vec<ConsolidationGroup> = Vec::new();
+
+    for component in duplicate_clusters(pairs) {
         // Choose canonical doc via canonicality score
-        component.sort(); // deterministic order
         let mut best: Option<(String, f64)> = None;
         for path in &component {
             if let Some(entry) = forward_index.files.get(path) {
-                let (score, _reasons) = score_canonicality_with_reasons(path, entry);
+                let (score, _reasons) = score_canonicality_with_reasons(
+                    path,
+                    entry,
+                    None,
+                    builtin_canonicality_rules(),
+                );
                 match best {
                     None => best = Some((path.clone(), score)),
                     Some((_, best_score)) => {
@@ -845,6 +1343,8 @@ pub(crate) fn build_consolidation_groups(
             canonical_score,
             avg_similarity,
             note,
+            unique_sections: None,
+            duplicated_sections: None,
         });
     }
 
@@ -857,12 +1357,90 @@ pub(crate) fn build_consolidation_groups(
     }
 }
 
+/// SimHash similarity at which sections with different headings still pair
+/// up in `diff`.
+pub(crate) const DIFF_SECTION_MATCH_THRESHOLD: f64 = 0.8;
+
+/// SimHash similarity at which a matched pair counts as near-identical.
+pub(crate) const DIFF_NEAR_IDENTICAL_THRESHOLD: f64 = 0.95;
+
+/// Pair up the sections of two documents.
+///
+/// Sections with the same heading (case-insensitive) always pair; others
+/// pair when their SimHash similarity reaches `DIFF_SECTION_MATCH_THRESHOLD`.
+/// Candidates are taken greedily, heading matches first and then by
+/// similarity, so each section joins at most one pair.
+pub(crate) fn align_sections(
+    sections1: &[SectionFingerprint],
+    sections2: &[SectionFingerprint],
+) -> DiffSectionAlignment {
+    let to_diff_section = |s: &SectionFingerprint| DiffSection {
+        heading: s.heading.clone(),
+        lines: [s.line_start, s.line_end],
+    };
+
+    let mut candidates: Vec<(bool, f64, usize, usize)> = Vec::new();
+    for (i, a) in sections1.iter().enumerate() {
+        for (j, b) in sections2.iter().enumerate() {
+            let same_heading = a.heading.to_lowercase() == b.heading.to_lowercase();
+            let similarity = simhash_similarity(a.simhash, b.simhash);
+            if same_heading || similarity >= DIFF_SECTION_MATCH_THRESHOLD {
+                candidates.push((same_heading, similarity, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(b.1.total_cmp(&a.1))
+            .then(a.2.cmp(&b.2))
+            .then(a.3.cmp(&b.3))
+    });
+
+    let mut used1 = vec![false; sections1.len()];
+    let mut used2 = vec![false; sections2.len()];
+    let mut pairs: Vec<(usize, usize, f64)> = Vec::new();
+    for (_, similarity, i, j) in candidates {
+        if used1[i] || used2[j] {
+            continue;
+        }
+        used1[i] = true;
+        used2[j] = true;
+        pairs.push((i, j, similarity));
+    }
+    pairs.sort_by_key(|&(i, j, _)| (i, j));
+
+    DiffSectionAlignment {
+        matched: pairs
+            .into_iter()
+            .map(|(i, j, similarity)| DiffSectionPair {
+                file1: to_diff_section(&sections1[i]),
+                file2: to_diff_section(&sections2[j]),
+                similarity,
+                near_identical: similarity >= DIFF_NEAR_IDENTICAL_THRESHOLD,
+            })
+            .collect(),
+        only_in_file1: sections1
+            .iter()
+            .zip(&used1)
+            .filter(|(_, used)| !**used)
+            .map(|(s, _)| to_diff_section(s))
+            .collect(),
+        only_in_file2: sections2
+            .iter()
+            .zip(&used2)
+            .filter(|(_, used)| !**used)
+            .map(|(s, _)| to_diff_section(s))
+            .collect(),
+    }
+}
+
 /// NEW: Show what's shared between two files
 pub(crate) fn cmd_diff(
     file1: &Path,
     file2: &Path,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
+    weights: SimilarityWeights,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
 
@@ -896,26 +1474,27 @@ pub(crate) fn cmd_diff(
         resolve_path(file2).ok_or_else(|| format!("File not in index: {}", file2.display()))?;
 
     // Compute similarities
-    let kw1: HashSet<String> = entry1
-        .keywords
-        .iter()
-        .chain(entry1.body_keywords.iter())
-        .map(|k| k.to_lowercase())
-        .collect();
-    let kw2: HashSet<String> = entry2
-        .keywords
-        .iter()
-        .chain(entry2.body_keywords.iter())
-        .map(|k| k.to_lowercase())
-        .collect();
+    let kw1 = entry_keyword_set(entry1);
+    let kw2 = entry_keyword_set(entry2);
 
     let shared: HashSet<_> = kw1.intersection(&kw2).cloned().collect();
     let only_in_1: HashSet<_> = kw1.difference(&kw2).cloned().collect();
     let only_in_2: HashSet<_> = kw2.difference(&kw1).cloned().collect();
 
-    let jaccard = jaccard_similarity(&kw1, &kw2);
-    let simhash_sim = simhash_similarity(entry1.simhash, entry2.simhash);
-    let combined = jaccard * 0.6 + simhash_sim * 0.4;
+    // Keys stay lowercased stems; output shows each file's original spelling
+    let keyword_display: BTreeMap<String, String> = kw1
+        .union(&kw2)
+        .filter_map(|key| {
+            let display = entry1
+                .keyword_display
+                .get(key)
+                .or_else(|| entry2.keyword_display.get(key))?;
+            Some((key.clone(), display.clone()))
+        })
+        .collect();
+
+    let scores = combined_similarity(entry1, entry2, weights);
+    let (jaccard, simhash_sim, combined) = (scores.jaccard, scores.simhash, scores.combined);
 
     // Show shared headings
     let h1: HashSet<String> = entry1
@@ -930,6 +1509,8 @@ pub(crate) fn cmd_diff(
         .collect();
     let shared_headings: Vec<String> = h1.intersection(&h2).cloned().collect();
 
+    let sections = align_sections(&entry1.section_fingerprints, &entry2.section_fingerprints);
+
     if json {
         let mut shared_vec: Vec<_> = shared.iter().cloned().collect();
         shared_vec.sort();
@@ -952,6 +1533,8 @@ pub(crate) fn cmd_diff(
             only_in_file1: only1_vec,
             only_in_file2: only2_vec,
             shared_headings: headings_vec,
+            keyword_display,
+            sections,
         };
         println!("{}", serde_json::to_string_pretty(&result)?);
         return Ok(());
@@ -987,7 +1570,7 @@ pub(crate) fn cmd_diff(
             "  {}",
             chunk
                 .iter()
-                .map(|s| s.as_str())
+                .map(|s| keyword_display.get(s.as_str()).unwrap_or(s).as_str())
                 .collect::<Vec<_>>()
                 .join(", ")
         );
@@ -1008,7 +1591,7 @@ pub(crate) fn cmd_diff(
             "  {}",
             chunk
                 .iter()
-                .map(|s| s.as_str())
+                .map(|s| keyword_display.get(s.as_str()).unwrap_or(s).as_str())
                 .collect::<Vec<_>>()
                 .join(", ")
         );
@@ -1032,7 +1615,7 @@ pub(crate) fn cmd_diff(
             "  {}",
             chunk
                 .iter()
-                .map(|s| s.as_str())
+                .map(|s| keyword_display.get(s.as_str()).unwrap_or(s).as_str())
                 .collect::<Vec<_>>()
                 .join(", ")
         );
@@ -1056,29 +1639,271 @@ pub(crate) fn cmd_diff(
         }
     }
 
+    print_section_alignment(&sections, &path1, &path2);
+
     Ok(())
 }
 
+fn print_section_alignment(alignment: &DiffSectionAlignment, path1: &str, path2: &str) {
+    let section_label =
+        |s: &DiffSection| format!("{} (lines {}-{})", s.heading, s.lines[0], s.lines[1]);
+
+    if !alignment.matched.is_empty() {
+        println!();
+        println!(
+            "{} ({} matched)",
+            "Section Alignment".green().bold(),
+            alignment.matched.len()
+        );
+        for pair in &alignment.matched {
+            let marker = if pair.near_identical { "=" } else { "~" };
+            println!(
+                "  {marker} {}% {} <-> {}",
+                (pair.similarity * 100.0) as u32,
+                section_label(&pair.file1),
+                section_label(&pair.file2)
+            );
+        }
+    }
+
+    for (path, only) in [
+        (path1, &alignment.only_in_file1),
+        (path2, &alignment.only_in_file2),
+    ] {
+        if only.is_empty() {
+            continue;
+        }
+        println!();
+        println!(
+            "{} ({} sections)",
+            format!(
+                "Sections only in {}",
+                path.split('/').next_back().unwrap_or(path)
+            )
+            .yellow()
+            .bold(),
+            only.len()
+        );
+        for section in only {
+            println!("  - {}", section_label(section));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SectionInfo {
+    pub file_path: String,
+    pub heading: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub simhash: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct SectionCluster {
+    pub heading: String,
+    pub files: Vec<(String, f64, usize, usize)>, // (file_path, similarity, line_start, line_end)
+    /// Member SimHashes, parallel to `files`
+    pub hashes: Vec<u64>,
+    /// Bitwise majority of the members' SimHashes
+    pub simhash: u64,
+}
+
+/// Bitwise majority vote over a set of SimHash fingerprints. Ties clear the
+/// bit, so the result does not depend on member order.
+fn majority_simhash(hashes: &[u64]) -> u64 {
+    let mut result = 0u64;
+    for bit in 0..64 {
+        let ones = hashes.iter().filter(|h| (*h >> bit) & 1 == 1).count();
+        if ones * 2 > hashes.len() {
+            result |= 1 << bit;
+        }
+    }
+    result
+}
+
+/// Connected components of `0..len` under the pairwise `linked` relation.
+///
+/// Each component is in ascending order, and components are ordered by
+/// their smallest member.
+fn connected_components(len: usize, linked: impl Fn(usize, usize) -> bool) -> Vec<Vec<usize>> {
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[i] = root;
+        root
+    }
+
+    let mut parent: Vec<usize> = (0..len).collect();
+    for i in 0..len {
+        for j in (i + 1)..len {
+            if linked(i, j) {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                if a != b {
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..len {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    groups.into_values().collect()
+}
+
+/// Cluster sections whose SimHash similarity reaches `threshold`.
+///
+/// Sections are linked pairwise and grouped into connected components, so
+/// the result is independent of input order. Members are sorted by path and
+/// line; each reports its similarity to the cluster's majority fingerprint,
+/// and the cluster takes its heading from the first member.
+pub(crate) fn cluster_sections(
+    mut sections: Vec<SectionInfo>,
+    threshold: f64,
+) -> Vec<SectionCluster> {
+    sections.sort_by(|a, b| {
+        (
+            &a.file_path,
+            a.line_start,
+            a.line_end,
+            &a.heading,
+            a.simhash,
+        )
+            .cmp(&(
+                &b.file_path,
+                b.line_start,
+                b.line_end,
+                &b.heading,
+                b.simhash,
+            ))
+    });
+
+    let components = connected_components(sections.len(), |i, j| {
+        simhash_similarity(sections[i].simhash, sections[j].simhash) >= threshold
+    });
+
+    let mut clusters: Vec<SectionCluster> = components
+        .into_iter()
+        .map(|component| {
+            let members: Vec<&SectionInfo> = component.iter().map(|&i| &sections[i]).collect();
+            let hashes: Vec<u64> = members.iter().map(|s| s.simhash).collect();
+            let simhash = majority_simhash(&hashes);
+            SectionCluster {
+                heading: members[0].heading.clone(),
+                files: members
+                    .iter()
+                    .map(|s| {
+                        (
+                            s.file_path.clone(),
+                            simhash_similarity(s.simhash, simhash),
+                            s.line_start,
+                            s.line_end,
+                        )
+                    })
+                    .collect(),
+                hashes,
+                simhash,
+            }
+        })
+        .collect();
+
+    // Largest first; components are already in first-member order
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.files.len()));
+    clusters
+}
+
+/// Indices into `cluster.files` of the two members whose SimHashes differ
+/// most. Ties keep the earliest pair.
+pub(crate) fn most_dissimilar_pair(cluster: &SectionCluster) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize, f64)> = None;
+    for i in 0..cluster.hashes.len() {
+        for j in (i + 1)..cluster.hashes.len() {
+            let similarity = simhash_similarity(cluster.hashes[i], cluster.hashes[j]);
+            if best.is_none_or(|(_, _, lowest)| similarity < lowest) {
+                best = Some((i, j, similarity));
+            }
+        }
+    }
+    best.map(|(i, j, _)| (i, j))
+}
+
+/// Line diff between two section texts with three lines of context.
+/// Each line is prefixed with `-`, `+`, or a space; hunks are separated by
+/// `@@`. Identical texts yield no lines.
+pub(crate) fn section_diff_lines(old: &str, new: &str) -> Vec<String> {
+    let diff = similar::TextDiff::from_lines(old, new);
+    let mut lines = Vec::new();
+    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+        if idx > 0 {
+            lines.push("@@".to_string());
+        }
+        for op in group {
+            for change in diff.iter_changes(op) {
+                let sign = match change.tag() {
+                    similar::ChangeTag::Delete => '-',
+                    similar::ChangeTag::Insert => '+',
+                    similar::ChangeTag::Equal => ' ',
+                };
+                lines.push(format!("{sign}{}", change.value().trim_end_matches('\n')));
+            }
+        }
+    }
+    lines
+}
+
+/// Text of lines `line_start..=line_end` (1-based) of an indexed doc.
+fn read_section_text(
+    index: &ForwardIndex,
+    doc_path: &str,
+    line_start: usize,
+    line_end: usize,
+) -> Option<String> {
+    let entry = index.files.get(doc_path)?;
+    let content = read_indexed_doc(index, doc_path, entry).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    let start = line_start.saturating_sub(1);
+    let end = line_end.min(lines.len());
+    let mut text = lines.get(start..end)?.join("\n");
+    text.push('\n');
+    Some(text)
+}
+
+/// Diff the two most dissimilar members of a cluster, labelled
+/// `path:start-end`. `None` when the cluster has one member or a section
+/// can't be read.
+fn cluster_section_diff(
+    index: &ForwardIndex,
+    cluster: &SectionCluster,
+) -> Option<(String, String, Vec<String>)> {
+    let (i, j) = most_dissimilar_pair(cluster)?;
+    let (path_a, _, start_a, end_a) = &cluster.files[i];
+    let (path_b, _, start_b, end_b) = &cluster.files[j];
+    let text_a = read_section_text(index, path_a, *start_a, *end_a)?;
+    let text_b = read_section_text(index, path_b, *start_b, *end_b)?;
+    Some((
+        format!("{path_a}:{start_a}-{end_a}"),
+        format!("{path_b}:{start_b}-{end_b}"),
+        section_diff_lines(&text_a, &text_b),
+    ))
+}
+
 /// Find duplicate sections across documents
 pub(crate) fn cmd_dupes_sections(
     threshold: f64,
     min_files: usize,
+    show_diff: bool,
     json: bool,
-    index_dir: &Path,
+    timing: bool,
+    index_dir: &IndexDir,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
     let start = Instant::now();
 
-    // Collect all sections from all files
-    #[derive(Debug, Clone)]
-    struct SectionInfo {
-        file_path: String,
-        heading: String,
-        line_start: usize,
-        line_end: usize,
-        simhash: u64,
-    }
-
     let mut all_sections: Vec<SectionInfo> = Vec::new();
     for (path, entry) in &forward_index.files {
         for section in &entry.section_fingerprints {
@@ -1097,51 +1922,8 @@ pub(crate) fn cmd_dupes_sections(
         return Ok(());
     }
 
-    // Group similar sections using SimHash similarity
-    #[derive(Debug)]
-    struct SectionCluster {
-        heading: String,
-        files: Vec<(String, f64, usize, usize)>, // (file_path, similarity, line_start, line_end)
-        avg_simhash: u64,
-    }
-
-    let mut clusters: Vec<SectionCluster> = Vec::new();
-
-    for section in &all_sections {
-        let mut best_cluster_idx: Option<usize> = None;
-        let mut best_similarity = 0.0;
-
-        // Find best matching cluster
-        for (cluster_idx, cluster) in clusters.iter().enumerate() {
-            let similarity = simhash_similarity(section.simhash, cluster.avg_simhash);
-            if similarity >= threshold && similarity > best_similarity {
-                best_similarity = similarity;
-                best_cluster_idx = Some(cluster_idx);
-            }
-        }
-
-        if let Some(cluster_idx) = best_cluster_idx {
-            // Add to existing cluster
-            clusters[cluster_idx].files.push((
-                section.file_path.clone(),
-                best_similarity,
-                section.line_start,
-                section.line_end,
-            ));
-        } else {
-            // Create new cluster
-            clusters.push(SectionCluster {
-                heading: section.heading.clone(),
-                files: vec![(
-                    section.file_path.clone(),
-                    1.0,
-                    section.line_start,
-                    section.line_end,
-                )],
-                avg_simhash: section.simhash,
-            });
-        }
-    }
+    let section_count = all_sections.len();
+    let clusters = cluster_sections(all_sections, threshold);
 
     let elapsed = start.elapsed();
 
@@ -1161,23 +1943,17 @@ pub(crate) fn cmd_dupes_sections(
             )
             .green()
         );
-        eprintln!(
-            "Section analysis: {:?} ({} sections analyzed)",
-            elapsed,
-            all_sections.len()
-        );
+        if timing {
+            eprintln!("Section analysis: {elapsed:?} ({section_count} sections analyzed)");
+        }
         return Ok(());
     }
 
-    // Sort clusters by number of files (descending)
-    let mut sorted_clusters = duplicate_clusters;
-    sorted_clusters.sort_by(|a, b| b.files.len().cmp(&a.files.len()));
-
     if json {
-        let output: Vec<_> = sorted_clusters
+        let output: Vec<_> = duplicate_clusters
             .iter()
             .map(|cluster| {
-                serde_json::json!({
+                let mut value = serde_json::json!({
                     "heading": cluster.heading,
                     "file_count": cluster.files.len(),
                     "files": cluster.files.iter().map(|(path, sim, start, end)| {
@@ -1188,7 +1964,18 @@ pub(crate) fn cmd_dupes_sections(
                             "line_end": end,
                         })
                     }).collect::<Vec<_>>(),
-                })
+                });
+                if show_diff {
+                    if let Some((from, to, lines)) = cluster_section_diff(&forward_index, cluster) {
+                        value["diff"] = serde_json::json!({
+                            "from": from,
+                            "to": to,
+                            "identical": lines.is_empty(),
+                            "lines": lines,
+                        });
+                    }
+                }
+                value
             })
             .collect();
         println!("{}", serde_json::to_string_pretty(&output)?);
@@ -1197,17 +1984,15 @@ pub(crate) fn cmd_dupes_sections(
 
     println!(
         "{} duplicate section clusters found (threshold: {}%, min files: {})",
-        sorted_clusters.len().to_string().yellow().bold(),
+        duplicate_clusters.len().to_string().yellow().bold(),
         (threshold * 100.0) as u32,
         min_files
     );
-    eprintln!(
-        "Section analysis: {:?} ({} sections analyzed)\n",
-        elapsed,
-        all_sections.len()
-    );
+    if timing {
+        eprintln!("Section analysis: {elapsed:?} ({section_count} sections analyzed)\n");
+    }
 
-    for cluster in sorted_clusters.iter().take(20) {
+    for cluster in duplicate_clusters.iter().take(20) {
         println!(
             "{} {} ({} files)",
             "Section:".cyan().bold(),
@@ -1225,15 +2010,32 @@ pub(crate) fn cmd_dupes_sections(
                 line_end
             );
         }
+        if show_diff {
+            if let Some((from, to, lines)) = cluster_section_diff(&forward_index, cluster) {
+                println!("  {} {} → {}", "Diff:".cyan(), from, to);
+                if lines.is_empty() {
+                    println!("    {}", "identical text".green());
+                }
+                for line in &lines {
+                    let line = match line.chars().next() {
+                        Some('-') => line.red().to_string(),
+                        Some('+') => line.green().to_string(),
+                        Some('@') => line.dimmed().to_string(),
+                        _ => line.clone(),
+                    };
+                    println!("    {line}");
+                }
+            }
+        }
         println!();
     }
 
-    if sorted_clusters.len() > 20 {
+    if duplicate_clusters.len() > 20 {
         println!(
             "{}",
             format!(
                 "... and {} more section clusters",
-                sorted_clusters.len() - 20
+                duplicate_clusters.len() - 20
             )
             .dimmed()
         );
@@ -1241,3 +2043,209 @@ pub(crate) fn cmd_dupes_sections(
 
     Ok(())
 }
+
+/// Lowercased alphanumeric tokens of a heading, used for similarity.
+fn heading_tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Similarity of two heading texts: the better of token-set Jaccard and
+/// normalized edit distance over the lowercased token sequence.
+pub(crate) fn heading_similarity(a: &str, b: &str) -> f64 {
+    let tokens_a = heading_tokens(a);
+    let tokens_b = heading_tokens(b);
+    if tokens_a.is_empty() || tokens_b.is_empty() {
+        return 0.0;
+    }
+    let set_a: HashSet<String> = tokens_a.iter().cloned().collect();
+    let set_b: HashSet<String> = tokens_b.iter().cloned().collect();
+    let jaccard = jaccard_similarity(&set_a, &set_b);
+
+    let chars_a: Vec<char> = tokens_a.join(" ").chars().collect();
+    let chars_b: Vec<char> = tokens_b.join(" ").chars().collect();
+    let longest = chars_a.len().max(chars_b.len());
+    let edit = 1.0 - edit_distance(&chars_a, &chars_b) as f64 / longest as f64;
+
+    jaccard.max(edit)
+}
+
+#[derive(Debug)]
+pub(crate) struct HeadingCluster {
+    /// Most frequent spelling in the cluster
+    pub canonical: String,
+    /// Distinct spellings with their occurrence counts, most frequent first
+    pub variants: Vec<(String, usize)>,
+    /// (path, line, heading, similarity to `canonical`)
+    pub locations: Vec<(String, usize, String, f64)>,
+}
+
+/// Cluster near-identical heading spellings. Distinct spellings are linked
+/// when their similarity reaches `threshold`; clusters with a single
+/// spelling (exact repeats) or spanning fewer than `min_files` files are
+/// dropped.
+pub(crate) fn cluster_headings(
+    headings: &[(String, usize, String)],
+    threshold: f64,
+    min_files: usize,
+) -> Vec<HeadingCluster> {
+    let mut spellings: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, (_, _, text)) in headings.iter().enumerate() {
+        let text = text.trim();
+        if !heading_tokens(text).is_empty() {
+            spellings.entry(text).or_default().push(i);
+        }
+    }
+    let texts: Vec<&str> = spellings.keys().copied().collect();
+
+    let components = connected_components(texts.len(), |i, j| {
+        heading_similarity(texts[i], texts[j]) >= threshold
+    });
+
+    let mut clusters: Vec<HeadingCluster> = Vec::new();
+    for component in components {
+        let members: Vec<&str> = component.iter().map(|&i| texts[i]).collect();
+        if members.len() < 2 {
+            continue;
+        }
+        let mut variants: Vec<(String, usize)> = members
+            .iter()
+            .map(|text| (text.to_string(), spellings[text].len()))
+            .collect();
+        variants.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let canonical = variants[0].0.clone();
+
+        let mut locations: Vec<(String, usize, String, f64)> = members
+            .iter()
+            .flat_map(|text| spellings[text].iter())
+            .map(|&i| {
+                let (path, line, text) = &headings[i];
+                let text = text.trim().to_string();
+                let similarity = if text == canonical {
+                    1.0
+                } else {
+                    heading_similarity(&text, &canonical)
+                };
+                (path.clone(), *line, text, similarity)
+            })
+            .collect();
+        locations.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let files: HashSet<&str> = locations.iter().map(|l| l.0.as_str()).collect();
+        if files.len() < min_files {
+            continue;
+        }
+        clusters.push(HeadingCluster {
+            canonical,
+            variants,
+            locations,
+        });
+    }
+
+    clusters.sort_by(|a, b| {
+        b.locations
+            .len()
+            .cmp(&a.locations.len())
+            .then_with(|| a.canonical.cmp(&b.canonical))
+    });
+    clusters
+}
+
+pub(crate) fn cmd_dupes_headings(
+    threshold: f64,
+    min_files: usize,
+    json: bool,
+    index_dir: &IndexDir,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let forward_index = load_forward_index(index_dir)?;
+
+    let mut headings: Vec<(String, usize, String)> = Vec::new();
+    for (path, entry) in &forward_index.files {
+        for heading in &entry.headings {
+            headings.push((path.clone(), heading.line, heading.text.clone()));
+        }
+    }
+
+    let clusters = cluster_headings(&headings, threshold, min_files);
+
+    if json {
+        let output: Vec<_> = clusters
+            .iter()
+            .map(|cluster| {
+                serde_json::json!({
+                    "canonical": cluster.canonical,
+                    "variants": cluster.variants.iter().map(|(text, count)| {
+                        serde_json::json!({ "heading": text, "count": count })
+                    }).collect::<Vec<_>>(),
+                    "locations": cluster.locations.iter().map(|(path, line, text, sim)| {
+                        serde_json::json!({
+                            "path": path,
+                            "line": line,
+                            "heading": text,
+                            "similarity": sim,
+                        })
+                    }).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    if clusters.is_empty() {
+        println!(
+            "{}",
+            format!(
+                "No near-duplicate headings found across {} or more files at {}% threshold.",
+                min_files,
+                (threshold * 100.0) as u32
+            )
+            .green()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} near-duplicate heading clusters found (threshold: {}%, min files: {})\n",
+        clusters.len().to_string().yellow().bold(),
+        (threshold * 100.0) as u32,
+        min_files
+    );
+
+    for cluster in &clusters {
+        let variants: Vec<String> = cluster
+            .variants
+            .iter()
+            .map(|(text, count)| format!("\"{text}\" ×{count}"))
+            .collect();
+        println!("{} {}", "Headings:".cyan().bold(), variants.join(", "));
+        for (path, line, text, similarity) in &cluster.locations {
+            println!(
+                "  {}% {}:{} {}",
+                ((similarity * 100.0) as u32).to_string().dimmed(),
+                path,
+                line,
+                text
+            );
+        }
+        println!();
+    }
+
+    Ok(())
+}
@@ -1,18 +1,128 @@
+use crate::commands_audit::{cmd_orphans, score_canonicality_with_reasons};
+use crate::commands_graph::{
+    build_link_graph, cmd_backlinks, collect_backlinks, compute_link_authority,
+};
 use crate::commands_query::*;
 use colored::Colorize;
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, BufRead, Write};
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::path::Path;
+use std::time::Instant;
 
 use crate::assemble::*;
 use crate::search::*;
 use crate::types::*;
 use crate::util::*;
 
+/// Nearest-rank percentile of an ascending slice; 0 when empty.
+fn percentile(sorted: &[usize], pct: usize) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+pub(crate) fn compute_stats_distribution(forward_index: &ForwardIndex) -> StatsDistribution {
+    let files = &forward_index.files;
+    let mut line_counts: Vec<usize> = files.values().map(|e| e.line_count).collect();
+    line_counts.sort_unstable();
+    let total_links: usize = files.values().map(|e| e.links.len()).sum();
+
+    StatsDistribution {
+        line_count_p50: percentile(&line_counts, 50),
+        line_count_p90: percentile(&line_counts, 90),
+        line_count_max: line_counts.last().copied().unwrap_or(0),
+        avg_links_per_file: if files.is_empty() {
+            0.0
+        } else {
+            total_links as f64 / files.len() as f64
+        },
+        files_without_headings: files.values().filter(|e| e.headings.is_empty()).count(),
+        files_without_links: files.values().filter(|e| e.links.is_empty()).count(),
+        files_without_minhash: files.values().filter(|e| e.minhash.is_empty()).count(),
+        files_without_sections: files
+            .values()
+            .filter(|e| !e.headings.is_empty() && e.section_fingerprints.is_empty())
+            .count(),
+    }
+}
+
+/// Report drift between the index and the files on disk. Returns true when
+/// anything was added, removed, or modified since the last build.
+pub(crate) fn cmd_check_drift(
+    index_dir: &IndexDir,
+    json: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let forward_index = load_forward_index(index_dir)?;
+    let result = crate::index::detect_index_drift(&forward_index);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(result.has_drift());
+    }
+
+    println!("{}", "Index Drift".green().bold());
+    println!();
+    println!(
+        "  Indexed at:        {}",
+        display_timestamp(&result.indexed_at).dimmed()
+    );
+    let added = if result.added_checked {
+        result.added.len().to_string().cyan().to_string()
+    } else {
+        "not checked (rebuild to record the build scope)"
+            .dimmed()
+            .to_string()
+    };
+    println!("  Added:             {added}");
+    println!(
+        "  Removed:           {}",
+        result.removed.len().to_string().cyan()
+    );
+    println!(
+        "  Modified:          {}",
+        result.modified.len().to_string().cyan()
+    );
+
+    if !result.has_drift() {
+        println!();
+        println!("{}", "✓ Index matches the files on disk.".green().bold());
+        return Ok(false);
+    }
+
+    println!();
+    for file in &result.added {
+        println!("  {} {file}", "+".green());
+    }
+    for file in &result.removed {
+        println!("  {} {file}", "-".red());
+    }
+    for file in &result.modified {
+        println!(
+            "  {} {} {}",
+            "~".yellow(),
+            file.file,
+            format!(
+                "({} -> {} bytes)",
+                file.indexed_size_bytes, file.current_size_bytes
+            )
+            .dimmed()
+        );
+    }
+    println!();
+    println!(
+        "{} Index is out of date; run `yore build` to refresh it.",
+        "Warning:".yellow()
+    );
+
+    Ok(true)
+}
+
 pub(crate) fn cmd_stats(
     top_keywords: usize,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
@@ -24,7 +134,9 @@ pub(crate) fn cmd_stats(
         .iter()
         .map(|(k, v)| (k.clone(), v.len()))
         .collect();
-    keyword_counts.sort_by(|a, b| b.1.cmp(&a.1));
+    keyword_counts.sort_by_key(|kc| std::cmp::Reverse(kc.1));
+    let keyword_display = keyword_display_map(&forward_index.files);
+    let display = |keyword: &String| keyword_display.get(keyword).unwrap_or(keyword).clone();
 
     let total_headings: usize = forward_index.files.values().map(|e| e.headings.len()).sum();
     let total_links: usize = forward_index.files.values().map(|e| e.links.len()).sum();
@@ -33,6 +145,7 @@ pub(crate) fn cmd_stats(
         .values()
         .map(|e| e.body_keywords.len())
         .sum();
+    let distribution = compute_stats_distribution(&forward_index);
 
     if json {
         let result = StatsResult {
@@ -43,11 +156,13 @@ pub(crate) fn cmd_stats(
             total_links,
             index_version: forward_index.version,
             indexed_at: forward_index.indexed_at.clone(),
+            distribution,
             top_keywords: keyword_counts
                 .iter()
                 .take(top_keywords)
                 .map(|(k, c)| KeywordCount {
                     keyword: k.clone(),
+                    display: display(k),
                     count: *c,
                 })
                 .collect(),
@@ -76,26 +191,282 @@ pub(crate) fn cmd_stats(
         "  Index version:     {}",
         forward_index.version.to_string().dimmed()
     );
-    println!("  Indexed at:        {}", forward_index.indexed_at.dimmed());
+    println!(
+        "  Indexed at:        {}",
+        display_timestamp(&forward_index.indexed_at).dimmed()
+    );
+    println!();
+    println!("{}", "Distribution".green().bold());
+    println!();
+    println!(
+        "  Lines per file:    p50 {}, p90 {}, max {}",
+        distribution.line_count_p50.to_string().cyan(),
+        distribution.line_count_p90.to_string().cyan(),
+        distribution.line_count_max.to_string().cyan()
+    );
+    println!(
+        "  Links per file:    {}",
+        format!("{:.1}", distribution.avg_links_per_file).cyan()
+    );
+    println!(
+        "  No headings:       {}",
+        distribution.files_without_headings.to_string().cyan()
+    );
+    println!(
+        "  No links:          {}",
+        distribution.files_without_links.to_string().cyan()
+    );
+    let stale = distribution.files_without_minhash + distribution.files_without_sections;
+    if stale > 0 {
+        println!(
+            "  {} {} file(s) missing MinHash, {} missing section fingerprints; rebuild the index",
+            "Warning:".yellow(),
+            distribution.files_without_minhash,
+            distribution.files_without_sections
+        );
+    }
     println!();
     println!("{}", format!("Top {top_keywords} Keywords").green().bold());
     println!();
 
     for (keyword, count) in keyword_counts.iter().take(top_keywords) {
         let bar = "=".repeat((count / 2).min(40));
-        println!("  {:>20} {:>4} {}", keyword.cyan(), count, bar.dimmed());
+        println!(
+            "  {:>20} {:>4} {}",
+            display(keyword).cyan(),
+            count,
+            bar.dimmed()
+        );
     }
 
     Ok(())
 }
 
-pub(crate) fn cmd_repl(index_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+/// Most frequent body terms of a document, highest count first, ties by
+/// term; heading occurrences are left out.
+pub(crate) fn top_body_terms(entry: &FileEntry, limit: usize) -> Vec<(String, usize)> {
+    let mut terms: Vec<(String, usize)> = entry
+        .term_frequencies
+        .iter()
+        .map(|(term, count)| {
+            let in_headings = entry.heading_term_frequencies.get(term).copied();
+            (term.clone(), count.saturating_sub(in_headings.unwrap_or(0)))
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    terms.truncate(limit);
+    terms
+}
+
+/// Print what the index stores about a single file
+pub(crate) fn cmd_show(
+    file: &str,
+    top_keywords: usize,
+    index_dir: &IndexDir,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let forward_index = load_forward_index(index_dir)?;
+    let path = resolve_indexed_path(file, &forward_index)
+        .ok_or_else(|| format!("File not in index: {file}"))?;
+    let entry = &forward_index.files[&path];
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(entry)?);
+        return Ok(());
+    }
+
+    let (inbound, _) = collect_backlinks(
+        &build_link_graph(&forward_index),
+        &normalize_path(Path::new(&path)),
+        1,
+    );
+    let authority = compute_link_authority(&forward_index);
+    let (canonicality, reasons) = score_canonicality_with_reasons(
+        &path,
+        entry,
+        authority.get(&path).copied(),
+        builtin_canonicality_rules(),
+    );
+
+    println!("{}", path.green().bold());
+    println!();
+    println!(
+        "  Lines:             {}",
+        entry.line_count.to_string().cyan()
+    );
+    println!(
+        "  Words:             {}",
+        entry.doc_length.to_string().cyan()
+    );
+    println!(
+        "  Size:              {} bytes",
+        entry.size_bytes.to_string().cyan()
+    );
+    println!(
+        "  Links:             {} outbound, {} inbound",
+        entry.links.len().to_string().cyan(),
+        inbound.len().to_string().cyan()
+    );
+    println!(
+        "  Canonicality:      {}",
+        format!("{canonicality:.2}").cyan()
+    );
+    for reason in &reasons {
+        println!("                     - {reason}");
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("Headings ({})", entry.headings.len())
+            .green()
+            .bold()
+    );
+    for heading in &entry.headings {
+        println!(
+            "  {:>5}  {}{} {}",
+            format!("L{}", heading.line).dimmed(),
+            "  ".repeat(heading.level.saturating_sub(1)),
+            "#".repeat(heading.level),
+            heading.text
+        );
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("Outbound Links ({})", entry.links.len())
+            .green()
+            .bold()
+    );
+    for link in &entry.links {
+        println!(
+            "  {:>5}  [{}]({})",
+            format!("L{}", link.line).dimmed(),
+            link.text,
+            link.target.cyan()
+        );
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!("Top {top_keywords} Body Keywords").green().bold()
+    );
+    for (term, count) in top_body_terms(entry, top_keywords) {
+        let display = entry.keyword_display.get(&term).unwrap_or(&term);
+        println!("  {:>20} {:>4}", display.cyan(), count);
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!(
+            "Section Fingerprints ({})",
+            entry.section_fingerprints.len()
+        )
+        .green()
+        .bold()
+    );
+    for section in &entry.section_fingerprints {
+        println!(
+            "  {:>9}  {:016x}  {} {}",
+            format!("L{}-{}", section.line_start, section.line_end).dimmed(),
+            section.simhash,
+            "#".repeat(section.level),
+            section.heading
+        );
+    }
+
+    Ok(())
+}
+
+/// One line of REPL input, parsed.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ReplCommand {
+    Quit,
+    Help,
+    /// `query` / `query-json`; `None` re-runs the last query
+    Query {
+        terms: Option<String>,
+        json: bool,
+    },
+    /// `limit` alone shows the current value
+    Limit(Option<usize>),
+    Similar(String),
+    Dupes,
+    Diff(String, String),
+    Stats,
+    Backlinks(String),
+    Orphans,
+    /// Malformed verb; carries the usage line to show
+    Usage(&'static str),
+}
+
+pub(crate) fn parse_repl_command(line: &str) -> Option<ReplCommand> {
+    let trimmed = line.trim();
+    let parts: Vec<&str> = trimmed.split_whitespace().collect();
+    let (&verb, args) = parts.split_first()?;
+    let rest = trimmed[verb.len()..].trim();
+    let rest = (!rest.is_empty()).then(|| rest.to_string());
+
+    let command = match verb {
+        "quit" | "exit" | "q" => ReplCommand::Quit,
+        "help" | "?" => ReplCommand::Help,
+        "query" => ReplCommand::Query {
+            terms: rest,
+            json: false,
+        },
+        "query-json" => ReplCommand::Query {
+            terms: rest,
+            json: true,
+        },
+        "limit" => match args {
+            [] => ReplCommand::Limit(None),
+            [n] => match n.parse::<usize>() {
+                Ok(n) if n > 0 => ReplCommand::Limit(Some(n)),
+                _ => ReplCommand::Usage("Usage: limit <n> (n >= 1)"),
+            },
+            _ => ReplCommand::Usage("Usage: limit <n> (n >= 1)"),
+        },
+        "similar" => match args.first() {
+            Some(file) => ReplCommand::Similar((*file).to_string()),
+            None => ReplCommand::Usage("Usage: similar <file>"),
+        },
+        "dupes" => ReplCommand::Dupes,
+        "diff" => match args {
+            [a, b, ..] => ReplCommand::Diff((*a).to_string(), (*b).to_string()),
+            _ => ReplCommand::Usage("Usage: diff <file1> <file2>"),
+        },
+        "stats" => ReplCommand::Stats,
+        "backlinks" => match args.first() {
+            Some(file) => ReplCommand::Backlinks((*file).to_string()),
+            None => ReplCommand::Usage("Usage: backlinks <file>"),
+        },
+        "orphans" => ReplCommand::Orphans,
+        // Treat as query
+        _ => ReplCommand::Query {
+            terms: Some(trimmed.to_string()),
+            json: false,
+        },
+    };
+    Some(command)
+}
+
+pub(crate) fn cmd_repl(index_dir: &IndexDir) -> Result<(), Box<dyn std::error::Error>> {
+    let index_dir = &session_index_dir(index_dir);
     println!("{}", "yore interactive mode (v2)".green().bold());
-    println!("Commands: query <terms>, similar <file>, dupes, diff <f1> <f2>, stats, help, quit\n");
+    println!(
+        "Commands: query <terms>, query-json <terms>, limit <n>, similar <file>, backlinks <file>, \
+         orphans, dupes, diff <f1> <f2>, stats, help, quit\n"
+    );
 
     let stdin = io::stdin();
     let mut stdout = io::stdout();
-    let query_options = QueryOptions {
+    // Session state: `limit` persists across queries, and a bare
+    // `query` / `query-json` re-runs the last query
+    let mut query_options = QueryOptions {
         limit: 10,
         files_only: false,
         json: false,
@@ -103,7 +474,17 @@ pub(crate) fn cmd_repl(index_dir: &Path) -> Result<(), Box<dyn std::error::Error
         explain: false,
         require_phrases: false,
         filter_stopwords: true,
+        tags: Vec::new(),
+        snippets: false,
+        offset: None,
+        min_score: None,
+        synonyms: Synonyms::default(),
+        scope: PathScope::default(),
+        heading_boost: DEFAULT_HEADING_BOOST,
+        code_boost: 0.0,
+        group_depth: None,
     };
+    let mut last_query: Option<String> = None;
 
     loop {
         print!("{} ", ">".cyan().bold());
@@ -114,57 +495,92 @@ pub(crate) fn cmd_repl(index_dir: &Path) -> Result<(), Box<dyn std::error::Error
             break;
         }
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.is_empty() {
+        let Some(command) = parse_repl_command(&line) else {
             continue;
-        }
+        };
 
-        match parts[0] {
-            "quit" | "exit" | "q" => break,
-            "help" | "?" => {
-                println!("  query <terms...>   - Search for keywords");
-                println!("  similar <file>     - Find similar files");
-                println!("  dupes              - Find duplicates");
-                println!("  diff <f1> <f2>     - Compare two files");
-                println!("  stats              - Show statistics");
-                println!("  quit               - Exit");
+        match command {
+            ReplCommand::Quit => break,
+            ReplCommand::Help => {
+                println!(
+                    "  query <terms...>       - Search for keywords (alone: repeat last query)"
+                );
+                println!("  query-json <terms...>  - Search, printing JSON results with scores");
+                println!(
+                    "  limit [n]              - Show or set the result limit for this session"
+                );
+                println!("  similar <file>         - Find similar files");
+                println!("  backlinks <file>       - List files linking to a file");
+                println!("  orphans                - List files with no inbound links");
+                println!("  dupes                  - Find duplicates");
+                println!("  diff <f1> <f2>         - Compare two files");
+                println!("  stats                  - Show statistics");
+                println!("  quit                   - Exit");
             }
-            "query" => {
-                let trimmed = line.trim();
-                let rest = trimmed.strip_prefix("query").unwrap_or("").trim();
-                if rest.is_empty() {
-                    println!("{}", "Usage: query <terms...>".yellow());
-                } else {
-                    let _ = cmd_query(rest, index_dir, &query_options);
+            ReplCommand::Query { terms, json } => match terms.or_else(|| last_query.clone()) {
+                Some(terms) => {
+                    query_options.json = json;
+                    let _ = cmd_query(&terms, index_dir, &query_options);
+                    last_query = Some(terms);
                 }
-            }
-            "similar" => {
-                if parts.len() < 2 {
-                    println!("{}", "Usage: similar <file>".yellow());
-                } else {
-                    let _ = cmd_similar(Path::new(parts[1]), 5, 0.3, false, 0, index_dir);
+                None => {
+                    let usage = if json {
+                        "Usage: query-json <terms...>"
+                    } else {
+                        "Usage: query <terms...>"
+                    };
+                    println!("{}", usage.yellow());
                 }
+            },
+            ReplCommand::Limit(Some(limit)) => {
+                query_options.limit = limit;
+                println!("limit = {limit}");
             }
-            "dupes" => {
-                let _ = cmd_dupes(0.35, false, false, index_dir);
+            ReplCommand::Limit(None) => println!("limit = {}", query_options.limit),
+            ReplCommand::Similar(file) => {
+                let _ = cmd_similar(
+                    Path::new(&file),
+                    5,
+                    0.3,
+                    false,
+                    0,
+                    SimilarityWeights::SIMILAR,
+                    index_dir,
+                );
             }
-            "diff" => {
-                if parts.len() < 3 {
-                    println!("{}", "Usage: diff <file1> <file2>".yellow());
-                } else {
-                    let _ = cmd_diff(Path::new(parts[1]), Path::new(parts[2]), index_dir, false);
-                }
+            ReplCommand::Dupes => {
+                let _ = cmd_dupes(
+                    &DupesOptions {
+                        threshold: 0.35,
+                        group: false,
+                        json: false,
+                        csv: false,
+                        weights: SimilarityWeights::DUPES,
+                        lsh_bands: None,
+                        timing: false,
+                    },
+                    index_dir,
+                );
+            }
+            ReplCommand::Diff(a, b) => {
+                let _ = cmd_diff(
+                    Path::new(&a),
+                    Path::new(&b),
+                    index_dir,
+                    false,
+                    SimilarityWeights::SIMILAR,
+                );
             }
-            "stats" => {
+            ReplCommand::Stats => {
                 let _ = cmd_stats(10, index_dir, false);
             }
-            _ => {
-                // Treat as query
-                let trimmed = line.trim();
-                if !trimmed.is_empty() {
-                    let _ = cmd_query(trimmed, index_dir, &query_options);
-                }
+            ReplCommand::Backlinks(file) => {
+                let _ = cmd_backlinks(&file, index_dir, 1, false);
+            }
+            ReplCommand::Orphans => {
+                let _ = cmd_orphans(index_dir, false, false, &[], None);
             }
+            ReplCommand::Usage(usage) => println!("{}", usage.yellow()),
         }
         println!();
     }
@@ -173,7 +589,7 @@ pub(crate) fn cmd_repl(index_dir: &Path) -> Result<(), Box<dyn std::error::Error
 }
 
 pub(crate) fn cmd_vocabulary(
-    index_dir: &Path,
+    index_dir: &IndexDir,
     limit: usize,
     format: &str,
     json: bool,
@@ -410,6 +826,10 @@ pub(crate) fn resolve_vocabulary_surface(
         token_idx: usize,
     }
 
+    let stemmer = forward_index.map_or(StemmerKind::Simple, |index| index.stemmer);
+    let stop_words = forward_index
+        .map(|index| index.stop_words.clone())
+        .unwrap_or_default();
     let mut ordered_postings = postings.to_vec();
     ordered_postings.sort_by(|a, b| {
         a.file
@@ -431,8 +851,11 @@ pub(crate) fn resolve_vocabulary_surface(
 
     for posting in &ordered_postings {
         if let Some(heading) = &posting.heading {
-            for (token_idx, token) in extract_keywords(heading).into_iter().enumerate() {
-                if stem_word(&token) == stem {
+            for (token_idx, token) in extract_keywords(heading, &stop_words)
+                .into_iter()
+                .enumerate()
+            {
+                if stem_word_with(&token, stemmer) == stem {
                     candidates.push(SurfaceCandidate {
                         value: token,
                         file: posting.file.clone(),
@@ -452,7 +875,7 @@ pub(crate) fn resolve_vocabulary_surface(
                     .chain(entry.body_keywords.iter())
                     .enumerate()
                 {
-                    if stem_word(&token.to_lowercase()) == stem {
+                    if stem_word_with(token, stemmer) == stem {
                         candidates.push(SurfaceCandidate {
                             value: token.to_lowercase(),
                             file: posting.file.clone(),
@@ -496,71 +919,108 @@ pub(crate) fn normalize_prompt_term(term: &str) -> String {
         .to_string()
 }
 
-/// Main assemble command handler
-pub(crate) fn cmd_assemble(
+/// Query text for `assemble`: the positional words, else the contents of
+/// `query_file`, else piped stdin. Only a `--from-files` run may go without
+/// one.
+pub(crate) fn read_assemble_query(
+    words: &[String],
+    query_file: Option<&Path>,
+    from_files: &[String],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let text = if !words.is_empty() {
+        words.join(" ")
+    } else if let Some(path) = query_file {
+        fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read --query-file {}: {err}", path.display()))?
+    } else if from_files.is_empty() && !io::stdin().is_terminal() {
+        io::read_to_string(io::stdin())?
+    } else {
+        String::new()
+    };
+
+    // Collapse line breaks so a multi-line question reads as one query
+    let query = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if query.is_empty() && from_files.is_empty() {
+        return Err(
+            "No query provided: pass it as arguments, with --query-file, or on stdin \
+             (or select documents with --from-files)"
+                .into(),
+        );
+    }
+    Ok(query)
+}
+
+/// Primary sections, expansion, and refinement behind one assembled digest
+pub(crate) struct AssembledContext {
+    pub query_label: String,
+    pub query_for_refiner: String,
+    /// Sections found by retrieval, in rank order, before expansion
+    pub primary_sections: Vec<SectionMatch>,
+    /// Primary and cross-referenced sections after refinement and ranking
+    pub refined_sections: Vec<RefinedSection>,
+    pub scope: DigestScope,
+}
+
+/// Run the assemble pipeline: primary selection, cross-reference expansion,
+/// deduplication, and extractive refinement. `assemble` and `eval` both go
+/// through here so eval measures exactly what assemble produces.
+///
+/// `options` must already pass `AssembleOptions::validate`; the error
+/// reports queries that select nothing to assemble.
+pub(crate) fn assemble_context(
     query: &str,
     from_files: &[String],
     options: &AssembleOptions,
+    forward_index: &ForwardIndex,
+    docs: &DocCache,
     index_dir: &Path,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if options.format != "markdown" {
-        return Err("Only markdown format is supported currently".into());
-    }
-
-    let forward_index = load_forward_index(index_dir)?;
-    let selection =
-        match collect_context_selection(query, from_files, &forward_index, options.max_sections) {
-            Ok(selection) => selection,
-            Err(ContextSelectionIssue::NoSearchableTerms) => {
-                println!("# No searchable terms in query. Try different keywords.");
-                return Ok(());
-            }
-            Err(ContextSelectionIssue::MissingFiles(missing)) => {
-                eprintln!(
-                    "{}",
-                    "Some files were not found in the index (they may be missing or excluded):"
-                        .yellow()
-                );
-                for path in missing {
-                    eprintln!("  - {path}");
-                }
-                return Ok(());
-            }
-            Err(ContextSelectionIssue::NoIndexedFilesMatched) => {
-                println!("# No indexed files matched the provided inputs.");
-                return Ok(());
-            }
-            Err(ContextSelectionIssue::NoRelevantSections(label)) => {
-                println!("# No relevant sections found for query: \"{label}\"");
-                return Ok(());
-            }
-        };
+) -> Result<AssembledContext, ContextSelectionIssue> {
+    let phase_start = Instant::now();
+    let search = SectionSearch {
+        index: forward_index,
+        docs,
+        max_sections: options.max_sections,
+        synonyms: &options.synonyms,
+        scope: &options.scope,
+        canonicality_weight: options.canonicality_weight,
+        heading_boost: options.heading_boost,
+    };
+    let selection = collect_context_selection(query, from_files, &search)?;
+    report_timing(options.timing, "primary selection", phase_start.elapsed());
     let query_label = selection.query_label;
     let query_for_refiner = selection.query_for_refiner;
     let primary_sections = selection.sections;
+    let digest_scope = DigestScope {
+        label: options.scope.describe(),
+        excluded_docs: selection.excluded_docs,
+        scanned_docs: Some(selection.scanned_docs),
+    };
 
     let primary_tokens: usize = primary_sections
         .iter()
-        .map(|s| estimate_tokens(&s.content))
+        .map(|s| count_tokens(&s.content, options.tokenizer))
         .sum();
 
     // Phase 2: Cross-reference expansion (if depth > 0)
+    let phase_start = Instant::now();
     let mut all_sections = primary_sections.clone();
 
     if options.depth > 0 {
-        // Calculate xref token budget
-        const XREF_TOKEN_FRACTION: f64 = 0.3;
-        const XREF_TOKEN_ABS_MAX: usize = 2000;
-
-        let xref_cap =
-            ((options.max_tokens as f64 * XREF_TOKEN_FRACTION) as usize).min(XREF_TOKEN_ABS_MAX);
-        let remaining_tokens = options.max_tokens.saturating_sub(primary_tokens);
-        let xref_token_budget = remaining_tokens.min(xref_cap);
+        let xref_token_budget = options
+            .xref
+            .token_budget(options.max_tokens, primary_tokens);
 
         let primary_docs: HashSet<String> = primary_sections
             .iter()
             .map(|s| s.doc_path.clone())
             .collect();
+        let expansion = XrefExpansion {
+            index: forward_index,
+            docs,
+            scope: &options.scope,
+            config: &options.xref,
+            tokenizer: options.tokenizer,
+        };
 
         if options.use_relations {
             // Graph-aware expansion via persisted relation edges
@@ -569,41 +1029,171 @@ pub(crate) fn cmd_assemble(
                 let xref_sections = resolve_crossrefs_from_relations(
                     &relation_index,
                     &primary_docs,
-                    &forward_index,
                     xref_token_budget,
+                    &expansion,
                 );
                 all_sections.extend(xref_sections);
             }
         } else {
             // Legacy on-the-fly cross-reference expansion
-            let adr_index = build_adr_index(&forward_index);
+            let adr_index = build_adr_index(forward_index);
             let crossrefs = collect_crossrefs(&primary_sections, &adr_index);
 
             if xref_token_budget > 0 && !crossrefs.is_empty() {
-                let xref_sections =
-                    resolve_crossrefs(&crossrefs, &primary_docs, &forward_index, xref_token_budget);
+                let xref_sections = resolve_crossrefs(
+                    &crossrefs,
+                    &primary_docs,
+                    &adr_index,
+                    xref_token_budget,
+                    options.depth,
+                    &expansion,
+                );
                 all_sections.extend(xref_sections);
             }
         }
     }
-    let (all_sections, _) = dedupe_section_matches(all_sections);
+    let all_sections = if options.dedup_threshold.is_some() {
+        // Copied content is merged after refinement, keeping the most canonical copy.
+        drop_overlapping_sections(all_sections)
+    } else {
+        dedupe_section_matches(all_sections).0
+    };
+
+    report_timing(options.timing, "xref expansion", phase_start.elapsed());
 
     // Phase 3: Extractive refinement (increase signal density)
+    let phase_start = Instant::now();
     let max_tokens_per_section = options.max_tokens / all_sections.len().max(1);
-    let refined_sections =
-        apply_extractive_refiner(all_sections, &query_for_refiner, max_tokens_per_section);
+    let mut refined_sections = apply_extractive_refiner(
+        all_sections,
+        &query_for_refiner,
+        &forward_index.stop_words,
+        max_tokens_per_section,
+        options.tokenizer,
+    );
+    if let Some(threshold) = options.dedup_threshold {
+        refined_sections = merge_similar_sections(refined_sections, threshold);
+    }
+    rank_refined_sections(&mut refined_sections, options.canonicality_weight);
+    report_timing(options.timing, "refinement", phase_start.elapsed());
+
+    Ok(AssembledContext {
+        query_label,
+        query_for_refiner,
+        primary_sections,
+        refined_sections,
+        scope: digest_scope,
+    })
+}
+
+/// Main assemble command handler
+pub(crate) fn cmd_assemble(
+    query: &str,
+    from_files: &[String],
+    options: &AssembleOptions,
+    index_dir: &IndexDir,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // `None` selects the structured JSON output
+    let digest_format = match options.format.as_str() {
+        "markdown" => Some(DigestFormat::Markdown),
+        "text" => Some(DigestFormat::Text),
+        "json" => None,
+        other => {
+            return Err(
+                format!("Unsupported format: {other} (expected markdown, text, or json)").into(),
+            );
+        }
+    };
+    let json = digest_format.is_none();
+    let digest_format = digest_format.unwrap_or(DigestFormat::Markdown);
+    let plain = digest_format == DigestFormat::Text;
+
+    options.validate()?;
+
+    let forward_index = load_forward_index(index_dir)?;
+    options.scope.check_types(&forward_index)?;
+    if from_files.is_empty() {
+        warn_if_missing_bm25(&forward_index);
+    }
+    let docs = DocCache::default();
+    let context =
+        match assemble_context(query, from_files, options, &forward_index, &docs, index_dir) {
+            Ok(context) => context,
+            Err(issue) if json => {
+                let error = match issue {
+                    ContextSelectionIssue::NoSearchableTerms => "no_query_terms",
+                    ContextSelectionIssue::MissingFiles(_) => "missing_files",
+                    ContextSelectionIssue::NoIndexedFilesMatched => "no_indexed_files",
+                    ContextSelectionIssue::NoRelevantSections(_) => "no_relevant_sections",
+                };
+                let result = AssembleJsonResult {
+                    query: query.to_string(),
+                    types: options.scope.types.clone(),
+                    max_tokens: options.max_tokens,
+                    estimated_tokens: 0,
+                    total_sections: 0,
+                    truncated: false,
+                    sections: Vec::new(),
+                    sources: Vec::new(),
+                    error: Some(error.to_string()),
+                };
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                return Ok(());
+            }
+            Err(ContextSelectionIssue::NoSearchableTerms) => {
+                println!("# No searchable terms in query. Try different keywords.");
+                return Ok(());
+            }
+            Err(ContextSelectionIssue::MissingFiles(missing)) => {
+                eprintln!(
+                    "{}",
+                    "Some files were not found in the index (they may be missing or excluded):"
+                        .yellow()
+                );
+                for path in missing {
+                    eprintln!("  - {path}");
+                }
+                return Ok(());
+            }
+            Err(ContextSelectionIssue::NoIndexedFilesMatched) => {
+                println!("# No indexed files matched the provided inputs.");
+                return Ok(());
+            }
+            Err(ContextSelectionIssue::NoRelevantSections(label)) => {
+                println!("# No relevant sections found for query: \"{label}\"");
+                return Ok(());
+            }
+        };
+
+    if json {
+        let phase_start = Instant::now();
+        let result = build_assemble_json(
+            &context.query_label,
+            &context.refined_sections,
+            &forward_index,
+            &context.query_for_refiner,
+            options,
+        );
+        report_timing(options.timing, "distill", phase_start.elapsed());
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
 
     // If doc_terms requested, prepend a source summary
     if options.doc_terms > 0 {
-        println!("<!-- Source Documents -->");
-        let query_terms = if query_for_refiner.is_empty() {
+        if plain {
+            println!("// Source Documents");
+        } else {
+            println!("<!-- Source Documents -->");
+        }
+        let query_terms = if context.query_for_refiner.is_empty() {
             Vec::new()
         } else {
-            parse_query_terms(&query_for_refiner, true)
+            parse_query_terms(&context.query_for_refiner, Some(&forward_index.stop_words))
         };
         let mut seen_docs: HashSet<String> = HashSet::new();
 
-        for section in &refined_sections {
+        for section in &context.refined_sections {
             if seen_docs.contains(&section.section.doc_path) {
                 continue;
             }
@@ -615,38 +1205,165 @@ pub(crate) fn cmd_assemble(
                     &forward_index.idf_map,
                     &query_terms,
                     options.doc_terms,
+                    forward_index.stemmer,
                 );
-                if !top_terms.is_empty() {
-                    println!(
-                        "<!-- {} : {} -->",
-                        section.section.doc_path,
-                        top_terms.join(", ")
-                    );
+                if top_terms.is_empty() {
+                    continue;
+                }
+                let doc_path = &section.section.doc_path;
+                let terms = top_terms.join(", ");
+                if plain {
+                    println!("// {doc_path} : {terms}");
+                } else {
+                    println!("<!-- {doc_path} : {terms} -->");
                 }
             }
         }
         println!();
     }
 
-    // Phase 4: Distill to markdown
-    let digest_sections: Vec<SectionMatch> = refined_sections
-        .iter()
-        .map(|section| section.section.clone())
-        .collect();
-    let digest = distill_to_markdown(&digest_sections, &query_label, options.max_tokens);
+    // Phase 4: Distill to markdown or plain text
+    let phase_start = Instant::now();
+    let digest = render_digest(&context, options, digest_format);
+    report_timing(options.timing, "distill", phase_start.elapsed());
 
     println!("{digest}");
 
     Ok(())
 }
 
-/// Evaluation command handler - runs retrieval pipeline against test questions
+/// Render an assembled context as a markdown or plain-text digest, noting
+/// the docs each merged section was also found in
+pub(crate) fn render_digest(
+    context: &AssembledContext,
+    options: &AssembleOptions,
+    format: DigestFormat,
+) -> String {
+    let plain = format == DigestFormat::Text;
+    let digest_sections: Vec<SectionMatch> = context
+        .refined_sections
+        .iter()
+        .map(|refined| {
+            let mut section = refined.section.clone();
+            if !refined.also_in.is_empty() {
+                use std::fmt::Write as _;
+                let also_in = refined.also_in.join(", ");
+                let _ = if plain {
+                    write!(section.content, "\n\n(also in {also_in})")
+                } else {
+                    write!(section.content, "\n\n*(also in {also_in})*")
+                };
+            }
+            section
+        })
+        .collect();
+    distill(
+        &digest_sections,
+        &context.query_label,
+        options.max_tokens,
+        format,
+        &context.scope,
+        options.canonicality_weight,
+        options.tokenizer,
+    )
+}
+
+/// Structured form of the assembled context for `assemble --format json`.
+///
+/// Sections are kept in ranked order until their content would exceed
+/// `max_tokens`; the markdown header and per-section framing are not counted.
+/// `sources` lists the documents and line ranges behind the kept sections.
+pub(crate) fn build_assemble_json(
+    query_label: &str,
+    refined_sections: &[RefinedSection],
+    forward_index: &ForwardIndex,
+    query_for_refiner: &str,
+    options: &AssembleOptions,
+) -> AssembleJsonResult {
+    let query_terms = if query_for_refiner.is_empty() {
+        Vec::new()
+    } else {
+        parse_query_terms(query_for_refiner, Some(&forward_index.stop_words))
+    };
+
+    let mut sections = Vec::new();
+    let mut used_tokens = 0usize;
+    let mut truncated = false;
+
+    for refined in refined_sections {
+        let section = &refined.section;
+        let section_tokens = count_tokens(&section.content, options.tokenizer);
+        if used_tokens + section_tokens > options.max_tokens {
+            truncated = true;
+            break;
+        }
+        used_tokens += section_tokens;
+
+        let doc_terms = match forward_index.files.get(&section.doc_path) {
+            Some(entry) if options.doc_terms > 0 => get_top_doc_terms(
+                entry,
+                &forward_index.idf_map,
+                &query_terms,
+                options.doc_terms,
+                forward_index.stemmer,
+            ),
+            _ => Vec::new(),
+        };
+
+        sections.push(AssembleJsonSection {
+            doc_path: section.doc_path.clone(),
+            heading: section.heading.clone(),
+            line_start: section.line_start,
+            line_end: section.line_end,
+            bm25_score: section.bm25_score,
+            canonicality: section.canonicality,
+            content: section.content.clone(),
+            estimated_tokens: section_tokens,
+            doc_terms,
+            also_in: refined.also_in.clone(),
+            via: section.via.clone(),
+        });
+    }
+
+    let sources = collect_sources(
+        refined_sections
+            .iter()
+            .take(sections.len())
+            .map(|refined| &refined.section),
+    );
+
+    AssembleJsonResult {
+        query: query_label.to_string(),
+        types: options.scope.types.clone(),
+        max_tokens: options.max_tokens,
+        estimated_tokens: used_tokens,
+        total_sections: sections.len(),
+        truncated,
+        sections,
+        sources,
+        error: None,
+    }
+}
+
+/// Evaluation command handler - runs the assemble pipeline against test
+/// questions with `options` as the assemble settings.
+///
+/// Returns `true` when the pass rate is below `fail_under`.
 pub(crate) fn cmd_eval(
     questions_path: &Path,
-    index_dir: &Path,
+    index_dir: &IndexDir,
     json: bool,
     k_values: &[usize],
-) -> Result<(), Box<dyn std::error::Error>> {
+    options: &AssembleOptions,
+    fail_under: Option<f64>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if let Some(rate) = fail_under {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(format!("--fail-under must be between 0.0 and 1.0, got {rate}").into());
+        }
+    }
+    options.validate()?;
+
     // Load questions from JSONL file
     let questions_content = fs::read_to_string(questions_path)?;
     let questions: Vec<Question> = questions_content
@@ -664,90 +1381,65 @@ pub(crate) fn cmd_eval(
         } else {
             println!("No questions found in {}", questions_path.display());
         }
-        return Ok(());
+        return Ok(false);
     }
 
-    // Load index once
+    // Load index once; docs read for one question are reused by the rest
     let forward_index = load_forward_index(index_dir)?;
+    let docs = DocCache::default();
 
     // Run evaluation for each question
     let mut results = Vec::new();
 
     for question in &questions {
-        // Run assemble internally (capture output as string)
-        let primary_sections = search_relevant_sections(&question.q, &forward_index, 20);
-
-        // Compute ranked doc list from initial BM25 retrieval
-        let ranked_docs = unique_doc_ranking(&primary_sections);
-
-        if primary_sections.is_empty() {
+        // Run the assemble pipeline with the same settings `assemble` uses
+        let Ok(context) =
+            assemble_context(&question.q, &[], options, &forward_index, &docs, index_dir)
+        else {
             results.push(EvalResult {
                 id: question.id,
                 question: question.q.clone(),
                 hits: 0,
                 total: question.expect.len(),
+                missed: question.expect.clone(),
                 passed: false,
                 tokens: 0,
-                ranked_docs,
+                ranked_docs: Vec::new(),
                 ranking: None,
+                files: (!question.expect_files.is_empty())
+                    .then(|| compute_file_coverage(&question.expect_files, &HashSet::new())),
                 digest: String::new(),
             });
             continue;
-        }
-
-        let primary_tokens: usize = primary_sections
-            .iter()
-            .map(|s| estimate_tokens(&s.content))
-            .sum();
-
-        // Cross-reference expansion
-        let mut all_sections = primary_sections.clone();
-        let adr_index = build_adr_index(&forward_index);
-        let crossrefs = collect_crossrefs(&primary_sections, &adr_index);
-
-        const XREF_TOKEN_FRACTION: f64 = 0.3;
-        const XREF_TOKEN_ABS_MAX: usize = 2000;
-        let max_tokens: usize = 8000; // Default for eval
-
-        let xref_cap = ((max_tokens as f64 * XREF_TOKEN_FRACTION) as usize).min(XREF_TOKEN_ABS_MAX);
-        let remaining_tokens = max_tokens.saturating_sub(primary_tokens);
-        let xref_token_budget = remaining_tokens.min(xref_cap);
-
-        if xref_token_budget > 0 && !crossrefs.is_empty() {
-            let primary_docs: HashSet<String> = primary_sections
-                .iter()
-                .map(|s| s.doc_path.clone())
-                .collect();
-
-            let xref_sections =
-                resolve_crossrefs(&crossrefs, &primary_docs, &forward_index, xref_token_budget);
-
-            all_sections.extend(xref_sections);
-        }
-
-        // Extractive refinement
-        let max_tokens_per_section = max_tokens / all_sections.len().max(1);
-        let refined_sections =
-            apply_extractive_refiner(all_sections, &question.q, max_tokens_per_section);
+        };
 
-        // Distill to markdown
-        let digest_sections: Vec<SectionMatch> = refined_sections
-            .iter()
-            .map(|section| section.section.clone())
-            .collect();
-        let digest = distill_to_markdown(&digest_sections, &question.q, max_tokens);
+        // Compute ranked doc list from initial BM25 retrieval
+        let ranked_docs = unique_doc_ranking(&context.primary_sections);
+        let digest = render_digest(&context, options, DigestFormat::Markdown);
 
         // Check coverage of expected substrings
         let digest_lower = digest.to_lowercase();
-        let hits = question
+        let missed: Vec<String> = question
             .expect
             .iter()
-            .filter(|e| digest_lower.contains(&e.to_lowercase()))
-            .count();
+            .filter(|e| !digest_lower.contains(&e.to_lowercase()))
+            .cloned()
+            .collect();
+        let hits = question.expect.len() - missed.len();
+
+        // Check which expected source files contributed sections
+        let files = (!question.expect_files.is_empty()).then(|| {
+            let retrieved: HashSet<String> = context
+                .refined_sections
+                .iter()
+                .map(|refined| refined.section.doc_path.clone())
+                .collect();
+            compute_file_coverage(&question.expect_files, &retrieved)
+        });
 
         let min_hits = question.min_hits.unwrap_or(question.expect.len());
-        let passed = hits >= min_hits;
-        let tokens = estimate_tokens(&digest);
+        let passed = hits >= min_hits && files.as_ref().is_none_or(|f| f.missing.is_empty());
+        let tokens = count_tokens(&digest, options.tokenizer);
 
         // Compute ranking metrics if relevant_docs is provided
         let ranking = question.relevant_docs.as_ref().map(|rel_docs| {
@@ -760,10 +1452,12 @@ pub(crate) fn cmd_eval(
             question: question.q.clone(),
             hits,
             total: question.expect.len(),
+            missed,
             passed,
             tokens,
             ranked_docs,
             ranking,
+            files,
             digest,
         });
     }
@@ -772,6 +1466,21 @@ pub(crate) fn cmd_eval(
     let passed_count = results.iter().filter(|r| r.passed).count();
     let total = results.len();
     let pass_rate_pct = passed_count as f64 / total as f64 * 100.0;
+    let expected_hits: usize = results.iter().map(|r| r.hits).sum();
+    let expected_total: usize = results.iter().map(|r| r.total).sum();
+    let recall = if expected_total == 0 {
+        1.0
+    } else {
+        expected_hits as f64 / expected_total as f64
+    };
+    let mean_tokens = results.iter().map(|r| r.tokens).sum::<usize>() as f64 / total as f64;
+    let below_threshold = fail_under.is_some_and(|rate| pass_rate_pct < rate * 100.0);
+    if let Some(rate) = fail_under.filter(|_| below_threshold) {
+        eprintln!(
+            "Pass rate {pass_rate_pct:.0}% is below --fail-under {:.0}%",
+            rate * 100.0
+        );
+    }
 
     // Compute aggregate ranking metrics across questions that have relevance data
     let per_question_rankings: Vec<RankingMetrics> =
@@ -781,34 +1490,34 @@ pub(crate) fn cmd_eval(
     } else {
         Some(aggregate_ranking_metrics(&per_question_rankings, k_values))
     };
+    let per_question_files: Vec<FileCoverage> =
+        results.iter().filter_map(|r| r.files.clone()).collect();
+    let file_metrics =
+        (!per_question_files.is_empty()).then(|| aggregate_file_coverage(&per_question_files));
 
     if json {
         let json_results: Vec<EvalQuestionResult> = results
             .iter()
-            .map(|r| {
-                let expected: Vec<String> = questions
-                    .iter()
-                    .find(|q| q.id == r.id)
-                    .map(|q| q.expect.clone())
-                    .unwrap_or_default();
-                let digest_lower = r.digest.to_lowercase();
-                let found: Vec<String> = expected
+            .zip(&questions)
+            .map(|(r, q)| {
+                let found: Vec<String> = q
+                    .expect
                     .iter()
-                    .filter(|e| digest_lower.contains(&e.to_lowercase()))
-                    .cloned()
-                    .collect();
-                let missing: Vec<String> = expected
-                    .iter()
-                    .filter(|e| !digest_lower.contains(&e.to_lowercase()))
+                    .filter(|e| !r.missed.contains(e))
                     .cloned()
                     .collect();
                 EvalQuestionResult {
+                    id: r.id,
                     question: r.question.clone(),
                     passed: r.passed,
-                    expected,
+                    hits: r.hits,
+                    total: r.total,
+                    tokens: r.tokens,
+                    expected: q.expect.clone(),
                     found,
-                    missing,
+                    missing: r.missed.clone(),
                     ranking: r.ranking.clone(),
+                    files: r.files.clone(),
                 }
             })
             .collect();
@@ -819,11 +1528,14 @@ pub(crate) fn cmd_eval(
             passed: passed_count,
             failed: total - passed_count,
             pass_rate: pass_rate_pct,
+            recall,
+            mean_tokens,
             results: json_results,
             ranking_metrics: aggregate,
+            file_metrics,
         };
         println!("{}", serde_json::to_string_pretty(&output)?);
-        return Ok(());
+        return Ok(below_threshold);
     }
 
     // Print results (human-readable)
@@ -841,6 +1553,22 @@ pub(crate) fn cmd_eval(
         println!("[{}] {}", result.id, result.question.white().bold());
         println!("  - hits: {}/{} {}", result.hits, result.total, status);
         println!("  - size: {} tokens", result.tokens);
+        if !result.missed.is_empty() {
+            let missed: Vec<String> = result.missed.iter().map(|m| format!("{m:?}")).collect();
+            println!("  - missed: {}", missed.join(", "));
+        }
+        if let Some(files) = &result.files {
+            println!(
+                "  - files: {}/{} (precision {:.3}, recall {:.3})",
+                files.found.len(),
+                files.found.len() + files.missing.len(),
+                files.precision,
+                files.recall
+            );
+            if !files.missing.is_empty() {
+                println!("  - missed files: {}", files.missing.join(", "));
+            }
+        }
 
         if let Some(ranking) = &result.ranking {
             println!("  - MRR: {:.3}", ranking.mrr);
@@ -863,6 +1591,17 @@ pub(crate) fn cmd_eval(
     println!("{}", "Summary".cyan().bold());
     println!("  Passed: {passed_count}/{total} ({pass_rate_pct:.0}%)");
     println!("  Failed: {}/{}", total - passed_count, total);
+    println!(
+        "  Recall: {expected_hits}/{expected_total} ({:.0}%)",
+        recall * 100.0
+    );
+    println!("  Mean tokens: {mean_tokens:.0}");
+    if let Some(files) = &file_metrics {
+        println!(
+            "  File precision: {:.3}, recall: {:.3} ({} questions with expect_files)",
+            files.mean_precision, files.mean_recall, files.questions_with_expect_files
+        );
+    }
 
     if let Some(agg) = &aggregate {
         println!();
@@ -898,5 +1637,5 @@ pub(crate) fn cmd_eval(
         println!();
     }
 
-    Ok(())
+    Ok(below_threshold)
 }
@@ -40,6 +40,99 @@ pub fn load_config(path: &Path, quiet: bool) -> Option<YoreConfig> {
     }
 }
 
+/// Commented `.yore.toml` written by `yore init`; `policy_path` is the
+/// rules file it points at.
+pub fn init_config_template(policy_path: &str) -> String {
+    let policy_path = toml::Value::String(policy_path.to_string());
+    format!(
+        r#"# Yore configuration. Select a profile with `yore --profile <name> <command>`.
+
+# Index profile "docs": `yore --profile docs build` indexes these roots, and
+# other commands run with `--profile docs` read the index from `output`.
+[index.docs]
+# Directories to index, relative to the repository root
+roots = ["docs"]
+# File extensions to index
+types = ["md", "txt", "rst", "adoc"]
+# Index directory
+output = ".yore"
+
+# Rules checked by `yore policy` and `yore check --taxonomy`
+[policy]
+rules-file = {policy_path}
+"#
+    )
+}
+
+/// Starter policy file written by `yore init`.
+pub const INIT_POLICY_TEMPLATE: &str = r###"# Yore documentation policy, checked with `yore policy --config <this file>`.
+#
+# Each rule applies to files matching `pattern`. Rules can also use
+# must_not_contain, must_match, must_not_match, min_length, and max_length.
+rules:
+  # Architecture decision records must state their status and decision
+  - name: adr-required-sections
+    pattern: "docs/adr/*.md"
+    severity: error
+    must_contain:
+      - "## Status"
+      - "## Decision"
+"###;
+
+/// Write a starter config and policy file, refusing to replace existing
+/// files unless `force` is set.
+pub fn cmd_init(
+    config_path: &Path,
+    policy_path: &Path,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = init_config_template(&policy_path.to_string_lossy());
+    // Both templates must load with the same structs yore reads them into
+    toml::from_str::<YoreConfig>(&config)?;
+    serde_yaml::from_str::<PolicyConfig>(INIT_POLICY_TEMPLATE)?;
+
+    let targets = [
+        (config_path, config.as_str()),
+        (policy_path, INIT_POLICY_TEMPLATE),
+    ];
+    if !force {
+        let existing: Vec<String> = targets
+            .iter()
+            .filter(|(path, _)| path.exists())
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        if !existing.is_empty() {
+            return Err(format!(
+                "Refusing to overwrite {} (use --force to replace)",
+                existing.join(", ")
+            )
+            .into());
+        }
+    }
+
+    for (path, content) in targets {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+        println!("{} {}", "Wrote".green(), path.display());
+    }
+
+    println!();
+    println!("{}", "Next steps:".cyan());
+    println!(
+        "  1. Edit the roots in {} to match your docs",
+        config_path.display()
+    );
+    println!("  2. yore --profile docs build");
+    println!(
+        "  3. yore check --taxonomy --policy {}",
+        policy_path.display()
+    );
+
+    Ok(())
+}
+
 pub fn resolve_build_params(
     path: PathBuf,
     output: PathBuf,
@@ -50,7 +143,7 @@ pub fn resolve_build_params(
     // Defaults from CLI definition
     let default_path = PathBuf::from(".");
     let default_output = PathBuf::from(".yore");
-    let default_types = "md,txt,rst".to_string();
+    let default_types = "md,txt,rst,adoc".to_string();
 
     let mut effective_path = path;
     let mut effective_output = output;
@@ -84,6 +177,15 @@ pub fn resolve_build_params(
     (effective_path, effective_output, effective_types, roots)
 }
 
+/// Skip-directory list from the active profile, if it sets one.
+pub fn resolve_skip_dirs(
+    profile: Option<&str>,
+    config: &Option<YoreConfig>,
+) -> Option<Vec<String>> {
+    let (profile_name, cfg) = (profile?, config.as_ref()?);
+    cfg.index.get(profile_name)?.skip_dirs.clone()
+}
+
 pub fn resolve_index_path(
     index: PathBuf,
     profile: Option<&str>,
@@ -1,37 +1,284 @@
 use colored::Colorize;
 use ignore::WalkBuilder;
-use regex::Regex;
-use std::collections::{HashMap, HashSet};
+#[cfg(feature = "watch")]
+use notify::{RecursiveMode, Watcher};
+use rayon::prelude::*;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+#[cfg(feature = "watch")]
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 use crate::assemble::extract_relations;
 use crate::search::*;
 use crate::types::*;
 use crate::util::*;
 
-#[allow(clippy::too_many_arguments)]
+/// Indexed form of one file: its index key, entry, and metrics.
+type IndexedDoc = (String, FileEntry, DocumentMetrics, SimhashFeatures);
+
+/// Inputs that stay fixed across a build and its `--watch` rebuilds.
+struct BuildPlan<'a> {
+    path: &'a Path,
+    output: &'a Path,
+    extensions: HashSet<String>,
+    exclude: &'a [String],
+    roots: Option<&'a [PathBuf]>,
+    /// Directory names whose contents are never indexed
+    skip_dirs: Vec<String>,
+    source_root: PathBuf,
+    index_options: IndexOptions,
+    format: IndexFormat,
+}
+
+/// Counts reported after the index files are written.
+struct IndexSummary {
+    file_count: usize,
+    total_headings: usize,
+    total_links: usize,
+    unique_keywords: usize,
+    relations_count: usize,
+}
+
+/// `build` settings as given on the command line; names are validated by
+/// `cmd_build`. The defaults match the CLI's.
+pub struct BuildOptions<'a> {
+    /// Comma-separated file extensions to index
+    pub types: &'a str,
+    pub exclude: &'a [String],
+    pub quiet: bool,
+    /// Profile roots; files outside them are skipped
+    pub roots: Option<&'a [PathBuf]>,
+    /// Directory names never indexed; `None` uses `DEFAULT_SKIP_DIRS`
+    pub skip_dirs: Option<&'a [String]>,
+    pub json: bool,
+    pub track_renames: bool,
+    /// Indexing threads; `None` uses one per core
+    pub jobs: Option<usize>,
+    pub stemmer: &'a str,
+    pub positions: bool,
+    pub minhash_perms: usize,
+    pub simhash_weighting: &'a str,
+    pub stop_words: Option<&'a Path>,
+    pub stop_words_mode: &'a str,
+    pub format: &'a str,
+    /// Keep rebuilding on changes, debounced by this long (`--watch`)
+    pub watch_debounce: Option<Duration>,
+}
+
+impl Default for BuildOptions<'_> {
+    fn default() -> Self {
+        BuildOptions {
+            types: "md,txt,rst,adoc",
+            exclude: &[],
+            quiet: false,
+            roots: None,
+            skip_dirs: None,
+            json: false,
+            track_renames: false,
+            jobs: None,
+            stemmer: "simple",
+            positions: false,
+            minhash_perms: 128,
+            simhash_weighting: "idf",
+            stop_words: None,
+            stop_words_mode: "merge",
+            format: "json",
+            watch_debounce: None,
+        }
+    }
+}
+
 pub fn cmd_build(
     path: &Path,
     output: &Path,
-    types: &str,
-    exclude: &[String],
-    quiet: bool,
-    roots: Option<&[PathBuf]>,
-    json: bool,
-    track_renames: bool,
+    options: &BuildOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let &BuildOptions {
+        types,
+        exclude,
+        quiet,
+        roots,
+        skip_dirs,
+        json,
+        track_renames,
+        jobs,
+        stemmer,
+        positions,
+        minhash_perms,
+        simhash_weighting,
+        stop_words,
+        stop_words_mode,
+        format,
+        watch_debounce,
+    } = options;
     let start = Instant::now();
+    let stemmer = StemmerKind::from_name(stemmer).ok_or_else(|| {
+        format!("Unsupported stemmer: {stemmer} (expected porter, simple, or none)")
+    })?;
+    let simhash_weighting = SimhashWeighting::from_name(simhash_weighting).ok_or_else(|| {
+        format!("Unsupported SimHash weighting: {simhash_weighting} (expected idf or uniform)")
+    })?;
+    let stop_words = load_index_stop_words(stop_words, stop_words_mode)?;
+    if minhash_perms == 0 {
+        return Err("--minhash-perms must be at least 1".into());
+    }
+    let format = IndexFormat::from_name(format)
+        .ok_or_else(|| format!("Unsupported index format: {format} (expected json or bin)"))?;
+    if watch_debounce.is_some() && !cfg!(feature = "watch") {
+        return Err("--watch requires yore built with the `watch` feature".into());
+    }
     let source_root = canonicalize_existing_path(&std::env::current_dir()?);
 
     if !quiet && !json {
         println!("{} {}", "Indexing".cyan().bold(), path.display());
     }
 
-    // Parse file types
-    let extensions: HashSet<String> = types.split(',').map(|s| s.trim().to_lowercase()).collect();
+    let plan = BuildPlan {
+        path,
+        output,
+        // Parse file types
+        extensions: types.split(',').map(|s| s.trim().to_lowercase()).collect(),
+        exclude,
+        roots,
+        skip_dirs: skip_dirs.map_or_else(
+            || DEFAULT_SKIP_DIRS.iter().map(|d| (*d).to_string()).collect(),
+            <[String]>::to_vec,
+        ),
+        source_root,
+        index_options: IndexOptions {
+            stemmer,
+            positions,
+            stop_words,
+            minhash_perms,
+            simhash_weighting,
+        },
+        format,
+    };
+
+    // Index files in parallel; unreadable files are skipped as before
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?;
+    let candidates = collect_build_candidates(&plan);
+    let docs: HashMap<PathBuf, IndexedDoc> = index_build_candidates(&pool, &plan, &candidates)
+        .into_iter()
+        .collect();
+    let summary = write_index_files(&plan, docs.values().cloned().collect())?;
+
+    // Track git renames if requested
+    let renames_count = if track_renames {
+        if !quiet && !json {
+            println!("  Extracting git rename history...");
+        }
+        let rename_history = extract_git_renames(path);
+        let count = rename_history.renames.len();
+        let rename_path = output.join("rename_history.json");
+        fs::write(&rename_path, serde_json::to_string_pretty(&rename_history)?)?;
+        if !quiet && !json {
+            println!("  Tracked {count} file renames");
+        }
+        Some(count)
+    } else {
+        None
+    };
+
+    let elapsed = start.elapsed();
 
+    if json {
+        let result = BuildResult {
+            index_path: output.to_string_lossy().to_string(),
+            files_indexed: summary.file_count,
+            total_headings: summary.total_headings,
+            total_links: summary.total_links,
+            unique_keywords: summary.unique_keywords,
+            duration_ms: elapsed.as_millis(),
+            renames_tracked: renames_count,
+            total_relations: Some(summary.relations_count),
+        };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else if !quiet {
+        println!();
+        println!("{}", "Index Statistics".green().bold());
+        println!(
+            "  Files indexed:    {}",
+            summary.file_count.to_string().cyan()
+        );
+        println!(
+            "  Unique keywords:  {}",
+            summary.unique_keywords.to_string().cyan()
+        );
+        println!(
+            "  Total headings:   {}",
+            summary.total_headings.to_string().cyan()
+        );
+        println!(
+            "  Total links:      {}",
+            summary.total_links.to_string().cyan()
+        );
+        println!(
+            "  Relations:        {}",
+            summary.relations_count.to_string().cyan()
+        );
+        println!("  Time elapsed:     {elapsed:.2?}");
+        println!();
+        println!(
+            "{} {}",
+            "Indexes written to".green(),
+            output.display().to_string().cyan()
+        );
+    }
+
+    if let Some(debounce) = watch_debounce {
+        watch_and_rebuild(&plan, &pool, docs, debounce, quiet, json)?;
+    }
+
+    Ok(())
+}
+
+/// Directories left out of `build` unless a profile sets `skip_dirs`.
+pub const DEFAULT_SKIP_DIRS: &[&str] = &[
+    "node_modules",
+    ".git",
+    "target",
+    "vendor",
+    "venv",
+    "__pycache__",
+];
+
+/// Whether any directory component of `path` is named in `skip_dirs`.
+/// Only whole names match, so `docs/vendor-integration/` is kept while
+/// `vendor/` is skipped.
+pub(crate) fn in_skipped_dir(path: &Path, skip_dirs: &[String]) -> bool {
+    path.parent().is_some_and(|parent| {
+        parent.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            skip_dirs.iter().any(|dir| *dir == name)
+        })
+    })
+}
+
+/// Walk the build path for files with an indexed extension.
+fn collect_build_candidates(plan: &BuildPlan) -> Vec<PathBuf> {
+    walk_build_path(
+        plan.path,
+        &plan.extensions,
+        plan.exclude,
+        plan.roots,
+        &plan.skip_dirs,
+    )
+}
+
+fn walk_build_path(
+    path: &Path,
+    extensions: &HashSet<String>,
+    exclude: &[String],
+    roots: Option<&[PathBuf]>,
+    skip_dirs: &[String],
+) -> Vec<PathBuf> {
     // Build walker with ignore patterns
     let mut builder = WalkBuilder::new(path);
     builder.hidden(true).git_ignore(true).git_global(true);
@@ -41,42 +288,23 @@ pub fn cmd_build(
         builder.add_ignore(Path::new(pattern));
     }
 
-    // Collect files
-    let mut forward_index = ForwardIndex {
-        files: HashMap::new(),
-        indexed_at: chrono_now(),
-        version: 4, // Version 4 adds source_root metadata for portable file resolution
-        source_root: source_root.to_string_lossy().to_string(),
-        avg_doc_length: 0.0,
-        idf_map: HashMap::new(),
-    };
-
-    let mut reverse_index = ReverseIndex {
-        keywords: HashMap::new(),
-    };
-    let mut document_metrics_index = DocumentMetricsIndex {
-        indexed_at: chrono_now(),
-        version: 1,
-        files: HashMap::new(),
-    };
-
-    let mut file_count = 0;
-    let mut total_headings = 0;
-    let mut total_links = 0;
-
+    // Collect candidate files first so indexing can fan out across threads
+    let mut candidates: Vec<PathBuf> = Vec::new();
     for entry in builder.build().filter_map(std::result::Result::ok) {
-        let path = entry.path();
+        let file = entry.path();
 
         // Skip directories
-        if path.is_dir() {
+        if file.is_dir() {
             continue;
         }
 
-        // If roots are configured, skip files outside those roots
+        // If roots are configured, skip files outside those roots. Profile
+        // builds walk "." so paths carry a leading "./" that roots lack.
         if let Some(root_list) = roots {
+            let relative = file.strip_prefix(".").unwrap_or(file);
             let mut inside_any_root = false;
             for root in root_list {
-                if path.starts_with(root) {
+                if relative.starts_with(root.strip_prefix(".").unwrap_or(root)) {
                     inside_any_root = true;
                     break;
                 }
@@ -87,7 +315,7 @@ pub fn cmd_build(
         }
 
         // Check extension
-        let ext = path
+        let ext = file
             .extension()
             .and_then(|e| e.to_str())
             .map(str::to_lowercase)
@@ -98,82 +326,222 @@ pub fn cmd_build(
         }
 
         // Skip common non-content directories
-        let path_str = path.to_string_lossy();
-        if path_str.contains("node_modules")
-            || path_str.contains(".git/")
-            || path_str.contains("target/")
-            || path_str.contains("vendor/")
-            || path_str.contains("venv/")
-            || path_str.contains("__pycache__")
-        {
+        let relative = file.strip_prefix(path).unwrap_or(file);
+        if in_skipped_dir(relative, skip_dirs) {
             continue;
         }
 
-        // Index the file
-        if let Ok((mut entry, mut metrics)) = index_file(path) {
-            let physical_path = canonicalize_existing_path(path);
-            let rel_path = build_indexed_doc_key(&physical_path, &source_root);
-            entry.path = physical_path.to_string_lossy().to_string();
-            metrics.path.clone_from(&rel_path);
+        candidates.push(file.to_path_buf());
+    }
+    candidates
+}
 
-            // Update reverse index with heading keywords
-            for keyword in &entry.keywords {
-                let stemmed = stem_word(&keyword.to_lowercase());
-                reverse_index
-                    .keywords
-                    .entry(stemmed)
-                    .or_default()
-                    .push(ReverseEntry {
-                        file: rel_path.clone(),
-                        line: None,
-                        heading: None,
-                        level: None,
-                    });
+fn build_scope(plan: &BuildPlan) -> BuildScope {
+    let mut extensions: Vec<String> = plan.extensions.iter().cloned().collect();
+    extensions.sort();
+    BuildScope {
+        path: plan.path.to_string_lossy().to_string(),
+        extensions,
+        exclude: plan.exclude.to_vec(),
+        roots: plan.roots.map(|roots| {
+            roots
+                .iter()
+                .map(|root| root.to_string_lossy().to_string())
+                .collect()
+        }),
+        skip_dirs: plan.skip_dirs.clone(),
+    }
+}
+
+/// Compare the index with the files on disk.
+///
+/// Removed and modified files are found from the index alone (size is the
+/// only stored file attribute). Added files need the recorded build scope:
+/// the build path is walked again with the same settings and any file
+/// without an index entry is reported.
+pub(crate) fn detect_index_drift(forward_index: &ForwardIndex) -> IndexDriftResult {
+    let source_root = forward_index_source_root(forward_index).unwrap_or_default();
+
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    for (key, entry) in &forward_index.files {
+        match fs::metadata(source_root.join(key)) {
+            Ok(meta) if meta.len() != entry.size_bytes => modified.push(DriftedFile {
+                file: key.clone(),
+                indexed_size_bytes: entry.size_bytes,
+                current_size_bytes: meta.len(),
+            }),
+            Ok(_) => {}
+            Err(_) => removed.push(key.clone()),
+        }
+    }
+    removed.sort();
+    modified.sort_by(|a, b| a.file.cmp(&b.file));
+
+    let mut added = Vec::new();
+    if let Some(scope) = &forward_index.build_scope {
+        let roots: Option<Vec<PathBuf>> = scope
+            .roots
+            .as_ref()
+            .map(|roots| roots.iter().map(|root| source_root.join(root)).collect());
+        let exclude: Vec<String> = scope
+            .exclude
+            .iter()
+            .map(|pattern| source_root.join(pattern).to_string_lossy().to_string())
+            .collect();
+        let candidates = walk_build_path(
+            &source_root.join(&scope.path),
+            &scope.extensions.iter().cloned().collect(),
+            &exclude,
+            roots.as_deref(),
+            &scope.skip_dirs,
+        );
+        for path in candidates {
+            let key = build_indexed_doc_key(&canonicalize_existing_path(&path), &source_root);
+            if !forward_index.files.contains_key(&key) {
+                added.push(key);
             }
+        }
+        added.sort();
+    }
+
+    IndexDriftResult {
+        indexed_at: forward_index.indexed_at.clone(),
+        added,
+        removed,
+        modified,
+        added_checked: forward_index.build_scope.is_some(),
+    }
+}
+
+/// Index `candidates` on `pool`, keyed by the walked path; unreadable files
+/// are left out.
+fn index_build_candidates(
+    pool: &rayon::ThreadPool,
+    plan: &BuildPlan,
+    candidates: &[PathBuf],
+) -> Vec<(PathBuf, IndexedDoc)> {
+    pool.install(|| {
+        candidates
+            .par_iter()
+            .filter_map(|path| {
+                let (mut entry, mut metrics, features) =
+                    index_file(path, &plan.index_options).ok()?;
+                let physical_path = canonicalize_existing_path(path);
+                let rel_path = build_indexed_doc_key(&physical_path, &plan.source_root);
+                entry.path = physical_path.to_string_lossy().to_string();
+                metrics.path.clone_from(&rel_path);
+                Some((path.clone(), (rel_path, entry, metrics, features)))
+            })
+            .collect()
+    })
+}
+
+/// Derive the reverse index, BM25 statistics, and relations from `indexed`
+/// and write every index file to the output directory.
+fn write_index_files(
+    plan: &BuildPlan,
+    mut indexed: Vec<IndexedDoc>,
+) -> Result<IndexSummary, Box<dyn std::error::Error>> {
+    let stemmer = plan.index_options.stemmer;
+    let output = plan.output;
+    let indexed_at = index_timestamp();
+
+    let mut forward_index = ForwardIndex {
+        files: HashMap::new(),
+        indexed_at: indexed_at.clone(),
+        version: CURRENT_INDEX_VERSION,
+        source_root: plan.source_root.to_string_lossy().to_string(),
+        avg_doc_length: 0.0,
+        idf_map: HashMap::new(),
+        stemmer,
+        positions: plan.index_options.positions,
+        stop_words: plan.index_options.stop_words.clone(),
+        minhash_perms: plan.index_options.minhash_perms,
+        simhash_weighting: plan.index_options.simhash_weighting,
+        build_scope: Some(build_scope(plan)),
+    };
+
+    let mut reverse_index = ReverseIndex {
+        keywords: HashMap::new(),
+    };
+    let mut document_metrics_index = DocumentMetricsIndex {
+        indexed_at: indexed_at.clone(),
+        version: 1,
+        files: HashMap::new(),
+    };
+
+    // Merge in path order so the reverse index is independent of thread count
+    indexed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut file_count = 0;
+    let mut total_headings = 0;
+    let mut total_links = 0;
+    let mut simhash_features: Vec<(String, SimhashFeatures)> = Vec::new();
+
+    for (rel_path, entry, metrics, features) in indexed {
+        // Update reverse index with heading keywords
+        for keyword in &entry.keywords {
+            let stemmed = stem_word_with(keyword, stemmer);
+            reverse_index
+                .keywords
+                .entry(stemmed)
+                .or_default()
+                .push(ReverseEntry {
+                    file: rel_path.clone(),
+                    line: None,
+                    heading: None,
+                    level: None,
+                });
+        }
+
+        // Update reverse index with body keywords
+        for keyword in &entry.body_keywords {
+            let stemmed = stem_word_with(keyword, stemmer);
+            reverse_index
+                .keywords
+                .entry(stemmed)
+                .or_default()
+                .push(ReverseEntry {
+                    file: rel_path.clone(),
+                    line: None,
+                    heading: None,
+                    level: None,
+                });
+        }
 
-            // Update reverse index with body keywords
-            for keyword in &entry.body_keywords {
-                let stemmed = stem_word(&keyword.to_lowercase());
+        for heading in &entry.headings {
+            let words = extract_keywords(&heading.text, &plan.index_options.stop_words);
+            for word in words {
+                let stemmed = stem_word_with(&word, stemmer);
                 reverse_index
                     .keywords
                     .entry(stemmed)
                     .or_default()
                     .push(ReverseEntry {
                         file: rel_path.clone(),
-                        line: None,
-                        heading: None,
-                        level: None,
+                        line: Some(heading.line),
+                        heading: Some(heading.text.clone()),
+                        level: Some(heading.level),
                     });
             }
+        }
 
-            for heading in &entry.headings {
-                let words = extract_keywords(&heading.text);
-                for word in words {
-                    let stemmed = stem_word(&word.to_lowercase());
-                    reverse_index
-                        .keywords
-                        .entry(stemmed)
-                        .or_default()
-                        .push(ReverseEntry {
-                            file: rel_path.clone(),
-                            line: Some(heading.line),
-                            heading: Some(heading.text.clone()),
-                            level: Some(heading.level),
-                        });
-                }
-            }
-
-            total_headings += entry.headings.len();
-            total_links += entry.links.len();
-            file_count += 1;
+        total_headings += entry.headings.len();
+        total_links += entry.links.len();
+        file_count += 1;
 
-            document_metrics_index
-                .files
-                .insert(rel_path.clone(), metrics);
-            forward_index.files.insert(rel_path, entry);
+        document_metrics_index
+            .files
+            .insert(rel_path.clone(), metrics);
+        if plan.index_options.simhash_weighting == SimhashWeighting::Idf {
+            simhash_features.push((rel_path.clone(), features));
         }
+        forward_index.files.insert(rel_path, entry);
     }
 
+    resolve_wiki_links(&mut forward_index);
+
     // Compute BM25 statistics (IDF and average document length)
     let total_docs = forward_index.files.len() as f64;
     let mut doc_frequencies: HashMap<String, usize> = HashMap::new();
@@ -205,16 +573,38 @@ pub fn cmd_build(
     };
     forward_index.idf_map = idf_map;
 
+    // With IDF known, re-weight file SimHash so shared boilerplate counts less
+    for (rel_path, features) in &simhash_features {
+        if let Some(entry) = forward_index.files.get_mut(rel_path) {
+            entry.simhash = idf_weighted_simhash(features, &forward_index.idf_map);
+        }
+    }
+
     // Create output directory
     fs::create_dir_all(output)?;
 
     // Write indexes
     let forward_path = output.join("forward_index.json");
+    let forward_bin_path = output.join("forward_index.bin");
     let reverse_path = output.join("reverse_index.json");
     let stats_path = output.join("stats.json");
     let metrics_path = output.join("document_metrics.json");
 
-    fs::write(&forward_path, serde_json::to_string_pretty(&forward_index)?)?;
+    // Write the requested forward index encoding and drop the other one so a
+    // leftover from an earlier build is never loaded
+    let stale_forward_path = match plan.format {
+        IndexFormat::Json => {
+            fs::write(&forward_path, serde_json::to_string_pretty(&forward_index)?)?;
+            &forward_bin_path
+        }
+        IndexFormat::Bin => {
+            fs::write(&forward_bin_path, encode_forward_index_bin(&forward_index)?)?;
+            &forward_path
+        }
+    };
+    if stale_forward_path.exists() {
+        fs::remove_file(stale_forward_path)?;
+    }
     fs::write(&reverse_path, serde_json::to_string_pretty(&reverse_index)?)?;
     fs::write(
         &metrics_path,
@@ -226,7 +616,7 @@ pub fn cmd_build(
         total_keywords: reverse_index.keywords.len(),
         total_headings,
         total_links,
-        indexed_at: chrono_now(),
+        indexed_at,
     };
     fs::write(&stats_path, serde_json::to_string_pretty(&stats)?)?;
 
@@ -239,121 +629,743 @@ pub fn cmd_build(
         serde_json::to_string_pretty(&relation_index)?,
     )?;
 
-    // Track git renames if requested
-    let renames_count = if track_renames {
-        if !quiet && !json {
-            println!("  Extracting git rename history...");
-        }
-        let rename_history = extract_git_renames(path);
-        let count = rename_history.renames.len();
-        let rename_path = output.join("rename_history.json");
-        fs::write(&rename_path, serde_json::to_string_pretty(&rename_history)?)?;
-        if !quiet && !json {
-            println!("  Tracked {count} file renames");
-        }
-        Some(count)
-    } else {
-        None
-    };
+    Ok(IndexSummary {
+        file_count,
+        total_headings,
+        total_links,
+        unique_keywords: reverse_index.keywords.len(),
+        relations_count,
+    })
+}
 
-    let elapsed = start.elapsed();
+#[cfg(feature = "watch")]
+enum WatchEvent {
+    Changed(Vec<PathBuf>),
+    Stop,
+}
 
-    if json {
-        let result = BuildResult {
-            index_path: output.to_string_lossy().to_string(),
-            files_indexed: file_count,
-            total_headings,
-            total_links,
-            unique_keywords: reverse_index.keywords.len(),
-            duration_ms: elapsed.as_millis(),
-            renames_tracked: renames_count,
-            total_relations: Some(relations_count),
-        };
-        println!("{}", serde_json::to_string_pretty(&result)?);
-    } else if !quiet {
-        println!();
-        println!("{}", "Index Statistics".green().bold());
-        println!("  Files indexed:    {}", file_count.to_string().cyan());
-        println!(
-            "  Unique keywords:  {}",
-            reverse_index.keywords.len().to_string().cyan()
-        );
-        println!("  Total headings:   {}", total_headings.to_string().cyan());
-        println!("  Total links:      {}", total_links.to_string().cyan());
-        println!("  Relations:        {}", relations_count.to_string().cyan());
-        println!("  Time elapsed:     {elapsed:.2?}");
-        println!();
+/// Rebuild the index whenever files under the build path change, re-indexing
+/// only changed, created, and deleted files. Returns on Ctrl-C.
+#[cfg(feature = "watch")]
+fn watch_and_rebuild(
+    plan: &BuildPlan,
+    pool: &rayon::ThreadPool,
+    mut docs: HashMap<PathBuf, IndexedDoc>,
+    debounce: Duration,
+    quiet: bool,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = mpsc::channel();
+    let fs_tx = tx.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if !event.kind.is_access() {
+                let _ = fs_tx.send(WatchEvent::Changed(event.paths));
+            }
+        }
+    })?;
+    let watch_root = canonicalize_existing_path(plan.path);
+    watcher.watch(&watch_root, RecursiveMode::Recursive)?;
+    ctrlc::set_handler(move || {
+        let _ = tx.send(WatchEvent::Stop);
+    })?;
+
+    // Writing the index must not trigger another rebuild
+    let output_dir = canonicalize_existing_path(plan.output);
+    if !quiet && !json {
         println!(
-            "{} {}",
-            "Indexes written to".green(),
-            output.display().to_string().cyan()
+            "{} {} (Ctrl-C to stop)",
+            "Watching".cyan().bold(),
+            plan.path.display()
         );
     }
 
+    'watch: loop {
+        // Block for the first change, then collect more until things go quiet
+        let mut changed: HashSet<PathBuf> = HashSet::new();
+        match rx.recv() {
+            Ok(WatchEvent::Changed(paths)) => changed.extend(paths),
+            Ok(WatchEvent::Stop) | Err(_) => break,
+        }
+        loop {
+            match rx.recv_timeout(debounce) {
+                Ok(WatchEvent::Changed(paths)) => changed.extend(paths),
+                Ok(WatchEvent::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => break 'watch,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+            }
+        }
+        changed.retain(|path| !path.starts_with(&output_dir));
+        if changed.is_empty() {
+            continue;
+        }
+
+        let start = Instant::now();
+        let candidates = collect_build_candidates(plan);
+        let current: HashSet<&PathBuf> = candidates.iter().collect();
+        let before = docs.len();
+        docs.retain(|path, _| current.contains(path));
+        let removed = before - docs.len();
+
+        let stale: Vec<PathBuf> = candidates
+            .iter()
+            .filter(|path| {
+                !docs.contains_key(*path) || changed.contains(&canonicalize_existing_path(path))
+            })
+            .cloned()
+            .collect();
+        if stale.is_empty() && removed == 0 {
+            continue;
+        }
+        let added = stale
+            .iter()
+            .filter(|path| !docs.contains_key(*path))
+            .count();
+        let updated = stale.len() - added;
+        for path in &stale {
+            docs.remove(path);
+        }
+        docs.extend(index_build_candidates(pool, plan, &stale));
+
+        let summary = write_index_files(plan, docs.values().cloned().collect())?;
+        let elapsed = start.elapsed();
+        if json {
+            let result = BuildResult {
+                index_path: plan.output.to_string_lossy().to_string(),
+                files_indexed: summary.file_count,
+                total_headings: summary.total_headings,
+                total_links: summary.total_links,
+                unique_keywords: summary.unique_keywords,
+                duration_ms: elapsed.as_millis(),
+                renames_tracked: None,
+                total_relations: Some(summary.relations_count),
+            };
+            println!("{}", serde_json::to_string(&result)?);
+        } else if !quiet {
+            println!(
+                "{} {} files (+{added} ~{updated} -{removed}) in {elapsed:.2?}",
+                "Rebuilt".green(),
+                summary.file_count
+            );
+        }
+    }
+
+    if !quiet && !json {
+        println!("Stopped watching");
+    }
     Ok(())
 }
 
-pub fn index_file(path: &Path) -> Result<(FileEntry, DocumentMetrics), Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(path)?;
-    let metadata = fs::metadata(path)?;
-
-    let lines: Vec<&str> = content.lines().collect();
-    let line_count = lines.len();
+#[cfg(not(feature = "watch"))]
+fn watch_and_rebuild(
+    _plan: &BuildPlan,
+    _pool: &rayon::ThreadPool,
+    _docs: HashMap<PathBuf, IndexedDoc>,
+    _debounce: Duration,
+    _quiet: bool,
+    _json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Unreachable in practice: cmd_build rejects --watch without the feature
+    Err("--watch requires yore built with the `watch` feature".into())
+}
 
-    // Extract headings (markdown)
-    let heading_re = Regex::new(r"^(#{1,6})\s+(.+)$")?;
-    let mut headings = Vec::new();
+/// Flag AsciiDoc listing (`----`) and literal (`....`) block lines,
+/// delimiters included.
+fn asciidoc_block_mask(lines: &[&str]) -> Vec<bool> {
+    let mut open: Option<&str> = None;
+    lines
+        .iter()
+        .map(|line| {
+            let trimmed = line.trim_end();
+            let is_delimiter = trimmed.len() >= 4
+                && (trimmed.chars().all(|c| c == '-') || trimmed.chars().all(|c| c == '.'));
+            match open {
+                Some(delimiter) if trimmed == delimiter => open = None,
+                None if is_delimiter => open = Some(trimmed),
+                _ => {}
+            }
+            is_delimiter || open.is_some()
+        })
+        .collect()
+}
 
+/// Extract AsciiDoc `link:target[text]` and `xref:target[text]` macros and
+/// `<<id>>` / `<<id,text>>` cross references. A link without text uses its
+/// target as the text.
+pub fn extract_asciidoc_links(lines: &[&str]) -> Vec<Link> {
+    let macro_re = Regex::new(r"\b(link|xref):([^\s\[]+)\[([^\]]*)\]").unwrap();
+    let xref_re = Regex::new(r"<<([^<>,\s]+)(?:,([^>]*))?>>").unwrap();
+    let mut links = Vec::new();
     for (i, line) in lines.iter().enumerate() {
-        if let Some(caps) = heading_re.captures(line) {
-            headings.push(Heading {
+        // (offset, target, text); empty text falls back to the raw target
+        let macros = macro_re.captures_iter(line).map(|caps| {
+            let raw = &caps[2];
+            let target = if &caps[1] == "xref" {
+                asciidoc_xref_target(raw)
+            } else {
+                raw.to_string()
+            };
+            let text = caps[3].trim();
+            let text = if text.is_empty() { raw } else { text };
+            (caps.get(0).unwrap().start(), target, text.to_string())
+        });
+        let xrefs = xref_re.captures_iter(line).map(|caps| {
+            let raw = &caps[1];
+            let text = caps.get(2).map_or("", |text| text.as_str().trim());
+            let text = if text.is_empty() { raw } else { text };
+            (
+                caps.get(0).unwrap().start(),
+                asciidoc_xref_target(raw),
+                text.to_string(),
+            )
+        });
+        let mut found: Vec<_> = macros.chain(xrefs).collect();
+        found.sort_by_key(|(start, _, _)| *start);
+        for (_, target, text) in found {
+            links.push(Link {
                 line: i + 1,
-                level: caps.get(1).map_or(1, |m| m.as_str().len()),
-                text: caps
-                    .get(2)
-                    .map(|m| m.as_str().to_string())
-                    .unwrap_or_default(),
+                text,
+                target,
+                wiki: None,
             });
         }
     }
+    links
+}
+
+/// An xref target with no file extension and no `#` names a section in the
+/// same document, so it becomes an anchor-only `#id` link.
+fn asciidoc_xref_target(target: &str) -> String {
+    if target.contains('#') || Path::new(target).extension().is_some() {
+        target.to_string()
+    } else {
+        format!("#{target}")
+    }
+}
+
+/// Adornment character and width when `line` is an RST underline/overline.
+fn rst_adornment(line: &str) -> Option<(char, usize)> {
+    let line = line.trim_end();
+    let first = line.chars().next()?;
+    if !first.is_ascii_punctuation() || !line.chars().all(|c| c == first) {
+        return None;
+    }
+    Some((first, line.chars().count()))
+}
+
+/// Extract reStructuredText section titles: a title line underlined (and
+/// optionally overlined) by punctuation at least as wide as the title.
+/// Levels follow the order in which adornment styles first appear, as in
+/// docutils. Headings report the line of the title text.
+pub fn extract_rst_headings(lines: &[&str], is_code: &[bool], start: usize) -> Vec<Heading> {
+    let mut styles: Vec<(char, bool)> = Vec::new();
+    let mut headings = Vec::new();
+    let is_blank = |i: usize| lines[i].trim().is_empty();
+
+    let mut i = start;
+    while i + 1 < lines.len() {
+        let title = lines[i].trim();
+        let underline = rst_adornment(lines[i + 1]);
+        let Some((adornment, width)) = underline else {
+            i += 1;
+            continue;
+        };
+        if is_code[i]
+            || is_code[i + 1]
+            || title.is_empty()
+            || rst_adornment(title).is_some()
+            || width < title.chars().count()
+        {
+            i += 1;
+            continue;
+        }
+
+        let overlined = i > start && rst_adornment(lines[i - 1]) == underline;
+        let separated = if overlined {
+            i - 1 == start || is_blank(i - 2)
+        } else {
+            !lines[i].starts_with(char::is_whitespace) && (i == start || is_blank(i - 1))
+        };
+        if !separated {
+            i += 1;
+            continue;
+        }
+
+        let style = (adornment, overlined);
+        let level = match styles.iter().position(|s| *s == style) {
+            Some(pos) => pos + 1,
+            None => {
+                styles.push(style);
+                styles.len()
+            }
+        };
+        headings.push(Heading {
+            line: i + 1,
+            level: level.min(6),
+            text: title.to_string(),
+            anchor: None,
+        });
+        i += 2;
+    }
+    headings
+}
+
+/// Extract `#`-style (markdown) or `=`-style (AsciiDoc) headings, skipping
+/// code lines and the first `start` lines.
+fn extract_prefixed_headings(
+    lines: &[&str],
+    is_code: &[bool],
+    start: usize,
+    marker: char,
+) -> Vec<Heading> {
+    let marker = regex::escape(&marker.to_string());
+    let heading_re = Regex::new(&format!(r"^({marker}{{1,6}})\s+(.+)$")).unwrap();
+    let custom_id_re = Regex::new(r"\s*\{#([^\s}]+)[^}]*\}\s*$").unwrap();
+    lines
+        .iter()
+        .enumerate()
+        .skip(start)
+        .filter(|&(i, _)| !is_code[i])
+        .filter_map(|(i, line)| {
+            let caps = heading_re.captures(line)?;
+            // `## Title {#custom-id}` sets the anchor and is not heading text
+            let (text, anchor) = match custom_id_re.captures(&caps[2]) {
+                Some(id) if id.get(0).is_some_and(|m| m.start() > 0) => (
+                    caps[2][..id.get(0).map_or(0, |m| m.start())].to_string(),
+                    Some(id[1].to_string()),
+                ),
+                _ => (caps[2].to_string(), None),
+            };
+            Some(Heading {
+                line: i + 1,
+                level: caps[1].len(),
+                text,
+                anchor,
+            })
+        })
+        .collect()
+}
+
+/// Explicit anchor ids outside headings: `<a name="…">` / `<a id="…">`
+/// tags and `{#id}` attribute blocks on non-code lines, outside inline code
+/// spans. Ids already set as a heading anchor are left out.
+pub fn extract_explicit_anchors(
+    lines: &[&str],
+    is_code: &[bool],
+    headings: &[Heading],
+) -> Vec<String> {
+    let tag_re = Regex::new(r"(?i)<a\b([^>]*)>").unwrap();
+    let custom_id_re = Regex::new(r"(?:^|[^$])\{:?\s*#([^\s}]+)[^}]*\}").unwrap();
+    let code_span_re = Regex::new(r"`[^`]*`").unwrap();
+    let mut anchors: Vec<String> = Vec::new();
+    let mut push = |id: String| {
+        let taken = headings.iter().any(|h| h.anchor.as_ref() == Some(&id));
+        if !id.is_empty() && !taken && !anchors.contains(&id) {
+            anchors.push(id);
+        }
+    };
+    for (line, _) in lines.iter().zip(is_code).filter(|(_, &code)| !code) {
+        // Inline code spans show anchor syntax rather than define anchors
+        let line = code_span_re.replace_all(line, "");
+        for caps in tag_re.captures_iter(&line) {
+            for attribute in ["id", "name"] {
+                if let Some(id) = html_attribute(&caps[1], attribute) {
+                    push(id);
+                }
+            }
+        }
+        for caps in custom_id_re.captures_iter(&line) {
+            push(caps[1].to_string());
+        }
+    }
+    anchors
+}
+
+/// Byte offset at which each line of `text` starts.
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// 1-based line holding byte `offset`, given `line_starts` output.
+fn line_at(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|&start| start <= offset)
+}
+
+/// Decode the handful of character entities common in generated HTML.
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Visible text of an HTML document. Tags, comments, and `<script>`/`<style>`
+/// bodies become spaces, keeping their line breaks so line numbers still
+/// match the source.
+pub fn html_text(html: &str) -> String {
+    let markup_re =
+        Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->|<[^>]*>")
+            .unwrap();
+    let stripped = markup_re.replace_all(html, |caps: &Captures| {
+        let newlines = caps[0].matches('\n').count();
+        if newlines == 0 {
+            " ".to_string()
+        } else {
+            "\n".repeat(newlines)
+        }
+    });
+    decode_html_entities(&stripped)
+}
+
+/// Value of attribute `name` in the attribute text of an HTML tag.
+fn html_attribute(attributes: &str, name: &str) -> Option<String> {
+    let attr_re = Regex::new(&format!(
+        r#"(?i)(?:^|\s){name}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#
+    ))
+    .unwrap();
+    let caps = attr_re.captures(attributes)?;
+    let value = caps.get(1).or(caps.get(2)).or(caps.get(3))?.as_str();
+    Some(decode_html_entities(value))
+}
+
+/// Collapse the visible text of an HTML fragment to one line.
+fn html_inline_text(fragment: &str) -> String {
+    html_text(fragment)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Flag the lines inside HTML `<pre>` blocks, tags included.
+fn html_pre_mask(html: &str) -> Vec<bool> {
+    let pre_re = Regex::new(r"(?is)<pre\b.*?</pre\s*>").unwrap();
+    let starts = line_starts(html);
+    let mut mask = vec![false; html.lines().count()];
+    for block in pre_re.find_iter(html) {
+        let first = line_at(&starts, block.start()) - 1;
+        let last = line_at(&starts, block.end() - 1);
+        for code in mask.iter_mut().take(last).skip(first) {
+            *code = true;
+        }
+    }
+    mask
+}
+
+/// Extract `<h1>`–`<h6>` headings; an `id` attribute becomes the anchor.
+pub fn extract_html_headings(html: &str) -> Vec<Heading> {
+    let heading_re = Regex::new(r"(?is)<h([1-6])\b([^>]*)>(.*?)</h[1-6]\s*>").unwrap();
+    let starts = line_starts(html);
+    heading_re
+        .captures_iter(html)
+        .map(|caps| Heading {
+            line: line_at(&starts, caps.get(0).map_or(0, |m| m.start())),
+            level: caps[1].parse().unwrap_or(1),
+            text: html_inline_text(&caps[3]),
+            anchor: html_attribute(&caps[2], "id"),
+        })
+        .collect()
+}
 
-    // Extract links
-    let link_re = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)")?;
+/// Extract `<a href>` links to local targets. Links with a URL scheme
+/// (`https:`, `mailto:`, …) or protocol-relative `//host` targets are skipped.
+pub fn extract_html_links(html: &str) -> Vec<Link> {
+    let anchor_re = Regex::new(r"(?is)<a\b([^>]*)>(.*?)</a\s*>").unwrap();
+    let scheme_re = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*:").unwrap();
+    let starts = line_starts(html);
     let mut links = Vec::new();
+    for caps in anchor_re.captures_iter(html) {
+        let Some(target) = html_attribute(&caps[1], "href") else {
+            continue;
+        };
+        let target = target.trim().to_string();
+        if target.is_empty()
+            || target == "#"
+            || target.starts_with("//")
+            || scheme_re.is_match(&target)
+        {
+            continue;
+        }
+        let text = html_inline_text(&caps[2]);
+        links.push(Link {
+            line: line_at(&starts, caps.get(0).map_or(0, |m| m.start())),
+            text: if text.is_empty() {
+                target.clone()
+            } else {
+                text
+            },
+            target,
+            wiki: None,
+        });
+    }
+    links
+}
+
+/// Extract markdown links from document lines.
+///
+/// Handles inline `[text](target)` links plus reference-style `[text][ref]`,
+/// collapsed `[ref][]` and shorthand `[ref]` links, which resolve through
+/// `[ref]: target` definitions anywhere in the document. Reference links are
+/// reported at the line of the usage, not the definition.
+///
+/// Wiki links (`[[Page]]`, `[[Page#Section]]`, `[[Page|alias]]`) get a
+/// provisional `Page.md#section` target; `resolve_wiki_links` rewrites it
+/// once every indexed path is known.
+pub fn extract_links(lines: &[&str]) -> Vec<Link> {
+    let wiki_re = Regex::new(r"\[\[([^\]|#]*)(?:#([^\]|]*))?(?:\|([^\]]*))?\]\]").unwrap();
+    let inline_re = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
+    let definition_re = Regex::new(r"^\s{0,3}\[([^\]]+)\]:\s*<?([^\s>]+)>?").unwrap();
+    let reference_re = Regex::new(r"\[([^\]]+)\](?:\[([^\]]*)\])?").unwrap();
+
+    // Reference labels are case-insensitive with collapsed whitespace
+    let normalize_label = |label: &str| {
+        label
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
 
+    let mut definitions: HashMap<String, String> = HashMap::new();
+    let mut definition_lines: HashSet<usize> = HashSet::new();
     for (i, line) in lines.iter().enumerate() {
-        for caps in link_re.captures_iter(line) {
-            links.push(Link {
-                line: i + 1,
-                text: caps
-                    .get(1)
-                    .map(|m| m.as_str().to_string())
-                    .unwrap_or_default(),
-                target: caps
-                    .get(2)
-                    .map(|m| m.as_str().to_string())
-                    .unwrap_or_default(),
-            });
+        if let Some(caps) = definition_re.captures(line) {
+            definition_lines.insert(i);
+            // First definition wins, as in CommonMark
+            definitions
+                .entry(normalize_label(&caps[1]))
+                .or_insert_with(|| caps[2].to_string());
+        }
+    }
+
+    let mut links = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut found: Vec<(usize, Link)> = Vec::new();
+        let mut inline_spans: Vec<(usize, usize)> = Vec::new();
+
+        for caps in wiki_re.captures_iter(line) {
+            let whole = caps.get(0).unwrap();
+            let page = caps[1].trim();
+            let section = caps.get(2).map(|m| m.as_str().trim());
+            if page.is_empty() && section.is_none_or(str::is_empty) {
+                continue;
+            }
+            inline_spans.push((whole.start(), whole.end()));
+            let alias = caps.get(3).map(|m| m.as_str().trim());
+            let text = alias
+                .filter(|a| !a.is_empty())
+                .unwrap_or(if page.is_empty() {
+                    section.unwrap_or_default()
+                } else {
+                    page
+                });
+            found.push((
+                whole.start(),
+                Link {
+                    line: i + 1,
+                    text: text.to_string(),
+                    target: wiki_link_target(page, section),
+                    wiki: Some(page.to_string()),
+                },
+            ));
         }
+
+        for caps in inline_re.captures_iter(line) {
+            let whole = caps.get(0).unwrap();
+            if inline_spans
+                .iter()
+                .any(|&(start, end)| whole.start() < end && start < whole.end())
+            {
+                continue;
+            }
+            inline_spans.push((whole.start(), whole.end()));
+            found.push((
+                whole.start(),
+                Link {
+                    line: i + 1,
+                    text: caps[1].to_string(),
+                    target: caps[2].to_string(),
+                    wiki: None,
+                },
+            ));
+        }
+
+        if !definitions.is_empty() && !definition_lines.contains(&i) {
+            for caps in reference_re.captures_iter(line) {
+                let whole = caps.get(0).unwrap();
+                if inline_spans
+                    .iter()
+                    .any(|&(start, end)| whole.start() < end && start < whole.end())
+                {
+                    continue;
+                }
+                // `[text](...)` is inline and `[text]:` is a definition, not a usage
+                if caps.get(2).is_none()
+                    && matches!(line[whole.end()..].chars().next(), Some('(' | ':'))
+                {
+                    continue;
+                }
+                let text = &caps[1];
+                let label = match caps.get(2) {
+                    Some(r) if !r.as_str().trim().is_empty() => r.as_str(),
+                    _ => text,
+                };
+                if let Some(target) = definitions.get(&normalize_label(label)) {
+                    found.push((
+                        whole.start(),
+                        Link {
+                            line: i + 1,
+                            text: text.to_string(),
+                            target: target.clone(),
+                            wiki: None,
+                        },
+                    ));
+                }
+            }
+        }
+
+        found.sort_by_key(|(column, _)| *column);
+        links.extend(found.into_iter().map(|(_, link)| link));
     }
 
+    links
+}
+
+/// Provisional target for a wiki link before page resolution.
+///
+/// Unresolved pages keep this `Page Name.md#section` form, so missing pages
+/// surface as ordinary `doc_missing` links.
+fn wiki_link_target(page: &str, section: Option<&str>) -> String {
+    let mut target = if page.is_empty() || page.to_lowercase().ends_with(".md") {
+        page.to_string()
+    } else {
+        format!("{page}.md")
+    };
+    if let Some(section) = section.filter(|s| !s.is_empty()) {
+        target.push('#');
+        target.push_str(&github_slug(section, &mut HashMap::new()));
+    }
+    target
+}
+
+/// Resolve `[[Page]]` links to relative paths of uniquely matching files.
+///
+/// Pages are matched by basename (case-insensitive, spaces as hyphens).
+/// Missing and ambiguous pages keep their provisional target; `check-links`
+/// reports the ambiguous ones.
+pub fn resolve_wiki_links(forward_index: &mut ForwardIndex) {
+    let page_map = build_wiki_page_map(forward_index.files.keys());
+    let empty = HashSet::new();
+
+    for (source, entry) in &mut forward_index.files {
+        for link in &mut entry.links {
+            let Some(page) = link.wiki.as_deref() else {
+                continue;
+            };
+            if page.is_empty() {
+                continue;
+            }
+            let Some([resolved]) = page_map.get(&wiki_page_key(page)).map(Vec::as_slice) else {
+                continue;
+            };
+            let relative = if resolved == source {
+                String::new()
+            } else {
+                compute_relative_path(source, resolved, &empty).unwrap_or_else(|| resolved.clone())
+            };
+            let section = link
+                .target
+                .split_once('#')
+                .map(|(_, anchor)| anchor.to_string());
+            link.target = relative;
+            if let Some(anchor) = section {
+                link.target.push('#');
+                link.target.push_str(&anchor);
+            }
+        }
+    }
+}
+
+/// Index one file. The returned SimHash features are what `build`
+/// re-weights by IDF once every file is indexed (empty under uniform
+/// weighting).
+pub fn index_file(
+    path: &Path,
+    options: &IndexOptions,
+) -> Result<(FileEntry, DocumentMetrics, SimhashFeatures), Box<dyn std::error::Error>> {
+    let stemmer = options.stemmer;
+    let stop_words = &options.stop_words;
+    let source = fs::read_to_string(path)?;
+    let metadata = fs::metadata(path)?;
+    let format = DocFormat::from_path(path);
+
+    // HTML is indexed by its visible text; headings and links come from markup
+    let content = match format {
+        DocFormat::Html => Cow::Owned(html_text(&source)),
+        _ => Cow::Borrowed(source.as_str()),
+    };
+
+    let lines: Vec<&str> = content.lines().collect();
+    let line_count = lines.len();
+
+    // Frontmatter is metadata, not prose: keep it out of keywords and SimHash
+    let (frontmatter, frontmatter_end) = parse_frontmatter(&lines);
+
+    // Code is not prose: fenced and indented lines (`<pre>` blocks in HTML)
+    // are kept out of headings, keywords, term statistics, and SimHash
+    let mut is_code = match format {
+        DocFormat::Html => html_pre_mask(&source),
+        _ => code_line_mask(lines.iter().copied()),
+    };
+    if format == DocFormat::AsciiDoc {
+        for (code, in_block) in is_code.iter_mut().zip(asciidoc_block_mask(&lines)) {
+            *code |= in_block;
+        }
+    }
+    let prose_lines: Vec<&str> = lines
+        .iter()
+        .zip(&is_code)
+        .skip(frontmatter_end)
+        .filter(|(_, &code)| !code)
+        .map(|(line, _)| *line)
+        .collect();
+
+    // Extract headings (`#` in markdown, `=` in AsciiDoc, underlines in RST)
+    let headings = match format {
+        DocFormat::Markdown => extract_prefixed_headings(&lines, &is_code, frontmatter_end, '#'),
+        DocFormat::AsciiDoc => extract_prefixed_headings(&lines, &is_code, frontmatter_end, '='),
+        DocFormat::Rst => extract_rst_headings(&lines, &is_code, frontmatter_end),
+        DocFormat::Html => extract_html_headings(&source)
+            .into_iter()
+            .filter(|h| !is_code[h.line - 1])
+            .collect(),
+    };
+
+    let anchors = extract_explicit_anchors(&lines, &is_code, &headings);
+
+    // Extract links (inline and reference-style, AsciiDoc macros, or anchors)
+    let links = match format {
+        DocFormat::Markdown | DocFormat::Rst => extract_links(&lines),
+        DocFormat::AsciiDoc => extract_asciidoc_links(&lines),
+        DocFormat::Html => extract_html_links(&source),
+    };
+
     // Extract keywords from headings
-    let mut keywords: HashSet<String> = HashSet::new();
+    let mut keywords: BTreeSet<String> = BTreeSet::new();
     for heading in &headings {
-        for kw in extract_keywords(&heading.text) {
-            keywords.insert(stem_word(&kw));
+        for kw in extract_keywords(&heading.text, stop_words) {
+            keywords.insert(stem_word_with(&kw, stemmer));
         }
     }
 
     // NEW: Extract keywords from full body text
-    let mut body_keywords: HashSet<String> = HashSet::new();
-    for line in &lines {
-        // Skip code blocks
-        if line.starts_with("```") || line.starts_with("    ") {
-            continue;
-        }
-        for kw in extract_keywords(line) {
-            body_keywords.insert(stem_word(&kw));
+    let mut body_keywords: BTreeSet<String> = BTreeSet::new();
+    for line in &prose_lines {
+        for kw in extract_keywords(line, stop_words) {
+            body_keywords.insert(stem_word_with(&kw, stemmer));
         }
     }
     // Remove heading keywords from body to avoid duplication
@@ -363,28 +1375,51 @@ pub fn index_file(path: &Path) -> Result<(FileEntry, DocumentMetrics), Box<dyn s
 
     // NEW: Compute term frequencies for BM25
     let mut term_frequencies: HashMap<String, usize> = HashMap::new();
+    let mut term_positions: HashMap<String, Vec<usize>> = HashMap::new();
     let mut total_terms = 0;
+    let mut token_position = 0;
 
-    for line in &lines {
-        // Skip code blocks
-        if line.starts_with("```") || line.starts_with("    ") {
-            continue;
-        }
-        let words = extract_keywords(line);
-        for word in words {
-            let stemmed = stem_word(&word);
+    for line in &prose_lines {
+        // Positions count stopwords too so phrase offsets line up with the query
+        for word in extract_keywords_with_options(line, None) {
+            token_position += 1;
+            if stop_words.contains(&word) {
+                continue;
+            }
+            let stemmed = stem_word_with(&word, stemmer);
+            if options.positions {
+                term_positions
+                    .entry(stemmed.clone())
+                    .or_default()
+                    .push(token_position - 1);
+            }
             *term_frequencies.entry(stemmed).or_insert(0) += 1;
             total_terms += 1;
         }
     }
 
+    let code_terms = extract_code_terms(prose_lines.iter().copied());
+    let keyword_display = keyword_display_forms(
+        headings
+            .iter()
+            .map(|h| h.text.as_str())
+            .chain(prose_lines.iter().copied()),
+        stemmer,
+        stop_words,
+    );
+
+    // Heading terms, counted the same way, for BM25 heading boosts
+    let heading_text: Vec<&str> = headings.iter().map(|h| h.text.as_str()).collect();
+    let (heading_term_frequencies, _) =
+        text_term_frequencies(&heading_text.join("\n"), stemmer, stop_words);
+
     // NEW: Compute MinHash signature
     let all_keywords: Vec<String> = keywords
         .iter()
         .chain(body_keywords.iter())
         .cloned()
         .collect();
-    let minhash = compute_minhash(&all_keywords, 128);
+    let minhash = compute_minhash(&all_keywords, options.minhash_perms);
 
     // NEW: Compute section-level SimHash fingerprints
     let section_fingerprints = index_sections(&content, &headings);
@@ -392,7 +1427,12 @@ pub fn index_file(path: &Path) -> Result<(FileEntry, DocumentMetrics), Box<dyn s
         compute_document_metrics(&path.to_string_lossy(), &content, &lines, &headings, &links);
 
     // Compute simhash fingerprint
-    let simhash = compute_simhash(&content);
+    let prose = prose_lines.join("\n");
+    let simhash = compute_simhash(&prose);
+    let simhash_features = match options.simhash_weighting {
+        SimhashWeighting::Idf => simhash_features(&prose, stemmer),
+        SimhashWeighting::Uniform => SimhashFeatures::default(),
+    };
 
     // Extract ADR references from content
     let adr_regex = Regex::new(r"\bADR[-_ ]?(\d{2,4})\b").unwrap();
@@ -421,12 +1461,19 @@ pub fn index_file(path: &Path) -> Result<(FileEntry, DocumentMetrics), Box<dyn s
             links,
             simhash,
             term_frequencies,
+            heading_term_frequencies,
             doc_length: total_terms,
             minhash,
             section_fingerprints,
             adr_references,
+            frontmatter,
+            term_positions,
+            code_terms,
+            keyword_display,
+            anchors,
         },
         metrics,
+        simhash_features,
     ))
 }
 
@@ -552,6 +1599,53 @@ pub fn extract_frontmatter_key_count(lines: &[&str]) -> (usize, usize) {
     (0, 0)
 }
 
+/// Parse a leading `---` YAML frontmatter block into flat key/value pairs.
+///
+/// Returns the parsed fields and the number of lines the block occupies
+/// (including both delimiters), or an empty map and `0` when the file has no
+/// frontmatter or the block is not a YAML mapping. Scalar values are
+/// stringified; sequences are joined with ", ".
+pub fn parse_frontmatter(lines: &[&str]) -> (HashMap<String, String>, usize) {
+    let (_, end) = extract_frontmatter_key_count(lines);
+    if end < 2 {
+        return (HashMap::new(), 0);
+    }
+
+    let block = lines[1..end - 1].join("\n");
+    let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str::<serde_yaml::Value>(&block)
+    else {
+        return (HashMap::new(), 0);
+    };
+
+    let mut fields = HashMap::new();
+    for (key, value) in mapping {
+        let Some(key) = frontmatter_scalar(&key) else {
+            continue;
+        };
+        let value = match value {
+            serde_yaml::Value::Sequence(items) => items
+                .iter()
+                .filter_map(frontmatter_scalar)
+                .collect::<Vec<_>>()
+                .join(", "),
+            other => frontmatter_scalar(&other).unwrap_or_default(),
+        };
+        fields.insert(key, value);
+    }
+
+    (fields, end)
+}
+
+fn frontmatter_scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Null => Some(String::new()),
+        _ => None,
+    }
+}
+
 pub fn heading_looks_like_part(text: &str) -> bool {
     let trimmed = text.trim().to_ascii_lowercase();
     trimmed.starts_with("part ")
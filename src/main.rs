@@ -109,6 +109,21 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
     let config = load_config(&cli.config, cli.quiet);
+    let tokenizer = TokenizerKind::from_name(&cli.tokenizer).ok_or_else(|| {
+        format!(
+            "Unsupported tokenizer: {} (expected approx or cl100k)",
+            cli.tokenizer
+        )
+    })?;
+    assemble::check_tokenizer_available(tokenizer)?;
+    let version_check = if cli.strict {
+        IndexVersionCheck::Strict
+    } else {
+        IndexVersionCheck::Warn
+    };
+    let index_dir = |path: PathBuf| IndexDir::new(path, version_check);
+    // `--quiet` suppresses timings even when `--timing` is set
+    let timing = cli.timing && !cli.quiet;
 
     let result = match cli.command {
         Commands::Check {
@@ -121,8 +136,16 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             index,
             policy,
             stale_days,
+            use_git,
+            ignore_file,
         } => {
-            let index_path = resolve_index_path(index, cli.profile.as_deref(), &config);
+            // Each check loads the index; an outdated one is reported by the first
+            let mut index_path =
+                index_dir(resolve_index_path(index, cli.profile.as_deref(), &config));
+            let allowlist = ignore_file
+                .as_deref()
+                .map(load_link_allowlist)
+                .transpose()?;
 
             let mut combined = CombinedCheckResult::default();
 
@@ -134,9 +157,17 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                     .and_then(|c| c.external.as_ref())
                     .map(|e| e.repos.iter().map(|r| r.path.clone()).collect())
                     .unwrap_or_default();
-                let link_result =
-                    run_link_check(&index_path, None, include_summary, false, &external_paths)?;
+                let link_result = run_link_check(
+                    &index_path,
+                    &LinkCheckOptions {
+                        include_summary,
+                        external_paths: &external_paths,
+                        allowlist: allowlist.as_ref(),
+                        ..LinkCheckOptions::default()
+                    },
+                )?;
                 combined.links = Some(link_result);
+                index_path = index_path.quiet();
             }
 
             // Run policy checks if requested
@@ -145,13 +176,18 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                     Some(p) => p,
                     None => PathBuf::from(".yore-policy.yaml"),
                 };
-                let policy_result = run_policy_check(&index_path, &policy_path)?;
+                let policy_result = run_policy_check(&index_path, &policy_path, false)?;
                 combined.policy = Some(policy_result);
+                index_path = index_path.quiet();
             }
 
             // Run staleness checks if requested
             if stale {
-                let stale_result = run_stale_check(&index_path, stale_days, 0)?;
+                let criteria = StaleCriteria {
+                    days: stale_days,
+                    ..StaleCriteria::default()
+                };
+                let stale_result = run_stale_check(&index_path, &criteria, use_git)?;
                 combined.stale = Some(stale_result);
             }
 
@@ -224,7 +260,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         } => cmd_health(
             file.as_deref(),
             all,
-            &index,
+            &index_dir(index),
             &HealthOptions {
                 max_lines,
                 max_part_sections,
@@ -233,6 +269,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             },
             json,
         ),
+        Commands::Init { policy, force } => cmd_init(&cli.config, &policy, force),
         Commands::Build {
             path,
             output,
@@ -240,18 +277,41 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             exclude,
             json,
             track_renames,
+            jobs,
+            stemmer,
+            positions,
+            minhash_perms,
+            simhash_weighting,
+            stop_words,
+            stop_words_mode,
+            format,
+            watch,
+            debounce_ms,
         } => {
             let (path, output, types, roots) =
                 resolve_build_params(path, output, types, cli.profile.as_deref(), &config);
+            let skip_dirs = resolve_skip_dirs(cli.profile.as_deref(), &config);
             cmd_build(
                 &path,
                 &output,
-                &types,
-                &exclude,
-                cli.quiet,
-                roots.as_deref(),
-                json,
-                track_renames,
+                &BuildOptions {
+                    types: &types,
+                    exclude: &exclude,
+                    quiet: cli.quiet,
+                    roots: roots.as_deref(),
+                    skip_dirs: skip_dirs.as_deref(),
+                    json,
+                    track_renames,
+                    jobs,
+                    stemmer: &stemmer,
+                    positions,
+                    minhash_perms,
+                    simhash_weighting: &simhash_weighting,
+                    stop_words: stop_words.as_deref(),
+                    stop_words_mode: &stop_words_mode,
+                    format: &format,
+                    watch_debounce: watch.then(|| std::time::Duration::from_millis(debounce_ms)),
+                },
             )
         }
         Commands::Query {
@@ -264,6 +324,17 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             explain,
             no_stopwords,
             phrase,
+            tags,
+            snippets,
+            offset,
+            min_score,
+            synonyms,
+            exclude,
+            types,
+            heading_boost,
+            code_boost,
+            group_by_dir,
+            group_depth,
             index,
         } => {
             let query_text = query.unwrap_or_else(|| terms.join(" "));
@@ -275,8 +346,17 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                 explain,
                 require_phrases: phrase,
                 filter_stopwords: !no_stopwords,
+                tags,
+                snippets,
+                offset,
+                min_score,
+                synonyms: load_optional_synonyms(synonyms.as_deref())?,
+                scope: compile_path_scope(&[], &exclude, &types)?,
+                heading_boost,
+                code_boost,
+                group_depth: group_by_dir.then_some(group_depth),
             };
-            cmd_query(&query_text, &index, &options)
+            cmd_query(&query_text, &index_dir(index), &options)
         }
         Commands::Similar {
             file,
@@ -285,33 +365,112 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             json,
             doc_terms,
             index,
-        } => cmd_similar(&file, limit, threshold, json, doc_terms, &index),
+            similarity,
+        } => {
+            let weights = SimilarityWeights::with_overrides(
+                SimilarityWeights::SIMILAR,
+                similarity.w_jaccard,
+                similarity.w_simhash,
+                similarity.w_minhash,
+            )?;
+            cmd_similar(
+                &file,
+                limit,
+                threshold,
+                json,
+                doc_terms,
+                weights,
+                &index_dir(index),
+            )
+        }
         Commands::Dupes {
             threshold,
             group,
             json,
+            csv,
+            lsh_bands,
             index,
-        } => cmd_dupes(threshold, group, json, &index),
+            similarity,
+        } => {
+            let weights = SimilarityWeights::with_overrides(
+                SimilarityWeights::DUPES,
+                similarity.w_jaccard,
+                similarity.w_simhash,
+                similarity.w_minhash,
+            )?;
+            cmd_dupes(
+                &DupesOptions {
+                    threshold,
+                    group,
+                    json,
+                    csv,
+                    weights,
+                    lsh_bands,
+                    timing,
+                },
+                &index_dir(index),
+            )
+        }
         Commands::DupesSections {
+            threshold,
+            min_files,
+            show_diff,
+            json,
+            index,
+        } => cmd_dupes_sections(
+            threshold,
+            min_files,
+            show_diff,
+            json,
+            timing,
+            &index_dir(index),
+        ),
+        Commands::DupesHeadings {
             threshold,
             min_files,
             json,
             index,
-        } => cmd_dupes_sections(threshold, min_files, json, &index),
+        } => cmd_dupes_headings(threshold, min_files, json, &index_dir(index)),
         Commands::Diff {
             file1,
             file2,
             index,
             json,
-        } => cmd_diff(&file1, &file2, &index, json),
+            similarity,
+        } => {
+            let weights = SimilarityWeights::with_overrides(
+                SimilarityWeights::SIMILAR,
+                similarity.w_jaccard,
+                similarity.w_simhash,
+                similarity.w_minhash,
+            )?;
+            cmd_diff(&file1, &file2, &index_dir(index), json, weights)
+        }
         Commands::Stats {
+            top_keywords,
+            check_drift,
+            index,
+            json,
+        } => {
+            if check_drift {
+                if cmd_check_drift(&index_dir(index), json)? {
+                    std::process::exit(1);
+                }
+                Ok(())
+            } else {
+                cmd_stats(top_keywords, &index_dir(index), json)
+            }
+        }
+        Commands::Show {
+            file,
             top_keywords,
             index,
             json,
-        } => cmd_stats(top_keywords, &index, json),
-        Commands::Repl { index } => cmd_repl(&index),
+        } => cmd_show(&file, top_keywords, &index_dir(index), json),
+        Commands::Repl { index } => cmd_repl(&index_dir(index)),
         Commands::Assemble {
             query,
+            query_file,
             max_tokens,
             max_sections,
             depth,
@@ -319,9 +478,20 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             doc_terms,
             from_files,
             use_relations,
+            dedup_threshold,
+            no_dedup,
+            synonyms,
+            include,
+            exclude,
+            types,
+            canonicality_weight,
+            heading_boost,
+            xref_fraction,
+            xref_max_tokens,
+            xref_per_doc_tokens,
             index,
         } => cmd_assemble(
-            &query.join(" "),
+            &read_assemble_query(&query, query_file.as_deref(), &from_files)?,
             &from_files,
             &AssembleOptions {
                 max_tokens,
@@ -330,8 +500,21 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                 format,
                 doc_terms,
                 use_relations,
+                dedup_threshold: (!no_dedup).then_some(dedup_threshold),
+                synonyms: load_optional_synonyms(synonyms.as_deref())?,
+                scope: compile_path_scope(&include, &exclude, &types)?,
+                canonicality_weight,
+                heading_boost,
+                xref: XrefConfig {
+                    fraction: xref_fraction,
+                    max_tokens: xref_max_tokens,
+                    per_doc_tokens: xref_per_doc_tokens,
+                    ..XrefConfig::DEFAULT
+                },
+                tokenizer,
+                timing,
             },
-            &index,
+            &index_dir(index),
         ),
         Commands::Mcp { command } => match command {
             McpCommands::SearchContext {
@@ -344,11 +527,12 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             } => cmd_mcp_search_context(
                 &query.join(" "),
                 &from_files,
-                &index,
+                &index_dir(index),
                 McpSearchOptions {
                     max_results,
                     max_tokens,
                     max_bytes,
+                    tokenizer,
                 },
             ),
             McpCommands::FetchContext {
@@ -358,20 +542,45 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                 index,
             } => cmd_mcp_fetch_context(
                 &handle,
-                &index,
+                &index_dir(index),
                 McpFetchOptions {
                     max_tokens,
                     max_bytes,
+                    tokenizer,
                 },
             ),
-            McpCommands::Serve { index } => cmd_mcp_serve(&index),
+            McpCommands::Serve { index } => cmd_mcp_serve(&index_dir(index), tokenizer),
         },
         Commands::Eval {
             questions,
             index,
             json,
             k,
-        } => cmd_eval(&questions, &index, json, &k),
+            synonyms,
+            max_tokens,
+            depth,
+            fail_under,
+        } => {
+            let options = AssembleOptions {
+                max_tokens,
+                depth,
+                synonyms: load_optional_synonyms(synonyms.as_deref())?,
+                tokenizer,
+                timing,
+                ..AssembleOptions::default()
+            };
+            if cmd_eval(
+                &questions,
+                &index_dir(index),
+                json,
+                &k,
+                &options,
+                fail_under,
+            )? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
         Commands::Vocabulary {
             index,
             limit,
@@ -382,7 +591,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             no_default_stopwords,
             common_terms,
         } => cmd_vocabulary(
-            &index,
+            &index_dir(index),
             limit,
             &format,
             json,
@@ -399,39 +608,98 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             root,
             summary,
             summary_only,
+            check_external,
+            timeout,
+            concurrency,
+            ignore_file,
+            context_lines,
+            fail_below,
         } => {
-            let index_path = resolve_index_path(index, cli.profile.as_deref(), &config);
+            let index_path = index_dir(resolve_index_path(index, cli.profile.as_deref(), &config));
+            let allowlist = ignore_file
+                .as_deref()
+                .map(load_link_allowlist)
+                .transpose()?;
             let external_paths: Vec<String> = config
                 .as_ref()
                 .and_then(|c| c.external.as_ref())
                 .map(|e| e.repos.iter().map(|r| r.path.clone()).collect())
                 .unwrap_or_default();
-            cmd_check_links(
+            let external_check = check_external.then_some(ExternalCheckOptions {
+                timeout_secs: timeout,
+                concurrency,
+            });
+            if cmd_check_links(
                 &index_path,
                 json,
-                root.as_deref(),
-                summary,
-                summary_only,
-                &external_paths,
-            )
+                fail_below,
+                &LinkCheckOptions {
+                    root: root.as_deref(),
+                    include_summary: summary,
+                    summary_only,
+                    external_paths: &external_paths,
+                    external_check: external_check.as_ref(),
+                    allowlist: allowlist.as_ref(),
+                    context_lines,
+                },
+            )? {
+                std::process::exit(1);
+            }
+            Ok(())
         }
-        Commands::Backlinks { file, index, json } => cmd_backlinks(&file, &index, json),
+        Commands::Backlinks {
+            file,
+            index,
+            depth,
+            json,
+        } => cmd_backlinks(&file, &index_dir(index), depth, json),
         Commands::Orphans {
             index,
             json,
+            csv,
             exclude,
-        } => cmd_orphans(&index, json, &exclude),
+            reachable_from,
+        } => cmd_orphans(
+            &index_dir(index),
+            json,
+            csv,
+            &exclude,
+            reachable_from.as_deref(),
+        ),
         Commands::Canonicality {
             index,
             json,
             threshold,
-        } => cmd_canonicality(&index, json, threshold),
+            no_graph,
+            limit,
+            sort,
+            rules,
+        } => cmd_canonicality(
+            &index_dir(index),
+            json,
+            threshold,
+            !no_graph,
+            limit,
+            sort == "asc",
+            rules.as_deref(),
+        ),
         Commands::CanonicalOrphans {
             index,
             json,
             threshold,
-        } => cmd_canonical_orphans(&index, threshold, json),
-        Commands::ExportGraph { format, index } => cmd_export_graph(&index, &format),
+        } => cmd_canonical_orphans(&index_dir(index), threshold, json),
+        Commands::ExportGraph {
+            format,
+            include_external,
+            index,
+        } => cmd_export_graph(&index_dir(index), &format, include_external),
+        Commands::Path {
+            from,
+            to,
+            undirected,
+            json,
+            index,
+        } => cmd_path(&from, &to, &index_dir(index), json, undirected),
         Commands::Paths {
             source,
             depth,
@@ -441,14 +709,24 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         } => cmd_paths(&source, depth, kind.as_deref(), json, &index),
         Commands::SuggestConsolidation {
             threshold,
+            plan,
             json,
             index,
-        } => cmd_suggest_consolidation(&index, threshold, json),
+        } => cmd_suggest_consolidation(&index_dir(index), threshold, plan, json),
         Commands::Policy {
             config,
             index,
             json,
-        } => cmd_policy(&config, &index, json),
+            fail_on,
+            no_fail,
+            explain,
+        } => {
+            let fail_on = (!no_fail).then_some(fail_on.as_str());
+            if cmd_policy(&config, &index_dir(index), json, fail_on, explain)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
         Commands::FixLinks {
             index,
             dry_run,
@@ -457,14 +735,18 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             apply_decisions,
             json,
             use_git_history,
+            prefer_canonical,
         } => cmd_fix_links(
-            &index,
-            dry_run,
-            apply,
-            propose,
-            apply_decisions,
-            json,
-            use_git_history,
+            &index_dir(index),
+            FixLinksOptions {
+                dry_run,
+                apply,
+                propose,
+                apply_decisions,
+                json,
+                use_git_history,
+                prefer_canonical,
+            },
         ),
         Commands::FixReferences {
             mapping,
@@ -472,7 +754,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             dry_run,
             apply,
             json,
-        } => cmd_fix_references(&index, &mapping, dry_run, apply, json),
+        } => cmd_fix_references(&index_dir(index), &mapping, dry_run, apply, json),
         Commands::Mv {
             from,
             to,
@@ -480,13 +762,25 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
             update_refs,
             dry_run,
             json,
-        } => cmd_mv(&from, &to, &index, update_refs, dry_run, json),
+        } => cmd_mv(&from, &to, &index_dir(index), update_refs, dry_run, json),
         Commands::Stale {
             index,
             days,
             min_inlinks,
+            max_inlinks,
+            require,
             json,
-        } => cmd_stale(&index, days, min_inlinks, json),
+            csv,
+            use_git,
+        } => {
+            let criteria = StaleCriteria {
+                days,
+                min_inlinks,
+                max_inlinks,
+                require: StaleRequirement::parse(&require)?,
+            };
+            cmd_stale(&index_dir(index), &criteria, json, csv, use_git)
+        }
     };
     result
 }
@@ -4,6 +4,7 @@ use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
 
 use crate::assemble::*;
+use crate::search::DEFAULT_HEADING_BOOST;
 use crate::types::*;
 use crate::util::*;
 
@@ -149,7 +150,7 @@ pub(crate) fn load_mcp_artifact(
 pub(crate) fn build_mcp_search_response(
     query: &str,
     from_files: &[String],
-    index_dir: &Path,
+    index_dir: &IndexDir,
     options: McpSearchOptions,
 ) -> Result<McpSearchResponse, Box<dyn std::error::Error>> {
     let forward_index = load_forward_index(index_dir)?;
@@ -167,12 +168,16 @@ pub(crate) fn build_mcp_search_response(
     };
 
     let selection_limit = options.max_results.max(1).saturating_mul(4).max(8);
-    let selection = match collect_context_selection(
-        query,
-        from_files,
-        &forward_index,
-        selection_limit,
-    ) {
+    let search = SectionSearch {
+        index: &forward_index,
+        docs: &DocCache::default(),
+        max_sections: selection_limit,
+        synonyms: &Synonyms::default(),
+        scope: &PathScope::default(),
+        canonicality_weight: DEFAULT_CANONICALITY_WEIGHT,
+        heading_boost: DEFAULT_HEADING_BOOST,
+    };
+    let selection = match collect_context_selection(query, from_files, &search) {
         Ok(selection) => selection,
         Err(issue) => {
             let (error, message, missing_files) = match issue {
@@ -234,7 +239,9 @@ pub(crate) fn build_mcp_search_response(
     let preview_sections = apply_extractive_refiner(
         unique_sections.clone(),
         &selection.query_for_refiner,
+        &forward_index.stop_words,
         per_result_tokens,
+        options.tokenizer,
     );
 
     let mut pressure = McpPressure::default();
@@ -265,8 +272,9 @@ pub(crate) fn build_mcp_search_response(
             &preview_section.section.content,
             per_result_tokens,
             per_result_bytes,
+            options.tokenizer,
         );
-        let preview_tokens = estimate_tokens(&preview);
+        let preview_tokens = count_tokens(&preview, options.tokenizer);
         let preview_bytes = preview.len();
         let mut result_truncated = preview_section.truncated || truncated;
         let mut result_reasons = preview_section.truncation_reasons.clone();
@@ -301,11 +309,11 @@ pub(crate) fn build_mcp_search_response(
             scores: McpScoreBreakdown {
                 bm25: raw_section.bm25_score,
                 canonicality: raw_section.canonicality,
-                combined: combined_section_score(raw_section),
+                combined: combined_section_score(raw_section, DEFAULT_CANONICALITY_WEIGHT),
             },
             preview: preview.clone(),
             content: raw_section.content.clone(),
-            created_at: chrono_now(),
+            created_at: rfc3339_now(),
             index_fingerprint: fingerprint.clone(),
         };
         if let Err(err) = store_mcp_artifact(index_dir, &artifact) {
@@ -396,7 +404,7 @@ pub(crate) fn build_mcp_search_response(
 pub(crate) fn cmd_mcp_search_context(
     query: &str,
     from_files: &[String],
-    index_dir: &Path,
+    index_dir: &IndexDir,
     options: McpSearchOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let response = build_mcp_search_response(query, from_files, index_dir, options)?;
@@ -437,9 +445,13 @@ pub(crate) fn build_mcp_fetch_response(
         });
     };
 
-    let (content, truncated, truncation_reasons) =
-        truncate_text_to_budget(&artifact.content, options.max_tokens, options.max_bytes);
-    let content_tokens = estimate_tokens(&content);
+    let (content, truncated, truncation_reasons) = truncate_text_to_budget(
+        &artifact.content,
+        options.max_tokens,
+        options.max_bytes,
+        options.tokenizer,
+    );
+    let content_tokens = count_tokens(&content, options.tokenizer);
     let content_bytes = content.len();
 
     let mut expansion_path = vec!["artifact_load".to_string()];
@@ -552,10 +564,13 @@ pub(crate) fn write_mcp_stdio_message<W: Write, T: Serialize>(
 }
 
 pub(crate) fn resolve_mcp_tool_index(
-    default_index: &Path,
+    default_index: &IndexDir,
     requested_index: Option<PathBuf>,
-) -> PathBuf {
-    requested_index.unwrap_or_else(|| default_index.to_path_buf())
+) -> IndexDir {
+    requested_index.map_or_else(
+        || default_index.clone(),
+        |path| IndexDir::new(path, default_index.version_check),
+    )
 }
 
 pub(crate) fn mcp_tool_definitions() -> serde_json::Value {
@@ -688,16 +703,17 @@ pub(crate) fn json_rpc_error(
     })
 }
 
-pub(crate) fn cmd_mcp_serve(index_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) fn cmd_mcp_serve(
+    index_dir: &IndexDir,
+    tokenizer: TokenizerKind,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let index_dir = &session_index_dir(index_dir);
     let stdin = io::stdin();
     let stdout = io::stdout();
     let mut reader = stdin.lock();
     let mut writer = stdout.lock();
 
-    loop {
-        let Some(message) = read_mcp_stdio_message(&mut reader)? else {
-            break;
-        };
+    while let Some(message) = read_mcp_stdio_message(&mut reader)? {
         let request: JsonRpcRequest = match serde_json::from_value(message) {
             Ok(request) => request,
             Err(err) => {
@@ -769,6 +785,7 @@ pub(crate) fn cmd_mcp_serve(index_dir: &Path) -> Result<(), Box<dyn std::error::
                                             max_results: args.max_results,
                                             max_tokens: args.max_tokens,
                                             max_bytes: args.max_bytes,
+                                            tokenizer,
                                         },
                                     )?;
                                     let result =
@@ -794,6 +811,7 @@ pub(crate) fn cmd_mcp_serve(index_dir: &Path) -> Result<(), Box<dyn std::error::
                                         McpFetchOptions {
                                             max_tokens: args.max_tokens,
                                             max_bytes: args.max_bytes,
+                                            tokenizer,
                                         },
                                     )?;
                                     let result =
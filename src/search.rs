@@ -1,36 +1,39 @@
 use ahash::AHasher;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash, Hasher};
 
 use crate::types::*;
-use crate::util::default_query_stop_words;
+use crate::util::jaccard_similarity;
 
 // BM25 tuning constants
 pub const BM25_K1: f64 = 1.5;
 pub const BM25_B: f64 = 0.75;
+/// Weight of a heading occurrence relative to a body occurrence in BM25
+pub const DEFAULT_HEADING_BOOST: f64 = 2.0;
+/// A keyword token: a letter followed by letters, digits, `_` or `-`
+const KEYWORD_PATTERN: &str = r"[a-zA-Z][a-zA-Z0-9_-]*";
 
-pub fn extract_keywords(text: &str) -> Vec<String> {
-    extract_keywords_with_options(text, true)
+pub fn extract_keywords(text: &str, stop_words: &StopWords) -> Vec<String> {
+    extract_keywords_with_options(text, Some(stop_words))
 }
 
-pub fn extract_keywords_with_options(text: &str, filter_stopwords: bool) -> Vec<String> {
-    let stop_words: HashSet<&str> = default_query_stop_words().iter().copied().collect();
-
-    let word_re = Regex::new(r"[a-zA-Z][a-zA-Z0-9_-]*").unwrap();
+/// Lowercased words of three or more characters, minus `stop_words` if given.
+pub fn extract_keywords_with_options(text: &str, stop_words: Option<&StopWords>) -> Vec<String> {
+    let word_re = Regex::new(KEYWORD_PATTERN).unwrap();
 
     word_re
         .find_iter(text)
         .map(|m| m.as_str().to_lowercase())
-        .filter(|w| w.len() >= 3 && (!filter_stopwords || !stop_words.contains(w.as_str())))
+        .filter(|w| w.len() >= 3 && !stop_words.is_some_and(|stop| stop.contains(w)))
         .collect()
 }
 
-pub fn parse_query_terms(query: &str, filter_stopwords: bool) -> Vec<String> {
-    extract_keywords_with_options(query, filter_stopwords)
+pub fn parse_query_terms(query: &str, stop_words: Option<&StopWords>) -> Vec<String> {
+    extract_keywords_with_options(query, stop_words)
 }
 
-pub fn parse_query(query: &str, filter_stopwords: bool) -> ParsedQuery {
+fn split_quoted_parts(query: &str) -> Vec<(String, bool)> {
     let mut parts: Vec<(String, bool)> = Vec::new();
     let mut buffer = String::new();
     let mut in_quote = false;
@@ -52,14 +55,74 @@ pub fn parse_query(query: &str, filter_stopwords: bool) -> ParsedQuery {
     if !trimmed.is_empty() {
         parts.push((trimmed.to_string(), in_quote));
     }
+    parts
+}
+
+/// Pull `heading:` / `body:` prefixed words out of the unquoted query parts.
+fn extract_field_terms(
+    parts: &mut Vec<(String, bool)>,
+    stop_words: Option<&StopWords>,
+) -> Vec<FieldTerm> {
+    let mut field_terms = Vec::new();
+    for (text, is_phrase) in parts.iter_mut() {
+        if *is_phrase {
+            continue;
+        }
+        let mut remaining = Vec::new();
+        for word in text.split_whitespace() {
+            let strip = |prefix: &str| {
+                word.get(..prefix.len())
+                    .filter(|head| head.eq_ignore_ascii_case(prefix))
+                    .map(|_| &word[prefix.len()..])
+            };
+            let field = strip("heading:")
+                .map(|value| (QueryField::Heading, value))
+                .or_else(|| strip("body:").map(|value| (QueryField::Body, value)));
+            match field {
+                Some((field, value)) => {
+                    for term in parse_query_terms(value, stop_words) {
+                        field_terms.push(FieldTerm { field, term });
+                    }
+                }
+                None => remaining.push(word),
+            }
+        }
+        *text = remaining.join(" ");
+    }
+    parts.retain(|(text, _)| !text.is_empty());
+    field_terms
+}
+
+pub fn parse_query(query: &str, stop_words: Option<&StopWords>) -> ParsedQuery {
+    let mut parts = split_quoted_parts(query);
+    let field_terms = extract_field_terms(&mut parts, stop_words);
+    let mut parsed = parse_query_parts(&parts, stop_words);
+    parsed
+        .terms
+        .extend(field_terms.iter().map(|field_term| field_term.term.clone()));
+    parsed.field_terms = field_terms;
+    parsed
+}
+
+fn parse_query_parts(parts: &[(String, bool)], stop_words: Option<&StopWords>) -> ParsedQuery {
+    let has_operator = parts.iter().any(|(text, is_phrase)| {
+        !is_phrase
+            && text
+                .split_whitespace()
+                .any(|word| matches!(word, "AND" | "OR" | "NOT"))
+    });
+    if has_operator {
+        return parse_boolean_query(parts, stop_words);
+    }
+
     let mut terms = Vec::new();
     let mut phrases = Vec::new();
 
     for (text, is_phrase) in parts {
-        let parsed_terms = parse_query_terms(&text, filter_stopwords);
+        let parsed_terms = parse_query_terms(text, stop_words);
         terms.extend(parsed_terms.iter().cloned());
-        if is_phrase {
-            let phrase_terms = extract_keywords_with_options(&text, false);
+        if *is_phrase {
+            let phrase_terms = extract_keywords_with_options(text, None);
             if !phrase_terms.is_empty() {
                 phrases.push(PhraseGroup {
                     terms: phrase_terms,
@@ -68,7 +131,194 @@ pub fn parse_query(query: &str, filter_stopwords: bool) -> ParsedQuery {
         }
     }
 
-    ParsedQuery { terms, phrases }
+    ParsedQuery {
+        terms,
+        phrases,
+        filter: None,
+        field_terms: Vec::new(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BoolToken {
+    And,
+    Or,
+    Not,
+    Operand(QueryExpr),
+}
+
+/// Parse a query containing uppercase `AND`/`OR`/`NOT` operators.
+///
+/// Precedence is NOT > AND > OR. Adjacent operands without an operator are
+/// OR'ed, matching plain queries, except that `a NOT b` means `a AND NOT b`.
+/// Quoted segments become single must-match phrase operands. BM25 ranking
+/// uses only the terms that are not negated.
+fn parse_boolean_query(parts: &[(String, bool)], stop_words: Option<&StopWords>) -> ParsedQuery {
+    let mut tokens = Vec::new();
+    for (text, is_phrase) in parts {
+        if *is_phrase {
+            let phrase_terms = extract_keywords_with_options(text, None);
+            if !phrase_terms.is_empty() {
+                tokens.push(BoolToken::Operand(QueryExpr::Phrase(phrase_terms)));
+            }
+            continue;
+        }
+        for word in text.split_whitespace() {
+            match word {
+                "AND" => tokens.push(BoolToken::And),
+                "OR" => tokens.push(BoolToken::Or),
+                "NOT" => tokens.push(BoolToken::Not),
+                _ => {
+                    let word_terms = parse_query_terms(word, stop_words);
+                    let operand = word_terms
+                        .into_iter()
+                        .map(QueryExpr::Term)
+                        .reduce(|acc, term| QueryExpr::And(Box::new(acc), Box::new(term)));
+                    if let Some(operand) = operand {
+                        tokens.push(BoolToken::Operand(operand));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut pos = 0;
+    let filter = parse_bool_or(&tokens, &mut pos);
+
+    let mut terms = Vec::new();
+    let mut phrases = Vec::new();
+    if let Some(expr) = &filter {
+        collect_positive_terms(expr, false, &mut terms, &mut phrases);
+    }
+
+    ParsedQuery {
+        terms,
+        phrases,
+        filter,
+        field_terms: Vec::new(),
+    }
+}
+
+fn parse_bool_or(tokens: &[BoolToken], pos: &mut usize) -> Option<QueryExpr> {
+    let mut left = parse_bool_and(tokens, pos);
+    while *pos < tokens.len() {
+        if tokens[*pos] == BoolToken::Or {
+            *pos += 1;
+        }
+        let Some(right) = parse_bool_and(tokens, pos) else {
+            break;
+        };
+        left = Some(match left {
+            Some(l) => QueryExpr::Or(Box::new(l), Box::new(right)),
+            None => right,
+        });
+    }
+    left
+}
+
+fn parse_bool_and(tokens: &[BoolToken], pos: &mut usize) -> Option<QueryExpr> {
+    let mut left = parse_bool_unary(tokens, pos);
+    while *pos < tokens.len() {
+        let right = match tokens[*pos] {
+            BoolToken::And => {
+                *pos += 1;
+                parse_bool_unary(tokens, pos)
+            }
+            BoolToken::Not if left.is_some() => parse_bool_unary(tokens, pos),
+            _ => break,
+        };
+        let Some(right) = right else {
+            break;
+        };
+        left = Some(match left {
+            Some(l) => QueryExpr::And(Box::new(l), Box::new(right)),
+            None => right,
+        });
+    }
+    left
+}
+
+fn parse_bool_unary(tokens: &[BoolToken], pos: &mut usize) -> Option<QueryExpr> {
+    while let Some(token) = tokens.get(*pos) {
+        *pos += 1;
+        match token {
+            BoolToken::Not => {
+                return parse_bool_unary(tokens, pos).map(|e| QueryExpr::Not(Box::new(e)));
+            }
+            BoolToken::Operand(expr) => return Some(expr.clone()),
+            // Dangling AND/OR operators are ignored
+            BoolToken::And | BoolToken::Or => {}
+        }
+    }
+    None
+}
+
+fn collect_positive_terms(
+    expr: &QueryExpr,
+    negated: bool,
+    terms: &mut Vec<String>,
+    phrases: &mut Vec<PhraseGroup>,
+) {
+    match expr {
+        QueryExpr::Term(term) => {
+            if !negated {
+                terms.push(term.clone());
+            }
+        }
+        QueryExpr::Phrase(phrase_terms) => {
+            if !negated {
+                terms.extend(phrase_terms.iter().cloned());
+                phrases.push(PhraseGroup {
+                    terms: phrase_terms.clone(),
+                });
+            }
+        }
+        QueryExpr::And(a, b) | QueryExpr::Or(a, b) => {
+            collect_positive_terms(a, negated, terms, phrases);
+            collect_positive_terms(b, negated, terms, phrases);
+        }
+        QueryExpr::Not(inner) => collect_positive_terms(inner, !negated, terms, phrases),
+    }
+}
+
+/// Evaluate a boolean query filter against a document.
+///
+/// Terms are checked against the stemmed `term_frequencies`; phrases are only
+/// resolved through `content_terms` (lazily loaded document tokens) once all
+/// of their words are known to be present.
+pub fn query_expr_matches(
+    expr: &QueryExpr,
+    entry: &FileEntry,
+    stemmer: StemmerKind,
+    stop_words: &StopWords,
+    content_terms: &mut dyn FnMut() -> Vec<String>,
+) -> bool {
+    match expr {
+        QueryExpr::Term(term) => entry
+            .term_frequencies
+            .contains_key(&stem_word_with(term, stemmer)),
+        QueryExpr::Phrase(phrase_terms) if !entry.term_positions.is_empty() => {
+            phrase_matches_positions(entry, phrase_terms, stemmer, stop_words)
+        }
+        QueryExpr::Phrase(phrase_terms) => {
+            phrase_terms.iter().all(|t| {
+                // Stopwords are not indexed, so only require indexed words
+                let stem = stem_word_with(t, stemmer);
+                entry.term_frequencies.contains_key(&stem) || stop_words.contains(t)
+            }) && contains_phrase_tokens(&content_terms(), phrase_terms)
+        }
+        QueryExpr::And(a, b) => {
+            query_expr_matches(a, entry, stemmer, stop_words, content_terms)
+                && query_expr_matches(b, entry, stemmer, stop_words, content_terms)
+        }
+        QueryExpr::Or(a, b) => {
+            query_expr_matches(a, entry, stemmer, stop_words, content_terms)
+                || query_expr_matches(b, entry, stemmer, stop_words, content_terms)
+        }
+        QueryExpr::Not(inner) => {
+            !query_expr_matches(inner, entry, stemmer, stop_words, content_terms)
+        }
+    }
 }
 
 /// Simple suffix-stripping stemmer
@@ -91,6 +341,17 @@ pub fn stem_word(word: &str) -> String {
     w
 }
 
+/// Stem a word with the given algorithm, lowercasing first
+pub fn stem_word_with(word: &str, stemmer: StemmerKind) -> String {
+    match stemmer {
+        StemmerKind::Simple => stem_word(word),
+        StemmerKind::Porter => rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English)
+            .stem(&word.to_lowercase())
+            .into_owned(),
+        StemmerKind::None => word.to_lowercase(),
+    }
+}
+
 /// Extract top N distinctive terms from a document, excluding query terms.
 /// Returns human-readable (unstemmed) terms ranked by TF-IDF.
 pub fn get_top_doc_terms(
@@ -98,6 +359,7 @@ pub fn get_top_doc_terms(
     idf_map: &HashMap<String, f64>,
     exclude_terms: &[String],
     n: usize,
+    stemmer: StemmerKind,
 ) -> Vec<String> {
     if n == 0 {
         return Vec::new();
@@ -106,7 +368,7 @@ pub fn get_top_doc_terms(
     // Stem the exclusion terms for comparison
     let exclude_stemmed: HashSet<String> = exclude_terms
         .iter()
-        .map(|t| stem_word(&t.to_lowercase()))
+        .map(|t| stem_word_with(t, stemmer))
         .collect();
 
     // Collect unique keywords with their TF-IDF scores
@@ -115,7 +377,7 @@ pub fn get_top_doc_terms(
     let mut term_scores: Vec<(String, f64)> = Vec::new();
 
     for kw in entry.body_keywords.iter().chain(entry.keywords.iter()) {
-        let stemmed = stem_word(&kw.to_lowercase());
+        let stemmed = stem_word_with(kw, stemmer);
 
         // Skip if already seen this stem, or if it's an excluded term
         if seen_stems.contains(&stemmed) || exclude_stemmed.contains(&stemmed) {
@@ -144,22 +406,133 @@ pub fn get_top_doc_terms(
         .collect()
 }
 
+/// Pick up to `max` lines of `content` with the most words whose stem matches
+/// one of `terms`, returned in document order.
+pub fn find_query_snippets(
+    content: &str,
+    terms: &[String],
+    stemmer: StemmerKind,
+    max: usize,
+) -> Vec<QuerySnippet> {
+    let term_stems: HashSet<String> = terms.iter().map(|t| stem_word_with(t, stemmer)).collect();
+    if term_stems.is_empty() || max == 0 {
+        return Vec::new();
+    }
+
+    let word_re = Regex::new(r"[a-zA-Z][a-zA-Z0-9_-]*").unwrap();
+    let mut is_match: HashMap<String, bool> = HashMap::new();
+    let mut snippets: Vec<QuerySnippet> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let hits = word_re
+                .find_iter(line)
+                .filter(|m| {
+                    let word = m.as_str().to_lowercase();
+                    *is_match
+                        .entry(word)
+                        .or_insert_with_key(|w| term_stems.contains(&stem_word_with(w, stemmer)))
+                })
+                .count();
+            (hits > 0).then(|| QuerySnippet {
+                line: idx + 1,
+                text: line.trim().to_string(),
+                hits,
+            })
+        })
+        .collect();
+
+    snippets.sort_by(|a, b| b.hits.cmp(&a.hits).then(a.line.cmp(&b.line)));
+    snippets.truncate(max);
+    snippets.sort_by_key(|s| s.line);
+    snippets
+}
+
+/// Headings containing one of `terms`, grouped by file, from the reverse
+/// index's heading-derived entries. Each file's hits are in line order.
+pub fn find_heading_hits(
+    reverse_index: &ReverseIndex,
+    terms: &[String],
+    stemmer: StemmerKind,
+) -> HashMap<String, Vec<HeadingHit>> {
+    let mut hits: HashMap<String, Vec<HeadingHit>> = HashMap::new();
+    let stems: HashSet<String> = terms.iter().map(|t| stem_word_with(t, stemmer)).collect();
+    for stem in &stems {
+        let Some(entries) = reverse_index.keywords.get(stem) else {
+            continue;
+        };
+        for entry in entries {
+            if let (Some(line), Some(heading)) = (entry.line, &entry.heading) {
+                hits.entry(entry.file.clone())
+                    .or_default()
+                    .push(HeadingHit {
+                        line,
+                        heading: heading.clone(),
+                    });
+            }
+        }
+    }
+    for file_hits in hits.values_mut() {
+        file_hits.sort();
+        file_hits.dedup();
+    }
+    hits
+}
+
 /// Compute simhash fingerprint for content
 pub fn compute_simhash(content: &str) -> u64 {
-    let mut v = [0i32; 64];
-
     // Extract features (word shingles)
     let words: Vec<&str> = content.split_whitespace().collect();
+    simhash_fingerprint(words.windows(3).map(|window| (shingle_hash(window), 1.0)))
+}
+
+/// Hash each 3-word shingle of `content` and record every word's indexed
+/// term, so the fingerprint can be weighted once IDF is known.
+pub fn simhash_features(content: &str, stemmer: StemmerKind) -> SimhashFeatures {
+    let words: Vec<&str> = content.split_whitespace().collect();
+    SimhashFeatures {
+        shingles: words.windows(3).map(shingle_hash).collect(),
+        terms: words
+            .iter()
+            .map(|word| {
+                let word = word
+                    .trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase();
+                stem_word_with(&word, stemmer)
+            })
+            .collect(),
+    }
+}
+
+/// SimHash over `features` with each shingle weighted by the mean IDF of
+/// its words, so boilerplate shared across documents barely moves the
+/// fingerprint. Words without an IDF (stop words, short tokens) count as
+/// the BM25 floor of 0.1.
+pub fn idf_weighted_simhash(features: &SimhashFeatures, idf_map: &HashMap<String, f64>) -> u64 {
+    let idf = |term: &String| idf_map.get(term).copied().unwrap_or(0.1);
+    simhash_fingerprint(
+        features
+            .shingles
+            .iter()
+            .zip(features.terms.windows(3))
+            .map(|(&hash, terms)| (hash, terms.iter().map(idf).sum::<f64>() / 3.0)),
+    )
+}
 
-    for window in words.windows(3) {
-        let shingle = format!("{} {} {}", window[0], window[1], window[2]);
-        let h = hash_string(&shingle);
+fn shingle_hash(window: &[&str]) -> u64 {
+    hash_string(&format!("{} {} {}", window[0], window[1], window[2]))
+}
 
+/// Fold weighted 64-bit feature hashes into a fingerprint: each bit is set
+/// when the features with that bit set outweigh those without it.
+fn simhash_fingerprint(features: impl Iterator<Item = (u64, f64)>) -> u64 {
+    let mut v = [0f64; 64];
+    for (h, weight) in features {
         for (i, item) in v.iter_mut().enumerate() {
             if (h >> i) & 1 == 1 {
-                *item += 1;
+                *item += weight;
             } else {
-                *item -= 1;
+                *item -= weight;
             }
         }
     }
@@ -167,7 +540,7 @@ pub fn compute_simhash(content: &str) -> u64 {
     // Convert to fingerprint
     let mut fingerprint: u64 = 0;
     for (i, item) in v.iter().enumerate() {
-        if *item > 0 {
+        if *item > 0.0 {
             fingerprint |= 1 << i;
         }
     }
@@ -223,13 +596,25 @@ pub fn index_sections(content: &str, headings: &[Heading]) -> Vec<SectionFingerp
     sections
 }
 
+/// Hasher with fixed keys so MinHash signatures and LSH bands are
+/// reproducible across processes (`AHasher::default()` is randomly keyed).
+fn stable_hasher() -> AHasher {
+    ahash::RandomState::with_seeds(
+        0x243f_6a88_85a3_08d3,
+        0x1319_8a2e_0370_7344,
+        0xa409_3822_299f_31d0,
+        0x082e_fa98_ec4e_6c89,
+    )
+    .build_hasher()
+}
+
 /// Compute MinHash signature for a set of keywords
 pub fn compute_minhash(keywords: &[String], num_hashes: usize) -> Vec<u64> {
     let mut hashes = vec![u64::MAX; num_hashes];
 
     for keyword in keywords {
         for (i, hash_slot) in hashes.iter_mut().enumerate().take(num_hashes) {
-            let mut hasher = AHasher::default();
+            let mut hasher = stable_hasher();
             keyword.hash(&mut hasher);
             i.hash(&mut hasher); // Use index as seed
             let h = hasher.finish();
@@ -252,36 +637,349 @@ pub fn minhash_similarity(a: &[u64], b: &[u64]) -> f64 {
     matches as f64 / a.len() as f64
 }
 
-/// Compute BM25 score for a document given query terms
-pub fn bm25_score(
+/// Highest-count spelling; ties go to the alphabetically first.
+fn most_frequent_spelling(counts: HashMap<&str, usize>) -> Option<&str> {
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(spelling, _)| spelling)
+}
+
+/// Most frequent original spelling of each stemmed keyword in `texts`,
+/// tokenized like `extract_keywords`. Keywords already spelled like their
+/// key are left out.
+pub fn keyword_display_forms<'a>(
+    texts: impl IntoIterator<Item = &'a str>,
+    stemmer: StemmerKind,
+    stop_words: &StopWords,
+) -> HashMap<String, String> {
+    let word_re = Regex::new(KEYWORD_PATTERN).unwrap();
+    let mut spellings: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for text in texts {
+        for word in word_re.find_iter(text).map(|m| m.as_str()) {
+            let lower = word.to_lowercase();
+            if lower.len() < 3 || stop_words.contains(&lower) {
+                continue;
+            }
+            *spellings
+                .entry(stem_word_with(&lower, stemmer))
+                .or_default()
+                .entry(word)
+                .or_insert(0) += 1;
+        }
+    }
+    spellings
+        .into_iter()
+        .filter_map(|(key, counts)| {
+            let best = most_frequent_spelling(counts)?;
+            (best != key).then(|| (key, best.to_string()))
+        })
+        .collect()
+}
+
+/// Display form of each keyword across `files`: the spelling most files
+/// use, falling back to the keyword itself.
+pub fn keyword_display_map(files: &HashMap<String, FileEntry>) -> HashMap<String, String> {
+    let mut votes: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+    for entry in files.values() {
+        for key in entry.keywords.iter().chain(entry.body_keywords.iter()) {
+            let display = entry.keyword_display.get(key).unwrap_or(key);
+            *votes.entry(key).or_default().entry(display).or_insert(0) += 1;
+        }
+    }
+    votes
+        .into_iter()
+        .filter_map(|(key, counts)| {
+            let best = most_frequent_spelling(counts)?;
+            (best != key).then(|| (key.to_string(), best.to_string()))
+        })
+        .collect()
+}
+
+/// Lowercased heading and body keywords of a document
+pub fn entry_keyword_set(entry: &FileEntry) -> HashSet<String> {
+    entry
+        .keywords
+        .iter()
+        .chain(entry.body_keywords.iter())
+        .map(|k| k.to_lowercase())
+        .collect()
+}
+
+/// Blend keyword Jaccard, SimHash and MinHash similarity with `weights`
+pub fn combined_similarity(
+    entry1: &FileEntry,
+    entry2: &FileEntry,
+    weights: SimilarityWeights,
+) -> SimilarityScores {
+    let jaccard = jaccard_similarity(&entry_keyword_set(entry1), &entry_keyword_set(entry2));
+    let simhash = simhash_similarity(entry1.simhash, entry2.simhash);
+    let minhash = minhash_similarity(&entry1.minhash, &entry2.minhash);
+    SimilarityScores {
+        jaccard,
+        simhash,
+        minhash,
+        combined: jaccard * weights.jaccard + simhash * weights.simhash + minhash * weights.minhash,
+    }
+}
+
+/// Stem each query term together with its synonyms.
+///
+/// A term joins a synonym group when its stem matches the stem of any word in
+/// the group, so `deployments` picks up the aliases listed for `deploy`.
+/// Terms outside every group expand to their own stem.
+pub fn expand_query_terms(
     query_terms: &[String],
+    synonyms: &Synonyms,
+    stemmer: StemmerKind,
+) -> Vec<Vec<String>> {
+    let stemmed_groups: Vec<Vec<String>> = synonyms
+        .groups
+        .iter()
+        .map(|group| {
+            let mut stems: Vec<String> = Vec::new();
+            for word in group {
+                let stem = stem_word_with(word, stemmer);
+                if !stems.contains(&stem) {
+                    stems.push(stem);
+                }
+            }
+            stems
+        })
+        .collect();
+
+    query_terms
+        .iter()
+        .map(|term| {
+            let stemmed = stem_word_with(term, stemmer);
+            stemmed_groups
+                .iter()
+                .find(|stems| stems.contains(&stemmed))
+                .cloned()
+                .unwrap_or_else(|| vec![stemmed])
+        })
+        .collect()
+}
+
+/// BM25 over stemmed term groups; each group contributes its best-scoring
+/// stem, so a document using any alias scores as if it used that one.
+/// Heading occurrences count `heading_boost` times (BM25F-style).
+pub fn bm25_score_groups(
+    term_groups: &[Vec<String>],
     doc: &FileEntry,
     avg_doc_length: f64,
     idf_map: &HashMap<String, f64>,
+    heading_boost: f64,
 ) -> f64 {
-    if doc.doc_length == 0 {
+    bm25_score_counts(
+        term_groups,
+        &doc.term_frequencies,
+        &doc.heading_term_frequencies,
+        doc.doc_length,
+        avg_doc_length,
+        idf_map,
+        heading_boost,
+    )
+}
+
+/// `bm25_score_groups` over raw stemmed term counts of a text spanning
+/// `length` terms, normalized against `avg_length`. `heading_frequencies`
+/// holds the share of each count that came from headings.
+pub fn bm25_score_counts(
+    term_groups: &[Vec<String>],
+    term_frequencies: &HashMap<String, usize>,
+    heading_frequencies: &HashMap<String, usize>,
+    length: usize,
+    avg_length: f64,
+    idf_map: &HashMap<String, f64>,
+    heading_boost: f64,
+) -> f64 {
+    if length == 0 {
         return 0.0;
     }
 
     let mut score = 0.0;
-    let norm_factor = 1.0 - BM25_B + BM25_B * (doc.doc_length as f64 / avg_doc_length);
+    let norm_factor = 1.0 - BM25_B + BM25_B * (length as f64 / avg_length);
 
-    for term in query_terms {
-        let stemmed = stem_word(&term.to_lowercase());
-        let tf = *doc.term_frequencies.get(&stemmed).unwrap_or(&0) as f64;
-        let idf = idf_map.get(&stemmed).unwrap_or(&0.0);
+    for group in term_groups {
+        let mut best: f64 = 0.0;
+        for stemmed in group {
+            let count = *term_frequencies.get(stemmed).unwrap_or(&0);
+            let in_headings = heading_frequencies
+                .get(stemmed)
+                .map_or(0, |&n| n.min(count));
+            let tf = (count - in_headings) as f64 + heading_boost * in_headings as f64;
+            let idf = idf_map.get(stemmed).unwrap_or(&0.0);
 
-        if tf > 0.0 {
-            score += idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * norm_factor);
+            if tf > 0.0 {
+                best = best.max(idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * norm_factor));
+            }
         }
+        score += best;
     }
 
     score
 }
 
-/// Build LSH buckets for fast duplicate detection
-pub fn lsh_buckets(files: &HashMap<String, FileEntry>, bands: usize) -> HashMap<u64, Vec<String>> {
-    let rows_per_band = 128 / bands; // Assuming 128 hashes
+/// Flag the lines that hold code: ```` ``` ```` fences, everything between an
+/// opening fence and its closing fence, and lines indented four spaces. An
+/// unclosed fence runs to the end of the text.
+pub fn code_line_mask<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<bool> {
+    let mut in_fence = false;
+    lines
+        .into_iter()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                return true;
+            }
+            in_fence || line.starts_with("    ")
+        })
+        .collect()
+}
+
+/// Identifiers inside inline code spans (`` `FooService` ``) of prose lines,
+/// lowercased and tokenized like queries but not stemmed.
+pub fn extract_code_terms<'a>(lines: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+    let span_re = Regex::new(r"`([^`]+)`").unwrap();
+    lines
+        .into_iter()
+        .flat_map(|line| {
+            span_re
+                .captures_iter(line)
+                .flat_map(|caps| extract_keywords_with_options(&caps[1], None))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Score multiplier for a document whose inline code names query terms:
+/// `1 + code_boost × (share of query terms found in its code_terms)`.
+pub fn code_term_boost(query_terms: &[String], doc: &FileEntry, code_boost: f64) -> f64 {
+    if code_boost == 0.0 || query_terms.is_empty() {
+        return 1.0;
+    }
+    let matched = query_terms
+        .iter()
+        .filter(|term| doc.code_terms.contains(term.as_str()))
+        .count();
+    1.0 + code_boost * matched as f64 / query_terms.len() as f64
+}
+
+/// Stemmed term counts and the number of counted terms in `text`, tokenized
+/// the way `index_file` builds `term_frequencies` (stopwords and code are
+/// skipped).
+pub fn text_term_frequencies(
+    text: &str,
+    stemmer: StemmerKind,
+    stop_words: &StopWords,
+) -> (HashMap<String, usize>, usize) {
+    let mut term_frequencies: HashMap<String, usize> = HashMap::new();
+    let mut total_terms = 0;
+    for (line, is_code) in text.lines().zip(code_line_mask(text.lines())) {
+        if is_code {
+            continue;
+        }
+        for word in extract_keywords_with_options(line, None) {
+            if stop_words.contains(&word) {
+                continue;
+            }
+            *term_frequencies
+                .entry(stem_word_with(&word, stemmer))
+                .or_insert(0) += 1;
+            total_terms += 1;
+        }
+    }
+    (term_frequencies, total_terms)
+}
+
+/// Occurrences of a stemmed term within one field of a document.
+///
+/// Heading counts come from the heading text; body counts are the remaining
+/// term frequency once heading occurrences are removed.
+pub fn field_term_frequency(
+    doc: &FileEntry,
+    field: QueryField,
+    stemmed: &str,
+    stemmer: StemmerKind,
+) -> usize {
+    let in_headings = doc
+        .headings
+        .iter()
+        .flat_map(|heading| extract_keywords_with_options(&heading.text, None))
+        .filter(|word| stem_word_with(word, stemmer) == stemmed)
+        .count();
+    match field {
+        QueryField::Heading => in_headings,
+        QueryField::Body => doc
+            .term_frequencies
+            .get(stemmed)
+            .copied()
+            .unwrap_or(0)
+            .saturating_sub(in_headings),
+    }
+}
+
+/// BM25 score honoring `heading:` / `body:` restricted terms.
+///
+/// Unrestricted terms score as in `bm25_score_groups`, expanded through
+/// `synonyms`. Each restricted term scores BM25 over its field frequency,
+/// without synonyms or heading boost, and a document missing any restricted
+/// term in that field scores 0.
+pub fn bm25_score_fields(
+    parsed: &ParsedQuery,
+    doc: &FileEntry,
+    avg_doc_length: f64,
+    idf_map: &HashMap<String, f64>,
+    stemmer: StemmerKind,
+    synonyms: &Synonyms,
+    heading_boost: f64,
+) -> f64 {
+    if parsed.field_terms.is_empty() {
+        let term_groups = expand_query_terms(&parsed.terms, synonyms, stemmer);
+        return bm25_score_groups(&term_groups, doc, avg_doc_length, idf_map, heading_boost);
+    }
+    if doc.doc_length == 0 {
+        return 0.0;
+    }
+
+    // `parse_query` appends the prefixed terms after the unprefixed ones
+    let general_count = parsed.terms.len() - parsed.field_terms.len();
+    let term_groups = expand_query_terms(&parsed.terms[..general_count], synonyms, stemmer);
+    let mut score = bm25_score_groups(&term_groups, doc, avg_doc_length, idf_map, heading_boost);
+    let norm_factor = 1.0 - BM25_B + BM25_B * (doc.doc_length as f64 / avg_doc_length);
+
+    for field_term in &parsed.field_terms {
+        let stemmed = stem_word_with(&field_term.term, stemmer);
+        let tf = field_term_frequency(doc, field_term.field, &stemmed, stemmer) as f64;
+        if tf == 0.0 {
+            return 0.0;
+        }
+        let idf = idf_map.get(&stemmed).unwrap_or(&0.0);
+        score += idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * norm_factor);
+    }
+
+    score
+}
+
+/// LSH bands used when none are requested: the largest divisor of
+/// `num_hashes` up to 16, so every band has the same number of rows.
+pub fn default_lsh_bands(num_hashes: usize) -> usize {
+    (1..=num_hashes.min(16))
+        .rev()
+        .find(|bands| num_hashes.is_multiple_of(*bands))
+        .unwrap_or(1)
+}
+
+/// Build LSH buckets for fast duplicate detection over `num_hashes`-long
+/// MinHash signatures split into `bands` bands. `bands` must divide
+/// `num_hashes`; see `validate_lsh_bands`.
+pub fn lsh_buckets(
+    files: &HashMap<String, FileEntry>,
+    num_hashes: usize,
+    bands: usize,
+) -> HashMap<u64, Vec<String>> {
+    let rows_per_band = num_hashes / bands;
     let mut buckets: HashMap<u64, Vec<String>> = HashMap::new();
 
     for (path, entry) in files {
@@ -294,7 +992,7 @@ pub fn lsh_buckets(files: &HashMap<String, FileEntry>, bands: usize) -> HashMap<
             let end = (start + rows_per_band).min(entry.minhash.len());
 
             // Hash this band's values
-            let mut hasher = AHasher::default();
+            let mut hasher = stable_hasher();
             for val in &entry.minhash[start..end] {
                 val.hash(&mut hasher);
             }
@@ -307,6 +1005,43 @@ pub fn lsh_buckets(files: &HashMap<String, FileEntry>, bands: usize) -> HashMap<
     buckets
 }
 
+/// Check phrase adjacency using positions recorded by `build --positions`.
+///
+/// Stopwords are not indexed, so they only occupy their slot in the phrase;
+/// every other term must appear at consecutive offsets.
+pub fn phrase_matches_positions(
+    entry: &FileEntry,
+    phrase_terms: &[String],
+    stemmer: StemmerKind,
+    stop_words: &StopWords,
+) -> bool {
+    let required: Vec<(usize, String)> = phrase_terms
+        .iter()
+        .enumerate()
+        .filter(|(_, term)| !stop_words.contains(term))
+        .map(|(offset, term)| (offset, stem_word_with(term, stemmer)))
+        .collect();
+
+    let Some((first_offset, first_stem)) = required.first() else {
+        return true;
+    };
+    let Some(first_positions) = entry.term_positions.get(first_stem) else {
+        return false;
+    };
+
+    first_positions.iter().any(|&pos| {
+        let Some(start) = pos.checked_sub(*first_offset) else {
+            return false;
+        };
+        required.iter().skip(1).all(|(offset, stem)| {
+            entry
+                .term_positions
+                .get(stem)
+                .is_some_and(|positions| positions.binary_search(&(start + offset)).is_ok())
+        })
+    })
+}
+
 pub fn contains_phrase_tokens(haystack: &[String], needle: &[String]) -> bool {
     if needle.is_empty() || haystack.len() < needle.len() {
         return false;
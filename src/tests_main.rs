@@ -43,6 +43,42 @@ fn test_simhash_similarity() {
     assert!((sim - (63.0 / 64.0)).abs() < 0.01);
 }
 
+#[test]
+fn test_idf_weighted_simhash_discounts_shared_boilerplate() {
+    let boilerplate = "licensed under the apache license version two see the license file \
+        for details copyright the project authors all rights reserved unless required \
+        by applicable law software distributed under the license is provided as is";
+    let doc_a =
+        format!("{boilerplate} tokenizer splits unicode graphemes before stemming suffixes");
+    let doc_b = format!("{boilerplate} scheduler retries failed uploads with exponential backoff");
+    let features_a = simhash_features(&doc_a, StemmerKind::None);
+    let features_b = simhash_features(&doc_b, StemmerKind::None);
+
+    // With no IDF every shingle weighs the same, matching the uniform SimHash
+    let empty = HashMap::new();
+    assert_eq!(
+        idf_weighted_simhash(&features_a, &empty),
+        compute_simhash(&doc_a)
+    );
+
+    let mut idf_map: HashMap<String, f64> = HashMap::new();
+    for term in features_a.terms.iter().chain(&features_b.terms) {
+        idf_map.insert(term.clone(), 0.1);
+    }
+    for term in "tokenizer splits unicode graphemes before stemming suffixes scheduler retries failed uploads with exponential backoff".split(' ') {
+        idf_map.insert(term.to_string(), 3.0);
+    }
+    let uniform = simhash_similarity(compute_simhash(&doc_a), compute_simhash(&doc_b));
+    let weighted = simhash_similarity(
+        idf_weighted_simhash(&features_a, &idf_map),
+        idf_weighted_simhash(&features_b, &idf_map),
+    );
+    assert!(
+        weighted < uniform,
+        "weighted {weighted} should be below uniform {uniform}"
+    );
+}
+
 #[test]
 fn test_hamming_distance() {
     assert_eq!(hamming_distance(0b1010, 0b1010), 0);
@@ -140,10 +176,16 @@ fn test_lsh_buckets() {
             links: vec![],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: compute_minhash(&keywords1, 128),
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
 
@@ -159,10 +201,16 @@ fn test_lsh_buckets() {
             links: vec![],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: compute_minhash(&keywords2, 128),
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
 
@@ -178,14 +226,20 @@ fn test_lsh_buckets() {
             links: vec![],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: compute_minhash(&keywords3, 128),
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
 
-    let buckets = lsh_buckets(&files, 16);
+    let buckets = lsh_buckets(&files, 128, 16);
 
     // Should create some buckets
     assert!(!buckets.is_empty());
@@ -205,6 +259,19 @@ fn test_lsh_buckets() {
     );
 }
 
+#[test]
+fn test_lsh_bands_must_divide_minhash_perms() {
+    assert_eq!(default_lsh_bands(128), 16);
+    assert_eq!(default_lsh_bands(100), 10);
+    assert_eq!(default_lsh_bands(24), 12);
+    assert_eq!(default_lsh_bands(7), 7);
+    assert_eq!(default_lsh_bands(0), 1);
+
+    assert!(validate_lsh_bands(8, 32).is_ok());
+    assert!(validate_lsh_bands(5, 32).is_err());
+    assert!(validate_lsh_bands(0, 32).is_err());
+}
+
 #[test]
 fn test_bm25_score_basic() {
     let mut term_freq = HashMap::new();
@@ -221,10 +288,16 @@ fn test_bm25_score_basic() {
         links: vec![],
         simhash: 0,
         term_frequencies: term_freq,
+        heading_term_frequencies: HashMap::new(),
         doc_length: 100,
         minhash: vec![],
         section_fingerprints: vec![],
         adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
     };
 
     let mut idf_map = HashMap::new();
@@ -232,14 +305,26 @@ fn test_bm25_score_basic() {
     idf_map.insert("word".to_string(), 1.8);
 
     let query = vec!["test".to_string()];
-    let score = bm25_score(&query, &doc, 100.0, &idf_map);
+    let score = bm25_score_groups(
+        &expand_query_terms(&query, &Synonyms::default(), StemmerKind::Simple),
+        &doc,
+        100.0,
+        &idf_map,
+        DEFAULT_HEADING_BOOST,
+    );
 
     // Score should be > 0 for matching term
     assert!(score > 0.0);
 
     // Query with no matching terms should score 0
     let empty_query = vec!["nonexistent".to_string()];
-    let zero_score = bm25_score(&empty_query, &doc, 100.0, &idf_map);
+    let zero_score = bm25_score_groups(
+        &expand_query_terms(&empty_query, &Synonyms::default(), StemmerKind::Simple),
+        &doc,
+        100.0,
+        &idf_map,
+        DEFAULT_HEADING_BOOST,
+    );
     assert_eq!(zero_score, 0.0);
 }
 
@@ -259,10 +344,16 @@ fn test_bm25_score_ordering() {
         links: vec![],
         simhash: 0,
         term_frequencies: tf_high,
+        heading_term_frequencies: HashMap::new(),
         doc_length: 50,
         minhash: vec![],
         section_fingerprints: vec![],
         adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
     };
 
     // Document with low term frequency
@@ -279,18 +370,36 @@ fn test_bm25_score_ordering() {
         links: vec![],
         simhash: 0,
         term_frequencies: tf_low,
+        heading_term_frequencies: HashMap::new(),
         doc_length: 50,
         minhash: vec![],
         section_fingerprints: vec![],
         adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
     };
 
     let mut idf_map = HashMap::new();
     idf_map.insert("test".to_string(), 2.0);
 
     let query = vec!["test".to_string()];
-    let score_high = bm25_score(&query, &doc_high_tf, 50.0, &idf_map);
-    let score_low = bm25_score(&query, &doc_low_tf, 50.0, &idf_map);
+    let score_high = bm25_score_groups(
+        &expand_query_terms(&query, &Synonyms::default(), StemmerKind::Simple),
+        &doc_high_tf,
+        50.0,
+        &idf_map,
+        DEFAULT_HEADING_BOOST,
+    );
+    let score_low = bm25_score_groups(
+        &expand_query_terms(&query, &Synonyms::default(), StemmerKind::Simple),
+        &doc_low_tf,
+        50.0,
+        &idf_map,
+        DEFAULT_HEADING_BOOST,
+    );
 
     // Higher term frequency should yield higher BM25 score
     assert!(score_high > score_low);
@@ -320,7 +429,7 @@ fn test_policy_rule_matching_and_violations() {
     let rule_ref = &policy.rules[0];
     let file_path = "agents/plans/plan.md";
     let content = String::new();
-    let violations = collect_policy_violations_for_content(rule_ref, file_path, &content);
+    let violations = policy_violations(rule_ref, file_path, &content);
 
     assert_eq!(violations.len(), 1);
     let v = &violations[0];
@@ -344,8 +453,7 @@ fn test_policy_min_max_length_violations() {
 
     // Too short: 3 lines
     let short_content = "line1\nline2\nline3\n";
-    let short_violations =
-        collect_policy_violations_for_content(&rule, "docs/short.md", short_content);
+    let short_violations = policy_violations(&rule, "docs/short.md", short_content);
     assert!(
         short_violations
             .iter()
@@ -354,9 +462,11 @@ fn test_policy_min_max_length_violations() {
     );
 
     // Too long: 25 lines
-    let long_content: String = (0..25).map(|i| format!("line{i}\n")).collect();
-    let long_violations =
-        collect_policy_violations_for_content(&rule, "docs/long.md", &long_content);
+    let long_content: String = (0..25)
+        .map(|i| format!("line{i}\n"))
+        .collect::<Vec<_>>()
+        .concat();
+    let long_violations = policy_violations(&rule, "docs/long.md", &long_content);
     assert!(
         long_violations
             .iter()
@@ -365,6 +475,36 @@ fn test_policy_min_max_length_violations() {
     );
 }
 
+#[test]
+fn test_policy_file_count_bounds() {
+    let rule = PolicyRule {
+        pattern: "docs/adr/ADR-0001-*.md".to_string(),
+        name: Some("single-adr-0001".to_string()),
+        min_files: Some(1),
+        max_files: Some(1),
+        ..PolicyRule::default()
+    };
+    assert!(file_count_violation(&rule, 1).is_none());
+
+    let under = file_count_violation(&rule, 0).expect("too few files");
+    assert_eq!(under.file, "docs/adr/ADR-0001-*.md");
+    assert_eq!(under.rule, "single-adr-0001");
+    assert_eq!(
+        under.message,
+        "Pattern matched 0 files, expected at least 1"
+    );
+
+    let over = file_count_violation(&rule, 2).expect("too many files");
+    assert_eq!(over.message, "Pattern matched 2 files, expected at most 1");
+
+    // Rules without bounds never report a count
+    let unbounded = PolicyRule {
+        pattern: "docs/*.md".to_string(),
+        ..PolicyRule::default()
+    };
+    assert!(file_count_violation(&unbounded, 0).is_none());
+}
+
 #[test]
 fn test_policy_required_and_forbidden_headings() {
     let rule = PolicyRule {
@@ -386,7 +526,7 @@ Some content here.
 ## Deprecated
 ";
 
-    let violations = collect_policy_violations_for_content(&rule, "docs/example.md", content);
+    let violations = policy_violations(&rule, "docs/example.md", content);
 
     // Should not flag missing Objective (it exists)
     assert!(
@@ -405,6 +545,39 @@ Some content here.
     );
 }
 
+#[test]
+fn test_policy_heading_depth_and_skipped_levels() {
+    let rule = PolicyRule {
+        pattern: "docs/*.md".to_string(),
+        max_heading_level: Some(5),
+        no_skipped_levels: true,
+        ..PolicyRule::default()
+    };
+    let content = "# Title\n\n### Jumped\n\n## Back\n\n```sh\n# not a heading\n```\n\n### Fine\n#### Deeper\n##### Deepest\n###### Too deep\n";
+
+    let messages: Vec<String> = policy_violations(&rule, "docs/guide.md", content)
+        .into_iter()
+        .map(|v| v.message)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "Heading \"Jumped\" at line 3 skips from H1 to H3",
+            "Heading \"Too deep\" at line 14 is H6 (max allowed: H5)",
+        ]
+    );
+
+    // Without the new fields the same document passes
+    let lenient = PolicyRule {
+        pattern: "docs/*.md".to_string(),
+        ..PolicyRule::default()
+    };
+    assert!(policy_violations(&lenient, "docs/guide.md", content).is_empty());
+    // YAML comments in frontmatter are not headings
+    let content = "---\ntitle: Guide\n###### generated file\n---\n# Title\n\n## Usage\n";
+    assert!(policy_violations(&rule, "docs/guide.md", content).is_empty());
+}
+
 #[test]
 fn test_policy_section_length_violation() {
     let rule = PolicyRule {
@@ -429,8 +602,7 @@ line4
 ok
 ";
 
-    let violations =
-        collect_policy_violations_for_content(&rule, "docs/IMPLEMENTATION_STATUS.md", content);
+    let violations = policy_violations(&rule, "docs/IMPLEMENTATION_STATUS.md", content);
 
     assert!(
         violations
@@ -454,8 +626,7 @@ fn test_policy_required_link() {
 # Status
 No links here.
 ";
-    let violations =
-        collect_policy_violations_for_content(&rule, "docs/IMPLEMENTATION_STATUS.md", missing_link);
+    let violations = policy_violations(&rule, "docs/IMPLEMENTATION_STATUS.md", missing_link);
     assert!(
         violations
             .iter()
@@ -467,8 +638,7 @@ No links here.
 # Status
 See [summary](ASYNC_MIGRATION_COMPLETE_SUMMARY.md).
 ";
-    let ok_violations =
-        collect_policy_violations_for_content(&rule, "docs/IMPLEMENTATION_STATUS.md", with_link);
+    let ok_violations = policy_violations(&rule, "docs/IMPLEMENTATION_STATUS.md", with_link);
     assert!(
         ok_violations.is_empty(),
         "Did not expect violations when required link is present"
@@ -487,18 +657,121 @@ fn test_suggest_new_link_target_same_dir() {
     assert_eq!(suggested.as_deref(), Some("auth.md"));
 }
 
+#[test]
+fn test_suggest_new_link_target_parent_dir() {
+    let mut available = HashSet::new();
+    available.insert("docs/auth.md".to_string());
+    available.insert("docs/guide/other.md".to_string());
+
+    // Candidate lives one level above the source's directory
+    let suggested = suggest_new_link_target("docs/guide/README.md", "auth.md", &available);
+    assert_eq!(suggested.as_deref(), Some("../auth.md"));
+}
+
+#[test]
+fn test_suggest_new_link_target_cross_dir() {
+    let mut available = HashSet::new();
+    available.insert("docs/security/auth.md".to_string());
+    available.insert("docs/guide/README.md".to_string());
+
+    // Candidate lives in a sibling directory of the source
+    let suggested = suggest_new_link_target("docs/guide/README.md", "old/auth.md", &available);
+    assert_eq!(suggested.as_deref(), Some("../security/auth.md"));
+
+    // Source at the repo root reaches down into the tree
+    let suggested = suggest_new_link_target("README.md", "auth.md", &available);
+    assert_eq!(suggested.as_deref(), Some("docs/security/auth.md"));
+}
+
+#[test]
+fn test_suggest_new_link_target_ambiguous_anywhere() {
+    let mut available = HashSet::new();
+    available.insert("docs/v1/auth.md".to_string());
+    available.insert("api/auth.md".to_string());
+
+    // Two candidates by basename: no suggestion, even across directories
+    let suggested = suggest_new_link_target("docs/guide/README.md", "auth.md", &available);
+    assert_eq!(suggested, None);
+}
+
 #[test]
 fn test_apply_reference_mapping_to_content() {
     let content = "See [auth](docs/old/auth.md) for details.";
-    let updated = apply_reference_mapping_to_content(
-        content,
+    let mapping = reference_mapping(
         "docs/old/auth.md",
         "docs/architecture/AUTH.md",
+        ReferenceMappingKind::Literal,
     );
+    let compiled = CompiledReferenceMapping::new(&mapping).unwrap();
+    let (updated, changed) = apply_reference_mapping_to_content(content, &compiled);
     assert_eq!(
         updated,
         "See [auth](docs/architecture/AUTH.md) for details."
     );
+    assert_eq!(changed, 1);
+}
+
+fn reference_mapping(from: &str, to: &str, kind: ReferenceMappingKind) -> ReferenceMapping {
+    ReferenceMapping {
+        from: from.to_string(),
+        to: to.to_string(),
+        kind,
+    }
+}
+
+#[test]
+fn test_apply_reference_mapping_prefix() {
+    let content = "\
+- [auth](docs/old/auth.md#tokens)
+- [pay](docs/old/billing/pay.md)
+- [keep](docs/older/x.md)
+
+[ref]: docs/old/ref.md
+";
+    let mapping = reference_mapping("docs/old/", "docs/new/", ReferenceMappingKind::Prefix);
+    let compiled = CompiledReferenceMapping::new(&mapping).unwrap();
+    let (updated, changed) = apply_reference_mapping_to_content(content, &compiled);
+
+    assert_eq!(changed, 3);
+    assert!(updated.contains("[auth](docs/new/auth.md#tokens)"));
+    assert!(updated.contains("[pay](docs/new/billing/pay.md)"));
+    assert!(updated.contains("[keep](docs/older/x.md)"));
+    assert!(updated.contains("[ref]: docs/new/ref.md"));
+}
+
+#[test]
+fn test_apply_reference_mapping_regex_capture_groups() {
+    let content = "[a](adr/0001-auth.md) [b](adr/0002-db.md#context) [c](guide.md)";
+    let mapping = reference_mapping(
+        r"^adr/(\d{4})-(?P<slug>[a-z-]+)\.md",
+        "decisions/ADR-$1-${slug}.md",
+        ReferenceMappingKind::Regex,
+    );
+    let compiled = CompiledReferenceMapping::new(&mapping).unwrap();
+    let (updated, changed) = apply_reference_mapping_to_content(content, &compiled);
+
+    assert_eq!(changed, 2);
+    assert_eq!(
+        updated,
+        "[a](decisions/ADR-0001-auth.md) [b](decisions/ADR-0002-db.md#context) [c](guide.md)"
+    );
+}
+
+#[test]
+fn test_reference_mapping_invalid_regex_errors() {
+    let mapping = reference_mapping("docs/(old", "docs/new", ReferenceMappingKind::Regex);
+    let err = CompiledReferenceMapping::new(&mapping).err().unwrap();
+    assert!(err.to_string().contains("Invalid regex mapping"));
+}
+
+#[test]
+fn test_reference_mapping_kind_defaults_to_literal() {
+    let cfg: ReferenceMappingConfig = serde_yaml::from_str(
+        "mappings:\n  - from: a.md\n    to: b.md\n  - from: docs/old/\n    to: docs/new/\n    kind: prefix\n",
+    )
+    .unwrap();
+    assert_eq!(cfg.mappings[0].kind, ReferenceMappingKind::Literal);
+    assert_eq!(cfg.mappings[1].kind, ReferenceMappingKind::Prefix);
 }
 
 #[test]
@@ -518,10 +791,16 @@ fn test_build_consolidation_groups_basic() {
             links: vec![],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: vec![],
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
     files.insert(
@@ -536,20 +815,32 @@ fn test_build_consolidation_groups_basic() {
             links: vec![],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: vec![],
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
 
     let forward_index = ForwardIndex {
         files,
-        indexed_at: chrono_now(),
+        indexed_at: rfc3339_now(),
         version: 3,
         source_root: String::new(),
         avg_doc_length: 0.0,
         idf_map: HashMap::new(),
+        stemmer: StemmerKind::Simple,
+        positions: false,
+        stop_words: StopWords::default(),
+        minhash_perms: DEFAULT_MINHASH_PERMS,
+        simhash_weighting: SimhashWeighting::Uniform,
+        build_scope: None,
     };
 
     let pairs = vec![("docs/a.md".to_string(), "docs/b.md".to_string(), 0.9_f64)];
@@ -578,13 +869,20 @@ fn test_compute_inbound_link_counts() {
                 line: 1,
                 text: "b".to_string(),
                 target: "b.md".to_string(),
+                wiki: None,
             }],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: vec![],
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
     files.insert(
@@ -599,10 +897,16 @@ fn test_compute_inbound_link_counts() {
             links: vec![],
             simhash: 0,
             term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
             doc_length: 0,
             minhash: vec![],
             section_fingerprints: vec![],
             adr_references: vec![],
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
         },
     );
 
@@ -613,6 +917,12 @@ fn test_compute_inbound_link_counts() {
         source_root: String::new(),
         avg_doc_length: 0.0,
         idf_map: HashMap::new(),
+        stemmer: StemmerKind::Simple,
+        positions: false,
+        stop_words: StopWords::default(),
+        minhash_perms: DEFAULT_MINHASH_PERMS,
+        simhash_weighting: SimhashWeighting::Uniform,
+        build_scope: None,
     };
 
     let counts = compute_inbound_link_counts(&forward_index);
@@ -628,16 +938,19 @@ fn test_index_sections() {
             line: 1,
             level: 1,
             text: "Introduction".to_string(),
+            anchor: None,
         },
         Heading {
             line: 4,
             level: 2,
             text: "Details".to_string(),
+            anchor: None,
         },
         Heading {
             line: 7,
             level: 2,
             text: "Summary".to_string(),
+            anchor: None,
         },
     ];
 
@@ -666,16 +979,19 @@ fn test_index_sections_similar_content() {
         line: 1,
         level: 2,
         text: "Testing".to_string(),
+        anchor: None,
     }];
     let headings2 = vec![Heading {
         line: 1,
         level: 2,
         text: "Testing".to_string(),
+        anchor: None,
     }];
     let headings3 = vec![Heading {
         line: 1,
         level: 2,
         text: "Testing".to_string(),
+        anchor: None,
     }];
 
     let sections1 = index_sections(content1, &headings1);
@@ -726,27 +1042,32 @@ fn main() {}
             line: 6,
             level: 1,
             text: "Overview".to_string(),
+            anchor: None,
         },
         Heading {
             line: 9,
             level: 2,
             text: "Part 1".to_string(),
+            anchor: None,
         },
         Heading {
             line: 13,
             level: 2,
             text: "Changelog".to_string(),
+            anchor: None,
         },
         Heading {
             line: 17,
             level: 2,
             text: "Completed Work".to_string(),
+            anchor: None,
         },
     ];
     let links = vec![Link {
         line: 7,
         text: "readme".to_string(),
         target: "README.md".to_string(),
+        wiki: None,
     }];
 
     let metrics = compute_document_metrics("docs/demo.md", content, &lines, &headings, &links);
@@ -791,7 +1112,16 @@ fn test_cmd_build_writes_document_metrics_index() {
     )
     .unwrap();
 
-    cmd_build(&docs_dir, &index_dir, "md", &[], true, None, false, false).unwrap();
+    cmd_build(
+        &docs_dir,
+        &index_dir,
+        &BuildOptions {
+            types: "md",
+            quiet: true,
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
 
     let metrics_path = index_dir.join("document_metrics.json");
     assert!(metrics_path.exists());
@@ -810,10 +1140,70 @@ fn test_cmd_build_writes_document_metrics_index() {
     fs::remove_dir_all(root).unwrap();
 }
 
+#[test]
+fn test_cmd_build_reverse_index_independent_of_jobs() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let root = std::env::temp_dir().join(format!("yore-build-jobs-{unique}"));
+    let docs_dir = root.join("docs");
+    fs::create_dir_all(&docs_dir).unwrap();
+    for i in 0..12 {
+        fs::write(
+            docs_dir.join(format!("doc{i}.md")),
+            format!("# Deploy {i}\n\nShared deployment notes for service {i}.\n"),
+        )
+        .unwrap();
+    }
+
+    let serial_dir = root.join("serial");
+    let parallel_dir = root.join("parallel");
+    cmd_build(
+        &docs_dir,
+        &serial_dir,
+        &BuildOptions {
+            types: "md",
+            quiet: true,
+            jobs: Some(1),
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+    cmd_build(
+        &docs_dir,
+        &parallel_dir,
+        &BuildOptions {
+            types: "md",
+            quiet: true,
+            jobs: Some(4),
+            ..BuildOptions::default()
+        },
+    )
+    .unwrap();
+
+    let serial = load_reverse_index(&serial_dir).unwrap();
+    let parallel = load_reverse_index(&parallel_dir).unwrap();
+    let deploy_files = |index: &ReverseIndex| -> Vec<String> {
+        index.keywords["deploy"]
+            .iter()
+            .map(|entry| entry.file.clone())
+            .collect()
+    };
+    assert_eq!(deploy_files(&serial), deploy_files(&parallel));
+    assert_eq!(serial.keywords.len(), parallel.keywords.len());
+
+    let forward =
+        load_forward_index(&IndexDir::new(&parallel_dir, IndexVersionCheck::Warn)).unwrap();
+    assert_eq!(forward.files.len(), 12);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
 #[test]
 fn test_extract_keywords() {
     let text = "This is a TEST document with some KEYWORDS";
-    let keywords = extract_keywords(text);
+    let keywords = extract_keywords(text, &StopWords::default());
 
     // Should lowercase (but not stem - extract_keywords doesn't stem)
     assert!(keywords.contains(&"test".to_string()));
@@ -841,826 +1231,1180 @@ fn test_stem_word() {
 }
 
 #[test]
-fn test_get_link_context_basic() {
-    let path = "test_get_link_context_basic.md";
-    fs::write(path, "first line\nsecond line with a link\nthird line\n").unwrap();
-
-    let mut cache: HashMap<String, Vec<String>> = HashMap::new();
-    let ctx = get_link_context(&mut cache, path, 2).unwrap();
-    assert_eq!(ctx.as_deref(), Some("second line with a link"));
-
-    // Out-of-range line number should yield None
-    let ctx_out = get_link_context(&mut cache, path, 10).unwrap();
-    assert!(ctx_out.is_none());
-
-    fs::remove_file(path).unwrap();
+fn test_parse_frontmatter_fields() {
+    let lines = vec![
+        "---",
+        "title: Deploy Guide",
+        "tags: [ops, runbook]",
+        "draft: true",
+        "---",
+        "# Deploy Guide",
+    ];
+    let (fields, end) = parse_frontmatter(&lines);
+    assert_eq!(end, 5);
+    assert_eq!(fields["title"], "Deploy Guide");
+    assert_eq!(fields["tags"], "ops, runbook");
+    assert_eq!(fields["draft"], "true");
+
+    let (none, end) = parse_frontmatter(&["# Title", "body"]);
+    assert!(none.is_empty());
+    assert_eq!(end, 0);
 }
 
 #[test]
-fn test_get_link_context_truncates_long_lines() {
-    let path = "test_get_link_context_truncate.md";
-    let long_line = "a".repeat(200);
-    fs::write(path, format!("{long_line}\n")).unwrap();
-
-    let mut cache: HashMap<String, Vec<String>> = HashMap::new();
-    let ctx = get_link_context(&mut cache, path, 1)
+fn test_index_file_captures_explicit_anchors() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
-        .expect("expected context");
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-anchors-{unique}.md"));
+    fs::write(
+        &path,
+        "# Guide\n\n## Install Steps {#install}\n\n<a id=\"faq\"></a>\nText.\n\n<a name=\"legacy\"></a>\nParagraph.\n{: #kramdown-id}\n\nWrite `{#notanchor}` or `<a id=\"shown\">` to add one.\n\n```bash\n<a id=\"in-code\"></a>\necho ${#PATH}\n```\n",
+    )
+    .unwrap();
 
-    assert!(ctx.len() <= 160);
-    assert!(ctx.ends_with("..."));
+    let (entry, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
 
-    fs::remove_file(path).unwrap();
+    let heading = &entry.headings[1];
+    assert_eq!(heading.text, "Install Steps");
+    assert_eq!(heading.anchor.as_deref(), Some("install"));
+    assert_eq!(entry.anchors, vec!["faq", "legacy", "kramdown-id"]);
 }
 
 #[test]
-fn test_get_top_doc_terms_basic() {
-    // Setup: doc with term frequencies and IDF map
-    // Note: term_frequencies and idf_map use STEMMED keys
-    // "docker" -> "dock", "nginx" -> "nginx", "helm" -> "helm"
-    let mut term_frequencies = HashMap::new();
-    term_frequencies.insert("dock".to_string(), 10); // stem of "docker"
-    term_frequencies.insert("nginx".to_string(), 5);
-    term_frequencies.insert("helm".to_string(), 3);
+fn test_index_file_excludes_fenced_code_interior() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-fence-{unique}.md"));
+    fs::write(
+        &path,
+        "# Pods\n\nList the running pods.\n\n```bash\n# inspect cluster\nkubectl get pods\n  helm status release\n```\n\nDone listing.\n",
+    )
+    .unwrap();
 
-    let entry = FileEntry {
-        path: "test.md".to_string(),
-        size_bytes: 100,
-        line_count: 10,
-        headings: vec![],
-        keywords: vec![],
-        body_keywords: vec![
-            "docker".to_string(),
-            "nginx".to_string(),
-            "helm".to_string(),
-            "container".to_string(), // not in tf, will be excluded
-        ],
-        links: vec![],
-        simhash: 0,
-        term_frequencies,
-        doc_length: 100,
-        minhash: vec![],
-        section_fingerprints: vec![],
-        adr_references: vec![],
-    };
+    let (entry, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
 
-    let mut idf_map = HashMap::new();
-    idf_map.insert("dock".to_string(), 2.0); // stemmed
-    idf_map.insert("nginx".to_string(), 1.5);
-    idf_map.insert("helm".to_string(), 3.0);
+    for token in ["kubectl", "helm", "inspect", "cluster", "bash"] {
+        let stemmed = stem_word(token);
+        assert!(!entry.term_frequencies.contains_key(&stemmed), "{token}");
+        assert!(!entry.body_keywords.contains(&stemmed), "{token}");
+    }
+    assert!(entry.term_frequencies.contains_key(&stem_word("listing")));
+    // A shell comment inside the block is not a heading.
+    assert_eq!(entry.headings.len(), 1);
 
-    // Test: get top 2 terms, excluding nothing
-    let terms = get_top_doc_terms(&entry, &idf_map, &[], 2);
+    assert_eq!(
+        code_line_mask("a\n```\nb\n```\n    c\nd".lines()),
+        [false, true, true, true, true, false]
+    );
+}
 
-    // docker: 10 * 2.0 = 20
-    // helm: 3 * 3.0 = 9
-    // nginx: 5 * 1.5 = 7.5
-    assert_eq!(terms.len(), 2);
-    assert_eq!(terms[0], "docker");
-    assert_eq!(terms[1], "helm");
+#[test]
+fn test_extract_code_terms_and_boost() {
+    let lines = [
+        "Call `FooService::start()` before `deploy_all`.",
+        "Plain prose mentions FooService too.",
+    ];
+    let terms = extract_code_terms(lines);
+    let expected: HashSet<String> = ["fooservice", "start", "deploy_all"]
+        .iter()
+        .map(|s| (*s).to_string())
+        .collect();
+    assert_eq!(terms, expected);
+
+    let mut doc = make_file_entry("docs/foo.md");
+    doc.code_terms = terms;
+    let query = vec!["fooservice".to_string(), "restart".to_string()];
+    assert_eq!(code_term_boost(&query, &doc, 0.0), 1.0);
+    assert!((code_term_boost(&query, &doc, 2.0) - 2.0).abs() < 1e-9);
+    // Code terms are not stemmed, so only exact identifiers match.
+    assert_eq!(code_term_boost(&["deploy".to_string()], &doc, 2.0), 1.0);
 }
 
 #[test]
-fn test_get_top_doc_terms_excludes_query_terms() {
-    // Note: term_frequencies and idf_map use STEMMED keys
-    let mut term_frequencies = HashMap::new();
-    term_frequencies.insert("kubernete".to_string(), 10); // stem of "kubernetes"
-    term_frequencies.insert("dock".to_string(), 5); // stem of "docker"
-    term_frequencies.insert("nginx".to_string(), 3);
+fn test_index_file_parses_asciidoc_headings_and_links() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-asciidoc-{unique}.adoc"));
+    fs::write(
+        &path,
+        "= Deploy Guide\n\n== Getting Started\n\nSee xref:ops/rollback.adoc#_steps[rollback] and link:https://example.com[].\nBack to <<_verify,checks>>, xref:_getting_started[], or <<_getting_started>>.\n\n----\n== not a heading\nkubectl apply\n----\n\n=== Verify\n\nCheck the pods.\n",
+    )
+    .unwrap();
 
-    let entry = FileEntry {
-        path: "test.md".to_string(),
-        size_bytes: 100,
-        line_count: 10,
-        headings: vec![],
-        keywords: vec![],
-        body_keywords: vec![
-            "kubernetes".to_string(),
-            "docker".to_string(),
-            "nginx".to_string(),
-        ],
-        links: vec![],
-        simhash: 0,
-        term_frequencies,
-        doc_length: 100,
-        minhash: vec![],
-        section_fingerprints: vec![],
-        adr_references: vec![],
-    };
+    let (entry, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
 
-    let mut idf_map = HashMap::new();
-    idf_map.insert("kubernete".to_string(), 2.0); // stemmed
-    idf_map.insert("dock".to_string(), 1.5); // stemmed
-    idf_map.insert("nginx".to_string(), 3.0);
+    let headings: Vec<(usize, &str)> = entry
+        .headings
+        .iter()
+        .map(|h| (h.level, h.text.as_str()))
+        .collect();
+    assert_eq!(
+        headings,
+        [(1, "Deploy Guide"), (2, "Getting Started"), (3, "Verify")]
+    );
+    assert_eq!(entry.section_fingerprints.len(), 3);
 
-    // Exclude "kubernetes" from results (different case, should still match after stemming)
-    let exclude = vec!["Kubernetes".to_string()];
-    let terms = get_top_doc_terms(&entry, &idf_map, &exclude, 3);
+    let links: Vec<(&str, &str)> = entry
+        .links
+        .iter()
+        .map(|l| (l.text.as_str(), l.target.as_str()))
+        .collect();
+    assert_eq!(
+        links,
+        [
+            ("rollback", "ops/rollback.adoc#_steps"),
+            ("https://example.com", "https://example.com"),
+            ("checks", "#_verify"),
+            ("_getting_started", "#_getting_started"),
+            ("_getting_started", "#_getting_started"),
+        ]
+    );
+    assert!(!entry.term_frequencies.contains_key("kubectl"));
 
-    assert_eq!(terms.len(), 2);
-    assert!(!terms.contains(&"kubernetes".to_string()));
-    assert_eq!(terms[0], "nginx"); // 3 * 3.0 = 9
-    assert_eq!(terms[1], "docker"); // 5 * 1.5 = 7.5
+    assert_eq!(asciidoc_section_id("Getting Started!"), "_getting_started");
 }
 
 #[test]
-fn test_get_top_doc_terms_deduplicates_stems() {
-    let mut term_frequencies = HashMap::new();
-    term_frequencies.insert("run".to_string(), 10); // stem of running, runs, run
-
-    let entry = FileEntry {
-        path: "test.md".to_string(),
-        size_bytes: 100,
-        line_count: 10,
-        headings: vec![],
-        keywords: vec![],
-        body_keywords: vec!["running".to_string(), "runs".to_string(), "run".to_string()],
-        links: vec![],
-        simhash: 0,
-        term_frequencies,
-        doc_length: 100,
-        minhash: vec![],
-        section_fingerprints: vec![],
-        adr_references: vec![],
-    };
-
-    let mut idf_map = HashMap::new();
-    idf_map.insert("run".to_string(), 1.0);
+fn test_index_file_parses_rst_headings() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-rst-{unique}.rst"));
+    fs::write(
+        &path,
+        "============\nDeploy Guide\n============\n\nInstall\n=======\n\nRun the installer.\nNot a heading\n-------------\n\nPrerequisites\n-------------\n\nExample::\n\n    Indented\n    ========\n\nTiny\n==\n\nRollback\n========\n\nVerify\n------\n",
+    )
+    .unwrap();
 
-    let terms = get_top_doc_terms(&entry, &idf_map, &[], 5);
+    let (entry, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
 
-    // Should only return one term (first occurrence), not all three
-    assert_eq!(terms.len(), 1);
+    let headings: Vec<(usize, usize, &str)> = entry
+        .headings
+        .iter()
+        .map(|h| (h.line, h.level, h.text.as_str()))
+        .collect();
+    assert_eq!(
+        headings,
+        [
+            (2, 1, "Deploy Guide"),
+            (5, 2, "Install"),
+            (12, 3, "Prerequisites"),
+            (23, 2, "Rollback"),
+            (26, 3, "Verify"),
+        ]
+    );
+    assert_eq!(entry.section_fingerprints.len(), 5);
 }
 
 #[test]
-fn test_get_top_doc_terms_zero_returns_empty() {
-    let entry = FileEntry {
-        path: "test.md".to_string(),
-        size_bytes: 100,
-        line_count: 10,
-        headings: vec![],
-        keywords: vec!["test".to_string()],
-        body_keywords: vec!["test".to_string()],
-        links: vec![],
-        simhash: 0,
-        term_frequencies: HashMap::new(),
-        doc_length: 100,
-        minhash: vec![],
-        section_fingerprints: vec![],
-        adr_references: vec![],
-    };
+fn test_index_file_parses_html_headings_and_links() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-html-{unique}.html"));
+    fs::write(
+        &path,
+        "<html>\n<head><style>.banner { color: red; }</style></head>\n<body>\n<h1 id=\"deploy\">Deploy &amp; Rollback</h1>\n<p>Read the <a href=\"ops/rollback.html#steps\">rollback\n  steps</a> or <a href=\"https://example.com\">upstream</a>.</p>\n<h2 class=\"sub\">Verify</h2>\n<pre>\n<h3>kubectl get pods</h3>\n</pre>\n<a href=\"mailto:ops@example.com\">mail</a> <a href='#deploy'></a>\n</body>\n</html>\n",
+    )
+    .unwrap();
 
-    let idf_map = HashMap::new();
-    let terms = get_top_doc_terms(&entry, &idf_map, &[], 0);
+    let (entry, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
 
-    assert!(terms.is_empty());
+    let headings: Vec<(usize, usize, &str, Option<&str>)> = entry
+        .headings
+        .iter()
+        .map(|h| (h.line, h.level, h.text.as_str(), h.anchor.as_deref()))
+        .collect();
+    assert_eq!(
+        headings,
+        [
+            (4, 1, "Deploy & Rollback", Some("deploy")),
+            (7, 2, "Verify", None),
+        ]
+    );
+
+    let links: Vec<(usize, &str, &str)> = entry
+        .links
+        .iter()
+        .map(|l| (l.line, l.text.as_str(), l.target.as_str()))
+        .collect();
+    assert_eq!(
+        links,
+        [
+            (5, "rollback steps", "ops/rollback.html#steps"),
+            (11, "#deploy", "#deploy"),
+        ]
+    );
+
+    assert!(entry.term_frequencies.contains_key("rollback"));
+    for markup in ["href", "banner", "color", "kubectl"] {
+        assert!(!entry.term_frequencies.contains_key(markup), "{markup}");
+    }
 }
 
 #[test]
-fn test_find_link_candidates_single_match() {
-    let mut available = HashSet::new();
-    available.insert("docs/guide/auth.md".to_string());
-    available.insert("docs/guide/other.md".to_string());
+fn test_custom_stop_words_excluded_from_keywords_and_term_frequencies() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("yore-stop-words-{unique}"));
+    fs::create_dir_all(&dir).unwrap();
+    let list = dir.join("stopwords.txt");
+    fs::write(&list, "Example\ndocs\n").unwrap();
+    let doc = dir.join("guide.md");
+    fs::write(
+        &doc,
+        "# Example Deployment\n\nThe docs walk over an example rollout.\n",
+    )
+    .unwrap();
 
-    // Source and target are in the same parent; filename matches exactly one file
-    let candidates = find_link_candidates("docs/guide/README.md", "auth.md", &available);
-    assert_eq!(candidates.len(), 1);
-    assert_eq!(candidates[0], "auth.md");
+    let merged = load_index_stop_words(Some(&list), "merge").unwrap();
+    assert!(merged.contains("example") && merged.contains("over"));
+    assert!(!merged.is_builtin());
+    let replaced = load_index_stop_words(Some(&list), "replace").unwrap();
+    assert!(replaced.contains("docs") && !replaced.contains("over"));
+    assert!(load_index_stop_words(Some(&list), "append").is_err());
+    assert!(load_index_stop_words(None, "merge").unwrap().is_builtin());
+
+    let options = IndexOptions {
+        stop_words: merged,
+        ..IndexOptions::default()
+    };
+    let (entry, _, _) = index_file(&doc, &options).unwrap();
+    let (plain, _, _) = index_file(&doc, &IndexOptions::default()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    for word in ["example", "doc"] {
+        assert!(!entry.keywords.contains(&word.to_string()), "{word}");
+        assert!(!entry.body_keywords.contains(&word.to_string()), "{word}");
+        assert!(!entry.term_frequencies.contains_key(word), "{word}");
+        assert!(plain.term_frequencies.contains_key(word), "{word}");
+    }
+    assert!(entry.keywords.contains(&stem_word("deployment")));
+    assert!(entry.term_frequencies.contains_key(&stem_word("rollout")));
 }
 
 #[test]
-fn test_find_link_candidates_multiple_matches() {
-    let mut available = HashSet::new();
-    available.insert("docs/v1/auth.md".to_string());
-    available.insert("docs/v2/auth.md".to_string());
-    available.insert("docs/archive/auth.md".to_string());
+fn test_keyword_display_keeps_most_frequent_spelling() {
+    let stop_words = StopWords::default();
+    let forms = keyword_display_forms(
+        [
+            "# RBAC for Kubernetes",
+            "Configure rbac roles. RBAC binds kubernetes users to Roles.",
+        ],
+        StemmerKind::Simple,
+        &stop_words,
+    );
+    assert_eq!(forms.get("rbac").map(String::as_str), Some("RBAC"));
+    // Tied spellings resolve to the alphabetically first one
+    assert_eq!(
+        forms.get(&stem_word("kubernetes")).map(String::as_str),
+        Some("Kubernetes")
+    );
+    assert_eq!(
+        forms.get(&stem_word("users")).map(String::as_str),
+        Some("users")
+    );
 
-    // Multiple files with same name - should return all
-    let candidates = find_link_candidates("docs/README.md", "auth.md", &available);
-    assert!(candidates.len() >= 2);
+    let mut a = make_file_entry("docs/a.md");
+    a.keywords = vec!["rbac".to_string()];
+    a.keyword_display = HashMap::from([("rbac".to_string(), "RBAC".to_string())]);
+    let mut b = make_file_entry("docs/b.md");
+    b.body_keywords = vec!["rbac".to_string()];
+    b.keyword_display = HashMap::from([("rbac".to_string(), "RBAC".to_string())]);
+    let mut c = make_file_entry("docs/c.md");
+    c.body_keywords = vec!["rbac".to_string()];
+    let index = make_forward_index(vec![a, b, c]);
+    let display = keyword_display_map(&index.files);
+    assert_eq!(display.get("rbac").map(String::as_str), Some("RBAC"));
 }
 
 #[test]
-fn test_find_link_candidates_no_match() {
-    let mut available = HashSet::new();
-    available.insert("docs/guide/other.md".to_string());
+fn test_index_file_excludes_frontmatter_from_keywords() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-frontmatter-{unique}.md"));
+    fs::write(
+        &path,
+        "---\ntitle: Rollout\nstatus: draft\n---\n# Rollout\n\nCanary deployment steps.\n",
+    )
+    .unwrap();
 
-    // No file matches
-    let candidates = find_link_candidates("docs/README.md", "nonexistent.md", &available);
-    assert!(candidates.is_empty());
+    let (entry, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(entry.frontmatter["status"], "draft");
+    assert!(!entry.term_frequencies.contains_key("draft"));
+    assert!(!entry.body_keywords.iter().any(|k| k == "draft"));
+    assert!(entry.term_frequencies.contains_key("canary"));
+    assert_eq!(entry.headings.len(), 1);
+
+    let tagged = FileEntry {
+        frontmatter: HashMap::from([("tags".to_string(), "Ops, runbook".to_string())]),
+        ..entry
+    };
+    assert!(entry_has_tags(&tagged, &["ops".to_string()]));
+    assert!(entry_has_tags(
+        &tagged,
+        &["runbook".to_string(), "OPS".to_string()]
+    ));
+    assert!(!entry_has_tags(&tagged, &["adr".to_string()]));
 }
 
 #[test]
-fn test_link_fix_proposal_serialization() {
-    let proposal = LinkFixProposal {
-        source: "docs/README.md".to_string(),
-        line: 42,
-        broken_target: "../old/auth.md".to_string(),
-        candidates: vec![
-            "../archive/auth.md".to_string(),
-            "../v2/auth.md".to_string(),
-        ],
-        decision: None,
+fn test_policy_require_frontmatter() {
+    let rule = PolicyRule {
+        pattern: "docs/*.md".to_string(),
+        require_frontmatter: vec!["title".to_string(), "owner".to_string()],
+        ..Default::default()
     };
 
-    let yaml = serde_yaml::to_string(&proposal).unwrap();
-    assert!(yaml.contains("source: docs/README.md"));
-    assert!(yaml.contains("line: 42"));
-    assert!(yaml.contains("broken_target:"));
-    assert!(yaml.contains("candidates:"));
+    let content = "---\ntitle: Plan\nowner: \n---\n# Plan\n";
+    let violations = policy_violations(&rule, "docs/plan.md", content);
+    assert_eq!(violations.len(), 1);
+    assert!(violations[0].message.contains("\"owner\""));
 
-    // Test deserialization
-    let parsed: LinkFixProposal = serde_yaml::from_str(&yaml).unwrap();
-    assert_eq!(parsed.source, "docs/README.md");
-    assert_eq!(parsed.line, 42);
-    assert_eq!(parsed.candidates.len(), 2);
+    let missing = policy_violations(&rule, "docs/plan.md", "# Plan\n");
+    assert_eq!(missing.len(), 2);
 }
 
 #[test]
-fn test_link_fix_proposal_with_decision() {
-    let yaml = r#"
-source: docs/README.md
-line: 42
-broken_target: "../old/auth.md"
-candidates:
-  - "../archive/auth.md"
-  - "../v2/auth.md"
-decision: 1
-"#;
-    let proposal: LinkFixProposal = serde_yaml::from_str(yaml).unwrap();
-    assert_eq!(proposal.decision, Some(1));
-    assert_eq!(proposal.candidates[1], "../v2/auth.md");
+fn test_stem_word_with_selected_stemmer() {
+    assert_eq!(stem_word_with("running", StemmerKind::Simple), "runn");
+    assert_eq!(stem_word_with("Running", StemmerKind::Porter), "run");
+    assert_eq!(
+        stem_word_with("connections", StemmerKind::Porter),
+        "connect"
+    );
+    assert_eq!(stem_word_with("Running", StemmerKind::None), "running");
+
+    assert_eq!(StemmerKind::from_name("PORTER"), Some(StemmerKind::Porter));
+    assert_eq!(StemmerKind::from_name("snowball"), None);
+    assert_eq!(StemmerKind::default().name(), "simple");
 }
 
 #[test]
-fn test_diff_result_serialization() {
-    let result = DiffResult {
-        file1: "docs/a.md".to_string(),
-        file2: "docs/b.md".to_string(),
-        similarity: DiffSimilarity {
-            combined: 0.75,
-            jaccard: 0.6,
-            simhash: 0.9,
+fn test_cmd_build_records_stemmer_in_forward_index() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let root = std::env::temp_dir().join(format!("yore-build-stemmer-{unique}"));
+    let docs_dir = root.join("docs");
+    let index_dir = root.join(".yore");
+    fs::create_dir_all(&docs_dir).unwrap();
+    fs::write(
+        docs_dir.join("runbook.md"),
+        "# Running services\n\nRunning the service requires connections.\n",
+    )
+    .unwrap();
+
+    cmd_build(
+        &docs_dir,
+        &index_dir,
+        &BuildOptions {
+            types: "md",
+            quiet: true,
+            stemmer: "porter",
+            ..BuildOptions::default()
         },
-        shared_keywords: vec!["auth".to_string(), "login".to_string()],
-        only_in_file1: vec!["oauth".to_string()],
-        only_in_file2: vec!["jwt".to_string()],
-        shared_headings: vec!["Introduction".to_string()],
-    };
+    )
+    .unwrap();
 
-    let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"file1\": \"docs/a.md\""));
-    assert!(json.contains("\"combined\": 0.75"));
-    assert!(json.contains("\"shared_keywords\""));
+    let index = load_forward_index(&IndexDir::new(&index_dir, IndexVersionCheck::Warn)).unwrap();
+    assert_eq!(index.stemmer, StemmerKind::Porter);
+    let entry = index.files.values().next().unwrap();
+    assert!(entry.term_frequencies.contains_key("run"));
+    assert!(entry.term_frequencies.contains_key("connect"));
+
+    let query = vec!["runs".to_string()];
+    let score = bm25_score_groups(
+        &expand_query_terms(&query, &Synonyms::default(), index.stemmer),
+        entry,
+        index.avg_doc_length,
+        &index.idf_map,
+        DEFAULT_HEADING_BOOST,
+    );
+    assert!(score > 0.0);
+
+    let err = cmd_build(
+        &docs_dir,
+        &index_dir,
+        &BuildOptions {
+            types: "md",
+            quiet: true,
+            stemmer: "lancaster",
+            ..BuildOptions::default()
+        },
+    );
+    assert!(err.is_err());
+
+    fs::remove_dir_all(root).unwrap();
 }
 
 #[test]
-fn test_stats_result_serialization() {
-    let result = StatsResult {
-        total_files: 100,
-        unique_keywords: 500,
-        total_headings: 250,
-        body_keywords: 1000,
-        total_links: 300,
-        index_version: 3,
-        indexed_at: "2024-01-01T00:00:00Z".to_string(),
-        top_keywords: vec![
-            KeywordCount {
-                keyword: "authentication".to_string(),
-                count: 50,
-            },
-            KeywordCount {
-                keyword: "kubernetes".to_string(),
-                count: 40,
-            },
-        ],
-    };
-
-    let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"total_files\": 100"));
-    assert!(json.contains("\"top_keywords\""));
-    assert!(json.contains("\"authentication\""));
+fn test_link_health_weights_missing_targets_over_unverified_anchors() {
+    let missing = HashMap::from([("doc_missing".to_string(), 2)]);
+    let unverified = HashMap::from([("anchor_unverified".to_string(), 2)]);
+
+    let health = link_health(10, 2, &missing);
+    assert_eq!(health.broken_ratio, 0.2);
+    assert_eq!(health.score, 80.0);
+    assert_eq!(health.by_kind.get("doc_missing"), Some(&2));
+    assert_eq!(link_health(10, 2, &unverified).score, 98.0);
+
+    // Informational kinds cost nothing, and an empty check is perfectly healthy
+    let info = HashMap::from([("code_reference".to_string(), 5)]);
+    assert_eq!(link_health(10, 0, &info).score, 100.0);
+    assert_eq!(link_health(0, 0, &HashMap::new()).score, 100.0);
+
+    // Duplicate headings are not links: one valid link stays fully healthy
+    let duplicates = HashMap::from([("anchor_duplicate".to_string(), 4)]);
+    let health = link_health(1, 0, &duplicates);
+    assert_eq!(health.score, 100.0);
+    assert_eq!(health.broken_ratio, 0.0);
+    assert!(health.by_kind.is_empty());
 }
 
 #[test]
-fn test_mv_result_serialization() {
-    let result = MvResult {
-        from: "docs/old.md".to_string(),
-        to: "docs/new.md".to_string(),
-        moved: true,
-        updated_files: vec!["docs/index.md".to_string(), "docs/guide.md".to_string()],
-    };
+fn test_stale_requirement_parse() {
+    assert_eq!(
+        StaleRequirement::parse("age AND inlinks").unwrap(),
+        StaleRequirement::default()
+    );
+    let any = StaleRequirement::parse("age or orphaned").unwrap();
+    assert!(any.any);
+    assert_eq!(
+        any.conditions,
+        [StaleCondition::Age, StaleCondition::Orphaned]
+    );
+    assert_eq!(any.describe(), "age OR orphaned");
+    assert_eq!(
+        StaleRequirement::parse("orphaned").unwrap().conditions,
+        [StaleCondition::Orphaned]
+    );
 
-    let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"from\": \"docs/old.md\""));
-    assert!(json.contains("\"moved\": true"));
-    assert!(json.contains("\"updated_files\""));
+    assert!(StaleRequirement::parse("").is_err());
+    assert!(StaleRequirement::parse("age AND").is_err());
+    assert!(StaleRequirement::parse("age XOR orphaned").is_err());
+    assert!(StaleRequirement::parse("age AND newest").is_err());
+    assert!(StaleRequirement::parse("age AND inlinks OR orphaned").is_err());
 }
 
 #[test]
-fn test_fix_references_result_serialization() {
-    let result = FixReferencesResult {
-        mapping_file: "mappings.yaml".to_string(),
-        mappings_count: 5,
-        updated_files: vec!["docs/a.md".to_string()],
-        applied: false,
-    };
+fn test_get_link_context_basic() {
+    let path = "test_get_link_context_basic.md";
+    fs::write(path, "first line\nsecond line with a link\nthird line\n").unwrap();
 
-    let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"mapping_file\": \"mappings.yaml\""));
-    assert!(json.contains("\"mappings_count\": 5"));
-    assert!(json.contains("\"applied\": false"));
+    let mut cache: HashMap<String, Vec<String>> = HashMap::new();
+    let ctx = get_link_context(&mut cache, path, 2, 0).unwrap();
+    assert_eq!(ctx.as_deref(), Some("second line with a link"));
+
+    // Out-of-range line number should yield None
+    let ctx_out = get_link_context(&mut cache, path, 10, 0).unwrap();
+    assert!(ctx_out.is_none());
+
+    fs::remove_file(path).unwrap();
 }
 
 #[test]
-fn test_yore_config_basic_parsing() {
-    let toml = r#"
-[index.docs]
-roots = ["docs/"]
-types = ["md"]
-output = ".yore"
-"#;
-    let config: YoreConfig = toml::from_str(toml).unwrap();
-    assert!(config.index.contains_key("docs"));
-    let docs = config.index.get("docs").unwrap();
-    assert_eq!(docs.roots, vec!["docs/"]);
-    assert_eq!(docs.types, vec!["md"]);
-}
+fn test_get_link_context_includes_surrounding_lines() {
+    let path = "test_get_link_context_surrounding.md";
+    fs::write(path, "one\ntwo\nthree [x](y.md)\nfour\nfive\n").unwrap();
 
-#[test]
-fn test_yore_config_link_check_section() {
-    let toml = r#"
-[link-check]
-exclude = ["archive/**", "deprecated/**"]
+    let mut cache: HashMap<String, Vec<String>> = HashMap::new();
+    let ctx = get_link_context(&mut cache, path, 3, 1).unwrap();
+    assert_eq!(ctx.as_deref(), Some("two\nthree [x](y.md)\nfour"));
 
-[[link-check.severity-overrides]]
-pattern = "archive/**"
-severity = "warn"
+    // Windows are clamped at the start and end of the file
+    let ctx = get_link_context(&mut cache, path, 1, 2).unwrap();
+    assert_eq!(ctx.as_deref(), Some("one\ntwo\nthree [x](y.md)"));
+    let ctx = get_link_context(&mut cache, path, 5, 10).unwrap();
+    assert_eq!(
+        ctx.as_deref(),
+        Some("one\ntwo\nthree [x](y.md)\nfour\nfive")
+    );
 
-[[link-check.severity-overrides]]
-pattern = "deprecated/**"
-severity = "info"
-"#;
-    let config: YoreConfig = toml::from_str(toml).unwrap();
-    let link_check = config.link_check.unwrap();
-    assert_eq!(link_check.exclude.len(), 2);
-    assert_eq!(link_check.severity_overrides.len(), 2);
-    assert_eq!(link_check.severity_overrides[0].pattern, "archive/**");
-    assert_eq!(link_check.severity_overrides[0].severity, "warn");
+    // The file is read once and served from the cache afterwards
+    fs::remove_file(path).unwrap();
+    let ctx = get_link_context(&mut cache, path, 4, 0).unwrap();
+    assert_eq!(ctx.as_deref(), Some("four"));
+    assert_eq!(cache.len(), 1);
 }
 
 #[test]
-fn test_yore_config_external_repos() {
-    let toml = r#"
-[[external.repos]]
-path = "../runtime/docs"
-prefix = "runtime"
+fn test_get_link_context_truncates_long_lines() {
+    let path = "test_get_link_context_truncate.md";
+    let long_line = "a".repeat(200);
+    fs::write(path, format!("{long_line}\n")).unwrap();
 
-[[external.repos]]
-path = "../api-docs"
-"#;
-    let config: YoreConfig = toml::from_str(toml).unwrap();
-    let external = config.external.unwrap();
-    assert_eq!(external.repos.len(), 2);
-    assert_eq!(external.repos[0].path, "../runtime/docs");
-    assert_eq!(external.repos[0].prefix, Some("runtime".to_string()));
-    assert_eq!(external.repos[1].prefix, None);
-}
+    let mut cache: HashMap<String, Vec<String>> = HashMap::new();
+    let ctx = get_link_context(&mut cache, path, 1, 0)
+        .unwrap()
+        .expect("expected context");
 
-#[test]
-fn test_yore_config_policy_section() {
-    let toml = r#"
-[policy]
-rules-file = ".yore-policy.yaml"
-"#;
-    let config: YoreConfig = toml::from_str(toml).unwrap();
-    let policy = config.policy.unwrap();
-    assert_eq!(policy.rules_file, Some(".yore-policy.yaml".to_string()));
+    assert!(ctx.len() <= 160);
+    assert!(ctx.ends_with("..."));
+
+    fs::remove_file(path).unwrap();
 }
 
 #[test]
-fn test_yore_config_full_example() {
-    let toml = r#"
-[index.docs]
-roots = ["docs/"]
-types = ["md", "txt"]
-output = ".yore"
+fn test_get_top_doc_terms_basic() {
+    // Setup: doc with term frequencies and IDF map
+    // Note: term_frequencies and idf_map use STEMMED keys
+    // "docker" -> "dock", "nginx" -> "nginx", "helm" -> "helm"
+    let mut term_frequencies = HashMap::new();
+    term_frequencies.insert("dock".to_string(), 10); // stem of "docker"
+    term_frequencies.insert("nginx".to_string(), 5);
+    term_frequencies.insert("helm".to_string(), 3);
 
-[index.all]
-roots = ["docs/", "specs/"]
-types = ["md"]
+    let entry = FileEntry {
+        path: "test.md".to_string(),
+        size_bytes: 100,
+        line_count: 10,
+        headings: vec![],
+        keywords: vec![],
+        body_keywords: vec![
+            "docker".to_string(),
+            "nginx".to_string(),
+            "helm".to_string(),
+            "container".to_string(), // not in tf, will be excluded
+        ],
+        links: vec![],
+        simhash: 0,
+        term_frequencies,
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 100,
+        minhash: vec![],
+        section_fingerprints: vec![],
+        adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
+    };
 
-[link-check]
-exclude = ["archive/**"]
+    let mut idf_map = HashMap::new();
+    idf_map.insert("dock".to_string(), 2.0); // stemmed
+    idf_map.insert("nginx".to_string(), 1.5);
+    idf_map.insert("helm".to_string(), 3.0);
 
-[[link-check.severity-overrides]]
-pattern = "deprecated/**"
-severity = "info"
+    // Test: get top 2 terms, excluding nothing
+    let terms = get_top_doc_terms(&entry, &idf_map, &[], 2, StemmerKind::Simple);
 
-[policy]
-rules-file = ".yore-policy.yaml"
+    // docker: 10 * 2.0 = 20
+    // helm: 3 * 3.0 = 9
+    // nginx: 5 * 1.5 = 7.5
+    assert_eq!(terms.len(), 2);
+    assert_eq!(terms[0], "docker");
+    assert_eq!(terms[1], "helm");
+}
 
-[[external.repos]]
-path = "../runtime/docs"
-prefix = "runtime"
-"#;
-    let config: YoreConfig = toml::from_str(toml).unwrap();
+#[test]
+fn test_get_top_doc_terms_excludes_query_terms() {
+    // Note: term_frequencies and idf_map use STEMMED keys
+    let mut term_frequencies = HashMap::new();
+    term_frequencies.insert("kubernete".to_string(), 10); // stem of "kubernetes"
+    term_frequencies.insert("dock".to_string(), 5); // stem of "docker"
+    term_frequencies.insert("nginx".to_string(), 3);
 
-    // Index profiles
-    assert_eq!(config.index.len(), 2);
-    assert!(config.index.contains_key("docs"));
-    assert!(config.index.contains_key("all"));
+    let entry = FileEntry {
+        path: "test.md".to_string(),
+        size_bytes: 100,
+        line_count: 10,
+        headings: vec![],
+        keywords: vec![],
+        body_keywords: vec![
+            "kubernetes".to_string(),
+            "docker".to_string(),
+            "nginx".to_string(),
+        ],
+        links: vec![],
+        simhash: 0,
+        term_frequencies,
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 100,
+        minhash: vec![],
+        section_fingerprints: vec![],
+        adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
+    };
 
-    // Link check
-    let link_check = config.link_check.unwrap();
-    assert_eq!(link_check.exclude.len(), 1);
-    assert_eq!(link_check.severity_overrides.len(), 1);
+    let mut idf_map = HashMap::new();
+    idf_map.insert("kubernete".to_string(), 2.0); // stemmed
+    idf_map.insert("dock".to_string(), 1.5); // stemmed
+    idf_map.insert("nginx".to_string(), 3.0);
 
-    // Policy
-    let policy = config.policy.unwrap();
-    assert!(policy.rules_file.is_some());
+    // Exclude "kubernetes" from results (different case, should still match after stemming)
+    let exclude = vec!["Kubernetes".to_string()];
+    let terms = get_top_doc_terms(&entry, &idf_map, &exclude, 3, StemmerKind::Simple);
 
-    // External
-    let external = config.external.unwrap();
-    assert_eq!(external.repos.len(), 1);
+    assert_eq!(terms.len(), 2);
+    assert!(!terms.contains(&"kubernetes".to_string()));
+    assert_eq!(terms[0], "nginx"); // 3 * 3.0 = 9
+    assert_eq!(terms[1], "docker"); // 5 * 1.5 = 7.5
 }
 
 #[test]
-fn test_yore_config_empty_is_valid() {
-    let toml = "";
-    let config: YoreConfig = toml::from_str(toml).unwrap();
-    assert!(config.index.is_empty());
-    assert!(config.link_check.is_none());
-    assert!(config.policy.is_none());
-    assert!(config.external.is_none());
-}
+fn test_get_top_doc_terms_deduplicates_stems() {
+    let mut term_frequencies = HashMap::new();
+    term_frequencies.insert("run".to_string(), 10); // stem of running, runs, run
 
-#[test]
-fn test_build_result_serialization() {
-    let result = BuildResult {
-        index_path: ".yore".to_string(),
-        files_indexed: 150,
-        total_headings: 450,
-        total_links: 200,
-        unique_keywords: 800,
-        duration_ms: 1234,
-        renames_tracked: None,
-        total_relations: None,
+    let entry = FileEntry {
+        path: "test.md".to_string(),
+        size_bytes: 100,
+        line_count: 10,
+        headings: vec![],
+        keywords: vec![],
+        body_keywords: vec!["running".to_string(), "runs".to_string(), "run".to_string()],
+        links: vec![],
+        simhash: 0,
+        term_frequencies,
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 100,
+        minhash: vec![],
+        section_fingerprints: vec![],
+        adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
     };
 
-    let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"index_path\": \".yore\""));
-    // renames_tracked should be absent when None due to skip_serializing_if
-    assert!(!json.contains("renames_tracked"));
-    assert!(json.contains("\"files_indexed\": 150"));
-    assert!(json.contains("\"total_headings\": 450"));
-    assert!(json.contains("\"total_links\": 200"));
-    assert!(json.contains("\"unique_keywords\": 800"));
-    assert!(json.contains("\"duration_ms\": 1234"));
+    let mut idf_map = HashMap::new();
+    idf_map.insert("run".to_string(), 1.0);
+
+    let terms = get_top_doc_terms(&entry, &idf_map, &[], 5, StemmerKind::Simple);
+
+    // Should only return one term (first occurrence), not all three
+    assert_eq!(terms.len(), 1);
 }
 
 #[test]
-fn test_eval_json_result_serialization() {
-    let result = EvalJsonResult {
-        questions_file: "questions.jsonl".to_string(),
-        total_questions: 10,
-        passed: 8,
-        failed: 2,
-        pass_rate: 80.0,
-        results: vec![
-            EvalQuestionResult {
-                question: "How do I authenticate?".to_string(),
-                passed: true,
-                expected: vec!["auth.md".to_string()],
-                found: vec!["auth.md".to_string()],
-                missing: vec![],
-                ranking: None,
+fn test_find_query_snippets_ranks_lines_by_stemmed_hits() {
+    let content = "# Deploy\n\nNothing here.\nDeploying is easy.\n\
+                   Deploy, then check the deployment.\nThe deploy step.\n";
+    let terms = vec!["deploy".to_string()];
+
+    let snippets = find_query_snippets(content, &terms, StemmerKind::Porter, 2);
+    // Line 5 has two hits; lines 1, 4 and 6 tie on one, so the earliest wins.
+    assert_eq!(
+        snippets,
+        vec![
+            QuerySnippet {
+                line: 1,
+                text: "# Deploy".to_string(),
+                hits: 1,
             },
-            EvalQuestionResult {
-                question: "What is the API endpoint?".to_string(),
-                passed: false,
-                expected: vec!["api.md".to_string()],
-                found: vec![],
-                missing: vec!["api.md".to_string()],
-                ranking: Some(RankingMetrics {
-                    precision_at_k: vec![MetricAtK { k: 5, value: 0.4 }],
-                    recall_at_k: vec![MetricAtK { k: 5, value: 1.0 }],
-                    mrr: 0.5,
-                    ndcg_at_k: vec![MetricAtK { k: 5, value: 0.8 }],
-                }),
+            QuerySnippet {
+                line: 5,
+                text: "Deploy, then check the deployment.".to_string(),
+                hits: 2,
             },
-        ],
-        ranking_metrics: None,
-    };
+        ]
+    );
 
-    let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"questions_file\": \"questions.jsonl\""));
-    assert!(json.contains("\"total_questions\": 10"));
-    assert!(json.contains("\"passed\": 8"));
-    assert!(json.contains("\"failed\": 2"));
-    assert!(json.contains("\"pass_rate\": 80.0"));
-    assert!(json.contains("\"results\""));
-    assert!(json.contains("How do I authenticate?"));
-    assert!(json.contains("\"missing\": []"));
-    // ranking is omitted when None
-    assert!(!json.contains("\"ranking\":\n") || json.contains("\"ranking\":"));
-    // ranking_metrics omitted at top level when None
-    assert!(!json.contains("\"ranking_metrics\""));
-    // The second question has ranking data
-    assert!(json.contains("\"mrr\": 0.5"));
-    assert!(json.contains("\"precision_at_k\""));
+    assert!(find_query_snippets(content, &[], StemmerKind::Porter, 2).is_empty());
 }
 
 #[test]
-fn test_unique_doc_ranking() {
-    let sections = vec![
-        SectionMatch {
-            doc_path: "docs/a.md".to_string(),
-            heading: "H1".to_string(),
-            line_start: 1,
-            line_end: 10,
-            bm25_score: 5.0,
-            content: "content a".to_string(),
-            canonicality: 0.5,
-        },
-        SectionMatch {
-            doc_path: "docs/b.md".to_string(),
-            heading: "H2".to_string(),
-            line_start: 1,
-            line_end: 10,
-            bm25_score: 4.0,
-            content: "content b".to_string(),
-            canonicality: 0.5,
-        },
-        SectionMatch {
-            doc_path: "docs/a.md".to_string(),
-            heading: "H3".to_string(),
-            line_start: 11,
-            line_end: 20,
-            bm25_score: 3.0,
-            content: "content a2".to_string(),
-            canonicality: 0.5,
-        },
-        SectionMatch {
-            doc_path: "docs/c.md".to_string(),
-            heading: "H4".to_string(),
-            line_start: 1,
-            line_end: 10,
-            bm25_score: 2.0,
-            content: "content c".to_string(),
-            canonicality: 0.5,
-        },
-    ];
-    let ranking = unique_doc_ranking(&sections);
-    assert_eq!(ranking, vec!["docs/a.md", "docs/b.md", "docs/c.md"]);
-}
+fn test_get_top_doc_terms_zero_returns_empty() {
+    let entry = FileEntry {
+        path: "test.md".to_string(),
+        size_bytes: 100,
+        line_count: 10,
+        headings: vec![],
+        keywords: vec!["test".to_string()],
+        body_keywords: vec!["test".to_string()],
+        links: vec![],
+        simhash: 0,
+        term_frequencies: HashMap::new(),
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 100,
+        minhash: vec![],
+        section_fingerprints: vec![],
+        adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
+    };
 
-#[test]
-fn test_precision_at_k() {
-    let ranked = vec![
-        "a.md".to_string(),
-        "b.md".to_string(),
-        "c.md".to_string(),
-        "d.md".to_string(),
-        "e.md".to_string(),
-    ];
-    let relevant: HashSet<String> = ["a.md", "c.md"].iter().map(|s| s.to_string()).collect();
+    let idf_map = HashMap::new();
+    let terms = get_top_doc_terms(&entry, &idf_map, &[], 0, StemmerKind::Simple);
 
-    assert_eq!(precision_at_k(&ranked, &relevant, 5), 0.4);
-    assert_eq!(precision_at_k(&ranked, &relevant, 1), 1.0);
-    assert_eq!(precision_at_k(&ranked, &relevant, 2), 0.5);
-    assert_eq!(precision_at_k(&ranked, &relevant, 0), 0.0);
+    assert!(terms.is_empty());
 }
 
 #[test]
-fn test_recall_at_k() {
-    let ranked = vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()];
-    let relevant: HashSet<String> = ["a.md", "c.md"].iter().map(|s| s.to_string()).collect();
-
-    assert_eq!(recall_at_k(&ranked, &relevant, 3), 1.0);
-    assert_eq!(recall_at_k(&ranked, &relevant, 1), 0.5);
-    assert_eq!(recall_at_k(&ranked, &relevant, 0), 0.0);
+fn test_find_link_candidates_single_match() {
+    let mut available = HashSet::new();
+    available.insert("docs/guide/auth.md".to_string());
+    available.insert("docs/guide/other.md".to_string());
 
-    // Empty relevant set
-    let empty: HashSet<String> = HashSet::new();
-    assert_eq!(recall_at_k(&ranked, &empty, 3), 0.0);
+    // Source and target are in the same parent; filename matches exactly one file
+    let candidates = find_link_candidates("docs/guide/README.md", "auth.md", &available);
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0], "auth.md");
 }
 
 #[test]
-fn test_reciprocal_rank() {
-    let ranked = vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()];
-
-    // Relevant at rank 1
-    let rel1: HashSet<String> = ["a.md"].iter().map(|s| s.to_string()).collect();
-    assert_eq!(reciprocal_rank(&ranked, &rel1), 1.0);
-
-    // Relevant at rank 2
-    let rel2: HashSet<String> = ["b.md"].iter().map(|s| s.to_string()).collect();
-    assert_eq!(reciprocal_rank(&ranked, &rel2), 0.5);
+fn test_find_link_candidates_multiple_matches() {
+    let mut available = HashSet::new();
+    available.insert("docs/v1/auth.md".to_string());
+    available.insert("docs/v2/auth.md".to_string());
+    available.insert("docs/archive/auth.md".to_string());
 
-    // No match
-    let no_match: HashSet<String> = ["z.md"].iter().map(|s| s.to_string()).collect();
-    assert_eq!(reciprocal_rank(&ranked, &no_match), 0.0);
+    // Multiple files with same name - should return all
+    let candidates = find_link_candidates("docs/README.md", "auth.md", &available);
+    assert!(candidates.len() >= 2);
 }
 
 #[test]
-fn test_ndcg_at_k() {
-    // Perfect ranking: both relevant docs at positions 0 and 1
-    let perfect = vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()];
-    let relevant: HashSet<String> = ["a.md", "b.md"].iter().map(|s| s.to_string()).collect();
-    assert_eq!(ndcg_at_k(&perfect, &relevant, 3), 1.0);
-
-    // Bad ranking: relevant docs at end
-    let bad = vec![
-        "c.md".to_string(),
-        "d.md".to_string(),
-        "a.md".to_string(),
-        "b.md".to_string(),
-    ];
-    let ndcg = ndcg_at_k(&bad, &relevant, 4);
-    assert!(ndcg < 1.0, "bad ranking should have nDCG < 1.0, got {ndcg}");
-    assert!(ndcg > 0.0, "bad ranking should have nDCG > 0.0, got {ndcg}");
+fn test_find_link_candidates_no_match() {
+    let mut available = HashSet::new();
+    available.insert("docs/guide/other.md".to_string());
 
-    // No relevant docs
-    let empty: HashSet<String> = HashSet::new();
-    assert_eq!(ndcg_at_k(&perfect, &empty, 3), 0.0);
+    // No file matches
+    let candidates = find_link_candidates("docs/README.md", "nonexistent.md", &available);
+    assert!(candidates.is_empty());
 }
 
 #[test]
-fn test_compute_ranking_metrics() {
-    let ranked = vec![
-        "a.md".to_string(),
-        "b.md".to_string(),
-        "c.md".to_string(),
-        "d.md".to_string(),
-        "e.md".to_string(),
+fn test_resolve_canonical_candidate_prefers_highest_score() {
+    let forward_index = make_forward_index(vec![
+        make_file_entry("docs/archive/auth.md"),
+        make_file_entry("docs/adr/auth.md"),
+        make_file_entry("docs/auth.md"),
+    ]);
+    let candidates = vec![
+        "docs/adr/auth.md".to_string(),
+        "docs/archive/auth.md".to_string(),
+        "docs/auth.md".to_string(),
     ];
-    let relevant: HashSet<String> = ["a.md", "c.md"].iter().map(|s| s.to_string()).collect();
-
-    let metrics = compute_ranking_metrics(&ranked, &relevant, &[3, 5]);
-    assert_eq!(metrics.precision_at_k.len(), 2);
-    assert_eq!(metrics.recall_at_k.len(), 2);
-    assert_eq!(metrics.ndcg_at_k.len(), 2);
-    assert_eq!(metrics.mrr, 1.0); // first relevant at rank 1
 
-    // P@3 = 2/3
-    assert!((metrics.precision_at_k[0].value - 2.0 / 3.0).abs() < 1e-9);
-    assert_eq!(metrics.precision_at_k[0].k, 3);
-
-    // P@5 = 2/5
-    assert!((metrics.precision_at_k[1].value - 0.4).abs() < 1e-9);
+    let resolution = resolve_canonical_candidate(&candidates, &forward_index).unwrap();
+    assert_eq!(resolution.chosen, "docs/adr/auth.md");
+    assert!(!resolution.reasons.is_empty());
+    let rejected: Vec<&str> = resolution
+        .rejected
+        .iter()
+        .map(|r| r.path.as_str())
+        .collect();
+    assert_eq!(rejected, vec!["docs/auth.md", "docs/archive/auth.md"]);
+    assert!(resolution
+        .rejected
+        .iter()
+        .all(|r| r.score <= resolution.score));
 }
 
 #[test]
-fn test_aggregate_ranking_metrics() {
-    let m1 = RankingMetrics {
-        precision_at_k: vec![MetricAtK { k: 5, value: 0.4 }],
-        recall_at_k: vec![MetricAtK { k: 5, value: 1.0 }],
-        mrr: 1.0,
-        ndcg_at_k: vec![MetricAtK { k: 5, value: 0.8 }],
-    };
-    let m2 = RankingMetrics {
-        precision_at_k: vec![MetricAtK { k: 5, value: 0.2 }],
-        recall_at_k: vec![MetricAtK { k: 5, value: 0.5 }],
-        mrr: 0.5,
-        ndcg_at_k: vec![MetricAtK { k: 5, value: 0.6 }],
-    };
+fn test_resolve_canonical_candidate_ties_prefer_shortest_path() {
+    let forward_index = make_forward_index(vec![
+        make_file_entry("docs/guides/setup/auth.md"),
+        make_file_entry("docs/api/auth.md"),
+        make_file_entry("docs/ops/auth.md"),
+    ]);
+    let candidates = vec![
+        "docs/api/auth.md".to_string(),
+        "docs/guides/setup/auth.md".to_string(),
+        "docs/ops/auth.md".to_string(),
+    ];
 
-    let agg = aggregate_ranking_metrics(&[m1, m2], &[5]);
-    assert_eq!(agg.questions_with_relevance, 2);
-    assert!((agg.mean_mrr - 0.75).abs() < 1e-9);
-    assert!((agg.mean_precision_at_k[0].value - 0.3).abs() < 1e-9);
-    assert!((agg.mean_recall_at_k[0].value - 0.75).abs() < 1e-9);
-    assert!((agg.mean_ndcg_at_k[0].value - 0.7).abs() < 1e-9);
+    let resolution = resolve_canonical_candidate(&candidates, &forward_index).unwrap();
+    // All score the baseline; shortest path wins, then path order
+    assert_eq!(resolution.chosen, "docs/api/auth.md");
+    let rejected: Vec<&str> = resolution
+        .rejected
+        .iter()
+        .map(|r| r.path.as_str())
+        .collect();
+    assert_eq!(
+        rejected,
+        vec!["docs/ops/auth.md", "docs/guides/setup/auth.md"]
+    );
 }
 
 #[test]
-fn test_rename_history_serialization() {
-    let history = RenameHistory {
-        renames: vec![
-            RenameEntry {
-                old_path: "docs/old/auth.md".to_string(),
-                new_path: "docs/v2/auth.md".to_string(),
-                commit: "abc123".to_string(),
-            },
-            RenameEntry {
-                old_path: "docs/v2/auth.md".to_string(),
-                new_path: "docs/current/auth.md".to_string(),
-                commit: "def456".to_string(),
-            },
+fn test_link_fix_proposal_serialization() {
+    let proposal = LinkFixProposal {
+        source: "docs/README.md".to_string(),
+        line: 42,
+        broken_target: "../old/auth.md".to_string(),
+        candidates: vec![
+            "../archive/auth.md".to_string(),
+            "../v2/auth.md".to_string(),
         ],
-        indexed_at: "1234567890".to_string(),
+        decision: None,
     };
 
-    let json = serde_json::to_string_pretty(&history).unwrap();
-    assert!(json.contains("\"old_path\": \"docs/old/auth.md\""));
-    assert!(json.contains("\"new_path\": \"docs/v2/auth.md\""));
-    assert!(json.contains("\"commit\": \"abc123\""));
+    let yaml = serde_yaml::to_string(&proposal).unwrap();
+    assert!(yaml.contains("source: docs/README.md"));
+    assert!(yaml.contains("line: 42"));
+    assert!(yaml.contains("broken_target:"));
+    assert!(yaml.contains("candidates:"));
 
-    // Verify roundtrip
-    let parsed: RenameHistory = serde_json::from_str(&json).unwrap();
-    assert_eq!(parsed.renames.len(), 2);
+    // Test deserialization
+    let parsed: LinkFixProposal = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(parsed.source, "docs/README.md");
+    assert_eq!(parsed.line, 42);
+    assert_eq!(parsed.candidates.len(), 2);
 }
 
 #[test]
-fn test_resolve_renamed_path_single_rename() {
-    let history = RenameHistory {
-        renames: vec![RenameEntry {
-            old_path: "docs/old.md".to_string(),
-            new_path: "docs/new.md".to_string(),
-            commit: "abc123".to_string(),
-        }],
-        indexed_at: "0".to_string(),
+fn test_link_fix_proposal_with_decision() {
+    let yaml = r#"
+source: docs/README.md
+line: 42
+broken_target: "../old/auth.md"
+candidates:
+  - "../archive/auth.md"
+  - "../v2/auth.md"
+decision: 1
+"#;
+    let proposal: LinkFixProposal = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(proposal.decision, Some(1));
+    assert_eq!(proposal.candidates[1], "../v2/auth.md");
+}
+
+#[test]
+fn test_diff_result_serialization() {
+    let result = DiffResult {
+        file1: "docs/a.md".to_string(),
+        file2: "docs/b.md".to_string(),
+        similarity: DiffSimilarity {
+            combined: 0.75,
+            jaccard: 0.6,
+            simhash: 0.9,
+        },
+        shared_keywords: vec!["auth".to_string(), "login".to_string()],
+        only_in_file1: vec!["oauth".to_string()],
+        only_in_file2: vec!["jwt".to_string()],
+        shared_headings: vec!["Introduction".to_string()],
+        keyword_display: std::collections::BTreeMap::from([(
+            "oauth".to_string(),
+            "OAuth".to_string(),
+        )]),
+        sections: DiffSectionAlignment::default(),
     };
 
-    assert_eq!(
-        resolve_renamed_path("docs/old.md", &history),
-        Some("docs/new.md".to_string())
-    );
-    assert_eq!(resolve_renamed_path("docs/other.md", &history), None);
+    let json = serde_json::to_string_pretty(&result).unwrap();
+    assert!(json.contains("\"file1\": \"docs/a.md\""));
+    assert!(json.contains("\"combined\": 0.75"));
+    assert!(json.contains("\"shared_keywords\""));
+    assert!(json.contains("\"oauth\": \"OAuth\""));
+    assert!(json.contains("\"only_in_file1\": []"));
 }
 
 #[test]
-fn test_resolve_renamed_path_chain() {
-    let history = RenameHistory {
-        renames: vec![
-            RenameEntry {
-                old_path: "a.md".to_string(),
-                new_path: "b.md".to_string(),
-                commit: "1".to_string(),
-            },
-            RenameEntry {
-                old_path: "b.md".to_string(),
-                new_path: "c.md".to_string(),
-                commit: "2".to_string(),
+fn test_stats_result_serialization() {
+    let result = StatsResult {
+        total_files: 100,
+        unique_keywords: 500,
+        total_headings: 250,
+        body_keywords: 1000,
+        total_links: 300,
+        index_version: 3,
+        indexed_at: "2024-01-01T00:00:00Z".to_string(),
+        distribution: StatsDistribution::default(),
+        top_keywords: vec![
+            KeywordCount {
+                keyword: "authentication".to_string(),
+                display: "authentication".to_string(),
+                count: 50,
             },
-            RenameEntry {
-                old_path: "c.md".to_string(),
-                new_path: "d.md".to_string(),
-                commit: "3".to_string(),
+            KeywordCount {
+                keyword: "kubernetes".to_string(),
+                display: "Kubernetes".to_string(),
+                count: 40,
             },
         ],
-        indexed_at: "0".to_string(),
     };
 
-    // Should follow the chain from a.md -> b.md -> c.md -> d.md
+    let json = serde_json::to_string_pretty(&result).unwrap();
+    assert!(json.contains("\"total_files\": 100"));
+    assert!(json.contains("\"top_keywords\""));
+    assert!(json.contains("\"authentication\""));
+}
+
+#[test]
+fn test_mv_result_serialization() {
+    let result = MvResult {
+        from: "docs/old.md".to_string(),
+        to: "docs/new.md".to_string(),
+        moved: true,
+        updated_files: vec!["docs/index.md".to_string(), "docs/guide.md".to_string()],
+        updated_links: Vec::new(),
+        outbound_links: Vec::new(),
+    };
+
+    let json = serde_json::to_string_pretty(&result).unwrap();
+    assert!(json.contains("\"from\": \"docs/old.md\""));
+    assert!(json.contains("\"moved\": true"));
+    assert!(json.contains("\"updated_files\""));
+}
+
+fn make_linking_entry(path: &str, targets: &[&str]) -> FileEntry {
+    let mut entry = make_file_entry(path);
+    entry.links = targets
+        .iter()
+        .map(|target| Link {
+            line: 1,
+            text: String::new(),
+            target: (*target).to_string(),
+            wiki: None,
+        })
+        .collect();
+    entry
+}
+
+fn planned_targets(
+    rewrites: &std::collections::BTreeMap<String, Vec<LinkFix>>,
+    file: &str,
+) -> Vec<(String, String)> {
+    rewrites
+        .get(file)
+        .map(|fixes| {
+            fixes
+                .iter()
+                .map(|f| (f.old_target.clone(), f.new_target.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_plan_inbound_link_rewrites_sibling_child_parent() {
+    let forward_index = make_forward_index(vec![
+        make_file_entry("docs/old/auth.md"),
+        // Sibling of the moved file
+        make_linking_entry("docs/old/login.md", &["auth.md", "./auth.md#tokens"]),
+        // Parent directory linking down into a child path
+        make_linking_entry("docs/README.md", &["old/auth.md"]),
+        // Nested directory linking up through a parent
+        make_linking_entry("docs/guides/setup/intro.md", &["../../old/auth.md"]),
+        // Unrelated links are left alone
+        make_linking_entry(
+            "docs/other.md",
+            &["old/login.md", "https://example.com/auth.md"],
+        ),
+    ]);
+
+    let rewrites =
+        plan_inbound_link_rewrites(&forward_index, "docs/old/auth.md", "docs/security/auth.md");
+
     assert_eq!(
-        resolve_renamed_path("a.md", &history),
-        Some("d.md".to_string())
+        planned_targets(&rewrites, "docs/old/login.md"),
+        vec![
+            ("auth.md".to_string(), "../security/auth.md".to_string()),
+            (
+                "./auth.md#tokens".to_string(),
+                "../security/auth.md#tokens".to_string()
+            ),
+        ]
     );
-    // Starting from middle should also work
     assert_eq!(
-        resolve_renamed_path("b.md", &history),
-        Some("d.md".to_string())
+        planned_targets(&rewrites, "docs/README.md"),
+        vec![("old/auth.md".to_string(), "security/auth.md".to_string())]
+    );
+    assert_eq!(
+        planned_targets(&rewrites, "docs/guides/setup/intro.md"),
+        vec![(
+            "../../old/auth.md".to_string(),
+            "../../security/auth.md".to_string()
+        )]
     );
+    assert!(!rewrites.contains_key("docs/other.md"));
 }
 
 #[test]
-fn test_compute_relative_path_same_dir() {
-    let files: HashSet<String> = HashSet::new();
+fn test_plan_outbound_link_rewrites_keeps_targets_after_move() {
+    let content = "\
+# Auth
+
+See [login](login.md#flow), [overview](../README.md), and [setup](guides/setup.md).
+
+![diagram](img/auth.png)
+
+Jump to [tokens](#tokens), [self](auth.md#tokens), [site](https://example.com/a.md),
+[mail](mailto:docs@example.com), [root](/docs/README.md), and [[Glossary]].
+
+Full details live in the [spec].
+
+[spec]: ../specs/auth.md
+";
+
+    let rewrites =
+        plan_outbound_link_rewrites(content, "docs/old/auth.md", "docs/security/v2/auth.md");
+    let pairs: Vec<(&str, &str)> = rewrites
+        .iter()
+        .map(|f| (f.old_target.as_str(), f.new_target.as_str()))
+        .collect();
+
     assert_eq!(
-        compute_relative_path("docs/foo.md", "docs/bar.md", &files),
-        Some("bar.md".to_string())
+        pairs,
+        vec![
+            ("login.md#flow", "../../old/login.md#flow"),
+            ("../README.md", "../../README.md"),
+            ("guides/setup.md", "../../old/guides/setup.md"),
+            ("img/auth.png", "../../old/img/auth.png"),
+            ("../specs/auth.md", "../../specs/auth.md"),
+        ]
     );
+    assert!(rewrites
+        .iter()
+        .all(|f| f.file == "docs/security/v2/auth.md"));
 }
 
 #[test]
-fn test_compute_relative_path_subdirectory() {
-    let files: HashSet<String> = HashSet::new();
+fn test_apply_link_rewrites_to_content_single_pass() {
+    let content = "[a](../b/y.md) and [b](y.md)\n\n[ref]: y.md\n";
+    let fix = |old: &str, new: &str| LinkFix {
+        file: "docs/b/x.md".to_string(),
+        old_target: old.to_string(),
+        new_target: new.to_string(),
+        resolution: None,
+    };
+    let rewrites = vec![fix("../b/y.md", "y.md"), fix("y.md", "../a/y.md")];
+
+    // A new target equal to another old target is not rewritten again
+    let updated = apply_link_rewrites_to_content(content, &rewrites);
     assert_eq!(
-        compute_relative_path("docs/index.md", "docs/guides/auth.md", &files),
-        Some("guides/auth.md".to_string())
+        updated,
+        "[a](y.md) and [b](../a/y.md)\n\n[ref]: ../a/y.md\n"
     );
 }
 
 #[test]
-fn test_compute_relative_path_parent_directory() {
-    let files: HashSet<String> = HashSet::new();
-    let result = compute_relative_path("docs/guides/auth.md", "docs/index.md", &files);
-    assert!(result.is_some());
-    assert!(result.unwrap().starts_with("../"));
+fn test_plan_inbound_link_rewrites_move_into_child_dir() {
+    let forward_index = make_forward_index(vec![
+        make_file_entry("docs/auth.md"),
+        make_linking_entry("docs/README.md", &["auth.md"]),
+        make_linking_entry("docs/guides/intro.md", &["../auth.md"]),
+    ]);
+
+    let rewrites = plan_inbound_link_rewrites(
+        &forward_index,
+        "./docs/auth.md",
+        "docs/guides/security/auth.md",
+    );
+
+    assert_eq!(
+        planned_targets(&rewrites, "docs/README.md"),
+        vec![("auth.md".to_string(), "guides/security/auth.md".to_string())]
+    );
+    assert_eq!(
+        planned_targets(&rewrites, "docs/guides/intro.md"),
+        vec![("../auth.md".to_string(), "security/auth.md".to_string())]
+    );
 }
 
 #[test]
-fn test_build_result_with_renames() {
-    let result = BuildResult {
-        index_path: ".yore".to_string(),
-        files_indexed: 100,
-        total_headings: 200,
-        total_links: 50,
-        unique_keywords: 500,
-        duration_ms: 1000,
-        renames_tracked: Some(25),
-        total_relations: None,
+fn test_fix_references_result_serialization() {
+    let result = FixReferencesResult {
+        mapping_file: "mappings.yaml".to_string(),
+        mappings_count: 5,
+        updated_files: vec!["docs/a.md".to_string()],
+        file_changes: vec![ReferenceFileChanges {
+            file: "docs/a.md".to_string(),
+            mappings: vec![ReferenceMappingCount {
+                from: "docs/old/".to_string(),
+                to: "docs/new/".to_string(),
+                kind: ReferenceMappingKind::Prefix,
+                links: 2,
+            }],
+        }],
+        applied: false,
     };
 
     let json = serde_json::to_string_pretty(&result).unwrap();
-    assert!(json.contains("\"renames_tracked\": 25"));
+    assert!(json.contains("\"mapping_file\": \"mappings.yaml\""));
+    assert!(json.contains("\"mappings_count\": 5"));
+    assert!(json.contains("\"kind\": \"prefix\""));
+    assert!(json.contains("\"links\": 2"));
+    assert!(json.contains("\"applied\": false"));
 }
 
 #[test]
-fn test_external_repos_path_extraction() {
+fn test_yore_config_basic_parsing() {
+    let toml = r#"
+[index.docs]
+roots = ["docs/"]
+types = ["md"]
+output = ".yore"
+"#;
+    let config: YoreConfig = toml::from_str(toml).unwrap();
+    assert!(config.index.contains_key("docs"));
+    let docs = config.index.get("docs").unwrap();
+    assert_eq!(docs.roots, vec!["docs/"]);
+    assert_eq!(docs.types, vec!["md"]);
+}
+
+#[test]
+fn test_parse_link_allowlist_entries() {
+    let allowlist = parse_link_allowlist(
+        "# comment\n\n./docs/a.md:12\ndocs/b.md:https://example.com/x\ndocs/c.md:#intro\n",
+    )
+    .unwrap();
+
+    assert!(allowlist.suppresses("docs/a.md", 12, "anything.md"));
+    assert!(!allowlist.suppresses("docs/a.md", 13, "anything.md"));
+    assert!(allowlist.suppresses("docs/b.md", 1, "https://example.com/x"));
+    assert!(allowlist.suppresses("docs/c.md", 7, "#intro"));
+    assert!(!allowlist.suppresses("docs/c.md", 7, "#outro"));
+
+    let err = parse_link_allowlist("docs/a.md:4\nno-colon-here\n").unwrap_err();
+    assert!(err.contains("line 2"), "got: {err}");
+}
+
+#[test]
+fn test_yore_config_link_check_section() {
+    let toml = r#"
+[link-check]
+exclude = ["archive/**", "deprecated/**"]
+
+[[link-check.severity-overrides]]
+pattern = "archive/**"
+severity = "warn"
+
+[[link-check.severity-overrides]]
+pattern = "deprecated/**"
+severity = "info"
+"#;
+    let config: YoreConfig = toml::from_str(toml).unwrap();
+    let link_check = config.link_check.unwrap();
+    assert_eq!(link_check.exclude.len(), 2);
+    assert_eq!(link_check.severity_overrides.len(), 2);
+    assert_eq!(link_check.severity_overrides[0].pattern, "archive/**");
+    assert_eq!(link_check.severity_overrides[0].severity, "warn");
+}
+
+#[test]
+fn test_yore_config_external_repos() {
     let toml = r#"
 [[external.repos]]
 path = "../runtime/docs"
@@ -1671,529 +2415,3250 @@ path = "../api-docs"
 "#;
     let config: YoreConfig = toml::from_str(toml).unwrap();
     let external = config.external.unwrap();
+    assert_eq!(external.repos.len(), 2);
+    assert_eq!(external.repos[0].path, "../runtime/docs");
+    assert_eq!(external.repos[0].prefix, Some("runtime".to_string()));
+    assert_eq!(external.repos[1].prefix, None);
+}
 
-    // Extract paths like the cmd_check_links dispatch does
-    let paths: Vec<String> = external.repos.iter().map(|r| r.path.clone()).collect();
-
-    assert_eq!(paths.len(), 2);
-    assert_eq!(paths[0], "../runtime/docs");
-    assert_eq!(paths[1], "../api-docs");
+#[test]
+fn test_yore_config_policy_section() {
+    let toml = r#"
+[policy]
+rules-file = ".yore-policy.yaml"
+"#;
+    let config: YoreConfig = toml::from_str(toml).unwrap();
+    let policy = config.policy.unwrap();
+    assert_eq!(policy.rules_file, Some(".yore-policy.yaml".to_string()));
 }
 
-fn make_file_entry(path: &str) -> FileEntry {
-    FileEntry {
-        path: path.to_string(),
-        size_bytes: 0,
-        line_count: 0,
-        headings: Vec::new(),
-        keywords: Vec::new(),
-        body_keywords: Vec::new(),
-        links: Vec::new(),
-        simhash: 0,
-        term_frequencies: HashMap::new(),
-        doc_length: 0,
-        minhash: Vec::new(),
-        section_fingerprints: Vec::new(),
-        adr_references: Vec::new(),
+#[test]
+fn test_yore_config_full_example() {
+    let toml = r#"
+[index.docs]
+roots = ["docs/"]
+types = ["md", "txt"]
+output = ".yore"
+
+[index.all]
+roots = ["docs/", "specs/"]
+types = ["md"]
+
+[link-check]
+exclude = ["archive/**"]
+
+[[link-check.severity-overrides]]
+pattern = "deprecated/**"
+severity = "info"
+
+[policy]
+rules-file = ".yore-policy.yaml"
+
+[[external.repos]]
+path = "../runtime/docs"
+prefix = "runtime"
+"#;
+    let config: YoreConfig = toml::from_str(toml).unwrap();
+
+    // Index profiles
+    assert_eq!(config.index.len(), 2);
+    assert!(config.index.contains_key("docs"));
+    assert!(config.index.contains_key("all"));
+
+    // Link check
+    let link_check = config.link_check.unwrap();
+    assert_eq!(link_check.exclude.len(), 1);
+    assert_eq!(link_check.severity_overrides.len(), 1);
+
+    // Policy
+    let policy = config.policy.unwrap();
+    assert!(policy.rules_file.is_some());
+
+    // External
+    let external = config.external.unwrap();
+    assert_eq!(external.repos.len(), 1);
+}
+
+#[test]
+fn test_yore_config_empty_is_valid() {
+    let toml = "";
+    let config: YoreConfig = toml::from_str(toml).unwrap();
+    assert!(config.index.is_empty());
+    assert!(config.link_check.is_none());
+    assert!(config.policy.is_none());
+    assert!(config.external.is_none());
+}
+
+#[test]
+fn test_build_result_serialization() {
+    let result = BuildResult {
+        index_path: ".yore".to_string(),
+        files_indexed: 150,
+        total_headings: 450,
+        total_links: 200,
+        unique_keywords: 800,
+        duration_ms: 1234,
+        renames_tracked: None,
+        total_relations: None,
+    };
+
+    let json = serde_json::to_string_pretty(&result).unwrap();
+    assert!(json.contains("\"index_path\": \".yore\""));
+    // renames_tracked should be absent when None due to skip_serializing_if
+    assert!(!json.contains("renames_tracked"));
+    assert!(json.contains("\"files_indexed\": 150"));
+    assert!(json.contains("\"total_headings\": 450"));
+    assert!(json.contains("\"total_links\": 200"));
+    assert!(json.contains("\"unique_keywords\": 800"));
+    assert!(json.contains("\"duration_ms\": 1234"));
+}
+
+#[test]
+fn test_eval_json_result_serialization() {
+    let result = EvalJsonResult {
+        questions_file: "questions.jsonl".to_string(),
+        total_questions: 10,
+        passed: 8,
+        failed: 2,
+        pass_rate: 80.0,
+        recall: 0.9,
+        mean_tokens: 420.0,
+        results: vec![
+            EvalQuestionResult {
+                id: 1,
+                question: "How do I authenticate?".to_string(),
+                passed: true,
+                hits: 1,
+                total: 1,
+                tokens: 400,
+                expected: vec!["auth.md".to_string()],
+                found: vec!["auth.md".to_string()],
+                missing: vec![],
+                ranking: None,
+                files: None,
+            },
+            EvalQuestionResult {
+                id: 2,
+                question: "What is the API endpoint?".to_string(),
+                passed: false,
+                hits: 0,
+                total: 1,
+                tokens: 440,
+                expected: vec!["api.md".to_string()],
+                found: vec![],
+                missing: vec!["api.md".to_string()],
+                ranking: Some(RankingMetrics {
+                    precision_at_k: vec![MetricAtK { k: 5, value: 0.4 }],
+                    recall_at_k: vec![MetricAtK { k: 5, value: 1.0 }],
+                    mrr: 0.5,
+                    ndcg_at_k: vec![MetricAtK { k: 5, value: 0.8 }],
+                }),
+                files: None,
+            },
+        ],
+        ranking_metrics: None,
+        file_metrics: None,
+    };
+
+    let json = serde_json::to_string_pretty(&result).unwrap();
+    assert!(json.contains("\"questions_file\": \"questions.jsonl\""));
+    assert!(json.contains("\"total_questions\": 10"));
+    assert!(json.contains("\"passed\": 8"));
+    assert!(json.contains("\"failed\": 2"));
+    assert!(json.contains("\"pass_rate\": 80.0"));
+    assert!(json.contains("\"recall\": 0.9"));
+    assert!(json.contains("\"mean_tokens\": 420.0"));
+    assert!(json.contains("\"results\""));
+    assert!(json.contains("How do I authenticate?"));
+    assert!(json.contains("\"missing\": []"));
+    // ranking is omitted when None
+    assert!(!json.contains("\"ranking\":\n") || json.contains("\"ranking\":"));
+    // ranking_metrics omitted at top level when None
+    assert!(!json.contains("\"ranking_metrics\""));
+    // The second question has ranking data
+    assert!(json.contains("\"mrr\": 0.5"));
+    assert!(json.contains("\"precision_at_k\""));
+}
+
+#[test]
+fn test_unique_doc_ranking() {
+    let sections = vec![
+        SectionMatch {
+            doc_path: "docs/a.md".to_string(),
+            heading: "H1".to_string(),
+            line_start: 1,
+            line_end: 10,
+            bm25_score: 5.0,
+            content: "content a".to_string(),
+            canonicality: 0.5,
+            via: None,
+        },
+        SectionMatch {
+            doc_path: "docs/b.md".to_string(),
+            heading: "H2".to_string(),
+            line_start: 1,
+            line_end: 10,
+            bm25_score: 4.0,
+            content: "content b".to_string(),
+            canonicality: 0.5,
+            via: None,
+        },
+        SectionMatch {
+            doc_path: "docs/a.md".to_string(),
+            heading: "H3".to_string(),
+            line_start: 11,
+            line_end: 20,
+            bm25_score: 3.0,
+            content: "content a2".to_string(),
+            canonicality: 0.5,
+            via: None,
+        },
+        SectionMatch {
+            doc_path: "docs/c.md".to_string(),
+            heading: "H4".to_string(),
+            line_start: 1,
+            line_end: 10,
+            bm25_score: 2.0,
+            content: "content c".to_string(),
+            canonicality: 0.5,
+            via: None,
+        },
+    ];
+    let ranking = unique_doc_ranking(&sections);
+    assert_eq!(ranking, vec!["docs/a.md", "docs/b.md", "docs/c.md"]);
+}
+
+#[test]
+fn test_precision_at_k() {
+    let ranked = vec![
+        "a.md".to_string(),
+        "b.md".to_string(),
+        "c.md".to_string(),
+        "d.md".to_string(),
+        "e.md".to_string(),
+    ];
+    let relevant: HashSet<String> = ["a.md", "c.md"].iter().map(ToString::to_string).collect();
+
+    assert_eq!(precision_at_k(&ranked, &relevant, 5), 0.4);
+    assert_eq!(precision_at_k(&ranked, &relevant, 1), 1.0);
+    assert_eq!(precision_at_k(&ranked, &relevant, 2), 0.5);
+    assert_eq!(precision_at_k(&ranked, &relevant, 0), 0.0);
+}
+
+#[test]
+fn test_recall_at_k() {
+    let ranked = vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()];
+    let relevant: HashSet<String> = ["a.md", "c.md"].iter().map(ToString::to_string).collect();
+
+    assert_eq!(recall_at_k(&ranked, &relevant, 3), 1.0);
+    assert_eq!(recall_at_k(&ranked, &relevant, 1), 0.5);
+    assert_eq!(recall_at_k(&ranked, &relevant, 0), 0.0);
+
+    // Empty relevant set
+    let empty: HashSet<String> = HashSet::new();
+    assert_eq!(recall_at_k(&ranked, &empty, 3), 0.0);
+}
+
+#[test]
+fn test_reciprocal_rank() {
+    let ranked = vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()];
+
+    // Relevant at rank 1
+    let rel1: HashSet<String> = ["a.md"].iter().map(ToString::to_string).collect();
+    assert_eq!(reciprocal_rank(&ranked, &rel1), 1.0);
+
+    // Relevant at rank 2
+    let rel2: HashSet<String> = ["b.md"].iter().map(ToString::to_string).collect();
+    assert_eq!(reciprocal_rank(&ranked, &rel2), 0.5);
+
+    // No match
+    let no_match: HashSet<String> = ["z.md"].iter().map(ToString::to_string).collect();
+    assert_eq!(reciprocal_rank(&ranked, &no_match), 0.0);
+}
+
+#[test]
+fn test_ndcg_at_k() {
+    // Perfect ranking: both relevant docs at positions 0 and 1
+    let perfect = vec!["a.md".to_string(), "b.md".to_string(), "c.md".to_string()];
+    let relevant: HashSet<String> = ["a.md", "b.md"].iter().map(ToString::to_string).collect();
+    assert_eq!(ndcg_at_k(&perfect, &relevant, 3), 1.0);
+
+    // Bad ranking: relevant docs at end
+    let bad = vec![
+        "c.md".to_string(),
+        "d.md".to_string(),
+        "a.md".to_string(),
+        "b.md".to_string(),
+    ];
+    let ndcg = ndcg_at_k(&bad, &relevant, 4);
+    assert!(ndcg < 1.0, "bad ranking should have nDCG < 1.0, got {ndcg}");
+    assert!(ndcg > 0.0, "bad ranking should have nDCG > 0.0, got {ndcg}");
+
+    // No relevant docs
+    let empty: HashSet<String> = HashSet::new();
+    assert_eq!(ndcg_at_k(&perfect, &empty, 3), 0.0);
+}
+
+#[test]
+fn test_compute_ranking_metrics() {
+    let ranked = vec![
+        "a.md".to_string(),
+        "b.md".to_string(),
+        "c.md".to_string(),
+        "d.md".to_string(),
+        "e.md".to_string(),
+    ];
+    let relevant: HashSet<String> = ["a.md", "c.md"].iter().map(ToString::to_string).collect();
+
+    let metrics = compute_ranking_metrics(&ranked, &relevant, &[3, 5]);
+    assert_eq!(metrics.precision_at_k.len(), 2);
+    assert_eq!(metrics.recall_at_k.len(), 2);
+    assert_eq!(metrics.ndcg_at_k.len(), 2);
+    assert_eq!(metrics.mrr, 1.0); // first relevant at rank 1
+
+    // P@3 = 2/3
+    assert!((metrics.precision_at_k[0].value - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(metrics.precision_at_k[0].k, 3);
+
+    // P@5 = 2/5
+    assert!((metrics.precision_at_k[1].value - 0.4).abs() < 1e-9);
+}
+
+#[test]
+fn test_aggregate_ranking_metrics() {
+    let m1 = RankingMetrics {
+        precision_at_k: vec![MetricAtK { k: 5, value: 0.4 }],
+        recall_at_k: vec![MetricAtK { k: 5, value: 1.0 }],
+        mrr: 1.0,
+        ndcg_at_k: vec![MetricAtK { k: 5, value: 0.8 }],
+    };
+    let m2 = RankingMetrics {
+        precision_at_k: vec![MetricAtK { k: 5, value: 0.2 }],
+        recall_at_k: vec![MetricAtK { k: 5, value: 0.5 }],
+        mrr: 0.5,
+        ndcg_at_k: vec![MetricAtK { k: 5, value: 0.6 }],
+    };
+
+    let agg = aggregate_ranking_metrics(&[m1, m2], &[5]);
+    assert_eq!(agg.questions_with_relevance, 2);
+    assert!((agg.mean_mrr - 0.75).abs() < 1e-9);
+    assert!((agg.mean_precision_at_k[0].value - 0.3).abs() < 1e-9);
+    assert!((agg.mean_recall_at_k[0].value - 0.75).abs() < 1e-9);
+    assert!((agg.mean_ndcg_at_k[0].value - 0.7).abs() < 1e-9);
+}
+
+#[test]
+fn test_compute_file_coverage() {
+    let expected = vec!["./docs/auth.md".to_string(), "docs/api.md".to_string()];
+    let retrieved: HashSet<String> = ["docs/auth.md", "docs/faq.md", "docs/intro.md", "docs/x.md"]
+        .iter()
+        .map(|s| (*s).to_string())
+        .collect();
+
+    let coverage = compute_file_coverage(&expected, &retrieved);
+    assert_eq!(coverage.found, vec!["docs/auth.md".to_string()]);
+    assert_eq!(coverage.missing, vec!["docs/api.md".to_string()]);
+    assert!((coverage.precision - 0.25).abs() < 1e-9);
+    assert!((coverage.recall - 0.5).abs() < 1e-9);
+
+    let agg =
+        aggregate_file_coverage(&[coverage, compute_file_coverage(&expected, &HashSet::new())]);
+    assert_eq!(agg.questions_with_expect_files, 2);
+    assert!((agg.mean_precision - 0.125).abs() < 1e-9);
+    assert!((agg.mean_recall - 0.25).abs() < 1e-9);
+}
+
+#[test]
+fn test_rename_history_serialization() {
+    let history = RenameHistory {
+        renames: vec![
+            RenameEntry {
+                old_path: "docs/old/auth.md".to_string(),
+                new_path: "docs/v2/auth.md".to_string(),
+                commit: "abc123".to_string(),
+            },
+            RenameEntry {
+                old_path: "docs/v2/auth.md".to_string(),
+                new_path: "docs/current/auth.md".to_string(),
+                commit: "def456".to_string(),
+            },
+        ],
+        indexed_at: "1234567890".to_string(),
+    };
+
+    let json = serde_json::to_string_pretty(&history).unwrap();
+    assert!(json.contains("\"old_path\": \"docs/old/auth.md\""));
+    assert!(json.contains("\"new_path\": \"docs/v2/auth.md\""));
+    assert!(json.contains("\"commit\": \"abc123\""));
+
+    // Verify roundtrip
+    let parsed: RenameHistory = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.renames.len(), 2);
+}
+
+#[test]
+fn test_resolve_renamed_path_single_rename() {
+    let history = RenameHistory {
+        renames: vec![RenameEntry {
+            old_path: "docs/old.md".to_string(),
+            new_path: "docs/new.md".to_string(),
+            commit: "abc123".to_string(),
+        }],
+        indexed_at: "0".to_string(),
+    };
+
+    assert_eq!(
+        resolve_renamed_path("docs/old.md", &history),
+        Some("docs/new.md".to_string())
+    );
+    assert_eq!(resolve_renamed_path("docs/other.md", &history), None);
+}
+
+#[test]
+fn test_resolve_renamed_path_chain() {
+    let history = RenameHistory {
+        renames: vec![
+            RenameEntry {
+                old_path: "a.md".to_string(),
+                new_path: "b.md".to_string(),
+                commit: "1".to_string(),
+            },
+            RenameEntry {
+                old_path: "b.md".to_string(),
+                new_path: "c.md".to_string(),
+                commit: "2".to_string(),
+            },
+            RenameEntry {
+                old_path: "c.md".to_string(),
+                new_path: "d.md".to_string(),
+                commit: "3".to_string(),
+            },
+        ],
+        indexed_at: "0".to_string(),
+    };
+
+    // Should follow the chain from a.md -> b.md -> c.md -> d.md
+    assert_eq!(
+        resolve_renamed_path("a.md", &history),
+        Some("d.md".to_string())
+    );
+    // Starting from middle should also work
+    assert_eq!(
+        resolve_renamed_path("b.md", &history),
+        Some("d.md".to_string())
+    );
+}
+
+#[test]
+fn test_compute_relative_path_same_dir() {
+    let files: HashSet<String> = HashSet::new();
+    assert_eq!(
+        compute_relative_path("docs/foo.md", "docs/bar.md", &files),
+        Some("bar.md".to_string())
+    );
+}
+
+#[test]
+fn test_compute_relative_path_subdirectory() {
+    let files: HashSet<String> = HashSet::new();
+    assert_eq!(
+        compute_relative_path("docs/index.md", "docs/guides/auth.md", &files),
+        Some("guides/auth.md".to_string())
+    );
+}
+
+#[test]
+fn test_compute_relative_path_parent_directory() {
+    let files: HashSet<String> = HashSet::new();
+    let result = compute_relative_path("docs/guides/auth.md", "docs/index.md", &files);
+    assert!(result.is_some());
+    assert!(result.unwrap().starts_with("../"));
+}
+
+#[test]
+fn test_build_result_with_renames() {
+    let result = BuildResult {
+        index_path: ".yore".to_string(),
+        files_indexed: 100,
+        total_headings: 200,
+        total_links: 50,
+        unique_keywords: 500,
+        duration_ms: 1000,
+        renames_tracked: Some(25),
+        total_relations: None,
+    };
+
+    let json = serde_json::to_string_pretty(&result).unwrap();
+    assert!(json.contains("\"renames_tracked\": 25"));
+}
+
+#[test]
+fn test_external_repos_path_extraction() {
+    let toml = r#"
+[[external.repos]]
+path = "../runtime/docs"
+prefix = "runtime"
+
+[[external.repos]]
+path = "../api-docs"
+"#;
+    let config: YoreConfig = toml::from_str(toml).unwrap();
+    let external = config.external.unwrap();
+
+    // Extract paths like the cmd_check_links dispatch does
+    let paths: Vec<String> = external.repos.iter().map(|r| r.path.clone()).collect();
+
+    assert_eq!(paths.len(), 2);
+    assert_eq!(paths[0], "../runtime/docs");
+    assert_eq!(paths[1], "../api-docs");
+}
+
+fn make_file_entry(path: &str) -> FileEntry {
+    FileEntry {
+        path: path.to_string(),
+        size_bytes: 0,
+        line_count: 0,
+        headings: Vec::new(),
+        keywords: Vec::new(),
+        body_keywords: Vec::new(),
+        links: Vec::new(),
+        simhash: 0,
+        term_frequencies: HashMap::new(),
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 0,
+        minhash: Vec::new(),
+        section_fingerprints: Vec::new(),
+        adr_references: Vec::new(),
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
+    }
+}
+
+/// Check one file against a rule whose regexes are known to compile.
+fn policy_violations(rule: &PolicyRule, file_path: &str, content: &str) -> Vec<PolicyViolation> {
+    let patterns = compile_policy_patterns(rule).unwrap();
+    collect_policy_violations_with_patterns(rule, &patterns, file_path, content, None)
+}
+
+fn make_forward_index(files: Vec<FileEntry>) -> ForwardIndex {
+    let map = files
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect();
+    ForwardIndex {
+        files: map,
+        indexed_at: "now".to_string(),
+        version: 1,
+        source_root: String::new(),
+        avg_doc_length: 0.0,
+        idf_map: HashMap::new(),
+        stemmer: StemmerKind::Simple,
+        positions: false,
+        stop_words: StopWords::default(),
+        minhash_perms: DEFAULT_MINHASH_PERMS,
+        simhash_weighting: SimhashWeighting::Uniform,
+        build_scope: None,
+    }
+}
+
+#[test]
+fn test_group_results_by_dir_sums_scores_per_prefix() {
+    assert_eq!(
+        dir_prefix("./docs/team-a/runbooks/oncall.md", 2),
+        "docs/team-a"
+    );
+    assert_eq!(dir_prefix("docs/team-a/oncall.md", 5), "docs/team-a");
+    assert_eq!(dir_prefix("README.md", 1), ".");
+
+    let results = vec![
+        ("docs/team-a/oncall.md".to_string(), 5.0),
+        ("docs/team-b/oncall.md".to_string(), 4.0),
+        ("docs/team-b/escalation.md".to_string(), 3.0),
+        ("README.md".to_string(), 1.0),
+    ];
+    let groups = group_results_by_dir(&results, 2);
+    let summary: Vec<(&str, f64, &[usize])> = groups
+        .iter()
+        .map(|g| (g.dir.as_str(), g.score, g.members.as_slice()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("docs/team-b", 7.0, &[1, 2][..]),
+            ("docs/team-a", 5.0, &[0][..]),
+            (".", 1.0, &[3][..]),
+        ]
+    );
+
+    let top_level = group_results_by_dir(&results, 1);
+    assert_eq!(top_level.len(), 2);
+    assert_eq!(top_level[0].dir, "docs");
+    assert_eq!(top_level[0].members, [0, 1, 2]);
+}
+
+#[test]
+fn test_parse_query_terms_punctuation_hyphen_case() {
+    let terms = parse_query_terms("Hello, async-migration!", Some(&StopWords::default()));
+    assert!(terms.contains(&"hello".to_string()));
+    assert!(terms.contains(&"async-migration".to_string()));
+}
+
+#[test]
+fn test_parse_query_terms_stopwords_only() {
+    let terms = parse_query_terms("the and of", Some(&StopWords::default()));
+    assert!(terms.is_empty());
+}
+
+#[test]
+fn test_load_vocabulary_stopwords_merges_defaults_and_custom() {
+    let default_words = load_vocabulary_stopwords(None, true).unwrap();
+    assert!(default_words.contains("the"));
+    assert!(default_words.contains("using"));
+
+    let custom_path = "tmp-vocabulary-stopwords.txt";
+    fs::write(custom_path, "custom\nThe\nvocab-test\n").unwrap();
+    let merged_words = load_vocabulary_stopwords(Some(Path::new(custom_path)), true).unwrap();
+
+    fs::remove_file(custom_path).unwrap();
+    assert!(merged_words.contains("custom"));
+    assert!(merged_words.contains("the"));
+    assert!(merged_words.contains("vocab-test"));
+}
+
+#[test]
+fn test_load_vocabulary_stopwords_can_disable_defaults() {
+    let stopwords = load_vocabulary_stopwords(None, false).unwrap();
+    assert!(!stopwords.contains("the"));
+    assert!(!stopwords.contains("and"));
+    assert!(stopwords.is_empty());
+}
+
+#[test]
+fn test_build_auto_common_vocabulary_stopwords() {
+    let candidates = vec![
+        VocabularyCandidateTerm {
+            term: "build".into(),
+            surface: None,
+            term_freq: 12,
+            doc_freq: 2,
+            first_file: "a".into(),
+            first_line: 1,
+            first_heading: "Build".into(),
+        },
+        VocabularyCandidateTerm {
+            term: "yore".into(),
+            surface: None,
+            term_freq: 9,
+            doc_freq: 3,
+            first_file: "a".into(),
+            first_line: 1,
+            first_heading: "Yore".into(),
+        },
+        VocabularyCandidateTerm {
+            term: "indexer".into(),
+            surface: None,
+            term_freq: 8,
+            doc_freq: 5,
+            first_file: "a".into(),
+            first_line: 1,
+            first_heading: "Index".into(),
+        },
+    ];
+
+    let common = build_auto_common_vocabulary_stopwords(&candidates, 2);
+    assert!(common.contains("build"));
+    assert!(common.contains("yore"));
+    assert_eq!(common.len(), 2);
+}
+
+#[test]
+fn test_is_hygienic_vocabulary_term() {
+    assert!(!is_hygienic_vocabulary_term("th"));
+    assert!(is_hygienic_vocabulary_term("yore"));
+    assert!(!is_hygienic_vocabulary_term("a1234567890"));
+    assert!(!is_hygienic_vocabulary_term("12345"));
+    assert!(!is_hygienic_vocabulary_term("v2.0"));
+    assert!(!is_hygienic_vocabulary_term("x"));
+}
+
+#[test]
+fn test_apply_vocabulary_limit_preserves_total_and_truncates_terms() {
+    let terms = vec![
+        VocabularyTerm {
+            term: "alpha".into(),
+            score: 3.0,
+            count: 4,
+        },
+        VocabularyTerm {
+            term: "beta".into(),
+            score: 2.0,
+            count: 3,
+        },
+        VocabularyTerm {
+            term: "gamma".into(),
+            score: 1.0,
+            count: 2,
+        },
+    ];
+    let (clipped, total) = apply_vocabulary_limit(terms, 2);
+    assert_eq!(total, 3);
+    assert_eq!(clipped.len(), 2);
+    assert_eq!(clipped[0].term, "alpha");
+    assert_eq!(clipped[1].term, "beta");
+}
+
+#[test]
+fn test_render_vocabulary_lines() {
+    let terms = vec![
+        VocabularyTerm {
+            term: "alpha".into(),
+            score: 1.2,
+            count: 7,
+        },
+        VocabularyTerm {
+            term: "beta".into(),
+            score: 0.9,
+            count: 5,
+        },
+    ];
+    assert_eq!(render_vocabulary_lines(&terms), "alpha\nbeta");
+}
+
+#[test]
+fn test_render_vocabulary_prompt_normalizes_terms() {
+    let terms = vec![
+        VocabularyTerm {
+            term: "alpha beta".into(),
+            score: 1.0,
+            count: 2,
+        },
+        VocabularyTerm {
+            term: "gamma\x00delta".into(),
+            score: 1.0,
+            count: 2,
+        },
+        VocabularyTerm {
+            term: "  spaced   out  ".into(),
+            score: 1.0,
+            count: 2,
+        },
+    ];
+    assert_eq!(
+        render_vocabulary_prompt(&terms),
+        "alpha beta, gammadelta, spaced out"
+    );
+}
+
+#[test]
+fn test_vocabulary_term_json_shape() {
+    let result = VocabularyResult {
+        format: "json".into(),
+        limit: 2,
+        total: 3,
+        terms: vec![
+            VocabularyTerm {
+                term: "alpha".into(),
+                score: 2.0,
+                count: 7,
+            },
+            VocabularyTerm {
+                term: "beta".into(),
+                score: 1.1,
+                count: 4,
+            },
+        ],
+        stopwords: None,
+        used_default_stopwords: true,
+        auto_common_terms: None,
+        include_stemming: false,
+    };
+    let json_value: serde_json::Value = serde_json::to_value(&result).unwrap();
+    assert_eq!(json_value["terms"][0]["term"], "alpha");
+    assert_eq!(json_value["terms"][0]["score"], 2.0);
+    assert_eq!(json_value["terms"][0]["count"], 7);
+    assert_eq!(json_value["terms"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_resolve_vocabulary_surface_prefers_heading_surface() {
+    let postings = vec![
+        ReverseEntry {
+            file: "notes.md".to_string(),
+            line: Some(10),
+            heading: Some("alpha term".to_string()),
+            level: None,
+        },
+        ReverseEntry {
+            file: "guide.md".to_string(),
+            line: Some(2),
+            heading: None,
+            level: None,
+        },
+    ];
+    let forward = make_forward_index(vec![
+        make_file_entry("notes.md"),
+        FileEntry {
+            path: "guide.md".to_string(),
+            size_bytes: 0,
+            line_count: 0,
+            headings: Vec::new(),
+            keywords: vec!["term".to_string(), "other".to_string()],
+            body_keywords: vec!["term".to_string()],
+            links: Vec::new(),
+            simhash: 0,
+            term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
+            doc_length: 0,
+            minhash: Vec::new(),
+            section_fingerprints: Vec::new(),
+            adr_references: Vec::new(),
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
+        },
+    ]);
+    let resolved = resolve_vocabulary_surface("term", &postings, Some(&forward)).unwrap();
+    assert_eq!(resolved, "term");
+}
+
+#[test]
+fn test_resolve_vocabulary_surface_fallbacks_to_forward_index() {
+    let postings = vec![
+        ReverseEntry {
+            file: "notes.md".to_string(),
+            line: Some(10),
+            heading: None,
+            level: None,
+        },
+        ReverseEntry {
+            file: "guide.md".to_string(),
+            line: Some(2),
+            heading: None,
+            level: None,
+        },
+    ];
+    let forward = make_forward_index(vec![
+        FileEntry {
+            path: "notes.md".to_string(),
+            size_bytes: 0,
+            line_count: 0,
+            headings: Vec::new(),
+            keywords: vec!["word".to_string()],
+            body_keywords: vec![],
+            links: Vec::new(),
+            simhash: 0,
+            term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
+            doc_length: 0,
+            minhash: Vec::new(),
+            section_fingerprints: Vec::new(),
+            adr_references: Vec::new(),
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
+        },
+        FileEntry {
+            path: "guide.md".to_string(),
+            size_bytes: 0,
+            line_count: 0,
+            headings: Vec::new(),
+            keywords: vec!["word".to_string()],
+            body_keywords: vec![],
+            links: Vec::new(),
+            simhash: 0,
+            term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
+            doc_length: 0,
+            minhash: Vec::new(),
+            section_fingerprints: Vec::new(),
+            adr_references: Vec::new(),
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
+        },
+    ]);
+    let resolved = resolve_vocabulary_surface("word", &postings, Some(&forward)).unwrap();
+    assert_eq!(resolved, "word");
+}
+
+#[test]
+fn test_parse_query_terms_mixed_case() {
+    let terms = parse_query_terms("TeSt CaSe", Some(&StopWords::default()));
+    assert_eq!(terms, vec!["test".to_string(), "case".to_string()]);
+}
+
+#[test]
+fn test_parse_query_boolean_operators() {
+    let plain = parse_query("kubernetes and deployment", Some(&StopWords::default()));
+    assert!(plain.filter.is_none());
+
+    let parsed = parse_query("docker NOT compose", Some(&StopWords::default()));
+    assert_eq!(parsed.terms, vec!["docker".to_string()]);
+    assert_eq!(
+        parsed.filter,
+        Some(QueryExpr::And(
+            Box::new(QueryExpr::Term("docker".to_string())),
+            Box::new(QueryExpr::Not(Box::new(QueryExpr::Term(
+                "compose".to_string()
+            ))))
+        ))
+    );
+
+    // AND binds tighter than OR
+    let parsed = parse_query("auth OR login AND sso", Some(&StopWords::default()));
+    assert_eq!(
+        parsed.filter,
+        Some(QueryExpr::Or(
+            Box::new(QueryExpr::Term("auth".to_string())),
+            Box::new(QueryExpr::And(
+                Box::new(QueryExpr::Term("login".to_string())),
+                Box::new(QueryExpr::Term("sso".to_string()))
+            ))
+        ))
+    );
+
+    let parsed = parse_query("\"async migration\" AND plan", Some(&StopWords::default()));
+    assert_eq!(parsed.phrases.len(), 1);
+    assert!(matches!(parsed.filter, Some(QueryExpr::And(ref a, _))
+        if **a == QueryExpr::Phrase(vec!["async".to_string(), "migration".to_string()])));
+}
+
+#[test]
+fn test_query_expr_matches_term_frequencies() {
+    let mut entry = make_file_entry("docs/a.md");
+    entry.term_frequencies = HashMap::from([(stem_word("docker"), 2), (stem_word("swarm"), 1)]);
+
+    let mut no_content = || Vec::new();
+    let and_not = parse_query("docker NOT compose", Some(&StopWords::default()))
+        .filter
+        .unwrap();
+    assert!(query_expr_matches(
+        &and_not,
+        &entry,
+        StemmerKind::Simple,
+        &StopWords::default(),
+        &mut no_content
+    ));
+
+    let both = parse_query("docker AND compose", Some(&StopWords::default()))
+        .filter
+        .unwrap();
+    assert!(!query_expr_matches(
+        &both,
+        &entry,
+        StemmerKind::Simple,
+        &StopWords::default(),
+        &mut no_content
+    ));
+
+    let either = parse_query("compose OR swarm", Some(&StopWords::default()))
+        .filter
+        .unwrap();
+    assert!(query_expr_matches(
+        &either,
+        &entry,
+        StemmerKind::Simple,
+        &StopWords::default(),
+        &mut no_content
+    ));
+}
+
+#[test]
+fn test_phrase_matches_positions() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-positions-{unique}.md"));
+    fs::write(
+        &path,
+        "# Limits\n\nThe retry budget caps the state of the art.\n\n    budget retry in code\n",
+    )
+    .unwrap();
+    let options = IndexOptions {
+        positions: true,
+        ..IndexOptions::default()
+    };
+    let (entry, _, _) = index_file(&path, &options).unwrap();
+    let (plain, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(plain.term_positions.is_empty());
+    let phrase = |text: &str| extract_keywords_with_options(text, None);
+    assert!(phrase_matches_positions(
+        &entry,
+        &phrase("retry budget"),
+        StemmerKind::Simple,
+        &StopWords::default(),
+    ));
+    assert!(!phrase_matches_positions(
+        &entry,
+        &phrase("budget retry"),
+        StemmerKind::Simple,
+        &StopWords::default(),
+    ));
+    // Stopwords hold their slot without being indexed
+    assert!(phrase_matches_positions(
+        &entry,
+        &phrase("state of the art"),
+        StemmerKind::Simple,
+        &StopWords::default(),
+    ));
+    assert!(!phrase_matches_positions(
+        &entry,
+        &phrase("retry caps"),
+        StemmerKind::Simple,
+        &StopWords::default(),
+    ));
+}
+
+#[test]
+fn test_parse_query_field_prefixes() {
+    let parsed = parse_query(
+        "heading:deployment kubernetes Body:rollback",
+        Some(&StopWords::default()),
+    );
+    assert_eq!(
+        parsed.field_terms,
+        vec![
+            FieldTerm {
+                field: QueryField::Heading,
+                term: "deployment".to_string()
+            },
+            FieldTerm {
+                field: QueryField::Body,
+                term: "rollback".to_string()
+            },
+        ]
+    );
+    assert_eq!(parsed.terms, vec!["kubernetes", "deployment", "rollback"]);
+}
+
+#[test]
+fn test_bm25_score_fields_restricts_to_field() {
+    let mut in_heading = make_file_entry("docs/heading.md");
+    in_heading.headings = vec![Heading {
+        line: 1,
+        level: 1,
+        text: "Deployment".to_string(),
+        anchor: None,
+    }];
+    in_heading.term_frequencies =
+        HashMap::from([(stem_word("deployment"), 1), (stem_word("kubernetes"), 2)]);
+    in_heading.doc_length = 3;
+
+    let mut in_body = make_file_entry("docs/body.md");
+    in_body.term_frequencies =
+        HashMap::from([(stem_word("deployment"), 1), (stem_word("kubernetes"), 2)]);
+    in_body.doc_length = 3;
+
+    let idf_map = HashMap::from([
+        (stem_word("deployment"), 1.0),
+        (stem_word("kubernetes"), 1.0),
+    ]);
+    let score = |query: &str, entry: &FileEntry| {
+        bm25_score_fields(
+            &parse_query(query, Some(&StopWords::default())),
+            entry,
+            3.0,
+            &idf_map,
+            StemmerKind::Simple,
+            &Synonyms::default(),
+            DEFAULT_HEADING_BOOST,
+        )
+    };
+
+    assert!(score("heading:deployment kubernetes", &in_heading) > 0.0);
+    assert_eq!(score("heading:deployment kubernetes", &in_body), 0.0);
+    assert_eq!(score("body:deployment", &in_heading), 0.0);
+    assert!(score("body:deployment", &in_body) > 0.0);
+    // An unprefixed copy of a field term still scores as a general term
+    assert!(
+        score("heading:deployment deployment", &in_heading)
+            > score("heading:deployment", &in_heading)
+    );
+    // Unprefixed queries score like plain BM25
+    assert_eq!(
+        score("deployment kubernetes", &in_body),
+        bm25_score_groups(
+            &expand_query_terms(
+                &["deployment".to_string(), "kubernetes".to_string()],
+                &Synonyms::default(),
+                StemmerKind::Simple,
+            ),
+            &in_body,
+            3.0,
+            &idf_map,
+            DEFAULT_HEADING_BOOST,
+        )
+    );
+}
+
+#[test]
+fn test_bm25_synonym_groups_take_best_alias() {
+    let mut k8s_doc = make_file_entry("docs/k8s.md");
+    k8s_doc.term_frequencies = HashMap::from([("k8s".to_string(), 2)]);
+    k8s_doc.doc_length = 4;
+    let mut both_doc = make_file_entry("docs/both.md");
+    both_doc.term_frequencies =
+        HashMap::from([("k8s".to_string(), 2), ("kubernet".to_string(), 2)]);
+    both_doc.doc_length = 4;
+    let idf_map = HashMap::from([("k8s".to_string(), 1.0), ("kubernet".to_string(), 1.0)]);
+
+    let synonyms = Synonyms {
+        groups: vec![vec!["kubernetes".to_string(), "k8s".to_string()]],
+    };
+    let query = vec!["kubernetes".to_string()];
+    let groups = expand_query_terms(&query, &synonyms, StemmerKind::Porter);
+    assert_eq!(
+        groups,
+        vec![vec!["kubernet".to_string(), "k8s".to_string()]]
+    );
+
+    // Without synonyms the alias-only doc does not match.
+    assert_eq!(
+        bm25_score_groups(
+            &expand_query_terms(&query, &Synonyms::default(), StemmerKind::Porter),
+            &k8s_doc,
+            4.0,
+            &idf_map,
+            DEFAULT_HEADING_BOOST,
+        ),
+        0.0
+    );
+    let k8s_score = bm25_score_groups(&groups, &k8s_doc, 4.0, &idf_map, 1.0);
+    assert!(k8s_score > 0.0);
+    // Using both spellings is not rewarded twice.
+    assert_eq!(
+        bm25_score_groups(&groups, &both_doc, 4.0, &idf_map, 1.0),
+        k8s_score
+    );
+}
+
+#[test]
+fn test_bm25_heading_boost_weights_heading_occurrences() {
+    let path = std::env::temp_dir().join(format!(
+        "yore-heading-boost-{}.md",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    fs::write(
+        &path,
+        "# Rollback\n\nSteps for the rollback and the release.\n",
+    )
+    .unwrap();
+    let (in_heading, _, _) = index_file(&path, &IndexOptions::default()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(in_heading.term_frequencies["rollback"], 2);
+    assert_eq!(in_heading.heading_term_frequencies["rollback"], 1);
+
+    let in_body = FileEntry {
+        heading_term_frequencies: HashMap::new(),
+        ..in_heading.clone()
+    };
+    let idf_map = HashMap::from([("rollback".to_string(), 1.0)]);
+    let groups = vec![vec!["rollback".to_string()]];
+    let score =
+        |entry: &FileEntry, boost: f64| bm25_score_groups(&groups, entry, 5.0, &idf_map, boost);
+
+    assert_eq!(score(&in_heading, 1.0), score(&in_body, 1.0));
+    assert!(score(&in_heading, DEFAULT_HEADING_BOOST) > score(&in_body, DEFAULT_HEADING_BOOST));
+    // A zero boost counts body occurrences only.
+    assert!(score(&in_heading, 0.0) < score(&in_body, 0.0));
+}
+#[test]
+fn test_parse_query_phrases() {
+    let parsed = parse_query("\"async migration\" plan", Some(&StopWords::default()));
+    assert_eq!(
+        parsed.terms,
+        vec![
+            "async".to_string(),
+            "migration".to_string(),
+            "plan".to_string()
+        ]
+    );
+    assert_eq!(parsed.phrases.len(), 1);
+    assert_eq!(
+        parsed.phrases[0].terms,
+        vec!["async".to_string(), "migration".to_string()]
+    );
+}
+
+#[test]
+fn test_expand_from_files_args_supports_list() {
+    let dir = std::env::temp_dir().join(format!(
+        "yore-test-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let list_path = dir.join("files.txt");
+    fs::write(&list_path, "docs/a.md\n\n docs/b.md\n").unwrap();
+
+    let args = vec![
+        format!("@{}", list_path.to_string_lossy()),
+        "docs/c.md".to_string(),
+    ];
+    let expanded = expand_from_files_args(&args).unwrap();
+
+    assert_eq!(
+        expanded,
+        vec![
+            "docs/a.md".to_string(),
+            "docs/b.md".to_string(),
+            "docs/c.md".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_resolve_from_files_reports_missing() {
+    let index = make_forward_index(vec![make_file_entry("docs/a.md")]);
+    let inputs = vec!["./docs/a.md".to_string(), "docs/missing.md".to_string()];
+    let (resolved, missing) = resolve_from_files(&inputs, &index);
+    assert_eq!(resolved, vec!["docs/a.md".to_string()]);
+    assert_eq!(missing, vec!["docs/missing.md".to_string()]);
+}
+
+#[test]
+fn test_collect_sections_for_files_max_sections() {
+    let dir = std::env::temp_dir().join(format!(
+        "yore-test-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("doc.md");
+    fs::write(&file_path, "# Title\n\nBody\n\n## Sub\n\nMore").unwrap();
+    let file_path_str = file_path.to_string_lossy().to_string();
+
+    let entry = FileEntry {
+        path: file_path_str.clone(),
+        size_bytes: 0,
+        line_count: 0,
+        headings: Vec::new(),
+        keywords: Vec::new(),
+        body_keywords: Vec::new(),
+        links: Vec::new(),
+        simhash: 0,
+        term_frequencies: HashMap::new(),
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 0,
+        minhash: Vec::new(),
+        section_fingerprints: vec![
+            SectionFingerprint {
+                heading: "Title".to_string(),
+                level: 1,
+                line_start: 1,
+                line_end: 3,
+                simhash: 0,
+            },
+            SectionFingerprint {
+                heading: "Sub".to_string(),
+                level: 2,
+                line_start: 5,
+                line_end: 6,
+                simhash: 0,
+            },
+        ],
+        adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
+    };
+    let index = make_forward_index(vec![entry]);
+    let search = SectionSearch {
+        index: &index,
+        docs: &DocCache::default(),
+        max_sections: 1,
+        synonyms: &Synonyms::default(),
+        scope: &PathScope::default(),
+        canonicality_weight: DEFAULT_CANONICALITY_WEIGHT,
+        heading_boost: DEFAULT_HEADING_BOOST,
+    };
+    let sections = collect_sections_for_files(&[file_path_str], "", &search);
+    assert_eq!(sections.len(), 1);
+}
+
+#[test]
+fn test_build_mcp_handle_is_stable() {
+    let section = SectionMatch {
+        doc_path: "docs/aa-auth.md".to_string(),
+        heading: "Authentication Overview".to_string(),
+        line_start: 1,
+        line_end: 11,
+        bm25_score: 0.25,
+        content: "# Authentication Overview\n\nAuthentication flow".to_string(),
+        canonicality: 0.5,
+        via: None,
+    };
+
+    let left = build_mcp_handle("authentication", &section);
+    let right = build_mcp_handle("authentication", &section);
+
+    assert_eq!(left, right);
+    assert!(left.starts_with("ctx_"));
+}
+
+#[test]
+fn test_compute_index_fingerprint_deterministic() {
+    let mut files = HashMap::new();
+    files.insert(
+        "docs/auth.md".to_string(),
+        FileEntry {
+            path: "docs/auth.md".to_string(),
+            size_bytes: 100,
+            line_count: 10,
+            headings: Vec::new(),
+            keywords: Vec::new(),
+            body_keywords: Vec::new(),
+            links: Vec::new(),
+            simhash: 0,
+            term_frequencies: HashMap::new(),
+            heading_term_frequencies: HashMap::new(),
+            doc_length: 0,
+            minhash: Vec::new(),
+            section_fingerprints: Vec::new(),
+            adr_references: Vec::new(),
+            frontmatter: HashMap::new(),
+            term_positions: HashMap::new(),
+            code_terms: HashSet::new(),
+            keyword_display: HashMap::new(),
+            anchors: Vec::new(),
+        },
+    );
+
+    let index = ForwardIndex {
+        files,
+        indexed_at: "2026-03-26T00:00:00Z".to_string(),
+        version: 5,
+        source_root: String::new(),
+        avg_doc_length: 0.0,
+        idf_map: HashMap::new(),
+        stemmer: StemmerKind::Simple,
+        positions: false,
+        stop_words: StopWords::default(),
+        minhash_perms: DEFAULT_MINHASH_PERMS,
+        simhash_weighting: SimhashWeighting::Uniform,
+        build_scope: None,
+    };
+
+    let left = compute_index_fingerprint(&index);
+    let right = compute_index_fingerprint(&index);
+    assert_eq!(left, right);
+    assert!(left.starts_with("idx_"));
+    assert_eq!(left.len(), 20); // "idx_" + 16 hex chars
+}
+
+#[test]
+fn test_compute_index_fingerprint_changes_with_index() {
+    let make_index = |indexed_at: &str| {
+        let mut files = HashMap::new();
+        files.insert(
+            "docs/auth.md".to_string(),
+            FileEntry {
+                path: "docs/auth.md".to_string(),
+                size_bytes: 100,
+                line_count: 10,
+                headings: Vec::new(),
+                keywords: Vec::new(),
+                body_keywords: Vec::new(),
+                links: Vec::new(),
+                simhash: 0,
+                term_frequencies: HashMap::new(),
+                heading_term_frequencies: HashMap::new(),
+                doc_length: 0,
+                minhash: Vec::new(),
+                section_fingerprints: Vec::new(),
+                adr_references: Vec::new(),
+                frontmatter: HashMap::new(),
+                term_positions: HashMap::new(),
+                code_terms: HashSet::new(),
+                keyword_display: HashMap::new(),
+                anchors: Vec::new(),
+            },
+        );
+        ForwardIndex {
+            files,
+            indexed_at: indexed_at.to_string(),
+            version: 5,
+            source_root: String::new(),
+            avg_doc_length: 0.0,
+            idf_map: HashMap::new(),
+            stemmer: StemmerKind::Simple,
+            positions: false,
+            stop_words: StopWords::default(),
+            minhash_perms: DEFAULT_MINHASH_PERMS,
+            simhash_weighting: SimhashWeighting::Uniform,
+            build_scope: None,
+        }
+    };
+
+    let fp1 = compute_index_fingerprint(&make_index("2026-03-26T00:00:00Z"));
+    let fp2 = compute_index_fingerprint(&make_index("2026-03-27T00:00:00Z"));
+    assert_ne!(fp1, fp2);
+}
+
+#[test]
+fn test_generate_trace_id_format() {
+    let id = generate_trace_id("test-seed");
+    assert!(id.starts_with("trc_"));
+    assert_eq!(id.len(), 20); // "trc_" + 16 hex chars
+}
+
+#[test]
+fn test_tokenizer_kind_from_name() {
+    assert_eq!(
+        TokenizerKind::from_name("approx"),
+        Some(TokenizerKind::Approx)
+    );
+    assert_eq!(
+        TokenizerKind::from_name("CL100K"),
+        Some(TokenizerKind::Cl100k)
+    );
+    assert_eq!(TokenizerKind::from_name("gpt2"), None);
+}
+
+#[test]
+fn test_count_tokens_approx_and_byte_budget() {
+    assert_eq!(count_tokens("abcdefgh", TokenizerKind::Approx), 2);
+    assert_eq!(count_tokens("", TokenizerKind::Approx), 0);
+    assert_eq!(
+        token_byte_budget("some text", 10, TokenizerKind::Approx),
+        40
+    );
+}
+
+#[cfg(not(feature = "tiktoken"))]
+#[test]
+fn test_cl100k_tokenizer_requires_feature() {
+    assert!(check_tokenizer_available(TokenizerKind::Cl100k).is_err());
+    assert!(check_tokenizer_available(TokenizerKind::Approx).is_ok());
+}
+
+#[cfg(feature = "tiktoken")]
+#[test]
+fn test_count_tokens_cl100k() {
+    assert_eq!(count_tokens("hello world", TokenizerKind::Cl100k), 2);
+}
+
+#[test]
+fn test_parse_line_anchor() {
+    assert_eq!(parse_line_anchor("L42"), Some((42, 42)));
+    assert_eq!(parse_line_anchor("L10-L20"), Some((10, 20)));
+    assert_eq!(parse_line_anchor("L0"), None);
+    assert_eq!(parse_line_anchor("L20-L10"), None);
+    assert_eq!(parse_line_anchor("L10-20"), None);
+    assert_eq!(parse_line_anchor("l42"), None);
+    assert_eq!(parse_line_anchor("install"), None);
+}
+
+#[test]
+fn test_github_slug_strips_punctuation_and_code_spans() {
+    let mut seen = HashMap::new();
+    assert_eq!(
+        github_slug("Using `yore build` flags", &mut seen),
+        "using-yore-build-flags"
+    );
+    assert_eq!(github_slug("Step 1: Setup", &mut seen), "step-1-setup");
+    assert_eq!(github_slug("What's new?", &mut seen), "whats-new");
+    assert_eq!(github_slug("snake_case-name", &mut seen), "snake_case-name");
+}
+
+#[test]
+fn test_github_slug_emoji_and_repeated_hyphens() {
+    let mut seen = HashMap::new();
+    assert_eq!(github_slug("🚀 Launch", &mut seen), "-launch");
+    assert_eq!(github_slug("A - B", &mut seen), "a---b");
+    assert_eq!(github_slug("Café Menü", &mut seen), "café-menü");
+}
+
+#[test]
+fn test_github_slug_disambiguates_duplicates() {
+    let mut seen = HashMap::new();
+    assert_eq!(github_slug("Setup", &mut seen), "setup");
+    assert_eq!(github_slug("Setup", &mut seen), "setup-1");
+    assert_eq!(github_slug("Setup", &mut seen), "setup-2");
+    assert_eq!(github_slug("Setup 1", &mut seen), "setup-1-1");
+}
+
+#[test]
+fn test_extract_links_reference_definition_after_usage() {
+    let lines = vec![
+        "# Guide",
+        "",
+        "See the [deploy guide][deploy] and [Runbook].",
+        "Inline [arch](architecture.md) still works.",
+        "",
+        "[deploy]: ops/deploy.md",
+        "[runbook]: <ops/runbook.md> \"Runbook\"",
+    ];
+    let links = extract_links(&lines);
+    let summary: Vec<(usize, &str, &str)> = links
+        .iter()
+        .map(|l| (l.line, l.text.as_str(), l.target.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (3, "deploy guide", "ops/deploy.md"),
+            (3, "Runbook", "ops/runbook.md"),
+            (4, "arch", "architecture.md"),
+        ]
+    );
+}
+
+#[test]
+fn test_extract_links_reference_definition_before_usage() {
+    let lines = vec![
+        "[Setup Notes]: setup.md#install",
+        "",
+        "# Intro",
+        "Read [setup notes][] first, then [the  setup][SETUP notes].",
+        "Unknown [text][missing] and plain [brackets] are ignored.",
+    ];
+    let links = extract_links(&lines);
+    assert_eq!(links.len(), 2);
+    assert!(links.iter().all(|l| l.line == 4));
+    assert!(links.iter().all(|l| l.target == "setup.md#install"));
+    assert_eq!(links[0].text, "setup notes");
+    assert_eq!(links[1].text, "the  setup");
+}
+
+#[test]
+fn test_extract_links_wiki_forms() {
+    let lines = vec![
+        "See [[Page Name]], [[Page Name#Deploy Steps]] and [[Other|alias]].",
+        "Local [[#Summary]] plus [inline](a.md).",
+    ];
+    let links = extract_links(&lines);
+    let summary: Vec<(&str, &str, Option<&str>)> = links
+        .iter()
+        .map(|l| (l.text.as_str(), l.target.as_str(), l.wiki.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("Page Name", "Page Name.md", Some("Page Name")),
+            ("Page Name", "Page Name.md#deploy-steps", Some("Page Name")),
+            ("alias", "Other.md", Some("Other")),
+            ("Summary", "#summary", Some("")),
+            ("inline", "a.md", None),
+        ]
+    );
+}
+
+#[test]
+fn test_wiki_page_key_normalizes_names() {
+    assert_eq!(wiki_page_key("Getting Started"), "getting-started");
+    assert_eq!(wiki_page_key("guides/Setup.md"), "setup");
+}
+
+#[test]
+fn test_duplicate_clusters_are_transitive() {
+    let pair = |a: &str, b: &str| (a.to_string(), b.to_string(), 0.9);
+    let pairs = vec![
+        pair("docs/b.md", "docs/c.md"),
+        pair("docs/d.md", "docs/e.md"),
+        pair("docs/a.md", "docs/b.md"),
+    ];
+    let clusters = duplicate_clusters(&pairs);
+    assert_eq!(
+        clusters,
+        vec![
+            vec!["docs/a.md", "docs/b.md", "docs/c.md"],
+            vec!["docs/d.md", "docs/e.md"],
+        ]
+    );
+}
+
+#[test]
+fn test_similarity_weights_defaults_and_normalization() {
+    let defaults =
+        SimilarityWeights::with_overrides(SimilarityWeights::DUPES, None, None, None).unwrap();
+    assert_eq!(defaults, SimilarityWeights::DUPES);
+
+    let tuned =
+        SimilarityWeights::with_overrides(SimilarityWeights::DUPES, Some(2.0), None, Some(0.0))
+            .unwrap();
+    assert!((tuned.jaccard - 2.0 / 2.3).abs() < 1e-12);
+    assert!((tuned.simhash - 0.3 / 2.3).abs() < 1e-12);
+    assert_eq!(tuned.minhash, 0.0);
+
+    assert!(SimilarityWeights::with_overrides(
+        SimilarityWeights::SIMILAR,
+        Some(0.0),
+        Some(0.0),
+        None
+    )
+    .is_err());
+    assert!(
+        SimilarityWeights::with_overrides(SimilarityWeights::SIMILAR, Some(-1.0), None, None)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_combined_similarity_matches_legacy_blends() {
+    let mut a = make_file_entry("docs/a.md");
+    a.keywords = vec!["deploy".to_string(), "helm".to_string()];
+    a.body_keywords = vec!["rollout".to_string()];
+    a.simhash = 0xF0F0;
+    a.minhash = vec![1, 2, 3, 4];
+    let mut b = make_file_entry("docs/b.md");
+    b.keywords = vec!["Deploy".to_string()];
+    b.body_keywords = vec!["canary".to_string(), "rollout".to_string()];
+    b.simhash = 0xF0FF;
+    b.minhash = vec![1, 2, 9, 9];
+
+    let jaccard = 2.0 / 4.0;
+    let simhash = simhash_similarity(a.simhash, b.simhash);
+    let minhash = 0.5;
+
+    let dupes = combined_similarity(&a, &b, SimilarityWeights::DUPES);
+    assert_eq!(dupes.jaccard, jaccard);
+    assert_eq!(dupes.minhash, minhash);
+    assert_eq!(
+        dupes.combined,
+        jaccard * 0.4 + simhash * 0.3 + minhash * 0.3
+    );
+
+    let similar = combined_similarity(&a, &b, SimilarityWeights::SIMILAR);
+    assert_eq!(similar.combined, jaccard * 0.6 + simhash * 0.4);
+}
+
+#[test]
+fn test_cmd_build_binary_forward_index_roundtrip() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let root = std::env::temp_dir().join(format!("yore-build-bin-{unique}"));
+    let docs_dir = root.join("docs");
+    let index_dir = root.join(".yore");
+    fs::create_dir_all(&docs_dir).unwrap();
+    fs::write(
+        docs_dir.join("guide.md"),
+        "# Guide\n\nDeploy with [ops](ops.md).\n",
+    )
+    .unwrap();
+    fs::write(docs_dir.join("ops.md"), "# Ops\n\nOperations runbook.\n").unwrap();
+
+    let build = |format: &str| {
+        cmd_build(
+            &docs_dir,
+            &index_dir,
+            &BuildOptions {
+                types: "md",
+                quiet: true,
+                format,
+                ..BuildOptions::default()
+            },
+        )
+    };
+
+    build("json").unwrap();
+    let from_json =
+        load_forward_index(&IndexDir::new(&index_dir, IndexVersionCheck::Warn)).unwrap();
+
+    build("bin").unwrap();
+    let bin_path = index_dir.join("forward_index.bin");
+    assert!(bin_path.exists());
+    let json_path = index_dir.join("forward_index.json");
+    assert!(!json_path.exists());
+    let from_bin = load_forward_index(&IndexDir::new(&index_dir, IndexVersionCheck::Warn)).unwrap();
+    assert_eq!(from_bin.version, CURRENT_INDEX_VERSION);
+    let mut json_keys: Vec<_> = from_json.files.keys().collect();
+    let mut bin_keys: Vec<_> = from_bin.files.keys().collect();
+    json_keys.sort();
+    bin_keys.sort();
+    assert_eq!(json_keys, bin_keys);
+    assert_eq!(from_bin.idf_map, from_json.idf_map);
+
+    // A binary from another index version is ignored in favor of the JSON
+    for version in [CURRENT_INDEX_VERSION - 1, CURRENT_INDEX_VERSION + 1] {
+        fs::write(&json_path, serde_json::to_string(&from_json).unwrap()).unwrap();
+        let mut bytes = encode_forward_index_bin(&from_bin).unwrap();
+        bytes[8..12].copy_from_slice(&version.to_le_bytes());
+        fs::write(&bin_path, bytes).unwrap();
+        assert!(load_forward_index_bin(&bin_path, &json_path)
+            .unwrap()
+            .is_none());
+        let fallback =
+            load_forward_index(&IndexDir::new(&index_dir, IndexVersionCheck::Strict)).unwrap();
+        assert_eq!(fallback.version, CURRENT_INDEX_VERSION);
+        assert_eq!(fallback.files.len(), from_json.files.len());
     }
+
+    build("json").unwrap();
+    assert!(!bin_path.exists());
+    assert!(build("yaml").is_err());
+
+    fs::remove_dir_all(root).unwrap();
 }
 
-fn make_forward_index(files: Vec<FileEntry>) -> ForwardIndex {
-    let map = files
-        .into_iter()
-        .map(|entry| (entry.path.clone(), entry))
-        .collect();
-    ForwardIndex {
-        files: map,
-        indexed_at: "now".to_string(),
-        version: 1,
+#[test]
+fn test_render_mermaid_graph_sanitizes_ids() {
+    let edges = vec![
+        GraphEdge {
+            source: "docs/README.md".to_string(),
+            target: "docs/guides/set-up.md".to_string(),
+            anchor: Some("first-steps".to_string()),
+            kind: GraphKind::Internal,
+        },
+        GraphEdge {
+            source: "docs/guides/set-up.md".to_string(),
+            target: "docs/README.md".to_string(),
+            anchor: None,
+            kind: GraphKind::Internal,
+        },
+    ];
+    let out = render_mermaid_graph(&edges);
+    assert!(out.starts_with("```mermaid\ngraph LR\n"));
+    assert!(out.ends_with("```\n"));
+    assert!(out.contains("  %% n_docs_README_md = docs/README.md\n"));
+    assert!(out.contains("  n_docs_guides_set_up_md[\"set-up.md\"]\n"));
+    assert!(out.contains("  n_docs_README_md -->|\"#first-steps\"| n_docs_guides_set_up_md\n"));
+    assert!(out.contains("  n_docs_guides_set_up_md --> n_docs_README_md\n"));
+}
+
+#[test]
+fn test_compute_link_authority_ranks_hub_highest() {
+    let entry = |path: &str, targets: &[&str]| FileEntry {
+        path: path.to_string(),
+        size_bytes: 0,
+        line_count: 1,
+        headings: vec![],
+        keywords: vec![],
+        body_keywords: vec![],
+        links: targets
+            .iter()
+            .map(|target| Link {
+                line: 1,
+                text: String::new(),
+                target: (*target).to_string(),
+                wiki: None,
+            })
+            .collect(),
+        simhash: 0,
+        term_frequencies: HashMap::new(),
+        heading_term_frequencies: HashMap::new(),
+        doc_length: 0,
+        minhash: vec![],
+        section_fingerprints: vec![],
+        adr_references: vec![],
+        frontmatter: HashMap::new(),
+        term_positions: HashMap::new(),
+        code_terms: HashSet::new(),
+        keyword_display: HashMap::new(),
+        anchors: Vec::new(),
+    };
+
+    let mut files = HashMap::new();
+    files.insert(
+        "docs/hub.md".to_string(),
+        entry("docs/hub.md", &["a.md", "https://example.com"]),
+    );
+    files.insert("docs/a.md".to_string(), entry("docs/a.md", &["hub.md"]));
+    files.insert(
+        "docs/b.md".to_string(),
+        entry("docs/b.md", &["hub.md", "hub.md#intro", "b.md"]),
+    );
+    files.insert(
+        "docs/sub/c.md".to_string(),
+        entry("docs/sub/c.md", &["../hub.md"]),
+    );
+
+    let forward_index = ForwardIndex {
+        files,
+        indexed_at: "0".to_string(),
+        version: 3,
         source_root: String::new(),
         avg_doc_length: 0.0,
         idf_map: HashMap::new(),
+        stemmer: StemmerKind::Simple,
+        positions: false,
+        stop_words: StopWords::default(),
+        minhash_perms: DEFAULT_MINHASH_PERMS,
+        simhash_weighting: SimhashWeighting::Uniform,
+        build_scope: None,
+    };
+
+    let authority = compute_link_authority(&forward_index);
+    assert_eq!(authority.len(), 4);
+    assert!((authority["docs/hub.md"] - 1.0).abs() < 1e-9);
+    assert!(authority["docs/a.md"] > 0.0 && authority["docs/a.md"] < 1.0);
+    assert!(authority["docs/b.md"].abs() < 1e-9);
+    assert!(authority["docs/sub/c.md"].abs() < 1e-9);
+
+    let hub = &forward_index.files["docs/hub.md"];
+    let (graph_score, reasons) = score_canonicality_with_reasons(
+        "docs/hub.md",
+        hub,
+        Some(1.0),
+        builtin_canonicality_rules(),
+    );
+    assert!((graph_score - 0.7).abs() < 1e-9);
+    assert!(reasons.contains(&"High inbound authority (+0.20)".to_string()));
+
+    let (plain_score, reasons) =
+        score_canonicality_with_reasons("docs/hub.md", hub, None, builtin_canonicality_rules());
+    assert!((plain_score - 0.5).abs() < 1e-9);
+    assert_eq!(reasons, vec!["Baseline score (0.5)".to_string()]);
+}
+
+#[test]
+fn test_canonicality_rules_file_merges_with_builtins() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let path = std::env::temp_dir().join(format!("yore-canon-rules-{unique}.yaml"));
+    fs::write(
+        &path,
+        "rules:\n  - name: Reference docs\n    path_contains: [Reference/]\n    delta: 0.25\n  - name: Scratch/archive/old location\n    path_contains: [scratch]\n    delta: -0.3\n",
+    )
+    .unwrap();
+    let rules = load_canonicality_rules(Some(&path)).unwrap();
+    let entry = make_file_entry("docs/reference/api.md");
+
+    // Configured rules are named in the reasons
+    let (score, reasons) =
+        score_canonicality_with_reasons("docs/reference/api.md", &entry, None, &rules);
+    assert!((score - 0.75).abs() < 1e-9);
+    assert_eq!(reasons, vec!["Rule 'Reference docs' (+0.25)".to_string()]);
+
+    // Same-named rule replaces the built-in: "old" no longer penalizes
+    let (score, _) = score_canonicality_with_reasons("docs/holdings.md", &entry, None, &rules);
+    assert!((score - 0.5).abs() < 1e-9);
+    let (score, reasons) =
+        score_canonicality_with_reasons("docs/adr/0001.md", &entry, None, &rules);
+    assert!((score - 0.7).abs() < 1e-9);
+    assert_eq!(
+        reasons,
+        vec!["Architecture/ADR document (+0.2)".to_string()]
+    );
+
+    fs::write(
+        &path,
+        "replace_builtin: true\nrules:\n  - name: Empty\n    delta: 0.1\n",
+    )
+    .unwrap();
+    let err = load_canonicality_rules(Some(&path)).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("needs path_contains or filename_contains"));
+
+    fs::write(&path, "replace_builtin: true\n").unwrap();
+    assert!(load_canonicality_rules(Some(&path)).unwrap().is_empty());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_find_shortest_link_path() {
+    let edge = |source: &str, target: &str| GraphEdge {
+        source: source.to_string(),
+        target: target.to_string(),
+        anchor: None,
+        kind: GraphKind::Internal,
+    };
+    let edges = vec![
+        edge("a.md", "b.md"),
+        edge("b.md", "d.md"),
+        edge("a.md", "c.md"),
+        edge("c.md", "e.md"),
+        edge("e.md", "d.md"),
+    ];
+
+    assert_eq!(
+        find_shortest_link_path(&edges, "a.md", "d.md", false),
+        Some(vec!["a.md".into(), "b.md".into(), "d.md".into()])
+    );
+    assert_eq!(find_shortest_link_path(&edges, "d.md", "a.md", false), None);
+    assert_eq!(
+        find_shortest_link_path(&edges, "d.md", "a.md", true),
+        Some(vec!["d.md".into(), "b.md".into(), "a.md".into()])
+    );
+    assert_eq!(
+        find_shortest_link_path(&edges, "c.md", "c.md", false),
+        Some(vec!["c.md".into()])
+    );
+}
+
+#[test]
+fn test_find_reachable_documents_follows_links_transitively() {
+    let edge = |source: &str, target: &str| GraphEdge {
+        source: source.to_string(),
+        target: target.to_string(),
+        anchor: None,
+        kind: GraphKind::Internal,
+    };
+    let edges = vec![
+        edge("README.md", "a.md"),
+        edge("a.md", "b.md"),
+        edge("b.md", "a.md"),
+        // An island: c links to d, but nothing reaches c
+        edge("c.md", "d.md"),
+    ];
+
+    let reachable = find_reachable_documents(&edges, &["README.md".to_string()]);
+    let mut reachable: Vec<&str> = reachable.iter().map(String::as_str).collect();
+    reachable.sort_unstable();
+    assert_eq!(reachable, vec!["README.md", "a.md", "b.md"]);
+
+    let reachable = find_reachable_documents(&edges, &["c.md".to_string(), "b.md".to_string()]);
+    assert_eq!(reachable.len(), 4);
+    assert!(!reachable.contains("README.md"));
+}
+
+#[test]
+fn test_default_reachability_roots_are_readme_and_index() {
+    let forward_index = make_forward_index(vec![
+        make_file_entry("docs/README.md"),
+        make_file_entry("docs/api/index.md"),
+        make_file_entry("docs/readme-notes.md"),
+        make_file_entry("docs/guide.md"),
+    ]);
+    assert_eq!(
+        default_reachability_roots(&forward_index),
+        vec![
+            "docs/README.md".to_string(),
+            "docs/api/index.md".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_policy_must_match_and_must_not_match() {
+    let rule = PolicyRule {
+        pattern: "docs/runbooks/*.md".to_string(),
+        name: Some("runbook-rollback".to_string()),
+        must_match: vec![r"^## Rollback\n+```".to_string()],
+        must_not_match: vec![r"(?i)\bTODO\b".to_string()],
+        ..Default::default()
+    };
+
+    let good = "# Deploy\n\n## Rollback\n\n```bash\n./rollback.sh\n```\n";
+    assert!(policy_violations(&rule, "docs/runbooks/deploy.md", good).is_empty());
+
+    let bad = "# Deploy\n\n## Rollback\n\nAsk on-call. todo: script it\n";
+    let violations = policy_violations(&rule, "docs/runbooks/deploy.md", bad);
+    assert_eq!(violations.len(), 2);
+    assert_eq!(
+        violations[0].message,
+        "No match for required pattern: ^## Rollback\\n+```"
+    );
+    assert_eq!(
+        violations[1].message,
+        "Forbidden pattern matched at line 5: (?i)\\bTODO\\b"
+    );
+
+    let invalid = PolicyRule {
+        pattern: "docs/*.md".to_string(),
+        must_match: vec!["(unclosed".to_string(), "also[bad".to_string()],
+        ..Default::default()
+    };
+    let err = compile_policy_patterns(&invalid).unwrap_err();
+    assert!(
+        err.starts_with("Invalid must_match regex in policy rule \"docs/*.md\": (unclosed\n"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_truncate_markdown_to_bytes_respects_fences_and_utf8() {
+    let text =
+        "Intro paragraph.\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n\nOutro.\n";
+    // Budget ends inside the code block: the whole block is dropped.
+    let cut = truncate_markdown_to_bytes(text, 40);
+    assert_eq!(cut, "Intro paragraph.");
+
+    // Budget covers the block: it is kept intact.
+    let block_end = text.find("```\n\n").unwrap() + 4;
+    let cut = truncate_markdown_to_bytes(text, block_end + 2);
+    assert!(cut.ends_with("}\n```"), "{cut}");
+
+    // Text that opens with a fence is cut on a line and re-closed.
+    let fenced = "```\nline one\nline two\nline three\n```\n";
+    let cut = truncate_markdown_to_bytes(fenced, 21);
+    assert_eq!(cut, "```\nline one\n```");
+
+    // Multibyte characters are never split.
+    let cut = truncate_markdown_to_bytes("héllo wörld", 2);
+    assert_eq!(cut, "h");
+}
+
+#[test]
+fn test_distill_to_markdown_does_not_cut_inside_code_block() {
+    let prose = "Deploy steps for the service are listed below in order.\n".repeat(6);
+    let code = "kubectl rollout status deployment/api --timeout=60s\n".repeat(200);
+    let content = format!("{prose}\n```bash\n{code}```\n\nAfter the block.\n");
+    let section = SectionMatch {
+        doc_path: "docs/deploy.md".to_string(),
+        heading: "Deploy".to_string(),
+        line_start: 1,
+        line_end: 220,
+        bm25_score: 1.0,
+        content: content.clone(),
+        canonicality: 0.5,
+        via: None,
+    };
+
+    // Budget lands in the middle of the code block.
+    let digest = distill(
+        std::slice::from_ref(&section),
+        "deploy",
+        900,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+    assert!(digest.contains("*[Section truncated]*"), "{digest}");
+    assert!(
+        !digest.contains("kubectl"),
+        "code block should be dropped whole"
+    );
+    assert_eq!(digest.matches("```").count() % 2, 0);
+
+    // A section that is only a code block is cut on a line and closed.
+    let section = SectionMatch {
+        content: format!("```bash\n{code}```\n"),
+        ..section
+    };
+    let digest = distill(
+        &[section],
+        "deploy",
+        900,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+    assert!(digest.contains("kubectl"));
+    assert!(digest.contains("--timeout=60s\n```\n\n*[Section truncated]*"));
+    assert_eq!(digest.matches("```").count() % 2, 0);
+}
+
+#[test]
+fn test_distill_text_format_drops_markdown_structure() {
+    let section = SectionMatch {
+        doc_path: "docs/deploy.md".to_string(),
+        heading: "Deploy".to_string(),
+        line_start: 4,
+        line_end: 15,
+        bm25_score: 1.0,
+        content: "## Deploy\n\nRun the script.\n\n### Rollback\n\n---\n\n```bash\n# not a heading\n./deploy.sh\n```\n".to_string(),
+        canonicality: 0.5,
+        via: None,
+    };
+
+    let text = distill(
+        std::slice::from_ref(&section),
+        "deploy",
+        8000,
+        DigestFormat::Text,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+    assert_eq!(
+        text,
+        "Query: deploy\n\n// docs/deploy.md:4-15 Deploy\nRun the script.\n\nRollback\n\n```bash\n# not a heading\n./deploy.sh\n```\n\n"
+    );
+
+    // The markdown renderer is unchanged by the shared planning step.
+    let markdown = distill(
+        std::slice::from_ref(&section),
+        "deploy",
+        8000,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+    assert!(markdown.contains("### Deploy (from docs/deploy.md)"));
+    assert!(markdown.contains("\n## Sources\n\n- docs/deploy.md:4-15"));
+    assert!(markdown.contains("## Usage with LLM"));
+    assert!(!markdown.contains("**Scope:**"));
+    assert!(!markdown.contains("Documents Excluded"));
+    // Without a count from the caller, the candidate documents are counted
+    assert!(markdown.contains("**Documents Scanned:** 1\n"));
+
+    let scope = DigestScope {
+        label: Some("include docs/**; exclude docs/archive/**".to_string()),
+        excluded_docs: 3,
+        scanned_docs: Some(42),
+    };
+    let markdown = distill(
+        &[section],
+        "deploy",
+        8000,
+        DigestFormat::Markdown,
+        &scope,
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+    assert!(markdown.contains("**Scope:** include docs/**; exclude docs/archive/**\n"));
+    assert!(markdown.contains("**Documents Excluded:** 3 (matched --exclude)"));
+    assert!(markdown.contains("**Documents Scanned:** 42\n"));
+}
+
+#[test]
+fn test_canonicality_weight_orders_sections_and_documents() {
+    let section = |doc: &str, bm25_score: f64, canonicality: f64| SectionMatch {
+        doc_path: doc.to_string(),
+        heading: "Deploy".to_string(),
+        line_start: 1,
+        line_end: 3,
+        bm25_score,
+        content: "Run the deploy script.".to_string(),
+        canonicality,
+        via: None,
+    };
+    let relevant = section("docs/scratch/deploy.md", 0.9, 0.2);
+    let canonical = section("docs/adr/deploy.md", 0.5, 0.95);
+
+    let order = |weight: f64| {
+        let mut sections = [canonical.clone(), relevant.clone()];
+        sections.sort_by(|a, b| compare_sections_by_relevance(a, b, weight));
+        sections[0].doc_path.clone()
+    };
+    assert_eq!(order(0.0), "docs/scratch/deploy.md");
+    assert_eq!(order(DEFAULT_CANONICALITY_WEIGHT), "docs/scratch/deploy.md");
+    assert_eq!(order(1.0), "docs/adr/deploy.md");
+
+    // The header's document ranking follows the same weight
+    let sections = [relevant.clone(), canonical.clone()];
+    let markdown = distill(
+        &sections,
+        "deploy",
+        8000,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        1.0,
+        TokenizerKind::Approx,
+    );
+    assert!(markdown.contains("1. **docs/adr/deploy.md** (score: 0.95, canonical: 0.95)"));
+    assert!(markdown.contains("2. **docs/scratch/deploy.md** (score: 0.20"));
+}
+
+#[test]
+fn test_check_index_version_warns_or_fails_on_old_index() {
+    let mut index = make_forward_index(vec![make_file_entry("docs/a.md")]);
+    index.version = CURRENT_INDEX_VERSION;
+    assert!(check_index_version(&index, IndexVersionCheck::Strict).is_ok());
+
+    index.version = 1;
+    assert!(check_index_version(&index, IndexVersionCheck::Warn).is_ok());
+    assert!(check_index_version(&index, IndexVersionCheck::Quiet).is_ok());
+    let err = check_index_version(&index, IndexVersionCheck::Strict)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("index version 1 is older"), "{err}");
+    assert!(err.contains("yore build"), "{err}");
+
+    index.idf_map.clear();
+    assert!(warn_if_missing_bm25(&index));
+    index.idf_map.insert("alpha".to_string(), 1.0);
+    assert!(!warn_if_missing_bm25(&index));
+
+    for entry in index.files.values_mut() {
+        entry.minhash.clear();
     }
+    assert!(warn_if_missing_minhash(&index));
+    assert!(!warn_if_missing_minhash(&make_forward_index(Vec::new())));
 }
 
 #[test]
-fn test_parse_query_terms_punctuation_hyphen_case() {
-    let terms = parse_query_terms("Hello, async-migration!", true);
-    assert!(terms.contains(&"hello".to_string()));
-    assert!(terms.contains(&"async-migration".to_string()));
+fn test_init_templates_parse_as_config_and_policy() {
+    let config: YoreConfig =
+        toml::from_str(&init_config_template("policies/docs \"main\".yaml")).unwrap();
+    let docs = &config.index["docs"];
+    assert_eq!(docs.roots, vec!["docs"]);
+    assert_eq!(docs.output.as_deref(), Some(".yore"));
+    assert_eq!(
+        config.policy.unwrap().rules_file.as_deref(),
+        Some("policies/docs \"main\".yaml")
+    );
+
+    let policy: PolicyConfig = serde_yaml::from_str(INIT_POLICY_TEMPLATE).unwrap();
+    assert_eq!(policy.rules.len(), 1);
+    assert_eq!(
+        policy.rules[0].must_contain,
+        vec!["## Status", "## Decision"]
+    );
 }
 
 #[test]
-fn test_parse_query_terms_stopwords_only() {
-    let terms = parse_query_terms("the and of", true);
-    assert!(terms.is_empty());
+fn test_find_heading_hits_uses_heading_entries_only() {
+    let entry = |file: &str, line: Option<usize>, heading: Option<&str>| ReverseEntry {
+        file: file.to_string(),
+        line,
+        heading: heading.map(str::to_string),
+        level: line.map(|_| 2),
+    };
+    let mut reverse_index = ReverseIndex {
+        keywords: HashMap::new(),
+    };
+    reverse_index.keywords.insert(
+        stem_word_with("deploy", StemmerKind::default()),
+        vec![
+            entry("docs/a.md", None, None),
+            entry("docs/a.md", Some(12), Some("Deploy Steps")),
+            entry("docs/a.md", Some(4), Some("Deploying")),
+            entry("docs/a.md", Some(4), Some("Deploying")),
+            entry("docs/b.md", None, None),
+        ],
+    );
+
+    let hits = find_heading_hits(
+        &reverse_index,
+        &["deployment".to_string(), "deploy".to_string()],
+        StemmerKind::default(),
+    );
+    assert_eq!(
+        hits["docs/a.md"],
+        vec![
+            HeadingHit {
+                line: 4,
+                heading: "Deploying".to_string()
+            },
+            HeadingHit {
+                line: 12,
+                heading: "Deploy Steps".to_string()
+            },
+        ]
+    );
+    assert!(!hits.contains_key("docs/b.md"));
 }
 
 #[test]
-fn test_load_vocabulary_stopwords_merges_defaults_and_custom() {
-    let default_words = load_vocabulary_stopwords(None, true).unwrap();
-    assert!(default_words.contains("the"));
-    assert!(default_words.contains("using"));
+fn test_path_scope_matches_index_paths() {
+    let exclude = compile_path_scope(
+        &[],
+        &["docs/archive/**".to_string(), "*.draft.md".to_string()],
+        &[],
+    )
+    .unwrap();
+    assert!(exclude.is_excluded("docs/archive/2023/old.md"));
+    assert!(exclude.is_excluded("./docs/archive/old.md"));
+    assert!(exclude.is_excluded("notes/plan.draft.md"));
+    assert!(!exclude.is_excluded("docs/guide.md"));
+
+    let index = make_forward_index(vec![
+        make_file_entry("docs/archive/old.md"),
+        make_file_entry("docs/guide.md"),
+    ]);
+    assert_eq!(exclude.count_excluded(&index, |_| true), 1);
+    assert_eq!(exclude.count_excluded(&index, |_| false), 0);
+    assert!(!PathScope::default().is_excluded("docs/archive/old.md"));
+    assert!(compile_path_scope(&[], &["a{b".to_string()], &[]).is_err());
+    assert_eq!(
+        exclude.describe().as_deref(),
+        Some("exclude docs/archive/**, *.draft.md")
+    );
+    assert_eq!(PathScope::default().describe(), None);
 
-    let custom_path = "tmp-vocabulary-stopwords.txt";
-    fs::write(custom_path, "custom\nThe\nvocab-test\n").unwrap();
-    let merged_words = load_vocabulary_stopwords(Some(Path::new(custom_path)), true).unwrap();
+    // Include narrows first; exclude then only counts included documents
+    let scoped = compile_path_scope(
+        &["docs/archive/**".to_string(), "docs/guide.md".to_string()],
+        &["docs/guide.md".to_string(), "notes/**".to_string()],
+        &[],
+    )
+    .unwrap();
+    assert!(!scoped.is_excluded("docs/archive/old.md"));
+    assert!(scoped.is_excluded("docs/guide.md"));
+    assert!(scoped.is_excluded("docs/other.md"));
+    let index = make_forward_index(vec![
+        make_file_entry("docs/archive/old.md"),
+        make_file_entry("docs/guide.md"),
+        make_file_entry("notes/todo.md"),
+    ]);
+    assert_eq!(scoped.count_excluded(&index, |_| true), 1);
+}
 
-    fs::remove_file(custom_path).unwrap();
-    assert!(merged_words.contains("custom"));
-    assert!(merged_words.contains("the"));
-    assert!(merged_words.contains("vocab-test"));
+#[test]
+fn test_top_body_terms_skips_heading_occurrences() {
+    let mut entry = make_file_entry("docs/guide.md");
+    entry.term_frequencies = HashMap::from([
+        ("deploy".to_string(), 4),
+        ("gateway".to_string(), 2),
+        ("guide".to_string(), 1),
+        ("rollback".to_string(), 2),
+    ]);
+    entry.heading_term_frequencies =
+        HashMap::from([("deploy".to_string(), 1), ("guide".to_string(), 1)]);
+
+    assert_eq!(
+        top_body_terms(&entry, 10),
+        vec![
+            ("deploy".to_string(), 3),
+            ("gateway".to_string(), 2),
+            ("rollback".to_string(), 2),
+        ]
+    );
+    assert_eq!(top_body_terms(&entry, 1).len(), 1);
 }
 
 #[test]
-fn test_load_vocabulary_stopwords_can_disable_defaults() {
-    let stopwords = load_vocabulary_stopwords(None, false).unwrap();
-    assert!(!stopwords.contains("the"));
-    assert!(!stopwords.contains("and"));
-    assert!(stopwords.is_empty());
+fn test_path_scope_type_filter() {
+    let scope = compile_path_scope(&[], &[], &[".MD".to_string(), "rst".to_string()]).unwrap();
+    assert_eq!(scope.types, vec!["md", "rst"]);
+    assert!(!scope.is_excluded("docs/guide.md"));
+    assert!(!scope.is_excluded("./docs/Intro.RST"));
+    assert!(scope.is_excluded("docs/notes.txt"));
+    assert!(scope.is_excluded("docs/Makefile"));
+    assert_eq!(scope.describe().as_deref(), Some("type md, rst"));
+
+    let index = make_forward_index(vec![
+        make_file_entry("docs/guide.md"),
+        make_file_entry("docs/notes.txt"),
+    ]);
+    assert_eq!(
+        scope.check_types(&index).unwrap_err(),
+        "Unknown --type 'rst'; the index has: md, txt"
+    );
+    let md_only = compile_path_scope(&[], &[], &["md".to_string()]).unwrap();
+    assert!(md_only.check_types(&index).is_ok());
+    assert!(PathScope::default().check_types(&index).is_ok());
 }
 
 #[test]
-fn test_build_auto_common_vocabulary_stopwords() {
-    let candidates = vec![
-        VocabularyCandidateTerm {
-            term: "build".into(),
-            surface: None,
-            term_freq: 12,
-            doc_freq: 2,
-            first_file: "a".into(),
-            first_line: 1,
-            first_heading: "Build".into(),
-        },
-        VocabularyCandidateTerm {
-            term: "yore".into(),
-            surface: None,
-            term_freq: 9,
-            doc_freq: 3,
-            first_file: "a".into(),
-            first_line: 1,
-            first_heading: "Yore".into(),
-        },
-        VocabularyCandidateTerm {
-            term: "indexer".into(),
-            surface: None,
-            term_freq: 8,
-            doc_freq: 5,
-            first_file: "a".into(),
-            first_line: 1,
-            first_heading: "Index".into(),
+fn test_merge_similar_sections_keeps_most_canonical_copy() {
+    let how_to = "To rotate credentials run the rotate script, wait for the health check, \
+                  then restart the gateway pods one at a time.";
+    let refined = |doc: &str, content: &str, canonicality: f64| RefinedSection {
+        section: SectionMatch {
+            doc_path: doc.to_string(),
+            heading: "Rotate credentials".to_string(),
+            line_start: 1,
+            line_end: 5,
+            bm25_score: 1.0,
+            content: content.to_string(),
+            canonicality,
+            via: None,
         },
+        truncated: false,
+        truncation_reasons: Vec::new(),
+        also_in: Vec::new(),
+    };
+
+    let sections = vec![
+        refined("docs/scratch/notes.md", how_to, 0.2),
+        refined(
+            "docs/other.md",
+            "Completely unrelated text about billing exports.",
+            0.5,
+        ),
+        refined("docs/runbook.md", how_to, 0.6),
+        refined("docs/guides/ops.md", &format!("{how_to}\n"), 0.5),
     ];
 
-    let common = build_auto_common_vocabulary_stopwords(&candidates, 2);
-    assert!(common.contains("build"));
-    assert!(common.contains("yore"));
-    assert_eq!(common.len(), 2);
-}
+    let merged = merge_similar_sections(sections, 0.9);
+    let docs: Vec<&str> = merged.iter().map(|r| r.section.doc_path.as_str()).collect();
+    // The merged copy takes the position of its highest-ranked occurrence.
+    assert_eq!(docs, vec!["docs/runbook.md", "docs/other.md"]);
+    assert_eq!(
+        merged[0].also_in,
+        vec![
+            "docs/scratch/notes.md".to_string(),
+            "docs/guides/ops.md".to_string()
+        ]
+    );
+    assert!(merged[1].also_in.is_empty());
+
+    // Long near-copies merge on SimHash similarity alone.
+    let long = (0..120)
+        .map(|i| format!("step{i}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let edited = long.replace("step60 ", "stepsixty ");
+    assert_ne!(long, edited);
+    let near = vec![
+        refined("docs/a.md", &long, 0.5),
+        refined("docs/b.md", &edited, 0.5),
+    ];
+    assert_eq!(merge_similar_sections(near, 0.9).len(), 1);
 
-#[test]
-fn test_is_hygienic_vocabulary_term() {
-    assert!(!is_hygienic_vocabulary_term("th"));
-    assert!(is_hygienic_vocabulary_term("yore"));
-    assert!(!is_hygienic_vocabulary_term("a1234567890"));
-    assert!(!is_hygienic_vocabulary_term("12345"));
-    assert!(!is_hygienic_vocabulary_term("v2.0"));
-    assert!(!is_hygienic_vocabulary_term("x"));
+    // Short sections only merge on exact matches.
+    let short = vec![
+        refined("docs/a.md", "See above", 0.5),
+        refined("docs/b.md", "See below", 0.5),
+    ];
+    assert_eq!(merge_similar_sections(short, 0.0).len(), 2);
 }
 
 #[test]
-fn test_apply_vocabulary_limit_preserves_total_and_truncates_terms() {
-    let terms = vec![
-        VocabularyTerm {
-            term: "alpha".into(),
-            score: 3.0,
-            count: 4,
-        },
-        VocabularyTerm {
-            term: "beta".into(),
-            score: 2.0,
-            count: 3,
-        },
-        VocabularyTerm {
-            term: "gamma".into(),
-            score: 1.0,
-            count: 2,
-        },
+fn test_distill_to_markdown_lists_sources_within_budget() {
+    let section = |doc: &str, start: usize, end: usize, canonicality: f64| SectionMatch {
+        doc_path: doc.to_string(),
+        heading: format!("Lines {start}-{end}"),
+        line_start: start,
+        line_end: end,
+        bm25_score: 1.0,
+        content: "Rollback drains the queue before switching traffic back.".to_string(),
+        canonicality,
+        via: None,
+    };
+    let sections = vec![
+        section("docs/runbook.md", 40, 52, 0.8),
+        section("docs/adr/ADR-002.md", 3, 9, 0.7),
+        section("docs/runbook.md", 10, 20, 0.8),
     ];
-    let (clipped, total) = apply_vocabulary_limit(terms, 2);
-    assert_eq!(total, 3);
-    assert_eq!(clipped.len(), 2);
-    assert_eq!(clipped[0].term, "alpha");
-    assert_eq!(clipped[1].term, "beta");
+
+    let sources = collect_sources(&sections);
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources[0].doc_path, "docs/runbook.md");
+    assert_eq!(sources[0].line_ranges, vec![[10, 20], [40, 52]]);
+
+    let digest = distill(
+        &sections,
+        "rollback",
+        8000,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+    assert!(digest.contains(
+        "## Sources\n\n- docs/runbook.md:10-20, 40-52 (canonical: 0.80)\n- docs/adr/ADR-002.md:3-9 (canonical: 0.70)\n"
+    ));
+
+    // Tight budgets reserve room for the manifest instead of overflowing.
+    for max_tokens in [150, 200, 260, 320] {
+        let digest = distill(
+            &sections,
+            "rollback",
+            max_tokens,
+            DigestFormat::Markdown,
+            &DigestScope::default(),
+            DEFAULT_CANONICALITY_WEIGHT,
+            TokenizerKind::Approx,
+        );
+        let body = &digest[..digest.find("\n## Metadata").unwrap()];
+        assert!(
+            count_tokens(body, TokenizerKind::Approx) <= max_tokens + 20,
+            "{max_tokens}: {}",
+            count_tokens(body, TokenizerKind::Approx)
+        );
+    }
 }
 
 #[test]
-fn test_render_vocabulary_lines() {
-    let terms = vec![
-        VocabularyTerm {
-            term: "alpha".into(),
-            score: 1.2,
-            count: 7,
-        },
-        VocabularyTerm {
-            term: "beta".into(),
-            score: 0.9,
-            count: 5,
-        },
+fn test_distill_packs_highest_scoring_sections_first() {
+    let section = |doc: &str, bm25_score: f64| SectionMatch {
+        doc_path: doc.to_string(),
+        heading: "Rollback".to_string(),
+        line_start: 1,
+        line_end: 10,
+        bm25_score,
+        content: format!("{doc} explains how rollback drains the queue. ").repeat(12),
+        canonicality: 0.5,
+        via: None,
+    };
+    // Collected with the weakest section first
+    let sections = vec![
+        section("docs/weak.md", 0.2),
+        section("docs/strong.md", 0.9),
+        section("docs/medium.md", 0.6),
     ];
-    assert_eq!(render_vocabulary_lines(&terms), "alpha\nbeta");
+    let plan = |max_tokens: usize| {
+        plan_digest(
+            &sections,
+            "rollback",
+            max_tokens,
+            DigestFormat::Markdown,
+            &DigestScope::default(),
+            DEFAULT_CANONICALITY_WEIGHT,
+            TokenizerKind::Approx,
+        )
+    };
+
+    // Room for everything but the weakest section's body: the two stronger
+    // sections fit whole and too little is left for a truncated third
+    let full_tokens = plan(100_000).used_tokens;
+    let budget = full_tokens - count_tokens(&sections[0].content, TokenizerKind::Approx);
+    let kept = plan(budget);
+    assert_eq!(kept.sections.len(), 2);
+    assert!(kept.sections.iter().all(|kept| !kept.truncated));
+
+    let digest = distill(
+        &sections,
+        "rollback",
+        budget,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
+    );
+
+    assert!(digest.contains("(from docs/strong.md)"), "{digest}");
+    assert!(digest.contains("(from docs/medium.md)"), "{digest}");
+    assert!(!digest.contains("(from docs/weak.md)"), "{digest}");
+    assert!(digest.contains("*[Content truncated due to token budget: 1 section dropped]*"));
+    // Kept sections stay in collection order
+    assert!(digest.find("(from docs/strong.md)") < digest.find("(from docs/medium.md)"));
 }
 
 #[test]
-fn test_render_vocabulary_prompt_normalizes_terms() {
-    let terms = vec![
-        VocabularyTerm {
-            term: "alpha beta".into(),
-            score: 1.0,
-            count: 2,
-        },
-        VocabularyTerm {
-            term: "gamma\x00delta".into(),
-            score: 1.0,
-            count: 2,
-        },
-        VocabularyTerm {
-            term: "  spaced   out  ".into(),
-            score: 1.0,
-            count: 2,
-        },
-    ];
+fn test_parse_repl_command_verbs_and_fallback() {
+    assert_eq!(parse_repl_command("   \n"), None);
+    assert_eq!(parse_repl_command("q"), Some(ReplCommand::Quit));
     assert_eq!(
-        render_vocabulary_prompt(&terms),
-        "alpha beta, gammadelta, spaced out"
+        parse_repl_command("query-json  kubernetes deploy \n"),
+        Some(ReplCommand::Query {
+            terms: Some("kubernetes deploy".to_string()),
+            json: true,
+        })
+    );
+    // A bare query re-runs the previous one.
+    assert_eq!(
+        parse_repl_command("query"),
+        Some(ReplCommand::Query {
+            terms: None,
+            json: false,
+        })
+    );
+    assert_eq!(parse_repl_command("limit"), Some(ReplCommand::Limit(None)));
+    assert_eq!(
+        parse_repl_command("limit 3"),
+        Some(ReplCommand::Limit(Some(3)))
+    );
+    assert!(matches!(
+        parse_repl_command("limit 0"),
+        Some(ReplCommand::Usage(_))
+    ));
+    assert_eq!(
+        parse_repl_command("backlinks docs/a.md"),
+        Some(ReplCommand::Backlinks("docs/a.md".to_string()))
+    );
+    assert!(matches!(
+        parse_repl_command("backlinks"),
+        Some(ReplCommand::Usage(_))
+    ));
+    assert_eq!(parse_repl_command("orphans"), Some(ReplCommand::Orphans));
+    // Unknown words are searched as-is.
+    assert_eq!(
+        parse_repl_command("rollback plan"),
+        Some(ReplCommand::Query {
+            terms: Some("rollback plan".to_string()),
+            json: false,
+        })
     );
 }
 
 #[test]
-fn test_vocabulary_term_json_shape() {
-    let result = VocabularyResult {
-        format: "json".into(),
-        limit: 2,
-        total: 3,
-        terms: vec![
-            VocabularyTerm {
-                term: "alpha".into(),
-                score: 2.0,
-                count: 7,
-            },
-            VocabularyTerm {
-                term: "beta".into(),
-                score: 1.1,
-                count: 4,
-            },
-        ],
-        stopwords: None,
-        used_default_stopwords: true,
-        auto_common_terms: None,
-        include_stemming: false,
+fn test_csv_field_quotes_only_when_needed() {
+    assert_eq!(csv_field("docs/plain.md"), "docs/plain.md");
+    assert_eq!(csv_field("docs/a, b.md"), "\"docs/a, b.md\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+}
+
+#[test]
+fn test_blend_section_bm25_ranks_sections_by_their_own_text() {
+    let index = ForwardIndex {
+        files: HashMap::new(),
+        indexed_at: "0".to_string(),
+        version: CURRENT_INDEX_VERSION,
+        source_root: String::new(),
+        avg_doc_length: 0.0,
+        idf_map: HashMap::from([("rollback".to_string(), 2.0)]),
+        stemmer: StemmerKind::Simple,
+        positions: false,
+        stop_words: StopWords::default(),
+        minhash_perms: DEFAULT_MINHASH_PERMS,
+        simhash_weighting: SimhashWeighting::Uniform,
+        build_scope: None,
     };
-    let json_value: serde_json::Value = serde_json::to_value(&result).unwrap();
-    assert_eq!(json_value["terms"][0]["term"], "alpha");
-    assert_eq!(json_value["terms"][0]["score"], 2.0);
-    assert_eq!(json_value["terms"][0]["count"], 7);
-    assert_eq!(json_value["terms"].as_array().unwrap().len(), 2);
+    let section = |heading: &str, content: &str| SectionMatch {
+        doc_path: "docs/ops.md".to_string(),
+        heading: heading.to_string(),
+        line_start: 1,
+        line_end: 5,
+        bm25_score: 1.0,
+        content: content.to_string(),
+        canonicality: 0.5,
+        via: None,
+    };
+    let mut sections = vec![
+        section(
+            "Overview",
+            "## Overview\nThe service handles billing requests for customers.",
+        ),
+        section(
+            "Rollback",
+            "## Rollback\nRollback the deploy with one command.",
+        ),
+    ];
+
+    let groups = vec![vec!["rollback".to_string()]];
+    blend_section_bm25(&mut sections, &groups, &index, DEFAULT_HEADING_BOOST);
+
+    // Both sections inherited the same document score; only the one that
+    // mentions the query term keeps more than the document share.
+    assert!((sections[0].bm25_score - 0.3).abs() < 1e-9);
+    assert!(sections[1].bm25_score > sections[0].bm25_score);
 }
 
 #[test]
-fn test_resolve_vocabulary_surface_prefers_heading_surface() {
-    let postings = vec![
-        ReverseEntry {
-            file: "notes.md".to_string(),
-            line: Some(10),
-            heading: Some("alpha term".to_string()),
-            level: None,
-        },
-        ReverseEntry {
-            file: "guide.md".to_string(),
-            line: Some(2),
-            heading: None,
-            level: None,
-        },
+fn test_doc_cache_reads_each_doc_once_per_run() {
+    let dir = std::env::temp_dir().join(format!(
+        "yore-test-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("adr-0001-rollback.md");
+    fs::write(
+        &file_path,
+        "# Context\n\nRollback is manual.\n\n## Decision\n\nAutomate rollback.\n\n## Consequences\n\nRollback is fast.\n",
+    )
+    .unwrap();
+    let file_path_str = file_path.to_string_lossy().to_string();
+
+    let section = |heading: &str, line_start: usize, line_end: usize| SectionFingerprint {
+        heading: heading.to_string(),
+        level: 2,
+        line_start,
+        line_end,
+        simhash: 0,
+    };
+    let mut entry = make_file_entry(&file_path_str);
+    entry.term_frequencies = HashMap::from([("rollback".to_string(), 3)]);
+    entry.doc_length = 9;
+    entry.section_fingerprints = vec![
+        section("Context", 1, 3),
+        section("Decision", 5, 7),
+        section("Consequences", 9, 11),
     ];
-    let forward = make_forward_index(vec![
-        make_file_entry("notes.md"),
-        FileEntry {
-            path: "guide.md".to_string(),
-            size_bytes: 0,
-            line_count: 0,
-            headings: Vec::new(),
-            keywords: vec!["term".to_string(), "other".to_string()],
-            body_keywords: vec!["term".to_string()],
-            links: Vec::new(),
-            simhash: 0,
-            term_frequencies: HashMap::new(),
-            doc_length: 0,
-            minhash: Vec::new(),
-            section_fingerprints: Vec::new(),
-            adr_references: Vec::new(),
-        },
-    ]);
-    let resolved = resolve_vocabulary_surface("term", &postings, Some(&forward)).unwrap();
-    assert_eq!(resolved, "term");
+    let mut index = make_forward_index(vec![entry]);
+    index.avg_doc_length = 9.0;
+    index.idf_map = HashMap::from([("rollback".to_string(), 1.0)]);
+
+    let docs = DocCache::default();
+    let search = SectionSearch {
+        index: &index,
+        docs: &docs,
+        max_sections: 10,
+        synonyms: &Synonyms::default(),
+        scope: &PathScope::default(),
+        canonicality_weight: DEFAULT_CANONICALITY_WEIGHT,
+        heading_boost: DEFAULT_HEADING_BOOST,
+    };
+    let primary = search_relevant_sections("rollback", &search);
+    assert_eq!(primary.len(), 3);
+
+    // Later lookups in the same run are served from the cache, not the disk.
+    fs::remove_file(&file_path).unwrap();
+    let entry = &index.files[&file_path_str];
+    let adr = select_sections_for_adr(&file_path_str, &index, &docs, entry, 3);
+    assert_eq!(adr.len(), 3);
+    assert!(adr[0].content.contains("Rollback is manual."));
+    assert!(DocCache::default()
+        .read(&index, &file_path_str, entry)
+        .is_none());
+
+    fs::remove_dir_all(&dir).ok();
 }
 
 #[test]
-fn test_resolve_vocabulary_surface_fallbacks_to_forward_index() {
-    let postings = vec![
-        ReverseEntry {
-            file: "notes.md".to_string(),
-            line: Some(10),
-            heading: None,
-            level: None,
-        },
-        ReverseEntry {
-            file: "guide.md".to_string(),
-            line: Some(2),
-            heading: None,
-            level: None,
-        },
+fn test_cluster_headings_groups_near_identical_spellings() {
+    let heading = |path: &str, line: usize, text: &str| (path.to_string(), line, text.to_string());
+    let headings = vec![
+        heading("docs/a.md", 1, "Getting Started"),
+        heading("docs/b.md", 3, "Getting started guide"),
+        heading("docs/c.md", 1, "Getting Started"),
+        heading("docs/c.md", 9, "Troubleshooting"),
+        heading("docs/d.md", 2, "Troubleshooting"),
+        heading("docs/d.md", 7, "Release Process"),
     ];
-    let forward = make_forward_index(vec![
-        FileEntry {
-            path: "notes.md".to_string(),
-            size_bytes: 0,
-            line_count: 0,
-            headings: Vec::new(),
-            keywords: vec!["word".to_string()],
-            body_keywords: vec![],
-            links: Vec::new(),
-            simhash: 0,
-            term_frequencies: HashMap::new(),
-            doc_length: 0,
-            minhash: Vec::new(),
-            section_fingerprints: Vec::new(),
-            adr_references: Vec::new(),
-        },
-        FileEntry {
-            path: "guide.md".to_string(),
-            size_bytes: 0,
-            line_count: 0,
-            headings: Vec::new(),
-            keywords: vec!["word".to_string()],
-            body_keywords: vec![],
-            links: Vec::new(),
-            simhash: 0,
-            term_frequencies: HashMap::new(),
-            doc_length: 0,
-            minhash: Vec::new(),
-            section_fingerprints: Vec::new(),
-            adr_references: Vec::new(),
-        },
-    ]);
-    let resolved = resolve_vocabulary_surface("word", &postings, Some(&forward)).unwrap();
-    assert_eq!(resolved, "word");
+
+    assert!(heading_similarity("Getting Started", "getting started") > 0.99);
+    assert!(heading_similarity("Getting Started", "Release Process") < 0.5);
+
+    let clusters = cluster_headings(&headings, 0.6, 2);
+    assert_eq!(clusters.len(), 1, "exact repeats alone are not clusters");
+    let cluster = &clusters[0];
+    assert_eq!(cluster.canonical, "Getting Started");
+    assert_eq!(
+        cluster.variants,
+        vec![
+            ("Getting Started".to_string(), 2),
+            ("Getting started guide".to_string(), 1),
+        ]
+    );
+    let locations: Vec<(&str, usize)> = cluster
+        .locations
+        .iter()
+        .map(|(path, line, _, _)| (path.as_str(), *line))
+        .collect();
+    assert_eq!(
+        locations,
+        [("docs/a.md", 1), ("docs/b.md", 3), ("docs/c.md", 1)]
+    );
+
+    assert!(cluster_headings(&headings, 0.6, 4).is_empty());
+}
+
+#[test]
+fn test_cluster_sections_is_independent_of_input_order() {
+    let section = |path: &str, line: usize, simhash: u64| SectionInfo {
+        file_path: path.to_string(),
+        heading: format!("{path}:{line}"),
+        line_start: line,
+        line_end: line + 5,
+        simhash,
+    };
+    // b is close to both a and c, which are not close to each other: the
+    // old greedy assignment split these differently depending on order.
+    let sections = vec![
+        section("docs/a.md", 1, 0x0000_0000_0000_0000),
+        section("docs/b.md", 1, 0x0000_0000_0000_00ff),
+        section("docs/c.md", 1, 0x0000_0000_0000_ffff),
+        section("docs/d.md", 1, 0xffff_ffff_ffff_ffff),
+        section("docs/e.md", 1, 0xffff_ffff_ffff_fff0),
+    ];
+
+    let expected = cluster_sections(sections.clone(), 0.85);
+    let orders: [[usize; 5]; 4] = [
+        [4, 3, 2, 1, 0],
+        [2, 0, 4, 1, 3],
+        [3, 1, 0, 4, 2],
+        [1, 4, 2, 3, 0],
+    ];
+    for order in orders {
+        let shuffled: Vec<SectionInfo> = order.iter().map(|&i| sections[i].clone()).collect();
+        assert_eq!(cluster_sections(shuffled, 0.85), expected);
+    }
+
+    assert_eq!(expected.len(), 2);
+    let paths: Vec<&str> = expected[0].files.iter().map(|f| f.0.as_str()).collect();
+    assert_eq!(paths, ["docs/a.md", "docs/b.md", "docs/c.md"]);
+    assert_eq!(expected[0].heading, "docs/a.md:1");
+    assert_eq!(expected[0].simhash, 0x0000_0000_0000_00ff);
+    assert_eq!(expected[1].files.len(), 2);
 }
 
 #[test]
-fn test_parse_query_terms_mixed_case() {
-    let terms = parse_query_terms("TeSt CaSe", true);
-    assert_eq!(terms, vec!["test".to_string(), "case".to_string()]);
+fn test_section_diff_picks_most_dissimilar_members() {
+    let section = |path: &str, simhash: u64| SectionInfo {
+        file_path: path.to_string(),
+        heading: "Setup".to_string(),
+        line_start: 1,
+        line_end: 4,
+        simhash,
+    };
+    let clusters = cluster_sections(
+        vec![
+            section("docs/a.md", 0x0000),
+            section("docs/b.md", 0x000f),
+            section("docs/c.md", 0x00ff),
+        ],
+        0.85,
+    );
+    assert_eq!(most_dissimilar_pair(&clusters[0]), Some((0, 2)));
+
+    let old = "# Setup\n\nInstall the tool.\nRun init.\n";
+    let new = "# Setup\n\nInstall the CLI.\nRun init.\n";
+    assert_eq!(
+        section_diff_lines(old, new),
+        [
+            " # Setup",
+            " ",
+            "-Install the tool.",
+            "+Install the CLI.",
+            " Run init.",
+        ]
+    );
+    assert!(section_diff_lines(old, old).is_empty());
 }
 
 #[test]
-fn test_parse_query_phrases() {
-    let parsed = parse_query("\"async migration\" plan", true);
-    assert_eq!(
-        parsed.terms,
+fn test_xref_config_token_budget() {
+    let xref = XrefConfig::default();
+    // 30% of 8000 is capped at 2000
+    assert_eq!(xref.token_budget(8000, 1000), 2000);
+    // 30% of 4000 is 1200
+    assert_eq!(xref.token_budget(4000, 0), 1200);
+    // Never more than what the primary sections leave over
+    assert_eq!(xref.token_budget(4000, 3500), 500);
+    assert_eq!(xref.token_budget(4000, 5000), 0);
+
+    let wide = XrefConfig {
+        fraction: 1.0,
+        max_tokens: 10_000,
+        ..XrefConfig::DEFAULT
+    };
+    assert_eq!(wide.token_budget(4000, 1000), 3000);
+    assert_eq!(xref.max_sections(&DocType::Adr), 3);
+    assert_eq!(xref.max_sections(&DocType::Design), 2);
+    let ops_heavy = XrefConfig {
+        ops_sections: 5,
+        ..XrefConfig::DEFAULT
+    };
+    assert_eq!(ops_heavy.max_sections(&DocType::Ops), 5);
+    assert_eq!(ops_heavy.max_sections(&DocType::Other), 2);
+}
+
+#[test]
+fn test_rank_refined_sections_puts_primary_before_xref() {
+    let section =
+        |doc_path: &str, heading: &str, bm25_score: f64, via: Option<&str>| SectionMatch {
+            doc_path: doc_path.to_string(),
+            heading: heading.to_string(),
+            line_start: 1,
+            line_end: 3,
+            bm25_score,
+            content: format!("## {heading}\n\nBody of {heading}."),
+            canonicality: 0.5,
+            via: via.map(str::to_string),
+        };
+    // Collected order: an xref section ahead of a strong primary match.
+    // Refinement must keep the xref's origin for the digest header.
+    let mut sections = apply_extractive_refiner(
         vec![
-            "async".to_string(),
-            "migration".to_string(),
-            "plan".to_string()
-        ]
+            section("docs/adr/0007.md", "Decision", 0.0, Some("docs/auth.md")),
+            section("docs/auth.md", "Token Rotation", 4.2, None),
+        ],
+        "rotation",
+        &StopWords::default(),
+        1000,
+        TokenizerKind::Approx,
     );
-    assert_eq!(parsed.phrases.len(), 1);
-    assert_eq!(
-        parsed.phrases[0].terms,
-        vec!["async".to_string(), "migration".to_string()]
+    assert_eq!(sections[0].section.via.as_deref(), Some("docs/auth.md"));
+    rank_refined_sections(&mut sections, DEFAULT_CANONICALITY_WEIGHT);
+    assert_eq!(sections[0].section.heading, "Token Rotation");
+
+    let digest_sections: Vec<SectionMatch> = sections.into_iter().map(|r| r.section).collect();
+    let digest = distill(
+        &digest_sections,
+        "rotation",
+        2000,
+        DigestFormat::Markdown,
+        &DigestScope::default(),
+        DEFAULT_CANONICALITY_WEIGHT,
+        TokenizerKind::Approx,
     );
+    let primary = digest.find("### Token Rotation").expect("primary header");
+    let xref = digest
+        .find("### Decision (from docs/adr/0007.md, via docs/auth.md)")
+        .expect("xref header names its origin");
+    assert!(primary < xref, "{digest}");
 }
 
 #[test]
-fn test_expand_from_files_args_supports_list() {
-    let dir = std::env::temp_dir().join(format!(
-        "yore-test-{}",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos()
-    ));
-    fs::create_dir_all(&dir).unwrap();
-    let list_path = dir.join("files.txt");
-    fs::write(&list_path, "docs/a.md\n\n docs/b.md\n").unwrap();
+fn test_collect_backlinks_walks_linkers_by_distance() {
+    let index = make_forward_index(vec![
+        make_linking_entry("docs/a.md", &["b.md"]),
+        make_linking_entry("docs/b.md", &["c.md"]),
+        make_linking_entry("docs/c.md", &["a.md", "#intro"]),
+        make_linking_entry("docs/d.md", &["c.md", "a.md"]),
+        make_linking_entry("docs/e.md", &["a.md"]),
+    ]);
+    let graph = build_link_graph(&index);
+
+    let summary = |depth| {
+        let (backlinks, internal) = collect_backlinks(&graph, "docs/c.md", depth);
+        let rows: Vec<(String, usize, Option<String>)> = backlinks
+            .into_iter()
+            .map(|b| (b.source_file, b.distance, b.via))
+            .collect();
+        (rows, internal)
+    };
 
-    let args = vec![
-        format!("@{}", list_path.to_string_lossy()),
-        "docs/c.md".to_string(),
-    ];
-    let expanded = expand_from_files_args(&args).unwrap();
+    let (direct, internal) = summary(1);
+    assert_eq!(internal, 1);
+    assert_eq!(
+        direct,
+        [
+            ("docs/b.md".to_string(), 1, None),
+            ("docs/d.md".to_string(), 1, None),
+        ]
+    );
 
+    // Each file appears once, at its shortest distance; the target's own
+    // link back into the chain is not followed
+    let (transitive, _) = summary(5);
     assert_eq!(
-        expanded,
-        vec![
-            "docs/a.md".to_string(),
-            "docs/b.md".to_string(),
-            "docs/c.md".to_string()
+        transitive,
+        [
+            ("docs/b.md".to_string(), 1, None),
+            ("docs/d.md".to_string(), 1, None),
+            ("docs/a.md".to_string(), 2, Some("docs/b.md".to_string())),
+            ("docs/e.md".to_string(), 3, Some("docs/a.md".to_string())),
         ]
     );
 }
 
 #[test]
-fn test_resolve_from_files_reports_missing() {
-    let index = make_forward_index(vec![make_file_entry("docs/a.md")]);
-    let inputs = vec!["./docs/a.md".to_string(), "docs/missing.md".to_string()];
-    let (resolved, missing) = resolve_from_files(&inputs, &index);
-    assert_eq!(resolved, vec!["docs/a.md".to_string()]);
-    assert_eq!(missing, vec!["docs/missing.md".to_string()]);
+fn test_build_link_graph_resolves_every_target_form() {
+    let guide = make_linking_entry(
+        "docs/guides/setup.md",
+        &[
+            "/docs/api.md",
+            "deploy.md#rollback",
+            "../architecture.md",
+            "#prerequisites",
+            "https://example.com/docs",
+        ],
+    );
+    let api = make_linking_entry("docs/api.md", &["./guides/setup.md"]);
+    let index = make_forward_index(vec![guide, api]);
+
+    let graph = build_link_graph(&index);
+    let edges: Vec<(&str, &str, Option<&str>)> = graph
+        .edges
+        .iter()
+        .map(|e| (e.source.as_str(), e.target.as_str(), e.anchor.as_deref()))
+        .collect();
+    assert_eq!(
+        edges,
+        [
+            ("docs/api.md", "docs/guides/setup.md", None),
+            ("docs/guides/setup.md", "docs/api.md", None),
+            (
+                "docs/guides/setup.md",
+                "docs/guides/deploy.md",
+                Some("rollback")
+            ),
+            ("docs/guides/setup.md", "docs/architecture.md", None),
+            (
+                "docs/guides/setup.md",
+                "docs/guides/setup.md",
+                Some("prerequisites")
+            ),
+        ]
+    );
+    assert!(graph.edges[4].is_self_link());
+    assert_eq!(graph.edges[4].raw_target, "#prerequisites");
+
+    // Consumers skip the self-link and keep only indexed targets
+    let counts = compute_inbound_link_counts(&index);
+    assert_eq!(counts.get("docs/guides/setup.md"), Some(&1));
+    assert_eq!(counts.get("docs/guides/deploy.md"), Some(&1));
+    let export = build_link_graph_edges(&index);
+    assert_eq!(export.len(), 2);
 }
 
 #[test]
-fn test_collect_sections_for_files_max_sections() {
-    let dir = std::env::temp_dir().join(format!(
-        "yore-test-{}",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos()
-    ));
-    fs::create_dir_all(&dir).unwrap();
-    let file_path = dir.join("doc.md");
-    fs::write(&file_path, "# Title\n\nBody\n\n## Sub\n\nMore").unwrap();
-    let file_path_str = file_path.to_string_lossy().to_string();
+fn test_build_external_graph_groups_links_by_host() {
+    assert_eq!(
+        external_link_host("https://user@Docs.Example.com:8443/a?b#c").as_deref(),
+        Some("docs.example.com")
+    );
+    assert_eq!(external_link_host("mailto:a@example.com"), None);
+    assert_eq!(external_link_host("guide.md"), None);
+
+    let index = make_forward_index(vec![
+        make_linking_entry(
+            "docs/a.md",
+            &["b.md", "https://example.com/x", "http://example.com/y"],
+        ),
+        make_linking_entry("docs/b.md", &["https://rust-lang.org/"]),
+    ]);
+    let (nodes, edges) = build_external_graph(&index);
 
-    let entry = FileEntry {
-        path: file_path_str.clone(),
-        size_bytes: 0,
-        line_count: 0,
-        headings: Vec::new(),
-        keywords: Vec::new(),
-        body_keywords: Vec::new(),
-        links: Vec::new(),
-        simhash: 0,
-        term_frequencies: HashMap::new(),
-        doc_length: 0,
-        minhash: Vec::new(),
-        section_fingerprints: vec![
-            SectionFingerprint {
-                heading: "Title".to_string(),
-                level: 1,
-                line_start: 1,
-                line_end: 3,
-                simhash: 0,
-            },
-            SectionFingerprint {
-                heading: "Sub".to_string(),
-                level: 2,
-                line_start: 5,
-                line_end: 6,
-                simhash: 0,
-            },
-        ],
-        adr_references: vec![],
+    let hosts: Vec<(&str, usize)> = nodes
+        .iter()
+        .map(|n| (n.id.as_str(), n.inbound_links))
+        .collect();
+    assert_eq!(hosts, [("example.com", 2), ("rust-lang.org", 1)]);
+    assert!(nodes.iter().all(|n| n.kind == GraphKind::External));
+    assert_eq!(edges.len(), 3);
+    assert!(edges.iter().all(|e| e.kind == GraphKind::External));
+    assert_eq!(edges[2].source, "docs/b.md");
+    assert_eq!(edges[2].target, "rust-lang.org");
+}
+
+#[test]
+fn test_build_merge_plan_splits_unique_and_duplicated_sections() {
+    let section = |heading: &str, line_start: usize, simhash: u64| SectionFingerprint {
+        heading: heading.to_string(),
+        level: 2,
+        line_start,
+        line_end: line_start + 4,
+        simhash,
     };
-    let index = make_forward_index(vec![entry]);
-    let sections = collect_sections_for_files(&[file_path_str], &index, "", 1);
-    assert_eq!(sections.len(), 1);
+    let mut canonical = make_file_entry("docs/guide.md");
+    canonical.section_fingerprints = vec![
+        section("Install", 1, 0x0000_0000_0000_0000),
+        section("Usage", 6, 0x00ff_00ff_00ff_00ff),
+    ];
+    let mut copy = make_file_entry("docs/old-guide.md");
+    copy.section_fingerprints = vec![
+        // One bit away from the canonical "Usage"
+        section("How to use", 1, 0x00ff_00ff_00ff_00fe),
+        section("Troubleshooting", 6, 0xffff_ffff_0000_00ff),
+    ];
+    let index = make_forward_index(vec![canonical, copy]);
+
+    let mut group = ConsolidationGroup {
+        canonical: "docs/guide.md".to_string(),
+        merge_into: vec!["docs/old-guide.md".to_string()],
+        canonical_score: 0.9,
+        avg_similarity: 0.8,
+        note: String::new(),
+        unique_sections: None,
+        duplicated_sections: None,
+    };
+    build_merge_plan(&index, &mut group, MERGE_PLAN_SECTION_THRESHOLD);
+
+    let duplicated = group.duplicated_sections.unwrap();
+    assert_eq!(duplicated.len(), 1);
+    assert_eq!(duplicated[0].heading, "How to use");
+    assert_eq!(duplicated[0].closest_canonical.as_deref(), Some("Usage"));
+    assert!(duplicated[0].similarity > 0.95);
+
+    let unique = group.unique_sections.unwrap();
+    assert_eq!(unique.len(), 1);
+    assert_eq!(unique[0].file, "docs/old-guide.md");
+    assert_eq!(unique[0].heading, "Troubleshooting");
+    assert_eq!(unique[0].lines, [6, 10]);
+    assert!(unique[0].similarity < MERGE_PLAN_SECTION_THRESHOLD);
 }
 
 #[test]
-fn test_build_mcp_handle_is_stable() {
-    let section = SectionMatch {
-        doc_path: "docs/aa-auth.md".to_string(),
-        heading: "Authentication Overview".to_string(),
-        line_start: 1,
-        line_end: 11,
-        bm25_score: 0.25,
-        content: "# Authentication Overview\n\nAuthentication flow".to_string(),
-        canonicality: 0.5,
+fn test_align_sections_pairs_by_heading_then_simhash() {
+    let section = |heading: &str, line_start: usize, simhash: u64| SectionFingerprint {
+        heading: heading.to_string(),
+        level: 2,
+        line_start,
+        line_end: line_start + 2,
+        simhash,
     };
+    let old = vec![
+        section("Install", 1, 0),
+        section("Usage", 4, 0x00ff_00ff_00ff_00ff),
+        section("Legacy flags", 7, 0xf0f0_f0f0_0000_0000),
+    ];
+    let new = vec![
+        // Renamed but nearly the same body as "Usage"
+        section("Getting started", 1, 0x00ff_00ff_00ff_00fe),
+        // Same heading, rewritten body
+        section("install", 4, 0xffff_0000_ffff_0000),
+        section("FAQ", 7, 0x0f0f_0f0f_ffff_ffff),
+    ];
 
-    let left = build_mcp_handle("authentication", &section);
-    let right = build_mcp_handle("authentication", &section);
+    let alignment = align_sections(&old, &new);
 
-    assert_eq!(left, right);
-    assert!(left.starts_with("ctx_"));
+    let pairs: Vec<(&str, &str, bool)> = alignment
+        .matched
+        .iter()
+        .map(|p| {
+            (
+                p.file1.heading.as_str(),
+                p.file2.heading.as_str(),
+                p.near_identical,
+            )
+        })
+        .collect();
+    assert_eq!(
+        pairs,
+        [
+            ("Install", "install", false),
+            ("Usage", "Getting started", true)
+        ]
+    );
+    assert_eq!(alignment.matched[0].similarity, 0.5);
+    assert_eq!(alignment.only_in_file1.len(), 1);
+    assert_eq!(alignment.only_in_file1[0].heading, "Legacy flags");
+    assert_eq!(alignment.only_in_file1[0].lines, [7, 9]);
+    assert_eq!(alignment.only_in_file2.len(), 1);
+    assert_eq!(alignment.only_in_file2[0].heading, "FAQ");
 }
 
 #[test]
-fn test_compute_index_fingerprint_deterministic() {
-    let mut files = HashMap::new();
-    files.insert(
-        "docs/auth.md".to_string(),
-        FileEntry {
-            path: "docs/auth.md".to_string(),
-            size_bytes: 100,
-            line_count: 10,
-            headings: Vec::new(),
-            keywords: Vec::new(),
-            body_keywords: Vec::new(),
-            links: Vec::new(),
-            simhash: 0,
-            term_frequencies: HashMap::new(),
-            doc_length: 0,
-            minhash: Vec::new(),
-            section_fingerprints: Vec::new(),
-            adr_references: Vec::new(),
-        },
+fn test_compute_stats_distribution() {
+    let mut files: Vec<FileEntry> = (1..=10)
+        .map(|i| {
+            let targets = vec!["a.md"; i % 3];
+            let mut entry = make_linking_entry(&format!("docs/{i}.md"), &targets);
+            entry.line_count = i * 10;
+            entry.minhash = vec![1];
+            entry
+        })
+        .collect();
+    files[0].headings = vec![Heading {
+        line: 1,
+        level: 1,
+        text: "Intro".to_string(),
+        anchor: None,
+    }];
+    files[1].minhash.clear();
+
+    let stats = compute_stats_distribution(&make_forward_index(files));
+    assert_eq!(stats.line_count_p50, 50);
+    assert_eq!(stats.line_count_p90, 90);
+    assert_eq!(stats.line_count_max, 100);
+    // Link counts cycle 1, 2, 0, ...: 1+2+0+1+2+0+1+2+0+1 = 10
+    assert_eq!(stats.avg_links_per_file, 1.0);
+    assert_eq!(stats.files_without_links, 3);
+    assert_eq!(stats.files_without_headings, 9);
+    assert_eq!(stats.files_without_minhash, 1);
+    // The only file with headings has no section fingerprints
+    assert_eq!(stats.files_without_sections, 1);
+
+    assert_eq!(
+        compute_stats_distribution(&make_forward_index(Vec::new())),
+        StatsDistribution::default()
     );
+}
 
-    let index = ForwardIndex {
-        files,
-        indexed_at: "2026-03-26T00:00:00Z".to_string(),
-        version: 5,
-        source_root: String::new(),
-        avg_doc_length: 0.0,
-        idf_map: HashMap::new(),
+#[test]
+fn test_detect_index_drift_reports_changes_since_build() {
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let root = std::env::temp_dir().join(format!("yore-drift-{unique}"));
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("docs/same.md"), "12345").unwrap();
+    fs::write(root.join("docs/grown.md"), "1234567").unwrap();
+    fs::write(root.join("docs/unindexed.md"), "new").unwrap();
+
+    let entry = |path: &str, size_bytes: u64| {
+        let mut entry = make_file_entry(path);
+        entry.size_bytes = size_bytes;
+        entry
     };
+    let mut index = make_forward_index(vec![
+        entry("docs/same.md", 5),
+        entry("docs/grown.md", 3),
+        entry("docs/gone.md", 9),
+    ]);
+    index.source_root = root.to_string_lossy().to_string();
 
-    let left = compute_index_fingerprint(&index);
-    let right = compute_index_fingerprint(&index);
-    assert_eq!(left, right);
-    assert!(left.starts_with("idx_"));
-    assert_eq!(left.len(), 20); // "idx_" + 16 hex chars
+    let drift = crate::index::detect_index_drift(&index);
+    assert!(drift.has_drift());
+    assert!(!drift.added_checked);
+    assert!(drift.added.is_empty());
+    assert_eq!(drift.removed, ["docs/gone.md"]);
+    assert_eq!(
+        drift.modified,
+        [DriftedFile {
+            file: "docs/grown.md".to_string(),
+            indexed_size_bytes: 3,
+            current_size_bytes: 7,
+        }]
+    );
+
+    index.build_scope = Some(BuildScope {
+        path: "docs".to_string(),
+        extensions: vec!["md".to_string()],
+        skip_dirs: Vec::new(),
+        ..BuildScope::default()
+    });
+    let drift = crate::index::detect_index_drift(&index);
+    assert!(drift.added_checked);
+    assert_eq!(drift.added, ["docs/unindexed.md"]);
+
+    fs::remove_dir_all(root).unwrap();
 }
 
 #[test]
-fn test_compute_index_fingerprint_changes_with_index() {
-    let make_index = |indexed_at: &str| {
-        let mut files = HashMap::new();
-        files.insert(
-            "docs/auth.md".to_string(),
-            FileEntry {
-                path: "docs/auth.md".to_string(),
-                size_bytes: 100,
-                line_count: 10,
-                headings: Vec::new(),
-                keywords: Vec::new(),
-                body_keywords: Vec::new(),
-                links: Vec::new(),
-                simhash: 0,
-                term_frequencies: HashMap::new(),
-                doc_length: 0,
-                minhash: Vec::new(),
-                section_fingerprints: Vec::new(),
-                adr_references: Vec::new(),
-            },
-        );
-        ForwardIndex {
-            files,
-            indexed_at: indexed_at.to_string(),
-            version: 5,
-            source_root: String::new(),
-            avg_doc_length: 0.0,
-            idf_map: HashMap::new(),
-        }
-    };
+fn test_format_rfc3339_utc() {
+    assert_eq!(format_rfc3339_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_rfc3339_utc(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_rfc3339_utc(1_700_000_000), "2023-11-14T22:13:20Z");
+    assert_eq!(format_rfc3339_utc(4_102_444_799), "2099-12-31T23:59:59Z");
+}
 
-    let fp1 = compute_index_fingerprint(&make_index("2026-03-26T00:00:00Z"));
-    let fp2 = compute_index_fingerprint(&make_index("2026-03-27T00:00:00Z"));
-    assert_ne!(fp1, fp2);
+#[test]
+fn test_parse_timestamp_reads_epoch_and_rfc3339() {
+    for secs in [0, 951_782_400, 1_700_000_000, 4_102_444_799] {
+        assert_eq!(parse_timestamp(&format_rfc3339_utc(secs)), Some(secs));
+    }
+    // Indexes built before timestamps were RFC 3339 stored epoch seconds
+    assert_eq!(parse_timestamp("1700000000"), Some(1_700_000_000));
+    assert_eq!(
+        parse_timestamp("2023-11-15T00:13:20.250+02:00"),
+        Some(1_700_000_000)
+    );
+    assert_eq!(
+        parse_timestamp("2023-11-14 17:13:20-05:00"),
+        Some(1_700_000_000)
+    );
+    for invalid in [
+        "",
+        "now",
+        "2023-13-01T00:00:00Z",
+        "2023-11-14T22:13:20",
+        "1969-12-31T23:59:59Z",
+    ] {
+        assert_eq!(parse_timestamp(invalid), None, "{invalid}");
+    }
+
+    assert_eq!(display_timestamp("1700000000"), "2023-11-14 22:13:20 UTC");
+    assert_eq!(display_timestamp("unknown"), "unknown");
 }
 
 #[test]
-fn test_generate_trace_id_format() {
-    let id = generate_trace_id("test-seed");
-    assert!(id.starts_with("trc_"));
-    assert_eq!(id.len(), 20); // "trc_" + 16 hex chars
+fn test_assemble_options_validate_rejects_out_of_range_settings() {
+    let base = || AssembleOptions {
+        tokenizer: TokenizerKind::Approx,
+        ..AssembleOptions::default()
+    };
+    let check = |options: AssembleOptions, flag: &str| {
+        let err = options
+            .validate()
+            .err()
+            .unwrap_or_else(|| panic!("{flag} should be rejected"))
+            .to_string();
+        assert!(err.contains(&format!("{flag} must be")), "{err}");
+    };
+
+    check(
+        AssembleOptions {
+            canonicality_weight: 1.5,
+            ..base()
+        },
+        "--canonicality-weight",
+    );
+    check(
+        AssembleOptions {
+            heading_boost: -1.0,
+            ..base()
+        },
+        "--heading-boost",
+    );
+    let mut options = base();
+    options.xref.fraction = 2.0;
+    check(options, "--xref-fraction");
+    check(
+        AssembleOptions {
+            dedup_threshold: Some(-0.1),
+            ..base()
+        },
+        "--dedup-threshold",
+    );
+
+    // Valid settings reach selection, which finds nothing in an empty index
+    assert!(base().validate().is_ok());
+    let index = make_forward_index(Vec::new());
+    let docs = DocCache::default();
+    assert!(matches!(
+        assemble_context("rollback", &[], &base(), &index, &docs, Path::new(".yore")),
+        Err(ContextSelectionIssue::NoSearchableTerms | ContextSelectionIssue::NoRelevantSections(_))
+    ));
 }
@@ -1,16 +1,23 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::util::{default_query_stop_words, normalize_path};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Question {
     pub id: usize,
     pub q: String,
+    #[serde(default)]
     pub expect: Vec<String>,
     #[serde(default)]
     pub min_hits: Option<usize>,
     #[serde(default)]
     pub relevant_docs: Option<Vec<String>>,
+    /// Source files the assembled context must draw sections from
+    #[serde(default)]
+    pub expect_files: Vec<String>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -27,6 +34,22 @@ pub struct RankingMetrics {
     pub ndcg_at_k: Vec<MetricAtK>,
 }
 
+/// File-level precision/recall of an assembled context against `expect_files`.
+#[derive(Serialize, Debug, Clone)]
+pub struct FileCoverage {
+    pub precision: f64,
+    pub recall: f64,
+    pub found: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AggregateFileCoverage {
+    pub questions_with_expect_files: usize,
+    pub mean_precision: f64,
+    pub mean_recall: f64,
+}
+
 #[derive(Serialize, Debug)]
 pub struct AggregateRankingMetrics {
     pub questions_with_relevance: usize,
@@ -42,10 +65,13 @@ pub struct EvalResult {
     pub question: String,
     pub hits: usize,
     pub total: usize,
+    /// Expected substrings absent from the digest
+    pub missed: Vec<String>,
     pub passed: bool,
     pub tokens: usize,
     pub ranked_docs: Vec<String>,
     pub ranking: Option<RankingMetrics>,
+    pub files: Option<FileCoverage>,
     pub digest: String,
 }
 
@@ -73,6 +99,9 @@ pub enum LinkKind {
     ExternalReference,
     AnchorMissing,
     AnchorUnverified,
+    AnchorDuplicate,
+    ExternalBroken,
+    WikiAmbiguous,
 }
 
 #[derive(Serialize, Debug)]
@@ -93,16 +122,197 @@ pub struct LinkCheckSummary {
     pub by_kind: Vec<LinkSummaryByKind>,
 }
 
+/// Link health rolled into one number for dashboards and CI gates.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LinkHealth {
+    /// Broken links over checked (non-suppressed) links, capped at 1.0
+    pub broken_ratio: f64,
+    /// Issue count per link kind, informational kinds included
+    pub by_kind: BTreeMap<String, usize>,
+    /// 0–100, where 100 means no weighted link issues
+    pub score: f64,
+}
+
 #[derive(Serialize, Debug)]
 pub struct LinkCheckResult {
     pub total_links: usize,
     pub valid_links: usize,
     pub broken_links: usize,
+    /// Links skipped because they match an `--ignore-file` allowlist entry
+    pub suppressed_links: usize,
+    pub health: LinkHealth,
     pub broken: Vec<BrokenLink>,
+    /// Heading slugs that collide within a file; not links, so not counted above
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicate_anchors: Vec<DuplicateAnchor>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub summary: Option<LinkCheckSummary>,
 }
 
+/// Headings within one file that produce the same GitHub slug; links to
+/// the anchor can only reach the first of them.
+#[derive(Serialize, Debug, Clone)]
+pub struct DuplicateAnchor {
+    pub file: String,
+    pub anchor: String,
+    /// Line of every heading with this slug, in file order
+    pub lines: Vec<usize>,
+    pub error: String,
+}
+
+/// Known-bad links to leave out of link checks, loaded from `--ignore-file`.
+///
+/// Entries name a link either by `source_file:line` or by
+/// `source_file:target`, with source paths stored normalized.
+#[derive(Debug, Default, Clone)]
+pub struct LinkAllowlist {
+    pub lines: HashSet<(String, usize)>,
+    pub targets: HashSet<(String, String)>,
+}
+
+impl LinkAllowlist {
+    /// Whether a link in the (normalized) `source` at `line` with `target`
+    /// is allowlisted
+    pub fn suppresses(&self, source: &str, line: usize, target: &str) -> bool {
+        self.lines.contains(&(source.to_string(), line))
+            || self
+                .targets
+                .contains(&(source.to_string(), target.to_string()))
+    }
+
+    /// Whether `link` in the (normalized) `source` is allowlisted by its
+    /// target or, for wiki links, by `[[Page]]`
+    pub fn suppresses_link(&self, source: &str, link: &Link) -> bool {
+        self.suppresses(source, link.line, &link.target)
+            || link
+                .wiki
+                .as_ref()
+                .is_some_and(|page| self.suppresses(source, link.line, &format!("[[{page}]]")))
+    }
+}
+
+/// Weights for blending keyword Jaccard, SimHash and MinHash similarity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimilarityWeights {
+    pub jaccard: f64,
+    pub simhash: f64,
+    pub minhash: f64,
+}
+
+impl SimilarityWeights {
+    /// Blend used by `dupes` and `suggest-consolidation`
+    pub const DUPES: Self = Self {
+        jaccard: 0.4,
+        simhash: 0.3,
+        minhash: 0.3,
+    };
+
+    /// Blend used by `similar` and `diff`
+    pub const SIMILAR: Self = Self {
+        jaccard: 0.6,
+        simhash: 0.4,
+        minhash: 0.0,
+    };
+
+    /// Apply `--w-*` overrides on top of `defaults`, normalized to sum to 1.0.
+    ///
+    /// Without overrides the defaults are returned untouched, so scores stay
+    /// bit-for-bit identical to the built-in blend.
+    pub fn with_overrides(
+        defaults: Self,
+        jaccard: Option<f64>,
+        simhash: Option<f64>,
+        minhash: Option<f64>,
+    ) -> Result<Self, String> {
+        if jaccard.is_none() && simhash.is_none() && minhash.is_none() {
+            return Ok(defaults);
+        }
+        let weights = Self {
+            jaccard: jaccard.unwrap_or(defaults.jaccard),
+            simhash: simhash.unwrap_or(defaults.simhash),
+            minhash: minhash.unwrap_or(defaults.minhash),
+        };
+        let parts = [weights.jaccard, weights.simhash, weights.minhash];
+        if parts.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err("Similarity weights must be non-negative numbers".to_string());
+        }
+        let total: f64 = parts.iter().sum();
+        if total <= 0.0 {
+            return Err("At least one similarity weight must be positive".to_string());
+        }
+        Ok(Self {
+            jaccard: weights.jaccard / total,
+            simhash: weights.simhash / total,
+            minhash: weights.minhash / total,
+        })
+    }
+}
+
+/// Per-signal and blended similarity between two documents
+#[derive(Debug, Clone, Copy)]
+pub struct SimilarityScores {
+    pub jaccard: f64,
+    pub simhash: f64,
+    pub minhash: f64,
+    pub combined: f64,
+}
+
+/// Settings for the opt-in `check-links --check-external` network pass
+#[derive(Debug, Clone)]
+pub struct ExternalCheckOptions {
+    pub timeout_secs: u64,
+    pub concurrency: usize,
+}
+
+/// Settings for one link check run (`check-links`, `check --links`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkCheckOptions<'a> {
+    /// Root for resolving absolute link paths; defaults to the index's source root
+    pub root: Option<&'a Path>,
+    pub include_summary: bool,
+    pub summary_only: bool,
+    /// Configured external repositories that may satisfy a link
+    pub external_paths: &'a [String],
+    /// Probe http(s) links over the network when set
+    pub external_check: Option<&'a ExternalCheckOptions>,
+    pub allowlist: Option<&'a LinkAllowlist>,
+    /// Lines of source shown around each broken link
+    pub context_lines: usize,
+}
+
+/// Mode and resolution settings for `fix-links`
+#[derive(Debug, Clone, Default)]
+pub struct FixLinksOptions {
+    pub dry_run: bool,
+    pub apply: bool,
+    /// Write proposed fixes to this file instead of applying them
+    pub propose: Option<PathBuf>,
+    /// Apply the decisions in a previously written proposal file
+    pub apply_decisions: Option<PathBuf>,
+    pub json: bool,
+    pub use_git_history: bool,
+    /// Resolve ambiguous basenames to the most canonical candidate
+    pub prefer_canonical: bool,
+}
+
+/// Cached outcome of probing one external URL
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExternalLinkStatus {
+    /// HTTP status code, if the server answered
+    pub status: Option<u16>,
+    /// Transport error (DNS, TLS, timeout) when there was no response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamp of the probe
+    pub checked_at: u64,
+}
+
+impl ExternalLinkStatus {
+    pub fn is_broken(&self) -> bool {
+        self.status.is_none_or(|code| code >= 400)
+    }
+}
+
 // Diff output structure
 #[derive(Serialize, Debug)]
 pub struct DiffResult {
@@ -113,6 +323,37 @@ pub struct DiffResult {
     pub only_in_file1: Vec<String>,
     pub only_in_file2: Vec<String>,
     pub shared_headings: Vec<String>,
+    /// Original spelling of listed keywords whose display form differs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keyword_display: BTreeMap<String, String>,
+    pub sections: DiffSectionAlignment,
+}
+
+/// A section on one side of a `diff`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffSection {
+    pub heading: String,
+    /// Inclusive `[start, end]` line range
+    pub lines: [usize; 2],
+}
+
+/// Two sections paired across the compared files.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiffSectionPair {
+    pub file1: DiffSection,
+    pub file2: DiffSection,
+    /// SimHash similarity of the section bodies (0.0 to 1.0)
+    pub similarity: f64,
+    pub near_identical: bool,
+}
+
+/// Sections of the two files matched by heading text and SimHash.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct DiffSectionAlignment {
+    /// Matched pairs, in file 1 order
+    pub matched: Vec<DiffSectionPair>,
+    pub only_in_file1: Vec<DiffSection>,
+    pub only_in_file2: Vec<DiffSection>,
 }
 
 #[derive(Serialize, Debug)]
@@ -132,12 +373,61 @@ pub struct StatsResult {
     pub total_links: usize,
     pub index_version: u32,
     pub indexed_at: String,
+    pub distribution: StatsDistribution,
     pub top_keywords: Vec<KeywordCount>,
 }
 
+/// Per-file shape of the index, for tracking index health over time.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct StatsDistribution {
+    /// Line count percentiles (nearest rank)
+    pub line_count_p50: usize,
+    pub line_count_p90: usize,
+    pub line_count_max: usize,
+    pub avg_links_per_file: f64,
+    pub files_without_headings: usize,
+    pub files_without_links: usize,
+    /// Files with no MinHash signature; usually an index built by an older
+    /// version, so a rebuild is due
+    pub files_without_minhash: usize,
+    /// Files with headings but no section fingerprints, also a sign of a
+    /// stale index
+    pub files_without_sections: usize,
+}
+
+/// Differences between the index and the files on disk (`stats --check-drift`).
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct IndexDriftResult {
+    pub indexed_at: String,
+    /// Files a rebuild would index that the index lacks
+    pub added: Vec<String>,
+    /// Indexed files no longer on disk
+    pub removed: Vec<String>,
+    /// Indexed files whose size changed
+    pub modified: Vec<DriftedFile>,
+    /// False for indexes built before the build scope was recorded; added
+    /// files can't be detected until the next build
+    pub added_checked: bool,
+}
+
+impl IndexDriftResult {
+    pub fn has_drift(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty() || !self.modified.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DriftedFile {
+    pub file: String,
+    pub indexed_size_bytes: u64,
+    pub current_size_bytes: u64,
+}
+
 #[derive(Serialize, Debug)]
 pub struct KeywordCount {
     pub keyword: String,
+    /// Most frequent original spelling of `keyword`
+    pub display: String,
     pub count: usize,
 }
 
@@ -311,12 +601,14 @@ pub struct McpSearchOptions {
     pub max_results: usize,
     pub max_tokens: usize,
     pub max_bytes: usize,
+    pub tokenizer: TokenizerKind,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct McpFetchOptions {
     pub max_tokens: usize,
     pub max_bytes: usize,
+    pub tokenizer: TokenizerKind,
 }
 
 pub const DEFAULT_MCP_PROTOCOL_VERSION: &str = "2025-11-25";
@@ -396,6 +688,9 @@ pub struct MvResult {
     pub to: String,
     pub moved: bool,
     pub updated_files: Vec<String>,
+    pub updated_links: Vec<LinkFix>,
+    /// The moved file's own relative links, rewritten for its new directory
+    pub outbound_links: Vec<LinkFix>,
 }
 
 // FixReferences output structure
@@ -404,9 +699,25 @@ pub struct FixReferencesResult {
     pub mapping_file: String,
     pub mappings_count: usize,
     pub updated_files: Vec<String>,
+    pub file_changes: Vec<ReferenceFileChanges>,
     pub applied: bool,
 }
 
+/// Links changed in one file, per mapping that matched
+#[derive(Serialize, Debug)]
+pub struct ReferenceFileChanges {
+    pub file: String,
+    pub mappings: Vec<ReferenceMappingCount>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReferenceMappingCount {
+    pub from: String,
+    pub to: String,
+    pub kind: ReferenceMappingKind,
+    pub links: usize,
+}
+
 // Build output structure
 #[derive(Serialize, Debug)]
 pub struct BuildResult {
@@ -422,6 +733,59 @@ pub struct BuildResult {
     pub total_relations: Option<usize>,
 }
 
+// Assemble JSON output structure
+#[derive(Serialize, Debug)]
+pub struct AssembleJsonResult {
+    pub query: String,
+    /// `--type` extensions the search was limited to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<String>,
+    pub max_tokens: usize,
+    pub estimated_tokens: usize,
+    pub total_sections: usize,
+    pub truncated: bool,
+    pub sections: Vec<AssembleJsonSection>,
+    pub sources: Vec<AssembleSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Text rendering of an assembled digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestFormat {
+    /// Headed markdown digest with sources manifest and usage footer
+    Markdown,
+    /// Query line plus sections under `//` source comments, no markup
+    Text,
+}
+
+/// One document that contributed to an assembled digest.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AssembleSource {
+    pub doc_path: String,
+    /// Inclusive `[start, end]` line ranges, sorted by start line
+    pub line_ranges: Vec<[usize; 2]>,
+    pub canonicality: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AssembleJsonSection {
+    pub doc_path: String,
+    pub heading: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub bm25_score: f64,
+    pub canonicality: f64,
+    pub content: String,
+    pub estimated_tokens: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub doc_terms: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub also_in: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+}
+
 // Eval JSON output structure
 #[derive(Serialize, Debug)]
 pub struct EvalJsonResult {
@@ -430,20 +794,31 @@ pub struct EvalJsonResult {
     pub passed: usize,
     pub failed: usize,
     pub pass_rate: f64,
+    /// Expected substrings found across all questions, as a fraction
+    pub recall: f64,
+    pub mean_tokens: f64,
     pub results: Vec<EvalQuestionResult>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ranking_metrics: Option<AggregateRankingMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_metrics: Option<AggregateFileCoverage>,
 }
 
 #[derive(Serialize, Debug)]
 pub struct EvalQuestionResult {
+    pub id: usize,
     pub question: String,
     pub passed: bool,
+    pub hits: usize,
+    pub total: usize,
+    pub tokens: usize,
     pub expected: Vec<String>,
     pub found: Vec<String>,
     pub missing: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ranking: Option<RankingMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<FileCoverage>,
 }
 
 // Policy / taxonomy structures
@@ -457,6 +832,13 @@ pub struct PolicyRule {
     /// Substrings that must NOT appear in matching files
     #[serde(default)]
     pub must_not_contain: Vec<String>,
+    /// Regexes that must match somewhere in matching files (`^`/`$` match
+    /// at line boundaries)
+    #[serde(default)]
+    pub must_match: Vec<String>,
+    /// Regexes that must NOT match anywhere in matching files
+    #[serde(default)]
+    pub must_not_match: Vec<String>,
     /// Optional rule name (for clearer reporting)
     #[serde(default)]
     pub name: Option<String>,
@@ -481,9 +863,24 @@ pub struct PolicyRule {
     /// Forbidden markdown headings (by text, without leading '#')
     #[serde(default)]
     pub forbidden_headings: Vec<String>,
+    /// Deepest heading level allowed (e.g. 5 forbids `######`)
+    #[serde(default)]
+    pub max_heading_level: Option<usize>,
+    /// Forbid headings that skip levels on the way down (H1 followed by H3)
+    #[serde(default)]
+    pub no_skipped_levels: bool,
     /// Required markdown link targets (resolved relative to file)
     #[serde(default)]
     pub must_link_to: Vec<String>,
+    /// Frontmatter keys that must be present with a non-empty value
+    #[serde(default)]
+    pub require_frontmatter: Vec<String>,
+    /// Minimum number of indexed files the pattern must match
+    #[serde(default)]
+    pub min_files: Option<usize>,
+    /// Maximum number of indexed files the pattern may match
+    #[serde(default)]
+    pub max_files: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -502,11 +899,35 @@ pub struct PolicyViolation {
     pub kind: String,
 }
 
+/// How one file fared against one policy rule, for `policy --explain`.
+#[derive(Serialize, Debug)]
+pub struct PolicyFileOutcome {
+    pub file: String,
+    pub passed: bool,
+    /// Violation messages; empty when the file passed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<String>,
+}
+
+/// Every file a policy rule's pattern matched, passing or not.
+#[derive(Serialize, Debug)]
+pub struct PolicyRuleExplanation {
+    pub rule: String,
+    pub pattern: String,
+    /// Rule-wide failures such as `min_files` / `max_files` bounds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<String>,
+    pub files: Vec<PolicyFileOutcome>,
+}
+
 #[derive(Serialize, Debug)]
 pub struct PolicyCheckResult {
     pub policy_file: String,
     pub total_violations: usize,
     pub violations: Vec<PolicyViolation>,
+    /// Per-rule match details, only with `--explain`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<PolicyRuleExplanation>>,
 }
 
 #[derive(Serialize, Debug, Default)]
@@ -524,6 +945,107 @@ pub struct StaleFile {
     pub file: String,
     pub days_since_modified: u64,
     pub inbound_links: usize,
+    /// Required conditions the file met (`age`, `inlinks`, `orphaned`)
+    pub reasons: Vec<String>,
+}
+
+/// A condition `stale --require` can combine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleCondition {
+    /// Not modified for at least `days`
+    Age,
+    /// Inbound link count within `min_inlinks..=max_inlinks`
+    Inlinks,
+    /// No inbound links left (orphaned-stale)
+    Orphaned,
+}
+
+impl StaleCondition {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "age" => Some(Self::Age),
+            "inlinks" => Some(Self::Inlinks),
+            "orphaned" => Some(Self::Orphaned),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Age => "age",
+            Self::Inlinks => "inlinks",
+            Self::Orphaned => "orphaned",
+        }
+    }
+}
+
+/// Conditions a file must meet to be reported as stale: all of them
+/// (`age AND inlinks`) or any of them (`age OR orphaned`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleRequirement {
+    pub conditions: Vec<StaleCondition>,
+    pub any: bool,
+}
+
+impl StaleRequirement {
+    /// Parse `--require`: condition names joined by `AND` or by `OR`
+    /// (case-insensitive), but not both.
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let tokens: Vec<&str> = expr.split_whitespace().collect();
+        if tokens.len().is_multiple_of(2) {
+            return Err(format!("Incomplete --require expression: {expr:?}"));
+        }
+        let mut conditions = Vec::new();
+        let mut operator: Option<String> = None;
+        for (i, token) in tokens.iter().enumerate() {
+            if i % 2 == 1 {
+                let op = token.to_uppercase();
+                if op != "AND" && op != "OR" {
+                    return Err(format!("Expected AND or OR in --require, got {token:?}"));
+                }
+                if operator.as_ref().is_some_and(|prev| *prev != op) {
+                    return Err("--require cannot mix AND and OR".to_string());
+                }
+                operator = Some(op);
+            } else {
+                conditions.push(StaleCondition::from_name(token).ok_or_else(|| {
+                    format!(
+                        "Unknown --require condition {token:?} (expected age, inlinks, or orphaned)"
+                    )
+                })?);
+            }
+        }
+        Ok(Self {
+            conditions,
+            any: operator.as_deref() == Some("OR"),
+        })
+    }
+
+    /// Render back in `--require` syntax.
+    pub fn describe(&self) -> String {
+        let names: Vec<&str> = self.conditions.iter().map(|c| c.name()).collect();
+        names.join(if self.any { " OR " } else { " AND " })
+    }
+}
+
+impl Default for StaleRequirement {
+    /// `age AND inlinks`, the behavior before `--require` existed.
+    fn default() -> Self {
+        Self {
+            conditions: vec![StaleCondition::Age, StaleCondition::Inlinks],
+            any: false,
+        }
+    }
+}
+
+/// What `stale` looks for: the age threshold, the inbound link range, and
+/// how the conditions combine.
+#[derive(Debug, Clone, Default)]
+pub struct StaleCriteria {
+    pub days: u64,
+    pub min_inlinks: usize,
+    pub max_inlinks: Option<usize>,
+    pub require: StaleRequirement,
 }
 
 #[derive(Serialize, Debug)]
@@ -556,9 +1078,34 @@ pub struct HealthResult {
     pub files: Vec<HealthFileResult>,
 }
 
+/// Whether a graph node or edge stays inside the indexed docs.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphKind {
+    #[default]
+    Internal,
+    /// An external host, or a link out to one (`export-graph --include-external`)
+    External,
+}
+
+impl GraphKind {
+    pub fn is_internal(&self) -> bool {
+        *self == GraphKind::Internal
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct GraphNode {
+    /// Document path, or the host name for external nodes
     pub id: String,
+    #[serde(skip_serializing_if = "GraphKind::is_internal")]
+    pub kind: GraphKind,
+    pub line_count: usize,
+    pub size_bytes: u64,
+    /// Path-based canonicality score (0.0–1.0), as used by `assemble`
+    pub canonicality: f64,
+    /// Links into this document from other documents
+    pub inbound_links: usize,
 }
 
 #[derive(Serialize, Debug)]
@@ -567,6 +1114,35 @@ pub struct GraphEdge {
     pub target: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub anchor: Option<String>,
+    #[serde(skip_serializing_if = "GraphKind::is_internal")]
+    pub kind: GraphKind,
+}
+
+/// A link between indexed documents, resolved against its source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkGraphEdge {
+    /// Forward index key of the linking document
+    pub source: String,
+    /// Normalized path the link resolves to; may not be indexed
+    pub target: String,
+    pub anchor: Option<String>,
+    pub text: String,
+    /// Link target as written in the source
+    pub raw_target: String,
+}
+
+impl LinkGraphEdge {
+    /// Anchor-only links and links back to the source itself.
+    pub fn is_self_link(&self) -> bool {
+        normalize_path(Path::new(&self.source)) == self.target
+    }
+}
+
+/// Every internal link in the index, with external URLs dropped.
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    /// Ordered by source path, then by position in the source
+    pub edges: Vec<LinkGraphEdge>,
 }
 
 #[derive(Serialize, Debug)]
@@ -575,6 +1151,34 @@ pub struct GraphExport {
     pub edges: Vec<GraphEdge>,
 }
 
+/// A document line that best matches the query, for `query --snippets`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QuerySnippet {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    /// Words on the line whose stem matches a query term
+    pub hits: usize,
+}
+
+/// A heading containing a query term, from the reverse index.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HeadingHit {
+    /// 1-based line number
+    pub line: usize,
+    pub heading: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LinkPathResult {
+    pub from: String,
+    pub to: String,
+    pub undirected: bool,
+    /// Number of links followed, or `None` when no path exists.
+    pub hops: Option<usize>,
+    pub path: Vec<String>,
+}
+
 // Relation extraction structs (YEH-004)
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -628,6 +1232,28 @@ pub struct ConsolidationGroup {
     pub canonical_score: f64,
     pub avg_similarity: f64,
     pub note: String,
+    /// With `--plan`: sections with no close match in the canonical doc,
+    /// which would need to be carried over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unique_sections: Option<Vec<MergePlanSection>>,
+    /// With `--plan`: sections the canonical doc already covers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicated_sections: Option<Vec<MergePlanSection>>,
+}
+
+/// A section of a file slated for merging, compared against the canonical
+/// doc's sections by SimHash.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MergePlanSection {
+    pub file: String,
+    pub heading: String,
+    /// Inclusive `[start, end]` line range
+    pub lines: [usize; 2],
+    /// Heading of the most similar canonical section, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closest_canonical: Option<String>,
+    /// SimHash similarity to that section (0.0 when the canonical has none)
+    pub similarity: f64,
 }
 
 #[derive(Serialize, Debug)]
@@ -641,6 +1267,33 @@ pub struct LinkFix {
     pub file: String,
     pub old_target: String,
     pub new_target: String,
+    /// Set when `--prefer-canonical` picked this target among several candidates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<CanonicalResolution>,
+}
+
+/// How `fix-links --prefer-canonical` chose between candidates sharing a filename
+#[derive(Serialize, Debug, Clone)]
+pub struct CanonicalResolution {
+    pub chosen: String,
+    pub score: f64,
+    pub reasons: Vec<String>,
+    pub rejected: Vec<RejectedCandidate>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RejectedCandidate {
+    pub path: String,
+    pub score: f64,
+}
+
+/// `fix-links --json` output, for both regular and `--apply-decisions` runs
+#[derive(Serialize, Debug)]
+pub struct FixLinksResult {
+    pub fixes: Vec<LinkFix>,
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 // Proposal structures for agent-friendly fix-links
@@ -669,12 +1322,32 @@ pub struct Backlink {
     pub link_text: String,
     pub link_target: String,
     pub anchor: Option<String>,
+    /// Hops from the source to the target; 1 for direct links
+    pub distance: usize,
+    /// Linked file one hop closer to the target; `None` for direct links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ReferenceMapping {
     pub from: String,
     pub to: String,
+    #[serde(default)]
+    pub kind: ReferenceMappingKind,
+}
+
+/// How a reference mapping matches link targets
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceMappingKind {
+    /// Target equals `from` exactly
+    #[default]
+    Literal,
+    /// Target starts with `from`; that prefix is replaced by `to`
+    Prefix,
+    /// `from` is a regex; matches are replaced by `to` (`$1`, `${name}` expand)
+    Regex,
 }
 
 #[derive(Debug, Deserialize)]
@@ -686,8 +1359,26 @@ pub struct ReferenceMappingConfig {
 #[derive(Serialize, Debug)]
 pub struct BacklinksResult {
     pub target_file: String,
+    /// Maximum distance searched (`--depth`)
+    pub depth: usize,
     pub total_backlinks: usize,
     pub backlinks: Vec<Backlink>,
+    /// Links from the target to itself (e.g. `[x](#section)`); not backlinks
+    pub internal_anchors: usize,
+}
+
+/// `backlinks` for every indexed file matching a glob, grouped by target
+#[derive(Serialize, Debug)]
+pub struct BacklinksGlobResult {
+    pub pattern: String,
+    pub depth: usize,
+    /// Indexed files matching `pattern`
+    pub matched_files: usize,
+    pub total_backlinks: usize,
+    /// Backlinks per matching file; files nothing links to map to `[]`
+    pub targets: BTreeMap<String, Vec<Backlink>>,
+    /// Self-links summed over all matching files
+    pub internal_anchors: usize,
 }
 
 // Orphans structures
@@ -702,6 +1393,9 @@ pub struct OrphanFile {
 pub struct OrphansResult {
     pub total_orphans: usize,
     pub orphans: Vec<OrphanFile>,
+    /// Roots used by `--reachable-from`; orphans are files unreachable from them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reachable_from: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -743,8 +1437,10 @@ pub struct FileEntry {
     pub body_keywords: Vec<String>, // keywords from full text
     pub links: Vec<Link>,
     pub simhash: u64, // content fingerprint
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_sorted_map")]
     pub term_frequencies: HashMap<String, usize>, // term counts for BM25
+    #[serde(default, serialize_with = "serialize_sorted_map")]
+    pub heading_term_frequencies: HashMap<String, usize>, // heading share of term_frequencies
     #[serde(default)]
     pub doc_length: usize, // total terms for BM25
     #[serde(default)]
@@ -753,6 +1449,28 @@ pub struct FileEntry {
     pub section_fingerprints: Vec<SectionFingerprint>, // NEW: section-level SimHash
     #[serde(default)]
     pub adr_references: Vec<AdrRef>,
+    #[serde(default, serialize_with = "serialize_sorted_map")]
+    pub frontmatter: HashMap<String, String>, // parsed YAML frontmatter fields
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    pub term_positions: HashMap<String, Vec<usize>>, // token offsets, built with --positions
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_sorted_set"
+    )]
+    pub code_terms: HashSet<String>, // unstemmed identifiers from inline code spans
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    pub keyword_display: HashMap<String, String>, // most frequent original spelling per keyword
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anchors: Vec<String>, // explicit `<a name/id>` and `{#id}` anchors outside headings
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -760,6 +1478,9 @@ pub struct Heading {
     pub line: usize,
     pub level: usize,
     pub text: String,
+    /// Explicit fragment id (HTML `id` attribute); others derive a slug
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -767,6 +1488,9 @@ pub struct Link {
     pub line: usize,
     pub text: String,
     pub target: String,
+    /// Page name for `[[Page]]` wiki links; `target` holds the resolved path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wiki: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -786,8 +1510,122 @@ pub struct ReverseEntry {
     pub level: Option<usize>,
 }
 
+/// Current `ForwardIndex::version`; binary indexes with another version are
+/// ignored and older JSON indexes load with a warning.
+/// Version 4 adds source_root metadata for portable file resolution.
+/// Version 5 adds per-file heading term frequencies for heading boosts.
+/// Version 6 adds inline code terms for code boosts.
+/// Version 7 adds explicit heading anchors for HTML files.
+/// Version 8 adds `<a id>` and `{#id}` anchors outside headings.
+pub const CURRENT_INDEX_VERSION: u32 = 8;
+
+/// How loading treats an index older than `CURRENT_INDEX_VERSION`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexVersionCheck {
+    /// Load it with a stderr warning
+    #[default]
+    Warn,
+    /// Refuse to load it (`--strict`)
+    Strict,
+    /// Load it silently, once a session has already warned
+    Quiet,
+}
+
+/// Index directory passed to a command, with how an outdated index found
+/// there is treated. Derefs to the directory path.
+#[derive(Debug, Clone)]
+pub struct IndexDir {
+    path: PathBuf,
+    pub version_check: IndexVersionCheck,
+}
+
+impl IndexDir {
+    pub fn new(path: impl Into<PathBuf>, version_check: IndexVersionCheck) -> Self {
+        IndexDir {
+            path: path.into(),
+            version_check,
+        }
+    }
+
+    /// The same directory, for later loads in a session that has already
+    /// reported an outdated index; `--strict` still applies.
+    #[must_use]
+    pub fn quiet(&self) -> Self {
+        let version_check = match self.version_check {
+            IndexVersionCheck::Strict => IndexVersionCheck::Strict,
+            _ => IndexVersionCheck::Quiet,
+        };
+        IndexDir::new(self.path.clone(), version_check)
+    }
+}
+
+impl std::ops::Deref for IndexDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for IndexDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Markup language of an indexed file, chosen by extension
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocFormat {
+    /// Markdown and plain text: `#` headings, `[text](url)` links
+    #[default]
+    Markdown,
+    /// AsciiDoc: `=` headings, `link:`/`xref:` macros
+    AsciiDoc,
+    /// reStructuredText: underlined (optionally overlined) headings
+    Rst,
+    /// HTML: `<h1>`–`<h6>` headings, `<a href>` links, text between tags
+    Html,
+}
+
+impl DocFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("adoc" | "asciidoc") => Self::AsciiDoc,
+            Some("rst" | "rest") => Self::Rst,
+            Some("html" | "htm") => Self::Html,
+            _ => Self::Markdown,
+        }
+    }
+}
+
+/// On-disk encoding of the forward index written by `build`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// Pretty-printed `forward_index.json`
+    #[default]
+    Json,
+    /// MessagePack `forward_index.bin` with a versioned header
+    Bin,
+}
+
+impl IndexFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "bin" => Some(Self::Bin),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ForwardIndex {
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub files: HashMap<String, FileEntry>,
     pub indexed_at: String,
     pub version: u32, // index version for compatibility
@@ -795,12 +1633,227 @@ pub struct ForwardIndex {
     pub source_root: String,
     #[serde(default)]
     pub avg_doc_length: f64, // NEW: average document length for BM25
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_sorted_map")]
     pub idf_map: HashMap<String, f64>, // NEW: IDF scores for BM25
+    #[serde(default)]
+    pub stemmer: StemmerKind, // stemmer used at build time; queries must match
+    #[serde(default)]
+    pub positions: bool, // whether term_positions were recorded for phrase queries
+    #[serde(default, skip_serializing_if = "StopWords::is_builtin")]
+    pub stop_words: StopWords, // stop words used at build time; queries must match
+    #[serde(default = "default_minhash_perms")]
+    pub minhash_perms: usize, // MinHash signature length chosen at build time
+    #[serde(default)]
+    pub simhash_weighting: SimhashWeighting, // how file SimHash shingles were weighted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_scope: Option<BuildScope>, // what `build` walked, for drift checks
+}
+
+/// The walk settings of a build, recorded so `stats --check-drift` can find
+/// files added since. Relative paths resolve against `source_root`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct BuildScope {
+    pub path: String,
+    /// Lowercased file extensions, sorted
+    pub extensions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<Vec<String>>,
+    pub skip_dirs: Vec<String>,
+}
+
+/// MinHash permutations per file unless `build --minhash-perms` says otherwise.
+pub const DEFAULT_MINHASH_PERMS: usize = 128;
+
+fn default_minhash_perms() -> usize {
+    DEFAULT_MINHASH_PERMS
+}
+
+/// Write a hash map in key order so identical builds produce identical files
+fn serialize_sorted_map<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Write a hash set in sorted order, for the same reason as `serialize_sorted_map`
+fn serialize_sorted_set<T, S>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Ord + Serialize,
+    S: Serializer,
+{
+    set.iter().collect::<BTreeSet<_>>().serialize(serializer)
+}
+
+/// Per-file indexing settings chosen at build time.
+#[derive(Debug, Clone)]
+pub struct IndexOptions {
+    pub stemmer: StemmerKind,
+    /// Record per-term token positions for phrase queries
+    pub positions: bool,
+    /// Words left out of keywords and term statistics
+    pub stop_words: StopWords,
+    /// Length of each file's MinHash signature
+    pub minhash_perms: usize,
+    /// Shingle weighting for file SimHash fingerprints
+    pub simhash_weighting: SimhashWeighting,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            stemmer: StemmerKind::default(),
+            positions: false,
+            stop_words: StopWords::default(),
+            minhash_perms: DEFAULT_MINHASH_PERMS,
+            simhash_weighting: SimhashWeighting::Idf,
+        }
+    }
+}
+
+/// Words skipped when extracting keywords, counting terms, and parsing
+/// queries. Defaults to the built-in English list; an index built with
+/// `--stop-words` stores its effective list instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+pub struct StopWords {
+    words: HashSet<String>,
+    builtin: bool,
+}
+
+impl StopWords {
+    /// A list that replaces the built-in one
+    pub fn custom(words: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            words: words.into_iter().map(|word| word.to_lowercase()).collect(),
+            builtin: false,
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        self.builtin
+    }
+}
+
+impl Default for StopWords {
+    fn default() -> Self {
+        Self {
+            words: default_query_stop_words()
+                .iter()
+                .map(|word| (*word).to_string())
+                .collect(),
+            builtin: true,
+        }
+    }
+}
+
+impl From<Vec<String>> for StopWords {
+    fn from(words: Vec<String>) -> Self {
+        Self::custom(words)
+    }
+}
+
+impl From<StopWords> for Vec<String> {
+    fn from(stop_words: StopWords) -> Self {
+        let mut words: Vec<String> = stop_words.words.into_iter().collect();
+        words.sort_unstable();
+        words
+    }
+}
+
+/// Token counting strategy used for context budgets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenizerKind {
+    /// Four bytes per token
+    #[default]
+    Approx,
+    /// OpenAI cl100k_base BPE (requires the `tiktoken` feature)
+    Cl100k,
+}
+
+impl TokenizerKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "approx" => Some(Self::Approx),
+            "cl100k" => Some(Self::Cl100k),
+            _ => None,
+        }
+    }
+}
+
+/// Stemming algorithm applied to keywords at index and query time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StemmerKind {
+    /// Naive suffix stripper (original behavior)
+    #[default]
+    Simple,
+    /// Snowball English (Porter2) stemmer
+    Porter,
+    /// No stemming; terms are only lowercased
+    None,
+}
+
+impl StemmerKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "simple" => Some(Self::Simple),
+            "porter" => Some(Self::Porter),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Simple => "simple",
+            Self::Porter => "porter",
+            Self::None => "none",
+        }
+    }
+}
+
+/// How shingles are weighted in each file's SimHash fingerprint.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SimhashWeighting {
+    /// Every shingle counts the same (indexes built before weighting)
+    #[default]
+    Uniform,
+    /// Shingles are weighted by the IDF of their words
+    Idf,
+}
+
+impl SimhashWeighting {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "uniform" => Some(Self::Uniform),
+            "idf" => Some(Self::Idf),
+            _ => None,
+        }
+    }
+}
+
+/// Shingle hashes and per-word terms of a file's prose, kept until IDF is
+/// known so `build` can compute an IDF-weighted SimHash.
+#[derive(Debug, Clone, Default)]
+pub struct SimhashFeatures {
+    pub shingles: Vec<u64>,
+    /// Indexed term of every word; shingle `i` covers `terms[i..i + 3]`
+    pub terms: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReverseIndex {
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub keywords: HashMap<String, Vec<ReverseEntry>>,
 }
 
@@ -849,6 +1902,7 @@ pub struct DocumentMetrics {
 pub struct DocumentMetricsIndex {
     pub indexed_at: String,
     pub version: u32,
+    #[serde(serialize_with = "serialize_sorted_map")]
     pub files: HashMap<String, DocumentMetrics>,
 }
 
@@ -888,6 +1942,9 @@ pub struct IndexProfileConfig {
     #[serde(default)]
     pub types: Vec<String>,
     pub output: Option<String>,
+    /// Directory names `build` never descends into; replaces the built-in
+    /// list when set
+    pub skip_dirs: Option<Vec<String>>,
 }
 
 /// Severity override for link checking based on path patterns
@@ -957,6 +2014,9 @@ pub struct SectionMatch {
     pub bm25_score: f64,
     pub content: String,
     pub canonicality: f64,
+    /// Doc whose cross-reference pulled this section in; `None` for
+    /// sections selected by the query itself
+    pub via: Option<String>,
 }
 
 pub const MCP_SCHEMA_VERSION: u32 = 1;
@@ -966,6 +2026,11 @@ pub struct ContextSelection {
     pub query_label: String,
     pub query_for_refiner: String,
     pub sections: Vec<SectionMatch>,
+    /// Documents matching the query that `--exclude` dropped before scoring
+    pub excluded_docs: usize,
+    /// Documents considered: the `--from-files` set, or every indexed
+    /// document inside the path scope
+    pub scanned_docs: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -981,14 +2046,235 @@ pub struct RefinedSection {
     pub section: SectionMatch,
     pub truncated: bool,
     pub truncation_reasons: Vec<String>,
+    /// Docs holding near-identical copies that were merged into this section
+    pub also_in: Vec<String>,
 }
 
 // Search / query types
 
+/// Compiled `--include` / `--exclude` globs and `--type` extensions,
+/// matched against indexed document paths. Include is applied first, then
+/// exclude; the extension filter applies to both.
+#[derive(Debug, Clone, Default)]
+pub struct PathScope {
+    pub include: globset::GlobSet,
+    pub exclude: globset::GlobSet,
+    pub include_patterns: Vec<String>,
+    pub exclude_patterns: Vec<String>,
+    /// Lowercased extensions without the leading dot; empty allows any
+    pub types: Vec<String>,
+}
+
+impl PathScope {
+    /// True when `path` (with any leading `./` ignored) is outside the
+    /// scope: its extension is not a requested type, no include glob
+    /// matches it, or an exclude glob does
+    pub fn is_excluded(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./");
+        !self.matches_type(path)
+            || (!self.include.is_empty() && !self.include.is_match(path))
+            || (!self.exclude.is_empty() && self.exclude.is_match(path))
+    }
+
+    fn matches_type(&self, path: &str) -> bool {
+        self.types.is_empty() || path_extension(path).is_some_and(|ext| self.types.contains(&ext))
+    }
+
+    /// Reject `--type` values that no indexed document has, listing the
+    /// extensions the index does contain
+    pub fn check_types(&self, index: &ForwardIndex) -> Result<(), String> {
+        let available: std::collections::BTreeSet<String> = index
+            .files
+            .keys()
+            .filter_map(|path| path_extension(path))
+            .collect();
+        match self.types.iter().find(|ext| !available.contains(*ext)) {
+            Some(unknown) => Err(format!(
+                "Unknown --type '{unknown}'; the index has: {}",
+                available.into_iter().collect::<Vec<_>>().join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of included documents accepted by `relevant` that an exclude
+    /// glob filters out
+    pub fn count_excluded(
+        &self,
+        index: &ForwardIndex,
+        relevant: impl Fn(&FileEntry) -> bool,
+    ) -> usize {
+        if self.exclude.is_empty() {
+            return 0;
+        }
+        index
+            .files
+            .iter()
+            .map(|(path, entry)| (path.trim_start_matches("./"), entry))
+            .filter(|(path, _)| self.matches_type(path))
+            .filter(|(path, _)| self.include.is_empty() || self.include.is_match(path))
+            .filter(|(path, _)| self.exclude.is_match(path))
+            .filter(|(_, entry)| relevant(entry))
+            .count()
+    }
+
+    /// Human-readable summary of the globs, `None` when unrestricted
+    pub fn describe(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if !self.types.is_empty() {
+            parts.push(format!("type {}", self.types.join(", ")));
+        }
+        if !self.include_patterns.is_empty() {
+            parts.push(format!("include {}", self.include_patterns.join(", ")));
+        }
+        if !self.exclude_patterns.is_empty() {
+            parts.push(format!("exclude {}", self.exclude_patterns.join(", ")));
+        }
+        (!parts.is_empty()).then(|| parts.join("; "))
+    }
+}
+
+/// Lowercased extension of an indexed path, without the leading dot
+fn path_extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Query-time alias groups from a `--synonyms` YAML file.
+#[derive(Debug, Clone, Default)]
+pub struct Synonyms {
+    /// Lowercased words per group, canonical term first
+    pub groups: Vec<Vec<String>>,
+}
+
+/// A canonicality score adjustment: documents whose lowercased path or
+/// filename contains any of the substrings gain `delta`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CanonicalityRule {
+    /// Label shown in canonicality reasons
+    pub name: String,
+    #[serde(default)]
+    pub path_contains: Vec<String>,
+    #[serde(default)]
+    pub filename_contains: Vec<String>,
+    pub delta: f64,
+    /// Loaded from a `--rules` file rather than built in
+    #[serde(skip)]
+    pub configured: bool,
+}
+
+impl CanonicalityRule {
+    fn builtin(name: &str, path: &[&str], filename: &[&str], delta: f64) -> Self {
+        let owned = |patterns: &[&str]| patterns.iter().map(ToString::to_string).collect();
+        CanonicalityRule {
+            name: name.to_string(),
+            path_contains: owned(path),
+            filename_contains: owned(filename),
+            delta,
+            configured: false,
+        }
+    }
+
+    /// Whether the rule applies to a lowercased path and filename
+    pub fn matches(&self, path_lower: &str, filename_lower: &str) -> bool {
+        self.path_contains
+            .iter()
+            .any(|pattern| path_lower.contains(pattern.as_str()))
+            || self
+                .filename_contains
+                .iter()
+                .any(|pattern| filename_lower.contains(pattern.as_str()))
+    }
+
+    /// Reason line for a document the rule matched, e.g. `Index document (+0.15)`
+    pub fn reason(&self) -> String {
+        if self.configured {
+            format!("Rule '{}' ({:+})", self.name, self.delta)
+        } else {
+            format!("{} ({:+})", self.name, self.delta)
+        }
+    }
+}
+
+/// Path and filename rules canonicality applies unless a `--rules` file
+/// replaces them.
+pub fn builtin_canonicality_rules() -> &'static [CanonicalityRule] {
+    static RULES: OnceLock<Vec<CanonicalityRule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        vec![
+            CanonicalityRule::builtin(
+                "Architecture/ADR document",
+                &["docs/adr/", "docs/architecture/"],
+                &[],
+                0.2,
+            ),
+            CanonicalityRule::builtin("Index document", &["docs/index/"], &[], 0.15),
+            CanonicalityRule::builtin(
+                "Scratch/archive/old location",
+                &["scratch", "archive", "old"],
+                &[],
+                -0.3,
+            ),
+            CanonicalityRule::builtin(
+                "Deprecated/backup location",
+                &["deprecated", "backup"],
+                &[],
+                -0.25,
+            ),
+            CanonicalityRule::builtin("README/INDEX file", &[], &["readme", "index"], 0.1),
+            CanonicalityRule::builtin(
+                "Guide/runbook/plan document",
+                &[],
+                &["guide", "runbook", "plan"],
+                0.1,
+            ),
+        ]
+    })
+}
+
+/// Contents of a canonicality `--rules` YAML file.
+#[derive(Debug, Deserialize)]
+pub struct CanonicalityRulesConfig {
+    /// Drop the built-in rules instead of merging with them
+    #[serde(default)]
+    pub replace_builtin: bool,
+    #[serde(default)]
+    pub rules: Vec<CanonicalityRule>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ParsedQuery {
     pub terms: Vec<String>,
     pub phrases: Vec<PhraseGroup>,
+    /// Boolean filter, present only when the query uses AND/OR/NOT operators
+    pub filter: Option<QueryExpr>,
+    /// Terms restricted to a field via `heading:` / `body:` prefixes (also
+    /// at the end of `terms`, after the unprefixed terms)
+    pub field_terms: Vec<FieldTerm>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    Heading,
+    Body,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldTerm {
+    pub field: QueryField,
+    pub term: String,
+}
+
+/// Boolean query expression built from `AND`, `OR`, and `NOT` operators.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    Term(String),
+    Phrase(Vec<String>),
+    And(Box<QueryExpr>, Box<QueryExpr>),
+    Or(Box<QueryExpr>, Box<QueryExpr>),
+    Not(Box<QueryExpr>),
 }
 
 #[derive(Debug, Clone)]
@@ -1020,3 +2306,55 @@ pub enum DocType {
     Ops,
     Other,
 }
+
+/// Limits on how much context cross-reference expansion may add.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct XrefConfig {
+    /// Share of the digest's `max_tokens` expansion may spend
+    pub fraction: f64,
+    /// Absolute ceiling on expansion tokens
+    pub max_tokens: usize,
+    /// Tokens one cross-referenced doc may add on the first hop; halves
+    /// with every further hop
+    pub per_doc_tokens: usize,
+    /// Sections taken from an ADR, design doc, ops doc, and any other doc
+    pub adr_sections: usize,
+    pub design_sections: usize,
+    pub ops_sections: usize,
+    pub other_sections: usize,
+}
+
+impl XrefConfig {
+    pub const DEFAULT: Self = Self {
+        fraction: 0.3,
+        max_tokens: 2000,
+        per_doc_tokens: 600,
+        adr_sections: 3,
+        design_sections: 2,
+        ops_sections: 2,
+        other_sections: 2,
+    };
+
+    /// Expansion budget for a `max_tokens` digest whose primary sections
+    /// already use `primary_tokens`
+    pub fn token_budget(&self, max_tokens: usize, primary_tokens: usize) -> usize {
+        let cap = ((max_tokens as f64 * self.fraction) as usize).min(self.max_tokens);
+        max_tokens.saturating_sub(primary_tokens).min(cap)
+    }
+
+    /// Section cap for a cross-referenced doc of `doc_type`
+    pub fn max_sections(&self, doc_type: &DocType) -> usize {
+        match doc_type {
+            DocType::Adr => self.adr_sections,
+            DocType::Design => self.design_sections,
+            DocType::Ops => self.ops_sections,
+            DocType::Other => self.other_sections,
+        }
+    }
+}
+
+impl Default for XrefConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
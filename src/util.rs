@@ -1,17 +1,150 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 
+use crate::assemble::token_byte_budget;
 use crate::types::*;
 
 // Helper functions
 
-pub fn load_forward_index(index_dir: &Path) -> Result<ForwardIndex, Box<dyn std::error::Error>> {
+/// Magic prefix of `forward_index.bin`, followed by the little-endian index version
+const FORWARD_INDEX_BIN_MAGIC: &[u8; 8] = b"YOREFWD\0";
+
+/// Print how long `phase` took to stderr when `timing` (`--timing`) is on.
+pub fn report_timing(timing: bool, phase: &str, elapsed: std::time::Duration) {
+    if timing {
+        eprintln!("timing: {phase}: {elapsed:.2?}");
+    }
+}
+
+pub fn load_forward_index(
+    index_dir: &IndexDir,
+) -> Result<ForwardIndex, Box<dyn std::error::Error>> {
     let path = index_dir.join("forward_index.json");
+    if let Some(index) = load_forward_index_bin(&index_dir.join("forward_index.bin"), &path)? {
+        return Ok(index);
+    }
     let content =
         fs::read_to_string(&path).map_err(|_| "Index not found. Run 'yore build' first.")?;
-    Ok(serde_json::from_str(&content)?)
+    let index: ForwardIndex = serde_json::from_str(&content)?;
+    check_index_version(&index, index_dir.version_check)?;
+    Ok(index)
+}
+
+/// For sessions that reload the index per command (REPL, MCP server):
+/// report an outdated index once up front, then load quietly.
+pub fn session_index_dir(index_dir: &IndexDir) -> IndexDir {
+    if index_dir.version_check == IndexVersionCheck::Warn {
+        // A missing or unreadable index is reported by each command instead
+        let _ = load_forward_index(index_dir);
+    }
+    index_dir.quiet()
+}
+
+/// Warn on stderr (or fail, under `IndexVersionCheck::Strict`) if `index`
+/// predates the current index format; older indexes lack fields that
+/// default to empty.
+pub fn check_index_version(
+    index: &ForwardIndex,
+    check: IndexVersionCheck,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if index.version >= CURRENT_INDEX_VERSION {
+        return Ok(());
+    }
+    let message = format!(
+        "index version {} is older than the version this yore expects ({}); run `yore build` to rebuild it",
+        index.version, CURRENT_INDEX_VERSION
+    );
+    match check {
+        IndexVersionCheck::Strict => return Err(message.into()),
+        IndexVersionCheck::Warn => eprintln!("Warning: {message}"),
+        IndexVersionCheck::Quiet => {}
+    }
+    Ok(())
+}
+
+/// Explain on stderr when an index has documents but no BM25 statistics, so
+/// ranking cannot score anything. Returns whether they are missing.
+pub fn warn_if_missing_bm25(index: &ForwardIndex) -> bool {
+    let missing = !index.files.is_empty() && index.idf_map.is_empty();
+    if missing {
+        eprintln!(
+            "Warning: index has no BM25 statistics (idf_map), so no document can be ranked; run `yore build` to rebuild it"
+        );
+    }
+    missing
+}
+
+/// Explain on stderr when no document carries a MinHash signature, so
+/// duplicate detection cannot find candidates. Returns whether they are missing.
+pub fn warn_if_missing_minhash(index: &ForwardIndex) -> bool {
+    let missing =
+        !index.files.is_empty() && index.files.values().all(|entry| entry.minhash.is_empty());
+    if missing {
+        eprintln!(
+            "Warning: index has no MinHash signatures, so duplicate detection finds nothing; run `yore build` to rebuild it"
+        );
+    }
+    missing
+}
+
+/// Load the binary forward index if it exists, is at least as new as the
+/// JSON index, and carries `CURRENT_INDEX_VERSION`. Returns `None`, after a
+/// warning, when the binary exists but cannot be used, so the caller falls
+/// back to JSON.
+pub(crate) fn load_forward_index_bin(
+    bin_path: &Path,
+    json_path: &Path,
+) -> Result<Option<ForwardIndex>, Box<dyn std::error::Error>> {
+    let Some(bin_modified) = fs::metadata(bin_path).and_then(|m| m.modified()).ok() else {
+        return Ok(None);
+    };
+    let json_modified = fs::metadata(json_path).and_then(|m| m.modified()).ok();
+    if json_modified.is_some_and(|json| json > bin_modified) {
+        return Ok(None);
+    }
+
+    let bytes = fs::read(bin_path)?;
+    let header = bytes
+        .strip_prefix(FORWARD_INDEX_BIN_MAGIC.as_slice())
+        .and_then(|rest| rest.split_first_chunk::<4>());
+    let Some((version, payload)) = header else {
+        eprintln!(
+            "Warning: {} is not a yore binary index; falling back to JSON. Run `yore build` to rebuild it",
+            bin_path.display()
+        );
+        return Ok(None);
+    };
+    let version = u32::from_le_bytes(*version);
+    if version != CURRENT_INDEX_VERSION {
+        eprintln!(
+            "Warning: {} has index version {version}, but this yore uses {CURRENT_INDEX_VERSION}; falling back to JSON. Run `yore build` to rebuild it",
+            bin_path.display()
+        );
+        return Ok(None);
+    }
+    let index: ForwardIndex = match rmp_serde::from_slice(payload) {
+        Ok(index) => index,
+        Err(err) => {
+            eprintln!(
+                "Warning: could not decode {} ({err}); falling back to JSON. Run `yore build` to rebuild it",
+                bin_path.display()
+            );
+            return Ok(None);
+        }
+    };
+    Ok(Some(index))
+}
+
+/// Encode a forward index as `forward_index.bin` bytes (header + MessagePack)
+pub fn encode_forward_index_bin(
+    index: &ForwardIndex,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = FORWARD_INDEX_BIN_MAGIC.to_vec();
+    bytes.extend_from_slice(&index.version.to_le_bytes());
+    bytes.extend(rmp_serde::to_vec_named(index)?);
+    Ok(bytes)
 }
 
 /// Load the relation index; returns an empty index if the file does not exist (backward compat).
@@ -209,6 +342,177 @@ pub fn load_vocabulary_stopwords(
     Ok(words)
 }
 
+/// Stop words for `build --stop-words`: the file's words merged into the
+/// built-in list (`merge`) or used on their own (`replace`).
+pub fn load_index_stop_words(
+    path: Option<&Path>,
+    mode: &str,
+) -> Result<StopWords, Box<dyn std::error::Error>> {
+    let replace = match mode {
+        "merge" => false,
+        "replace" => true,
+        _ => {
+            return Err(
+                format!("Unsupported stop-words mode: {mode} (expected merge or replace)").into(),
+            )
+        }
+    };
+    let Some(path) = path else {
+        return Ok(StopWords::default());
+    };
+
+    let path_value = path.to_string_lossy().to_string();
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read stop-word file '{path_value}': {err}"))?;
+    let custom = content.split_whitespace().map(str::to_string);
+    Ok(if replace {
+        StopWords::custom(custom)
+    } else {
+        StopWords::custom(
+            default_query_stop_words()
+                .iter()
+                .map(|word| (*word).to_string())
+                .chain(custom),
+        )
+    })
+}
+
+/// Load a synonyms file mapping each canonical term to a list of aliases.
+pub fn load_synonyms(path: &Path) -> Result<Synonyms, Box<dyn std::error::Error>> {
+    let path_value = path.to_string_lossy().to_string();
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read synonyms file '{path_value}': {err}"))?;
+    let mapping: BTreeMap<String, Vec<String>> = serde_yaml::from_str(&content)
+        .map_err(|err| format!("Invalid synonyms file '{path_value}': {err}"))?;
+
+    let mut groups = Vec::new();
+    for (canonical, aliases) in mapping {
+        let mut group: Vec<String> = Vec::new();
+        for word in std::iter::once(&canonical).chain(&aliases) {
+            let word = word.trim().to_lowercase();
+            if word.split_whitespace().count() != 1 {
+                return Err(
+                    format!("Synonym {word:?} in '{path_value}' must be a single word").into(),
+                );
+            }
+            if !group.contains(&word) {
+                group.push(word);
+            }
+        }
+        groups.push(group);
+    }
+    Ok(Synonyms { groups })
+}
+
+/// Effective canonicality rules for an optional `--rules` file. Configured
+/// rules are added to the built-ins, replacing any built-in of the same name,
+/// unless the file sets `replace_builtin: true`.
+pub fn load_canonicality_rules(
+    path: Option<&Path>,
+) -> Result<Vec<CanonicalityRule>, Box<dyn std::error::Error>> {
+    let builtin = builtin_canonicality_rules().to_vec();
+    let Some(path) = path else {
+        return Ok(builtin);
+    };
+    let path_value = path.to_string_lossy().to_string();
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read rules file '{path_value}': {err}"))?;
+    let config: CanonicalityRulesConfig = serde_yaml::from_str(&content)
+        .map_err(|err| format!("Invalid rules file '{path_value}': {err}"))?;
+
+    let mut rules = if config.replace_builtin {
+        Vec::new()
+    } else {
+        builtin
+    };
+    for mut rule in config.rules {
+        if rule.path_contains.is_empty() && rule.filename_contains.is_empty() {
+            return Err(format!(
+                "Rule '{}' in '{path_value}' needs path_contains or filename_contains",
+                rule.name
+            )
+            .into());
+        }
+        if !rule.delta.is_finite() {
+            return Err(format!(
+                "Rule '{}' in '{path_value}' has an invalid delta",
+                rule.name
+            )
+            .into());
+        }
+        for pattern in rule
+            .path_contains
+            .iter_mut()
+            .chain(rule.filename_contains.iter_mut())
+        {
+            *pattern = pattern.to_lowercase();
+        }
+        rule.configured = true;
+        match rules.iter_mut().find(|existing| existing.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => rules.push(rule),
+        }
+    }
+    Ok(rules)
+}
+
+/// Reject a boost flag value that is negative or not a number.
+pub fn validate_boost(flag: &str, value: f64) -> Result<(), Box<dyn std::error::Error>> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{flag} must be a non-negative number, got {value}").into())
+    }
+}
+
+/// `--lsh-bands` must split the index's MinHash signature into equal bands.
+pub fn validate_lsh_bands(
+    bands: usize,
+    num_hashes: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if bands == 0 || !num_hashes.is_multiple_of(bands) {
+        return Err(format!(
+            "--lsh-bands must divide the index's {num_hashes} MinHash permutations evenly, got {bands}"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Compile repeatable `--include` / `--exclude` globs and `--type`
+/// extensions; no patterns or types leave every document in scope.
+pub fn compile_path_scope(
+    include: &[String],
+    exclude: &[String],
+    types: &[String],
+) -> Result<PathScope, Box<dyn std::error::Error>> {
+    let compile = |flag: &str, patterns: &[String]| -> Result<globset::GlobSet, String> {
+        let mut builder = globset::GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = globset::Glob::new(pattern)
+                .map_err(|err| format!("Invalid {flag} glob '{pattern}': {err}"))?;
+            builder.add(glob);
+        }
+        builder.build().map_err(|err| err.to_string())
+    };
+    Ok(PathScope {
+        include: compile("--include", include)?,
+        exclude: compile("--exclude", exclude)?,
+        include_patterns: include.to_vec(),
+        exclude_patterns: exclude.to_vec(),
+        types: types
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect(),
+    })
+}
+
+/// `load_synonyms` for an optional `--synonyms` flag; no file means no aliases.
+pub fn load_optional_synonyms(path: Option<&Path>) -> Result<Synonyms, Box<dyn std::error::Error>> {
+    path.map_or_else(|| Ok(Synonyms::default()), load_synonyms)
+}
+
 pub fn is_hygienic_vocabulary_term(term: &str) -> bool {
     if term.len() < 3 || term.len() > 48 {
         return false;
@@ -250,10 +554,177 @@ pub fn jaccard_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
     intersection as f64 / union as f64
 }
 
-pub fn chrono_now() -> String {
+/// Current UTC time as RFC 3339, e.g. `2024-05-01T12:30:00Z`.
+pub fn rfc3339_now() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
     let duration = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-    format!("{}", duration.as_secs())
+    format_rfc3339_utc(duration.as_secs())
+}
+
+/// Timestamp recorded in index files: `SOURCE_DATE_EPOCH` when set, so
+/// reproducible builds write identical indexes, otherwise the current time.
+pub fn index_timestamp() -> String {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .map_or_else(rfc3339_now, format_rfc3339_utc)
+}
+
+/// Format Unix seconds as an RFC 3339 UTC timestamp.
+pub fn format_rfc3339_utc(secs: u64) -> String {
+    let days = secs / 86_400;
+    let time = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm;
+    // unsigned since the epoch is the earliest input)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+/// Parse a stored timestamp into Unix seconds.
+///
+/// Accepts RFC 3339 (`2024-05-01T12:30:00Z`, with optional fractional
+/// seconds and `±HH:MM` offset) as well as the bare epoch seconds that
+/// older indexes recorded.
+pub fn parse_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return value.parse().ok();
+    }
+
+    let field = |range: std::ops::Range<usize>| -> Option<u64> {
+        let part = value.get(range)?;
+        if part.bytes().all(|b| b.is_ascii_digit()) {
+            part.parse().ok()
+        } else {
+            None
+        }
+    };
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // Skip fractional seconds, then read the offset
+    let mut rest = &value[19..];
+    if let Some(frac) = rest.strip_prefix('.') {
+        let digits = frac.bytes().take_while(u8::is_ascii_digit).count();
+        rest = &frac[digits..];
+    }
+    let offset_secs: i64 = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (h, m) = rest[1..].split_once(':')?;
+            if h.len() != 2 || m.len() != 2 {
+                return None;
+            }
+            sign * (h.parse::<i64>().ok()? * 3_600 + m.parse::<i64>().ok()? * 60)
+        }
+    };
+
+    // Days since 1970-01-01 from a civil date (inverse of the above)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146_097 + doe).checked_sub(719_468)?;
+
+    let local = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    u64::try_from(i64::try_from(local).ok()? - offset_secs).ok()
+}
+
+/// A stored timestamp as `YYYY-MM-DD HH:MM:SS UTC`, or unchanged when it
+/// can't be parsed.
+pub fn display_timestamp(value: &str) -> String {
+    parse_timestamp(value).map_or_else(
+        || value.to_string(),
+        |secs| {
+            format_rfc3339_utc(secs)
+                .replacen('T', " ", 1)
+                .replace('Z', " UTC")
+        },
+    )
+}
+
+/// Memoized last-commit timestamps from `git log`.
+///
+/// Each path is looked up at most once per run. When the working directory
+/// is not inside a git work tree, no per-file processes are spawned and every
+/// lookup returns `None`.
+pub struct GitCommitTimes {
+    enabled: bool,
+    cache: HashMap<String, Option<u64>>,
+}
+
+impl GitCommitTimes {
+    pub fn new() -> Self {
+        let enabled = std::process::Command::new("git")
+            .args(["rev-parse", "--is-inside-work-tree"])
+            .output()
+            .is_ok_and(|o| o.status.success());
+        Self {
+            enabled,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Unix timestamp of the last commit touching `path`, or `None` when the
+    /// file is untracked or git is unavailable.
+    pub fn last_commit_secs(&mut self, path: &str) -> Option<u64> {
+        if !self.enabled {
+            return None;
+        }
+        if let Some(cached) = self.cache.get(path) {
+            return *cached;
+        }
+
+        let timestamp = std::process::Command::new("git")
+            .args(["log", "-1", "--format=%ct", "--", path])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .and_then(|o| String::from_utf8_lossy(&o.stdout).trim().parse().ok());
+        self.cache.insert(path.to_string(), timestamp);
+        timestamp
+    }
+}
+
+impl Default for GitCommitTimes {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Extract file rename history from git
@@ -280,7 +751,7 @@ pub fn extract_git_renames(path: &Path) -> RenameHistory {
         _ => {
             return RenameHistory {
                 renames: vec![],
-                indexed_at: chrono_now(),
+                indexed_at: rfc3339_now(),
             };
         }
     };
@@ -316,7 +787,7 @@ pub fn extract_git_renames(path: &Path) -> RenameHistory {
 
     RenameHistory {
         renames,
-        indexed_at: chrono_now(),
+        indexed_at: rfc3339_now(),
     }
 }
 
@@ -396,13 +867,23 @@ pub fn compute_relative_path(
 // Context Assembly for LLMs (Phase 2)
 // ============================================================================
 
-pub fn combined_section_score(section: &SectionMatch) -> f64 {
-    section.bm25_score * 0.7 + section.canonicality * 0.3
+/// Share of the combined ranking score given to canonicality; BM25 gets the rest.
+pub const DEFAULT_CANONICALITY_WEIGHT: f64 = 0.3;
+
+/// SimHash similarity at which assembled sections count as copies.
+pub const DEFAULT_DEDUP_THRESHOLD: f64 = 0.9;
+
+pub fn combined_section_score(section: &SectionMatch, canonicality_weight: f64) -> f64 {
+    section.bm25_score * (1.0 - canonicality_weight) + section.canonicality * canonicality_weight
 }
 
-pub fn compare_sections_by_relevance(a: &SectionMatch, b: &SectionMatch) -> std::cmp::Ordering {
-    combined_section_score(b)
-        .partial_cmp(&combined_section_score(a))
+pub fn compare_sections_by_relevance(
+    a: &SectionMatch,
+    b: &SectionMatch,
+    canonicality_weight: f64,
+) -> std::cmp::Ordering {
+    combined_section_score(b, canonicality_weight)
+        .partial_cmp(&combined_section_score(a, canonicality_weight))
         .unwrap_or(std::cmp::Ordering::Equal)
         .then_with(|| a.doc_path.cmp(&b.doc_path))
         .then_with(|| a.line_start.cmp(&b.line_start))
@@ -480,17 +961,33 @@ pub fn read_indexed_doc(
     fs::read_to_string(resolve_doc_fs_path(index, doc_path, entry))
 }
 
+fn sections_overlap(a: &SectionMatch, b: &SectionMatch) -> bool {
+    a.doc_path == b.doc_path && a.line_start <= b.line_end && b.line_start <= a.line_end
+}
+
+/// Drop sections whose line range overlaps an earlier section of the same doc.
+pub fn drop_overlapping_sections(sections: Vec<SectionMatch>) -> Vec<SectionMatch> {
+    let mut unique: Vec<SectionMatch> = Vec::new();
+    for section in sections {
+        if !unique
+            .iter()
+            .any(|existing| sections_overlap(existing, &section))
+        {
+            unique.push(section);
+        }
+    }
+    unique
+}
+
 pub fn dedupe_section_matches(sections: Vec<SectionMatch>) -> (Vec<SectionMatch>, usize) {
     let mut unique: Vec<SectionMatch> = Vec::new();
     let mut seen_content = HashSet::new();
     let mut deduped_hits = 0usize;
 
     for section in sections {
-        let overlaps_existing = unique.iter().any(|existing| {
-            existing.doc_path == section.doc_path
-                && existing.line_start <= section.line_end
-                && section.line_start <= existing.line_end
-        });
+        let overlaps_existing = unique
+            .iter()
+            .any(|existing| sections_overlap(existing, &section));
 
         let content_key = normalize_content_for_dedupe(&section.content);
         let duplicate_content = !content_key.is_empty() && !seen_content.insert(content_key);
@@ -514,17 +1011,60 @@ pub fn floor_char_boundary(text: &str, limit: usize) -> usize {
     idx
 }
 
+/// Truncate markdown to at most `max_bytes` without splitting a UTF-8
+/// character or a fenced code block where avoidable.
+///
+/// Cuts at the last line break within the budget that sits outside a
+/// ```` ``` ```` fence, so a code block that doesn't fit is dropped whole.
+/// When no such break exists because the text opens with a fence, the block
+/// is cut at a line break and closed with a trailing ```` ``` ````.
+pub fn truncate_markdown_to_bytes(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let limit = floor_char_boundary(text, max_bytes);
+
+    let mut in_fence = false;
+    let mut safe_cut = 0;
+    let mut line_cut = 0;
+    for line in text.split_inclusive('\n') {
+        let end = line_cut + line.len();
+        if end > limit {
+            break;
+        }
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence {
+            safe_cut = end;
+        }
+        line_cut = end;
+    }
+
+    if safe_cut > 0 {
+        return text[..safe_cut].trim_end().to_string();
+    }
+
+    let cut = if line_cut > 0 { line_cut } else { limit };
+    let mut truncated = text[..cut].trim_end().to_string();
+    if in_fence || (line_cut == 0 && text.trim_start().starts_with("```")) {
+        truncated.push_str("\n```");
+    }
+    truncated
+}
+
 pub fn truncate_text_to_budget(
     text: &str,
     max_tokens: usize,
     max_bytes: usize,
+    tokenizer: TokenizerKind,
 ) -> (String, bool, Vec<String>) {
     const TRUNCATION_MARKER: &str = " ...[truncated]";
 
     let mut reasons = Vec::new();
     let mut limit = text.len();
 
-    let token_char_limit = max_tokens.saturating_mul(4);
+    let token_char_limit = token_byte_budget(text, max_tokens, tokenizer);
     if token_char_limit > 0 && text.len() > token_char_limit {
         reasons.push("token_cap".to_string());
         limit = limit.min(token_char_limit);
@@ -669,6 +1209,40 @@ pub fn compute_ranking_metrics(
     }
 }
 
+/// Compare the files an assembled context drew from against the expected ones.
+pub fn compute_file_coverage(expected: &[String], retrieved: &HashSet<String>) -> FileCoverage {
+    let (found, missing): (Vec<String>, Vec<String>) = expected
+        .iter()
+        .map(|path| path.strip_prefix("./").unwrap_or(path).to_string())
+        .partition(|path| retrieved.contains(path));
+    let precision = if retrieved.is_empty() {
+        0.0
+    } else {
+        found.len() as f64 / retrieved.len() as f64
+    };
+    let recall = if expected.is_empty() {
+        0.0
+    } else {
+        found.len() as f64 / expected.len() as f64
+    };
+    FileCoverage {
+        precision,
+        recall,
+        found,
+        missing,
+    }
+}
+
+/// Average file coverage across the questions that set `expect_files`.
+pub fn aggregate_file_coverage(per_question: &[FileCoverage]) -> AggregateFileCoverage {
+    let n = per_question.len().max(1) as f64;
+    AggregateFileCoverage {
+        questions_with_expect_files: per_question.len(),
+        mean_precision: per_question.iter().map(|c| c.precision).sum::<f64>() / n,
+        mean_recall: per_question.iter().map(|c| c.recall).sum::<f64>() / n,
+    }
+}
+
 /// Aggregate ranking metrics across multiple questions by averaging.
 pub fn aggregate_ranking_metrics(
     per_question: &[RankingMetrics],
@@ -752,6 +1326,21 @@ pub fn aggregate_ranking_metrics(
     }
 }
 
+/// Quote a CSV field when it contains a comma, quote, or line break.
+pub fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// Print one CSV record to stdout.
+pub fn print_csv_row<S: AsRef<str>>(fields: &[S]) {
+    let row: Vec<_> = fields.iter().map(|f| csv_field(f.as_ref())).collect();
+    println!("{}", row.join(","));
+}
+
 pub fn normalize_path(path: &Path) -> String {
     let mut components = Vec::new();
 
@@ -768,3 +1357,89 @@ pub fn normalize_path(path: &Path) -> String {
 
     components.join("/")
 }
+
+/// Slugify a heading the way GitHub renders heading anchors.
+///
+/// Lowercases, drops everything except letters, numbers, `-`, `_` and
+/// spaces, then turns spaces into hyphens (repeated hyphens are kept, as on
+/// GitHub). `seen` tracks slugs already used in the same document so that
+/// repeated headings get `-1`, `-2`, ... suffixes.
+pub fn github_slug(heading: &str, seen: &mut HashMap<String, usize>) -> String {
+    let base: String = heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect();
+
+    let mut slug = base.clone();
+    while seen.contains_key(&slug) {
+        let count = seen.entry(base.clone()).or_insert(0);
+        *count += 1;
+        slug = format!("{base}-{count}");
+    }
+    seen.insert(slug.clone(), 0);
+    slug
+}
+
+/// Line range of a GitHub line anchor: `L42` or `L10-L20`.
+///
+/// Returns `None` for anything else, including `L0` and reversed ranges.
+pub fn parse_line_anchor(anchor: &str) -> Option<(usize, usize)> {
+    let parse_line = |text: &str| {
+        let line: usize = text.strip_prefix('L')?.parse().ok()?;
+        (line > 0).then_some(line)
+    };
+    let (start, end) = match anchor.split_once('-') {
+        Some((start, end)) => (parse_line(start)?, parse_line(end)?),
+        None => {
+            let line = parse_line(anchor)?;
+            (line, line)
+        }
+    };
+    (start <= end).then_some((start, end))
+}
+
+/// Default Asciidoctor section ID for a heading: lowercase words joined by
+/// `_` with a leading `_` (`Getting Started` -> `_getting_started`).
+pub fn asciidoc_section_id(heading: &str) -> String {
+    let words: Vec<String> = heading
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    format!("_{}", words.join("_"))
+}
+
+/// Normalize a wiki page name or file stem for `[[Page Name]]` matching:
+/// last path component, no `.md` suffix, lowercase, spaces as hyphens.
+pub fn wiki_page_key(name: &str) -> String {
+    let base = name.trim().rsplit('/').next().unwrap_or_default();
+    let base = base
+        .strip_suffix(".md")
+        .or_else(|| base.strip_suffix(".MD"))
+        .unwrap_or(base);
+    base.to_lowercase().replace(' ', "-")
+}
+
+/// Map wiki page keys to the indexed files whose basename matches, sorted.
+pub fn build_wiki_page_map<'a>(
+    paths: impl Iterator<Item = &'a String>,
+) -> HashMap<String, Vec<String>> {
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    for path in paths {
+        let stem = Path::new(path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        map.entry(wiki_page_key(&stem))
+            .or_default()
+            .push(path.clone());
+    }
+    for matches in map.values_mut() {
+        matches.sort();
+    }
+    map
+}
@@ -231,6 +231,60 @@ Use PostgreSQL for all persistent data.
     .unwrap();
 }
 
+// ── init ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_init_scaffolds_config_and_policy() {
+    let root = temp_dir("init");
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+            .current_dir(&root)
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (ok, stdout, stderr) = run(&["init"]);
+    assert!(ok, "{stderr}");
+    assert!(stdout.contains("Wrote .yore.toml"), "{stdout}");
+    assert!(stdout.contains("Wrote .yore-policy.yaml"), "{stdout}");
+    assert!(stdout.contains("yore --profile docs build"), "{stdout}");
+
+    // The generated profile and policy work as-is
+    write_fixture(&root);
+    fs::create_dir_all(root.join("docs/adr")).unwrap();
+    fs::write(root.join("docs/adr/ADR-0001.md"), "# ADR 1\n\n## Status\n").unwrap();
+    let (ok, _, stderr) = run(&["--profile", "docs", "build"]);
+    assert!(ok, "{stderr}");
+    assert!(root.join(".yore/forward_index.json").exists());
+    let (ok, stdout, stderr) = run(&["check", "--taxonomy", "--policy", ".yore-policy.yaml"]);
+    assert!(ok, "{stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let violations = v["policy"]["violations"].as_array().unwrap();
+    assert_eq!(violations.len(), 1, "{stdout}");
+    assert_eq!(violations[0]["rule"], "adr-required-sections");
+
+    fs::write(root.join(".yore.toml"), "# edited\n").unwrap();
+    let (ok, _, stderr) = run(&["init"]);
+    assert!(!ok);
+    assert!(stderr.contains("Refusing to overwrite"), "{stderr}");
+    assert_eq!(
+        fs::read_to_string(root.join(".yore.toml")).unwrap(),
+        "# edited\n"
+    );
+
+    let (ok, _, stderr) = run(&["init", "--force"]);
+    assert!(ok, "{stderr}");
+    assert!(fs::read_to_string(root.join(".yore.toml"))
+        .unwrap()
+        .contains("[index.docs]"));
+}
+
 // ── stats ───────────────────────────────────────────────────────────
 
 #[test]
@@ -244,7 +298,9 @@ fn test_stats_human_output() {
     assert!(ok, "stats failed: {stdout}");
     // Human output uses "Total files:" not "Files indexed:"
     assert!(stdout.contains("Total files"), "expected file count");
+    assert!(stdout.contains(" UTC\n"), "expected a readable index time");
     assert!(stdout.contains("Unique keywords"), "expected keyword count");
+    assert!(stdout.contains("Lines per file:"), "{stdout}");
 }
 
 #[test]
@@ -260,289 +316,2374 @@ fn test_stats_json_output() {
     // JSON key is "total_files" not "files_indexed"
     assert!(v["total_files"].as_u64().unwrap() >= 9);
     assert!(v["unique_keywords"].as_u64().unwrap() > 0);
+    let indexed_at = v["indexed_at"].as_str().unwrap();
+    assert!(
+        indexed_at.len() == 20 && indexed_at.ends_with('Z'),
+        "expected an RFC 3339 timestamp, got {indexed_at}"
+    );
     assert!(v["top_keywords"].is_array());
+    let dist = &v["distribution"];
+    let p50 = dist["line_count_p50"].as_u64().unwrap();
+    let p90 = dist["line_count_p90"].as_u64().unwrap();
+    assert!(p50 > 0 && p50 <= p90, "{dist}");
+    assert!(p90 <= dist["line_count_max"].as_u64().unwrap(), "{dist}");
+    assert!(dist["avg_links_per_file"].as_f64().unwrap() > 0.0);
+    assert_eq!(dist["files_without_minhash"], 0);
+    assert_eq!(dist["files_without_sections"], 0);
 }
 
-// ── check-links ─────────────────────────────────────────────────────
-
 #[test]
-fn test_check_links_finds_broken_link() {
-    let root = temp_dir("check-links");
+fn test_stats_check_drift() {
+    let root = temp_dir("stats-drift");
     write_fixture(&root);
+    fs::write(root.join("docs/notes.txt"), "not indexed\n").unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
-    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let (ok, stdout, _) = yore_at(&root, &["stats", "--check-drift"], &index);
+    assert!(ok, "fresh index should not drift: {stdout}");
+    assert!(stdout.contains("Index matches"), "{stdout}");
 
-    // JSON shape: { broken_links: N, broken: [...] }
+    fs::write(root.join("docs/new-guide.md"), "# New\n\nFresh content.\n").unwrap();
+    fs::remove_file(root.join("docs/orphan-notes.md")).unwrap();
+    let arch = root.join("docs/architecture.md");
+    let mut content = fs::read_to_string(&arch).unwrap();
+    content.push_str("\n## Appendix\n\nMore.\n");
+    fs::write(&arch, content).unwrap();
+
+    let (ok, stdout, _) = yore_at(&root, &["stats", "--check-drift", "--json"], &index);
+    assert!(!ok, "drift should exit nonzero: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["added_checked"], true);
+    assert_eq!(v["added"], serde_json::json!(["docs/new-guide.md"]));
+    assert_eq!(v["removed"], serde_json::json!(["docs/orphan-notes.md"]));
+    let modified = v["modified"].as_array().unwrap();
+    assert_eq!(modified.len(), 1);
+    assert_eq!(modified[0]["file"], "docs/architecture.md");
     assert!(
-        v["broken_links"].as_u64().unwrap() >= 1,
-        "expected at least one broken link (runbook.md)"
+        modified[0]["current_size_bytes"].as_u64().unwrap()
+            > modified[0]["indexed_size_bytes"].as_u64().unwrap()
     );
-    let broken = v["broken"].as_array().unwrap();
-    let has_runbook = broken
-        .iter()
-        .any(|b| b["link_target"].as_str().unwrap_or("").contains("runbook"));
-    assert!(has_runbook, "expected broken link to runbook.md");
+
+    let (ok, stdout, _) = yore_at(&root, &["stats", "--check-drift"], &index);
+    assert!(!ok);
+    assert!(stdout.contains("+ docs/new-guide.md"), "{stdout}");
+    assert!(stdout.contains("- docs/orphan-notes.md"), "{stdout}");
+    assert!(stdout.contains("~ docs/architecture.md"), "{stdout}");
 }
 
 #[test]
-fn test_check_links_summary_only() {
-    let root = temp_dir("check-links-summary");
+fn test_show_file_summary() {
+    let root = temp_dir("show");
     write_fixture(&root);
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (_, stdout, _) = yore_at(&root, &["check-links", "--summary-only"], &index);
+    let (ok, stdout, stderr) = yore_at(&root, &["show", "docs/architecture.md"], &index);
+    assert!(ok, "show failed: {stderr}");
+    assert!(stdout.contains("1 outbound, 4 inbound"), "{stdout}");
+    assert!(stdout.contains("Canonicality:"), "{stdout}");
+    assert!(stdout.contains("Headings (5)"), "{stdout}");
+    assert!(stdout.contains("### API Layer"), "{stdout}");
+    assert!(stdout.contains("[API Reference](api-reference.md#endpoints)"));
+    assert!(stdout.contains("Section Fingerprints (5)"), "{stdout}");
+
+    let (ok, stdout, stderr) =
+        yore_at(&root, &["show", "./docs/architecture.md", "--json"], &index);
+    assert!(ok, "{stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["line_count"], 15);
+    assert_eq!(v["headings"].as_array().unwrap().len(), 5);
+    assert_eq!(v["links"][0]["target"], "api-reference.md#endpoints");
+    assert!(v["section_fingerprints"].is_array());
+
+    let (ok, _, stderr) = yore_at(&root, &["show", "docs/missing.md"], &index);
+    assert!(!ok);
     assert!(
-        stdout.contains("broken") || stdout.contains("Broken") || stdout.contains("Summary"),
-        "expected summary output, got: {stdout}"
+        stderr.contains("File not in index: docs/missing.md"),
+        "{stderr}"
     );
 }
 
-// ── backlinks ───────────────────────────────────────────────────────
-
 #[test]
-fn test_backlinks_finds_inbound_links() {
-    let root = temp_dir("backlinks");
-    write_fixture(&root);
+fn test_stats_and_diff_show_original_keyword_case() {
+    let root = temp_dir("keyword-display");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("a.md"),
+        "# RBAC on Kubernetes\n\nRBAC roles limit Kubernetes access.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("b.md"),
+        "# Kubernetes Networking\n\nKubernetes services route traffic.\n",
+    )
+    .unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    // Must use the full indexed path: docs/architecture.md
-    let (ok, stdout, _) = yore(&["backlinks", "docs/architecture.md", "--json"], &index);
-    assert!(ok, "backlinks failed: {stdout}");
+    let (ok, stdout, _) = yore_at(&root, &["stats", "--json"], &index);
+    assert!(ok, "stats failed: {stdout}");
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    let backlinks = v["backlinks"].as_array().unwrap();
+    let top = v["top_keywords"].as_array().unwrap();
+    let rbac = top.iter().find(|k| k["keyword"] == "rbac").unwrap();
+    assert_eq!(rbac["display"], "RBAC");
+    assert!(top.iter().any(|k| k["display"] == "Kubernetes"));
+
+    let (ok, stdout, _) = yore_at(&root, &["stats"], &index);
+    assert!(ok);
     assert!(
-        backlinks.len() >= 3,
-        "expected >=3 backlinks to architecture.md, got {}",
-        backlinks.len()
+        stdout.contains("RBAC") && !stdout.contains("rbac"),
+        "{stdout}"
     );
+
+    let (ok, stdout, _) = yore_at(&root, &["diff", "docs/a.md", "docs/b.md"], &index);
+    assert!(ok, "diff failed: {stdout}");
+    assert!(
+        stdout.contains("Kubernetes") && stdout.contains("RBAC"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("rbac"), "{stdout}");
 }
 
-// ── orphans ─────────────────────────────────────────────────────────
+// ── check-links ─────────────────────────────────────────────────────
 
 #[test]
-fn test_orphans_finds_unlinked_docs() {
-    let root = temp_dir("orphans");
+fn test_check_links_finds_broken_link() {
+    let root = temp_dir("check-links");
     write_fixture(&root);
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (ok, stdout, _) = yore(&["orphans", "--json"], &index);
-    assert!(ok, "orphans failed: {stdout}");
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    let orphans = v["orphans"].as_array().unwrap();
 
-    let orphan_files: Vec<&str> = orphans
-        .iter()
-        .map(|o| o["file"].as_str().unwrap())
-        .collect();
+    // JSON shape: { broken_links: N, broken: [...] }
     assert!(
-        orphan_files.iter().any(|f| f.contains("orphan-notes")),
-        "expected orphan-notes.md in orphans, got: {orphan_files:?}"
+        v["broken_links"].as_u64().unwrap() >= 1,
+        "expected at least one broken link (runbook.md)"
     );
+    let broken = v["broken"].as_array().unwrap();
+    let has_runbook = broken
+        .iter()
+        .any(|b| b["link_target"].as_str().unwrap_or("").contains("runbook"));
+    assert!(has_runbook, "expected broken link to runbook.md");
 }
 
-// ── canonicality ────────────────────────────────────────────────────
-
 #[test]
-fn test_canonicality_scores_all_files() {
-    let root = temp_dir("canonicality");
-    write_fixture(&root);
+fn test_check_links_resolves_asciidoc_xrefs() {
+    let root = temp_dir("check-links-asciidoc");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("guide.adoc"),
+        "= Guide\n\n== Setup\n\nSee xref:ops.adoc#_rolling_back[rollback], xref:ops.adoc#_missing[gone], and link:absent.adoc[absent].\nBack to xref:_setup[setup], <<_setup>>, <<ops.adoc#_rolling_back,ops>>, or <<_nowhere,nowhere>>.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("ops.adoc"),
+        "= Operations\n\n== Rolling Back\n\nRevert the release.\n",
+    )
+    .unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (ok, stdout, _) = yore(&["canonicality", "--json"], &index);
-    assert!(ok, "canonicality failed: {stdout}");
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    let files = v["files"].as_array().unwrap();
-    assert!(
-        files.len() >= 9,
-        "expected all files scored, got {}",
-        files.len()
-    );
-
-    let readme = files
+    let mut broken: Vec<&str> = v["broken"]
+        .as_array()
+        .unwrap()
         .iter()
-        .find(|f| f["file"].as_str().unwrap_or("").contains("README"));
-    assert!(readme.is_some(), "README should appear in canonicality");
-    let score = readme.unwrap()["score"].as_f64().unwrap();
-    assert!(
-        score > 0.5,
-        "README should have high canonicality, got {score}"
-    );
+        .map(|b| b["link_target"].as_str().unwrap())
+        .collect();
+    broken.sort_unstable();
+    // Same-document xrefs are checked as anchors
+    assert_eq!(broken, ["#_nowhere", "absent.adoc", "ops.adoc#_missing"]);
+
+    let (ok, stdout, _) = yore_at(&root, &["backlinks", "docs/ops.adoc"], &index);
+    assert!(ok);
+    assert!(stdout.contains("guide.adoc"), "{stdout}");
 }
 
-// ── canonical-orphans ───────────────────────────────────────────────
+#[test]
+fn test_check_links_validates_html_site_when_typed() {
+    let root = temp_dir("check-links-html");
+    let site = root.join("site");
+    fs::create_dir_all(site.join("ops")).unwrap();
+    fs::write(
+        site.join("index.html"),
+        "<h1 id=\"home\">Home</h1>\n<p><a href=\"ops/rollback.html#steps\">Rollback</a>,\n<a href=\"ops/rollback.html#rolling-back\">slug</a>,\n<a href=\"missing.html\">gone</a>, <a href=\"#home\">top</a>, and\n<a href=\"https://example.com/x\">upstream</a>.</p>\n",
+    )
+    .unwrap();
+    fs::write(
+        site.join("ops/rollback.html"),
+        "<h1>Ops</h1>\n<h2 id=\"steps\">Rolling Back</h2>\n<p>Revert.</p>\n",
+    )
+    .unwrap();
+    fs::write(root.join("notes.md"), "# Notes\n").unwrap();
+
+    // Without html in --types the site is not indexed at all
+    let index = root.join(".yore");
+    build_index(&root, "site", &index);
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["total_links"], 0, "{stdout}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["build", "site", "--types", "md,html", "--output"])
+        .arg(&index)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let mut broken: Vec<&str> = v["broken"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["link_target"].as_str().unwrap())
+        .collect();
+    broken.sort_unstable();
+    assert_eq!(broken, ["missing.html", "ops/rollback.html#rolling-back"]);
+}
 
 #[test]
-fn test_canonical_orphans_json() {
-    let root = temp_dir("canonical-orphans");
-    write_fixture(&root);
+fn test_check_links_accepts_explicit_anchors() {
+    let root = temp_dir("check-links-explicit-anchors");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("guide.md"),
+        "# Guide\n\nSee [setup](ref.md#install-steps), [faq](ref.md#faq), [legacy](ref.md#old-name),\n[gone](ref.md#nowhere), and [bare](plain.md#intro).\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("ref.md"),
+        "# Reference\n\n## Installing {#install-steps}\n\n<a id=\"faq\"></a>\nQuestions.\n\n<a name=\"old-name\"></a>\nLegacy.\n",
+    )
+    .unwrap();
+    fs::write(docs.join("plain.md"), "No headings here.\n").unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (ok, stdout, _) = yore(
-        &["canonical-orphans", "--threshold", "0.3", "--json"],
-        &index,
-    );
-    assert!(ok, "canonical-orphans failed: {stdout}");
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    assert!(v["orphans"].is_array());
+    let mut broken: Vec<(&str, &str)> = v["broken"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| {
+            (
+                b["link_target"].as_str().unwrap(),
+                b["kind"].as_str().unwrap_or_default(),
+            )
+        })
+        .collect();
+    broken.sort_unstable();
+    let targets: Vec<&str> = broken.iter().map(|(target, _)| *target).collect();
+    assert_eq!(targets, ["plain.md#intro", "ref.md#nowhere"], "{stdout}");
+    let by_kind = v["health"]["by_kind"].clone();
+    assert_eq!(by_kind["anchor_missing"], 1, "{stdout}");
+    assert_eq!(by_kind["anchor_unverified"], 1, "{stdout}");
 }
 
-// ── similar ─────────────────────────────────────────────────────────
-
 #[test]
-fn test_similar_finds_related_docs() {
-    let root = temp_dir("similar");
-    write_fixture(&root);
+fn test_check_links_validates_line_anchors() {
+    let root = temp_dir("check-links-line-anchors");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("guide.md"),
+        "# Guide\n\nSee [one](notes.txt#L2), [range](notes.txt#L2-L4), [self](#L1),\n[past](notes.txt#L9), and [long](notes.txt#L3-L8).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("notes.txt"), "a\nb\nc\nd\ne\n").unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    // similar returns a bare JSON array, not { results: [...] }
-    let (ok, stdout, _) = yore(
-        &[
-            "similar",
-            "docs/architecture.md",
-            "--json",
-            "--threshold",
-            "0.1",
-        ],
-        &index,
-    );
-    assert!(ok, "similar failed: {stdout}");
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    let results = v.as_array().unwrap();
-    assert!(!results.is_empty(), "expected at least one similar doc");
-
-    // architecture-v2 should be the top match
-    let top_path = results[0]["path"].as_str().unwrap_or("");
-    assert!(
-        top_path.contains("architecture-v2"),
-        "expected architecture-v2 as top similar, got {top_path}"
+    let mut broken: Vec<(&str, &str)> = v["broken"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| {
+            (
+                b["link_target"].as_str().unwrap(),
+                b["error"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    broken.sort_unstable();
+    assert_eq!(
+        broken,
+        [
+            (
+                "notes.txt#L3-L8",
+                "Line anchor out of range: #L3-L8 (docs/notes.txt has 5 lines)"
+            ),
+            (
+                "notes.txt#L9",
+                "Line anchor out of range: #L9 (docs/notes.txt has 5 lines)"
+            ),
+        ],
+        "{stdout}"
     );
+    assert_eq!(v["health"]["by_kind"]["anchor_missing"], 2, "{stdout}");
 }
 
-// ── dupes ───────────────────────────────────────────────────────────
-
 #[test]
-fn test_dupes_detects_near_duplicates() {
-    let root = temp_dir("dupes");
+fn test_check_links_health_score_gates_fail_below() {
+    let root = temp_dir("check-links-health");
     write_fixture(&root);
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    // dupes returns a bare JSON array of pair objects
-    let (ok, stdout, _) = yore(&["dupes", "--json", "--threshold", "0.1"], &index);
-    assert!(ok, "dupes failed: {stdout}");
+    let (ok, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
+    assert!(ok, "check-links failed: {stdout}");
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    let pairs = v.as_array().unwrap();
-
-    let has_arch_pair = pairs.iter().any(|p| {
-        let f1 = p["file1"].as_str().unwrap_or("");
-        let f2 = p["file2"].as_str().unwrap_or("");
-        (f1.contains("architecture.md") && f2.contains("architecture-v2"))
-            || (f1.contains("architecture-v2") && f2.contains("architecture.md"))
-    });
+    let health = &v["health"];
+    let score = health["score"].as_f64().unwrap();
     assert!(
-        has_arch_pair,
-        "expected architecture.md <-> architecture-v2.md pair"
+        (0.0..100.0).contains(&score),
+        "broken links lower the score"
     );
+    assert!(health["broken_ratio"].as_f64().unwrap() > 0.0);
+    assert!(health["by_kind"]["doc_missing"].as_u64().unwrap() >= 1);
+
+    let (ok, _, _) = yore_at(&root, &["check-links", "--fail-below", "0"], &index);
+    assert!(ok);
+    let (ok, _, stderr) = yore_at(&root, &["check-links", "--fail-below", "100"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("--fail-below"), "stderr: {stderr}");
 }
 
 #[test]
-fn test_dupes_group_mode() {
-    let root = temp_dir("dupes-group");
+fn test_check_links_summary_only() {
+    let root = temp_dir("check-links-summary");
     write_fixture(&root);
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    // LSH candidate generation is probabilistic (AHasher random seeds),
-    // so we cannot assert a specific pair. Verify the command succeeds
-    // and produces grouped output with at least one similarity line.
-    let (ok, stdout, _) = yore(&["dupes", "--group", "--threshold", "0.01"], &index);
-    assert!(ok, "dupes --group failed: {stdout}");
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--summary-only"], &index);
     assert!(
-        stdout.contains('%'),
-        "expected grouped output with similarity percentages, got: {stdout}"
+        stdout.contains("broken") || stdout.contains("Broken") || stdout.contains("Summary"),
+        "expected summary output, got: {stdout}"
     );
 }
 
-// ── diff ────────────────────────────────────────────────────────────
-
+#[cfg(feature = "external-links")]
 #[test]
-fn test_diff_between_similar_files() {
-    let root = temp_dir("diff");
-    write_fixture(&root);
+fn test_check_links_external_reports_http_errors_and_caches() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    // Minimal HTTP server: /ok answers 200, everything else 404. Expects
+    // three requests: HEAD /ok, HEAD /gone, and the GET fallback for /gone.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        for stream in listener.incoming().take(3) {
+            let mut stream = stream.unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            let status = if request_line.contains(" /ok ") {
+                "200 OK"
+            } else {
+                "404 Not Found"
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+        }
+    });
+
+    let root = temp_dir("check-links-external");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("links.md"),
+        format!(
+            "# Links\n\nSee [ok](http://127.0.0.1:{port}/ok) and [gone](http://127.0.0.1:{port}/gone).\n"
+        ),
+    )
+    .unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (ok, stdout, _) = yore(
-        &[
-            "diff",
-            "docs/architecture.md",
-            "docs/architecture-v2.md",
-            "--json",
-        ],
+    // Without the flag external URLs are not checked
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["broken_links"], 0);
+
+    let (_, stdout, _) = yore_at(
+        &root,
+        &["check-links", "--json", "--check-external"],
         &index,
     );
-    assert!(ok, "diff failed: {stdout}");
+    server.join().unwrap();
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    // similarity is an object: { combined, jaccard, simhash }
-    let sim = v["similarity"]["combined"].as_f64().unwrap();
-    assert!(sim > 0.5, "expected high similarity, got {sim}");
-    assert!(v["shared_keywords"].is_array());
+    assert_eq!(v["broken_links"], 1);
+    let broken = &v["broken"][0];
+    assert!(broken["link_target"].as_str().unwrap().ends_with("/gone"));
+    assert!(broken["error"].as_str().unwrap().contains("404"));
+    assert!(index.join("external_links.json").exists());
+
+    // Second run is served from the cache; the server is gone
+    let (_, stdout, _) = yore_at(
+        &root,
+        &["check-links", "--json", "--check-external"],
+        &index,
+    );
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["broken_links"], 1);
 }
 
-// ── export-graph ────────────────────────────────────────────────────
-
+#[cfg(not(feature = "external-links"))]
 #[test]
-fn test_export_graph_json() {
-    let root = temp_dir("export-graph-json");
-    write_fixture(&root);
+fn test_check_links_external_requires_feature() {
+    let root = temp_dir("check-links-external-off");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("a.md"),
+        "# A\n\nSee [site](https://example.com).\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, _, stderr) = yore_at(&root, &["check-links", "--check-external"], &index);
+    assert!(!ok);
+    assert!(
+        stderr.contains("requires yore built with the `external-links` feature"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_check_links_ignore_file_suppresses_allowlisted_links() {
+    let root = temp_dir("check-links-ignore");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("a.md"),
+        "# A\n\nSee [gone](gone.md).\nAnd [legacy](legacy.md).\nAlso [new](new-missing.md).\nRead [b](b.md).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("b.md"), "# B\n").unwrap();
+    // The b.md entry matches a valid link, which is not suppressed
+    fs::write(
+        root.join("links-allowlist.txt"),
+        "# Known link debt\n./docs/a.md:3\ndocs/a.md:legacy.md\ndocs/a.md:b.md\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (_, stdout, _) = yore_at(
+        &root,
+        &[
+            "check-links",
+            "--json",
+            "--ignore-file",
+            "links-allowlist.txt",
+        ],
+        &index,
+    );
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["broken_links"], 1);
+    assert_eq!(v["suppressed_links"], 2);
+    assert_eq!(v["valid_links"], 1);
+    assert_eq!(v["broken"][0]["link_target"], "new-missing.md");
+
+    let (_, stdout, _) = yore_at(
+        &root,
+        &["check-links", "--ignore-file", "links-allowlist.txt"],
+        &index,
+    );
+    assert!(stdout.contains("Suppressed:   2"), "got: {stdout}");
+
+    // `check --ci` only fails on links that are not allowlisted
+    fs::write(
+        root.join("links-allowlist.txt"),
+        "docs/a.md:3\ndocs/a.md:4\ndocs/a.md:new-missing.md\n",
+    )
+    .unwrap();
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    let status = cmd
+        .current_dir(&root)
+        .args(["check", "--links", "--ci", "--fail-on", "doc_missing"])
+        .args(["--ignore-file", "links-allowlist.txt", "--index"])
+        .arg(&index)
+        .output()
+        .unwrap();
+    assert!(
+        status.status.success(),
+        "check --ci failed despite allowlist"
+    );
+    let v: Value = serde_json::from_slice(&status.stdout).unwrap();
+    assert_eq!(v["links"]["suppressed_links"], 3);
+
+    let (ok, _, stderr) = yore_at(
+        &root,
+        &["check-links", "--ignore-file", "missing.txt"],
+        &index,
+    );
+    assert!(!ok);
+    assert!(stderr.contains("missing.txt"), "got: {stderr}");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+// ── fix-links ───────────────────────────────────────────────────────
+
+#[test]
+fn test_fix_links_rewrites_to_relative_path_outside_source_dir() {
+    let root = temp_dir("fix-links-relative");
+    let guide = root.join("docs/guide");
+    let security = root.join("docs/security");
+    fs::create_dir_all(&guide).unwrap();
+    fs::create_dir_all(&security).unwrap();
+    fs::write(
+        guide.join("README.md"),
+        "# Guide\n\nSee [auth](old/auth.md) and [overview](overview.md).\n",
+    )
+    .unwrap();
+    fs::write(security.join("auth.md"), "# Auth\n\nToken rules.\n").unwrap();
+    fs::write(root.join("docs/overview.md"), "# Overview\n\nIntro.\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore_at(&root, &["fix-links", "--dry-run"], &index);
+    assert!(ok, "fix-links --dry-run failed: {stderr}");
+    assert!(stdout.contains("../security/auth.md"), "got: {stdout}");
+    assert!(stdout.contains("../overview.md"), "got: {stdout}");
+
+    let (ok, _, stderr) = yore_at(&root, &["fix-links", "--apply"], &index);
+    assert!(ok, "fix-links --apply failed: {stderr}");
+    let updated = fs::read_to_string(guide.join("README.md")).unwrap();
+    assert!(
+        updated.contains("[auth](../security/auth.md)"),
+        "got: {updated}"
+    );
+    assert!(
+        updated.contains("[overview](../overview.md)"),
+        "got: {updated}"
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_fix_links_prefer_canonical_resolves_ambiguous_basename() {
+    let root = temp_dir("fix-links-canonical");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("archive")).unwrap();
+    fs::create_dir_all(docs.join("security")).unwrap();
+    fs::write(
+        docs.join("README.md"),
+        "# Docs\n\nSee [auth](old/auth.md).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("archive/auth.md"), "# Old auth\n\nLegacy.\n").unwrap();
+    fs::write(docs.join("security/auth.md"), "# Auth\n\nCurrent.\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    // Without the flag the ambiguous link is left alone
+    let (ok, stdout, _) = yore_at(&root, &["fix-links", "--dry-run"], &index);
+    assert!(ok);
+    assert!(stdout.contains("No safe link fixes found"), "got: {stdout}");
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["fix-links", "--dry-run", "--prefer-canonical"],
+        &index,
+    );
+    assert!(ok, "fix-links --prefer-canonical failed: {stderr}");
+    assert!(stdout.contains("security/auth.md"), "got: {stdout}");
+    assert!(
+        stdout.contains("chose docs/security/auth.md"),
+        "got: {stdout}"
+    );
+    assert!(
+        stdout.contains("rejected docs/archive/auth.md"),
+        "got: {stdout}"
+    );
+
+    let (ok, stdout, _) = yore_at(
+        &root,
+        &["fix-links", "--dry-run", "--prefer-canonical", "--json"],
+        &index,
+    );
+    assert!(ok);
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    let fix = &json["fixes"][0];
+    assert_eq!(fix["new_target"], "security/auth.md");
+    assert_eq!(fix["resolution"]["chosen"], "docs/security/auth.md");
+    assert_eq!(
+        fix["resolution"]["rejected"][0]["path"],
+        "docs/archive/auth.md"
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+// ── mv ──────────────────────────────────────────────────────────────
+
+#[test]
+fn test_mv_update_refs_rewrites_relative_inbound_links() {
+    let root = temp_dir("mv-relative");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("old")).unwrap();
+    fs::create_dir_all(docs.join("guides")).unwrap();
+    fs::write(docs.join("old/auth.md"), "# Auth\n\nTokens.\n").unwrap();
+    fs::write(
+        docs.join("old/login.md"),
+        "# Login\n\nSee [auth](auth.md#tokens).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("README.md"), "# Docs\n\n- [Auth](old/auth.md)\n").unwrap();
+    fs::write(
+        docs.join("guides/intro.md"),
+        "# Intro\n\nRead [auth](../old/auth.md) first.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "mv",
+            "docs/old/auth.md",
+            "docs/security/auth.md",
+            "--update-refs",
+            "--dry-run",
+        ],
+        &index,
+    );
+    assert!(ok, "mv --dry-run failed: {stderr}");
+    assert!(
+        stdout.contains("../old/auth.md -> ../security/auth.md"),
+        "got: {stdout}"
+    );
+    assert!(docs.join("old/auth.md").exists());
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "mv",
+            "docs/old/auth.md",
+            "docs/security/auth.md",
+            "--update-refs",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "mv failed: {stderr}");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["updated_files"].as_array().unwrap().len(), 3);
+    assert!(docs.join("security/auth.md").exists());
+
+    let login = fs::read_to_string(docs.join("old/login.md")).unwrap();
+    assert!(
+        login.contains("[auth](../security/auth.md#tokens)"),
+        "got: {login}"
+    );
+    let readme = fs::read_to_string(docs.join("README.md")).unwrap();
+    assert!(readme.contains("[Auth](security/auth.md)"), "got: {readme}");
+    let intro = fs::read_to_string(docs.join("guides/intro.md")).unwrap();
+    assert!(
+        intro.contains("[auth](../security/auth.md)"),
+        "got: {intro}"
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_mv_update_refs_rewrites_moved_file_outbound_links() {
+    let root = temp_dir("mv-outbound");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("img")).unwrap();
+    fs::write(
+        docs.join("auth.md"),
+        "# Auth\n\nSee [login](login.md), [tokens](#tokens), and [site](https://example.com).\n\n![flow](img/flow.png)\n\n## Tokens\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("login.md"),
+        "# Login\n\nBack to [auth](auth.md).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("img/flow.png"), "png").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let args = [
+        "mv",
+        "docs/auth.md",
+        "docs/security/auth.md",
+        "--update-refs",
+    ];
+    let mut dry_run = args.to_vec();
+    dry_run.push("--dry-run");
+    let (ok, stdout, stderr) = yore_at(&root, &dry_run, &index);
+    assert!(ok, "mv --dry-run failed: {stderr}");
+    let inbound_at = stdout.find("Updating references").expect(&stdout);
+    let outbound_at = stdout
+        .find("Would rewrite 2 outbound link(s) in docs/security/auth.md")
+        .expect(&stdout);
+    assert!(inbound_at < outbound_at, "got: {stdout}");
+    assert!(stdout.contains("login.md -> ../login.md"), "got: {stdout}");
+    assert!(
+        stdout.contains("img/flow.png -> ../img/flow.png"),
+        "got: {stdout}"
+    );
+    assert_eq!(
+        fs::read_to_string(docs.join("auth.md"))
+            .unwrap()
+            .matches("](login.md)")
+            .count(),
+        1
+    );
+
+    let mut with_json = args.to_vec();
+    with_json.push("--json");
+    let (ok, stdout, stderr) = yore_at(&root, &with_json, &index);
+    assert!(ok, "mv failed: {stderr}");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["outbound_links"].as_array().unwrap().len(), 2);
+    assert_eq!(json["updated_files"][0], "docs/login.md");
+
+    let moved = fs::read_to_string(docs.join("security/auth.md")).unwrap();
+    assert!(moved.contains("[login](../login.md)"), "got: {moved}");
+    assert!(moved.contains("![flow](../img/flow.png)"), "got: {moved}");
+    assert!(moved.contains("[tokens](#tokens)"), "got: {moved}");
+    assert!(
+        moved.contains("[site](https://example.com)"),
+        "got: {moved}"
+    );
+    let login = fs::read_to_string(docs.join("login.md")).unwrap();
+    assert!(login.contains("[auth](security/auth.md)"), "got: {login}");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+// ── fix-references ──────────────────────────────────────────────────
+
+#[test]
+fn test_fix_references_prefix_and_regex_mappings_report_counts() {
+    let root = temp_dir("fix-references-kinds");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("README.md"),
+        "# Docs\n\n- [Auth](old/auth.md)\n- [Pay](old/pay.md)\n- [ADR](adr/0007-cache.md)\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("mappings.yaml"),
+        "\
+mappings:
+  - from: old/
+    to: current/
+    kind: prefix
+  - from: '^adr/(\\d+)-(.+)\\.md$'
+    to: 'decisions/$2-$1.md'
+    kind: regex
+",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "fix-references",
+            "--mapping",
+            "mappings.yaml",
+            "--dry-run",
+            "--apply",
+        ],
+        &index,
+    );
+    assert!(ok, "fix-references --dry-run failed: {stderr}");
+    assert!(
+        stdout.contains("old/ -> current/ (prefix): 2 link(s)"),
+        "got: {stdout}"
+    );
+    assert!(stdout.contains("(regex): 1 link(s)"), "got: {stdout}");
+    let untouched = fs::read_to_string(docs.join("README.md")).unwrap();
+    assert!(untouched.contains("[Auth](old/auth.md)"));
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "fix-references",
+            "--mapping",
+            "mappings.yaml",
+            "--apply",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "fix-references --apply failed: {stderr}");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["file_changes"][0]["mappings"][0]["links"], 2);
+    assert_eq!(json["file_changes"][0]["mappings"][1]["kind"], "regex");
+    let updated = fs::read_to_string(docs.join("README.md")).unwrap();
+    assert!(
+        updated.contains("[Auth](current/auth.md)"),
+        "got: {updated}"
+    );
+    assert!(updated.contains("[Pay](current/pay.md)"), "got: {updated}");
+    assert!(
+        updated.contains("[ADR](decisions/cache-0007.md)"),
+        "got: {updated}"
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_mv_and_fix_commands_dry_run_json_write_nothing() {
+    let root = temp_dir("dry-run-json");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("guide")).unwrap();
+    let readme = "# Docs\n\nSee [setup](setup.md) and [auth](old/auth.md).\n";
+    fs::write(docs.join("README.md"), readme).unwrap();
+    fs::write(docs.join("guide/setup.md"), "# Setup\n\nSteps.\n").unwrap();
+    fs::write(docs.join("auth.md"), "# Auth\n\nTokens.\n").unwrap();
+    fs::write(
+        root.join("mappings.yaml"),
+        "mappings:\n  - from: old/\n    to: \"\"\n    kind: prefix\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    // --dry-run wins over --apply
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["fix-links", "--dry-run", "--apply", "--json"],
+        &index,
+    );
+    assert!(ok, "fix-links failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["applied"], false);
+    let fix = &v["fixes"][0];
+    assert_eq!(fix["file"], "docs/README.md");
+    assert_eq!(fix["old_target"], "old/auth.md");
+    assert_eq!(fix["new_target"], "auth.md");
+    assert_eq!(fix["resolution"], Value::Null);
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "fix-references",
+            "--mapping",
+            "mappings.yaml",
+            "--dry-run",
+            "--apply",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "fix-references failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["applied"], false);
+    assert_eq!(v["file_changes"][0]["file"], "docs/README.md");
+    assert_eq!(v["file_changes"][0]["mappings"][0]["links"], 1);
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "mv",
+            "docs/guide/setup.md",
+            "docs/setup.md",
+            "--update-refs",
+            "--dry-run",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "mv failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["moved"], false);
+    assert_eq!(v["to"], "docs/setup.md");
+    assert!(v["updated_links"].as_array().unwrap().is_empty());
+    assert!(docs.join("guide/setup.md").exists());
+    assert!(!docs.join("setup.md").exists());
+
+    assert_eq!(fs::read_to_string(docs.join("README.md")).unwrap(), readme);
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+// ── backlinks ───────────────────────────────────────────────────────
+
+#[test]
+fn test_backlinks_finds_inbound_links() {
+    let root = temp_dir("backlinks");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    // Must use the full indexed path: docs/architecture.md
+    let (ok, stdout, _) = yore(&["backlinks", "docs/architecture.md", "--json"], &index);
+    assert!(ok, "backlinks failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let backlinks = v["backlinks"].as_array().unwrap();
+    assert!(
+        backlinks.len() >= 3,
+        "expected >=3 backlinks to architecture.md, got {}",
+        backlinks.len()
+    );
+}
+
+#[test]
+fn test_backlinks_excludes_self_links() {
+    let root = temp_dir("backlinks-self");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("auth.md"),
+        "# Auth\n\nJump to [tokens](#tokens) or [setup](auth.md#setup).\n\n## Tokens\n\n## Setup\n",
+    )
+    .unwrap();
+    fs::write(docs.join("login.md"), "# Login\n\nSee [auth](auth.md).\n").unwrap();
+    fs::write(
+        docs.join("lonely.md"),
+        "# Lonely\n\nSee [below](#below).\n\n## Below\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["backlinks", "docs/auth.md", "--json"], &index);
+    assert!(ok, "backlinks failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["total_backlinks"], 1);
+    assert_eq!(v["backlinks"][0]["source_file"], "docs/login.md");
+    assert_eq!(v["internal_anchors"], 2);
+
+    // Only self-links: the file is reported as unreferenced
+    let (ok, stdout, _) = yore(&["backlinks", "docs/lonely.md"], &index);
+    assert!(ok, "backlinks failed: {stdout}");
+    assert!(stdout.contains("Total backlinks: 0"), "got: {stdout}");
+    assert!(stdout.contains("Internal anchors: 1"), "got: {stdout}");
+    assert!(
+        stdout.contains("not referenced by any other file"),
+        "got: {stdout}"
+    );
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_backlinks_glob_groups_by_target() {
+    let root = temp_dir("backlinks-glob");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore(&["backlinks", "docs/guides/*.md", "--json"], &index);
+    assert!(ok, "backlinks failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["pattern"], "docs/guides/*.md");
+    let targets = v["targets"].as_object().unwrap();
+    assert_eq!(targets.len() as u64, v["matched_files"].as_u64().unwrap());
+    assert!(targets.keys().all(|t| t.starts_with("docs/guides/")));
+    let sources: Vec<&str> = targets["docs/guides/getting-started.md"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| b["source_file"].as_str().unwrap())
+        .collect();
+    assert_eq!(sources, ["docs/README.md"]);
+    let total: usize = targets.values().map(|b| b.as_array().unwrap().len()).sum();
+    assert_eq!(v["total_backlinks"], total);
+
+    let (ok, stdout, _) = yore(&["backlinks", "docs/guides/*.md"], &index);
+    assert!(ok);
+    assert!(
+        stdout.contains("docs/guides/getting-started.md (1)"),
+        "got: {stdout}"
+    );
+
+    let (ok, _, stderr) = yore(&["backlinks", "docs/missing/**"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("No indexed files match"), "got: {stderr}");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_path_finds_shortest_link_chain() {
+    let root = temp_dir("path");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(
+        &[
+            "path",
+            "docs/guides/getting-started.md",
+            "docs/api-reference.md",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "path failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let path: Vec<&str> = v["path"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p.as_str().unwrap())
+        .collect();
+    assert_eq!(
+        path,
+        vec![
+            "docs/guides/getting-started.md",
+            "docs/architecture.md",
+            "docs/api-reference.md"
+        ]
+    );
+    assert_eq!(v["hops"], 2);
+
+    // Nothing links back into the guide, so only --undirected connects them.
+    let (ok, stdout, _) = yore(
+        &[
+            "path",
+            "docs/api-reference.md",
+            "docs/guides/getting-started.md",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "path failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert!(v["hops"].is_null());
+    assert!(v["path"].as_array().unwrap().is_empty());
+
+    let (ok, stdout, _) = yore(
+        &[
+            "path",
+            "docs/api-reference.md",
+            "docs/guides/getting-started.md",
+            "--undirected",
+        ],
+        &index,
+    );
+    assert!(ok, "path --undirected failed: {stdout}");
+    assert!(stdout.contains("Shortest path"), "{stdout}");
+    assert!(stdout.contains("<- docs/"), "{stdout}");
+
+    let (ok, _, stderr) = yore(&["path", "docs/nope.md", "docs/README.md"], &index);
+    assert!(!ok);
+    assert!(
+        stderr.contains("'docs/nope.md' is not in the index"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_wiki_links_resolve_for_backlinks_and_flag_ambiguity() {
+    let root = temp_dir("wiki-links");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("guides")).unwrap();
+    fs::create_dir_all(docs.join("a")).unwrap();
+    fs::create_dir_all(docs.join("b")).unwrap();
+    fs::write(
+        docs.join("index.md"),
+        "# Home\n\nStart with [[Getting Started#First Steps|the guide]].\nAlso [[Notes]].\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("guides").join("getting-started.md"),
+        "# Getting Started\n\n## First Steps\n\nDo things.\n",
+    )
+    .unwrap();
+    fs::write(docs.join("a").join("notes.md"), "# Notes A\n").unwrap();
+    fs::write(docs.join("b").join("notes.md"), "# Notes B\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(
+        &["backlinks", "docs/guides/getting-started.md", "--json"],
+        &index,
+    );
+    assert!(ok, "backlinks failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let backlinks = v["backlinks"].as_array().unwrap();
+    assert_eq!(backlinks.len(), 1, "expected wiki backlink: {stdout}");
+
+    let (_, stdout, _) = yore_at(&root, &["check-links", "--json"], &index);
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let broken = v["broken"].as_array().unwrap();
+    assert_eq!(
+        broken.len(),
+        1,
+        "only the ambiguous link is broken: {stdout}"
+    );
+    assert_eq!(broken[0]["link_target"], "[[Notes]]");
+    let error = broken[0]["error"].as_str().unwrap();
+    assert!(error.contains("docs/a/notes.md") && error.contains("docs/b/notes.md"));
+}
+
+// ── orphans ─────────────────────────────────────────────────────────
+
+#[test]
+fn test_orphans_finds_unlinked_docs() {
+    let root = temp_dir("orphans");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["orphans", "--json"], &index);
+    assert!(ok, "orphans failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let orphans = v["orphans"].as_array().unwrap();
+
+    let orphan_files: Vec<&str> = orphans
+        .iter()
+        .map(|o| o["file"].as_str().unwrap())
+        .collect();
+    assert!(
+        orphan_files.iter().any(|f| f.contains("orphan-notes")),
+        "expected orphan-notes.md in orphans, got: {orphan_files:?}"
+    );
+}
+
+#[test]
+fn test_orphans_reachable_from_reports_unreachable_chains() {
+    let root = temp_dir("orphans-reachable");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(docs.join("README.md"), "# Docs\n\nStart with [a](a.md).\n").unwrap();
+    fs::write(docs.join("a.md"), "# A\n\nNext: [b](b.md).\n").unwrap();
+    fs::write(docs.join("b.md"), "# B\n\nDone.\n").unwrap();
+    // c is orphaned and d is linked only from c
+    fs::write(docs.join("c.md"), "# C\n\nSee [d](d.md).\n").unwrap();
+    fs::write(docs.join("d.md"), "# D\n\nDead end.\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let orphan_files = |stdout: &str| -> Vec<String> {
+        let v: Value = serde_json::from_str(stdout).unwrap();
+        v["orphans"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| o["file"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Default mode: only direct inbound links count
+    let (ok, stdout, _) = yore_at(&root, &["orphans", "--json"], &index);
+    assert!(ok, "orphans failed: {stdout}");
+    assert_eq!(orphan_files(&stdout), vec!["docs/README.md", "docs/c.md"]);
+
+    // Bare flag: README/index documents are the roots
+    let (ok, stdout, stderr) = yore_at(&root, &["orphans", "--reachable-from", "--json"], &index);
+    assert!(ok, "orphans --reachable-from failed: {stderr}");
+    assert_eq!(orphan_files(&stdout), vec!["docs/c.md", "docs/d.md"]);
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["reachable_from"][0], "docs/README.md");
+
+    // Explicit, repeated roots
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "orphans",
+            "--reachable-from",
+            "docs/a.md",
+            "--reachable-from",
+            "./docs/c.md",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "orphans --reachable-from failed: {stderr}");
+    assert_eq!(orphan_files(&stdout), vec!["docs/README.md"]);
+
+    let (ok, _, stderr) = yore_at(
+        &root,
+        &["orphans", "--reachable-from", "docs/missing.md"],
+        &index,
+    );
+    assert!(!ok);
+    assert!(stderr.contains("is not in the index"), "got: {stderr}");
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_csv_reports_quote_paths_and_exclude_json() {
+    let root = temp_dir("csv");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    let body = "# Runbook\n\nRestart the workers, then drain the queue slowly.\n";
+    fs::write(docs.join("ops, old.md"), body).unwrap();
+    fs::write(docs.join("ops.md"), body).unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore_at(&root, &["orphans", "--csv"], &index);
+    assert!(ok, "orphans --csv failed: {stderr}");
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(rows[0], "file,size_bytes,line_count");
+    assert!(rows[1].starts_with("\"docs/ops, old.md\","), "{stdout}");
+    assert!(rows[2].starts_with("docs/ops.md,"), "{stdout}");
+
+    let (ok, stdout, _) = yore_at(&root, &["dupes", "--csv"], &index);
+    assert!(ok);
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(rows[0], "file1,file2,jaccard,simhash,minhash,combined");
+    assert!(
+        rows[1].starts_with("\"docs/ops, old.md\",docs/ops.md,"),
+        "{stdout}"
+    );
+
+    let (ok, stdout, _) = yore_at(&root, &["stale", "--days", "0", "--csv"], &index);
+    assert!(ok);
+    assert_eq!(
+        stdout.lines().next(),
+        Some("file,days_since_modified,inbound_links,reasons")
+    );
+    assert_eq!(stdout.lines().count(), 3, "{stdout}");
+
+    let (ok, _, stderr) = yore_at(&root, &["stale", "--csv", "--json"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+}
+
+// ── canonicality ────────────────────────────────────────────────────
+
+#[test]
+fn test_canonicality_scores_all_files() {
+    let root = temp_dir("canonicality");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["canonicality", "--json"], &index);
+    assert!(ok, "canonicality failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let files = v["files"].as_array().unwrap();
+    assert!(
+        files.len() >= 9,
+        "expected all files scored, got {}",
+        files.len()
+    );
+
+    let readme = files
+        .iter()
+        .find(|f| f["file"].as_str().unwrap_or("").contains("README"));
+    assert!(readme.is_some(), "README should appear in canonicality");
+    let score = readme.unwrap()["score"].as_f64().unwrap();
+    assert!(
+        score > 0.5,
+        "README should have high canonicality, got {score}"
+    );
+}
+
+#[test]
+fn test_canonicality_graph_authority_and_no_graph() {
+    let root = temp_dir("canonicality-graph");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let score_of = |stdout: &str, name: &str| -> (f64, Vec<String>) {
+        let v: Value = serde_json::from_str(stdout).unwrap();
+        let file = v["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["file"].as_str().unwrap_or("").ends_with(name))
+            .cloned()
+            .unwrap_or_else(|| panic!("{name} missing from canonicality output"));
+        let reasons = file["reasons"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r.as_str().unwrap().to_string())
+            .collect();
+        (file["score"].as_f64().unwrap(), reasons)
+    };
+
+    let (ok, with_graph, _) = yore(&["canonicality", "--json"], &index);
+    assert!(ok, "canonicality failed: {with_graph}");
+    let (ok, without_graph, _) = yore(&["canonicality", "--json", "--no-graph"], &index);
+    assert!(ok, "canonicality --no-graph failed: {without_graph}");
+
+    // api-reference.md is linked from several documents.
+    let (graph_score, graph_reasons) = score_of(&with_graph, "api-reference.md");
+    let (plain_score, plain_reasons) = score_of(&without_graph, "api-reference.md");
+    assert!(
+        graph_score > plain_score,
+        "expected authority boost: {graph_score} vs {plain_score}"
+    );
+    assert!(
+        graph_reasons
+            .iter()
+            .any(|r| r.contains("inbound authority")),
+        "{graph_reasons:?}"
+    );
+    assert!(
+        !plain_reasons
+            .iter()
+            .any(|r| r.contains("inbound authority")),
+        "{plain_reasons:?}"
+    );
+}
+
+#[test]
+fn test_canonicality_limit_and_sort() {
+    let root = temp_dir("canonicality-limit");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["canonicality", "--json", "--sort", "asc"], &index);
+    assert!(ok, "canonicality failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let scores: Vec<f64> = v["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["score"].as_f64().unwrap())
+        .collect();
+    assert!(scores.len() >= 9);
+    assert!(
+        scores.windows(2).all(|pair| pair[0] <= pair[1]),
+        "{scores:?}"
+    );
+
+    let (ok, stdout, _) = yore(&["canonicality", "--limit", "1"], &index);
+    assert!(ok);
+    assert!(stdout.contains("... and "), "got: {stdout}");
+
+    let (ok, stdout, _) = yore(&["canonicality", "--limit", "0", "--sort", "asc"], &index);
+    assert!(ok);
+    assert!(!stdout.contains("... and "), "got: {stdout}");
+    let listed = stdout.lines().filter(|l| l.starts_with("  [")).count();
+    assert_eq!(listed, scores.len());
+    let low = stdout.find("Low canonicality").unwrap();
+    let high = stdout.find("High canonicality").unwrap();
+    assert!(low < high, "asc should list the low band first: {stdout}");
+}
+
+#[test]
+fn test_canonicality_rules_file() {
+    let root = temp_dir("canonicality-rules");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+    fs::write(
+        root.join("canon.yaml"),
+        "rules:\n  - name: API docs\n    filename_contains: [api-]\n    delta: 0.3\n",
+    )
+    .unwrap();
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["canonicality", "--json", "--rules", "canon.yaml"],
+        &index,
+    );
+    assert!(ok, "canonicality failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let api = v["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["file"] == "docs/api-reference.md")
+        .unwrap();
+    assert!(
+        api["reasons"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|r| r == "Rule 'API docs' (+0.3)"),
+        "{api}"
+    );
+
+    let (ok, _, stderr) = yore_at(&root, &["canonicality", "--rules", "missing.yaml"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("Unable to read rules file"), "{stderr}");
+}
+
+// ── canonical-orphans ───────────────────────────────────────────────
+
+#[test]
+fn test_canonical_orphans_json() {
+    let root = temp_dir("canonical-orphans");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(
+        &["canonical-orphans", "--threshold", "0.3", "--json"],
+        &index,
+    );
+    assert!(ok, "canonical-orphans failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert!(v["orphans"].is_array());
+}
+
+// ── similar ─────────────────────────────────────────────────────────
+
+#[test]
+fn test_similar_finds_related_docs() {
+    let root = temp_dir("similar");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    // similar returns a bare JSON array, not { results: [...] }
+    let (ok, stdout, _) = yore(
+        &[
+            "similar",
+            "docs/architecture.md",
+            "--json",
+            "--threshold",
+            "0.1",
+        ],
+        &index,
+    );
+    assert!(ok, "similar failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let results = v.as_array().unwrap();
+    assert!(!results.is_empty(), "expected at least one similar doc");
+
+    // architecture-v2 should be the top match
+    let top_path = results[0]["path"].as_str().unwrap_or("");
+    assert!(
+        top_path.contains("architecture-v2"),
+        "expected architecture-v2 as top similar, got {top_path}"
+    );
+}
+
+// ── dupes ───────────────────────────────────────────────────────────
+
+#[test]
+fn test_dupes_detects_near_duplicates() {
+    let root = temp_dir("dupes");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    // dupes returns a bare JSON array of pair objects
+    let (ok, stdout, _) = yore(&["dupes", "--json", "--threshold", "0.1"], &index);
+    assert!(ok, "dupes failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let pairs = v.as_array().unwrap();
+
+    let has_arch_pair = pairs.iter().any(|p| {
+        let f1 = p["file1"].as_str().unwrap_or("");
+        let f2 = p["file2"].as_str().unwrap_or("");
+        (f1.contains("architecture.md") && f2.contains("architecture-v2"))
+            || (f1.contains("architecture-v2") && f2.contains("architecture.md"))
+    });
+    assert!(
+        has_arch_pair,
+        "expected architecture.md <-> architecture-v2.md pair"
+    );
+}
+
+#[test]
+fn test_dupes_group_mode() {
+    let root = temp_dir("dupes-group");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["dupes", "--group", "--threshold", "0.01"], &index);
+    assert!(ok, "dupes --group failed: {stdout}");
+    assert!(
+        stdout.contains("Cluster 1"),
+        "expected transitive clusters, got: {stdout}"
+    );
+    assert!(
+        stdout.contains('%'),
+        "expected grouped output with similarity percentages, got: {stdout}"
+    );
+
+    // Each file appears as a member of at most one cluster
+    let members: Vec<&str> = stdout
+        .lines()
+        .filter(|l| l.starts_with("  ") && !l.starts_with("    "))
+        .map(str::trim)
+        .collect();
+    let unique: std::collections::HashSet<&str> = members.iter().copied().collect();
+    assert_eq!(members.len(), unique.len(), "duplicate members: {stdout}");
+}
+
+#[test]
+fn test_dupes_lsh_bands_follow_stored_minhash_perms() {
+    let root = temp_dir("dupes-lsh-bands");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["build", "docs", "--minhash-perms", "32", "--output"])
+        .arg(&index)
+        .output()
+        .expect("yore build failed to start");
+    assert!(output.status.success());
+
+    let (ok, stdout, _) = yore(
+        &["dupes", "--json", "--threshold", "0.1", "--lsh-bands", "8"],
+        &index,
+    );
+    assert!(ok, "dupes --lsh-bands 8 failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert!(v.as_array().is_some_and(|pairs| !pairs.is_empty()));
+
+    // 32 permutations cannot be split into 5 equal bands
+    let (ok, _, stderr) = yore(&["dupes", "--lsh-bands", "5"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("--lsh-bands"), "stderr: {stderr}");
+}
+
+// ── diff ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_diff_between_similar_files() {
+    let root = temp_dir("diff");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(
+        &[
+            "diff",
+            "docs/architecture.md",
+            "docs/architecture-v2.md",
+            "--json",
+        ],
+        &index,
+    );
+    assert!(ok, "diff failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    // similarity is an object: { combined, jaccard, simhash }
+    let sim = v["similarity"]["combined"].as_f64().unwrap();
+    assert!(sim > 0.5, "expected high similarity, got {sim}");
+    assert!(v["similarity"]["jaccard"].is_f64());
+    assert!(v["similarity"]["simhash"].is_f64());
+    assert_eq!(v["file1"], "docs/architecture.md");
+    assert_eq!(v["file2"], "docs/architecture-v2.md");
+    for key in [
+        "shared_keywords",
+        "only_in_file1",
+        "only_in_file2",
+        "shared_headings",
+    ] {
+        let list: Vec<&str> = v[key]
+            .as_array()
+            .unwrap_or_else(|| panic!("{key} should be an array"))
+            .iter()
+            .map(|k| k.as_str().unwrap())
+            .collect();
+        let mut sorted = list.clone();
+        sorted.sort_unstable();
+        assert_eq!(list, sorted, "{key} should be sorted");
+    }
+    assert!(!v["shared_headings"].as_array().unwrap().is_empty());
+    let sections = &v["sections"];
+    assert!(
+        !sections["matched"].as_array().unwrap().is_empty(),
+        "expected aligned sections: {sections}"
+    );
+    let pair = &sections["matched"][0];
+    assert!(pair["file1"]["heading"].is_string());
+    assert!(pair["similarity"].is_f64());
+    assert!(pair["near_identical"].is_boolean());
+    assert!(sections["only_in_file1"].is_array());
+    assert!(sections["only_in_file2"].is_array());
+
+    let (ok, stdout, _) = yore(
+        &["diff", "docs/architecture.md", "docs/architecture-v2.md"],
+        &index,
+    );
+    assert!(ok, "diff failed: {stdout}");
+    assert!(stdout.contains("Section Alignment"), "{stdout}");
+}
+
+// ── export-graph ────────────────────────────────────────────────────
+
+#[test]
+fn test_export_graph_json() {
+    let root = temp_dir("export-graph-json");
+    write_fixture(&root);
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
     let (ok, stdout, _) = yore(&["export-graph", "--format", "json"], &index);
     assert!(ok, "export-graph json failed: {stdout}");
     let v: Value = serde_json::from_str(&stdout).unwrap();
-    assert!(v["nodes"].is_array());
-    assert!(v["edges"].is_array());
-    let nodes = v["nodes"].as_array().unwrap();
-    assert!(nodes.len() >= 9, "expected all docs as nodes");
+    assert!(v["nodes"].is_array());
+    assert!(v["edges"].is_array());
+    let nodes = v["nodes"].as_array().unwrap();
+    assert!(nodes.len() >= 9, "expected all docs as nodes");
+    for node in nodes {
+        assert!(node["line_count"].as_u64().unwrap() > 0, "{node}");
+        assert!(node["size_bytes"].as_u64().unwrap() > 0, "{node}");
+        let canonicality = node["canonicality"].as_f64().unwrap();
+        assert!((0.0..=1.0).contains(&canonicality), "{node}");
+        assert!(node["inbound_links"].is_u64(), "{node}");
+    }
+    assert!(
+        nodes
+            .iter()
+            .any(|n| n["inbound_links"].as_u64().unwrap() > 0),
+        "expected at least one linked-to doc"
+    );
+}
+
+#[test]
+fn test_export_graph_dot() {
+    let root = temp_dir("export-graph-dot");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["export-graph", "--format", "dot"], &index);
+    assert!(ok, "export-graph dot failed: {stdout}");
+    assert!(stdout.contains("digraph"), "expected DOT digraph output");
+    assert!(stdout.contains("->"), "expected edges in DOT output");
+    assert!(
+        stdout.contains("[line_count=") && stdout.contains("inbound_links="),
+        "expected node metadata attributes: {stdout}"
+    );
+}
+
+#[test]
+fn test_export_graph_mermaid() {
+    let root = temp_dir("export-graph-mermaid");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["export-graph", "--format", "mermaid"], &index);
+    assert!(ok, "export-graph mermaid failed: {stdout}");
+    assert!(stdout.starts_with("```mermaid\ngraph LR\n"));
+    assert!(stdout.contains("-->"), "expected edges in Mermaid output");
+    assert!(
+        stdout.contains("[\"architecture.md\"]"),
+        "expected basename labels"
+    );
+
+    let (ok, _, stderr) = yore(&["export-graph", "--format", "svg"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("Unsupported format: svg"), "{stderr}");
+}
+
+#[test]
+fn test_export_graph_include_external() {
+    let root = temp_dir("export-graph-external");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("a.md"),
+        "# A\n\nSee [B](b.md), [Rust](https://www.rust-lang.org/learn), and [docs](https://www.rust-lang.org/).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("b.md"), "# B\n\nBack to [A](a.md).\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore_at(&root, &["export-graph", "--format", "json"], &index);
+    assert!(ok, "export-graph failed: {stdout}");
+    assert!(
+        !stdout.contains("rust-lang.org"),
+        "default is internal-only"
+    );
+
+    let (ok, stdout, _) = yore_at(
+        &root,
+        &["export-graph", "--format", "json", "--include-external"],
+        &index,
+    );
+    assert!(ok, "export-graph --include-external failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let host = v["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|n| n["id"] == "www.rust-lang.org")
+        .expect("external host node");
+    assert_eq!(host["kind"], "external");
+    assert_eq!(host["inbound_links"], 2);
+    let external_edges: Vec<&Value> = v["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["kind"] == "external")
+        .collect();
+    assert_eq!(external_edges.len(), 2);
+    assert!(v["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|e| e.get("kind").is_none()));
+
+    let (ok, stdout, _) = yore_at(
+        &root,
+        &["export-graph", "--format", "dot", "--include-external"],
+        &index,
+    );
+    assert!(ok);
+    assert!(stdout.contains("\"www.rust-lang.org\" [shape=box, style=dashed"));
+    assert!(stdout.contains("-> \"www.rust-lang.org\" [style=dashed];"));
+}
+
+// ── assemble (standalone) ───────────────────────────────────────────
+
+#[test]
+fn test_assemble_produces_context_digest() {
+    let root = temp_dir("assemble");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(&["assemble", "architecture layered"], &index);
+    assert!(ok, "assemble failed: {stdout}");
+    assert!(
+        stdout.contains("Context Digest"),
+        "expected Context Digest header"
+    );
+    assert!(stdout.contains("\n## Sources\n\n- docs/"), "{stdout}");
+
+    let (_, stats, _) = yore(&["stats", "--json"], &index);
+    let total_files = serde_json::from_str::<Value>(&stats).unwrap()["total_files"].clone();
+    assert!(
+        stdout.contains(&format!("**Documents Scanned:** {total_files}\n")),
+        "{stdout}"
+    );
+    let generated = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("**Generated:** "))
+        .expect("Generated line");
+    assert!(
+        generated.len() == 20 && generated.as_bytes()[10] == b'T' && generated.ends_with('Z'),
+        "expected an RFC 3339 timestamp, got {generated}"
+    );
+}
+
+#[test]
+fn test_assemble_json_format() {
+    let root = temp_dir("assemble-json");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore(
+        &["assemble", "architecture layered", "--format", "json"],
+        &index,
+    );
+    assert!(ok, "assemble --format json failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["query"], "architecture layered");
+    assert_eq!(v["max_tokens"], 8000);
+    let sections = v["sections"].as_array().unwrap();
+    assert!(!sections.is_empty(), "expected selected sections");
+    let first = &sections[0];
+    for field in [
+        "doc_path",
+        "heading",
+        "line_start",
+        "line_end",
+        "bm25_score",
+        "canonicality",
+        "content",
+    ] {
+        assert!(!first[field].is_null(), "missing section field {field}");
+    }
+
+    let sources = v["sources"].as_array().unwrap();
+    assert!(!sources.is_empty(), "expected a sources manifest");
+    for section in sections {
+        let doc = &section["doc_path"];
+        let source = sources
+            .iter()
+            .find(|s| &s["doc_path"] == doc)
+            .unwrap_or_else(|| panic!("{doc} missing from sources"));
+        let range = serde_json::json!([section["line_start"], section["line_end"]]);
+        assert!(source["line_ranges"].as_array().unwrap().contains(&range));
+    }
+
+    let (ok, _, stderr) = yore(&["assemble", "architecture", "--format", "xml"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("Unsupported format"));
+}
+
+#[test]
+fn test_assemble_text_format() {
+    let root = temp_dir("assemble-text");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore(
+        &["assemble", "architecture layered", "--format", "text"],
+        &index,
+    );
+    assert!(ok, "assemble --format text failed: {stderr}");
+    assert!(
+        stdout.starts_with("Query: architecture layered\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("\n// docs/"), "{stdout}");
+    for markup in ["# Context Digest", "\n---\n", "## Sources", "## Metadata"] {
+        assert!(
+            !stdout.contains(markup),
+            "unexpected {markup:?} in {stdout}"
+        );
+    }
+}
+
+#[test]
+fn test_assemble_depth_follows_multiple_hops() {
+    let root = temp_dir("assemble-depth");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("start.md"),
+        "# Zebra Rollout\n\nZebra rollout plan. See [hop one](hop1.md).\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("hop1.md"),
+        "# Hop One\n\nIntermediate notes. See [hop two](hop2.md).\n",
+    )
+    .unwrap();
+    fs::write(docs.join("hop2.md"), "# Hop Two\n\nDeep detail only.\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let doc_paths = |depth: &str| -> Vec<String> {
+        let (ok, stdout, stderr) = yore_at(
+            &root,
+            &["assemble", "zebra", "--depth", depth, "--format", "json"],
+            &index,
+        );
+        assert!(ok, "assemble --depth {depth} failed: {stderr}");
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        v["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["doc_path"].as_str().unwrap().to_string())
+            .collect()
+    };
+    let has = |paths: &[String], name: &str| paths.iter().any(|p| p.ends_with(name));
+
+    let depth0 = doc_paths("0");
+    assert!(has(&depth0, "start.md"));
+    assert!(!has(&depth0, "hop1.md"), "depth 0 must skip expansion");
+
+    let depth1 = doc_paths("1");
+    assert!(has(&depth1, "hop1.md"), "depth 1 should follow one hop");
+    assert!(!has(&depth1, "hop2.md"), "depth 1 must not reach hop two");
+
+    let depth2 = doc_paths("2");
+    assert!(has(&depth2, "hop1.md"));
+    assert!(has(&depth2, "hop2.md"), "depth 2 should follow two hops");
+}
+
+#[test]
+fn test_assemble_xref_budget_flags() {
+    let root = temp_dir("assemble-xref-budget");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("start.md"),
+        "# Zebra Rollout\n\nZebra rollout plan. See [details](details.md).\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("details.md"),
+        "# Details\n\nLinked rollout detail that expansion pulls in.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let has_details = |extra: &[&str]| -> bool {
+        let mut args = vec!["assemble", "zebra", "--format", "json"];
+        args.extend_from_slice(extra);
+        let (ok, stdout, stderr) = yore_at(&root, &args, &index);
+        assert!(ok, "assemble {extra:?} failed: {stderr}");
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        v["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|s| s["doc_path"].as_str().unwrap().ends_with("details.md"))
+    };
+
+    assert!(has_details(&[]), "defaults should expand the link");
+    assert!(!has_details(&["--xref-max-tokens", "0"]));
+    assert!(!has_details(&["--xref-fraction", "0"]));
+    assert!(!has_details(&["--xref-per-doc-tokens", "1"]));
+
+    let (ok, _, stderr) = yore_at(
+        &root,
+        &["assemble", "zebra", "--xref-fraction", "1.5"],
+        &index,
+    );
+    assert!(!ok);
+    assert!(
+        stderr.contains("--xref-fraction must be between"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_assemble_reads_query_from_file_or_stdin() {
+    use std::io::Write as _;
+    use std::process::Stdio;
+
+    let root = temp_dir("assemble-query-input");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("rollback.md"),
+        "# Rollback\n\nRoll back a release by redeploying the previous tag.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    fs::write(
+        root.join("question.txt"),
+        "How do we roll back\na release?\n",
+    )
+    .unwrap();
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "assemble",
+            "--query-file",
+            "question.txt",
+            "--format",
+            "json",
+        ],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["query"], "How do we roll back a release?");
+    assert_eq!(v["sections"][0]["doc_path"], "docs/rollback.md");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["assemble", "--format", "json", "--index"])
+        .arg(&index)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"previous tag rollback\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let v: Value = serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap();
+    assert_eq!(v["query"], "previous tag rollback");
+
+    // No arguments, file, or piped input
+    let (ok, _, stderr) = yore_at(&root, &["assemble"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("No query provided"), "{stderr}");
+
+    let (ok, _, stderr) = yore_at(&root, &["assemble", "--query-file", "absent.txt"], &index);
+    assert!(!ok);
+    assert!(
+        stderr.contains("Failed to read --query-file absent.txt"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_assemble_exclude_filters_search_and_crossrefs() {
+    let root = temp_dir("assemble-exclude");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("archive")).unwrap();
+    fs::write(
+        docs.join("start.md"),
+        "# Zebra Rollout\n\nZebra rollout plan. See [legacy notes](archive/linked.md).\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("archive/zebra.md"),
+        "# Old Zebra\n\nZebra zebra rollout from last year.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("archive/linked.md"),
+        "# Linked\n\nArchived detail reached only by a link.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let assemble = |extra: &[&str]| -> (Vec<String>, String) {
+        let mut args = vec!["assemble", "zebra rollout", "--format", "json"];
+        args.extend_from_slice(extra);
+        let (ok, stdout, stderr) = yore_at(&root, &args, &index);
+        assert!(ok, "assemble failed: {stderr}");
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        let paths = v["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["doc_path"].as_str().unwrap().to_string())
+            .collect();
+        (paths, stdout)
+    };
+    let has = |paths: &[String], name: &str| paths.iter().any(|p| p.ends_with(name));
+
+    let (all, _) = assemble(&[]);
+    assert!(has(&all, "archive/zebra.md"));
+    assert!(has(&all, "archive/linked.md"));
+
+    let (filtered, _) = assemble(&["--exclude", "**/archive/**"]);
+    assert!(has(&filtered, "start.md"));
+    assert!(!has(&filtered, "archive/zebra.md"), "excluded from search");
+    assert!(
+        !has(&filtered, "archive/linked.md"),
+        "excluded from cross-reference expansion"
+    );
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["assemble", "zebra rollout", "--exclude", "**/archive/**"],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    // archive/linked.md never mentions the query, so only zebra.md counts
+    assert!(
+        stdout.contains("**Documents Excluded:** 1 (matched --exclude)"),
+        "{stdout}"
+    );
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "assemble",
+            "zebra rollout",
+            "--format",
+            "text",
+            "--exclude",
+            "**/archive/**",
+        ],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    assert!(stdout.contains("[1 document excluded by --exclude]"));
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["query", "zebra", "--json", "--exclude", "**/archive/**"],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    assert!(stdout.contains("start.md"), "{stdout}");
+    assert!(!stdout.contains("archive/zebra.md"), "{stdout}");
+
+    let (ok, _, stderr) = yore_at(&root, &["query", "zebra", "--exclude", "a{b"], &index);
+    assert!(!ok);
+    assert!(stderr.contains("Invalid --exclude glob"), "{stderr}");
+}
+
+#[test]
+fn test_assemble_include_restricts_scope() {
+    let root = temp_dir("assemble-include");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("architecture")).unwrap();
+    fs::write(
+        docs.join("architecture/gateway.md"),
+        "# Gateway\n\nThe gateway routes zebra traffic. See [ops](../ops.md).\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("architecture/legacy.md"),
+        "# Legacy Gateway\n\nOld zebra routing notes.\n",
+    )
+    .unwrap();
+    fs::write(docs.join("ops.md"), "# Ops\n\nZebra on-call runbook.\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "assemble",
+            "zebra",
+            "--format",
+            "json",
+            "--include",
+            "**/architecture/**",
+            "--exclude",
+            "**/legacy.md",
+        ],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let paths: Vec<&str> = v["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["doc_path"].as_str().unwrap())
+        .collect();
+    assert!(paths.iter().any(|p| p.ends_with("gateway.md")), "{paths:?}");
+    assert!(
+        !paths
+            .iter()
+            .any(|p| p.ends_with("ops.md") || p.ends_with("legacy.md")),
+        "{paths:?}"
+    );
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["assemble", "zebra", "--include", "**/architecture/**"],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    assert!(
+        stdout.contains("**Scope:** include **/architecture/**\n"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("Documents Excluded"), "{stdout}");
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "assemble",
+            "zebra",
+            "--format",
+            "text",
+            "--include",
+            "**/architecture/**",
+        ],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    assert!(
+        stdout.starts_with("Query: zebra\nScope: include **/architecture/**\n\n"),
+        "{stdout}"
+    );
 }
 
 #[test]
-fn test_export_graph_dot() {
-    let root = temp_dir("export-graph-dot");
-    write_fixture(&root);
+fn test_type_filter_limits_query_and_assemble() {
+    let root = temp_dir("type-filter");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(docs.join("guide.md"), "# Guide\n\nZebra rollout steps.\n").unwrap();
+    fs::write(
+        docs.join("runbook.rst"),
+        "Runbook\n=======\n\nZebra rollout checklist.\n",
+    )
+    .unwrap();
+    fs::write(docs.join("notes.txt"), "Zebra rollout scratch notes.\n").unwrap();
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (ok, stdout, _) = yore(&["export-graph", "--format", "dot"], &index);
-    assert!(ok, "export-graph dot failed: {stdout}");
-    assert!(stdout.contains("digraph"), "expected DOT digraph output");
-    assert!(stdout.contains("->"), "expected edges in DOT output");
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &["query", "zebra", "--json", "--type", "md,RST"],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["types"], serde_json::json!(["md", "rst"]));
+    let paths: Vec<&str> = v["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths.len(), 2, "{paths:?}");
+    assert!(!paths.iter().any(|p| p.ends_with("notes.txt")), "{paths:?}");
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "assemble",
+            "zebra rollout",
+            "--format",
+            "json",
+            "--type",
+            "txt",
+        ],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(v["types"], serde_json::json!(["txt"]));
+    assert!(v["sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|s| s["doc_path"].as_str().unwrap().ends_with("notes.txt")));
+
+    let (ok, _, stderr) = yore_at(&root, &["query", "zebra", "--type", "adoc"], &index);
+    assert!(!ok);
+    assert!(
+        stderr.contains("Unknown --type 'adoc'; the index has: md, rst, txt"),
+        "{stderr}"
+    );
 }
 
-// ── assemble (standalone) ───────────────────────────────────────────
+#[test]
+fn test_assemble_canonicality_weight_is_validated() {
+    let root = temp_dir("assemble-canon-weight");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    for weight in ["0", "1"] {
+        let (ok, stdout, stderr) = yore(
+            &["assemble", "architecture", "--canonicality-weight", weight],
+            &index,
+        );
+        assert!(ok, "weight {weight} failed: {stderr}");
+        assert!(stdout.contains("Context Digest"));
+    }
+
+    let (ok, _, stderr) = yore(
+        &["assemble", "architecture", "--canonicality-weight", "1.5"],
+        &index,
+    );
+    assert!(!ok);
+    assert!(
+        stderr.contains("--canonicality-weight must be between 0.0 and 1.0"),
+        "{stderr}"
+    );
+}
 
 #[test]
-fn test_assemble_produces_context_digest() {
-    let root = temp_dir("assemble");
+fn test_timing_flag_gates_stderr_timings() {
+    let root = temp_dir("timing");
     write_fixture(&root);
     let index = root.join(".yore");
     build_index(&root, "docs", &index);
 
-    let (ok, stdout, _) = yore(&["assemble", "architecture layered"], &index);
-    assert!(ok, "assemble failed: {stdout}");
+    let (ok, _, stderr) = yore(&["dupes"], &index);
+    assert!(ok);
+    assert!(stderr.is_empty(), "dupes stderr should be clean: {stderr}");
+    let (ok, _, stderr) = yore(&["--timing", "dupes"], &index);
+    assert!(ok);
+    assert!(stderr.contains("LSH duplicate detection:"), "{stderr}");
+
+    let (ok, _, stderr) = yore(
+        &["assemble", "architecture", "--format", "json", "--timing"],
+        &index,
+    );
+    assert!(ok, "{stderr}");
+    for phase in [
+        "primary selection",
+        "xref expansion",
+        "refinement",
+        "distill",
+    ] {
+        assert!(
+            stderr.contains(&format!("timing: {phase}:")),
+            "missing {phase}: {stderr}"
+        );
+    }
+
+    let (ok, _, stderr) = yore(&["--timing", "--quiet", "assemble", "architecture"], &index);
+    assert!(ok);
+    assert!(!stderr.contains("timing:"), "{stderr}");
+}
+
+#[test]
+fn test_assemble_merges_copied_sections() {
+    let root = temp_dir("assemble-dedup");
+    let docs = root.join("docs");
+    fs::create_dir_all(docs.join("adr")).unwrap();
+    fs::create_dir_all(docs.join("scratch")).unwrap();
+    let body = "Credential rotation starts by running the rotate script against the \
+                staging vault, waiting for the gateway health check to report green, \
+                and then restarting each gateway pod one at a time so that traffic keeps \
+                flowing while the new credential rotation settles across every region.\n";
+    fs::write(
+        docs.join("scratch/notes.md"),
+        format!("# Notes\n\n## Rotating Credentials\n\n{body}"),
+    )
+    .unwrap();
+    fs::write(
+        docs.join("adr/ADR-010.md"),
+        format!("# ADR-010\n\n## Credential Rotation\n\n{body}"),
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let sections = |extra: &[&str]| -> Vec<Value> {
+        let mut args = vec!["assemble", "credential rotation", "--format", "json"];
+        args.extend_from_slice(extra);
+        let (ok, stdout, stderr) = yore_at(&root, &args, &index);
+        assert!(ok, "assemble failed: {stderr}");
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        v["sections"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|s| s["content"].as_str().unwrap().contains("gateway pod"))
+            .cloned()
+            .collect()
+    };
+
+    let merged = sections(&[]);
+    assert_eq!(merged.len(), 1, "{merged:?}");
+    assert_eq!(merged[0]["doc_path"], "docs/adr/ADR-010.md");
+    assert_eq!(merged[0]["also_in"][0], "docs/scratch/notes.md");
+
+    assert_eq!(sections(&["--no-dedup"]).len(), 2);
+
+    let (ok, stdout, _) = yore_at(&root, &["assemble", "credential rotation"], &index);
+    assert!(ok);
     assert!(
-        stdout.contains("Context Digest"),
-        "expected Context Digest header"
+        stdout.contains("*(also in docs/scratch/notes.md)*"),
+        "{stdout}"
+    );
+
+    let (ok, _, stderr) = yore_at(
+        &root,
+        &[
+            "assemble",
+            "credential rotation",
+            "--dedup-threshold",
+            "1.5",
+        ],
+        &index,
+    );
+    assert!(!ok);
+    assert!(
+        stderr.contains("--dedup-threshold must be between"),
+        "{stderr}"
     );
 }
 
@@ -562,6 +2703,48 @@ fn test_check_links_flag() {
     );
 }
 
+#[test]
+fn test_check_links_reports_duplicate_anchors() {
+    let root = temp_dir("check-anchor-duplicate");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("guide.md"),
+        "# Guide\n\n## Setup\n\nFirst.\n\n## Usage\n\n## Setup\n\nSecond.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, _) = yore_at(
+        &root,
+        &["check", "--links", "--ci", "--fail-on", "anchor_duplicate"],
+        &index,
+    );
+    assert!(
+        !ok,
+        "duplicate anchors should fail --fail-on anchor_duplicate"
+    );
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let by_kind = v["links"]["summary"]["by_kind"].as_array().unwrap();
+    assert!(by_kind
+        .iter()
+        .any(|k| k["kind"] == "anchor_duplicate" && k["count"] == 1));
+    // Headings are not links: nothing is broken and the link counts are untouched
+    assert_eq!(v["links"]["total_links"], 0);
+    assert_eq!(v["links"]["broken_links"], 0);
+    assert!(v["links"]["broken"].as_array().unwrap().is_empty());
+    let dup = &v["links"]["duplicate_anchors"][0];
+    assert_eq!(dup["file"], "docs/guide.md");
+    assert_eq!(dup["anchor"], "setup");
+    assert_eq!(dup["lines"], serde_json::json!([3, 9]));
+    let error = dup["error"].as_str().unwrap();
+    assert!(
+        error.contains("line 3") && error.contains("line 9"),
+        "{error}"
+    );
+}
+
 #[test]
 fn test_check_dupes_flag() {
     let root = temp_dir("check-dupes-flag");
@@ -597,6 +2780,37 @@ fn test_suggest_consolidation_json() {
         v["total_groups"].as_u64().unwrap() >= 1,
         "expected at least one consolidation group"
     );
+    assert!(v["groups"][0].get("unique_sections").is_none());
+
+    let (ok, stdout, _) = yore(
+        &[
+            "suggest-consolidation",
+            "--plan",
+            "--json",
+            "--threshold",
+            "0.3",
+        ],
+        &index,
+    );
+    assert!(ok, "suggest-consolidation --plan failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    for group in v["groups"].as_array().unwrap() {
+        let unique = group["unique_sections"].as_array().unwrap();
+        let duplicated = group["duplicated_sections"].as_array().unwrap();
+        for section in unique.iter().chain(duplicated) {
+            assert!(section["file"].is_string(), "{section}");
+            assert!(section["heading"].is_string(), "{section}");
+            assert_eq!(section["lines"].as_array().unwrap().len(), 2);
+        }
+    }
+
+    let (ok, stdout, _) = yore(
+        &["suggest-consolidation", "--plan", "--threshold", "0.3"],
+        &index,
+    );
+    assert!(ok);
+    assert!(stdout.contains("Carry over ("), "{stdout}");
+    assert!(stdout.contains("Already in canonical ("), "{stdout}");
 }
 
 // ── eval ────────────────────────────────────────────────────────────
@@ -702,21 +2916,270 @@ fn test_eval_without_relevant_docs_omits_metrics() {
     let output = cmd.output().expect("eval failed");
     let stdout = String::from_utf8_lossy(&output.stdout);
 
-    assert!(output.status.success(), "eval --json failed: {stdout}");
-    let v: Value = serde_json::from_str(&stdout).unwrap();
+    assert!(output.status.success(), "eval --json failed: {stdout}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+
+    // Per-question ranking should be absent
+    let results = v["results"].as_array().unwrap();
+    assert!(
+        results[0]["ranking"].is_null(),
+        "ranking should be absent when no relevant_docs"
+    );
+
+    // Aggregate ranking_metrics should be absent
+    assert!(
+        v["ranking_metrics"].is_null(),
+        "ranking_metrics should be absent when no relevant_docs"
+    );
+}
+
+#[test]
+fn test_eval_synonyms_expand_queries() {
+    let root = temp_dir("eval-synonyms");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("kube.md"),
+        "# Cluster\n\nThe k8s cluster runs three nodes.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("other.md"),
+        "# Billing\n\nInvoices go out monthly.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let questions = root.join("questions.jsonl");
+    fs::write(
+        &questions,
+        "{\"id\": 1, \"q\": \"kubernetes\", \"expect\": [\"nodes\"], \"relevant_docs\": [\"docs/kube.md\"]}\n",
+    )
+    .unwrap();
+    let synonyms = root.join("synonyms.yaml");
+    fs::write(&synonyms, "kubernetes: [k8s, kube]\n").unwrap();
+
+    let eval = |extra: &[&str]| -> Value {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.current_dir(&root)
+            .args(["eval", "--json", "--questions"])
+            .arg(&questions)
+            .args(extra)
+            .arg("--index")
+            .arg(&index);
+        let output = cmd.output().expect("eval failed");
+        assert!(output.status.success());
+        serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap()
+    };
+
+    let plain = eval(&[]);
+    assert!(!plain["results"][0]["passed"].as_bool().unwrap());
+    let expanded = eval(&["--synonyms", "synonyms.yaml"]);
+    assert!(expanded["results"][0]["passed"].as_bool().unwrap());
+    assert_eq!(expanded["results"][0]["ranking"]["mrr"], 1.0);
+}
+
+#[test]
+fn test_eval_reports_misses_and_fail_under_gates() {
+    let root = temp_dir("eval-fail-under");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("deploy.md"),
+        "# Deploy\n\nDeploy with the blue green rollout.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let questions = root.join("questions.jsonl");
+    fs::write(
+        &questions,
+        "{\"id\": 1, \"q\": \"deploy\", \"expect\": [\"blue green\"]}\n\
+         {\"id\": 2, \"q\": \"deploy rollout\", \"expect\": [\"rollout\", \"canary\"]}\n",
+    )
+    .unwrap();
+
+    let eval = |extra: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.args(["eval", "--questions"])
+            .arg(&questions)
+            .args(extra)
+            .arg("--index")
+            .arg(&index);
+        cmd.output().expect("eval failed")
+    };
+
+    let output = eval(&["--json"]);
+    assert!(output.status.success());
+    let v: Value = serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap();
+    assert_eq!(v["passed"], 1);
+    assert!((v["recall"].as_f64().unwrap() - 2.0 / 3.0).abs() < 1e-9);
+    assert!(v["mean_tokens"].as_f64().unwrap() > 0.0);
+    assert_eq!(v["results"][1]["id"], 2);
+    assert_eq!(v["results"][1]["hits"], 1);
+    assert_eq!(v["results"][1]["missing"], serde_json::json!(["canary"]));
+
+    let output = eval(&[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("missed: \"canary\""), "{stdout}");
+    assert!(stdout.contains("Recall: 2/3 (67%)"), "{stdout}");
+
+    // Half the questions pass: 0.5 is met, 0.75 is not.
+    assert!(eval(&["--fail-under", "0.5"]).status.success());
+    let output = eval(&["--json", "--fail-under", "0.75"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("below --fail-under 75%"), "{stderr}");
+}
+
+#[test]
+fn test_eval_follows_assemble_depth_and_budget() {
+    let root = temp_dir("eval-depth");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("start.md"),
+        "# Zebra Rollout\n\nZebra rollout plan. See [details](details.md).\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("details.md"),
+        "# Details\n\nThe canary window lasts one hour.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let questions = root.join("questions.jsonl");
+    fs::write(
+        &questions,
+        "{\"id\": 1, \"q\": \"zebra rollout\", \"expect\": [\"canary window\"]}\n",
+    )
+    .unwrap();
+
+    let eval = |extra: &[&str]| -> Value {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.args(["eval", "--json", "--questions"])
+            .arg(&questions)
+            .args(extra)
+            .arg("--index")
+            .arg(&index);
+        let output = cmd.output().expect("eval failed");
+        assert!(output.status.success());
+        serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap()
+    };
+
+    // The answer is only reachable through cross-reference expansion
+    let expanded = eval(&[]);
+    assert_eq!(expanded["passed"], 1, "{expanded}");
+    assert_eq!(eval(&["--depth", "0"])["passed"], 0);
+
+    let small = eval(&["--max-tokens", "40"]);
+    assert!(
+        small["mean_tokens"].as_f64().unwrap() < expanded["mean_tokens"].as_f64().unwrap(),
+        "{small}"
+    );
+}
+
+#[test]
+fn test_eval_expect_files_checks_source_documents() {
+    let root = temp_dir("eval-expect-files");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("deploy.md"),
+        "# Deploy\n\nDeploy with the blue green rollout.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("billing.md"),
+        "# Billing\n\nInvoices go out monthly.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    // Question 2 expects a file that holds nothing about deploys.
+    let questions = root.join("questions.jsonl");
+    fs::write(
+        &questions,
+        "{\"id\": 1, \"q\": \"deploy\", \"expect_files\": [\"docs/deploy.md\"]}\n\
+         {\"id\": 2, \"q\": \"deploy\", \"expect\": [\"rollout\"], \"expect_files\": [\"docs/billing.md\"]}\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.args(["eval", "--json", "--questions"])
+        .arg(&questions)
+        .arg("--index")
+        .arg(&index);
+    let output = cmd.output().expect("eval failed");
+    assert!(output.status.success());
+    let v: Value = serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap();
+
+    let first = &v["results"][0];
+    assert!(first["passed"].as_bool().unwrap());
+    assert_eq!(
+        first["files"]["found"],
+        serde_json::json!(["docs/deploy.md"])
+    );
+    assert_eq!(first["files"]["recall"], 1.0);
+
+    // The substring matched, but the expected source was not retrieved.
+    let second = &v["results"][1];
+    assert_eq!(second["hits"], 1);
+    assert!(!second["passed"].as_bool().unwrap());
+    assert_eq!(
+        second["files"]["missing"],
+        serde_json::json!(["docs/billing.md"])
+    );
+
+    assert_eq!(v["file_metrics"]["questions_with_expect_files"], 2);
+    assert_eq!(v["file_metrics"]["mean_recall"], 0.5);
+}
+
+// ── index version ───────────────────────────────────────────────────
 
-    // Per-question ranking should be absent
-    let results = v["results"].as_array().unwrap();
-    assert!(
-        results[0]["ranking"].is_null(),
-        "ranking should be absent when no relevant_docs"
-    );
+#[test]
+fn test_old_index_version_warns_and_strict_fails() {
+    let root = temp_dir("old-index-version");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
 
-    // Aggregate ranking_metrics should be absent
-    assert!(
-        v["ranking_metrics"].is_null(),
-        "ranking_metrics should be absent when no relevant_docs"
+    // Downgrade to a v1-style index without BM25 statistics or MinHash
+    let forward = index.join("forward_index.json");
+    let mut v: Value = serde_json::from_str(&fs::read_to_string(&forward).unwrap()).unwrap();
+    v["version"] = Value::from(1);
+    v.as_object_mut().unwrap().remove("idf_map");
+    for entry in v["files"].as_object_mut().unwrap().values_mut() {
+        entry["minhash"] = Value::Array(Vec::new());
+    }
+    fs::write(&forward, serde_json::to_string(&v).unwrap()).unwrap();
+    let _ = fs::remove_file(index.join("forward_index.bin"));
+
+    let (ok, _, stderr) = yore(&["query", "architecture"], &index);
+    assert!(ok, "{stderr}");
+    assert!(stderr.contains("index version 1 is older"), "{stderr}");
+    assert!(stderr.contains("no BM25 statistics"), "{stderr}");
+
+    let (ok, _, stderr) = yore(&["dupes"], &index);
+    assert!(ok, "{stderr}");
+    assert!(stderr.contains("no MinHash signatures"), "{stderr}");
+
+    // `check` loads the index once per check but reports it once
+    let (_, _, stderr) = yore(&["check", "--links", "--stale"], &index);
+    assert_eq!(
+        stderr.matches("index version 1 is older").count(),
+        1,
+        "{stderr}"
     );
+
+    let (ok, _, stderr) = yore(&["--strict", "query", "architecture"], &index);
+    assert!(!ok, "--strict should reject an old index");
+    assert!(stderr.contains("run `yore build`"), "{stderr}");
 }
 
 // ── build JSON output ───────────────────────────────────────────────
@@ -741,6 +3204,188 @@ fn test_build_json_output() {
     assert!(v["total_relations"].as_u64().is_some());
 }
 
+#[test]
+fn test_build_is_reproducible() {
+    let root = temp_dir("build-reproducible");
+    write_fixture(&root);
+    let index = root.join(".yore");
+
+    let build = |jobs: &str| {
+        let _ = fs::remove_dir_all(&index);
+        let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+            .current_dir(&root)
+            .env("SOURCE_DATE_EPOCH", "1700000000")
+            .args(["build", "docs", "--jobs", jobs, "--output"])
+            .arg(&index)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        [
+            "forward_index.json",
+            "reverse_index.json",
+            "document_metrics.json",
+        ]
+        .map(|name| fs::read_to_string(index.join(name)).unwrap())
+    };
+
+    let first = build("4");
+    let second = build("1");
+    assert_eq!(first, second);
+
+    let v: Value = serde_json::from_str(&first[0]).unwrap();
+    assert_eq!(v["indexed_at"], "2023-11-14T22:13:20Z");
+    let keywords: Vec<&str> = v["files"]["docs/architecture.md"]["body_keywords"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|k| k.as_str().unwrap())
+        .collect();
+    assert!(!keywords.is_empty());
+    assert!(keywords.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+// ── build skip dirs ─────────────────────────────────────────────────
+
+#[test]
+fn test_build_skips_whole_directory_names_only() {
+    let root = temp_dir("build-skip-dirs");
+    for path in [
+        "docs/vendoring.md",
+        "docs/vendor-integration/setup.md",
+        "vendor/lib.md",
+        "docs/node_modules/pkg/readme.md",
+    ] {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "# Notes\n\nVendored dependency notes.\n").unwrap();
+    }
+    let indexed = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+            .current_dir(&root)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let index = fs::read_to_string(root.join(".yore/forward_index.json")).unwrap();
+        let v: Value = serde_json::from_str(&index).unwrap();
+        let mut paths: Vec<String> = v["files"].as_object().unwrap().keys().cloned().collect();
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(
+        indexed(&["build", "."]),
+        ["docs/vendor-integration/setup.md", "docs/vendoring.md"]
+    );
+
+    // A profile's skip_dirs replaces the built-in list
+    fs::write(
+        root.join(".yore.toml"),
+        "[index.all]\nroots = [\".\"]\nskip_dirs = [\"vendor-integration\"]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        indexed(&["--profile", "all", "build"]),
+        [
+            "docs/node_modules/pkg/readme.md",
+            "docs/vendoring.md",
+            "vendor/lib.md"
+        ]
+    );
+}
+
+// ── build --watch ───────────────────────────────────────────────────
+
+#[cfg(not(feature = "watch"))]
+#[test]
+fn test_build_watch_requires_feature() {
+    let root = temp_dir("build-watch-off");
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("docs/a.md"), "# A\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["build", "docs", "--output", ".yore", "--watch"])
+        .output()
+        .expect("yore build failed to start");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("requires yore built with the `watch` feature"),
+        "{stderr}"
+    );
+}
+
+#[cfg(all(unix, feature = "watch"))]
+#[test]
+fn test_build_watch_reindexes_changed_files() {
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let root = temp_dir("build-watch");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(docs.join("first.md"), "# First\n\nOriginal text.\n").unwrap();
+    fs::write(docs.join("gone.md"), "# Gone\n\nSoon deleted.\n").unwrap();
+    let index = root.join(".yore");
+    let forward = index.join("forward_index.json");
+
+    let child = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args([
+            "build",
+            "docs",
+            "--watch",
+            "--debounce-ms",
+            "100",
+            "--output",
+        ])
+        .arg(&index)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("yore build --watch failed to start");
+
+    let wait_for = |what: &str, ready: &dyn Fn(&str) -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(15);
+        loop {
+            let content = fs::read_to_string(&forward).unwrap_or_default();
+            if ready(&content) {
+                return;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for {what}");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    };
+    wait_for("initial build", &|c| c.contains("gone.md"));
+    // Give the watcher time to register before editing
+    std::thread::sleep(Duration::from_millis(500));
+
+    fs::write(docs.join("second.md"), "# Second\n\nAdded later.\n").unwrap();
+    fs::remove_file(docs.join("gone.md")).unwrap();
+    wait_for("rebuild", &|c| {
+        c.contains("second.md") && !c.contains("gone.md")
+    });
+
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "watch exited with error: {stdout}");
+    assert!(stdout.contains("Rebuilt 2 files"), "{stdout}");
+    assert!(stdout.contains("Stopped watching"), "{stdout}");
+}
+
 // ── policy ──────────────────────────────────────────────────────────
 
 #[test]
@@ -782,6 +3427,297 @@ rules:
     assert!(v["violations"].is_array());
 }
 
+#[test]
+fn test_policy_explain_lists_matched_files() {
+    let root = temp_dir("policy-explain");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let policy = root.join(".yore-policy.yaml");
+    fs::write(
+        &policy,
+        "\
+rules:
+  - pattern: \"**/adr/*.md\"
+    name: adr-must-have-status
+    must_contain:
+      - \"Status\"
+",
+    )
+    .unwrap();
+    let run_policy = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+            .current_dir(&root)
+            .args(["policy", "--config"])
+            .arg(&policy)
+            .args(extra)
+            .arg("--index")
+            .arg(&index)
+            .output()
+            .expect("policy failed");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Default output stays lean
+    let v: Value = serde_json::from_str(&run_policy(&["--json"])).unwrap();
+    assert!(v.get("rules").is_none());
+
+    // With no violations, --explain still shows what each rule matched
+    let v: Value = serde_json::from_str(&run_policy(&["--json", "--explain"])).unwrap();
+    assert_eq!(v["total_violations"], 0);
+    let rules = v["rules"].as_array().unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0]["rule"], "adr-must-have-status");
+    let files = rules[0]["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().all(|file| file["passed"] == true));
+
+    let text = run_policy(&["--explain"]);
+    assert!(
+        text.contains("adr-must-have-status (**/adr/*.md): 2 matched"),
+        "{text}"
+    );
+    assert!(text.contains("ADR-001.md"), "{text}");
+}
+
+#[test]
+fn test_policy_regex_rules() {
+    let root = temp_dir("policy-regex");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let run_policy = |yaml: &str| {
+        let policy = root.join(".yore-policy.yaml");
+        fs::write(&policy, yaml).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+            .current_dir(&root)
+            .args(["policy", "--json", "--config"])
+            .arg(&policy)
+            .arg("--index")
+            .arg(&index)
+            .output()
+            .expect("policy failed");
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let (ok, stdout, _) = run_policy(
+        "\
+rules:
+  - pattern: \"**/adr/*.md\"
+    name: adr-status-line
+    must_match:
+      - \"^## Status\"
+    must_not_match:
+      - \"^## Status\"
+",
+    );
+    assert!(!ok, "error-severity violations should fail the run");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let violations = v["violations"].as_array().unwrap();
+    assert!(!violations.is_empty());
+    assert!(violations.iter().all(|violation| violation["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Forbidden pattern matched at line")
+        && violation["message"]
+            .as_str()
+            .unwrap()
+            .ends_with(": ^## Status")));
+
+    let (ok, _, stderr) = run_policy(
+        "\
+rules:
+  - pattern: \"**/*.md\"
+    name: broken
+    must_not_match:
+      - \"(unclosed\"
+",
+    );
+    assert!(!ok, "invalid regex should fail the run");
+    assert_eq!(
+        stderr.matches("Invalid must_not_match regex").count(),
+        1,
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_policy_file_count_bounds_report_the_pattern() {
+    let root = temp_dir("policy-file-count");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let policy = root.join(".yore-policy.yaml");
+    fs::write(
+        &policy,
+        "\
+rules:
+  - pattern: \"**/adr/*.md\"
+    name: few-adrs
+    max_files: 1
+  - pattern: \"**/adr/*.md\"
+    name: many-adrs
+    min_files: 5
+  - pattern: \"**/adr/*.md\"
+    name: adr-range
+    min_files: 1
+    max_files: 5
+",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["policy", "--json", "--config"])
+        .arg(&policy)
+        .arg("--index")
+        .arg(&index)
+        .output()
+        .expect("policy failed");
+    assert!(!output.status.success());
+
+    let v: Value = serde_json::from_str(&String::from_utf8_lossy(&output.stdout)).unwrap();
+    let violations = v["violations"].as_array().unwrap();
+    let rules: Vec<&str> = violations
+        .iter()
+        .map(|violation| violation["rule"].as_str().unwrap())
+        .collect();
+    assert_eq!(rules, ["few-adrs", "many-adrs"]);
+    assert!(violations
+        .iter()
+        .all(|violation| violation["file"] == "**/adr/*.md"));
+    assert!(violations[0]["message"]
+        .as_str()
+        .unwrap()
+        .ends_with("expected at most 1"));
+    assert!(violations[1]["message"]
+        .as_str()
+        .unwrap()
+        .ends_with("expected at least 5"));
+}
+
+#[test]
+fn test_policy_must_link_to_uses_indexed_links() {
+    let root = temp_dir("policy-must-link");
+    let adr = root.join("docs/adr");
+    fs::create_dir_all(&adr).unwrap();
+    fs::write(root.join("docs/architecture.md"), "# Architecture\n").unwrap();
+    // Reference-style and wiki links only show up in the parsed index.
+    fs::write(
+        adr.join("ADR-001.md"),
+        "# ADR-001\n\nSee the [architecture index][arch].\n\n[arch]: ../architecture.md\n",
+    )
+    .unwrap();
+    fs::write(
+        adr.join("ADR-002.md"),
+        "# ADR-002\n\nBack to [[architecture]].\n",
+    )
+    .unwrap();
+    fs::write(adr.join("ADR-003.md"), "# ADR-003\n\nNo links here.\n").unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let policy = root.join(".yore-policy.yaml");
+    fs::write(
+        &policy,
+        "\
+rules:
+  - pattern: \"docs/adr/*.md\"
+    name: adr-links-architecture
+    must_link_to:
+      - \"docs/architecture.md\"
+",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["policy", "--json", "--config"])
+        .arg(&policy)
+        .arg("--index")
+        .arg(&index)
+        .output()
+        .expect("policy failed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "missing link should fail the run");
+
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let violations = v["violations"].as_array().unwrap();
+    assert_eq!(violations.len(), 1, "{stdout}");
+    assert_eq!(violations[0]["file"], "docs/adr/ADR-003.md");
+    assert_eq!(
+        violations[0]["message"],
+        "Missing required link: \"docs/architecture.md\""
+    );
+}
+
+#[test]
+fn test_policy_exit_status_follows_severity() {
+    let root = temp_dir("policy-exit");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let policy = root.join(".yore-policy.yaml");
+    let run_policy = |severity: &str, extra: &[&str]| {
+        fs::write(
+            &policy,
+            format!(
+                "\
+rules:
+  - pattern: \"**/adr/*.md\"
+    name: adr-needs-owner
+    severity: {severity}
+    must_contain:
+      - \"Owner:\"
+"
+            ),
+        )
+        .unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_yore"))
+            .current_dir(&root)
+            .args(["policy", "--json", "--config"])
+            .arg(&policy)
+            .arg("--index")
+            .arg(&index)
+            .args(extra)
+            .output()
+            .expect("policy failed");
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    for (severity, extra, expected) in [
+        ("error", &[][..], 1),
+        ("error", &["--no-fail"][..], 0),
+        ("warn", &[][..], 0),
+        ("warn", &["--fail-on", "warn"][..], 1),
+    ] {
+        let (code, stdout, _) = run_policy(severity, extra);
+        assert_eq!(code, Some(expected), "{severity} {extra:?}");
+        // Output is identical whatever the exit status.
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        assert!(v["total_violations"].as_u64().unwrap() > 0);
+    }
+
+    let (code, _, stderr) = run_policy("warn", &["--fail-on", "bogus"]);
+    assert_eq!(code, Some(1));
+    assert!(
+        stderr.contains("Unsupported --fail-on value: bogus"),
+        "{stderr}"
+    );
+}
+
 // ── stale ───────────────────────────────────────────────────────────
 
 #[test]
@@ -797,6 +3733,159 @@ fn test_stale_runs_without_error() {
     assert!(v["files"].is_array());
 }
 
+#[test]
+fn test_stale_require_combines_age_and_orphaned() {
+    let root = temp_dir("stale-require");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let stale = |args: &[&str]| -> Vec<(String, Vec<String>)> {
+        let mut all = vec!["stale", "--json"];
+        all.extend_from_slice(args);
+        let (ok, stdout, stderr) = yore_at(&root, &all, &index);
+        assert!(ok, "stale failed: {stdout}{stderr}");
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        v["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| {
+                let reasons = f["reasons"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|r| r.as_str().unwrap().to_string())
+                    .collect();
+                (f["file"].as_str().unwrap().to_string(), reasons)
+            })
+            .collect()
+    };
+
+    // Fresh files are never stale by age, but orphans still qualify under OR
+    let orphaned = stale(&["--days", "365", "--require", "age OR orphaned"]);
+    assert!(!orphaned.is_empty());
+    assert!(orphaned
+        .iter()
+        .all(|(_, reasons)| reasons == &["orphaned".to_string()]));
+    assert!(stale(&["--days", "365", "--require", "age AND orphaned"]).is_empty());
+
+    // --max-inlinks 0 selects the same low-connectivity docs
+    let mut unlinked: Vec<String> = stale(&["--days", "0", "--max-inlinks", "0"])
+        .into_iter()
+        .map(|(file, _)| file)
+        .collect();
+    let mut orphan_files: Vec<String> = orphaned.into_iter().map(|(file, _)| file).collect();
+    unlinked.sort();
+    orphan_files.sort();
+    assert_eq!(unlinked, orphan_files);
+
+    let (ok, _, stderr) = yore(
+        &["stale", "--require", "age AND inlinks OR orphaned"],
+        &index,
+    );
+    assert!(!ok);
+    assert!(stderr.contains("cannot mix AND and OR"), "{stderr}");
+}
+
+#[test]
+fn test_stale_use_git_ages_by_commit_date() {
+    let root = temp_dir("stale-git");
+    write_fixture(&root);
+
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .current_dir(&root)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "Yore Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Yore Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .env("GIT_AUTHOR_DATE", "2020-01-01T00:00:00Z")
+            .env("GIT_COMMITTER_DATE", "2020-01-01T00:00:00Z")
+            .status()
+            .expect("git failed to start");
+        assert!(status.success(), "git {args:?} failed");
+    };
+    git(&["init", "-q"]);
+    git(&["add", "docs/README.md"]);
+    git(&["commit", "-q", "-m", "Add README"]);
+
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let stale_files = |extra: &[&str]| -> Vec<(String, u64)> {
+        let mut args = vec!["stale", "--days", "365", "--json"];
+        args.extend_from_slice(extra);
+        let (ok, stdout, _) = yore_at(&root, &args, &index);
+        assert!(ok, "stale failed: {stdout}");
+        let v: Value = serde_json::from_str(&stdout).unwrap();
+        v["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| {
+                (
+                    f["file"].as_str().unwrap().to_string(),
+                    f["days_since_modified"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    };
+
+    // Freshly written files are not stale by mtime.
+    assert!(stale_files(&[]).is_empty());
+
+    // The committed README is years old by commit date; untracked files
+    // fall back to mtime and stay fresh.
+    let stale = stale_files(&["--use-git"]);
+    assert_eq!(stale.len(), 1, "{stale:?}");
+    assert_eq!(stale[0].0, "docs/README.md");
+    assert!(stale[0].1 > 365 * 5);
+}
+
+// ── repl ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_repl_session_limit_and_new_verbs() {
+    use std::io::Write as _;
+    use std::process::Stdio;
+
+    let root = temp_dir("repl");
+    write_fixture(&root);
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yore"))
+        .current_dir(&root)
+        .args(["repl", "--index"])
+        .arg(&index)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("yore repl failed to start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            b"limit 1\nquery-json architecture\nbacklinks docs/architecture.md\norphans\nquit\n",
+        )
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("limit = 1"), "{stdout}");
+    let json_start = stdout.find("[\n").expect("query-json output");
+    let json_end = json_start + stdout[json_start..].find("\n]").unwrap() + 2;
+    let results: Value = serde_json::from_str(&stdout[json_start..json_end]).unwrap();
+    let results = results.as_array().unwrap();
+    assert_eq!(results.len(), 1, "limit should persist into queries");
+    assert!(results[0]["score"].as_f64().unwrap() > 0.0);
+    assert!(stdout.contains("Finding orphaned files"), "{stdout}");
+}
+
 // ── dupes-sections ──────────────────────────────────────────────────
 
 #[test]
@@ -816,3 +3905,155 @@ fn test_dupes_sections_json() {
         "expected at least one duplicate section group"
     );
 }
+
+#[test]
+fn test_dupes_sections_show_diff() {
+    let root = temp_dir("dupes-sections-diff");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    let steps = "Install the toolchain with the package manager.\n\
+                 Clone the repository and run the bootstrap script.\n\
+                 Configure credentials in the local settings file.\n\
+                 Start the development server and open the dashboard.\n";
+    fs::write(
+        docs.join("one.md"),
+        format!("# One\n\n## Setup\n\n{steps}Verify the health endpoint responds.\n"),
+    )
+    .unwrap();
+    fs::write(
+        docs.join("two.md"),
+        format!("# Two\n\n## Setup\n\n{steps}Verify the status endpoint responds.\n"),
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, plain, _) = yore(&["dupes-sections", "--json", "--threshold", "0.5"], &index);
+    assert!(ok);
+    let v: Value = serde_json::from_str(&plain).unwrap();
+    assert!(v
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|c| c.get("diff").is_none()));
+
+    let (ok, stdout, stderr) = yore_at(
+        &root,
+        &[
+            "dupes-sections",
+            "--json",
+            "--threshold",
+            "0.5",
+            "--show-diff",
+        ],
+        &index,
+    );
+    assert!(ok, "dupes-sections --show-diff failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let setup = v
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["heading"] == "Setup")
+        .expect("Setup cluster");
+    let diff = &setup["diff"];
+    assert_eq!(diff["identical"], false);
+    let lines: Vec<&str> = diff["lines"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| l.as_str().unwrap())
+        .collect();
+    assert!(
+        lines.contains(&"-Verify the health endpoint responds."),
+        "{stdout}"
+    );
+    assert!(
+        lines.contains(&"+Verify the status endpoint responds."),
+        "{stdout}"
+    );
+}
+
+// ── deterministic ordering ──────────────────────────────────────────
+
+#[test]
+fn test_equal_scores_order_by_path_across_runs() {
+    let root = temp_dir("tie-break");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    let body = "# Rotation\n\nRotate the signing keys quarterly and record the rotation.\n";
+    for name in ["zeta.md", "alpha.md", "mid.md", "beta.md"] {
+        fs::write(docs.join(name), body).unwrap();
+    }
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let runs: [&[&str]; 4] = [
+        &["query", "rotation", "--json"],
+        &["similar", "docs/mid.md", "--json"],
+        &["dupes", "--json"],
+        &["assemble", "rotation", "--format", "json"],
+    ];
+    for args in runs {
+        let (ok, first, stderr) = yore_at(&root, args, &index);
+        assert!(ok, "{args:?} failed: {stderr}");
+        for _ in 0..4 {
+            let (_, again, _) = yore_at(&root, args, &index);
+            assert_eq!(first, again, "{args:?} order changed between runs");
+        }
+    }
+
+    let (_, stdout, _) = yore_at(&root, &["query", "rotation", "--json"], &index);
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let paths: Vec<&str> = v
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "docs/alpha.md",
+            "docs/beta.md",
+            "docs/mid.md",
+            "docs/zeta.md"
+        ]
+    );
+}
+
+// ── dupes-headings ──────────────────────────────────────────────────
+
+#[test]
+fn test_dupes_headings_json_reports_locations() {
+    let root = temp_dir("dupes-headings");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("intro.md"),
+        "# Intro\n\n## Getting Started\n\nInstall the tool.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("guide.md"),
+        "# Guide\n\n## Getting started guide\n\nRun the setup script.\n",
+    )
+    .unwrap();
+    let index = root.join(".yore");
+    build_index(&root, "docs", &index);
+
+    let (ok, stdout, stderr) = yore(&["dupes-headings", "--json"], &index);
+    assert!(ok, "dupes-headings failed: {stderr}");
+    let v: Value = serde_json::from_str(&stdout).unwrap();
+    let clusters = v.as_array().unwrap();
+    assert_eq!(clusters.len(), 1, "{stdout}");
+    let locations = clusters[0]["locations"].as_array().unwrap();
+    assert_eq!(locations.len(), 2);
+    assert!(locations.iter().all(|l| l["line"].as_u64() == Some(3)));
+    let headings: Vec<&str> = locations
+        .iter()
+        .map(|l| l["heading"].as_str().unwrap())
+        .collect();
+    assert!(headings.contains(&"Getting Started"), "{stdout}");
+    assert!(headings.contains(&"Getting started guide"), "{stdout}");
+}
@@ -118,3 +118,585 @@ fn test_query_empty_results_json_explain() {
     assert!(diagnostics.contains_key("tokens"));
     assert!(diagnostics.contains_key("notice"));
 }
+
+#[test]
+fn test_query_tag_filter_uses_frontmatter() {
+    let root = temp_dir("query-tag");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("runbook.md"),
+        "---\ntags: [ops, runbook]\n---\n# Deployment Runbook\n\nDeployment steps.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("design.md"),
+        "---\ntags: [design]\n---\n# Deployment Design\n\nDeployment rationale.\n",
+    )
+    .unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args([
+            "query",
+            "deployment",
+            "--tag",
+            "runbook",
+            "--json",
+            "--index",
+        ])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "tagged query failed");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    let results = json.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0]["path"].as_str().unwrap().ends_with("runbook.md"));
+}
+
+#[test]
+fn test_query_boolean_operators_filter_results() {
+    let root = temp_dir("query-boolean");
+    write_docs(&root);
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let run_query = |query: &str| -> Vec<String> {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.current_dir(&root)
+            .args(["query", "--query", query, "--json", "--index"])
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "query {query:?} failed");
+        let json: Value = serde_json::from_str(&stdout).unwrap();
+        json.as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["path"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let plain = run_query("deployment kubernetes");
+    assert_eq!(plain.len(), 2);
+
+    let and = run_query("deployment AND kubernetes");
+    assert_eq!(and.len(), 1);
+    assert!(and[0].ends_with("a.md"));
+
+    let not = run_query("deployment NOT kubernetes");
+    assert_eq!(not.len(), 1);
+    assert!(not[0].ends_with("b.md"));
+}
+
+#[test]
+fn test_query_phrase_uses_positions_index() {
+    let root = temp_dir("query-positions");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("adjacent.md"),
+        "# Limits\n\nEach client has a retry budget per minute.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("scattered.md"),
+        "# Budget\n\nThe budget is fixed. Clients retry on failure.\n",
+    )
+    .unwrap();
+    let index_dir = root.join(".yore-test");
+    let mut build = Command::new(env!("CARGO_BIN_EXE_yore"));
+    build
+        .current_dir(&root)
+        .args(["build", "docs", "--positions", "--output"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(build);
+    assert!(ok, "build failed: {stdout}");
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "--query", "\"retry budget\"", "--json", "--index"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "phrase query failed");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    let results = json.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0]["path"]
+        .as_str()
+        .unwrap()
+        .ends_with("adjacent.md"));
+}
+
+#[test]
+fn test_query_heading_field_prefix() {
+    let root = temp_dir("query-heading-field");
+    write_docs(&root);
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args([
+            "query",
+            "heading:migration",
+            "deployment",
+            "--json",
+            "--index",
+        ])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "field query failed");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    let results = json.as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert!(results[0]["path"].as_str().unwrap().ends_with("a.md"));
+}
+
+#[test]
+fn test_query_reports_heading_hits_from_reverse_index() {
+    let root = temp_dir("query-heading-hits");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("guide.md"),
+        "# Guide\n\nIntro.\n\n## Setup\n\nText.\n\n## Overview\n\nText.\n\n\
+         ## Migration Steps\n\nMigrate here.\n\n## Rollback Migration\n\nUndo.\n",
+    )
+    .unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "migration", "--json", "--index"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "query failed");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    // Both matching headings come after the first three
+    assert_eq!(
+        json[0]["heading_hits"],
+        serde_json::json!([
+            { "line": 13, "heading": "Migration Steps" },
+            { "line": 17, "heading": "Rollback Migration" }
+        ])
+    );
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "migration", "--index"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok);
+    assert!(stdout.contains("L13: Migration Steps"), "{stdout}");
+    assert!(stdout.contains("L17: Rollback Migration"), "{stdout}");
+    assert!(!stdout.contains("Setup"), "{stdout}");
+}
+
+#[test]
+fn test_query_snippets_show_matching_lines() {
+    let root = temp_dir("query-snippets");
+    write_docs(&root);
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "kubernetes", "--snippets", "--json", "--index"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "snippets query failed");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    let snippets = json[0]["snippets"].as_array().unwrap();
+    assert_eq!(snippets.len(), 1);
+    assert_eq!(snippets[0]["line"], 3);
+    assert_eq!(snippets[0]["text"], "Kubernetes deployment steps.");
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "kubernetes", "--snippets", "--index"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok);
+    assert!(stdout.contains("L3:"), "{stdout}");
+    assert!(stdout.contains("deployment steps."), "{stdout}");
+
+    // --files-only prints bare paths, without snippets
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args([
+            "query",
+            "kubernetes",
+            "--snippets",
+            "--files-only",
+            "--index",
+        ])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok);
+    assert!(!stdout.contains("L3:"), "{stdout}");
+}
+
+#[test]
+fn test_query_offset_pages_do_not_overlap() {
+    let root = temp_dir("query-offset");
+    write_docs(&root);
+    let docs = root.join("docs");
+    // Identical bodies score the same, so page order rests on the path tie-break
+    fs::write(docs.join("c.md"), "# Notes\n\nDeployment guide.\n").unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let page = |offset: &str| -> Value {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.current_dir(&root)
+            .args([
+                "query",
+                "deployment",
+                "--limit",
+                "1",
+                "--offset",
+                offset,
+                "--json",
+                "--index",
+            ])
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "paged query failed");
+        serde_json::from_str(&stdout).unwrap()
+    };
+
+    let mut seen = Vec::new();
+    for offset in ["0", "1", "2"] {
+        let json = page(offset);
+        assert_eq!(json["offset"], offset.parse::<u64>().unwrap());
+        assert_eq!(json["limit"], 1);
+        assert_eq!(json["total_matches"], 3);
+        let results = json["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        seen.push(results[0]["path"].as_str().unwrap().to_string());
+    }
+    let mut unique = seen.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 3, "pages overlapped: {seen:?}");
+    let b = seen.iter().position(|p| p.ends_with("b.md")).unwrap();
+    let c = seen.iter().position(|p| p.ends_with("c.md")).unwrap();
+    assert_eq!(c, b + 1, "equal scores should be ordered by path: {seen:?}");
+
+    let past_end = page("5");
+    assert_eq!(past_end["total_matches"], 3);
+    assert!(past_end["results"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_query_synonyms_match_aliases() {
+    let root = temp_dir("query-synonyms");
+    write_docs(&root);
+    fs::write(root.join("docs/c.md"), "# Cluster\n\nThe k8s cluster.\n").unwrap();
+    fs::write(root.join("synonyms.yaml"), "kubernetes:\n  - k8s\n").unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let paths = |extra: &[&str]| -> Vec<String> {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.current_dir(&root)
+            .args(["query", "kubernetes", "--json"])
+            .args(extra)
+            .arg("--index")
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "query failed: {stdout}");
+        let json: Value = serde_json::from_str(&stdout).unwrap();
+        let mut paths: Vec<String> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                r["path"]
+                    .as_str()
+                    .unwrap()
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(paths(&[]), ["a.md"]);
+    assert_eq!(paths(&["--synonyms", "synonyms.yaml"]), ["a.md", "c.md"]);
+}
+
+#[test]
+fn test_query_heading_boost_ranks_heading_matches_first() {
+    let root = temp_dir("query-heading-boost");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("body.md"),
+        "# Release\n\nSteps for the rollback plan.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("heading.md"),
+        "# Rollback\n\nSteps for the release plan.\n",
+    )
+    .unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let paths = |extra: &[&str]| -> Vec<String> {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.args(["query", "rollback", "--json"])
+            .args(extra)
+            .arg("--index")
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "query failed: {stdout}");
+        let json: Value = serde_json::from_str(&stdout).unwrap();
+        json.as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                r["path"]
+                    .as_str()
+                    .unwrap()
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    };
+
+    assert_eq!(paths(&[]), ["heading.md", "body.md"]);
+    // With no heading weight only the body mention counts.
+    assert_eq!(paths(&["--heading-boost", "0"]), ["body.md"]);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.args(["query", "rollback", "--heading-boost=-1", "--index"])
+        .arg(&index_dir);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--heading-boost"));
+}
+
+#[test]
+fn test_query_code_boost_prefers_inline_code_matches() {
+    let root = temp_dir("query-code-boost");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("code.md"),
+        "# Services\n\nThe `scheduler` restarts failed jobs.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("prose.md"),
+        "# Services\n\nThe scheduler restarts failed jobs.\n\nIt runs the scheduler twice.\n",
+    )
+    .unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let top = |extra: &[&str]| -> String {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.args(["query", "scheduler", "--json"])
+            .args(extra)
+            .arg("--index")
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "query failed: {stdout}");
+        let json: Value = serde_json::from_str(&stdout).unwrap();
+        json[0]["path"].as_str().unwrap().to_string()
+    };
+
+    assert!(top(&[]).ends_with("prose.md"));
+    assert!(top(&["--code-boost", "1.0"]).ends_with("code.md"));
+}
+
+#[test]
+fn test_query_uses_stop_words_recorded_at_build() {
+    let root = temp_dir("query-stop-words");
+    write_docs(&root);
+    fs::write(root.join("stopwords.txt"), "kubernetes\n").unwrap();
+    let index_dir = root.join(".yore-test");
+    let query = |terms: &str| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.current_dir(&root)
+            .args(["query", terms, "--json", "--index"])
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "query failed: {stdout}");
+        serde_json::from_str::<Value>(&stdout).unwrap()
+    };
+
+    let mut build = Command::new(env!("CARGO_BIN_EXE_yore"));
+    build
+        .current_dir(&root)
+        .args(["build", "docs", "--stop-words", "stopwords.txt", "--output"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(build);
+    assert!(ok, "build failed: {stdout}");
+    assert_eq!(query("kubernetes")["error"], "no_query_terms");
+    assert_eq!(query("the")["error"], "no_query_terms");
+
+    // Replacing the built-in list makes its words searchable
+    fs::write(
+        root.join("docs/c.md"),
+        "# Plans\n\nWhat happens when it fails.\n",
+    )
+    .unwrap();
+    let mut build = Command::new(env!("CARGO_BIN_EXE_yore"));
+    build
+        .current_dir(&root)
+        .args([
+            "build",
+            "docs",
+            "--stop-words",
+            "stopwords.txt",
+            "--stop-words-mode",
+            "replace",
+            "--output",
+        ])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(build);
+    assert!(ok, "build failed: {stdout}");
+    let results = query("happens when");
+    let paths: Vec<&str> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["path"].as_str().unwrap())
+        .collect();
+    assert!(paths.iter().any(|p| p.ends_with("c.md")), "{results}");
+    assert_eq!(query("kubernetes")["error"], "no_query_terms");
+}
+
+#[test]
+fn test_query_group_by_dir_nests_results() {
+    let root = temp_dir("query-group-by-dir");
+    for (path, body) in [
+        (
+            "docs/payments/api/refunds.md",
+            "# Refunds\n\nRefund escalation path.\n",
+        ),
+        (
+            "docs/payments/oncall.md",
+            "# Oncall\n\nEscalation for refund failures.\n",
+        ),
+        (
+            "docs/search/oncall.md",
+            "# Oncall\n\nEscalation for search outages.\n",
+        ),
+        ("docs/intro.md", "# Intro\n\nNothing relevant.\n"),
+    ] {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, body).unwrap();
+    }
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args([
+            "query",
+            "escalation",
+            "--group-by-dir",
+            "--group-depth",
+            "2",
+            "--json",
+            "--index",
+        ])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "grouped query failed: {stdout}");
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["group_depth"], 2);
+    assert_eq!(json["total_matches"], 3);
+    let groups = json["groups"].as_array().unwrap();
+    let dirs: Vec<&str> = groups.iter().map(|g| g["dir"].as_str().unwrap()).collect();
+    assert_eq!(dirs, ["docs/payments", "docs/search"]);
+    let payments = groups[0]["results"].as_array().unwrap();
+    assert_eq!(payments.len(), 2);
+    let member_total: f64 = payments.iter().map(|r| r["score"].as_f64().unwrap()).sum();
+    assert!((groups[0]["score"].as_f64().unwrap() - member_total).abs() < 1e-9);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "escalation", "--group-by-dir", "--index"])
+        .arg(&index_dir);
+    let (ok, stdout) = run_cmd(cmd);
+    assert!(ok, "grouped query failed: {stdout}");
+    assert!(stdout.contains("(score: "), "{stdout}");
+    assert!(stdout.contains("3 files"), "{stdout}");
+}
+
+#[test]
+fn test_query_min_score_drops_weak_matches_before_paging() {
+    let root = temp_dir("query-min-score");
+    let docs = root.join("docs");
+    fs::create_dir_all(&docs).unwrap();
+    fs::write(
+        docs.join("strong.md"),
+        "# Rollback\n\nRollback the release. Rollback verification. Rollback drills.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("medium.md"),
+        "# Release\n\nRollback if the release fails and rollback again.\n",
+    )
+    .unwrap();
+    fs::write(
+        docs.join("weak.md"),
+        "# Changelog\n\nMany unrelated words about releases, builds, tags, artifacts, \
+         signing, publishing, and a single rollback mention.\n",
+    )
+    .unwrap();
+    let index_dir = root.join(".yore-test");
+    build_index(&root, &index_dir);
+
+    let query = |extra: &[&str]| {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+        cmd.current_dir(&root)
+            .args(["query", "rollback", "--json"])
+            .args(extra)
+            .arg("--index")
+            .arg(&index_dir);
+        let (ok, stdout) = run_cmd(cmd);
+        assert!(ok, "query failed: {stdout}");
+        serde_json::from_str::<Value>(&stdout).unwrap()
+    };
+
+    let all = query(&[]);
+    let scores: Vec<f64> = all
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["score"].as_f64().unwrap())
+        .collect();
+    assert_eq!(scores.len(), 3);
+    assert!(scores[1] > scores[2]);
+
+    let floor = scores[1].to_string();
+    let json = query(&["--min-score", &floor, "--limit", "1", "--offset", "1"]);
+    assert_eq!(json["below_min_score"], 1);
+    assert_eq!(json["total_matches"], 2);
+    assert_eq!(json["min_score"].as_f64().unwrap(), scores[1]);
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["score"].as_f64().unwrap(), scores[1]);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yore"));
+    cmd.current_dir(&root)
+        .args(["query", "rollback", "--min-score", "-1", "--index"])
+        .arg(&index_dir);
+    let (ok, _) = run_cmd(cmd);
+    assert!(!ok, "negative --min-score should be rejected");
+}